@@ -3,19 +3,32 @@
 fn main() {
     println!("cargo:rerun-if-changed=src/proto/varfish/v1/clinvar.proto");
     println!("cargo:rerun-if-changed=src/proto/varfish/v1/sv.proto");
+    println!("cargo:rerun-if-changed=src/proto/varfish/v1/query.proto");
     prost_build::Config::new()
         .protoc_arg("-Isrc/proto")
         // Add serde serialization and deserialization to the generated code.
         .type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]")
         // Skip serializing `None` values.
         .type_attribute(".", "#[serde_with::skip_serializing_none]")
+        // Population counts are only present for some background databases.
+        .field_attribute(
+            ".varfish.v1.svs.BgDbRecord.population_counts",
+            "#[serde(default, skip_serializing_if = \"Vec::is_empty\")]",
+        )
         // Define the protobuf files to compile.
         .compile_protos(
             &[
                 "src/proto/varfish/v1/clinvar.proto",
                 "src/proto/varfish/v1/sv.proto",
+                "src/proto/varfish/v1/query.proto",
             ],
             &["src/"],
         )
         .unwrap();
+    // The gRPC service of `server run`.
+    println!("cargo:rerun-if-changed=src/proto/varfish/v1/server.proto");
+    tonic_build::configure()
+        .protoc_arg("-Isrc/proto")
+        .compile(&["src/proto/varfish/v1/server.proto"], &["src/"])
+        .unwrap();
 }
@@ -0,0 +1,268 @@
+//! Catalogue of the stable warning and error codes and the `codes` subcommand.
+//!
+//! Warnings and errors that VarFish Server shows to its users are prefixed with a stable
+//! code, e.g., `W-INGEST-0003: dropping input samples missing from pedigree: [...]`.  The
+//! server maps the codes to localized messages using the catalogue written by `codes`.
+//!
+//! Codes are never reused or renumbered; obsolete codes stay in the catalogue.
+
+use std::io::Write as _;
+
+/// Severity of a code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The subcommand continues, e.g., after skipping a record.
+    Warning,
+    /// The subcommand fails.
+    Error,
+}
+
+/// A stable warning or error code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct Code {
+    /// The code, e.g., `W-INGEST-0003`.
+    pub id: &'static str,
+    /// The severity.
+    pub severity: Severity,
+    /// Short English description of the condition.
+    pub summary: &'static str,
+}
+
+impl std::fmt::Display for Code {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+/// Define the code constants and the `CATALOGUE` with all of them.
+macro_rules! codes {
+    ($($name:ident = ($id:literal, $severity:ident, $summary:literal);)*) => {
+        $(
+            #[doc = $summary]
+            pub const $name: Code = Code {
+                id: $id,
+                severity: Severity::$severity,
+                summary: $summary,
+            };
+        )*
+
+        /// All codes.
+        pub const CATALOGUE: &[Code] = &[$($name),*];
+    };
+}
+
+codes! {
+    ADD_SAMPLE_CASE_UUID_MISMATCH = (
+        "W-ADD-SAMPLE-0001", Warning,
+        "case UUID of sample file differs from case UUID of case file"
+    );
+    COMMON_PARTIAL_OUTPUT_KEPT = (
+        "W-COMMON-0001", Warning,
+        "partially written output removed or renamed after failure"
+    );
+    COMMON_TX_TREE_CACHE_IGNORED = (
+        "W-COMMON-0002", Warning,
+        "transcript tree cache could not be read or written"
+    );
+    COMMON_INTERRUPTED = (
+        "W-COMMON-0003", Warning,
+        "interrupted by termination signal, partial output kept"
+    );
+    COMMON_STALE_LOCK_REMOVED = (
+        "W-COMMON-0004", Warning,
+        "stale database lock of a dead job removed"
+    );
+    COMMON_GENE_SYMBOL_REMAPPED = (
+        "W-COMMON-0005", Warning,
+        "outdated gene symbol remapped to the approved symbol"
+    );
+    COMMON_GENE_SYMBOL_AMBIGUOUS = (
+        "W-COMMON-0006", Warning,
+        "outdated gene symbol refers to several genes and was ignored"
+    );
+    INGEST_PEDIGREE_INDIVIDUAL_DROPPED = (
+        "W-INGEST-0001", Warning,
+        "pedigree individual missing from input dropped"
+    );
+    INGEST_PEDIGREE_INDIVIDUAL_NOCALL = (
+        "W-INGEST-0002", Warning,
+        "pedigree individual missing from input written with no-call genotypes"
+    );
+    INGEST_SAMPLE_MISSING_FROM_PEDIGREE = (
+        "W-INGEST-0003", Warning,
+        "sample missing from pedigree"
+    );
+    INGEST_UNEXPECTED_ALLELE_SKIPPED = (
+        "W-INGEST-0004", Warning,
+        "unexpected allele skipped"
+    );
+    INGEST_EXTRA_FORMAT_KEY_IGNORED = (
+        "W-INGEST-0005", Warning,
+        "extra FORMAT key ignored as it is always copied"
+    );
+    INGEST_MAX_VAR_COUNT_REACHED = (
+        "W-INGEST-0006", Warning,
+        "stopped after maximal number of variants"
+    );
+    INGEST_NO_PARTIAL_OUTPUT = (
+        "W-INGEST-0007", Warning,
+        "no partial output to resume from"
+    );
+    INGEST_PARTIAL_OUTPUT_TRUNCATED = (
+        "W-INGEST-0008", Warning,
+        "partial output truncated, resuming after last complete record"
+    );
+    INGEST_QUICK_QC_TIMED_OUT = (
+        "W-INGEST-0009", Warning,
+        "quick QC stopped at time limit"
+    );
+    INGEST_PARTIAL_OUTPUT_HEADER_MISMATCH = (
+        "W-INGEST-0010", Warning,
+        "partial output written with different header, starting from scratch"
+    );
+    QC_RELATEDNESS_PEDIGREE_MISMATCH = (
+        "W-QC-0001", Warning,
+        "estimated relatedness does not match pedigree"
+    );
+    QC_SEX_PEDIGREE_MISMATCH = (
+        "W-QC-0002", Warning,
+        "inferred sex does not match pedigree"
+    );
+    SEQVARS_AGGREGATE_CASE_SKIPPED = (
+        "W-SEQVARS-AGGREGATE-0001", Warning,
+        "case already in aggregate skipped"
+    );
+    SEQVARS_AGGREGATE_CASE_INCOMPLETE = (
+        "W-SEQVARS-AGGREGATE-0002", Warning,
+        "incompletely imported case removed and imported again"
+    );
+    SEQVARS_QUERY_RESULT_TRUNCATED = (
+        "W-SEQVARS-QUERY-0001", Warning,
+        "result set truncated"
+    );
+    SEQVARS_QUERY_EXPLAIN_NOT_FOUND = (
+        "W-SEQVARS-QUERY-0002", Warning,
+        "variant to explain not found in input"
+    );
+    SEQVARS_QUERY_INVALID_HGVS = (
+        "W-SEQVARS-QUERY-0003", Warning,
+        "HGVS description could not be parsed"
+    );
+    SPECIAL_LOCI_NO_CALLER_OUTPUTS = (
+        "W-SPECIAL-LOCI-0001", Warning,
+        "no caller outputs given"
+    );
+    STRUCVARS_AGGREGATE_CASE_SKIPPED = (
+        "W-STRUCVARS-AGGREGATE-0001", Warning,
+        "case already in aggregate skipped"
+    );
+    STRUCVARS_QUERY_UNKNOWN_GENE = (
+        "W-STRUCVARS-QUERY-0001", Warning,
+        "HGNC gene ID could not be resolved"
+    );
+    COMMON_DATABASE_LOCKED = (
+        "E-COMMON-0001", Error,
+        "database is locked by another job"
+    );
+    INGEST_SAMPLE_MISMATCH = (
+        "E-INGEST-0001", Error,
+        "samples of pedigree and input do not match"
+    );
+    INGEST_UNEXPECTED_ALLELE = (
+        "E-INGEST-0002", Error,
+        "unexpected allele in strict mode"
+    );
+    INGEST_UNKNOWN_VARIANT_CALLER = (
+        "E-INGEST-0003", Error,
+        "original variant caller could not be determined from header"
+    );
+    INGEST_UNDEFINED_FORMAT_KEY = (
+        "E-INGEST-0004", Error,
+        "extra FORMAT key not defined in input header"
+    );
+    INGEST_UNDEFINED_INFO_KEY = (
+        "E-INGEST-0005", Error,
+        "kept INFO key not defined in input header"
+    );
+    INGEST_INVALID_HPO_TERM = (
+        "E-INGEST-0006", Error,
+        "invalid HPO term or unknown sample"
+    );
+    SEQVARS_QUERY_INVALID_HPO_TERM = (
+        "E-SEQVARS-QUERY-0001", Error,
+        "invalid HPO term"
+    );
+}
+
+/// Command line arguments for `codes` subcommand.
+#[derive(Debug, clap::Parser)]
+#[command(author, version, about = "write warning and error codes as JSON", long_about = None)]
+pub struct Args {
+    /// Path to the output JSON file, standard output if not given.
+    #[clap(long)]
+    pub path_out: Option<String>,
+}
+
+/// Main entry point for `codes` sub command.
+pub fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::debug!("args_common = {:#?}", &args_common);
+    tracing::debug!("args = {:#?}", &args);
+
+    let mut writer: Box<dyn std::io::Write> = match args.path_out.as_ref() {
+        Some(path_out) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path_out)
+                .map_err(|e| anyhow::anyhow!("could not create {}: {}", path_out, e))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    serde_json::to_writer_pretty(&mut writer, CATALOGUE)
+        .map_err(|e| anyhow::anyhow!("could not write codes: {}", e))?;
+    writeln!(writer)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Severity, CATALOGUE};
+
+    #[test]
+    fn catalogue_consistent() {
+        let mut ids = std::collections::HashSet::new();
+        for code in CATALOGUE {
+            assert!(ids.insert(code.id), "duplicate code {}", code.id);
+            let prefix = match code.severity {
+                Severity::Warning => "W-",
+                Severity::Error => "E-",
+            };
+            assert!(code.id.starts_with(prefix), "wrong severity of {}", code.id);
+            let (_, number) = code.id.rsplit_once('-').unwrap();
+            assert_eq!(number.len(), 4, "malformed code {}", code.id);
+            assert!(number.chars().all(|c| c.is_ascii_digit()));
+        }
+    }
+
+    #[test]
+    fn smoke_test() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_out = tmpdir.join("codes.json");
+
+        let args = super::Args {
+            path_out: Some(path_out.to_str().unwrap().into()),
+        };
+        super::run(&crate::common::Args::default(), &args)?;
+
+        let codes: Vec<serde_json::Value> =
+            serde_json::from_reader(std::fs::File::open(&path_out)?)?;
+        assert_eq!(codes.len(), CATALOGUE.len());
+        let code = codes
+            .iter()
+            .find(|code| code["id"] == "W-INGEST-0003")
+            .expect("code missing");
+        assert_eq!(code["severity"], "warning");
+
+        Ok(())
+    }
+}
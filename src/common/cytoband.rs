@@ -0,0 +1,314 @@
+//! Cytogenetic bands, e.g., for describing CNVs in ISCN nomenclature.
+//!
+//! The bands are bundled in UCSC `cytoBand.txt` format for each genome release and can be
+//! overridden by a `cytobands.txt` file in the worker database.
+
+use std::{collections::HashMap, path::Path};
+
+use mehari::common::io::std::open_read_maybe_gz;
+
+use super::{contig_length, GenomeRelease};
+
+/// Centromere positions (0-based, half-open) on GRCh37.
+pub const CENTROMERES_37: [(&str, usize, usize); 24] = [
+    ("1", 121535434, 124535434),
+    ("2", 92326171, 95326171),
+    ("3", 90504854, 93504854),
+    ("4", 49660117, 52660117),
+    ("5", 46405641, 49405641),
+    ("6", 58830166, 61830166),
+    ("7", 58054331, 61054331),
+    ("8", 43838887, 46838887),
+    ("9", 47367679, 50367679),
+    ("10", 39254935, 42254935),
+    ("11", 51644205, 54644205),
+    ("12", 34856694, 37856694),
+    ("13", 16000000, 19000000),
+    ("14", 16000000, 19000000),
+    ("15", 17000000, 20000000),
+    ("16", 35335801, 38335801),
+    ("17", 22263006, 25263006),
+    ("18", 15460898, 18460898),
+    ("19", 24681782, 27681782),
+    ("20", 26369569, 29369569),
+    ("21", 11288129, 14288129),
+    ("22", 13000000, 16000000),
+    ("X", 58632012, 61632012),
+    ("Y", 10104553, 13104553),
+];
+
+/// Centromere positions (0-based, half-open) on GRCh38.
+pub const CENTROMERES_38: [(&str, usize, usize); 24] = [
+    ("1", 121700000, 125100000),
+    ("2", 91800000, 96000000),
+    ("3", 87800000, 94000000),
+    ("4", 48200000, 51800000),
+    ("5", 46100000, 51400000),
+    ("6", 58500000, 62600000),
+    ("7", 58100000, 62100000),
+    ("8", 43200000, 47200000),
+    ("9", 42200000, 45500000),
+    ("10", 38000000, 41600000),
+    ("11", 51000000, 55800000),
+    ("12", 33200000, 37800000),
+    ("13", 16500000, 18900000),
+    ("14", 16100000, 18200000),
+    ("15", 17500000, 20500000),
+    ("16", 35300000, 38400000),
+    ("17", 22700000, 27400000),
+    ("18", 15400000, 21500000),
+    ("19", 24200000, 28100000),
+    ("20", 25700000, 30400000),
+    ("21", 10900000, 13000000),
+    ("22", 13700000, 17400000),
+    ("X", 58100000, 61000000),
+    ("Y", 10300000, 10600000),
+];
+
+/// Return the centromere of `chrom` as 0-based, half-open range, if known.
+pub fn centromere(genome_release: GenomeRelease, chrom: &str) -> Option<(usize, usize)> {
+    let centromeres = match genome_release {
+        GenomeRelease::Grch37 => &CENTROMERES_37,
+        GenomeRelease::Grch38 => &CENTROMERES_38,
+    };
+    let chrom = annonars::common::cli::canonicalize(chrom);
+    centromeres
+        .iter()
+        .find(|(name, _, _)| *name == chrom)
+        .map(|(_, start, end)| (*start, *end))
+}
+
+/// A single cytogenetic band.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Band {
+    /// 0-based begin position.
+    pub begin: usize,
+    /// End position.
+    pub end: usize,
+    /// Name of the band without the chromosome, e.g., `q11.23`.
+    pub name: String,
+}
+
+/// Cytogenetic bands by canonical chromosome name.
+#[derive(Debug, Default, Clone)]
+pub struct Cytobands {
+    /// Bands sorted by position, by canonical chromosome name.
+    pub bands: HashMap<String, Vec<Band>>,
+}
+
+/// Code for reading the UCSC `cytoBand.txt` format.
+mod input {
+    use serde::Deserialize;
+
+    /// Type for record structs from input.
+    #[derive(Deserialize, Debug)]
+    pub struct Record {
+        /// Chromosome name.
+        pub chrom: String,
+        /// 0-based begin position.
+        pub begin: usize,
+        /// End position.
+        pub end: usize,
+        /// Name of the band.
+        pub name: String,
+        /// Giemsa stain result.
+        #[allow(dead_code)]
+        pub gie_stain: String,
+    }
+}
+
+/// Bundled bands on GRCh37 in UCSC `cytoBand.txt` format.
+const CYTOBANDS_37: &str = include_str!("cytoband/grch37.txt");
+
+/// Bundled bands on GRCh38 in UCSC `cytoBand.txt` format.
+const CYTOBANDS_38: &str = include_str!("cytoband/grch38.txt");
+
+impl Cytobands {
+    /// Read the bands in UCSC `cytoBand.txt` format from `reader`.
+    pub fn from_reader<R: std::io::Read>(reader: R) -> Result<Self, anyhow::Error> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .comment(Some(b'#'))
+            .delimiter(b'\t')
+            .from_reader(reader);
+        let mut result = Self::default();
+        for record in reader.deserialize() {
+            let record: input::Record =
+                record.map_err(|e| anyhow::anyhow!("problem reading cytobands: {}", e))?;
+            result
+                .bands
+                .entry(annonars::common::cli::canonicalize(&record.chrom))
+                .or_default()
+                .push(Band {
+                    begin: record.begin,
+                    end: record.end,
+                    name: record.name,
+                });
+        }
+        for bands in result.bands.values_mut() {
+            bands.sort_by_key(|band| band.begin);
+        }
+        Ok(result)
+    }
+
+    /// Load the bands from the UCSC `cytoBand.txt` file at `path`, optionally gzip-compressed.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        Self::from_reader(open_read_maybe_gz(path.to_str().unwrap())?)
+            .map_err(|e| anyhow::anyhow!("problem loading cytobands {:?}: {}", path, e))
+    }
+
+    /// Return the bands bundled for `genome_release`.
+    pub fn bundled(genome_release: GenomeRelease) -> Self {
+        let text = match genome_release {
+            GenomeRelease::Grch37 => CYTOBANDS_37,
+            GenomeRelease::Grch38 => CYTOBANDS_38,
+        };
+        Self::from_reader(text.as_bytes()).expect("bundled cytobands must be valid")
+    }
+
+    /// Load the bands from `{path_db}/{genome_release}/features/cytobands.txt` and fall back
+    /// to the bundled bands if the file does not exist.
+    pub fn load(path_db: &str, genome_release: GenomeRelease) -> Result<Self, anyhow::Error> {
+        let path = Path::new(path_db).join(format!("{}/features/cytobands.txt", genome_release));
+        if path.exists() {
+            let result = Self::from_path(&path)?;
+            result.validate(genome_release)?;
+            Ok(result)
+        } else {
+            tracing::info!("  no cytobands at {:?}, using bundled bands", &path);
+            Ok(Self::bundled(genome_release))
+        }
+    }
+
+    /// Check that the bands fit the contig lengths of `genome_release`, e.g., to detect a
+    /// table for the wrong genome build.
+    pub fn validate(&self, genome_release: GenomeRelease) -> Result<(), anyhow::Error> {
+        for (chrom, bands) in &self.bands {
+            let (Some(length), Some(last)) = (contig_length(genome_release, chrom), bands.last())
+            else {
+                continue;
+            };
+            if last.end > length {
+                anyhow::bail!(
+                    "cytoband {}{} ends at {} beyond the length {} of the contig in {}",
+                    chrom,
+                    last.name,
+                    last.end,
+                    length,
+                    genome_release.name()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the band containing the 1-based position `pos` on `chrom`.
+    pub fn band_for(&self, chrom: &str, pos: usize) -> Option<&Band> {
+        let bands = self
+            .bands
+            .get(&annonars::common::cli::canonicalize(chrom))?;
+        let idx = bands.partition_point(|band| band.end < pos);
+        bands
+            .get(idx)
+            .filter(|band| band.begin < pos && pos <= band.end)
+    }
+
+    /// Describe the 1-based, closed range `start..=end` on `chrom` by its bands, e.g.,
+    /// `7q11.23` or `7q11.22q11.23` if the range spans more than one band.
+    pub fn describe_range(&self, chrom: &str, start: usize, end: usize) -> Option<String> {
+        let chrom = annonars::common::cli::canonicalize(chrom);
+        let first = self.band_for(&chrom, start)?;
+        let last = self.band_for(&chrom, end)?;
+        if first == last {
+            Some(format!("{}{}", chrom, first.name))
+        } else {
+            Some(format!("{}{}{}", chrom, first.name, last.name))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::common::GenomeRelease;
+
+    #[test]
+    fn centromere() {
+        assert_eq!(
+            super::centromere(GenomeRelease::Grch37, "chr21"),
+            Some((11288129, 14288129))
+        );
+        assert_eq!(super::centromere(GenomeRelease::Grch38, "MT"), None);
+    }
+
+    #[test]
+    fn from_reader() -> Result<(), anyhow::Error> {
+        let cytobands = super::Cytobands::from_reader(
+            "chr7\t72200000\t73300000\tq11.22\tgpos50\n\
+             chr7\t73300000\t77400000\tq11.23\tgneg\n"
+                .as_bytes(),
+        )?;
+
+        assert_eq!(
+            cytobands
+                .band_for("7", 73_300_000)
+                .map(|band| band.name.as_str()),
+            Some("q11.22")
+        );
+        assert_eq!(
+            cytobands
+                .band_for("chr7", 73_300_001)
+                .map(|band| band.name.as_str()),
+            Some("q11.23")
+        );
+        assert_eq!(cytobands.band_for("7", 1), None);
+        assert_eq!(
+            cytobands.describe_range("7", 73_650_120, 74_154_209),
+            Some("7q11.23".to_string())
+        );
+        assert_eq!(
+            cytobands.describe_range("chr7", 72_650_120, 74_154_209),
+            Some("7q11.22q11.23".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_path() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("cytoBand.txt");
+        std::fs::write(&path, "chr7\t73300000\t77400000\tq11.23\tgneg\n")?;
+        let cytobands = super::Cytobands::from_path(&path)?;
+
+        assert_eq!(
+            cytobands.describe_range("7", 73_650_120, 74_154_209),
+            Some("7q11.23".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn validate() {
+        let cytobands = super::Cytobands::bundled(GenomeRelease::Grch38);
+        assert!(cytobands.validate(GenomeRelease::Grch38).is_ok());
+        // chr1 is longer on GRCh38 than on GRCh37.
+        assert!(cytobands.validate(GenomeRelease::Grch37).is_err());
+    }
+
+    #[test]
+    fn bundled() {
+        let cytobands = super::Cytobands::bundled(GenomeRelease::Grch37);
+
+        assert_eq!(
+            cytobands.describe_range("5", 1_000, 2_000),
+            Some("5p".to_string())
+        );
+        assert_eq!(
+            cytobands.describe_range("5", 1_000, 180_000_000),
+            Some("5pq".to_string())
+        );
+        assert_eq!(cytobands.describe_range("MT", 1, 100), None);
+    }
+}
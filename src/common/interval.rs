@@ -0,0 +1,153 @@
+//! Interval arithmetic on linear and circular contigs.
+//!
+//! Intervals are 0-based and half-open.  On circular contigs such as the mitochondrial
+//! genome, intervals spanning the origin are given with `end < begin`.
+
+use std::ops::Range;
+
+/// Length of the mitochondrial genome (rCRS, same in GRCh37 and GRCh38).
+pub const MT_LENGTH: i32 = 16_569;
+
+/// Return whether `chrom` is the name of the mitochondrial genome.
+pub fn is_mt(chrom: &str) -> bool {
+    matches!(chrom, "M" | "MT" | "chrM" | "chrMT")
+}
+
+/// Topology of a contig.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    /// Linear contig, intervals with `end < begin` are empty.
+    Linear,
+    /// Circular contig of the given length.
+    Circular {
+        /// Length of the contig.
+        length: i32,
+    },
+}
+
+impl Topology {
+    /// Return the topology of the contig with the given name.
+    ///
+    /// Only the mitochondrial genome is considered to be circular.
+    pub fn from_chrom(chrom: &str) -> Self {
+        if is_mt(chrom) {
+            Topology::Circular { length: MT_LENGTH }
+        } else {
+            Topology::Linear
+        }
+    }
+}
+
+/// A 0-based, half-open interval on a contig.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    /// 0-based begin position.
+    pub begin: i32,
+    /// End position, smaller than `begin` for intervals spanning the origin.
+    pub end: i32,
+}
+
+impl Interval {
+    /// Construct new interval.
+    pub fn new(begin: i32, end: i32) -> Self {
+        Self { begin, end }
+    }
+
+    /// Return whether the interval spans the origin of a circular contig.
+    pub fn spans_origin(&self, topology: Topology) -> bool {
+        matches!(topology, Topology::Circular { .. }) && self.end < self.begin
+    }
+
+    /// Return the length of the interval.
+    pub fn len(&self, topology: Topology) -> i32 {
+        match topology {
+            Topology::Circular { length } if self.end < self.begin => {
+                length - self.begin + self.end
+            }
+            _ => std::cmp::max(0, self.end - self.begin),
+        }
+    }
+
+    /// Split the interval into linear ranges, empty ranges are dropped.
+    pub fn linear_parts(&self, topology: Topology) -> Vec<Range<i32>> {
+        let parts = match topology {
+            Topology::Circular { length } if self.end < self.begin => {
+                [self.begin..length, 0..self.end]
+            }
+            _ => [self.begin..self.end, 0..0],
+        };
+        parts
+            .into_iter()
+            .filter(|range| !range.is_empty())
+            .collect()
+    }
+
+    /// Return the length of the overlap with `other`.
+    pub fn overlap(&self, other: &Interval, topology: Topology) -> i32 {
+        let mut result = 0;
+        for lhs in self.linear_parts(topology) {
+            for rhs in other.linear_parts(topology) {
+                result += std::cmp::max(
+                    0,
+                    std::cmp::min(lhs.end, rhs.end) - std::cmp::max(lhs.start, rhs.start),
+                );
+            }
+        }
+        result
+    }
+}
+
+impl From<Range<i32>> for Interval {
+    fn from(range: Range<i32>) -> Self {
+        Self::new(range.start, range.end)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Interval, Topology, MT_LENGTH};
+
+    const CIRCULAR: Topology = Topology::Circular { length: MT_LENGTH };
+
+    #[rstest::rstest]
+    #[case("MT", CIRCULAR)]
+    #[case("chrM", CIRCULAR)]
+    #[case("1", Topology::Linear)]
+    #[case("chrX", Topology::Linear)]
+    fn topology_from_chrom(#[case] chrom: &str, #[case] expected: Topology) {
+        assert_eq!(Topology::from_chrom(chrom), expected);
+    }
+
+    #[rstest::rstest]
+    #[case((100, 200), Topology::Linear, 100)]
+    #[case((200, 100), Topology::Linear, 0)]
+    #[case((100, 200), CIRCULAR, 100)]
+    #[case((16_500, 100), CIRCULAR, 169)]
+    fn len(#[case] interval: (i32, i32), #[case] topology: Topology, #[case] expected: i32) {
+        assert_eq!(
+            Interval::new(interval.0, interval.1).len(topology),
+            expected
+        );
+    }
+
+    #[rstest::rstest]
+    #[case((0, 100), (50, 150), 50)]
+    #[case((0, 100), (100, 150), 0)]
+    #[case((16_500, 100), (0, 50), 50)]
+    #[case((16_500, 100), (16_400, 16_550), 50)]
+    #[case((16_500, 100), (16_530, 10), 49)]
+    fn overlap_circular(#[case] lhs: (i32, i32), #[case] rhs: (i32, i32), #[case] expected: i32) {
+        let lhs = Interval::new(lhs.0, lhs.1);
+        let rhs = Interval::new(rhs.0, rhs.1);
+        assert_eq!(lhs.overlap(&rhs, CIRCULAR), expected);
+        assert_eq!(rhs.overlap(&lhs, CIRCULAR), expected);
+    }
+
+    #[test]
+    fn spans_origin() {
+        let interval = Interval::new(16_469, 100);
+        assert!(interval.spans_origin(CIRCULAR));
+        assert!(!interval.spans_origin(Topology::Linear));
+        assert!(!Interval::new(100, 200).spans_origin(CIRCULAR));
+    }
+}
@@ -0,0 +1,5 @@
+//! Common I/O code.
+
+pub mod remote;
+pub mod std;
+pub mod tokio;
@@ -0,0 +1,388 @@
+//! Reading of remote files, i.e., S3 objects and HTTP(S) URLs.
+//!
+//! Remote files are streamed rather than downloaded first.  Optionally, only a byte range
+//! is read, e.g., the bgzf blocks of a region given an index.
+
+use std::{io::SeekFrom, ops::Range, pin::Pin};
+
+use futures::TryStreamExt;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt};
+
+/// Maximal number of HTTP redirects to follow.
+const MAX_REDIRECTS: usize = 5;
+
+/// Size of the chunks passed from the async to the blocking reader.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Number of chunks buffered between the async and the blocking reader.
+const CHANNEL_CAPACITY: usize = 16;
+
+/// Location of a file to read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    /// A local file path.
+    Local(String),
+    /// An S3 object, given as `s3://<bucket>/<key>`.
+    S3 {
+        /// The bucket name.
+        bucket: String,
+        /// The object key.
+        key: String,
+    },
+    /// An `http://` or `https://` URL.
+    Http(String),
+}
+
+impl Location {
+    /// Parse `path`, a local path unless it starts with `s3://`, `http://`, or `https://`.
+    pub fn parse(path: &str) -> Result<Self, anyhow::Error> {
+        if let Some(bucket_key) = path.strip_prefix("s3://") {
+            match bucket_key.split_once('/') {
+                Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => Ok(Location::S3 {
+                    bucket: bucket.to_string(),
+                    key: key.to_string(),
+                }),
+                _ => anyhow::bail!("invalid S3 URL {}, must be s3://<bucket>/<key>", path),
+            }
+        } else if path.starts_with("http://") || path.starts_with("https://") {
+            Ok(Location::Http(path.to_string()))
+        } else {
+            Ok(Location::Local(path.to_string()))
+        }
+    }
+
+    /// Return whether the location is remote.
+    pub fn is_remote(&self) -> bool {
+        !matches!(self, Location::Local(_))
+    }
+
+    /// Return the path without URL query or fragment, e.g., to check the file extension.
+    pub fn path(&self) -> &str {
+        match self {
+            Location::Local(path) => path,
+            Location::S3 { key, .. } => key,
+            Location::Http(url) => url.split(['?', '#']).next().unwrap_or(url),
+        }
+    }
+}
+
+/// Return whether `path` is an S3 or HTTP(S) URL.
+pub fn is_remote(path: &str) -> bool {
+    path.starts_with("s3://") || path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// Return the HTTP `Range` header value for `range`.
+fn range_header(range: &Range<u64>) -> String {
+    format!("bytes={}-{}", range.start, range.end - 1)
+}
+
+/// Return S3 client configured from the environment.
+///
+/// A custom endpoint is used if `AWS_ENDPOINT_URL` is set, see
+/// `crate::common::s3::config_from_env()`.
+async fn s3_client() -> Result<aws_sdk_s3::Client, anyhow::Error> {
+    if std::env::var("AWS_ENDPOINT_URL").is_ok() {
+        Ok(aws_sdk_s3::Client::from_conf(
+            crate::common::s3::config_from_env().await?,
+        ))
+    } else {
+        Ok(aws_sdk_s3::Client::new(&aws_config::load_from_env().await))
+    }
+}
+
+/// Send a `GET` request for `url` and `range`, following redirects.
+async fn http_get(
+    url: &str,
+    range: Option<&Range<u64>>,
+) -> Result<hyper::Response<hyper::Body>, anyhow::Error> {
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client = hyper::Client::builder().build::<_, hyper::Body>(connector);
+
+    let mut url = url.to_string();
+    for _ in 0..=MAX_REDIRECTS {
+        let mut builder = hyper::Request::get(url.as_str());
+        if let Some(range) = range {
+            builder = builder.header(hyper::header::RANGE, range_header(range));
+        }
+        let request = builder
+            .body(hyper::Body::empty())
+            .map_err(|e| anyhow::anyhow!("invalid URL {}: {}", &url, e))?;
+        let response = client
+            .request(request)
+            .await
+            .map_err(|e| anyhow::anyhow!("could not get {}: {}", &url, e))?;
+
+        let status = response.status();
+        if status.is_redirection() {
+            url = response
+                .headers()
+                .get(hyper::header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .ok_or_else(|| anyhow::anyhow!("redirect without location from {}", &url))?
+                .to_string();
+            tracing::trace!("following redirect to {}", &url);
+        } else if !status.is_success() {
+            anyhow::bail!("could not get {}: HTTP status {}", &url, status);
+        } else if range.is_some() && status != hyper::StatusCode::PARTIAL_CONTENT {
+            anyhow::bail!("server does not support byte ranges for {}", &url);
+        } else {
+            return Ok(response);
+        }
+    }
+
+    anyhow::bail!("too many redirects for {}", &url)
+}
+
+/// Open `location` for reading, only the byte `range` if given.
+///
+/// # Errors
+///
+/// If the file cannot be opened or `range` is empty.
+pub async fn open_read(
+    location: &Location,
+    range: Option<Range<u64>>,
+) -> Result<Pin<Box<dyn AsyncRead>>, anyhow::Error> {
+    if let Some(range) = range.as_ref() {
+        if range.is_empty() {
+            anyhow::bail!("empty byte range {:?} for {:?}", range, location);
+        }
+    }
+    tracing::trace!("Opening {:?} for reading range {:?}", location, &range);
+
+    match location {
+        Location::Local(path) => {
+            let mut file = tokio::fs::File::open(path)
+                .await
+                .map_err(|e| anyhow::anyhow!("could not open file {}: {}", path, e))?;
+            if let Some(range) = range {
+                file.seek(SeekFrom::Start(range.start))
+                    .await
+                    .map_err(|e| anyhow::anyhow!("could not seek in file {}: {}", path, e))?;
+                Ok(Box::pin(file.take(range.end - range.start)))
+            } else {
+                Ok(Box::pin(file))
+            }
+        }
+        Location::S3 { bucket, key } => {
+            let mut request = s3_client().await?.get_object().bucket(bucket).key(key);
+            if let Some(range) = range.as_ref() {
+                request = request.range(range_header(range));
+            }
+            let object = request.send().await.map_err(|e| {
+                anyhow::anyhow!("could not get S3 object s3://{}/{}: {}", bucket, key, e)
+            })?;
+            Ok(Box::pin(object.body.into_async_read()))
+        }
+        Location::Http(url) => {
+            let response = http_get(url, range.as_ref()).await?;
+            Ok(Box::pin(tokio_util::io::StreamReader::new(
+                response
+                    .into_body()
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e)),
+            )))
+        }
+    }
+}
+
+/// Blocking reader for a file read by `open_read()` in a background thread.
+///
+/// The background thread has its own runtime so this also works when called from async
+/// code.  It sends the chunks read, an empty chunk at the end of the file, or the error.
+pub struct BlockingReader {
+    /// Receives the chunks from the background thread.
+    receiver: std::sync::mpsc::Receiver<std::io::Result<Vec<u8>>>,
+    /// The current chunk.
+    chunk: Vec<u8>,
+    /// Position in the current chunk.
+    pos: usize,
+    /// Whether the end of the file has been reached.
+    done: bool,
+}
+
+impl BlockingReader {
+    /// Open `location` for blocking reads, only the byte `range` if given.
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be opened.
+    pub fn open(location: Location, range: Option<Range<u64>>) -> Result<Self, anyhow::Error> {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(CHANNEL_CAPACITY);
+        std::thread::spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    let _ = sender.send(Err(e));
+                    return;
+                }
+            };
+            runtime.block_on(async move {
+                let mut reader = match open_read(&location, range).await {
+                    Ok(reader) => reader,
+                    Err(e) => {
+                        let _ = sender.send(Err(std::io::Error::new(
+                            std::io::ErrorKind::Other,
+                            e.to_string(),
+                        )));
+                        return;
+                    }
+                };
+                loop {
+                    let mut chunk = vec![0; CHUNK_SIZE];
+                    match reader.read(&mut chunk).await {
+                        Ok(count) => {
+                            chunk.truncate(count);
+                            if sender.send(Ok(chunk)).is_err() || count == 0 {
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            let _ = sender.send(Err(e));
+                            break;
+                        }
+                    }
+                }
+            });
+        });
+
+        // Receive the first chunk so problems with opening are reported here.
+        let mut result = Self {
+            receiver,
+            chunk: Vec::new(),
+            pos: 0,
+            done: false,
+        };
+        result
+            .next_chunk()
+            .map_err(|e| anyhow::anyhow!("could not open for reading: {}", e))?;
+        Ok(result)
+    }
+
+    /// Receive the next chunk from the background thread.
+    fn next_chunk(&mut self) -> std::io::Result<()> {
+        match self.receiver.recv() {
+            Ok(Ok(chunk)) => {
+                self.done = chunk.is_empty();
+                self.chunk = chunk;
+                self.pos = 0;
+                Ok(())
+            }
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "reader thread terminated unexpectedly",
+            )),
+        }
+    }
+}
+
+impl std::io::Read for BlockingReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.next_chunk()?;
+        }
+        let count = buf.len().min(self.chunk.len() - self.pos);
+        buf[..count].copy_from_slice(&self.chunk[self.pos..self.pos + count]);
+        self.pos += count;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use tokio::io::AsyncReadExt;
+
+    use super::Location;
+
+    #[rstest::rstest]
+    #[case("tests/file.vcf", Location::Local("tests/file.vcf".into()))]
+    #[case("s3://bucket/case/file.vcf.gz", Location::S3 { bucket: "bucket".into(), key: "case/file.vcf.gz".into() })]
+    #[case("https://example.com/file.vcf.gz", Location::Http("https://example.com/file.vcf.gz".into()))]
+    fn location_parse(#[case] path: &str, #[case] expected: Location) -> Result<(), anyhow::Error> {
+        assert_eq!(Location::parse(path)?, expected);
+        assert_eq!(super::is_remote(path), expected.is_remote());
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case("s3://bucket")]
+    #[case("s3:///key")]
+    fn location_parse_invalid(#[case] path: &str) {
+        assert!(Location::parse(path).is_err());
+    }
+
+    #[test]
+    fn location_path() -> Result<(), anyhow::Error> {
+        assert_eq!(
+            Location::parse("https://example.com/file.vcf.gz?token=x")?.path(),
+            "https://example.com/file.vcf.gz"
+        );
+        assert_eq!(
+            Location::parse("s3://bucket/dir/file.vcf")?.path(),
+            "dir/file.vcf"
+        );
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case(None)]
+    #[case(Some(10..20))]
+    #[tokio::test]
+    async fn open_read_local(
+        #[case] range: Option<std::ops::Range<u64>>,
+    ) -> Result<(), anyhow::Error> {
+        let path = "tests/seqvars/query/Case_1.ingested.vcf";
+        let expected = std::fs::read(path)?;
+        let expected = match range.as_ref() {
+            Some(range) => expected[range.start as usize..range.end as usize].to_vec(),
+            None => expected,
+        };
+
+        let mut actual = Vec::new();
+        super::open_read(&Location::Local(path.into()), range)
+            .await?
+            .read_to_end(&mut actual)
+            .await?;
+        assert_eq!(actual, expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn open_read_empty_range() {
+        assert!(super::open_read(
+            &Location::Local("tests/seqvars/query/Case_1.ingested.vcf".into()),
+            Some(5..5)
+        )
+        .await
+        .is_err());
+    }
+
+    #[test]
+    fn blocking_reader() -> Result<(), anyhow::Error> {
+        let path = "tests/seqvars/query/Case_1.ingested.vcf";
+        let mut actual = Vec::new();
+        super::BlockingReader::open(Location::Local(path.into()), None)?
+            .read_to_end(&mut actual)?;
+        assert_eq!(actual, std::fs::read(path)?);
+
+        assert!(
+            super::BlockingReader::open(Location::Local("does/not/exist".into()), None).is_err()
+        );
+
+        Ok(())
+    }
+}
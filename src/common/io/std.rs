@@ -0,0 +1,166 @@
+//! Blocking common I/O code.
+//!
+//! Complements `mehari::common::io::std` with reading of remote files and writing of
+//! compressed files.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use flate2::bufread::MultiGzDecoder;
+use mehari::common::io::std::is_gz;
+use noodles_bgzf as bgzf;
+
+use super::remote::{BlockingReader, Location};
+use super::tokio::OutputCompression;
+
+/// Transparently open a local or remote file with a gzip decoder if necessary.
+///
+/// Local paths are opened with `mehari::common::io::std::open_read_maybe_gz()`.  Paths
+/// starting with `s3://`, `http://`, or `https://` are streamed in a background thread,
+/// see `super::remote::BlockingReader`.  Files ending in `.gz` or `.bgz` are decoded,
+/// allowing multiple gzip members as in bgzip files.
+///
+/// # Arguments
+///
+/// * `path` - A path or URL of the file to open.
+pub fn open_read_maybe_gz<P>(path: P) -> Result<Box<dyn BufRead>, anyhow::Error>
+where
+    P: AsRef<Path>,
+{
+    let location = Location::parse(&path.as_ref().to_string_lossy())?;
+    if !location.is_remote() {
+        return mehari::common::io::std::open_read_maybe_gz(path);
+    }
+
+    let path_is_gzip = is_gz(location.path());
+    tracing::trace!(
+        "Opening {:?} as {} for reading",
+        &location,
+        if path_is_gzip {
+            "gzip (allow multi-member)"
+        } else {
+            "plain text"
+        }
+    );
+    let reader = BufReader::new(
+        BlockingReader::open(location.clone(), None)
+            .map_err(|e| anyhow::anyhow!("could not open {}: {}", path.as_ref().display(), e))?,
+    );
+    if path_is_gzip {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Transparently open a file with a compressing encoder for writing.
+///
+/// This is the blocking counterpart of `super::tokio::open_write_maybe_bgzf()` for the
+/// outputs written with `std::io`, e.g., JSON reports.  Files ending in `.gz` or `.bgz`
+/// are written in bgzip format, files ending in `.zst` in zstd format, and all other files
+/// as plain text.  The `compression_level` is passed to the encoder (0-9 for bgzip, 1-22
+/// for zstd) and the format's default is used if `None`.  The encoders write their final
+/// blocks when the writer is dropped.
+///
+/// # Arguments
+///
+/// * `path` - A path to the file to open.
+/// * `compression_level` - Compression level of the encoder.
+pub fn open_write_maybe_bgzf<P>(
+    path: P,
+    compression_level: Option<i32>,
+) -> Result<Box<dyn Write + Send>, anyhow::Error>
+where
+    P: AsRef<Path>,
+{
+    let compression = OutputCompression::from_path(path.as_ref());
+    tracing::trace!(
+        "Opening {} as {} for writing",
+        path.as_ref().display(),
+        match compression {
+            OutputCompression::Plain => "plain text",
+            OutputCompression::Bgzf => "bgzip (block gzip)",
+            OutputCompression::Zstd => "zstd",
+        }
+    );
+    let file = File::create(path.as_ref())
+        .map_err(|e| anyhow::anyhow!("could not open file {}: {}", path.as_ref().display(), e))?;
+
+    match compression {
+        OutputCompression::Plain => Ok(Box::new(BufWriter::new(file))),
+        OutputCompression::Bgzf => {
+            let mut builder = bgzf::writer::Builder::default();
+            if let Some(compression_level) = compression_level {
+                builder = builder.set_compression_level(
+                    u8::try_from(compression_level)
+                        .ok()
+                        .and_then(|level| bgzf::writer::CompressionLevel::try_from(level).ok())
+                        .ok_or_else(|| {
+                            anyhow::anyhow!(
+                                "invalid bgzip compression level {}, must be 0-9",
+                                compression_level
+                            )
+                        })?,
+                );
+            }
+            Ok(Box::new(builder.build_with_writer(file)))
+        }
+        OutputCompression::Zstd => {
+            let encoder = zstd::stream::write::Encoder::new(
+                BufWriter::new(file),
+                compression_level.unwrap_or(0),
+            )
+            .map_err(|e| anyhow::anyhow!("could not create zstd encoder: {}", e))?;
+            Ok(Box::new(encoder.auto_finish()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read as _, Write as _};
+
+    use super::OutputCompression;
+
+    #[rstest::rstest]
+    #[case("test.txt", None)]
+    #[case("test.txt.gz", None)]
+    #[case("test.txt.gz", Some(9))]
+    #[case("test.txt.zst", None)]
+    #[case("test.txt.zst", Some(19))]
+    fn open_write_maybe_bgzf(
+        #[case] filename: &str,
+        #[case] compression_level: Option<i32>,
+    ) -> Result<(), anyhow::Error> {
+        let tmp_dir = temp_testdir::TempDir::default();
+        let tmp_file_path = tmp_dir.join(filename);
+
+        let mut expected = String::new();
+        {
+            let mut writer = super::open_write_maybe_bgzf(&tmp_file_path, compression_level)?;
+            for i in 1..100_000 {
+                let line = format!("{}\n", i);
+                writer.write_all(line.as_bytes())?;
+                expected.push_str(&line);
+            }
+        }
+
+        let mut actual = String::new();
+        if OutputCompression::from_path(&tmp_file_path) == OutputCompression::Zstd {
+            zstd::stream::read::Decoder::new(std::fs::File::open(&tmp_file_path)?)?
+                .read_to_string(&mut actual)?;
+        } else {
+            super::open_read_maybe_gz(&tmp_file_path)?.read_to_string(&mut actual)?;
+        }
+        assert_eq!(actual, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn open_write_maybe_bgzf_invalid_level() {
+        let tmp_dir = temp_testdir::TempDir::default();
+        assert!(super::open_write_maybe_bgzf(tmp_dir.join("test.txt.gz"), Some(10)).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! Tokio-based async common I/O code.
+//!
+//! Complements `mehari::common::io::tokio` with writing of compressed files and reading
+//! of remote files.  The output compression is selected by the file name extension, see `OutputCompression`.
+
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::pin::Pin;
+
+use async_compression::{
+    tokio::{bufread::GzipDecoder, write::ZstdEncoder},
+    Level,
+};
+use mehari::common::io::std::is_gz;
+use noodles_bgzf as bgzf;
+use tokio::fs::File;
+use tokio::io::{AsyncBufRead, AsyncWrite, BufReader, BufWriter};
+
+use super::remote::Location;
+
+/// Transparently open a local or remote file with a gzip decoder if necessary.
+///
+/// Local paths are opened with `mehari::common::io::tokio::open_read_maybe_gz()`.  Paths
+/// starting with `s3://`, `http://`, or `https://` are streamed, see
+/// `super::remote::open_read()`.  Files ending in `.gz` or `.bgz` are decoded, allowing
+/// multiple gzip members as in bgzip files.
+///
+/// # Arguments
+///
+/// * `path` - A path or URL of the file to open.
+pub async fn open_read_maybe_gz<P>(path: P) -> Result<Pin<Box<dyn AsyncBufRead>>, anyhow::Error>
+where
+    P: AsRef<Path>,
+{
+    let location = Location::parse(&path.as_ref().to_string_lossy())?;
+    if !location.is_remote() {
+        return mehari::common::io::tokio::open_read_maybe_gz(path).await;
+    }
+
+    let path_is_gzip = is_gz(location.path());
+    tracing::trace!(
+        "Opening {:?} as {} for reading (async)",
+        &location,
+        if path_is_gzip {
+            "gzip (allow multi-member)"
+        } else {
+            "plain text"
+        }
+    );
+    let reader = BufReader::new(super::remote::open_read(&location, None).await?);
+    if path_is_gzip {
+        let mut decoder = GzipDecoder::new(reader);
+        decoder.multiple_members(true);
+        Ok(Box::pin(BufReader::new(decoder)))
+    } else {
+        Ok(Box::pin(reader))
+    }
+}
+
+/// Compression of an output file, selected by the file name extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputCompression {
+    /// Plain text, the default for all other extensions.
+    Plain,
+    /// Block gzip, for `.gz` and `.bgz`.
+    Bgzf,
+    /// Zstandard, for `.zst`.
+    Zstd,
+}
+
+impl OutputCompression {
+    /// Select the compression from the extension of `path`.
+    pub fn from_path<P>(path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        if is_gz(path.as_ref()) {
+            OutputCompression::Bgzf
+        } else if path.as_ref().extension().and_then(|ext| ext.to_str()) == Some("zst") {
+            OutputCompression::Zstd
+        } else {
+            OutputCompression::Plain
+        }
+    }
+}
+
+/// Transparently open a file with a compressing encoder for writing.
+///
+/// Files ending in `.gz` or `.bgz` are written in bgzip format which can be read by any
+/// gzip decoder.  The blocks are compressed by `worker_count` blocking tasks (number of
+/// logical CPUs if `None`).  Files ending in `.zst` are written in zstd format and all
+/// other files as plain text.  The `compression_level` is passed to the encoder (0-9 for
+/// bgzip, 1-22 for zstd) and the format's default is used if `None`.  Note that the
+/// caller must call `shutdown()` on the writer so the final blocks and the EOF marker are
+/// written.
+///
+/// # Arguments
+///
+/// * `path` - A path to the file to open.
+/// * `worker_count` - Number of workers for block compression.
+/// * `compression_level` - Compression level of the encoder.
+pub async fn open_write_maybe_bgzf<P>(
+    path: P,
+    worker_count: Option<NonZeroUsize>,
+    compression_level: Option<i32>,
+) -> Result<Pin<Box<dyn AsyncWrite + Send>>, anyhow::Error>
+where
+    P: AsRef<Path>,
+{
+    let compression = OutputCompression::from_path(path.as_ref());
+    tracing::trace!(
+        "Opening {} as {} for writing (async)",
+        path.as_ref().display(),
+        match compression {
+            OutputCompression::Plain => "plain text",
+            OutputCompression::Bgzf => "bgzip (block gzip)",
+            OutputCompression::Zstd => "zstd",
+        }
+    );
+    let file = File::create(path.as_ref())
+        .await
+        .map_err(|e| anyhow::anyhow!("could not open file {}: {}", path.as_ref().display(), e))?;
+
+    match compression {
+        OutputCompression::Plain => Ok(Box::pin(BufWriter::new(file))),
+        OutputCompression::Bgzf => {
+            let mut builder = bgzf::r#async::writer::Builder::default();
+            if let Some(worker_count) = worker_count {
+                builder = builder.set_worker_count(worker_count);
+            }
+            if let Some(compression_level) = compression_level {
+                builder = builder.set_compression_level(
+                    u8::try_from(compression_level)
+                        .ok()
+                        .and_then(|level| bgzf::writer::CompressionLevel::try_from(level).ok())
+                        .ok_or_else(|| {
+                            anyhow::anyhow!(
+                                "invalid bgzip compression level {}, must be 0-9",
+                                compression_level
+                            )
+                        })?,
+                );
+            }
+            Ok(Box::pin(BufWriter::new(builder.build_with_writer(file))))
+        }
+        OutputCompression::Zstd => {
+            let level = compression_level
+                .map(Level::Precise)
+                .unwrap_or(Level::Default);
+            Ok(Box::pin(ZstdEncoder::with_quality(
+                BufWriter::new(file),
+                level,
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroUsize;
+
+    use async_compression::tokio::bufread::ZstdDecoder;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::OutputCompression;
+
+    #[rstest::rstest]
+    #[case("test.txt", OutputCompression::Plain)]
+    #[case("test.vcf.gz", OutputCompression::Bgzf)]
+    #[case("test.vcf.bgz", OutputCompression::Bgzf)]
+    #[case("test.vcf.zst", OutputCompression::Zstd)]
+    fn output_compression_from_path(#[case] filename: &str, #[case] expected: OutputCompression) {
+        assert_eq!(OutputCompression::from_path(filename), expected);
+    }
+
+    #[rstest::rstest]
+    #[case("test.txt", None, None)]
+    #[case("test.txt.gz", None, None)]
+    #[case("test.txt.gz", NonZeroUsize::new(1), Some(9))]
+    #[case("test.txt.bgz", NonZeroUsize::new(4), None)]
+    #[case("test.txt.zst", None, None)]
+    #[case("test.txt.zst", None, Some(19))]
+    #[tokio::test]
+    async fn open_write_maybe_bgzf(
+        #[case] filename: &str,
+        #[case] worker_count: Option<NonZeroUsize>,
+        #[case] compression_level: Option<i32>,
+    ) -> Result<(), anyhow::Error> {
+        let tmp_dir = temp_testdir::TempDir::default();
+        let tmp_file_path = tmp_dir.join(filename);
+
+        let mut expected = String::new();
+        {
+            let mut writer =
+                super::open_write_maybe_bgzf(&tmp_file_path, worker_count, compression_level)
+                    .await?;
+            for i in 1..100_000 {
+                let line = format!("{}\n", i);
+                writer.write_all(line.as_bytes()).await?;
+                expected.push_str(&line);
+            }
+            writer.shutdown().await?;
+        }
+
+        let mut actual = String::new();
+        if OutputCompression::from_path(&tmp_file_path) == OutputCompression::Zstd {
+            ZstdDecoder::new(tokio::io::BufReader::new(
+                tokio::fs::File::open(&tmp_file_path).await?,
+            ))
+            .read_to_string(&mut actual)
+            .await?;
+        } else {
+            super::open_read_maybe_gz(&tmp_file_path)
+                .await?
+                .read_to_string(&mut actual)
+                .await?;
+        }
+        assert_eq!(actual, expected);
+
+        Ok(())
+    }
+}
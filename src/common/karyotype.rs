@@ -0,0 +1,174 @@
+//! Per-sample sex chromosome karyotypes.
+//!
+//! By default, the karyotype is derived from the sex in the pedigree.  Samples with
+//! non-standard karyotypes, e.g., Klinefelter (XXY) or Turner (X0) syndrome, can be
+//! declared in a karyotypes TSV file with the columns sample name and karyotype.
+
+use mehari::ped::Sex;
+
+use super::Chrom;
+
+/// Sex chromosome karyotype of a sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum::Display, strum::EnumString)]
+pub enum Karyotype {
+    /// Typical female karyotype.
+    #[strum(serialize = "XX")]
+    Xx,
+    /// Typical male karyotype.
+    #[strum(serialize = "XY")]
+    Xy,
+    /// Klinefelter syndrome.
+    #[strum(serialize = "XXY")]
+    Xxy,
+    /// Turner syndrome.
+    #[strum(serialize = "X0", serialize = "XO")]
+    X0,
+    /// Jacobs syndrome.
+    #[strum(serialize = "XYY")]
+    Xyy,
+    /// Triple X syndrome.
+    #[strum(serialize = "XXX")]
+    Xxx,
+}
+
+impl Karyotype {
+    /// Return the typical karyotype for `sex`, if known.
+    pub fn from_sex(sex: Sex) -> Option<Self> {
+        match sex {
+            Sex::Male => Some(Karyotype::Xy),
+            Sex::Female => Some(Karyotype::Xx),
+            Sex::Unknown => None,
+        }
+    }
+
+    /// Return the ploidy of `chrom`.
+    pub fn ploidy(&self, chrom: Chrom) -> usize {
+        let (count_x, count_y) = match self {
+            Karyotype::Xx => (2, 0),
+            Karyotype::Xy => (1, 1),
+            Karyotype::Xxy => (2, 1),
+            Karyotype::X0 => (1, 0),
+            Karyotype::Xyy => (1, 2),
+            Karyotype::Xxx => (3, 0),
+        };
+        match chrom {
+            Chrom::Auto => 2,
+            Chrom::X => count_x,
+            Chrom::Y => count_y,
+        }
+    }
+
+    /// Return the sex whose typical ploidy of `chrom` matches this karyotype.
+    ///
+    /// This allows to use the karyotype in code distinguishing male and female samples
+    /// on the gonosomes.  On the autosomes, the sex is irrelevant and `Sex::Unknown` is
+    /// returned.
+    pub fn effective_sex(&self, chrom: Chrom) -> Sex {
+        match (chrom, self.ploidy(chrom)) {
+            (Chrom::Auto, _) => Sex::Unknown,
+            (Chrom::X, 1) | (Chrom::Y, 1..) => Sex::Male,
+            (Chrom::X, _) | (Chrom::Y, _) => Sex::Female,
+        }
+    }
+}
+
+/// Karyotypes declared for samples with non-standard karyotypes.
+#[derive(Debug, Clone, Default)]
+pub struct Karyotypes {
+    /// The declared karyotypes by sample name.
+    pub by_sample: indexmap::IndexMap<String, Karyotype>,
+}
+
+impl Karyotypes {
+    /// Load from the TSV file at `path`; lines starting with `#` are ignored.
+    pub fn from_path(path: &str) -> Result<Self, anyhow::Error> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("could not read karyotypes file {}: {}", path, e))?;
+        let by_sample = contents
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (sample, karyotype) = line
+                    .split_once('\t')
+                    .ok_or_else(|| anyhow::anyhow!("invalid karyotypes line: {:?}", line))?;
+                let karyotype = karyotype.trim().parse().map_err(|e| {
+                    anyhow::anyhow!("invalid karyotype {:?} of {}: {}", karyotype, sample, e)
+                })?;
+                Ok((sample.to_string(), karyotype))
+            })
+            .collect::<Result<_, anyhow::Error>>()?;
+        Ok(Self { by_sample })
+    }
+
+    /// Load from `path` if given, otherwise return empty karyotypes.
+    pub fn from_optional_path(path: Option<&str>) -> Result<Self, anyhow::Error> {
+        path.map(Self::from_path)
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    /// Return the karyotype of `sample`, falling back to the typical karyotype for `sex`.
+    pub fn get(&self, sample: &str, sex: Sex) -> Option<Karyotype> {
+        self.by_sample
+            .get(sample)
+            .copied()
+            .or_else(|| Karyotype::from_sex(sex))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::ped::Sex;
+
+    use super::{Karyotype, Karyotypes};
+    use crate::common::Chrom;
+
+    #[rstest::rstest]
+    #[case(Karyotype::Xx, Chrom::X, 2, Sex::Female)]
+    #[case(Karyotype::Xx, Chrom::Y, 0, Sex::Female)]
+    #[case(Karyotype::Xy, Chrom::X, 1, Sex::Male)]
+    #[case(Karyotype::Xy, Chrom::Y, 1, Sex::Male)]
+    #[case(Karyotype::Xxy, Chrom::X, 2, Sex::Female)]
+    #[case(Karyotype::Xxy, Chrom::Y, 1, Sex::Male)]
+    #[case(Karyotype::X0, Chrom::X, 1, Sex::Male)]
+    #[case(Karyotype::X0, Chrom::Y, 0, Sex::Female)]
+    #[case(Karyotype::Xyy, Chrom::Y, 2, Sex::Male)]
+    #[case(Karyotype::Xxx, Chrom::X, 3, Sex::Female)]
+    #[case(Karyotype::X0, Chrom::Auto, 2, Sex::Unknown)]
+    fn ploidy_and_effective_sex(
+        #[case] karyotype: Karyotype,
+        #[case] chrom: Chrom,
+        #[case] expected_ploidy: usize,
+        #[case] expected_sex: Sex,
+    ) {
+        assert_eq!(karyotype.ploidy(chrom), expected_ploidy);
+        assert_eq!(karyotype.effective_sex(chrom), expected_sex);
+    }
+
+    #[test]
+    fn from_path() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("karyotypes.tsv").to_str().unwrap().to_string();
+        std::fs::write(&path, "# sample\tkaryotype\nindex\tXXY\nmother\tXO\n")?;
+
+        let karyotypes = Karyotypes::from_path(&path)?;
+
+        assert_eq!(karyotypes.get("index", Sex::Male), Some(Karyotype::Xxy));
+        assert_eq!(karyotypes.get("mother", Sex::Female), Some(Karyotype::X0));
+        assert_eq!(karyotypes.get("father", Sex::Male), Some(Karyotype::Xy));
+        assert_eq!(karyotypes.get("other", Sex::Unknown), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_path_invalid() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("karyotypes.tsv").to_str().unwrap().to_string();
+        std::fs::write(&path, "index\tXYZ\n")?;
+
+        assert!(Karyotypes::from_path(&path).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,638 @@
+//! Key-value store backed by a remote annonars REST server.
+//!
+//! Instead of opening the multi-hundred-GB frequency and ClinVar RocksDB databases
+//! locally, the values are fetched with one `/annos/variant` request per variant and
+//! converted into the same binary representation as stored in the local databases.
+//! Requests for a batch of keys are sent concurrently via `VariantKvStore::prefetch` and
+//! retried with exponential backoff on transient errors.
+//!
+//! The responses can be recorded in a directory with `Recordings` and replayed from there
+//! on re-runs of the same case or in tests without a server.
+
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use annonars::{
+    common::keys,
+    freqs::serialized::{auto, mt, xy},
+};
+use prost::Message;
+use sha2::Digest;
+
+use crate::common::GenomeRelease;
+
+use super::VariantKvStore;
+
+/// Number of variants to keep the fetched values for.
+const CACHE_SIZE: usize = 100_000;
+
+/// Delay before the first retry, doubled for each further retry.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+
+/// The HTTP(S) client type used for querying the server.
+type Client =
+    hyper::Client<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>, hyper::Body>;
+
+/// Values of one variant, in the binary representation of the local databases.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Values {
+    /// Value for the `autosomal` table.
+    pub autosomal: Option<Vec<u8>>,
+    /// Value for the `gonosomal` table.
+    pub gonosomal: Option<Vec<u8>>,
+    /// Value for the `mitochondrial` table.
+    pub mitochondrial: Option<Vec<u8>>,
+    /// Value for the `clinvar` table.
+    pub clinvar: Option<Vec<u8>>,
+}
+
+impl Values {
+    /// Return the value for `table`.
+    fn get(&self, table: &str) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        match table {
+            "autosomal" => Ok(self.autosomal.clone()),
+            "gonosomal" => Ok(self.gonosomal.clone()),
+            "mitochondrial" => Ok(self.mitochondrial.clone()),
+            "clinvar" => Ok(self.clinvar.clone()),
+            _ => anyhow::bail!("no table {} on annotation server", table),
+        }
+    }
+
+    /// Convert the `result` of an `/annos/variant` response for a variant on `chrom`.
+    pub fn from_result(chrom: &str, result: &serde_json::Value) -> Result<Self, anyhow::Error> {
+        let db = |name: &str| result.get(name).filter(|value| !value.is_null());
+        let exomes = db("gnomad_exomes");
+        let genomes = db("gnomad_genomes");
+
+        let mut values = Values::default();
+        match chrom.trim_start_matches("chr") {
+            "M" | "MT" => {
+                let gnomad_mtdna = db("gnomad_mtdna");
+                let helixmtdb = db("helixmtdb");
+                if gnomad_mtdna.is_some() || helixmtdb.is_some() {
+                    let record = mt::Record {
+                        gnomad_mtdna: gnomad_mtdna
+                            .map(|record| mt::Counts {
+                                an: int(record, "an"),
+                                ac_hom: int(record, "acHom"),
+                                ac_het: int(record, "acHet"),
+                            })
+                            .unwrap_or_default(),
+                        helixmtdb: helixmtdb
+                            .map(|record| mt::Counts {
+                                an: int(record, "numTotal"),
+                                ac_hom: int(record, "numHom"),
+                                ac_het: int(record, "numHet"),
+                            })
+                            .unwrap_or_default(),
+                    };
+                    let mut buf = vec![0u8; mt::Record::buf_len()];
+                    record.to_buf(&mut buf);
+                    values.mitochondrial = Some(buf);
+                }
+            }
+            "X" | "Y" => {
+                if exomes.is_some() || genomes.is_some() {
+                    let record = xy::Record {
+                        gnomad_exomes: exomes.map(xy_counts).unwrap_or_default(),
+                        gnomad_genomes: genomes.map(xy_counts).unwrap_or_default(),
+                    };
+                    let mut buf = vec![0u8; xy::Record::buf_len()];
+                    record.to_buf(&mut buf);
+                    values.gonosomal = Some(buf);
+                }
+            }
+            _ => {
+                if exomes.is_some() || genomes.is_some() {
+                    let record = auto::Record {
+                        gnomad_exomes: exomes.map(auto_counts).unwrap_or_default(),
+                        gnomad_genomes: genomes.map(auto_counts).unwrap_or_default(),
+                    };
+                    let mut buf = vec![0u8; auto::Record::buf_len()];
+                    record.to_buf(&mut buf);
+                    values.autosomal = Some(buf);
+                }
+            }
+        }
+
+        if let Some(clinvar) = db("clinvar") {
+            let record: annonars::pbs::clinvar::minimal::Record =
+                serde_json::from_value(clinvar.clone())
+                    .map_err(|e| anyhow::anyhow!("problem decoding ClinVar record: {}", e))?;
+            values.clinvar = Some(record.encode_to_vec());
+        }
+
+        Ok(values)
+    }
+}
+
+/// Return the integer field `name` of `value`, zero if missing.
+fn int(value: &serde_json::Value, name: &str) -> u32 {
+    value
+        .get(name)
+        .and_then(serde_json::Value::as_i64)
+        .unwrap_or_default() as u32
+}
+
+/// Return the allele counts by sex of the overall cohort of a gnomAD record.
+fn counts_by_sex(record: &serde_json::Value) -> Option<&serde_json::Value> {
+    record
+        .get("alleleCounts")?
+        .as_array()?
+        .iter()
+        .find(|counts| counts.get("cohort").map(|c| c.is_null()).unwrap_or(true))?
+        .get("bySex")
+}
+
+/// Convert gnomAD record JSON to autosomal counts.
+fn auto_counts(record: &serde_json::Value) -> auto::Counts {
+    let overall = counts_by_sex(record).and_then(|by_sex| by_sex.get("overall"));
+    let field = |name: &str| overall.map(|value| int(value, name)).unwrap_or_default();
+    let ac_hom = field("nhomalt");
+    auto::Counts {
+        an: field("an"),
+        ac_hom,
+        ac_het: field("ac").saturating_sub(2 * ac_hom),
+    }
+}
+
+/// Convert gnomAD record JSON to gonosomal counts, same as `xy::Counts::from_vcf_allele`.
+fn xy_counts(record: &serde_json::Value) -> xy::Counts {
+    let by_sex = counts_by_sex(record);
+    let field = |sex: &str, name: &str| {
+        by_sex
+            .and_then(|by_sex| by_sex.get(sex))
+            .map(|value| int(value, name))
+            .unwrap_or_default()
+    };
+    let an = field("overall", "an");
+    let ac_xx = field("xx", "ac");
+    let ac_hom_xx = field("xx", "nhomalt");
+    let ac_xy = field("xy", "ac");
+    let ac_hom_xy = field("xy", "nhomalt");
+    let nonpar = record
+        .get("nonpar")
+        .and_then(serde_json::Value::as_bool)
+        .unwrap_or(false);
+
+    if nonpar {
+        xy::Counts {
+            an,
+            ac_hom: ac_hom_xx,
+            ac_het: ac_xx.saturating_sub(2 * ac_hom_xx),
+            ac_hemi: ac_xy,
+        }
+    } else {
+        xy::Counts {
+            an,
+            ac_hom: ac_hom_xx + ac_hom_xy,
+            ac_het: ac_xx.saturating_sub(2 * ac_hom_xx + 2 * ac_hom_xy),
+            ac_hemi: 0,
+        }
+    }
+}
+
+/// Decode a key of the local databases into a variant.
+pub fn key_to_var(key: &[u8]) -> Result<keys::Var, anyhow::Error> {
+    if key.len() < 7 {
+        anyhow::bail!("key too short: {:?}", key);
+    }
+    let chrom = keys::chrom_key_to_name(&key[0..2]);
+    let pos = i32::from_be_bytes(key[2..6].try_into().expect("slice has length 4"));
+    let alleles = std::str::from_utf8(&key[6..])
+        .map_err(|e| anyhow::anyhow!("problem decoding alleles in key: {}", e))?;
+    let (reference, alternative) = alleles
+        .split_once('>')
+        .ok_or_else(|| anyhow::anyhow!("no '>' in alleles of key: {}", alleles))?;
+
+    Ok(keys::Var::from(&chrom, pos, reference, alternative))
+}
+
+/// On-disk recordings of server responses, one JSON file per request.
+#[derive(Debug, Clone)]
+pub struct Recordings {
+    /// Directory with the recorded responses.
+    dir: PathBuf,
+    /// Whether to fail for requests without recorded response instead of querying the
+    /// server.
+    replay_only: bool,
+}
+
+impl Recordings {
+    /// Record responses in and replay them from `dir`, only replaying if `replay_only`.
+    pub fn new(dir: &str, replay_only: bool) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+            replay_only,
+        }
+    }
+
+    /// Return the path of the recording for the request with `query`.
+    ///
+    /// The path only depends on the query so recordings can be replayed against other
+    /// server URLs.
+    fn path(&self, query: &str) -> PathBuf {
+        let hash = base16ct::lower::encode_string(&sha2::Sha256::digest(query.as_bytes()));
+        self.dir.join(format!("{}.json", hash))
+    }
+
+    /// Load the recorded response for `query`, if any.
+    async fn load(&self, query: &str) -> Result<Option<serde_json::Value>, anyhow::Error> {
+        let path = self.path(query);
+        match tokio::fs::read(&path).await {
+            Ok(buf) => {
+                let mut recording: serde_json::Value = serde_json::from_slice(&buf)
+                    .map_err(|e| anyhow::anyhow!("problem parsing {}: {}", path.display(), e))?;
+                Ok(Some(recording["response"].take()))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => anyhow::bail!("problem reading {}: {}", path.display(), e),
+        }
+    }
+
+    /// Record `response` for `query`.
+    async fn save(&self, query: &str, response: &serde_json::Value) -> Result<(), anyhow::Error> {
+        let path = self.path(query);
+        let buf = serde_json::to_vec(&serde_json::json!({
+            "query": query,
+            "response": response,
+        }))?;
+        // Write to a temporary file first so concurrent runs never see partial files.
+        let path_tmp = path.with_extension(format!("json.{}", std::process::id()));
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(&path_tmp, buf)
+            .await
+            .map_err(|e| anyhow::anyhow!("problem writing {}: {}", path_tmp.display(), e))?;
+        tokio::fs::rename(&path_tmp, &path)
+            .await
+            .map_err(|e| anyhow::anyhow!("problem renaming to {}: {}", path.display(), e))
+    }
+
+    /// Return the response for `query` from the recordings or from the server at
+    /// `base_url`, recording it in the latter case.
+    async fn get_json(
+        &self,
+        client: &Client,
+        base_url: &str,
+        query: &str,
+        max_retries: u32,
+    ) -> Result<serde_json::Value, anyhow::Error> {
+        if let Some(response) = self.load(query).await? {
+            return Ok(response);
+        }
+        if self.replay_only {
+            anyhow::bail!("no recorded response for {}", query);
+        }
+        let response = get_json(client, &format!("{}/{}", base_url, query), max_retries).await?;
+        self.save(query, &response).await?;
+        Ok(response)
+    }
+}
+
+/// Store that queries a remote annonars server.
+pub struct HttpStore {
+    /// Base URL of the server, without trailing slash.
+    base_url: String,
+    /// Genome release to query for.
+    genome_release: GenomeRelease,
+    /// Maximal number of concurrent requests.
+    batch_size: usize,
+    /// Maximal number of retries per request.
+    max_retries: u32,
+    /// The HTTP(S) client.
+    client: Client,
+    /// Recordings of the responses to use, if any.
+    recordings: Option<Recordings>,
+    /// The values fetched so far, by key.
+    cache: quick_cache::sync::Cache<Vec<u8>, Arc<Values>>,
+    /// Runtime for the requests, independent of the caller's runtime, if any.
+    runtime: Option<tokio::runtime::Runtime>,
+}
+
+impl HttpStore {
+    /// Create a new store for the server at `base_url`.
+    ///
+    /// # Errors
+    ///
+    /// If the runtime for the requests cannot be created.
+    pub fn new(
+        base_url: &str,
+        genome_release: GenomeRelease,
+        batch_size: usize,
+        max_retries: u32,
+    ) -> Result<Self, anyhow::Error> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .map_err(|e| anyhow::anyhow!("problem creating runtime: {}", e))?;
+        let connector = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .https_or_http()
+            .enable_http1()
+            .build();
+
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            genome_release,
+            batch_size: batch_size.max(1),
+            max_retries,
+            client: hyper::Client::builder().build(connector),
+            recordings: None,
+            cache: quick_cache::sync::Cache::new(CACHE_SIZE),
+            runtime: Some(runtime),
+        })
+    }
+
+    /// Use `recordings` of the responses.
+    pub fn with_recordings(mut self, recordings: Recordings) -> Self {
+        self.recordings = Some(recordings);
+        self
+    }
+
+    /// Return the path and query of the `/annos/variant` request for `var`.
+    fn query(&self, var: &keys::Var) -> String {
+        format!(
+            "annos/variant?genome_release={}&chromosome={}&pos={}&reference={}&alternative={}",
+            self.genome_release.name().to_lowercase(),
+            var.chrom,
+            var.pos,
+            var.reference,
+            var.alternative
+        )
+    }
+
+    /// Fetch the values for all `keys` concurrently and store them in the cache.
+    fn fetch(&self, keys: Vec<Vec<u8>>) -> Result<(), anyhow::Error> {
+        let requests = keys
+            .into_iter()
+            .map(|key| {
+                let var = key_to_var(&key)?;
+                Ok((key, self.query(&var), var.chrom))
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+        let client = self.client.clone();
+        let base_url = self.base_url.clone();
+        let recordings = self.recordings.clone();
+        let max_retries = self.max_retries;
+        let runtime = self.runtime.as_ref().expect("runtime only taken on drop");
+
+        // Spawn on our own runtime and block on the join handle so this also works when
+        // called from within another runtime.
+        let results = futures::executor::block_on(runtime.spawn(async move {
+            futures::future::join_all(requests.into_iter().map(|(key, query, chrom)| {
+                let client = client.clone();
+                let base_url = &base_url;
+                let recordings = &recordings;
+                async move {
+                    let result = match recordings {
+                        Some(recordings) => {
+                            recordings
+                                .get_json(&client, base_url, &query, max_retries)
+                                .await?
+                        }
+                        None => {
+                            get_json(&client, &format!("{}/{}", base_url, query), max_retries)
+                                .await?
+                        }
+                    };
+                    let values = Values::from_result(&chrom, &result["result"])?;
+                    Ok::<_, anyhow::Error>((key, values))
+                }
+            }))
+            .await
+        }))
+        .map_err(|e| anyhow::anyhow!("problem joining requests: {}", e))?;
+
+        for result in results {
+            let (key, values) = result?;
+            self.cache.insert(key, Arc::new(values));
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for HttpStore {
+    fn drop(&mut self) {
+        // Dropping a runtime blocks, which is not allowed from within another runtime.
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+impl VariantKvStore for HttpStore {
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        if let Some(values) = self.cache.get(key) {
+            return values.get(table);
+        }
+        self.fetch(vec![key.to_vec()])?;
+        self.cache
+            .get(key)
+            .ok_or_else(|| anyhow::anyhow!("no values fetched for key {:?}", key))?
+            .get(table)
+    }
+
+    fn prefetch(&self, keys: &[Vec<u8>]) -> Result<(), anyhow::Error> {
+        let mut missing = keys
+            .iter()
+            .filter(|key| self.cache.get(key.as_slice()).is_none())
+            .cloned()
+            .collect::<Vec<_>>();
+        missing.sort();
+        missing.dedup();
+        for chunk in missing.chunks(self.batch_size) {
+            self.fetch(chunk.to_vec())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Send a `GET` request to `url` and parse the response as JSON.
+///
+/// Connection errors, server errors, and rate limiting are retried up to `max_retries`
+/// times with exponential backoff.
+async fn get_json(
+    client: &Client,
+    url: &str,
+    max_retries: u32,
+) -> Result<serde_json::Value, anyhow::Error> {
+    let mut attempt = 0;
+    loop {
+        let error = match client.get(url.parse()?).await {
+            Ok(response) => {
+                let status = response.status();
+                if status.is_success() {
+                    let body = hyper::body::to_bytes(response.into_body())
+                        .await
+                        .map_err(|e| anyhow::anyhow!("problem reading response body: {}", e))?;
+                    return serde_json::from_slice(&body).map_err(|e| {
+                        anyhow::anyhow!("problem parsing response of {}: {}", url, e)
+                    });
+                } else if status.is_server_error() || status == hyper::StatusCode::TOO_MANY_REQUESTS
+                {
+                    anyhow::anyhow!("status {} for {}", status, url)
+                } else {
+                    anyhow::bail!("status {} for {}", status, url);
+                }
+            }
+            Err(e) => anyhow::anyhow!("problem requesting {}: {}", url, e),
+        };
+
+        if attempt >= max_retries {
+            return Err(error);
+        }
+        tracing::debug!("retrying after error: {}", error);
+        tokio::time::sleep(RETRY_BASE_DELAY * 2u32.pow(attempt)).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use annonars::{
+        common::keys,
+        freqs::serialized::{auto, mt, xy},
+    };
+
+    use super::{key_to_var, HttpStore, Recordings, Values};
+    use crate::common::kv::VariantKvStore;
+
+    #[test]
+    fn key_to_var_roundtrip() -> Result<(), anyhow::Error> {
+        for var in [
+            keys::Var::from("1", 55505599, "C", "G"),
+            keys::Var::from("X", 100, "AT", "A"),
+            keys::Var::from("MT", 3, "T", "C"),
+        ] {
+            let key: Vec<u8> = var.clone().into();
+            assert_eq!(key_to_var(&key)?, var);
+        }
+        assert!(key_to_var(b"01").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn values_autosomal() -> Result<(), anyhow::Error> {
+        let result = serde_json::json!({
+            "gnomad_exomes": {
+                "alleleCounts": [
+                    {"cohort": "controls", "bySex": {"overall": {"ac": 1, "an": 10}}},
+                    {"bySex": {"overall": {"ac": 5, "an": 100, "nhomalt": 1}}}
+                ]
+            },
+            "gnomad_genomes": null,
+        });
+        let values = Values::from_result("1", &result)?;
+
+        assert_eq!(values.gonosomal, None);
+        assert_eq!(values.mitochondrial, None);
+        assert_eq!(values.clinvar, None);
+        let record = auto::Record::from_buf(values.autosomal.as_ref().unwrap());
+        assert_eq!(record.gnomad_exomes.an, 100);
+        assert_eq!(record.gnomad_exomes.ac_hom, 1);
+        assert_eq!(record.gnomad_exomes.ac_het, 3);
+        assert_eq!(record.gnomad_genomes.an, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn values_gonosomal() -> Result<(), anyhow::Error> {
+        let by_sex = serde_json::json!({
+            "overall": {"ac": 7, "an": 100, "nhomalt": 2},
+            "xx": {"ac": 5, "an": 60, "nhomalt": 2},
+            "xy": {"ac": 2, "an": 40},
+        });
+        let result = serde_json::json!({
+            "gnomad_exomes": {"nonpar": true, "alleleCounts": [{"bySex": by_sex}]},
+            "gnomad_genomes": {"alleleCounts": [{"bySex": by_sex}]},
+        });
+        let values = Values::from_result("X", &result)?;
+
+        assert_eq!(values.autosomal, None);
+        let record = xy::Record::from_buf(values.gonosomal.as_ref().unwrap());
+        assert_eq!(
+            (
+                record.gnomad_exomes.an,
+                record.gnomad_exomes.ac_hom,
+                record.gnomad_exomes.ac_het,
+                record.gnomad_exomes.ac_hemi
+            ),
+            (100, 2, 1, 2)
+        );
+        assert_eq!(
+            (
+                record.gnomad_genomes.an,
+                record.gnomad_genomes.ac_hom,
+                record.gnomad_genomes.ac_het,
+                record.gnomad_genomes.ac_hemi
+            ),
+            (100, 2, 1, 0)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn values_mitochondrial() -> Result<(), anyhow::Error> {
+        let result = serde_json::json!({
+            "gnomad_mtdna": {"an": 1000, "acHom": 10, "acHet": 2},
+            "helixmtdb": {"numTotal": 2000, "numHom": 20, "numHet": 4},
+        });
+        let values = Values::from_result("MT", &result)?;
+
+        let record = mt::Record::from_buf(values.mitochondrial.as_ref().unwrap());
+        assert_eq!(record.gnomad_mtdna.an, 1000);
+        assert_eq!(record.gnomad_mtdna.ac_hom, 10);
+        assert_eq!(record.gnomad_mtdna.ac_het, 2);
+        assert_eq!(record.helixmtdb.an, 2000);
+        assert_eq!(record.helixmtdb.ac_hom, 20);
+        assert_eq!(record.helixmtdb.ac_het, 4);
+        assert!(values.get("unknown").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn replay_recordings() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let dir = tmpdir.to_str().unwrap();
+        let recordings = Recordings::new(dir, true);
+        let key: Vec<u8> = keys::Var::from("1", 55505599, "C", "G").into();
+        let query = "annos/variant?genome_release=grch37&chromosome=1&pos=55505599&reference=C&alternative=G";
+        std::fs::write(
+            recordings.path(query),
+            serde_json::to_vec(&serde_json::json!({
+                "query": query,
+                "response": {
+                    "result": {
+                        "gnomad_genomes": {
+                            "alleleCounts": [{"bySex": {"overall": {"ac": 2, "an": 10}}}]
+                        }
+                    }
+                }
+            }))?,
+        )?;
+
+        let store = HttpStore::new(
+            "http://localhost:1",
+            crate::common::GenomeRelease::Grch37,
+            4,
+            0,
+        )?
+        .with_recordings(recordings);
+        let value = store.get("autosomal", &key)?.expect("must be recorded");
+        let record = auto::Record::from_buf(&value);
+        assert_eq!(record.gnomad_genomes.an, 10);
+        assert_eq!(record.gnomad_genomes.ac_het, 2);
+        assert_eq!(store.get("clinvar", &key)?, None);
+
+        let other: Vec<u8> = keys::Var::from("1", 100, "A", "T").into();
+        assert!(store.get("autosomal", &other).is_err());
+
+        Ok(())
+    }
+}
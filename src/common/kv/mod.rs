@@ -0,0 +1,110 @@
+//! Key-value store abstraction for the variant lookups.
+//!
+//! The frequency, ClinVar, and in-house lookups only need point lookups of raw values by
+//! key in named tables, i.e., RocksDB column families.  `VariantKvStore` abstracts over
+//! this so the RocksDB databases can be replaced by an `InMemoryStore` in tests or by an
+//! `http::HttpStore` that queries a remote annonars server.
+
+use std::collections::HashMap;
+
+pub mod http;
+
+/// Point lookups of raw values by key in named tables.
+pub trait VariantKvStore: Send + Sync {
+    /// Return the value for `key` in `table`, if any.
+    ///
+    /// # Errors
+    ///
+    /// If `table` does not exist or there is a problem querying the store.
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>, anyhow::Error>;
+
+    /// Hint that the values for `keys` will be requested soon.
+    ///
+    /// Stores with expensive lookups can fetch them in one batch; the default does nothing.
+    fn prefetch(&self, _keys: &[Vec<u8>]) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+}
+
+impl VariantKvStore for rocksdb::DB {
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        let cf = self
+            .cf_handle(table)
+            .ok_or_else(|| anyhow::anyhow!("no column family {} in RocksDB", table))?;
+        self.get_cf(cf, key)
+            .map_err(|e| anyhow::anyhow!("problem querying RocksDB: {}", e))
+    }
+}
+
+/// Store that keeps all tables in memory.
+#[derive(Debug, Default, Clone)]
+pub struct InMemoryStore {
+    /// The values by key, by table name.
+    tables: HashMap<String, HashMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl InMemoryStore {
+    /// Create a store with the given empty `tables`.
+    pub fn with_tables<'a>(tables: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            tables: tables
+                .into_iter()
+                .map(|table| (table.to_string(), HashMap::new()))
+                .collect(),
+        }
+    }
+
+    /// Insert `value` for `key` into `table`, creating the table if necessary.
+    pub fn insert(&mut self, table: &str, key: Vec<u8>, value: Vec<u8>) {
+        self.tables
+            .entry(table.to_string())
+            .or_default()
+            .insert(key, value);
+    }
+}
+
+impl VariantKvStore for InMemoryStore {
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        Ok(self
+            .tables
+            .get(table)
+            .ok_or_else(|| anyhow::anyhow!("no table {} in store", table))?
+            .get(key)
+            .cloned())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InMemoryStore, VariantKvStore};
+
+    /// Check that `store` has `b"value"` for `b"key"` in table `data` only.
+    fn check_store(store: &dyn VariantKvStore) -> Result<(), anyhow::Error> {
+        assert_eq!(store.get("data", b"key")?, Some(b"value".to_vec()));
+        assert_eq!(store.get("data", b"other")?, None);
+        assert_eq!(store.get("empty", b"key")?, None);
+        assert!(store.get("missing", b"key").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn in_memory() -> Result<(), anyhow::Error> {
+        let mut store = InMemoryStore::with_tables(["data", "empty"]);
+        store.insert("data", b"key".to_vec(), b"value".to_vec());
+
+        check_store(&store)
+    }
+
+    #[test]
+    fn rocksdb() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let mut options = rocksdb::Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+        let db = rocksdb::DB::open_cf(&options, &*tmpdir, ["data", "empty"])?;
+        db.put_cf(&db.cf_handle("data").unwrap(), b"key", b"value")?;
+
+        check_store(&db)
+    }
+}
@@ -0,0 +1,216 @@
+//! Advisory lock files for the writable databases owned by the worker.
+//!
+//! The aggregation subcommands write the in-house databases that are shared between jobs.
+//! Before writing, they exclusively create `<path>.lock` with the host name, process ID,
+//! and creation time of the job and remove it when done, such that a concurrent job fails
+//! instead of corrupting the database.
+//!
+//! A lock left behind by a killed job is stale if its process no longer runs on this host
+//! or, for locks of other hosts, if it is older than the stale timeout.  Stale locks are
+//! removed with a warning.
+
+use std::io::Write as _;
+
+/// Suffix appended to the paths of lock files.
+pub const LOCK_SUFFIX: &str = "lock";
+
+/// Return the path of the lock file for the database at `path`.
+pub fn lock_path(path: &str) -> String {
+    format!("{}.{}", path, LOCK_SUFFIX)
+}
+
+/// Owner of a lock, as written to the lock file.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct LockOwner {
+    /// Name of the host the job runs on.
+    pub hostname: String,
+    /// Process ID of the job.
+    pub pid: u32,
+    /// When the lock was created.
+    pub created: chrono::DateTime<chrono::Utc>,
+}
+
+impl LockOwner {
+    /// Return the owner for the current process.
+    fn current() -> Self {
+        Self {
+            hostname: hostname(),
+            pid: std::process::id(),
+            created: chrono::Utc::now(),
+        }
+    }
+
+    /// Return whether the lock is stale, i.e., its owner has died.
+    ///
+    /// For locks of other hosts, the process cannot be checked and locks older than
+    /// `stale_after` are considered stale.
+    pub fn is_stale(&self, stale_after: std::time::Duration) -> bool {
+        if self.hostname == hostname() {
+            !std::path::Path::new(&format!("/proc/{}", self.pid)).exists()
+        } else {
+            chrono::Utc::now()
+                .signed_duration_since(self.created)
+                .to_std()
+                .map(|age| age > stale_after)
+                .unwrap_or_default()
+        }
+    }
+}
+
+impl std::fmt::Display for LockOwner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "process {} on {} since {}",
+            self.pid,
+            &self.hostname,
+            self.created.to_rfc3339()
+        )
+    }
+}
+
+/// Return the name of the current host.
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .map(|name| name.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Lock of the database at a path that is released on drop.
+#[derive(Debug)]
+pub struct LockFile {
+    /// Path to the lock file.
+    path: String,
+}
+
+impl LockFile {
+    /// Lock the database at `path` by creating `<path>.lock`.
+    ///
+    /// Stale locks, see `LockOwner::is_stale()`, are removed with a warning.
+    ///
+    /// # Errors
+    ///
+    /// If the database is locked by another job or the lock file cannot be written.
+    pub fn acquire(path: &str, stale_after: std::time::Duration) -> Result<Self, anyhow::Error> {
+        let path = lock_path(path);
+        loop {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    let result = Self { path };
+                    serde_json::to_writer(&mut file, &LockOwner::current())?;
+                    file.flush()?;
+                    tracing::debug!("acquired lock {}", &result.path);
+                    return Ok(result);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let owner = std::fs::read_to_string(&path)
+                        .ok()
+                        .and_then(|content| serde_json::from_str::<LockOwner>(&content).ok());
+                    match owner {
+                        Some(owner) if owner.is_stale(stale_after) => {
+                            tracing::warn!(
+                                "{}: removing stale lock {} of {}",
+                                crate::codes::COMMON_STALE_LOCK_REMOVED,
+                                &path,
+                                &owner
+                            );
+                            match std::fs::remove_file(&path) {
+                                Ok(()) => (),
+                                Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+                                Err(e) => {
+                                    anyhow::bail!("could not remove stale lock {}: {}", &path, e)
+                                }
+                            }
+                        }
+                        Some(owner) => anyhow::bail!(
+                            "{}: database is locked by {}; remove {} if that job is gone",
+                            crate::codes::COMMON_DATABASE_LOCKED,
+                            &owner,
+                            &path
+                        ),
+                        None => anyhow::bail!(
+                            "{}: database is locked by unknown owner; remove {} if no job is \
+                            running",
+                            crate::codes::COMMON_DATABASE_LOCKED,
+                            &path
+                        ),
+                    }
+                }
+                Err(e) => anyhow::bail!("could not create lock {}: {}", &path, e),
+            }
+        }
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        match std::fs::remove_file(&self.path) {
+            Ok(()) => tracing::debug!("released lock {}", &self.path),
+            Err(e) => tracing::error!("could not remove lock {}: {}", &self.path, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{lock_path, LockFile, LockOwner};
+
+    /// Timeout for stale locks of other hosts in the tests.
+    const STALE_AFTER: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn acquire_and_release() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("db").to_str().unwrap().to_string();
+
+        {
+            let _lock = LockFile::acquire(&path, STALE_AFTER)?;
+            assert!(std::path::Path::new(&lock_path(&path)).exists());
+
+            let err = LockFile::acquire(&path, STALE_AFTER).unwrap_err();
+            assert!(err.to_string().starts_with("E-COMMON-0001"));
+        }
+        assert!(!std::path::Path::new(&lock_path(&path)).exists());
+        let _lock = LockFile::acquire(&path, STALE_AFTER)?;
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case::dead_process(true, u32::MAX, 0, true)]
+    #[case::live_process(true, std::process::id(), 0, false)]
+    #[case::other_host_recent(false, 1, 60, false)]
+    #[case::other_host_old(false, 1, 7200, true)]
+    fn remove_stale(
+        #[case] same_host: bool,
+        #[case] pid: u32,
+        #[case] age_secs: i64,
+        #[case] stale: bool,
+    ) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("db").to_str().unwrap().to_string();
+        let owner = LockOwner {
+            hostname: if same_host {
+                super::hostname()
+            } else {
+                "other-host".into()
+            },
+            pid,
+            created: chrono::Utc::now() - chrono::Duration::seconds(age_secs),
+        };
+        assert_eq!(owner.is_stale(STALE_AFTER), stale);
+        std::fs::write(lock_path(&path), serde_json::to_string(&owner)?)?;
+
+        assert_eq!(LockFile::acquire(&path, STALE_AFTER).is_ok(), stale);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,213 @@
+//! Manifest of the files produced by a subcommand.
+//!
+//! With `--path-manifest`, a JSON file is written after a successful run that lists the
+//! produced files with their size, checksum, format, and role so the server can find the
+//! artifacts without guessing file names.
+
+/// Suffixes of the compressed formats, checked before the plain extensions.
+const COMPRESSED_SUFFIXES: &[&str] = &[".gz", ".bgz", ".zst"];
+
+/// One produced file.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Entry {
+    /// Path of the file.
+    pub path: String,
+    /// Role of the file, e.g., `seqvars-ingested-vcf` or `qc-json`.
+    pub role: String,
+    /// Format of the file, e.g., `vcf.gz` or `json`.
+    pub format: String,
+    /// Size of the file in bytes.
+    pub size: u64,
+    /// Hex-encoded SHA256 checksum of the file.
+    pub sha256: String,
+}
+
+/// The manifest of one run.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+    /// Version of the worker.
+    pub worker_version: String,
+    /// The subcommand, e.g., `seqvars ingest`.
+    pub command: String,
+    /// The produced files.
+    pub files: Vec<Entry>,
+}
+
+/// Return the format of the file at `path` from its extension, e.g., `vcf.gz`.
+pub fn guess_format(path: &str) -> String {
+    let file_name = std::path::Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (stem, compression) = COMPRESSED_SUFFIXES
+        .iter()
+        .find_map(|suffix| {
+            file_name
+                .strip_suffix(suffix)
+                .map(|stem| (stem, Some(&suffix[1..])))
+        })
+        .unwrap_or((file_name.as_str(), None));
+    let format = match stem.rsplit_once('.') {
+        Some((_, extension)) => extension.to_string(),
+        None => String::from("unknown"),
+    };
+    match compression {
+        Some(compression) => format!("{}.{}", format, compression),
+        None => format,
+    }
+}
+
+impl Manifest {
+    /// Construct an empty manifest for `command`.
+    pub fn new(command: &str) -> Self {
+        Self {
+            worker_version: super::worker_version().to_string(),
+            command: command.to_string(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Add the file at `path` with `role`.
+    ///
+    /// Files in directories, e.g., RocksDB databases, are added recursively with the
+    /// format `format_dir`.  Remote paths and paths that do not exist are skipped.
+    fn add_with_format(
+        &mut self,
+        path: &str,
+        role: &str,
+        format_dir: Option<&str>,
+    ) -> Result<(), anyhow::Error> {
+        if path.contains("://") {
+            tracing::debug!("not adding remote path {} to manifest", path);
+            return Ok(());
+        }
+        let metadata = match std::fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(_) => {
+                tracing::debug!("not adding missing path {} to manifest", path);
+                return Ok(());
+            }
+        };
+
+        if metadata.is_dir() {
+            let mut paths = std::fs::read_dir(path)
+                .map_err(|e| anyhow::anyhow!("could not list {}: {}", path, e))?
+                .map(|entry| entry.map(|entry| entry.path().to_string_lossy().to_string()))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| anyhow::anyhow!("could not list {}: {}", path, e))?;
+            paths.sort();
+            for path in paths {
+                self.add_with_format(&path, role, format_dir)?;
+            }
+        } else {
+            self.files.push(Entry {
+                path: path.to_string(),
+                role: role.to_string(),
+                format: format_dir
+                    .map(String::from)
+                    .unwrap_or_else(|| guess_format(path)),
+                size: metadata.len(),
+                sha256: super::sha256_checksum(path)?,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Add the file at `path` with `role`, if it exists.
+    pub fn add(&mut self, path: &str, role: &str) -> Result<(), anyhow::Error> {
+        self.add_with_format(path, role, None)
+    }
+
+    /// Add the files in the directory at `path` with `role` and `format`, if it exists.
+    pub fn add_dir(&mut self, path: &str, role: &str, format: &str) -> Result<(), anyhow::Error> {
+        self.add_with_format(path, role, Some(format))
+    }
+
+    /// Add the VCF file at `path` with `role` and its index files, if they exist.
+    pub fn add_vcf(&mut self, path: &str, role: &str) -> Result<(), anyhow::Error> {
+        self.add(path, role)?;
+        for suffix in ["tbi", "csi"] {
+            self.add(&format!("{}.{}", path, suffix), "vcf-index")?;
+        }
+        Ok(())
+    }
+
+    /// Write the manifest as JSON to `path`.
+    pub fn write(&self, path: &str) -> Result<(), anyhow::Error> {
+        serde_json::to_writer_pretty(
+            std::fs::File::create(path)
+                .map_err(|e| anyhow::anyhow!("could not create {}: {}", path, e))?,
+            self,
+        )
+        .map_err(|e| anyhow::anyhow!("could not write manifest to {}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Manifest;
+
+    #[rstest::rstest]
+    #[case("out.vcf.gz", "vcf.gz")]
+    #[case("dir.d/out.vcf", "vcf")]
+    #[case("out.stats.json", "json")]
+    #[case("out.bin.zst", "bin.zst")]
+    #[case("out.vcf.gz.tbi", "tbi")]
+    #[case("README", "unknown")]
+    fn guess_format(#[case] path: &str, #[case] expected: &str) {
+        assert_eq!(super::guess_format(path), expected);
+    }
+
+    #[test]
+    fn add_and_write() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_vcf = tmpdir.join("out.vcf").to_string_lossy().to_string();
+        std::fs::write(&path_vcf, "content")?;
+        std::fs::write(format!("{}.tbi", &path_vcf), "")?;
+        let path_db = tmpdir.join("db").to_string_lossy().to_string();
+        std::fs::create_dir_all(format!("{}/sub", &path_db))?;
+        std::fs::write(format!("{}/CURRENT", &path_db), "x")?;
+        std::fs::write(format!("{}/sub/000001.sst", &path_db), "y")?;
+
+        let mut manifest = Manifest::new("seqvars ingest");
+        manifest.add_vcf(&path_vcf, "seqvars-ingested-vcf")?;
+        manifest.add(&tmpdir.join("missing.json").to_string_lossy(), "qc-json")?;
+        manifest.add("s3://bucket/out.vcf", "seqvars-ingested-vcf")?;
+        manifest.add_dir(&path_db, "seqvars-aggregate-rocksdb", "rocksdb")?;
+
+        let summary = manifest
+            .files
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{}:{}:{}:{}",
+                    entry.path.strip_prefix(tmpdir.to_str().unwrap()).unwrap(),
+                    entry.role,
+                    entry.format,
+                    entry.size
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                "/out.vcf:seqvars-ingested-vcf:vcf:7",
+                "/out.vcf.tbi:vcf-index:tbi:0",
+                "/db/CURRENT:seqvars-aggregate-rocksdb:rocksdb:1",
+                "/db/sub/000001.sst:seqvars-aggregate-rocksdb:rocksdb:1",
+            ]
+        );
+        assert_eq!(
+            manifest.files[0].sha256,
+            "ed7002b439e9ac845f22357d822bac1444730fbdb6016d3ec9432297b9ec9f73"
+        );
+
+        let path_manifest = tmpdir.join("manifest.json").to_string_lossy().to_string();
+        manifest.write(&path_manifest)?;
+        let read: Manifest = serde_json::from_reader(std::fs::File::open(&path_manifest)?)?;
+        assert_eq!(read, manifest);
+
+        Ok(())
+    }
+}
@@ -9,8 +9,26 @@ use clap_verbosity_flag::{InfoLevel, Verbosity};
 use indexmap::IndexMap;
 use noodles_vcf as vcf;
 
+use self::interval::{Interval, Topology};
+
+pub mod cytoband;
+pub mod interval;
+pub mod io;
+pub mod karyotype;
+pub mod kv;
+pub mod lock;
+pub mod manifest;
 pub mod noodles;
+pub mod partial;
+pub mod pbs;
+pub mod perf;
+pub mod phenopacket;
+pub mod record;
+pub mod reference;
 pub mod s3;
+pub mod shutdown;
+pub mod tx_trees;
+pub mod validate;
 
 /// Commonly used command line arguments.
 #[derive(Parser, Debug)]
@@ -18,12 +36,21 @@ pub struct Args {
     /// Verbosity of the program
     #[clap(flatten)]
     pub verbose: Verbosity<InfoLevel>,
+    /// Compression level of `.gz`/`.bgz` (0-9) and `.zst` (1-22) output files, the
+    /// format's default if not given
+    #[clap(long, global = true)]
+    pub compression_level: Option<i32>,
+    /// Path to write a JSON manifest of the produced files to
+    #[clap(long, global = true)]
+    pub path_manifest: Option<String>,
 }
 
 impl Default for Args {
     fn default() -> Self {
         Self {
             verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
         }
     }
 }
@@ -66,20 +93,20 @@ pub fn build_chrom_map() -> IndexMap<String, usize> {
     result
 }
 
-// Compute reciprocal overlap between two ranges.
-pub fn reciprocal_overlap(lhs: Range<i32>, rhs: Range<i32>) -> f32 {
-    let lhs_b = lhs.start;
-    let lhs_e = lhs.end;
-    let rhs_b = rhs.start;
-    let rhs_e = rhs.end;
-    let ovl_b = std::cmp::max(lhs_b, rhs_b);
-    let ovl_e = std::cmp::min(lhs_e, rhs_e);
-    if ovl_b >= ovl_e {
+// Compute reciprocal overlap between two ranges on a contig with the given topology.
+//
+// On circular contigs, ranges with `end < start` span the origin.
+pub fn reciprocal_overlap(lhs: Range<i32>, rhs: Range<i32>, topology: Topology) -> f32 {
+    let lhs = Interval::from(lhs);
+    let rhs = Interval::from(rhs);
+    let lhs_len = lhs.len(topology);
+    let rhs_len = rhs.len(topology);
+    if lhs_len == 0 || rhs_len == 0 {
         0f32
     } else {
-        let ovl_len = (ovl_e - ovl_b) as f32;
-        let x1 = ovl_len / (lhs_e - lhs_b) as f32;
-        let x2 = ovl_len / (rhs_e - rhs_b) as f32;
+        let ovl_len = lhs.overlap(&rhs, topology) as f32;
+        let x1 = ovl_len / lhs_len as f32;
+        let x2 = ovl_len / rhs_len as f32;
         x1.min(x2)
     }
 }
@@ -167,6 +194,18 @@ impl std::str::FromStr for GenomeRelease {
     }
 }
 
+/// Output file format for the `query` subcommands.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// TSV with the payload as JSON string, ready for import into the database.
+    #[default]
+    Tsv,
+    /// One JSON object per line with the payload as nested object.
+    Jsonl,
+    /// Length-delimited protobuf messages as defined in `varfish.v1.query`.
+    Protobuf,
+}
+
 /// Helper type for encoding genotypes in parsing.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Genotype {
@@ -183,18 +222,29 @@ pub enum Genotype {
 impl std::str::FromStr for Genotype {
     type Err = anyhow::Error;
 
+    /// Parse from haploid ("1"), diploid ("0/1"), or phased ("0|1") genotype strings
+    /// with only one alternate allele.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(match s {
-            "0/0" | "0|0" | "0" => Genotype::HomRef,
-            "0/1" | "1/0" | "0|1" | "1|0" => Genotype::Het,
-            "1/1" | "1|1" | "1" => Genotype::HomAlt,
-            "./." | "./0" | "./1" | "0/." | "1/." => Genotype::WithNoCall,
-            _ => anyhow::bail!("invalid genotype value: {:?}", s),
+        let (mut count_ref, mut count_alt, mut count_no_call) = (0, 0, 0);
+        for allele in s.split(['/', '|']) {
+            match allele {
+                "0" => count_ref += 1,
+                "1" => count_alt += 1,
+                "." => count_no_call += 1,
+                _ => anyhow::bail!("invalid genotype value: {:?}", s),
+            }
+        }
+        Ok(match (count_ref, count_alt, count_no_call) {
+            (_, _, 1..) => Genotype::WithNoCall,
+            (1.., 0, 0) => Genotype::HomRef,
+            (0, 1.., 0) => Genotype::HomAlt,
+            (1.., 1.., 0) => Genotype::Het,
+            _ => unreachable!("split always yields at least one allele"),
         })
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Chrom {
     Auto, // or chrMT, but does not matter for carrier computation
     X,
@@ -231,6 +281,18 @@ mod tests {
     }
 }
 
+/// Return the hex-encoded SHA256 checksum of the file at `path`.
+pub fn sha256_checksum(path: &str) -> Result<String, anyhow::Error> {
+    use sha2::Digest as _;
+
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| anyhow::anyhow!("could not open {} for checksum: {}", path, e))?;
+    let mut hasher = sha2::Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .map_err(|e| anyhow::anyhow!("could not read {} for checksum: {}", path, e))?;
+    Ok(base16ct::lower::encode_string(&hasher.finalize()))
+}
+
 /// Return the version of the `varfish-server-worker` crate and `x.y.z` in tests.
 pub fn worker_version() -> &'static str {
     if cfg!(test) {
@@ -240,6 +302,92 @@ pub fn worker_version() -> &'static str {
     }
 }
 
+/// Overwrite the top-level keys of the JSON object `base` with the ones of `overrides`.
+///
+/// Used for applying the settings of a query JSON on top of a preset.
+pub fn overlay_json(
+    mut base: serde_json::Value,
+    overrides: serde_json::Value,
+) -> serde_json::Value {
+    if let (Some(base), serde_json::Value::Object(overrides)) = (base.as_object_mut(), overrides) {
+        base.extend(overrides);
+    }
+    base
+}
+
+/// Canonical contigs of GRCh37 with their lengths.
+pub const CONTIGS_37: [(&str, usize); 25] = [
+    ("1", 249250621),
+    ("2", 243199373),
+    ("3", 198022430),
+    ("4", 191154276),
+    ("5", 180915260),
+    ("6", 171115067),
+    ("7", 159138663),
+    ("8", 146364022),
+    ("9", 141213431),
+    ("10", 135534747),
+    ("11", 135006516),
+    ("12", 133851895),
+    ("13", 115169878),
+    ("14", 107349540),
+    ("15", 102531392),
+    ("16", 90354753),
+    ("17", 81195210),
+    ("18", 78077248),
+    ("19", 59128983),
+    ("20", 63025520),
+    ("21", 48129895),
+    ("22", 51304566),
+    ("X", 155270560),
+    ("Y", 59373566),
+    ("MT", 16569),
+];
+
+/// Canonical contigs of GRCh38 with their lengths.
+pub const CONTIGS_38: [(&str, usize); 25] = [
+    ("chr1", 248956422),
+    ("chr2", 242193529),
+    ("chr3", 198295559),
+    ("chr4", 190214555),
+    ("chr5", 181538259),
+    ("chr6", 170805979),
+    ("chr7", 159345973),
+    ("chr8", 145138636),
+    ("chr9", 138394717),
+    ("chr10", 133797422),
+    ("chr11", 135086622),
+    ("chr12", 133275309),
+    ("chr13", 114364328),
+    ("chr14", 107043718),
+    ("chr15", 101991189),
+    ("chr16", 90338345),
+    ("chr17", 83257441),
+    ("chr18", 80373285),
+    ("chr19", 58617616),
+    ("chr20", 64444167),
+    ("chr21", 46709983),
+    ("chr22", 50818468),
+    ("chrX", 156040895),
+    ("chrY", 57227415),
+    ("chrM", 16569),
+];
+
+/// Return the length of the canonical contig `chrom` in `genome_release`.
+///
+/// The chromosome name is canonicalized so both "chr1" and "1" are found.
+pub fn contig_length(genome_release: GenomeRelease, chrom: &str) -> Option<usize> {
+    let contigs = match genome_release {
+        GenomeRelease::Grch37 => &CONTIGS_37,
+        GenomeRelease::Grch38 => &CONTIGS_38,
+    };
+    let chrom = annonars::common::cli::canonicalize(chrom);
+    contigs
+        .iter()
+        .find(|(name, _)| annonars::common::cli::canonicalize(name) == chrom)
+        .map(|(_, length)| *length)
+}
+
 /// Add contigs for GRCh37.
 pub fn add_contigs_37(
     builder: vcf::header::Builder,
@@ -249,33 +397,7 @@ pub fn add_contigs_37(
 
     let mut builder = builder;
 
-    let specs: &[(&str, usize); 25] = &[
-        ("1", 249250621),
-        ("2", 243199373),
-        ("3", 198022430),
-        ("4", 191154276),
-        ("5", 180915260),
-        ("6", 171115067),
-        ("7", 159138663),
-        ("8", 146364022),
-        ("9", 141213431),
-        ("10", 135534747),
-        ("11", 135006516),
-        ("12", 133851895),
-        ("13", 115169878),
-        ("14", 107349540),
-        ("15", 102531392),
-        ("16", 90354753),
-        ("17", 81195210),
-        ("18", 78077248),
-        ("19", 59128983),
-        ("20", 63025520),
-        ("21", 48129895),
-        ("22", 51304566),
-        ("X", 155270560),
-        ("Y", 59373566),
-        ("MT", 16569),
-    ];
+    let specs = &CONTIGS_37;
 
     for (contig, length) in specs {
         builder = builder.add_contig(
@@ -410,33 +532,7 @@ pub fn add_contigs_38(
 
     let mut builder = builder;
 
-    let specs: &[(&str, usize); 25] = &[
-        ("chr1", 248956422),
-        ("chr2", 242193529),
-        ("chr3", 198295559),
-        ("chr4", 190214555),
-        ("chr5", 181538259),
-        ("chr6", 170805979),
-        ("chr7", 159345973),
-        ("chr8", 145138636),
-        ("chr9", 138394717),
-        ("chr10", 133797422),
-        ("chr11", 135086622),
-        ("chr12", 133275309),
-        ("chr13", 114364328),
-        ("chr14", 107043718),
-        ("chr15", 101991189),
-        ("chr16", 90338345),
-        ("chr17", 83257441),
-        ("chr18", 80373285),
-        ("chr19", 58617616),
-        ("chr20", 64444167),
-        ("chr21", 46709983),
-        ("chr22", 50818468),
-        ("chrX", 156040895),
-        ("chrY", 57227415),
-        ("chrM", 16569),
-    ];
+    let specs = &CONTIGS_38;
 
     for (contig, length) in specs {
         builder = builder.add_contig(
@@ -529,6 +625,51 @@ macro_rules! flush_and_shutdown {
 mod test {
     use noodles_vcf as vcf;
 
+    use super::Genotype;
+
+    #[rstest::rstest]
+    #[case("0", Genotype::HomRef)]
+    #[case("0/0", Genotype::HomRef)]
+    #[case("0|0", Genotype::HomRef)]
+    #[case("1", Genotype::HomAlt)]
+    #[case("1/1", Genotype::HomAlt)]
+    #[case("1|1", Genotype::HomAlt)]
+    #[case("0/1", Genotype::Het)]
+    #[case("1|0", Genotype::Het)]
+    #[case(".", Genotype::WithNoCall)]
+    #[case("./.", Genotype::WithNoCall)]
+    #[case(".|1", Genotype::WithNoCall)]
+    #[case("0/.", Genotype::WithNoCall)]
+    fn genotype_from_str(#[case] s: &str, #[case] expected: Genotype) -> Result<(), anyhow::Error> {
+        assert_eq!(s.parse::<Genotype>()?, expected);
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case("")]
+    #[case("2")]
+    #[case("0/2")]
+    #[case("0-1")]
+    fn genotype_from_str_invalid(#[case] s: &str) {
+        assert!(s.parse::<Genotype>().is_err());
+    }
+
+    #[rstest::rstest]
+    #[case(crate::common::GenomeRelease::Grch37, "1", Some(249250621))]
+    #[case(crate::common::GenomeRelease::Grch37, "chrX", Some(155270560))]
+    #[case(crate::common::GenomeRelease::Grch38, "1", Some(248956422))]
+    #[case(crate::common::GenomeRelease::Grch38, "chrM", Some(16569))]
+    #[case(crate::common::GenomeRelease::Grch38, "MT", Some(16569))]
+    #[case(crate::common::GenomeRelease::Grch38, "chrUn", None)]
+    fn contig_length(
+        #[case] genome_release: super::GenomeRelease,
+        #[case] chrom: &str,
+        #[case] expected: Option<usize>,
+    ) {
+        assert_eq!(super::contig_length(genome_release, chrom), expected);
+    }
+
     #[test]
     fn trace_rss_now_smoke() {
         super::trace_rss_now();
@@ -552,10 +693,32 @@ mod test {
         #[case] rhs: std::ops::Range<i32>,
         #[case] expected: f32,
     ) {
-        let actual = super::reciprocal_overlap(lhs, rhs);
+        let actual = super::reciprocal_overlap(lhs, rhs, super::interval::Topology::Linear);
         assert!(float_cmp::approx_eq!(f32, expected, actual, ulps = 2))
     }
 
+    // Ranges spanning the origin are given as tuples, as `end < start`.
+    #[rstest::rstest]
+    #[case((0, 10), (5, 15), 0.5)]
+    #[case((16_469, 100), (16_519, 150), 0.75)]
+    #[case((16_469, 100), (0, 100), 0.5)]
+    #[case((16_469, 100), (200, 300), 0.0)]
+    fn reciprocal_overlap_circular(
+        #[case] lhs: (i32, i32),
+        #[case] rhs: (i32, i32),
+        #[case] expected: f32,
+    ) {
+        use super::interval::{Topology, MT_LENGTH};
+
+        let topology = Topology::Circular { length: MT_LENGTH };
+        let actual = super::reciprocal_overlap(lhs.0..lhs.1, rhs.0..rhs.1, topology);
+        assert!(float_cmp::approx_eq!(f32, expected, actual, ulps = 2));
+        assert_eq!(
+            super::reciprocal_overlap(lhs.0..lhs.1, rhs.0..rhs.1, Topology::Linear),
+            if lhs.0 < lhs.1 { expected } else { 0.0 }
+        );
+    }
+
     #[rstest::rstest]
     #[case("ENSG0000000142", 142)]
     #[case("42", 42)]
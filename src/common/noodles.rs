@@ -9,19 +9,121 @@
 
 use async_compression::tokio::bufread::GzipDecoder;
 use mehari::common::{
-    io::{std::is_gz, tokio::open_read_maybe_gz},
-    noodles::AsyncVcfReader,
+    io::std::is_gz,
+    noodles::{AsyncVcfReader, AsyncVcfWriter},
 };
 use noodles_bgzf as bgzf;
 use noodles_core::Position;
-use noodles_csi::{self as csi, binning_index::index::reference_sequence::bin::Chunk};
+use noodles_csi::{
+    self as csi,
+    binning_index::index::{
+        header::ReferenceSequenceNames,
+        reference_sequence::{bin::Chunk, index::BinnedIndex},
+    },
+};
 use noodles_tabix as tabix;
 use noodles_vcf as vcf;
-use std::{path::Path, pin::Pin};
-use tokio::io::{AsyncBufRead, BufReader};
+use std::{path::Path, pin::Pin, sync::Arc};
+use tokio::io::{AsyncBufRead, AsyncWrite, BufReader};
+
+use crate::common::{
+    io::tokio::open_read_maybe_gz,
+    perf::{CountingWriter, WriteCounters},
+};
+
+/// Minimal shift for CSI indices, as for TBI.
+const CSI_MIN_SHIFT: u8 = 14;
+
+/// Default depth for CSI indices, as for TBI.
+const CSI_DEFAULT_DEPTH: u8 = 5;
+
+/// The index format to write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexFormat {
+    /// Tabix index (`.tbi`).
+    Tbi,
+    /// Coordinate-sorted index (`.csi`).
+    Csi,
+}
+
+impl IndexFormat {
+    /// Return the file name suffix of the index.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            IndexFormat::Tbi => "tbi",
+            IndexFormat::Csi => "csi",
+        }
+    }
+}
+
+/// Choice of index to write for bgzip-compressed output, selectable on the command line.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WriteIndex {
+    /// Write a tabix index.
+    Tbi,
+    /// Write a CSI index.
+    Csi,
+    /// Write a tabix index unless a contig is too long for tabix.
+    #[default]
+    Auto,
+}
+
+impl WriteIndex {
+    /// Resolve to the index format to use for a file with the given `header`.
+    pub fn resolve(&self, header: &vcf::Header) -> IndexFormat {
+        match self {
+            WriteIndex::Tbi => IndexFormat::Tbi,
+            WriteIndex::Csi => IndexFormat::Csi,
+            WriteIndex::Auto => {
+                if max_contig_len(header) > tbi_max_len() {
+                    IndexFormat::Csi
+                } else {
+                    IndexFormat::Tbi
+                }
+            }
+        }
+    }
+}
+
+/// Maximal contig length supported by tabix.
+fn tbi_max_len() -> usize {
+    1 << (CSI_MIN_SHIFT + 3 * CSI_DEFAULT_DEPTH)
+}
+
+/// Return the length of the longest contig in `header`, 0 if there is none.
+fn max_contig_len(header: &vcf::Header) -> usize {
+    header
+        .contigs()
+        .values()
+        .filter_map(|contig| contig.length())
+        .max()
+        .unwrap_or_default()
+}
+
+/// Return the smallest CSI depth that can represent `max_len` positions.
+fn csi_depth(max_len: usize) -> u8 {
+    let mut depth = CSI_DEFAULT_DEPTH;
+    while (1usize << (CSI_MIN_SHIFT + 3 * depth)) < max_len {
+        depth += 1;
+    }
+    depth
+}
 
 /// Build TBI for file at `path_src` and write to `path_dst`.
 pub async fn build_tbi<S, D>(path_src: S, path_dst: D) -> Result<(), anyhow::Error>
+where
+    S: AsRef<std::path::Path>,
+    D: AsRef<std::path::Path>,
+{
+    build_index(path_src, path_dst, IndexFormat::Tbi).await
+}
+
+/// Build index in the given `format` for file at `path_src` and write to `path_dst`.
+pub async fn build_index<S, D>(
+    path_src: S,
+    path_dst: D,
+    format: IndexFormat,
+) -> Result<(), anyhow::Error>
 where
     S: AsRef<std::path::Path>,
     D: AsRef<std::path::Path>,
@@ -30,22 +132,97 @@ where
         .await
         .map(bgzf::AsyncReader::new)
         .map(vcf::AsyncReader::new)
-        .map_err(|e| anyhow::anyhow!("error input file for tbi creation: {}", e))?;
+        .map_err(|e| anyhow::anyhow!("error input file for index creation: {}", e))?;
 
     let header = reader
         .read_header()
         .await
         .map_err(|e| anyhow::anyhow!("error reading header: {}", e))?;
 
-    let mut record = vcf::Record::default();
+    match format {
+        IndexFormat::Tbi => {
+            let mut indexer = tabix::index::Indexer::default();
+            indexer.set_header(csi::binning_index::index::header::Builder::vcf().build());
+
+            index_records(&mut reader, &header, |name, start, end, chunk| {
+                indexer
+                    .add_record(name, start, end, chunk)
+                    .map_err(|e| anyhow::anyhow!("error adding record to tabix index: {}", e))
+            })
+            .await?;
+
+            let index = indexer.build();
+
+            let mut writer = tokio::fs::File::create(path_dst.as_ref())
+                .await
+                .map(tokio::io::BufWriter::new)
+                .map(tabix::AsyncWriter::new)
+                .map_err(|e| anyhow::anyhow!("error output file for tbi creation: {}", e))?;
+            writer
+                .write_index(&index)
+                .await
+                .map_err(|e| anyhow::anyhow!("error writing tabix index: {}", e))?;
+            writer
+                .shutdown()
+                .await
+                .map_err(|e| anyhow::anyhow!("error flushing tabix index: {}", e))?;
+        }
+        IndexFormat::Csi => {
+            let mut names = ReferenceSequenceNames::new();
+            let mut indexer = csi::binning_index::Indexer::<BinnedIndex>::new(
+                CSI_MIN_SHIFT,
+                csi_depth(max_contig_len(&header)),
+            );
+
+            index_records(&mut reader, &header, |name, start, end, chunk| {
+                let (reference_sequence_id, _) = names.insert_full(name.to_string());
+                indexer
+                    .add_record(Some((reference_sequence_id, start, end, true)), chunk)
+                    .map_err(|e| anyhow::anyhow!("error adding record to CSI index: {}", e))
+            })
+            .await?;
+
+            let reference_sequence_count = names.len();
+            let index = indexer
+                .set_header(
+                    csi::binning_index::index::header::Builder::vcf()
+                        .set_reference_sequence_names(names)
+                        .build(),
+                )
+                .build(reference_sequence_count);
+
+            // Note that the async CSI writer of noodles-csi does not write the number of
+            // reference sequences, so we use the sync writer.
+            let path_dst = path_dst.as_ref().to_path_buf();
+            tokio::task::spawn_blocking(move || csi::write(path_dst, &index))
+                .await
+                .map_err(|e| anyhow::anyhow!("error joining CSI writer task: {}", e))?
+                .map_err(|e| anyhow::anyhow!("error writing CSI index: {}", e))?;
+        }
+    }
+
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    Ok(())
+}
 
-    let mut indexer = tabix::index::Indexer::default();
-    indexer.set_header(csi::binning_index::index::header::Builder::vcf().build());
+/// Read all records from `reader` and call `add_record` with the reference sequence name,
+/// start and end position, and the chunk of each record.
+async fn index_records<R, F>(
+    reader: &mut vcf::AsyncReader<bgzf::AsyncReader<R>>,
+    header: &vcf::Header,
+    mut add_record: F,
+) -> Result<(), anyhow::Error>
+where
+    R: tokio::io::AsyncRead + Unpin,
+    F: FnMut(&str, Position, Position, Chunk) -> Result<(), anyhow::Error>,
+{
+    let mut record = vcf::Record::default();
 
     let mut start_position = reader.get_ref().virtual_position();
 
     while reader
-        .read_record(&header, &mut record)
+        .read_record(header, &mut record)
         .await
         .map_err(|e| anyhow::anyhow!("problem reading record: {}", e))?
         != 0
@@ -66,33 +243,11 @@ where
             })
             .map_err(|e| anyhow::anyhow!("error converting end position: {}", e))?;
 
-        indexer
-            .add_record(&reference_sequence_name, start, end, chunk)
-            .map_err(|e| anyhow::anyhow!("error adding record to tabix index: {}", e))?;
+        add_record(&reference_sequence_name, start, end, chunk)?;
 
         start_position = end_position;
     }
 
-    let index = indexer.build();
-
-    {
-        let mut writer = tokio::fs::File::create(path_dst.as_ref())
-            .await
-            .map(tokio::io::BufWriter::new)
-            .map(tabix::AsyncWriter::new)
-            .map_err(|e| anyhow::anyhow!("error output file for tbi creation: {}", e))?;
-        writer
-            .write_index(&index)
-            .await
-            .map_err(|e| anyhow::anyhow!("error writing tabix index: {}", e))?;
-        writer
-            .shutdown()
-            .await
-            .map_err(|e| anyhow::anyhow!("error flushing tabix index: {}", e))?;
-
-        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-    }
-
     Ok(())
 }
 
@@ -140,7 +295,10 @@ where
 pub async fn open_vcf_readers(paths: &[String]) -> Result<Vec<AsyncVcfReader>, anyhow::Error> {
     let mut result = Vec::new();
     for path in paths.iter() {
-        let buf_read = if super::s3::s3_mode() && !path.starts_with('/') {
+        let buf_read = if super::s3::s3_mode()
+            && !path.starts_with('/')
+            && !super::io::remote::is_remote(path)
+        {
             s3_open_read_maybe_gz(path).await?
         } else {
             open_read_maybe_gz(path).await?
@@ -156,6 +314,7 @@ pub async fn open_vcf_readers(paths: &[String]) -> Result<Vec<AsyncVcfReader>, a
 ///
 /// - If `path_in` is "-" then open stdin and read as plain text.
 /// - If environment variable `AWS_PROFILE` is set to "varfish-s3" then enable S3 mode.
+/// - If `path_in` starts with `s3://`, `http://`, or `https://` then stream the remote file.
 /// - If `path_in` is absolute or S3 mode is disabled then open `path_in` as local file
 /// - Otherwise, attempt to open `path_in` as S3 object.
 pub async fn open_vcf_reader(path_in: &str) -> Result<AsyncVcfReader, anyhow::Error> {
@@ -163,7 +322,7 @@ pub async fn open_vcf_reader(path_in: &str) -> Result<AsyncVcfReader, anyhow::Er
         Ok(s) => s == "varfish-s3",
         _ => false,
     };
-    if s3_mode && !path_in.starts_with('/') {
+    if s3_mode && !path_in.starts_with('/') && !super::io::remote::is_remote(path_in) {
         Ok(vcf::AsyncReader::new(
             s3_open_read_maybe_gz(path_in)
                 .await
@@ -178,6 +337,37 @@ pub async fn open_vcf_reader(path_in: &str) -> Result<AsyncVcfReader, anyhow::Er
     }
 }
 
+/// Helper function that opens one VCF writer at the given path.
+///
+/// In contrast to `mehari::common::noodles::open_vcf_writer()`, this also supports zstd
+/// compression for `.zst` files and the given `compression_level`, see
+/// `crate::common::io::tokio::open_write_maybe_bgzf()`.
+pub async fn open_vcf_writer(
+    path_out: &str,
+    compression_level: Option<i32>,
+) -> Result<AsyncVcfWriter, anyhow::Error> {
+    Ok(vcf::AsyncWriter::new(
+        crate::common::io::tokio::open_write_maybe_bgzf(path_out, None, compression_level)
+            .await
+            .map_err(|e| anyhow::anyhow!("could not build VCF writer: {}", e))?,
+    ))
+}
+
+/// Same as `open_vcf_writer()` but also return the counters of the (uncompressed) bytes
+/// written, see `CountingWriter`.
+pub async fn open_vcf_writer_with_counters(
+    path_out: &str,
+    compression_level: Option<i32>,
+) -> Result<(AsyncVcfWriter, Arc<WriteCounters>), anyhow::Error> {
+    let (writer, counters) = CountingWriter::new(
+        crate::common::io::tokio::open_write_maybe_bgzf(path_out, None, compression_level)
+            .await
+            .map_err(|e| anyhow::anyhow!("could not build VCF writer: {}", e))?,
+    );
+    let writer: Pin<Box<dyn AsyncWrite>> = Box::pin(writer);
+    Ok((vcf::AsyncWriter::new(writer), counters))
+}
+
 #[cfg(test)]
 mod test {
     #[tokio::test]
@@ -199,4 +389,28 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn build_csi() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+
+        let path_in: String = "tests/seqvars/ingest/NA12878_dragen.vcf.gz".into();
+        let path_out = tmpdir.join("out.vcf.gz.csi");
+        super::build_index(&path_in, &path_out, super::IndexFormat::Csi).await?;
+
+        let index = noodles_csi::read(&path_out)?;
+        assert_eq!(index.reference_sequences().len(), 2);
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case(1_000, 5)]
+    #[case(1 << 29, 5)]
+    #[case((1 << 29) + 1, 6)]
+    #[case(1 << 32, 6)]
+    #[case((1 << 32) + 1, 7)]
+    fn csi_depth(#[case] max_len: usize, #[case] expected: u8) {
+        assert_eq!(super::csi_depth(max_len), expected);
+    }
 }
@@ -0,0 +1,142 @@
+//! Handling of partially written output files.
+//!
+//! If a subcommand fails partway, its output files must not be mistaken for complete
+//! ones by downstream importers.  The `PartialOutputGuard` removes the output file or
+//! renames it to `<path>.partial` unless it has been marked as finished.
+
+use super::noodles::IndexFormat;
+
+/// Suffix appended to the paths of partially written output files.
+pub const PARTIAL_SUFFIX: &str = "partial";
+
+/// Return the path of the partially written output file for `path`.
+pub fn partial_path(path: &str) -> String {
+    format!("{}.{}", path, PARTIAL_SUFFIX)
+}
+
+/// What to do with an output file that has not been finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnFailure {
+    /// Remove the output file.
+    Remove,
+    /// Rename the output file to `<path>.partial` so it can be resumed from.
+    Rename,
+}
+
+/// Guard for an output file that is cleaned up on drop unless marked as finished.
+///
+/// Index files next to the output file are always removed on failure.
+#[derive(Debug)]
+pub struct PartialOutputGuard {
+    /// Path to the output file.
+    path: String,
+    /// What to do on failure.
+    on_failure: OnFailure,
+    /// Whether the output has been finished.
+    finished: bool,
+}
+
+impl PartialOutputGuard {
+    /// Construct guard for the output file at `path`.
+    pub fn new(path: &str, on_failure: OnFailure) -> Self {
+        Self {
+            path: path.to_string(),
+            on_failure,
+            finished: false,
+        }
+    }
+
+    /// Mark the output file as complete.
+    pub fn finish(mut self) {
+        self.finished = true;
+    }
+
+    /// Clean up the unfinished output file.
+    fn cleanup(&self) -> Result<(), std::io::Error> {
+        for format in [IndexFormat::Tbi, IndexFormat::Csi] {
+            let path_index = format!("{}.{}", &self.path, format.suffix());
+            if std::path::Path::new(&path_index).exists() {
+                std::fs::remove_file(&path_index)?;
+            }
+        }
+
+        if !std::path::Path::new(&self.path).exists() {
+            return Ok(());
+        }
+        match self.on_failure {
+            OnFailure::Remove => {
+                tracing::warn!(
+                    "{}: removing partially written output {}",
+                    crate::codes::COMMON_PARTIAL_OUTPUT_KEPT,
+                    &self.path
+                );
+                std::fs::remove_file(&self.path)
+            }
+            OnFailure::Rename => {
+                let path_partial = partial_path(&self.path);
+                tracing::warn!(
+                    "{}: renaming partially written output {} to {}",
+                    crate::codes::COMMON_PARTIAL_OUTPUT_KEPT,
+                    &self.path,
+                    &path_partial
+                );
+                std::fs::rename(&self.path, &path_partial)
+            }
+        }
+    }
+}
+
+impl Drop for PartialOutputGuard {
+    fn drop(&mut self) {
+        if !self.finished {
+            if let Err(e) = self.cleanup() {
+                tracing::error!(
+                    "could not clean up partially written output {}: {}",
+                    &self.path,
+                    e
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{OnFailure, PartialOutputGuard};
+
+    #[rstest::rstest]
+    #[case::remove_unfinished(OnFailure::Remove, false, false, false)]
+    #[case::rename_unfinished(OnFailure::Rename, false, false, true)]
+    #[case::remove_finished(OnFailure::Remove, true, true, false)]
+    #[case::rename_finished(OnFailure::Rename, true, true, false)]
+    fn guard(
+        #[case] on_failure: OnFailure,
+        #[case] finish: bool,
+        #[case] expected_out: bool,
+        #[case] expected_partial: bool,
+    ) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("out.vcf.gz").to_str().unwrap().to_string();
+        std::fs::write(&path, "data")?;
+        std::fs::write(format!("{}.tbi", &path), "index")?;
+
+        let guard = PartialOutputGuard::new(&path, on_failure);
+        if finish {
+            guard.finish();
+        } else {
+            drop(guard);
+        }
+
+        assert_eq!(std::path::Path::new(&path).exists(), expected_out);
+        assert_eq!(
+            std::path::Path::new(&format!("{}.tbi", &path)).exists(),
+            expected_out
+        );
+        assert_eq!(
+            std::path::Path::new(&super::partial_path(&path)).exists(),
+            expected_partial
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,3 @@
+//! Data structures for (de-)serialization of query results as generated by `prost-build`.
+
+include!(concat!(env!("OUT_DIR"), "/varfish.v1.query.rs"));
@@ -0,0 +1,258 @@
+//! Performance counters for the storage and compression layers.
+//!
+//! The counters are reported at the end of a run such that performance regressions can be
+//! attributed to storage (RocksDB block cache misses, bytes read) or CPU (output
+//! compression) without re-running under a profiler.
+
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use thousands::Separable;
+use tokio::io::AsyncWrite;
+
+/// Counters of a RocksDB database, parsed from its statistics.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RocksDbCounters {
+    /// Number of block cache hits.
+    pub block_cache_hit: u64,
+    /// Number of block cache misses.
+    pub block_cache_miss: u64,
+    /// Number of bytes read by point lookups and iterators.
+    pub bytes_read: u64,
+}
+
+impl RocksDbCounters {
+    /// Parse the ticker counts from the `statistics` string of `rocksdb::Options`.
+    ///
+    /// Ticker lines look like `rocksdb.block.cache.hit COUNT : 42`; other lines, e.g., of
+    /// histograms, are ignored.
+    pub fn from_statistics(statistics: &str) -> Self {
+        let mut result = Self::default();
+        for line in statistics.lines() {
+            let tokens = line.split_whitespace().collect::<Vec<_>>();
+            let [name, "COUNT", ":", value] = tokens.as_slice() else {
+                continue;
+            };
+            let Ok(value) = value.parse::<u64>() else {
+                continue;
+            };
+            match *name {
+                "rocksdb.block.cache.hit" => result.block_cache_hit += value,
+                "rocksdb.block.cache.miss" => result.block_cache_miss += value,
+                "rocksdb.bytes.read" | "rocksdb.iter.bytes.read" => result.bytes_read += value,
+                _ => (),
+            }
+        }
+        result
+    }
+
+    /// Return the fraction of block cache lookups that were hits, `None` without lookups.
+    pub fn block_cache_hit_rate(&self) -> Option<f64> {
+        let total = self.block_cache_hit + self.block_cache_miss;
+        (total > 0).then(|| self.block_cache_hit as f64 / total as f64)
+    }
+
+    /// Log the counters of the database `name`.
+    pub fn log(&self, name: &str) {
+        tracing::info!(
+            "RocksDB {}: {} block cache hits, {} misses (hit rate {}), {} read",
+            name,
+            self.block_cache_hit.separate_with_commas(),
+            self.block_cache_miss.separate_with_commas(),
+            self.block_cache_hit_rate()
+                .map(|rate| format!("{:.1}%", rate * 100.0))
+                .unwrap_or_else(|| "n/a".into()),
+            format_bytes(self.bytes_read)
+        );
+    }
+}
+
+impl std::ops::Add for RocksDbCounters {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            block_cache_hit: self.block_cache_hit + rhs.block_cache_hit,
+            block_cache_miss: self.block_cache_miss + rhs.block_cache_miss,
+            bytes_read: self.bytes_read + rhs.bytes_read,
+        }
+    }
+}
+
+/// Format `bytes` in MiB.
+fn format_bytes(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+/// Counters of a `CountingWriter`, shared with the writer.
+#[derive(Debug)]
+pub struct WriteCounters {
+    /// Time of creating the writer.
+    start: std::time::Instant,
+    /// Number of (uncompressed) bytes written.
+    bytes_written: AtomicU64,
+    /// Nanoseconds during which writes were pending, e.g., waiting for compression.
+    stalled_nanos: AtomicU64,
+}
+
+impl Default for WriteCounters {
+    fn default() -> Self {
+        Self {
+            start: std::time::Instant::now(),
+            bytes_written: Default::default(),
+            stalled_nanos: Default::default(),
+        }
+    }
+}
+
+impl WriteCounters {
+    /// Return the number of bytes written.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Relaxed)
+    }
+
+    /// Return the time during which writes were pending.
+    pub fn stalled(&self) -> std::time::Duration {
+        std::time::Duration::from_nanos(self.stalled_nanos.load(Ordering::Relaxed))
+    }
+
+    /// Log the throughput of writing to `path_out`, including the compression ratio if the
+    /// file exists locally.
+    pub fn log(&self, path_out: &str) {
+        let elapsed = self.start.elapsed();
+        let bytes_written = self.bytes_written();
+        let compressed = std::fs::metadata(path_out)
+            .map(|metadata| {
+                format!(
+                    ", {} on disk (ratio {:.2})",
+                    format_bytes(metadata.len()),
+                    bytes_written as f64 / metadata.len().max(1) as f64
+                )
+            })
+            .unwrap_or_default();
+        tracing::info!(
+            "output: {} written in {:?} ({:.1} MiB/s){}, stalled on compression for {:?}",
+            format_bytes(bytes_written),
+            elapsed,
+            bytes_written as f64 / (1024.0 * 1024.0) / elapsed.as_secs_f64().max(1e-9),
+            compressed,
+            self.stalled()
+        );
+    }
+}
+
+/// `AsyncWrite` wrapper that counts the bytes written and the time writes are pending.
+pub struct CountingWriter<W> {
+    /// The wrapped writer.
+    inner: W,
+    /// The shared counters.
+    counters: Arc<WriteCounters>,
+    /// Start of the current pending write, if any.
+    stall_start: Option<std::time::Instant>,
+}
+
+impl<W> CountingWriter<W> {
+    /// Wrap `inner`, returning the writer and its counters.
+    pub fn new(inner: W) -> (Self, Arc<WriteCounters>) {
+        let counters = Arc::new(WriteCounters::default());
+        (
+            Self {
+                inner,
+                counters: counters.clone(),
+                stall_start: None,
+            },
+            counters,
+        )
+    }
+
+    /// Update the stall time for the `poll` result of the wrapped writer.
+    fn track<T>(&mut self, poll: &Poll<T>) {
+        match (poll, self.stall_start) {
+            (Poll::Pending, None) => self.stall_start = Some(std::time::Instant::now()),
+            (Poll::Ready(_), Some(start)) => {
+                self.counters
+                    .stalled_nanos
+                    .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+                self.stall_start = None;
+            }
+            _ => (),
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for CountingWriter<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.track(&poll);
+        if let Poll::Ready(Ok(count)) = &poll {
+            self.counters
+                .bytes_written
+                .fetch_add(*count as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let poll = Pin::new(&mut self.inner).poll_flush(cx);
+        self.track(&poll);
+        poll
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let poll = Pin::new(&mut self.inner).poll_shutdown(cx);
+        self.track(&poll);
+        poll
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::AsyncWriteExt as _;
+
+    use super::{CountingWriter, RocksDbCounters};
+
+    #[test]
+    fn rocksdb_counters_from_statistics() {
+        let statistics = "rocksdb.block.cache.miss COUNT : 10\n\
+                          rocksdb.block.cache.hit COUNT : 30\n\
+                          rocksdb.bytes.read COUNT : 1000\n\
+                          rocksdb.iter.bytes.read COUNT : 24\n\
+                          rocksdb.db.get.micros P50 : 1.0 P95 : 2.0 P99 : 3.0 P100 : 4.0 \
+                          COUNT : 5 SUM : 6\n";
+        let counters = RocksDbCounters::from_statistics(statistics);
+
+        assert_eq!(
+            counters,
+            RocksDbCounters {
+                block_cache_hit: 30,
+                block_cache_miss: 10,
+                bytes_read: 1024,
+            }
+        );
+        assert_eq!(counters.block_cache_hit_rate(), Some(0.75));
+        assert_eq!(RocksDbCounters::default().block_cache_hit_rate(), None);
+    }
+
+    #[tokio::test]
+    async fn counting_writer() -> Result<(), anyhow::Error> {
+        let (mut writer, counters) = CountingWriter::new(Vec::new());
+        writer.write_all(b"hello ").await?;
+        writer.write_all(b"world").await?;
+        writer.shutdown().await?;
+
+        assert_eq!(counters.bytes_written(), 11);
+        assert_eq!(writer.inner, b"hello world");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+//! Reading case metadata from GA4GH phenopackets (schema v2, JSON).
+//!
+//! Both a `Family` message with `proband`, `relatives`, and `pedigree` and a single
+//! `Phenopacket` are accepted.  The pedigree of a family is read from its `pedigree`
+//! persons; a single phenopacket describes one affected individual, its `subject`.  The
+//! case UUID is taken from the `id` of the family or phenopacket if it is a UUID.
+
+use indexmap::IndexMap;
+use mehari::ped::{Disease, Individual, PedigreeByName, Sex};
+
+/// Case metadata read from a phenopacket.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CaseMetadata {
+    /// The case UUID, if the identifier of the phenopacket is a UUID.
+    pub case_uuid: Option<uuid::Uuid>,
+    /// The pedigree.
+    pub pedigree: PedigreeByName,
+    /// The observed HPO terms by individual, without duplicates.
+    pub hpo_terms: IndexMap<String, Vec<String>>,
+}
+
+impl CaseMetadata {
+    /// Read the case metadata from the phenopacket JSON file at `path`.
+    pub fn from_path(path: &str) -> Result<Self, anyhow::Error> {
+        let reader = std::io::BufReader::new(
+            std::fs::File::open(path)
+                .map_err(|e| anyhow::anyhow!("could not open phenopacket {}: {}", path, e))?,
+        );
+        let document: input::Document = serde_json::from_reader(reader)
+            .map_err(|e| anyhow::anyhow!("could not parse phenopacket {}: {}", path, e))?;
+        Self::try_from(document).map_err(|e| anyhow::anyhow!("invalid phenopacket {}: {}", path, e))
+    }
+
+    /// Return the observed HPO terms of the affected individuals, without duplicates.
+    pub fn affected_hpo_terms(&self) -> Vec<String> {
+        let mut result = Vec::new();
+        for (name, terms) in &self.hpo_terms {
+            let affected = self
+                .pedigree
+                .individuals
+                .get(name)
+                .map(|individual| individual.disease == Disease::Affected)
+                .unwrap_or_default();
+            if affected {
+                for term in terms {
+                    if !result.contains(term) {
+                        result.push(term.clone());
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+impl TryFrom<input::Document> for CaseMetadata {
+    type Error = anyhow::Error;
+
+    fn try_from(document: input::Document) -> Result<Self, Self::Error> {
+        let (id, pedigree, phenopackets) = match document {
+            input::Document::Family(family) => {
+                let pedigree = family
+                    .pedigree
+                    .ok_or_else(|| anyhow::anyhow!("family {} has no pedigree", &family.id))?;
+                let mut individuals = IndexMap::new();
+                for person in pedigree.persons {
+                    let individual = Individual {
+                        family: person.family_id,
+                        name: person.individual_id.clone(),
+                        father: parent_id(person.paternal_id),
+                        mother: parent_id(person.maternal_id),
+                        sex: sex(person.sex.as_deref()),
+                        disease: disease(person.affected_status.as_deref()),
+                    };
+                    if individuals
+                        .insert(person.individual_id.clone(), individual)
+                        .is_some()
+                    {
+                        anyhow::bail!("duplicate person {} in pedigree", &person.individual_id);
+                    }
+                }
+                let phenopackets = std::iter::once(family.proband)
+                    .chain(family.relatives)
+                    .collect::<Vec<_>>();
+                (family.id, PedigreeByName { individuals }, phenopackets)
+            }
+            input::Document::Phenopacket(phenopacket) => {
+                let subject = phenopacket.subject.as_ref().ok_or_else(|| {
+                    anyhow::anyhow!("phenopacket {} has no subject", &phenopacket.id)
+                })?;
+                let individual = Individual {
+                    family: phenopacket.id.clone(),
+                    name: subject.id.clone(),
+                    father: None,
+                    mother: None,
+                    sex: sex(subject.sex.as_deref()),
+                    disease: Disease::Affected,
+                };
+                let pedigree = PedigreeByName {
+                    individuals: [(subject.id.clone(), individual)].into_iter().collect(),
+                };
+                (phenopacket.id.clone(), pedigree, vec![phenopacket])
+            }
+        };
+
+        let mut hpo_terms = IndexMap::<String, Vec<String>>::new();
+        for phenopacket in phenopackets {
+            let Some(subject) = phenopacket.subject else {
+                continue;
+            };
+            if !pedigree.individuals.contains_key(&subject.id) {
+                anyhow::bail!("subject {} is not in the pedigree", &subject.id);
+            }
+            let terms = hpo_terms.entry(subject.id).or_default();
+            for feature in phenopacket.phenotypic_features {
+                if !feature.excluded && !terms.contains(&feature.type_.id) {
+                    terms.push(feature.type_.id);
+                }
+            }
+        }
+
+        Ok(Self {
+            case_uuid: id.parse().ok(),
+            pedigree,
+            hpo_terms,
+        })
+    }
+}
+
+/// Return the parent from the `paternalId` or `maternalId`, `None` for founders.
+fn parent_id(id: Option<String>) -> Option<String> {
+    id.filter(|id| !id.is_empty() && id != "0")
+}
+
+/// Convert the phenopacket `sex` value.
+fn sex(value: Option<&str>) -> Sex {
+    match value {
+        Some("MALE") => Sex::Male,
+        Some("FEMALE") => Sex::Female,
+        _ => Sex::Unknown,
+    }
+}
+
+/// Convert the phenopacket `affectedStatus` value.
+fn disease(value: Option<&str>) -> Disease {
+    match value {
+        Some("AFFECTED") => Disease::Affected,
+        Some("UNAFFECTED") => Disease::Unaffected,
+        _ => Disease::Unknown,
+    }
+}
+
+/// Module with the subset of the phenopacket schema that we read.
+mod input {
+    use serde::Deserialize;
+
+    /// A family or a single phenopacket.
+    #[derive(Debug, Deserialize)]
+    #[serde(untagged)]
+    pub enum Document {
+        /// A family, recognized by the `proband`.
+        Family(Family),
+        /// A single phenopacket.
+        Phenopacket(Phenopacket),
+    }
+
+    /// A `Family` message.
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Family {
+        /// Identifier of the family.
+        pub id: String,
+        /// The phenopacket of the proband.
+        pub proband: Phenopacket,
+        /// The phenopackets of the relatives.
+        #[serde(default)]
+        pub relatives: Vec<Phenopacket>,
+        /// The pedigree.
+        pub pedigree: Option<Pedigree>,
+    }
+
+    /// A `Phenopacket` message.
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Phenopacket {
+        /// Identifier of the phenopacket.
+        pub id: String,
+        /// The individual described by the phenopacket.
+        pub subject: Option<Subject>,
+        /// The phenotypic features of the subject.
+        #[serde(default)]
+        pub phenotypic_features: Vec<PhenotypicFeature>,
+    }
+
+    /// An `Individual` message, the subject of a phenopacket.
+    #[derive(Debug, Deserialize)]
+    pub struct Subject {
+        /// Identifier of the individual, the sample name.
+        pub id: String,
+        /// The sex, e.g., `FEMALE`.
+        pub sex: Option<String>,
+    }
+
+    /// A `PhenotypicFeature` message.
+    #[derive(Debug, Deserialize)]
+    pub struct PhenotypicFeature {
+        /// The HPO term of the feature.
+        #[serde(rename = "type")]
+        pub type_: OntologyClass,
+        /// Whether the feature has been excluded, i.e., is absent.
+        #[serde(default)]
+        pub excluded: bool,
+    }
+
+    /// An `OntologyClass` message.
+    #[derive(Debug, Deserialize)]
+    pub struct OntologyClass {
+        /// The term ID, e.g., `HP:0001250`.
+        pub id: String,
+    }
+
+    /// A `Pedigree` message.
+    #[derive(Debug, Deserialize)]
+    pub struct Pedigree {
+        /// The persons of the pedigree.
+        #[serde(default)]
+        pub persons: Vec<Person>,
+    }
+
+    /// A `Pedigree.Person` message.
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Person {
+        /// Identifier of the family.
+        pub family_id: String,
+        /// Identifier of the individual, the sample name.
+        pub individual_id: String,
+        /// Identifier of the father, if any.
+        pub paternal_id: Option<String>,
+        /// Identifier of the mother, if any.
+        pub maternal_id: Option<String>,
+        /// The sex, e.g., `FEMALE`.
+        pub sex: Option<String>,
+        /// The affected status, e.g., `AFFECTED`.
+        pub affected_status: Option<String>,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::ped::{Disease, Sex};
+
+    use super::CaseMetadata;
+
+    #[test]
+    fn family() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("family.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "id": "d2bad2ec-a75d-44b9-bd0a-83a3f1331b7c",
+                "proband": {
+                    "id": "index-phenopacket",
+                    "subject": {"id": "index", "sex": "MALE"},
+                    "phenotypicFeatures": [
+                        {"type": {"id": "HP:0001250", "label": "Seizure"}},
+                        {"type": {"id": "HP:0000252", "label": "Microcephaly"}, "excluded": true}
+                    ]
+                },
+                "relatives": [
+                    {
+                        "id": "mother-phenopacket",
+                        "subject": {"id": "mother", "sex": "FEMALE"},
+                        "phenotypicFeatures": [{"type": {"id": "HP:0000365"}}]
+                    }
+                ],
+                "pedigree": {
+                    "persons": [
+                        {"familyId": "FAM", "individualId": "index", "paternalId": "father",
+                         "maternalId": "mother", "sex": "MALE", "affectedStatus": "AFFECTED"},
+                        {"familyId": "FAM", "individualId": "father", "paternalId": "0",
+                         "sex": "MALE", "affectedStatus": "UNAFFECTED"},
+                        {"familyId": "FAM", "individualId": "mother", "sex": "FEMALE"}
+                    ]
+                }
+            }"#,
+        )?;
+
+        let metadata = CaseMetadata::from_path(path.to_str().unwrap())?;
+        assert_eq!(
+            metadata.case_uuid,
+            Some("d2bad2ec-a75d-44b9-bd0a-83a3f1331b7c".parse()?)
+        );
+        let individuals = &metadata.pedigree.individuals;
+        assert_eq!(
+            individuals.keys().collect::<Vec<_>>(),
+            vec!["index", "father", "mother"]
+        );
+        assert_eq!(individuals["index"].father.as_deref(), Some("father"));
+        assert_eq!(individuals["index"].disease, Disease::Affected);
+        assert_eq!(individuals["father"].father, None);
+        assert_eq!(individuals["mother"].sex, Sex::Female);
+        assert_eq!(individuals["mother"].disease, Disease::Unknown);
+        assert_eq!(metadata.hpo_terms["index"], vec!["HP:0001250"]);
+        assert_eq!(metadata.hpo_terms["mother"], vec!["HP:0000365"]);
+        assert_eq!(metadata.affected_hpo_terms(), vec!["HP:0001250"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn single_phenopacket() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("phenopacket.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "id": "case-1",
+                "subject": {"id": "index", "sex": "FEMALE"},
+                "phenotypicFeatures": [{"type": {"id": "HP:0001263"}}]
+            }"#,
+        )?;
+
+        let metadata = CaseMetadata::from_path(path.to_str().unwrap())?;
+        assert_eq!(metadata.case_uuid, None);
+        assert_eq!(metadata.pedigree.individuals.len(), 1);
+        assert_eq!(metadata.pedigree.individuals["index"].sex, Sex::Female);
+        assert_eq!(metadata.affected_hpo_terms(), vec!["HP:0001263"]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,347 @@
+//! Internal model of annotated variant records, decoupled from `noodles_vcf::Record`.
+//!
+//! Code that only inspects records works on `Variant` which owns plain data, is `Send`,
+//! and can be constructed in tests without a VCF header.  The conversion from and to the
+//! `noodles` types happens at the I/O boundary with `Variant::from_vcf()` and
+//! `Variant::to_vcf()`, so changes to the `noodles` API are contained here.
+
+use noodles_vcf as vcf;
+
+use super::Genotype;
+
+/// Value of an `INFO` or `FORMAT` field.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Value {
+    /// A flag, only valid in `INFO`.
+    Flag,
+    /// A 32-bit integer.
+    Integer(i32),
+    /// A single-precision floating-point.
+    Float(f32),
+    /// A character.
+    Character(char),
+    /// A string.
+    String(String),
+    /// An array of 32-bit integers.
+    IntegerArray(Vec<Option<i32>>),
+    /// An array of single-precision floating-points.
+    FloatArray(Vec<Option<f32>>),
+    /// An array of characters.
+    CharacterArray(Vec<Option<char>>),
+    /// An array of strings.
+    StringArray(Vec<Option<String>>),
+}
+
+impl From<&vcf::record::info::field::Value> for Value {
+    fn from(value: &vcf::record::info::field::Value) -> Self {
+        use vcf::record::info::field::{value::Array, Value as V};
+        match value {
+            V::Integer(value) => Value::Integer(*value),
+            V::Float(value) => Value::Float(*value),
+            V::Flag => Value::Flag,
+            V::Character(value) => Value::Character(*value),
+            V::String(value) => Value::String(value.clone()),
+            V::Array(Array::Integer(values)) => Value::IntegerArray(values.clone()),
+            V::Array(Array::Float(values)) => Value::FloatArray(values.clone()),
+            V::Array(Array::Character(values)) => Value::CharacterArray(values.clone()),
+            V::Array(Array::String(values)) => Value::StringArray(values.clone()),
+        }
+    }
+}
+
+impl From<&Value> for vcf::record::info::field::Value {
+    fn from(value: &Value) -> Self {
+        use vcf::record::info::field::{value::Array, Value as V};
+        match value {
+            Value::Flag => V::Flag,
+            Value::Integer(value) => V::Integer(*value),
+            Value::Float(value) => V::Float(*value),
+            Value::Character(value) => V::Character(*value),
+            Value::String(value) => V::String(value.clone()),
+            Value::IntegerArray(values) => V::Array(Array::Integer(values.clone())),
+            Value::FloatArray(values) => V::Array(Array::Float(values.clone())),
+            Value::CharacterArray(values) => V::Array(Array::Character(values.clone())),
+            Value::StringArray(values) => V::Array(Array::String(values.clone())),
+        }
+    }
+}
+
+impl From<&vcf::record::genotypes::sample::Value> for Value {
+    fn from(value: &vcf::record::genotypes::sample::Value) -> Self {
+        use vcf::record::genotypes::sample::{value::Array, Value as V};
+        match value {
+            V::Integer(value) => Value::Integer(*value),
+            V::Float(value) => Value::Float(*value),
+            V::Character(value) => Value::Character(*value),
+            V::String(value) => Value::String(value.clone()),
+            V::Array(Array::Integer(values)) => Value::IntegerArray(values.clone()),
+            V::Array(Array::Float(values)) => Value::FloatArray(values.clone()),
+            V::Array(Array::Character(values)) => Value::CharacterArray(values.clone()),
+            V::Array(Array::String(values)) => Value::StringArray(values.clone()),
+        }
+    }
+}
+
+impl TryFrom<&Value> for vcf::record::genotypes::sample::Value {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        use vcf::record::genotypes::sample::{value::Array, Value as V};
+        Ok(match value {
+            Value::Flag => anyhow::bail!("flag values are not allowed in FORMAT"),
+            Value::Integer(value) => V::Integer(*value),
+            Value::Float(value) => V::Float(*value),
+            Value::Character(value) => V::Character(*value),
+            Value::String(value) => V::String(value.clone()),
+            Value::IntegerArray(values) => V::Array(Array::Integer(values.clone())),
+            Value::FloatArray(values) => V::Array(Array::Float(values.clone())),
+            Value::CharacterArray(values) => V::Array(Array::Character(values.clone())),
+            Value::StringArray(values) => V::Array(Array::String(values.clone())),
+        })
+    }
+}
+
+/// The call of one sample, i.e., its `FORMAT` values by key; `None` for missing values.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Call {
+    /// The values by `FORMAT` key, in the order of the record.
+    pub values: indexmap::IndexMap<String, Option<Value>>,
+}
+
+impl Call {
+    /// Return the value of `key`, if present and not missing.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.values.get(key).and_then(Option::as_ref)
+    }
+
+    /// Return the parsed `GT` value, no-calls for missing values.
+    pub fn genotype(&self) -> Result<Genotype, anyhow::Error> {
+        match self.get("GT") {
+            Some(Value::String(gt)) => gt.parse(),
+            _ => Ok(Genotype::WithNoCall),
+        }
+    }
+
+    /// Return the `DP` value.
+    pub fn dp(&self) -> Option<i32> {
+        match self.get("DP") {
+            Some(Value::Integer(dp)) => Some(*dp),
+            _ => None,
+        }
+    }
+
+    /// Return the `GQ` value, which some callers write as a float.
+    pub fn gq(&self) -> Option<f32> {
+        match self.get("GQ") {
+            Some(Value::Integer(gq)) => Some(*gq as f32),
+            Some(Value::Float(gq)) => Some(*gq),
+            _ => None,
+        }
+    }
+}
+
+/// An annotated variant record.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Variant {
+    /// Index of the chromosome in the contigs of the header, if declared there.
+    pub chrom_id: Option<usize>,
+    /// The chromosome name as in the input.
+    pub chrom: String,
+    /// The 1-based position.
+    pub pos: i32,
+    /// The IDs, empty if missing.
+    pub ids: Vec<String>,
+    /// The reference allele.
+    pub reference: String,
+    /// The alternate alleles.
+    pub alternatives: Vec<String>,
+    /// The quality score, if any.
+    pub quality: Option<f32>,
+    /// The filters, `None` if missing.
+    pub filters: Option<Vec<String>>,
+    /// The annotations, i.e., the `INFO` values by key; `None` for missing values.
+    pub info: indexmap::IndexMap<String, Option<Value>>,
+    /// The per-sample calls, in the order of the samples in the header.
+    pub calls: Vec<Call>,
+}
+
+impl Variant {
+    /// Convert from `noodles` record `record` read with `header`.
+    pub fn from_vcf(header: &vcf::Header, record: &vcf::Record) -> Self {
+        let chrom = record.chromosome().to_string();
+        let genotypes = record.genotypes();
+        Self {
+            chrom_id: header.contigs().get_index_of(chrom.as_str()),
+            chrom,
+            pos: usize::from(record.position()) as i32,
+            ids: record.ids().iter().map(|id| id.to_string()).collect(),
+            reference: record.reference_bases().to_string(),
+            alternatives: record
+                .alternate_bases()
+                .iter()
+                .map(|allele| allele.to_string())
+                .collect(),
+            quality: record.quality_score().map(f32::from),
+            filters: record.filters().map(|filters| match filters {
+                vcf::record::Filters::Pass => vec!["PASS".to_string()],
+                vcf::record::Filters::Fail(filters) => filters.iter().cloned().collect(),
+            }),
+            info: record
+                .info()
+                .as_ref()
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.as_ref().map(Value::from)))
+                .collect(),
+            calls: genotypes
+                .values()
+                .map(|sample| Call {
+                    values: genotypes
+                        .keys()
+                        .iter()
+                        .zip(sample.values())
+                        .map(|(key, value)| (key.to_string(), value.as_ref().map(Value::from)))
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Convert to a `noodles` record.
+    ///
+    /// The `FORMAT` keys are taken from the first call.
+    pub fn to_vcf(&self) -> Result<vcf::Record, anyhow::Error> {
+        let mut builder = vcf::Record::builder()
+            .set_chromosome(
+                self.chrom
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("invalid chromosome {}: {}", &self.chrom, e))?,
+            )
+            .set_position(vcf::record::Position::from(self.pos as usize))
+            .set_ids(
+                self.ids
+                    .iter()
+                    .map(|id| id.parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| anyhow::anyhow!("invalid ID: {}", e))?,
+            )
+            .set_reference_bases(
+                self.reference
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("invalid reference {}: {}", &self.reference, e))?,
+            )
+            .set_alternate_bases(vcf::record::AlternateBases::from(
+                self.alternatives
+                    .iter()
+                    .map(|allele| allele.parse())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| anyhow::anyhow!("invalid alternate allele: {}", e))?,
+            ))
+            .set_info(
+                self.info
+                    .iter()
+                    .map(|(key, value)| {
+                        Ok((
+                            key.parse()
+                                .map_err(|e| anyhow::anyhow!("invalid INFO key {}: {}", key, e))?,
+                            value.as_ref().map(Into::into),
+                        ))
+                    })
+                    .collect::<Result<_, anyhow::Error>>()?,
+            );
+        if let Some(quality) = self.quality {
+            builder = builder.set_quality_score(
+                vcf::record::QualityScore::try_from(quality)
+                    .map_err(|e| anyhow::anyhow!("invalid quality {}: {}", quality, e))?,
+            );
+        }
+        if let Some(filters) = self.filters.as_ref() {
+            builder = builder.set_filters(
+                vcf::record::Filters::try_from_iter(filters)
+                    .map_err(|e| anyhow::anyhow!("invalid filters: {}", e))?,
+            );
+        }
+
+        if let Some(first) = self.calls.first() {
+            let keys = first
+                .values
+                .keys()
+                .map(|key| {
+                    key.parse()
+                        .map_err(|e| anyhow::anyhow!("invalid FORMAT key {}: {}", key, e))
+                })
+                .collect::<Result<Vec<vcf::record::genotypes::keys::Key>, _>>()?;
+            let values = self
+                .calls
+                .iter()
+                .map(|call| {
+                    first
+                        .values
+                        .keys()
+                        .map(|key| call.get(key).map(TryInto::try_into).transpose())
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            builder = builder.set_genotypes(vcf::record::Genotypes::new(
+                vcf::record::genotypes::Keys::try_from(keys)
+                    .map_err(|e| anyhow::anyhow!("invalid FORMAT keys: {}", e))?,
+                values,
+            ));
+        }
+
+        builder
+            .build()
+            .map_err(|e| anyhow::anyhow!("could not build record: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use noodles_vcf as vcf;
+
+    use super::{Value, Variant};
+    use crate::common::Genotype;
+
+    #[test]
+    fn is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Variant>();
+    }
+
+    #[test]
+    fn round_trip() -> Result<(), anyhow::Error> {
+        let mut reader =
+            vcf::reader::Builder.build_from_path("tests/seqvars/query/Case_1.ingested.vcf")?;
+        let header = reader.read_header()?;
+        for record in reader.records(&header) {
+            let record = record?;
+            let variant = Variant::from_vcf(&header, &record);
+            assert_eq!(variant.to_vcf()?.to_string(), record.to_string());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn from_vcf() -> Result<(), anyhow::Error> {
+        let mut reader =
+            vcf::reader::Builder.build_from_path("tests/seqvars/query/Case_1.ingested.vcf")?;
+        let header = reader.read_header()?;
+        let record = reader.records(&header).next().expect("no record in file")?;
+        let variant = Variant::from_vcf(&header, &record);
+        assert_eq!(variant.chrom_id, Some(16));
+        assert_eq!(variant.chrom, "17");
+        assert_eq!(variant.pos, 41249263);
+        assert_eq!(variant.reference, "G");
+        assert_eq!(variant.alternatives, vec!["A".to_string()]);
+        assert_eq!(
+            variant.info.get("gnomad_exomes_an"),
+            Some(&Some(Value::Integer(31398)))
+        );
+        assert_eq!(variant.calls.len(), 3);
+        assert_eq!(variant.calls[0].genotype()?, Genotype::HomRef);
+        assert_eq!(variant.calls[0].dp(), Some(52));
+        assert_eq!(variant.calls[0].gq(), Some(99.0));
+
+        Ok(())
+    }
+}
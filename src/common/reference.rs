@@ -0,0 +1,49 @@
+//! Access to the bases of the reference genome.
+
+/// Access to the bases of the reference genome.
+pub trait Reference {
+    /// Return the bases of `chrom` in the 1-based, closed interval `[start, end]`.
+    fn bases(&mut self, chrom: &str, start: i32, end: i32) -> Result<String, anyhow::Error>;
+}
+
+/// Reference genome from an indexed FASTA file.
+pub struct FastaReference {
+    /// The indexed FASTA reader.
+    reader: bio::io::fasta::IndexedReader<std::fs::File>,
+}
+
+impl FastaReference {
+    /// Open the indexed FASTA file at `path`.
+    pub fn from_path(path: &str) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            reader: bio::io::fasta::IndexedReader::from_file(&path)
+                .map_err(|e| anyhow::anyhow!("could not open reference {}: {}", path, e))?,
+        })
+    }
+}
+
+impl Reference for FastaReference {
+    fn bases(&mut self, chrom: &str, start: i32, end: i32) -> Result<String, anyhow::Error> {
+        let name = [
+            chrom.to_string(),
+            format!("chr{}", chrom),
+            "chrM".to_string(),
+        ]
+        .into_iter()
+        .take(if chrom == "MT" { 3 } else { 2 })
+        .find(|name| {
+            self.reader
+                .index
+                .sequences()
+                .iter()
+                .any(|seq| &seq.name == name)
+        })
+        .ok_or_else(|| anyhow::anyhow!("chromosome {} not in reference", chrom))?;
+        let mut seq = Vec::new();
+        self.reader
+            .fetch(&name, (start - 1) as u64, end as u64)
+            .and_then(|_| self.reader.read(&mut seq))
+            .map_err(|e| anyhow::anyhow!("could not read {}:{}-{}: {}", chrom, start, end, e))?;
+        Ok(String::from_utf8(seq)?.to_ascii_uppercase())
+    }
+}
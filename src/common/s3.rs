@@ -2,6 +2,8 @@
 
 use mehari::common::io::std::is_gz;
 
+use crate::common::noodles::IndexFormat;
+
 /// Helper that returns whether S3 mode has been enabled via `AWS_ACCESS_KEY_ID`.
 pub fn s3_mode() -> bool {
     std::env::var("AWS_ACCESS_KEY_ID").is_ok()
@@ -113,17 +115,27 @@ impl OutputPathHelper {
 
     /// Create TBI file if necessary.
     pub async fn create_tbi_for_bgzf(&self) -> Result<(), anyhow::Error> {
+        self.create_index_for_bgzf(IndexFormat::Tbi).await
+    }
+
+    /// Create index file in the given `format` if necessary.
+    pub async fn create_index_for_bgzf(&self, format: IndexFormat) -> Result<(), anyhow::Error> {
         if is_gz(&self.path_out_orig) {
-            tracing::info!("Creating TBI index for BGZF VCF file...");
-            crate::common::noodles::build_tbi(
+            let suffix = format.suffix();
+            tracing::info!(
+                "Creating {} index for BGZF VCF file...",
+                suffix.to_uppercase()
+            );
+            crate::common::noodles::build_index(
                 &self.path_out_effective,
-                &format!("{}.tbi", &self.path_out_effective),
+                &format!("{}.{}", &self.path_out_effective, suffix),
+                format,
             )
             .await
-            .map_err(|e| anyhow::anyhow!("problem building TBI: {}", e))?;
-            tracing::info!("... done writing TBI index");
+            .map_err(|e| anyhow::anyhow!("problem building {}: {}", suffix.to_uppercase(), e))?;
+            tracing::info!("... done writing {} index", suffix.to_uppercase());
         } else {
-            tracing::info!("(not building TBI index for plain text VCF file");
+            tracing::info!("(not building index for non-BGZF VCF file)");
         }
 
         Ok(())
@@ -135,11 +147,28 @@ impl OutputPathHelper {
             tracing::info!("Uploading to S3...");
             upload_file(&self.path_out_effective, &self.path_out_orig).await?;
             if is_gz(&self.path_out_orig) {
-                upload_file(
-                    &format!("{}.tbi", &self.path_out_effective),
-                    &format!("{}.tbi", &self.path_out_orig),
-                )
-                .await?;
+                for format in [IndexFormat::Tbi, IndexFormat::Csi] {
+                    let path_index = format!("{}.{}", &self.path_out_effective, format.suffix());
+                    if std::path::Path::new(&path_index).exists() {
+                        upload_file(
+                            &path_index,
+                            &format!("{}.{}", &self.path_out_orig, format.suffix()),
+                        )
+                        .await?;
+                    }
+                }
+            }
+            // Sidecar files with the record statistics and family structure of
+            // `seqvars ingest`.
+            for suffix in ["stats.json", "family.json"] {
+                let path_sidecar = format!("{}.{}", &self.path_out_effective, suffix);
+                if std::path::Path::new(&path_sidecar).exists() {
+                    upload_file(
+                        &path_sidecar,
+                        &format!("{}.{}", &self.path_out_orig, suffix),
+                    )
+                    .await?;
+                }
             }
             tracing::info!("... done uploading to S3");
         }
@@ -0,0 +1,111 @@
+//! Graceful handling of termination signals.
+//!
+//! Kubernetes preempts pods with `SIGTERM` and a grace period.  Instead of dying in the
+//! middle of a bgzf block, subcommands stop reading input once a `ShutdownSignal` has been
+//! requested, flush and close their output, and return an `Interrupted` error.  The output
+//! is then kept as `<path>.partial` for resuming and the executable exits with
+//! `EXIT_CODE_INTERRUPTED`.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Exit code after an interruption by a termination signal (`EX_TEMPFAIL`).
+///
+/// The job can be retried, e.g., with `--resume-from-partial`.
+pub const EXIT_CODE_INTERRUPTED: i32 = 75;
+
+/// Error returned by subcommands that stopped early because of a termination signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interrupted;
+
+impl std::fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "interrupted by termination signal")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// Flag that is set once a termination signal has been received.
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownSignal {
+    /// Whether shutdown has been requested.
+    requested: Arc<AtomicBool>,
+}
+
+impl ShutdownSignal {
+    /// Construct signal that is requested on `SIGTERM` or `SIGINT`.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn install() -> Self {
+        let result = Self::default();
+        let signal = result.clone();
+        tokio::spawn(async move {
+            wait_for_signal().await;
+            tracing::warn!(
+                "{}: received termination signal, stopping after the current records",
+                crate::codes::COMMON_INTERRUPTED
+            );
+            signal.request();
+        });
+        result
+    }
+
+    /// Request shutdown.
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Return whether shutdown has been requested.
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+}
+
+/// Wait for `SIGTERM` or `SIGINT`.
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = sigterm.recv() => (),
+                    _ = tokio::signal::ctrl_c() => (),
+                }
+            }
+            Err(e) => {
+                tracing::error!("could not install SIGTERM handler: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Interrupted, ShutdownSignal};
+
+    #[test]
+    fn request() {
+        let signal = ShutdownSignal::default();
+        let clone = signal.clone();
+        assert!(!clone.is_requested());
+
+        signal.request();
+        assert!(clone.is_requested());
+    }
+
+    #[test]
+    fn interrupted_downcast() {
+        let err: anyhow::Error = Interrupted.into();
+        assert!(err.is::<Interrupted>());
+    }
+}
@@ -0,0 +1,142 @@
+//! On-disk cache of the transcript interval trees.
+//!
+//! Building the interval trees from `txs.bin.zst` at every start is slow for the large
+//! transcript databases.  The built trees are thus serialized once into a cache directory,
+//! keyed by the SHA256 checksum of the transcript database and the genome release, and
+//! loaded from there on subsequent starts.
+//!
+//! Note that mehari's `Provider` always builds its own trees, so only the users of plain
+//! `TxIntervalTrees` can use the cache.
+
+use std::{
+    collections::HashMap,
+    io::{BufReader, BufWriter},
+};
+
+use bio::data_structures::interval_tree::ArrayBackedIntervalTree;
+use mehari::annotate::seqvars::provider::TxIntervalTrees;
+use mehari::pbs::txs::TxSeqDatabase;
+
+use super::{sha256_checksum, GenomeRelease};
+
+/// Version of the cache file format, part of the file name.
+const CACHE_VERSION: u32 = 1;
+
+/// The serialized form of `TxIntervalTrees`.
+type Serialized = (
+    HashMap<String, usize>,
+    Vec<ArrayBackedIntervalTree<i32, u32>>,
+);
+
+/// Return the path of the cache file in `cache_dir` for the transcript database with
+/// `checksum`.
+pub fn cache_path(cache_dir: &str, genome_release: GenomeRelease, checksum: &str) -> String {
+    format!(
+        "{}/tx-trees.{}.{}.v{}.msgpack",
+        cache_dir, genome_release, checksum, CACHE_VERSION
+    )
+}
+
+/// Write `trees` to `path`.
+///
+/// The file is written next to `path` first and renamed so concurrent readers never
+/// see a partially written cache file.
+pub fn write(trees: &TxIntervalTrees, path: &str) -> Result<(), anyhow::Error> {
+    let path_tmp = format!("{}.tmp.{}", path, std::process::id());
+    {
+        let mut writer = BufWriter::new(
+            std::fs::File::create(&path_tmp)
+                .map_err(|e| anyhow::anyhow!("could not create {}: {}", &path_tmp, e))?,
+        );
+        rmp_serde::encode::write(&mut writer, &(&trees.contig_to_idx, &trees.trees))
+            .map_err(|e| anyhow::anyhow!("could not write transcript trees: {}", e))?;
+    }
+    std::fs::rename(&path_tmp, path)
+        .map_err(|e| anyhow::anyhow!("could not rename {} to {}: {}", &path_tmp, path, e))
+}
+
+/// Read the trees written with `write` from `path`.
+pub fn read(path: &str) -> Result<TxIntervalTrees, anyhow::Error> {
+    let reader = BufReader::new(
+        std::fs::File::open(path).map_err(|e| anyhow::anyhow!("could not open {}: {}", path, e))?,
+    );
+    let (contig_to_idx, trees): Serialized = rmp_serde::decode::from_read(reader)
+        .map_err(|e| anyhow::anyhow!("could not read transcript trees from {}: {}", path, e))?;
+    Ok(TxIntervalTrees {
+        contig_to_idx,
+        trees,
+    })
+}
+
+/// Load the trees for `tx_db` from `path_tx_db` from `cache_dir` if present there, or
+/// build them and write them to `cache_dir`.
+///
+/// Problems with the cache are logged and the trees are built instead.
+pub fn load_or_build(
+    tx_db: &TxSeqDatabase,
+    path_tx_db: &str,
+    genome_release: GenomeRelease,
+    cache_dir: &str,
+) -> Result<TxIntervalTrees, anyhow::Error> {
+    let path = cache_path(cache_dir, genome_release, &sha256_checksum(path_tx_db)?);
+    if std::path::Path::new(&path).exists() {
+        match read(&path) {
+            Ok(trees) => {
+                tracing::info!("loaded transcript trees from {}", &path);
+                return Ok(trees);
+            }
+            Err(e) => tracing::warn!(
+                "{}: ignoring transcript tree cache: {}",
+                crate::codes::COMMON_TX_TREE_CACHE_IGNORED,
+                e
+            ),
+        }
+    }
+
+    let trees = TxIntervalTrees::new(tx_db, genome_release.into());
+    if let Err(e) = std::fs::create_dir_all(cache_dir)
+        .map_err(|e| anyhow::anyhow!("could not create {}: {}", cache_dir, e))
+        .and_then(|_| write(&trees, &path))
+    {
+        tracing::warn!(
+            "{}: could not write transcript tree cache: {}",
+            crate::codes::COMMON_TX_TREE_CACHE_IGNORED,
+            e
+        );
+    } else {
+        tracing::info!("wrote transcript trees to {}", &path);
+    }
+    Ok(trees)
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::seqvars::provider::TxIntervalTrees;
+
+    use crate::common::GenomeRelease;
+
+    #[test]
+    fn load_or_build() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let cache_dir = tmpdir.join("cache").to_string_lossy().to_string();
+        let path_tx_db = "tests/seqvars/ingest/db/grch37/txs.bin.zst";
+        let tx_db = mehari::annotate::seqvars::load_tx_db(path_tx_db)?;
+
+        let built = super::load_or_build(&tx_db, path_tx_db, GenomeRelease::Grch37, &cache_dir)?;
+        let path = super::cache_path(
+            &cache_dir,
+            GenomeRelease::Grch37,
+            &crate::common::sha256_checksum(path_tx_db)?,
+        );
+        assert!(std::path::Path::new(&path).exists());
+
+        let cached = super::load_or_build(&tx_db, path_tx_db, GenomeRelease::Grch37, &cache_dir)?;
+        let expected = TxIntervalTrees::new(&tx_db, GenomeRelease::Grch37.into());
+        for trees in [&built, &cached] {
+            assert_eq!(trees.contig_to_idx, expected.contig_to_idx);
+            assert_eq!(trees.trees, expected.trees);
+        }
+
+        Ok(())
+    }
+}
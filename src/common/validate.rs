@@ -0,0 +1,263 @@
+//! Self-check of written VCF files against their header.
+//!
+//! This is run as an optional final pass by the subcommands writing VCF files to catch
+//! mismatches between the header and the records before the files are imported.
+
+use mehari::common::{io::std::is_gz, noodles::open_vcf_reader};
+use noodles_vcf as vcf;
+use std::io::{Read, Seek, SeekFrom};
+use vcf::header::Number;
+use vcf::record::genotypes::sample::value::{Array, Value};
+
+/// The empty BGZF block marking the end of a BGZF file.
+const BGZF_EOF: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00,
+    0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// Maximal number of problems to report before giving up.
+const MAX_PROBLEMS: usize = 10;
+
+/// Check whether the file at `path` ends with the BGZF EOF marker.
+pub fn has_bgzf_eof(path: &str) -> Result<bool, anyhow::Error> {
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| anyhow::anyhow!("could not open {} for validation: {}", path, e))?;
+    let len = file.metadata()?.len();
+    if len < BGZF_EOF.len() as u64 {
+        return Ok(false);
+    }
+    let mut buf = [0u8; BGZF_EOF.len()];
+    file.seek(SeekFrom::End(-(BGZF_EOF.len() as i64)))?;
+    file.read_exact(&mut buf)?;
+    Ok(buf == BGZF_EOF)
+}
+
+/// Return the number of values expected for `number` or `None` if it is not fixed.
+fn expected_len(number: Number, allele_count: usize, ploidy: Option<usize>) -> Option<usize> {
+    match number {
+        Number::Count(n) => Some(n),
+        Number::A => Some(allele_count - 1),
+        Number::R => Some(allele_count),
+        Number::G => ploidy.map(|ploidy| genotype_count(allele_count, ploidy)),
+        Number::Unknown => None,
+    }
+}
+
+/// Number of unordered genotypes for `allele_count` alleles and `ploidy`.
+fn genotype_count(allele_count: usize, ploidy: usize) -> usize {
+    // binomial coefficient (allele_count + ploidy - 1) choose ploidy
+    (1..=ploidy).fold(1, |acc, k| acc * (allele_count + k - 1) / k)
+}
+
+/// Check `record` against `header`, appending the problems found to `problems`.
+pub fn check_record(header: &vcf::Header, record: &vcf::Record, problems: &mut Vec<String>) {
+    let locus = format!("{}:{}", record.chromosome(), record.position());
+
+    for key in record.info().keys() {
+        if !header.infos().contains_key(key) {
+            problems.push(format!(
+                "{}: INFO key {} not declared in header",
+                &locus, key
+            ));
+        }
+    }
+    let keys = record.genotypes().keys();
+    for key in keys.iter() {
+        if !header.formats().contains_key(key) {
+            problems.push(format!(
+                "{}: FORMAT key {} not declared in header",
+                &locus, key
+            ));
+        }
+    }
+
+    let allele_count = 1 + record.alternate_bases().len();
+    for (i, sample) in record.genotypes().values().enumerate() {
+        let sample_name = header
+            .sample_names()
+            .get_index(i)
+            .map(|name| name.as_str())
+            .unwrap_or("?");
+        let ploidy = match sample.genotype() {
+            Some(Ok(genotype)) => {
+                if genotype
+                    .iter()
+                    .any(|allele| allele.position().map_or(false, |pos| pos >= allele_count))
+                {
+                    problems.push(format!(
+                        "{}: genotype of {} refers to missing allele",
+                        &locus, sample_name
+                    ));
+                }
+                Some(genotype.len())
+            }
+            Some(Err(e)) => {
+                problems.push(format!(
+                    "{}: invalid genotype of {}: {}",
+                    &locus, sample_name, e
+                ));
+                None
+            }
+            None => None,
+        };
+
+        for (key, value) in keys.iter().zip(sample.values().iter()) {
+            let (Some(Value::Array(array)), Some(format)) = (value, header.formats().get(key))
+            else {
+                continue;
+            };
+            let len = match array {
+                Array::Integer(values) => values.len(),
+                Array::Float(values) => values.len(),
+                Array::Character(values) => values.len(),
+                Array::String(values) => values.len(),
+            };
+            if let Some(expected) = expected_len(format.number(), allele_count, ploidy) {
+                if len != expected {
+                    problems.push(format!(
+                        "{}: FORMAT/{} of {} has {} values but {} expected",
+                        &locus, key, sample_name, len, expected
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Re-read the VCF file at `path` and check the records against the header.
+///
+/// For bgzip-compressed files, the presence of the BGZF EOF marker is checked as well.
+pub async fn validate_vcf(path: &str) -> Result<(), anyhow::Error> {
+    tracing::info!("validating output file {}", path);
+    let mut problems = Vec::new();
+    if is_gz(path) && !has_bgzf_eof(path)? {
+        problems.push("BGZF EOF marker missing".to_string());
+    }
+
+    let mut reader = open_vcf_reader(path)
+        .await
+        .map_err(|e| anyhow::anyhow!("could not open {} for validation: {}", path, e))?;
+    let header = reader
+        .read_header()
+        .await
+        .map_err(|e| anyhow::anyhow!("could not read header of {}: {}", path, e))?;
+    let mut record = vcf::Record::default();
+    let mut count = 0;
+    while problems.len() < MAX_PROBLEMS {
+        match reader.read_record(&header, &mut record).await {
+            Ok(0) => break,
+            Ok(_) => check_record(&header, &record, &mut problems),
+            Err(e) => {
+                problems.push(format!("could not read record: {}", e));
+                break;
+            }
+        }
+        count += 1;
+    }
+
+    if problems.is_empty() {
+        tracing::info!("... validated {} records", count);
+        Ok(())
+    } else {
+        for problem in &problems {
+            tracing::error!("{}", problem);
+        }
+        anyhow::bail!(
+            "validation of {} failed with {} problem(s), first: {}",
+            path,
+            problems.len(),
+            &problems[0]
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[rstest::rstest]
+    #[case(2, 1, 2)]
+    #[case(2, 2, 3)]
+    #[case(3, 2, 6)]
+    #[case(3, 1, 3)]
+    fn genotype_count(#[case] allele_count: usize, #[case] ploidy: usize, #[case] expected: usize) {
+        assert_eq!(super::genotype_count(allele_count, ploidy), expected);
+    }
+
+    #[rstest::rstest]
+    #[case::valid("0/1", "3,4", "0,10,100", true)]
+    #[case::ad_too_short("0/1", "3", "0,10,100", false)]
+    #[case::pl_haploid("1", "3,4", "0,10", true)]
+    #[case::pl_ploidy_mismatch("1", "3,4", "0,10,100", false)]
+    #[case::missing_allele("0/2", "3,4", "0,10,100", false)]
+    fn check_record(
+        #[case] gt: &str,
+        #[case] ad: &str,
+        #[case] pl: &str,
+        #[case] expected_ok: bool,
+    ) -> Result<(), anyhow::Error> {
+        use noodles_vcf as vcf;
+
+        let header: vcf::Header = "##fileformat=VCFv4.2\n\
+            ##INFO=<ID=DP,Number=1,Type=Integer,Description=\"Depth\">\n\
+            ##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">\n\
+            ##FORMAT=<ID=AD,Number=R,Type=Integer,Description=\"Allele depths\">\n\
+            ##FORMAT=<ID=PL,Number=G,Type=Integer,Description=\"Genotype likelihoods\">\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tsample\n"
+            .parse()?;
+        let record = vcf::Record::try_from((
+            &header,
+            format!("1\t100\t.\tA\tG\t.\tPASS\tDP=7\tGT:AD:PL\t{gt}:{ad}:{pl}").as_str(),
+        ))?;
+
+        let mut problems = Vec::new();
+        super::check_record(&header, &record, &mut problems);
+
+        assert_eq!(problems.is_empty(), expected_ok, "{:?}", &problems);
+
+        Ok(())
+    }
+
+    #[test]
+    fn undeclared_keys() -> Result<(), anyhow::Error> {
+        use noodles_vcf as vcf;
+
+        let header: vcf::Header = "##fileformat=VCFv4.2\n\
+            ##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">\n\
+            #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tsample\n"
+            .parse()?;
+        let record =
+            vcf::Record::try_from((&header, "1\t100\t.\tA\tG\t.\tPASS\tXX=7\tGT:YY\t0/1:3"))?;
+
+        let mut problems = Vec::new();
+        super::check_record(&header, &record, &mut problems);
+
+        insta::assert_yaml_snapshot!(problems);
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case("tests/seqvars/ingest/NA12878_dragen.vcf.gz", true)]
+    #[case("tests/seqvars/add_sample/case.vcf", false)]
+    fn has_bgzf_eof(#[case] path: &str, #[case] expected: bool) -> Result<(), anyhow::Error> {
+        assert_eq!(super::has_bgzf_eof(path)?, expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn validate_vcf() -> Result<(), anyhow::Error> {
+        super::validate_vcf("tests/seqvars/ingest/NA12878_dragen.vcf.gz").await
+    }
+
+    #[tokio::test]
+    async fn validate_vcf_truncated() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("out.vcf.gz").to_str().unwrap().to_string();
+        let data = std::fs::read("tests/seqvars/ingest/NA12878_dragen.vcf.gz")?;
+        std::fs::write(&path, &data[..data.len() - 28])?;
+
+        assert!(super::validate_vcf(&path).await.is_err());
+
+        Ok(())
+    }
+}
@@ -1,7 +1,12 @@
 //! VarFish Server Worker main executable
 
+pub mod codes;
 pub mod common;
+pub mod meta;
+pub mod presets;
+pub mod results;
 pub mod seqvars;
+pub mod server;
 pub mod strucvars;
 
 use clap::{Args, Parser, Subcommand};
@@ -33,6 +38,81 @@ enum Commands {
     Strucvars(Strucvars),
     /// Sequence variant related commands.
     Seqvars(Seqvars),
+    /// Write metadata of the worker for the server.
+    Meta(meta::Args),
+    /// Write the catalogue of warning and error codes for the server.
+    Codes(codes::Args),
+    /// Query settings presets bundled with the worker.
+    Presets(Presets),
+    /// Operations on query result files.
+    Results(Results),
+    /// Long-running server modes.
+    Server(Server),
+    /// Tools for the developers, e.g., benchmarks.
+    #[command(hide = true)]
+    Devtools(Devtools),
+}
+
+/// Parsing of "presets *" sub commands.
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Presets {
+    /// The sub command to run
+    #[command(subcommand)]
+    command: PresetsCommands,
+}
+
+/// Enum supporting the parsing of "presets *" sub commands.
+#[derive(Debug, Subcommand)]
+enum PresetsCommands {
+    Dump(presets::dump::Args),
+}
+
+/// Parsing of "results *" sub commands.
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Results {
+    /// The sub command to run
+    #[command(subcommand)]
+    command: ResultsCommands,
+}
+
+/// Enum supporting the parsing of "results *" sub commands.
+#[derive(Debug, Subcommand)]
+enum ResultsCommands {
+    Combine(results::combine::Args),
+    Diff(results::diff::Args),
+}
+
+/// Parsing of "server *" sub commands.
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Server {
+    /// The sub command to run
+    #[command(subcommand)]
+    command: ServerCommands,
+}
+
+/// Enum supporting the parsing of "server *" sub commands.
+#[derive(Debug, Subcommand)]
+enum ServerCommands {
+    Run(server::run::Args),
+    Rest(server::rest::Args),
+}
+
+/// Parsing of "devtools *" sub commands.
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Devtools {
+    /// The sub command to run
+    #[command(subcommand)]
+    command: DevtoolsCommands,
+}
+
+/// Enum supporting the parsing of "devtools *" sub commands.
+#[derive(Debug, Subcommand)]
+enum DevtoolsCommands {
+    BenchIngest(seqvars::ingest::bench::Args),
 }
 
 /// Parsing of "strucvars *" sub commands.
@@ -49,6 +129,7 @@ struct Strucvars {
 enum StrucvarsCommands {
     Aggregate(strucvars::aggregate::cli::Args),
     Ingest(strucvars::ingest::Args),
+    Merge(strucvars::merge::Args),
     Query(strucvars::query::Args),
     TxtToBin(strucvars::txt_to_bin::cli::Args),
 }
@@ -65,10 +146,270 @@ struct Seqvars {
 /// Enum supporting the parsing of "strucvars *" sub commands.
 #[derive(Debug, Subcommand)]
 enum SeqvarsCommands {
+    AddSample(seqvars::add_sample::Args),
     Aggregate(seqvars::aggregate::Args),
+    AnnotateRegion(seqvars::annotate_region::Args),
+    ExportParquet(seqvars::export_parquet::Args),
     Ingest(seqvars::ingest::Args),
+    Lookup(seqvars::lookup::Args),
     Prefilter(seqvars::prefilter::Args),
+    PrefilterStats(seqvars::prefilter_stats::Args),
+    Qc(SeqvarsQc),
     Query(seqvars::query::Args),
+    SpecialLoci(seqvars::special_loci::Args),
+    SplitCohort(seqvars::ingest::split_cohort::Args),
+}
+
+/// Parsing of "seqvars qc *" sub commands.
+#[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true)]
+struct SeqvarsQc {
+    /// The sub command to run
+    #[command(subcommand)]
+    command: SeqvarsQcCommands,
+}
+
+/// Enum supporting the parsing of "seqvars qc *" sub commands.
+#[derive(Debug, Subcommand)]
+enum SeqvarsQcCommands {
+    Relatedness(seqvars::qc::relatedness::Args),
+    Sex(seqvars::qc::sex::Args),
+}
+
+/// Build the manifest with the files produced by `command`.
+fn build_manifest(command: &Commands) -> Result<common::manifest::Manifest, anyhow::Error> {
+    use common::manifest::Manifest;
+
+    let manifest = match command {
+        Commands::Seqvars(seqvars) => match &seqvars.command {
+            SeqvarsCommands::AddSample(args) => {
+                let mut manifest = Manifest::new("seqvars add-sample");
+                manifest.add_vcf(&args.path_out, "seqvars-ingested-vcf")?;
+                if let Some(path) = args.path_case_rocksdb.as_ref() {
+                    manifest.add_dir(path, "seqvars-case-rocksdb", "rocksdb")?;
+                }
+                manifest
+            }
+            SeqvarsCommands::Aggregate(args) => {
+                let mut manifest = Manifest::new("seqvars aggregate");
+                manifest.add_dir(
+                    &args.path_out_rocksdb,
+                    "seqvars-aggregate-rocksdb",
+                    "rocksdb",
+                )?;
+                manifest
+            }
+            SeqvarsCommands::Ingest(args) => {
+                let mut manifest = Manifest::new("seqvars ingest");
+                if args.quick_qc {
+                    if let Some(path) = args.path_quick_qc.as_ref() {
+                        manifest.add(path, "qc-json")?;
+                    }
+                } else {
+                    manifest.add_vcf(&args.path_out, "seqvars-ingested-vcf")?;
+                    manifest.add(
+                        &format!(
+                            "{}.{}",
+                            &args.path_out,
+                            seqvars::ingest::stats::SIDECAR_SUFFIX
+                        ),
+                        "seqvars-ingest-stats-json",
+                    )?;
+                    if !args.annotate_only {
+                        manifest.add(
+                            &format!(
+                                "{}.{}",
+                                &args.path_out,
+                                seqvars::ingest::family::SIDECAR_SUFFIX
+                            ),
+                            "family-json",
+                        )?;
+                    }
+                    if let Some(path) = args.path_allele_report.as_ref() {
+                        manifest.add(path, "allele-report-json")?;
+                    }
+                    manifest.add(
+                        &common::partial::partial_path(&args.path_out),
+                        "seqvars-ingested-vcf-partial",
+                    )?;
+                }
+                manifest
+            }
+            SeqvarsCommands::AnnotateRegion(args) => {
+                let mut manifest = Manifest::new("seqvars annotate-region");
+                if let Some(path) = args.path_out.as_ref() {
+                    manifest.add(path, "seqvars-annotate-region")?;
+                }
+                manifest
+            }
+            SeqvarsCommands::ExportParquet(args) => {
+                let mut manifest = Manifest::new("seqvars export-parquet");
+                manifest.add(&args.path_out, "seqvars-parquet")?;
+                manifest
+            }
+            SeqvarsCommands::Lookup(args) => {
+                let mut manifest = Manifest::new("seqvars lookup");
+                if let Some(path) = args.path_out.as_ref() {
+                    manifest.add(path, "seqvars-lookup-jsonl")?;
+                }
+                manifest
+            }
+            SeqvarsCommands::Prefilter(args) => {
+                let mut manifest = Manifest::new("seqvars prefilter");
+                for path in seqvars::prefilter::output_paths(args)? {
+                    manifest.add_vcf(&path, "seqvars-prefiltered-vcf")?;
+                }
+                manifest
+            }
+            SeqvarsCommands::PrefilterStats(args) => {
+                let mut manifest = Manifest::new("seqvars prefilter-stats");
+                manifest.add(&args.path_out, "prefilter-stats-json")?;
+                manifest
+            }
+            SeqvarsCommands::Qc(qc) => match &qc.command {
+                SeqvarsQcCommands::Relatedness(args) => {
+                    let mut manifest = Manifest::new("seqvars qc relatedness");
+                    manifest.add(&args.path_out, "qc-json")?;
+                    manifest
+                }
+                SeqvarsQcCommands::Sex(args) => {
+                    let mut manifest = Manifest::new("seqvars qc sex");
+                    manifest.add(&args.path_out, "qc-json")?;
+                    manifest
+                }
+            },
+            SeqvarsCommands::Query(args) => {
+                let mut manifest = Manifest::new("seqvars query");
+                manifest.add(&args.path_output, "seqvars-query-result")?;
+                if let Some(path) = args.path_output_info.as_ref() {
+                    manifest.add(path, "seqvars-query-info-json")?;
+                }
+                if let Some(path) = args.path_gene_summary.as_ref() {
+                    manifest.add(path, "seqvars-query-gene-summary")?;
+                }
+                if let Some(path) = args.path_output_explain.as_ref() {
+                    manifest.add(path, "seqvars-query-explain-jsonl")?;
+                }
+                manifest
+            }
+            SeqvarsCommands::SpecialLoci(args) => {
+                let mut manifest = Manifest::new("seqvars special-loci");
+                manifest.add(&args.path_out, "special-loci-json")?;
+                manifest
+            }
+            SeqvarsCommands::SplitCohort(args) => {
+                let mut manifest = Manifest::new("seqvars split-cohort");
+                for mapping in seqvars::ingest::split_cohort::load_families(&args.path_families)? {
+                    manifest.add_vcf(&mapping.path_out, "seqvars-ingested-vcf")?;
+                    manifest.add(
+                        &format!(
+                            "{}.{}",
+                            &mapping.path_out,
+                            seqvars::ingest::stats::SIDECAR_SUFFIX
+                        ),
+                        "seqvars-ingest-stats-json",
+                    )?;
+                    manifest.add(
+                        &format!(
+                            "{}.{}",
+                            &mapping.path_out,
+                            seqvars::ingest::family::SIDECAR_SUFFIX
+                        ),
+                        "family-json",
+                    )?;
+                }
+                manifest
+            }
+        },
+        Commands::Strucvars(strucvars) => match &strucvars.command {
+            StrucvarsCommands::Aggregate(args) => {
+                let mut manifest = Manifest::new("strucvars aggregate");
+                manifest.add(&args.path_output.to_string_lossy(), "strucvars-inhouse-tsv")?;
+                if let Some(path) = args.path_output_bin.as_ref() {
+                    manifest.add(&path.to_string_lossy(), "strucvars-inhouse-bin")?;
+                }
+                manifest
+            }
+            StrucvarsCommands::Ingest(args) => {
+                let mut manifest = Manifest::new("strucvars ingest");
+                manifest.add_vcf(&args.path_out, "strucvars-ingested-vcf")?;
+                manifest
+            }
+            StrucvarsCommands::Merge(args) => {
+                let mut manifest = Manifest::new("strucvars merge");
+                manifest.add_vcf(&args.path_out, "strucvars-ingested-vcf")?;
+                manifest
+            }
+            StrucvarsCommands::Query(args) => {
+                let mut manifest = Manifest::new("strucvars query");
+                manifest.add(&args.path_output, "strucvars-query-result")?;
+                manifest
+            }
+            StrucvarsCommands::TxtToBin(args) => {
+                let mut manifest = Manifest::new("strucvars txt-to-bin");
+                manifest.add(&args.path_output.to_string_lossy(), "strucvars-bin")?;
+                manifest
+            }
+        },
+        Commands::Meta(args) => {
+            let mut manifest = Manifest::new("meta");
+            if let Some(path) = args.path_out.as_ref() {
+                manifest.add(path, "meta-json")?;
+            }
+            manifest
+        }
+        Commands::Codes(args) => {
+            let mut manifest = Manifest::new("codes");
+            if let Some(path) = args.path_out.as_ref() {
+                manifest.add(path, "codes-json")?;
+            }
+            manifest
+        }
+        Commands::Presets(presets) => match &presets.command {
+            PresetsCommands::Dump(args) => {
+                let mut manifest = Manifest::new("presets dump");
+                if let Some(path) = args.path_out.as_ref() {
+                    manifest.add(path, "presets-json")?;
+                }
+                manifest
+            }
+        },
+        Commands::Results(results) => match &results.command {
+            ResultsCommands::Combine(args) => {
+                let mut manifest = Manifest::new("results combine");
+                manifest.add(&args.path_out, "query-result-jsonl")?;
+                manifest
+            }
+            ResultsCommands::Diff(args) => {
+                let mut manifest = Manifest::new("results diff");
+                if let Some(path) = args.path_out.as_ref() {
+                    manifest.add(path, "results-diff-json")?;
+                }
+                manifest
+            }
+        },
+        Commands::Server(_) => Manifest::new("server"),
+        Commands::Devtools(devtools) => match &devtools.command {
+            DevtoolsCommands::BenchIngest(args) => {
+                let mut manifest = Manifest::new("devtools bench-ingest");
+                if let Some(path) = args.path_report.as_ref() {
+                    manifest.add(path, "bench-report-json")?;
+                }
+                manifest
+            }
+        },
+    };
+
+    Ok(manifest)
+}
+
+/// Write the manifest if `--path-manifest` is given.
+fn write_manifest(cli: &Cli) -> Result<(), anyhow::Error> {
+    if let Some(path_manifest) = cli.common.path_manifest.as_ref() {
+        tracing::info!("writing manifest to {}...", path_manifest);
+        build_manifest(&cli.command)?.write(path_manifest)?;
+    }
+    Ok(())
 }
 
 #[tokio::main]
@@ -96,20 +437,56 @@ async fn main() -> Result<(), anyhow::Error> {
     let term = Term::stderr();
     match &cli.command {
         Commands::Seqvars(seqvars) => match &seqvars.command {
+            SeqvarsCommands::AddSample(args) => {
+                seqvars::add_sample::run(&cli.common, args).await?;
+            }
             SeqvarsCommands::Aggregate(args) => {
                 // Note that aggregate is not async as it uses Rayon and will
                 // block internally for the read files.
                 seqvars::aggregate::run(&cli.common, args)?;
             }
+            SeqvarsCommands::AnnotateRegion(args) => {
+                seqvars::annotate_region::run(&cli.common, args).await?;
+            }
+            SeqvarsCommands::ExportParquet(args) => {
+                seqvars::export_parquet::run(&cli.common, args).await?;
+            }
             SeqvarsCommands::Ingest(args) => {
-                seqvars::ingest::run(&cli.common, args).await?;
+                if let Err(e) = seqvars::ingest::run(&cli.common, args).await {
+                    if e.is::<common::shutdown::Interrupted>() {
+                        // Record the partial output such that the server can resume.
+                        write_manifest(&cli)?;
+                        std::process::exit(common::shutdown::EXIT_CODE_INTERRUPTED);
+                    }
+                    return Err(e);
+                }
+            }
+            SeqvarsCommands::Lookup(args) => {
+                seqvars::lookup::run(&cli.common, args).await?;
             }
             SeqvarsCommands::Prefilter(args) => {
                 seqvars::prefilter::run(&cli.common, args).await?;
             }
+            SeqvarsCommands::PrefilterStats(args) => {
+                seqvars::prefilter_stats::run(&cli.common, args).await?;
+            }
+            SeqvarsCommands::Qc(qc) => match &qc.command {
+                SeqvarsQcCommands::Relatedness(args) => {
+                    seqvars::qc::relatedness::run(&cli.common, args).await?;
+                }
+                SeqvarsQcCommands::Sex(args) => {
+                    seqvars::qc::sex::run(&cli.common, args).await?;
+                }
+            },
             SeqvarsCommands::Query(args) => {
                 seqvars::query::run(&cli.common, args).await?;
             }
+            SeqvarsCommands::SpecialLoci(args) => {
+                seqvars::special_loci::run(&cli.common, args)?;
+            }
+            SeqvarsCommands::SplitCohort(args) => {
+                seqvars::ingest::split_cohort::run(&cli.common, args).await?;
+            }
         },
         Commands::Strucvars(strucvars) => match &strucvars.command {
             StrucvarsCommands::Aggregate(args) => {
@@ -118,6 +495,9 @@ async fn main() -> Result<(), anyhow::Error> {
             StrucvarsCommands::Ingest(args) => {
                 strucvars::ingest::run(&cli.common, args).await?;
             }
+            StrucvarsCommands::Merge(args) => {
+                strucvars::merge::run(&cli.common, args).await?;
+            }
             StrucvarsCommands::Query(args) => {
                 strucvars::query::run(&cli.common, args).await?;
             }
@@ -125,7 +505,40 @@ async fn main() -> Result<(), anyhow::Error> {
                 strucvars::txt_to_bin::cli::run(&cli.common, args)?;
             }
         },
+        Commands::Meta(args) => {
+            meta::run(&cli.common, args)?;
+        }
+        Commands::Codes(args) => {
+            codes::run(&cli.common, args)?;
+        }
+        Commands::Presets(presets) => match &presets.command {
+            PresetsCommands::Dump(args) => {
+                presets::dump::run(&cli.common, args)?;
+            }
+        },
+        Commands::Results(results) => match &results.command {
+            ResultsCommands::Combine(args) => {
+                results::combine::run(&cli.common, args)?;
+            }
+            ResultsCommands::Diff(args) => {
+                results::diff::run(&cli.common, args)?;
+            }
+        },
+        Commands::Server(server) => match &server.command {
+            ServerCommands::Run(args) => {
+                server::run::run(&cli.common, args).await?;
+            }
+            ServerCommands::Rest(args) => {
+                server::rest::run(&cli.common, args).await?;
+            }
+        },
+        Commands::Devtools(devtools) => match &devtools.command {
+            DevtoolsCommands::BenchIngest(args) => {
+                seqvars::ingest::bench::run(&cli.common, args).await?;
+            }
+        },
     }
+    write_manifest(&cli)?;
     term.write_line(&format!("All done. Have a nice day!{}", Emoji(" 😃", "")))?;
 
     Ok(())
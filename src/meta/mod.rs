@@ -0,0 +1,137 @@
+//! Implementation of `meta` subcommand.
+//!
+//! This writes the tables and defaults that the worker uses as JSON, e.g., the consequence
+//! severities and the query defaults, so VarFish Server can render filter options that
+//! exactly match the worker's behaviour instead of keeping parallel lists.
+
+use std::io::Write as _;
+
+use mehari::annotate::seqvars::ann::{Consequence, PutativeImpact};
+use strum::IntoEnumIterator as _;
+
+use crate::{common, seqvars, strucvars};
+
+/// Command line arguments for `meta` subcommand.
+#[derive(Debug, clap::Parser)]
+#[command(author, version, about = "write worker metadata as JSON", long_about = None)]
+pub struct Args {
+    /// Path to the output JSON file, standard output if not given.
+    #[clap(long)]
+    pub path_out: Option<String>,
+}
+
+/// A consequence with its putative impact.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ConsequenceInfo {
+    /// The consequence, as used in the query JSON.
+    pub consequence: Consequence,
+    /// The putative impact of the consequence.
+    pub impact: PutativeImpact,
+    /// Rank in order of decreasing severity, starting with 0.
+    pub rank: usize,
+}
+
+/// The versions of the worker and the schemas.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Versions {
+    /// The version of the worker.
+    pub worker: String,
+    /// The version of the `seqvars query` JSON schema.
+    pub seqvars_query_schema: u32,
+    /// The version of the `strucvars query` JSON schema.
+    pub strucvars_query_schema: u32,
+}
+
+/// The metadata written by the `meta` command.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Meta {
+    /// The versions of the worker and the schemas.
+    pub versions: Versions,
+    /// The consequences in order of decreasing severity.
+    pub consequences: Vec<ConsequenceInfo>,
+    /// The filters of `seqvars query`, in order of evaluation.
+    pub seqvars_filters: Vec<String>,
+    /// The defaults of the `seqvars query` JSON.
+    pub seqvars_query_defaults: seqvars::query::schema::CaseQuery,
+    /// The defaults of the `strucvars query` JSON.
+    pub strucvars_query_defaults: strucvars::query::schema::CaseQuery,
+}
+
+impl Meta {
+    /// Collect the metadata of the worker.
+    pub fn new() -> Self {
+        Self {
+            versions: Versions {
+                worker: common::worker_version().to_string(),
+                seqvars_query_schema: seqvars::query::schema::SCHEMA_VERSION,
+                strucvars_query_schema: strucvars::query::schema::SCHEMA_VERSION,
+            },
+            consequences: Consequence::iter()
+                .enumerate()
+                .map(|(rank, consequence)| ConsequenceInfo {
+                    consequence,
+                    impact: consequence.impact(),
+                    rank,
+                })
+                .collect(),
+            seqvars_filters: seqvars::query::interpreter::Filter::iter()
+                .map(|filter| filter.to_string())
+                .collect(),
+            seqvars_query_defaults: Default::default(),
+            strucvars_query_defaults: Default::default(),
+        }
+    }
+}
+
+impl Default for Meta {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Main entry point for `meta` sub command.
+pub fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::debug!("args_common = {:#?}", &args_common);
+    tracing::debug!("args = {:#?}", &args);
+
+    let mut writer: Box<dyn std::io::Write> = match args.path_out.as_ref() {
+        Some(path_out) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path_out)
+                .map_err(|e| anyhow::anyhow!("could not create {}: {}", path_out, e))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    serde_json::to_writer_pretty(&mut writer, &Meta::new())
+        .map_err(|e| anyhow::anyhow!("could not write metadata: {}", e))?;
+    writeln!(writer)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn consequences_ordered_by_impact() {
+        let meta = super::Meta::new();
+        assert!(meta
+            .consequences
+            .windows(2)
+            .all(|pair| pair[0].impact <= pair[1].impact));
+    }
+
+    #[test]
+    fn smoke_test() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_out = tmpdir.join("meta.json");
+
+        let args = super::Args {
+            path_out: Some(path_out.to_str().unwrap().into()),
+        };
+        super::run(&crate::common::Args::default(), &args)?;
+
+        insta::assert_snapshot!(std::fs::read_to_string(&path_out)?);
+
+        Ok(())
+    }
+}
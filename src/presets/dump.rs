@@ -0,0 +1,83 @@
+//! Implementation of the `presets dump` subcommand.
+
+use std::io::Write as _;
+
+/// Command line arguments for `presets dump` subcommand.
+#[derive(Debug, clap::Parser)]
+#[command(author, version, about = "Write the bundled query presets as JSON", long_about = None)]
+pub struct Args {
+    /// Path to the output JSON file, standard output if not given.
+    #[clap(long)]
+    pub path_out: Option<String>,
+}
+
+/// The bundled presets by variant type and name.
+#[derive(Debug, serde::Serialize)]
+pub struct Presets {
+    /// The `seqvars query` presets; the genotype choices are filled from the pedigree at runtime.
+    pub seqvars: indexmap::IndexMap<String, crate::seqvars::query::schema::CaseQuery>,
+    /// The `strucvars query` presets.
+    pub strucvars: indexmap::IndexMap<String, crate::strucvars::query::schema::CaseQuery>,
+}
+
+impl Presets {
+    /// Collect all bundled presets.
+    pub fn new() -> Self {
+        Self {
+            seqvars: crate::seqvars::query::presets::all(),
+            strucvars: crate::strucvars::query::presets::all(),
+        }
+    }
+}
+
+impl Default for Presets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Main entry point for `presets dump` sub command.
+pub fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::debug!("args_common = {:#?}", &args_common);
+    tracing::debug!("args = {:#?}", &args);
+
+    let mut writer: Box<dyn std::io::Write> = match args.path_out.as_ref() {
+        Some(path_out) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path_out)
+                .map_err(|e| anyhow::anyhow!("could not create {}: {}", path_out, e))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    serde_json::to_writer_pretty(&mut writer, &Presets::new())
+        .map_err(|e| anyhow::anyhow!("could not write presets: {}", e))?;
+    writeln!(writer)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn smoke_test() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_out = tmpdir.join("presets.json");
+
+        let args = super::Args {
+            path_out: Some(path_out.to_str().unwrap().into()),
+        };
+        super::run(&crate::common::Args::default(), &args)?;
+
+        let presets: serde_json::Value = serde_json::from_reader(std::fs::File::open(&path_out)?)?;
+        assert_eq!(
+            presets["seqvars"]["x-linked"]["genomic_regions"][0]["chrom"],
+            "X"
+        );
+        assert_eq!(
+            presets["strucvars"]["karyotype-level-only"]["sv_size_min"],
+            5_000_000
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,6 @@
+//! Implementation of the `presets *` subcommands.
+//!
+//! The query settings presets are bundled with the worker so that VarFish Server can
+//! offer preset pickers without duplicating the settings.
+
+pub mod dump;
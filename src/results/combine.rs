@@ -0,0 +1,157 @@
+//! Implementation of the `results combine` subcommand.
+
+use std::io::Write as _;
+
+use super::{read_results, ResultLine};
+
+/// The set operation to apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Op {
+    /// Records in any of the input files.
+    Union,
+    /// Records of the first input file that are in all other input files.
+    Intersect,
+    /// Records of the first input file that are in none of the other input files.
+    Subtract,
+}
+
+/// Command line arguments for `results combine` subcommand.
+#[derive(Debug, clap::Parser)]
+#[command(author, version, about = "Combine JSONL query result files with set operations", long_about = None)]
+pub struct Args {
+    /// The set operation to apply.
+    #[arg(long, value_enum)]
+    pub op: Op,
+    /// Paths to the JSONL result files.
+    #[arg(long, required = true)]
+    pub path_in: Vec<String>,
+    /// Path to the output JSONL file.
+    #[arg(long)]
+    pub path_out: String,
+}
+
+/// Apply `op` to the records of `inputs`, returning the selected lines.
+///
+/// The records are returned in the order of the input files and the first occurrence of
+/// each variant is kept.
+fn combine(op: Op, inputs: &[Vec<ResultLine>]) -> Vec<String> {
+    let (first, others) = inputs.split_first().expect("must have at least one input");
+    let other_keys = others
+        .iter()
+        .map(|records| {
+            records
+                .iter()
+                .map(|result| result.key.as_str())
+                .collect::<std::collections::HashSet<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut seen = std::collections::HashSet::new();
+    let candidates: Box<dyn Iterator<Item = &ResultLine>> = match op {
+        Op::Union => Box::new(inputs.iter().flatten()),
+        Op::Intersect | Op::Subtract => Box::new(first.iter()),
+    };
+    candidates
+        .filter(|result| match op {
+            Op::Union => true,
+            Op::Intersect => other_keys
+                .iter()
+                .all(|keys| keys.contains(result.key.as_str())),
+            Op::Subtract => !other_keys
+                .iter()
+                .any(|keys| keys.contains(result.key.as_str())),
+        })
+        .filter(|result| seen.insert(result.key.clone()))
+        .map(|result| result.line.clone())
+        .collect()
+}
+
+/// Main entry point for `results combine` sub command.
+pub fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::info!("args_common = {:?}", &args_common);
+    tracing::info!("args = {:?}", &args);
+
+    let inputs = args
+        .path_in
+        .iter()
+        .map(|path| read_results(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let lines = combine(args.op, &inputs);
+    tracing::info!(
+        "{:?} of {} input files yields {} records",
+        args.op,
+        inputs.len(),
+        lines.len()
+    );
+
+    let mut writer = crate::common::io::std::open_write_maybe_bgzf(
+        &args.path_out,
+        args_common.compression_level,
+    )?;
+    for line in &lines {
+        writeln!(writer, "{}", line)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Op;
+
+    #[rstest::rstest]
+    #[case(Op::Union, vec![1, 2, 3, 4])]
+    #[case(Op::Intersect, vec![2])]
+    #[case(Op::Subtract, vec![1])]
+    fn run(#[case] op: Op, #[case] expected: Vec<i32>) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let record = |start: i32, score: &str| {
+            serde_json::json!({
+                "release": "GRCh37",
+                "chromosome": "1",
+                "start": start,
+                "end": start,
+                "reference": "A",
+                "alternative": "G",
+                "payload": {"score": score},
+            })
+            .to_string()
+        };
+        let inputs = [
+            vec![record(1, "a"), record(2, "a"), record(3, "a")],
+            vec![record(2, "b"), record(3, "b"), record(4, "b")],
+            vec![record(2, "c"), record(4, "c")],
+        ];
+        let mut path_in = Vec::new();
+        for (i, lines) in inputs.iter().enumerate() {
+            let path = tmpdir.join(format!("in-{}.jsonl", i));
+            std::fs::write(&path, lines.join("\n"))?;
+            path_in.push(path.to_str().unwrap().to_string());
+        }
+        let path_out = tmpdir.join("out.jsonl");
+
+        let args = super::Args {
+            op,
+            path_in,
+            path_out: path_out.to_str().unwrap().into(),
+        };
+        super::run(&crate::common::Args::default(), &args)?;
+
+        let records = std::fs::read_to_string(&path_out)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<serde_json::Value>, _>>()?;
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| record["start"].as_i64().unwrap() as i32)
+                .collect::<Vec<_>>(),
+            expected
+        );
+        // The records are taken from the first file that contains them.
+        assert_eq!(records[0]["payload"]["score"], "a");
+
+        Ok(())
+    }
+}
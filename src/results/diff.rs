@@ -0,0 +1,355 @@
+//! Implementation of the `results diff` subcommand.
+
+use std::collections::BTreeMap;
+use std::io::Write as _;
+
+use indexmap::IndexMap;
+
+use super::{read_results, ResultLine, IDENTITY_FIELDS};
+
+/// Command line arguments for `results diff` subcommand.
+#[derive(Debug, clap::Parser)]
+#[command(author, version, about = "Compare two JSONL query result files", long_about = None)]
+pub struct Args {
+    /// Path to the old JSONL result file, e.g., of the reference worker version.
+    pub path_old: String,
+    /// Path to the new JSONL result file.
+    pub path_new: String,
+    /// Path to the output JSON report, standard output if not given.
+    #[arg(long)]
+    pub path_out: Option<String>,
+    /// Fields to ignore in the comparison, including their nested fields.
+    #[arg(long, default_value = "sodar_uuid")]
+    pub ignore_field: Vec<String>,
+    /// Fail if there are any differences.
+    #[arg(long)]
+    pub fail_on_diff: bool,
+}
+
+/// Summary statistics of the comparison.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Summary {
+    /// Number of records in the old file.
+    pub old: usize,
+    /// Number of records in the new file.
+    pub new: usize,
+    /// Number of records that are equal in both files.
+    pub unchanged: usize,
+    /// Number of records only in the new file.
+    pub added: usize,
+    /// Number of records only in the old file.
+    pub removed: usize,
+    /// Number of records in both files with differing fields.
+    pub changed: usize,
+    /// Number of changed records by differing field.
+    pub changed_fields: BTreeMap<String, usize>,
+}
+
+/// Difference in one field of a record.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct FieldDiff {
+    /// Path of the field, e.g., `payload.gene_related.identity.hgnc_symbol`.
+    pub field: String,
+    /// Value in the old file, `null` if missing.
+    pub old: serde_json::Value,
+    /// Value in the new file, `null` if missing.
+    pub new: serde_json::Value,
+}
+
+/// A record in both files with differing fields.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ChangedRecord {
+    /// The identity fields of the variant.
+    pub variant: serde_json::Map<String, serde_json::Value>,
+    /// The differing fields.
+    pub differences: Vec<FieldDiff>,
+}
+
+/// The report written by `results diff`.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize)]
+pub struct Report {
+    /// Summary statistics.
+    pub summary: Summary,
+    /// The identity fields of the variants only in the new file.
+    pub added: Vec<serde_json::Map<String, serde_json::Value>>,
+    /// The identity fields of the variants only in the old file.
+    pub removed: Vec<serde_json::Map<String, serde_json::Value>>,
+    /// The records with differing fields.
+    pub changed: Vec<ChangedRecord>,
+}
+
+impl Report {
+    /// Whether the files differ.
+    pub fn has_differences(&self) -> bool {
+        self.summary.added + self.summary.removed + self.summary.changed > 0
+    }
+}
+
+/// Return the identity fields of the result record `value`.
+fn variant(value: &serde_json::Value) -> serde_json::Map<String, serde_json::Value> {
+    IDENTITY_FIELDS
+        .iter()
+        .filter_map(|field| value.get(field).map(|v| (field.to_string(), v.clone())))
+        .collect()
+}
+
+/// Flatten `value` into `fields` by dotted path, with array elements by index.
+fn flatten(
+    prefix: &str,
+    value: &serde_json::Value,
+    fields: &mut BTreeMap<String, serde_json::Value>,
+) {
+    let path = |key: &str| {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", prefix, key)
+        }
+    };
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                flatten(&path(key), value, fields);
+            }
+        }
+        serde_json::Value::Array(values) => {
+            for (i, value) in values.iter().enumerate() {
+                flatten(&path(&i.to_string()), value, fields);
+            }
+        }
+        _ => {
+            fields.insert(prefix.to_string(), value.clone());
+        }
+    }
+}
+
+/// Return the differing fields of `old` and `new`, except for `ignore_fields`.
+fn diff_fields(
+    old: &serde_json::Value,
+    new: &serde_json::Value,
+    ignore_fields: &[String],
+) -> Vec<FieldDiff> {
+    let mut old_fields = BTreeMap::new();
+    flatten("", old, &mut old_fields);
+    let mut new_fields = BTreeMap::new();
+    flatten("", new, &mut new_fields);
+
+    let is_ignored = |field: &str| {
+        ignore_fields.iter().any(|ignored| {
+            field == ignored.as_str()
+                || field
+                    .strip_prefix(ignored.as_str())
+                    .map(|rest| rest.starts_with('.'))
+                    .unwrap_or(false)
+        })
+    };
+    old_fields
+        .keys()
+        .chain(new_fields.keys())
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .filter(|field| !is_ignored(field.as_str()))
+        .filter_map(|field| {
+            let old = old_fields.get(field).cloned().unwrap_or_default();
+            let new = new_fields.get(field).cloned().unwrap_or_default();
+            if old == new {
+                None
+            } else {
+                Some(FieldDiff {
+                    field: field.clone(),
+                    old,
+                    new,
+                })
+            }
+        })
+        .collect()
+}
+
+/// Compare the records of `old` and `new`.
+///
+/// Records are matched by variant identity; the first record of each variant is used.
+pub fn diff(old: &[ResultLine], new: &[ResultLine], ignore_fields: &[String]) -> Report {
+    fn by_key(results: &[ResultLine]) -> IndexMap<&str, &serde_json::Value> {
+        let mut by_key = IndexMap::new();
+        for result in results {
+            by_key.entry(result.key.as_str()).or_insert(&result.value);
+        }
+        by_key
+    }
+    let old = by_key(old);
+    let new = by_key(new);
+
+    let mut report = Report {
+        summary: Summary {
+            old: old.len(),
+            new: new.len(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    for (key, old_value) in &old {
+        match new.get(key) {
+            None => report.removed.push(variant(old_value)),
+            Some(new_value) => {
+                let differences = diff_fields(old_value, new_value, ignore_fields);
+                if differences.is_empty() {
+                    report.summary.unchanged += 1;
+                } else {
+                    for difference in &differences {
+                        *report
+                            .summary
+                            .changed_fields
+                            .entry(difference.field.clone())
+                            .or_default() += 1;
+                    }
+                    report.changed.push(ChangedRecord {
+                        variant: variant(old_value),
+                        differences,
+                    });
+                }
+            }
+        }
+    }
+    report.added = new
+        .iter()
+        .filter(|(key, _)| !old.contains_key(*key))
+        .map(|(_, new_value)| variant(new_value))
+        .collect();
+    report.summary.added = report.added.len();
+    report.summary.removed = report.removed.len();
+    report.summary.changed = report.changed.len();
+
+    report
+}
+
+/// Main entry point for `results diff` sub command.
+pub fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::info!("args_common = {:?}", &args_common);
+    tracing::info!("args = {:?}", &args);
+
+    let report = diff(
+        &read_results(&args.path_old)?,
+        &read_results(&args.path_new)?,
+        &args.ignore_field,
+    );
+    tracing::info!(
+        "{} unchanged, {} added, {} removed, {} changed records",
+        report.summary.unchanged,
+        report.summary.added,
+        report.summary.removed,
+        report.summary.changed
+    );
+
+    let mut writer: Box<dyn std::io::Write> = match args.path_out.as_ref() {
+        Some(path_out) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path_out)
+                .map_err(|e| anyhow::anyhow!("could not create {}: {}", path_out, e))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    serde_json::to_writer_pretty(&mut writer, &report)
+        .map_err(|e| anyhow::anyhow!("could not write report: {}", e))?;
+    writeln!(writer)?;
+    writer.flush()?;
+
+    if args.fail_on_diff && report.has_differences() {
+        anyhow::bail!(
+            "results differ: {} added, {} removed, {} changed records",
+            report.summary.added,
+            report.summary.removed,
+            report.summary.changed
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::results::{identity, ResultLine};
+
+    fn result(start: i32, uuid: &str, symbol: &str) -> ResultLine {
+        let value = serde_json::json!({
+            "sodar_uuid": uuid,
+            "release": "GRCh37",
+            "chromosome": "1",
+            "start": start,
+            "end": start,
+            "reference": "A",
+            "alternative": "G",
+            "payload": {"gene": {"symbol": symbol}, "frequencies": [0.1, 0.2]},
+        });
+        ResultLine {
+            key: identity(&value),
+            line: value.to_string(),
+            value,
+        }
+    }
+
+    #[test]
+    fn diff() {
+        let old = vec![
+            result(1, "a", "X"),
+            result(2, "b", "Y"),
+            result(3, "c", "Z"),
+        ];
+        let new = vec![
+            result(2, "d", "Y"),
+            result(3, "e", "W"),
+            result(4, "f", "V"),
+        ];
+
+        let report = super::diff(&old, &new, &["sodar_uuid".to_string()]);
+
+        assert_eq!(
+            report.summary,
+            super::Summary {
+                old: 3,
+                new: 3,
+                unchanged: 1,
+                added: 1,
+                removed: 1,
+                changed: 1,
+                changed_fields: [("payload.gene.symbol".to_string(), 1)].into(),
+            }
+        );
+        assert_eq!(report.added[0]["start"], 4);
+        assert_eq!(report.removed[0]["start"], 1);
+        assert_eq!(report.changed[0].variant["start"], 3);
+        assert_eq!(
+            report.changed[0].differences,
+            vec![super::FieldDiff {
+                field: "payload.gene.symbol".into(),
+                old: "Z".into(),
+                new: "W".into(),
+            }]
+        );
+        assert!(report.has_differences());
+    }
+
+    #[test]
+    fn run_fail_on_diff() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_old = tmpdir.join("old.jsonl");
+        std::fs::write(&path_old, result(1, "a", "X").line)?;
+        let path_new = tmpdir.join("new.jsonl");
+        std::fs::write(&path_new, result(1, "b", "Y").line)?;
+        let path_out = tmpdir.join("report.json");
+
+        let mut args = super::Args {
+            path_old: path_old.to_str().unwrap().into(),
+            path_new: path_old.to_str().unwrap().into(),
+            path_out: Some(path_out.to_str().unwrap().into()),
+            ignore_field: vec!["sodar_uuid".into()],
+            fail_on_diff: true,
+        };
+        super::run(&crate::common::Args::default(), &args)?;
+
+        args.path_new = path_new.to_str().unwrap().into();
+        assert!(super::run(&crate::common::Args::default(), &args).is_err());
+        let report: serde_json::Value = serde_json::from_reader(std::fs::File::open(&path_out)?)?;
+        assert_eq!(report["summary"]["changed"], 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+//! Implementation of the `results *` subcommands.
+//!
+//! These work on the JSONL result files written by `{seqvars,strucvars} query
+//! --output-format jsonl`, e.g., to compare the results of different presets.
+
+use std::io::BufRead as _;
+
+pub mod combine;
+pub mod diff;
+
+/// Fields of the result records that identify the variant.
+///
+/// Fields that are missing in a record are ignored, such that the same keys work for
+/// both `seqvars query` and `strucvars query` results.
+pub const IDENTITY_FIELDS: &[&str] = &[
+    "release",
+    "chromosome",
+    "start",
+    "end",
+    "reference",
+    "alternative",
+    "chromosome2",
+    "sv_type",
+];
+
+/// One line of a JSONL result file.
+#[derive(Debug, Clone)]
+pub struct ResultLine {
+    /// Identity key of the variant, see `IDENTITY_FIELDS`.
+    pub key: String,
+    /// The parsed record.
+    pub value: serde_json::Value,
+    /// The original line.
+    pub line: String,
+}
+
+/// Return the identity key of the result record `value`.
+pub fn identity(value: &serde_json::Value) -> String {
+    IDENTITY_FIELDS
+        .iter()
+        .map(|field| value.get(field).map(|v| v.to_string()).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\t")
+}
+
+/// Read the JSONL result file at `path`.
+pub fn read_results(path: &str) -> Result<Vec<ResultLine>, anyhow::Error> {
+    let reader = std::io::BufReader::new(
+        std::fs::File::open(path).map_err(|e| anyhow::anyhow!("could not open {}: {}", path, e))?,
+    );
+    reader
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| {
+            let line = line?;
+            let value: serde_json::Value = serde_json::from_str(&line)
+                .map_err(|e| anyhow::anyhow!("could not parse record in {}: {}", path, e))?;
+            Ok(ResultLine {
+                key: identity(&value),
+                value,
+                line,
+            })
+        })
+        .collect()
+}
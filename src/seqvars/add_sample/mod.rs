@@ -0,0 +1,589 @@
+//! Implementation of `seqvars add-sample` subcommand.
+//!
+//! This supports the "add sibling later" workflow: the genotypes of one or more samples
+//! from an additional ingested VCF file are merged into the ingested VCF file of an
+//! existing case, instead of re-ingesting the whole family.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use mehari::common::{
+    io::std::is_gz,
+    noodles::{open_vcf_reader, AsyncVcfReader, AsyncVcfWriter},
+};
+use noodles_vcf as vcf;
+use thousands::Separable;
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    common::{
+        self,
+        noodles::{open_vcf_writer, WriteIndex},
+    },
+    flush_and_shutdown,
+    seqvars::query::case_store,
+};
+
+/// Command line arguments for `seqvars add-sample` subcommand.
+#[derive(Debug, Clone, clap::Parser)]
+#[command(author, version, about = "add samples to an ingested case VCF", long_about = None)]
+pub struct Args {
+    /// Path to the ingested VCF file of the existing case.
+    #[clap(long)]
+    pub path_case: String,
+    /// Path to the ingested VCF file with the sample(s) to add.
+    #[clap(long)]
+    pub path_sample: String,
+    /// Path to the output file, may be the same as `--path-case`.
+    #[clap(long)]
+    pub path_out: String,
+    /// Path to the updated pedigree file; optional.
+    ///
+    /// If not given, the pedigrees from the headers of both input files are combined.
+    #[clap(long)]
+    pub path_ped: Option<String>,
+    /// Path to the per-case RocksDB of the case; optional.
+    ///
+    /// If given, the per-case RocksDB is re-materialized from the output file.
+    #[clap(long)]
+    pub path_case_rocksdb: Option<String>,
+    /// Number of hours after which a lock of the output file held by a job on another
+    /// host is considered stale.
+    #[clap(long, default_value_t = 48)]
+    pub stale_lock_hours: u64,
+    /// The index to write for bgzip-compressed output.
+    #[clap(long, value_enum, default_value = "auto")]
+    pub write_index: WriteIndex,
+}
+
+/// Return the path of the temporary file to write to before renaming to `path`.
+///
+/// The file name keeps the suffix of `path` so the compression is the same.
+fn tmp_path(path: &str) -> Result<PathBuf, anyhow::Error> {
+    let path = Path::new(path);
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("invalid output path: {:?}", path))?
+        .to_string_lossy();
+    Ok(path.with_file_name(format!(".add-sample-{}.{}", std::process::id(), file_name)))
+}
+
+/// Combine the pedigrees from the case and sample headers.
+fn merge_pedigrees(
+    case_header: &vcf::Header,
+    sample_header: &vcf::Header,
+) -> Result<mehari::ped::PedigreeByName, anyhow::Error> {
+    let (mut pedigree, case_uuid) = common::extract_pedigree_and_case_uuid(case_header)
+        .map_err(|e| anyhow::anyhow!("problem extracting pedigree from case: {}", e))?;
+    let (sample_pedigree, sample_case_uuid) = common::extract_pedigree_and_case_uuid(sample_header)
+        .map_err(|e| anyhow::anyhow!("problem extracting pedigree from sample: {}", e))?;
+    if case_uuid != sample_case_uuid {
+        tracing::warn!(
+            "{}: case UUID {} of sample file differs from case UUID {}",
+            crate::codes::ADD_SAMPLE_CASE_UUID_MISMATCH,
+            &sample_case_uuid,
+            &case_uuid
+        );
+    }
+
+    for (name, individual) in sample_pedigree.individuals {
+        pedigree.individuals.entry(name).or_insert(individual);
+    }
+    Ok(pedigree)
+}
+
+/// Build the output header from the case header for the given samples.
+///
+/// The `SAMPLE` and `PEDIGREE` header records are rebuilt from `pedigree` and the
+/// `FORMAT` header records of `sample_header` are added if missing.
+fn build_output_header(
+    case_header: &vcf::Header,
+    sample_header: &vcf::Header,
+    pedigree: &mehari::ped::PedigreeByName,
+) -> Result<vcf::Header, anyhow::Error> {
+    use vcf::header::record::value::{map::Other, Map};
+
+    let case_samples = case_header.sample_names();
+    let duplicates = sample_header
+        .sample_names()
+        .iter()
+        .filter(|name| case_samples.contains(*name))
+        .collect::<Vec<_>>();
+    if !duplicates.is_empty() {
+        anyhow::bail!("samples already present in case: {:?}", &duplicates);
+    }
+
+    let mut sample_names = case_samples
+        .iter()
+        .chain(sample_header.sample_names().iter())
+        .cloned()
+        .collect::<Vec<_>>();
+    sample_names.sort();
+
+    let ped_idv = pedigree.individuals.keys().cloned().collect::<HashSet<_>>();
+    let input_idv = sample_names.iter().cloned().collect::<HashSet<_>>();
+    if !ped_idv.eq(&input_idv) {
+        anyhow::bail!(
+            "pedigree individuals = {:?} != input individuals: {:?}",
+            &ped_idv,
+            &input_idv
+        )
+    }
+
+    let mut header = case_header.clone();
+    *header.sample_names_mut() = sample_names.iter().cloned().collect();
+    for (key, format) in sample_header.formats() {
+        if !header.formats().contains_key(key) {
+            header.formats_mut().insert(key.clone(), format.clone());
+        }
+    }
+
+    header.other_records_mut().shift_remove("SAMPLE");
+    header.other_records_mut().shift_remove("PEDIGREE");
+    for name in &sample_names {
+        let i = pedigree
+            .individuals
+            .get(name)
+            .expect("checked equality above");
+
+        // Add SAMPLE entry.
+        header.insert(
+            "SAMPLE".parse()?,
+            vcf::header::record::Value::Map(
+                i.name.clone(),
+                Map::<Other>::builder()
+                    .insert(
+                        "Sex".parse()?,
+                        mehari::annotate::strucvars::vcf_header::sex_str(i.sex),
+                    )
+                    .insert(
+                        "Disease".parse()?,
+                        mehari::annotate::strucvars::vcf_header::disease_str(i.disease),
+                    )
+                    .build()?,
+            ),
+        )?;
+
+        // Add PEDIGREE entry.
+        let mut map_builder = Map::<Other>::builder();
+        if let Some(father) = i.father.as_ref() {
+            map_builder = map_builder.insert("Father".parse()?, father.clone());
+        }
+        if let Some(mother) = i.mother.as_ref() {
+            map_builder = map_builder.insert("Mother".parse()?, mother.clone());
+        }
+        header.insert(
+            "PEDIGREE".parse()?,
+            vcf::header::record::Value::Map(i.name.clone(), map_builder.build()?),
+        )?;
+    }
+
+    Ok(header)
+}
+
+/// Key for ordering records: contig index in the output header and 1-based position.
+type PosKey = (usize, usize);
+
+/// Reads the records of one input file grouped by position.
+struct GroupedRecords {
+    /// The reader of the input file.
+    reader: AsyncVcfReader,
+    /// The header of the input file.
+    header: vcf::Header,
+    /// The next record that has been read but not returned yet.
+    next: Option<(PosKey, vcf::Record)>,
+}
+
+impl GroupedRecords {
+    /// Construct with the given `reader` and `header`.
+    fn new(reader: AsyncVcfReader, header: vcf::Header) -> Self {
+        Self {
+            reader,
+            header,
+            next: None,
+        }
+    }
+
+    /// Read the next record into `self.next` if it is empty.
+    async fn fill(&mut self, output_header: &vcf::Header) -> Result<(), anyhow::Error> {
+        if self.next.is_none() {
+            let mut record = vcf::Record::default();
+            if self
+                .reader
+                .read_record(&self.header, &mut record)
+                .await
+                .map_err(|e| anyhow::anyhow!("could not read VCF record: {}", e))?
+                > 0
+            {
+                let chrom = record.chromosome().to_string();
+                let contig_idx = output_header
+                    .contigs()
+                    .get_index_of(chrom.as_str())
+                    .ok_or_else(|| anyhow::anyhow!("unknown contig in record: {}", &chrom))?;
+                let pos = usize::from(record.position());
+                self.next = Some(((contig_idx, pos), record));
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the position of the next group of records, if any.
+    async fn peek_key(
+        &mut self,
+        output_header: &vcf::Header,
+    ) -> Result<Option<PosKey>, anyhow::Error> {
+        self.fill(output_header).await?;
+        Ok(self.next.as_ref().map(|(key, _)| *key))
+    }
+
+    /// Return all records at the next position.
+    async fn next_group(
+        &mut self,
+        output_header: &vcf::Header,
+    ) -> Result<Vec<vcf::Record>, anyhow::Error> {
+        let mut result = Vec::new();
+        let Some(key) = self.peek_key(output_header).await? else {
+            return Ok(result);
+        };
+        while self.peek_key(output_header).await? == Some(key) {
+            let (prev_key, record) = self.next.take().expect("checked above");
+            result.push(record);
+            self.fill(output_header).await?;
+            if let Some((next_key, _)) = self.next.as_ref() {
+                if *next_key < prev_key {
+                    anyhow::bail!("input file is not sorted by coordinate");
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Return whether the two records describe the same variant.
+fn same_variant(lhs: &vcf::Record, rhs: &vcf::Record) -> bool {
+    lhs.reference_bases() == rhs.reference_bases() && lhs.alternate_bases() == rhs.alternate_bases()
+}
+
+/// Build the output record from the case record and/or the sample record.
+///
+/// Samples without a record are written with a missing genotype (`./.`).
+fn merge_record(
+    case: Option<(&vcf::Record, &vcf::Header)>,
+    sample: Option<(&vcf::Record, &vcf::Header)>,
+    output_header: &vcf::Header,
+) -> Result<vcf::Record, anyhow::Error> {
+    use vcf::record::genotypes::{keys::key, sample::Value, Keys};
+
+    let mut record = case
+        .or(sample)
+        .map(|(record, _)| record.clone())
+        .expect("at least one record must be given");
+
+    let mut keys = Vec::new();
+    for (input_record, _) in case.iter().chain(sample.iter()) {
+        for key in input_record.genotypes().keys().iter() {
+            if !keys.contains(key) {
+                keys.push(key.clone());
+            }
+        }
+    }
+
+    let mut values = Vec::new();
+    for name in output_header.sample_names() {
+        let input_sample =
+            case.iter()
+                .chain(sample.iter())
+                .find_map(|(input_record, input_header)| {
+                    input_header
+                        .sample_names()
+                        .get_index_of(name)
+                        .and_then(|i| input_record.genotypes().get_index(i))
+                });
+        let sample_values = keys
+            .iter()
+            .map(|k| match input_sample.as_ref() {
+                Some(input_sample) => input_sample.get(k).flatten().cloned(),
+                None if *k == key::GENOTYPE => Some(Value::String("./.".into())),
+                None => None,
+            })
+            .collect::<Vec<_>>();
+        values.push(sample_values);
+    }
+
+    *record.genotypes_mut() = vcf::record::Genotypes::new(
+        Keys::try_from(keys).map_err(|e| anyhow::anyhow!("invalid genotype keys: {}", e))?,
+        values,
+    );
+    Ok(record)
+}
+
+/// Merge the records of `case` and `sample` and write them to `writer`.
+///
+/// Returns the number of written records.
+async fn merge_records(
+    case: &mut GroupedRecords,
+    sample: &mut GroupedRecords,
+    writer: &mut AsyncVcfWriter,
+    output_header: &vcf::Header,
+) -> Result<usize, anyhow::Error> {
+    let mut count = 0;
+    loop {
+        let case_key = case.peek_key(output_header).await?;
+        let sample_key = sample.peek_key(output_header).await?;
+        let (case_group, mut sample_group) = match (case_key, sample_key) {
+            (None, None) => break,
+            (Some(_), None) => (case.next_group(output_header).await?, Vec::new()),
+            (None, Some(_)) => (Vec::new(), sample.next_group(output_header).await?),
+            (Some(case_key), Some(sample_key)) => match case_key.cmp(&sample_key) {
+                std::cmp::Ordering::Less => (case.next_group(output_header).await?, Vec::new()),
+                std::cmp::Ordering::Greater => {
+                    (Vec::new(), sample.next_group(output_header).await?)
+                }
+                std::cmp::Ordering::Equal => (
+                    case.next_group(output_header).await?,
+                    sample.next_group(output_header).await?,
+                ),
+            },
+        };
+
+        // Records at the same position are not necessarily sorted by alleles, so
+        // match them within the group.
+        let mut records = Vec::new();
+        for case_record in &case_group {
+            let sample_record = sample_group
+                .iter()
+                .position(|sample_record| same_variant(case_record, sample_record))
+                .map(|i| sample_group.remove(i));
+            records.push(merge_record(
+                Some((case_record, &case.header)),
+                sample_record
+                    .as_ref()
+                    .map(|record| (record, &sample.header)),
+                output_header,
+            )?);
+        }
+        for sample_record in &sample_group {
+            records.push(merge_record(
+                None,
+                Some((sample_record, &sample.header)),
+                output_header,
+            )?);
+        }
+
+        for record in &records {
+            writer
+                .write_record(record)
+                .await
+                .map_err(|e| anyhow::anyhow!("failed to write record: {}", e))?;
+        }
+        count += records.len();
+    }
+
+    Ok(count)
+}
+
+/// Open the VCF file at `path` and read its header.
+async fn open_grouped(path: &str) -> Result<GroupedRecords, anyhow::Error> {
+    let mut reader = open_vcf_reader(path)
+        .await
+        .map_err(|e| anyhow::anyhow!("could not open file {} for reading: {}", path, e))?;
+    let header = reader
+        .read_header()
+        .await
+        .map_err(|e| anyhow::anyhow!("problem reading header of {}: {}", path, e))?;
+    Ok(GroupedRecords::new(reader, header))
+}
+
+/// Main entry point for `seqvars add-sample` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    let before_anything = std::time::Instant::now();
+    tracing::info!("args_common = {:#?}", &args_common);
+    tracing::info!("args = {:#?}", &args);
+
+    tracing::info!("acquiring lock for {}...", &args.path_out);
+    let _lock = common::lock::LockFile::acquire(
+        &args.path_out,
+        std::time::Duration::from_secs(args.stale_lock_hours * 3600),
+    )?;
+
+    tracing::info!("opening input files...");
+    let mut case = open_grouped(&args.path_case).await?;
+    let mut sample = open_grouped(&args.path_sample).await?;
+
+    let pedigree = if let Some(path_ped) = args.path_ped.as_ref() {
+        mehari::ped::PedigreeByName::from_path(path_ped)
+            .map_err(|e| anyhow::anyhow!("problem parsing PED file: {}", e))?
+    } else {
+        merge_pedigrees(&case.header, &sample.header)?
+    };
+    let output_header = build_output_header(&case.header, &sample.header, &pedigree)?;
+
+    let path_tmp = tmp_path(&args.path_out)?;
+    let path_tmp_str = path_tmp.to_string_lossy().to_string();
+    let partial_guard =
+        common::partial::PartialOutputGuard::new(&path_tmp_str, common::partial::OnFailure::Remove);
+    {
+        let mut writer = open_vcf_writer(&path_tmp_str, args_common.compression_level).await?;
+        writer
+            .write_header(&output_header)
+            .await
+            .map_err(|e| anyhow::anyhow!("problem writing header: {}", e))?;
+
+        tracing::info!("merging records...");
+        let count = merge_records(&mut case, &mut sample, &mut writer, &output_header).await?;
+        tracing::info!("... wrote {} records", count.separate_with_commas());
+
+        flush_and_shutdown!(writer);
+    }
+
+    // Build the index for the temporary file and move both into place.
+    if is_gz(&args.path_out) {
+        let format = args.write_index.resolve(&output_header);
+        let suffix = format.suffix();
+        let path_tmp_index = format!("{}.{}", &path_tmp_str, suffix);
+        tracing::info!("writing {} index...", suffix);
+        common::noodles::build_index(&path_tmp, &path_tmp_index, format)
+            .await
+            .map_err(|e| anyhow::anyhow!("problem building index: {}", e))?;
+        std::fs::rename(&path_tmp_index, format!("{}.{}", &args.path_out, suffix))?;
+    }
+    std::fs::rename(&path_tmp, &args.path_out)?;
+    partial_guard.finish();
+
+    if let Some(path_case_rocksdb) = args.path_case_rocksdb.as_ref() {
+        // The per-case RocksDB is rebuilt as the output file has changed.
+        case_store::open(&args.path_out, Some(path_case_rocksdb)).await?;
+    }
+
+    tracing::info!(
+        "All of `seqvars add-sample` completed in {:?}",
+        before_anything.elapsed()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use futures::TryStreamExt;
+
+    #[rstest::rstest]
+    #[case::from_headers(None)]
+    #[case::from_ped(Some("tests/seqvars/add_sample/Case_1.ped"))]
+    #[tokio::test]
+    async fn smoke_test(#[case] path_ped: Option<&str>) -> Result<(), anyhow::Error> {
+        mehari::common::set_snapshot_suffix!(
+            "{}",
+            path_ped.map(|_| "from_ped").unwrap_or("from_headers")
+        );
+
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_out = tmpdir.join("out.vcf").to_str().unwrap().to_string();
+        let args = super::Args {
+            path_case: "tests/seqvars/add_sample/case.vcf".into(),
+            path_sample: "tests/seqvars/add_sample/sibling.vcf".into(),
+            path_out: path_out.clone(),
+            path_ped: path_ped.map(String::from),
+            path_case_rocksdb: None,
+            stale_lock_hours: 48,
+            write_index: Default::default(),
+        };
+
+        super::run(&crate::common::Args::default(), &args).await?;
+
+        assert!(!std::path::Path::new(&format!("{}.lock", &path_out)).exists());
+        insta::assert_snapshot!(std::fs::read_to_string(&path_out)?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn in_place_with_case_rocksdb() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_case = tmpdir.join("case.vcf.gz").to_str().unwrap().to_string();
+        let path_rocksdb = tmpdir.join("case-rocksdb").to_str().unwrap().to_string();
+
+        // Create bgzip-compressed case file and stale per-case RocksDB.
+        {
+            let mut reader =
+                mehari::common::noodles::open_vcf_reader("tests/seqvars/add_sample/case.vcf")
+                    .await?;
+            let header = reader.read_header().await?;
+            let mut writer = mehari::common::noodles::open_vcf_writer(&path_case).await?;
+            writer.write_header(&header).await?;
+            let mut record = noodles_vcf::Record::default();
+            while reader.read_record(&header, &mut record).await? > 0 {
+                writer.write_record(&record).await?;
+            }
+            use tokio::io::AsyncWriteExt;
+            crate::flush_and_shutdown!(writer);
+        }
+        crate::seqvars::query::case_store::open(&path_case, Some(&path_rocksdb)).await?;
+
+        let args = super::Args {
+            path_case: path_case.clone(),
+            path_sample: "tests/seqvars/add_sample/sibling.vcf".into(),
+            path_out: path_case.clone(),
+            path_ped: None,
+            path_case_rocksdb: Some(path_rocksdb.clone()),
+            stale_lock_hours: 48,
+            write_index: Default::default(),
+        };
+        super::run(&crate::common::Args::default(), &args).await?;
+
+        assert!(std::path::Path::new(&format!("{}.tbi", &path_case)).exists());
+        let store =
+            crate::seqvars::query::case_store::open(&path_case, Some(&path_rocksdb)).await?;
+        let variants = store.variants().try_collect::<Vec<_>>().await?;
+        assert_eq!(variants.len(), 6);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn duplicate_sample() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let args = super::Args {
+            path_case: "tests/seqvars/add_sample/case.vcf".into(),
+            path_sample: "tests/seqvars/add_sample/case.vcf".into(),
+            path_out: tmpdir.join("out.vcf").to_str().unwrap().to_string(),
+            path_ped: None,
+            path_case_rocksdb: None,
+            stale_lock_hours: 48,
+            write_index: Default::default(),
+        };
+
+        assert!(super::run(&crate::common::Args::default(), &args)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn locked_output() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_out = tmpdir.join("out.vcf").to_str().unwrap().to_string();
+        let args = super::Args {
+            path_case: "tests/seqvars/add_sample/case.vcf".into(),
+            path_sample: "tests/seqvars/add_sample/sibling.vcf".into(),
+            path_out: path_out.clone(),
+            path_ped: None,
+            path_case_rocksdb: None,
+            stale_lock_hours: 48,
+            write_index: Default::default(),
+        };
+
+        let _lock = crate::common::lock::LockFile::acquire(
+            &path_out,
+            std::time::Duration::from_secs(3600),
+        )?;
+        let err = super::run(&crate::common::Args::default(), &args)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().starts_with("E-COMMON-0001"));
+
+        Ok(())
+    }
+}
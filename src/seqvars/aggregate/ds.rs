@@ -43,6 +43,14 @@ impl Counts {
         self.count_hom += other.count_hom;
         self.count_hemi += other.count_hemi;
     }
+
+    /// Subtract other from self, e.g., to remove the contribution of a case.
+    pub fn subtract(&mut self, other: &Self) {
+        self.count_an = self.count_an.saturating_sub(other.count_an);
+        self.count_het = self.count_het.saturating_sub(other.count_het);
+        self.count_hom = self.count_hom.saturating_sub(other.count_hom);
+        self.count_hemi = self.count_hemi.saturating_sub(other.count_hemi);
+    }
 }
 
 /// Genotype in a carrier.
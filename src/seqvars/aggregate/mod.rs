@@ -1,14 +1,16 @@
 //! Implementation of `seqvars aggregate` subcommand.
 
 pub mod ds;
+pub mod reader;
 
 use futures::TryStreamExt;
 use mehari::common::noodles::open_vcf_reader;
 use noodles_vcf as vcf;
 use rayon::prelude::*;
 use std::sync::Arc;
+use thousands::Separable;
 
-use crate::common::{self, Chrom, Genotype};
+use crate::common::{self, karyotype::Karyotypes, Chrom, Genotype};
 
 /// Command line arguments for `seqvars aggregate` subcommand.
 #[derive(Debug, clap::Parser)]
@@ -30,6 +32,12 @@ pub struct Args {
     /// Column family name for the carrier UUID data.
     #[clap(long, default_value = "carriers")]
     pub cf_carriers: String,
+    /// Column family name for the counts contributed by each case, used for removing cases.
+    #[clap(long, default_value = "contributions")]
+    pub cf_contributions: String,
+    /// UUIDs of cases to remove from the database, e.g., after consent was withdrawn.
+    #[clap(long, value_delimiter = ',')]
+    pub remove_case: Vec<uuid::Uuid>,
     /// Set the number of threads to use, defaults to number of cores.
     #[clap(long)]
     pub num_threads: Option<usize>,
@@ -37,6 +45,14 @@ pub struct Args {
     /// Optional path to RocksDB WAL directory.
     #[arg(long)]
     pub path_wal_dir: Option<String>,
+    /// Optional path to a TSV file with the karyotypes of samples with non-standard
+    /// sex chromosome karyotypes, e.g., `XXY` or `X0`.
+    #[arg(long)]
+    pub path_karyotypes: Option<String>,
+    /// Number of hours after which a lock of the output RocksDB held by a job on another
+    /// host is considered stale.
+    #[arg(long, default_value_t = 48)]
+    pub stale_lock_hours: u64,
 }
 
 /// Extract counts and carrier data from a single VCF record.
@@ -45,6 +61,7 @@ fn handle_record(
     input_header: &vcf::Header,
     pedigree: &mehari::ped::PedigreeByName,
     case_uuid: &uuid::Uuid,
+    karyotypes: &Karyotypes,
 ) -> Result<(ds::Counts, ds::CarrierList), anyhow::Error> {
     let chrom: Chrom =
         annonars::common::cli::canonicalize(input_record.chromosome().to_string().as_str())
@@ -75,7 +92,13 @@ fn handle_record(
                 continue; // skip, no-call or empty
             };
 
-        let carrier_genotype = match (chrom, individual.sex, genotype) {
+        // On the gonosomes, count the samples by the ploidy of their karyotype.
+        let sex = match (chrom, karyotypes.get(name, individual.sex)) {
+            (Chrom::Auto, _) => individual.sex,
+            (chrom, Some(karyotype)) => karyotype.effective_sex(chrom),
+            (_, None) => mehari::ped::Sex::Unknown,
+        };
+        let carrier_genotype = match (chrom, sex, genotype) {
             (_, _, Genotype::WithNoCall) => continue,
             // on the autosomes, male/female count the same
             (Chrom::Auto, _, Genotype::HomRef) => {
@@ -144,37 +167,193 @@ fn handle_record(
     Ok((res_counts, res_carriers))
 }
 
+/// Return the key of the counts contributed by the case `case_uuid` to the variant `key`.
+///
+/// The keys start with the case UUID such that the contributions of a case are adjacent.
+fn contribution_key(case_uuid: &uuid::Uuid, key: &[u8]) -> Vec<u8> {
+    let mut result = case_uuid.as_bytes().to_vec();
+    result.extend_from_slice(key);
+    result
+}
+
+/// Return the key in the `meta` column family that marks the import of the case
+/// `case_uuid` as complete.
+fn completion_key(case_uuid: &uuid::Uuid) -> Vec<u8> {
+    format!("case-complete:{}", case_uuid).into_bytes()
+}
+
+/// Iterate the contributions of the case `case_uuid` as pairs of variant key and counts.
+fn case_contributions<'a>(
+    db: &'a rocksdb::TransactionDB<rocksdb::MultiThreaded>,
+    cf_contributions: &impl rocksdb::AsColumnFamilyRef,
+    case_uuid: &'a uuid::Uuid,
+) -> impl Iterator<Item = Result<(Vec<u8>, ds::Counts), anyhow::Error>> + 'a {
+    let prefix = case_uuid.as_bytes();
+    db.iterator_cf(
+        cf_contributions,
+        rocksdb::IteratorMode::From(prefix, rocksdb::Direction::Forward),
+    )
+    .map(|item| item.map_err(|e| anyhow::anyhow!("problem reading contributions: {}", e)))
+    .take_while(move |item| match item {
+        Ok((key, _)) => key.starts_with(prefix),
+        Err(_) => true,
+    })
+    .map(move |item| {
+        item.map(|(key, value)| (key[prefix.len()..].to_vec(), ds::Counts::from_vec(&value)))
+    })
+}
+
+/// Remove the contributions of the case `case_uuid` from the database.
+///
+/// Returns the number of variants the case contributed to.
+fn remove_case(
+    db: &rocksdb::TransactionDB<rocksdb::MultiThreaded>,
+    case_uuid: &uuid::Uuid,
+    cf_counts: &str,
+    cf_carriers: &str,
+    cf_contributions: &str,
+) -> Result<usize, anyhow::Error> {
+    let cf_meta = db.cf_handle("meta").expect("checked earlier");
+    let cf_counts = db.cf_handle(cf_counts).expect("checked earlier");
+    let cf_carriers = db.cf_handle(cf_carriers).expect("checked earlier");
+    let cf_contributions = db.cf_handle(cf_contributions).expect("checked earlier");
+
+    let completed = db.get_cf(&cf_meta, completion_key(case_uuid))?.is_some();
+    let mut count_variants = 0;
+    for item in case_contributions(db, &cf_contributions, case_uuid) {
+        let (key, contribution) = item?;
+
+        let transaction = db.transaction();
+        let mut db_counts_data = transaction
+            .get_cf(&cf_counts, &key)?
+            .map(|buffer| ds::Counts::from_vec(&buffer))
+            .unwrap_or_default();
+        let mut db_carrier_data = transaction
+            .get_cf(&cf_carriers, &key)?
+            .map(|buffer| ds::CarrierList::from_vec(&buffer))
+            .unwrap_or_default();
+
+        db_counts_data.subtract(&contribution);
+        db_carrier_data
+            .carriers
+            .retain(|carrier| carrier.uuid != *case_uuid);
+
+        if db_counts_data.count_an == 0 {
+            transaction.delete_cf(&cf_counts, &key)?;
+        } else {
+            transaction.put_cf(&cf_counts, &key, db_counts_data.to_vec())?;
+        }
+        if db_carrier_data.carriers.is_empty() {
+            transaction.delete_cf(&cf_carriers, &key)?;
+        } else {
+            transaction.put_cf(&cf_carriers, &key, db_carrier_data.to_vec())?;
+        }
+        transaction.delete_cf(&cf_contributions, contribution_key(case_uuid, &key))?;
+        transaction
+            .commit()
+            .map_err(|e| anyhow::anyhow!("problem removing case {}: {}", case_uuid, e))?;
+
+        count_variants += 1;
+    }
+
+    db.delete_cf(&cf_meta, completion_key(case_uuid))?;
+
+    if count_variants == 0 && !completed {
+        anyhow::bail!(
+            "case {} not found in database; cases aggregated before the contributions were \
+            recorded cannot be removed",
+            case_uuid
+        );
+    }
+    Ok(count_variants)
+}
+
 /// Import one VCF file into the database.
 ///
+/// The counts of each case are also written to `cf_contributions` for removing the case
+/// later.  The import of a case is marked as complete in the `meta` column family together
+/// with its last record; cases that are already in the database are skipped and the
+/// contributions of incomplete imports, e.g., of a killed job, are removed first.
+///
 /// This function is `async` because we potentially need to read from S3.
 async fn import_vcf(
     db: &Arc<rocksdb::TransactionDB<rocksdb::MultiThreaded>>,
     path_input: &str,
     cf_counts: &str,
     cf_carriers: &str,
+    cf_contributions: &str,
+    karyotypes: &Karyotypes,
 ) -> Result<(), anyhow::Error> {
     let mut input_reader = open_vcf_reader(path_input)
         .await
         .map_err(|e| anyhow::anyhow!("could not open file {} for reading: {}", path_input, e))?;
     let input_header = input_reader.read_header().await?;
 
+    let (pedigree, case_uuid) = common::extract_pedigree_and_case_uuid(&input_header)?;
+    let cf_meta = db.cf_handle("meta").expect("checked earlier");
+    if db.get_cf(&cf_meta, completion_key(&case_uuid))?.is_some() {
+        tracing::warn!(
+            "{}: case {} of {} is already in the database, skipping",
+            crate::codes::SEQVARS_AGGREGATE_CASE_SKIPPED,
+            &case_uuid,
+            path_input
+        );
+        return Ok(());
+    }
+    if case_contributions(
+        db,
+        &db.cf_handle(cf_contributions).expect("checked earlier"),
+        &case_uuid,
+    )
+    .next()
+    .transpose()?
+    .is_some()
+    {
+        tracing::warn!(
+            "{}: import of case {} was not completed, importing {} again",
+            crate::codes::SEQVARS_AGGREGATE_CASE_INCOMPLETE,
+            &case_uuid,
+            path_input
+        );
+        remove_case(db, &case_uuid, cf_counts, cf_carriers, cf_contributions)?;
+    }
+
     let cf_counts = db.cf_handle(cf_counts).expect("checked earlier");
     let cf_carriers = db.cf_handle(cf_carriers).expect("checked earlier");
+    let cf_contributions = db.cf_handle(cf_contributions).expect("checked earlier");
 
-    let (pedigree, case_uuid) = common::extract_pedigree_and_case_uuid(&input_header)?;
     let mut prev = std::time::Instant::now();
     let mut records = input_reader.records(&input_header);
-    while let Some(input_record) = records
+    // Read one record ahead to write the completion marker with the last record.
+    let mut next_record = records
         .try_next()
         .await
-        .map_err(|e| anyhow::anyhow!("problem reading VCF file {}: {}", path_input, e))?
-    {
+        .map_err(|e| anyhow::anyhow!("problem reading VCF file {}: {}", path_input, e))?;
+    if next_record.is_none() {
+        db.put_cf(
+            &cf_meta,
+            completion_key(&case_uuid),
+            common::worker_version(),
+        )?;
+    }
+    while let Some(input_record) = next_record {
+        next_record = records
+            .try_next()
+            .await
+            .map_err(|e| anyhow::anyhow!("problem reading VCF file {}: {}", path_input, e))?;
+
         // Obtain counts from the current variant.
-        let (this_counts_data, this_carrier_data) =
-            handle_record(&input_record, &input_header, &pedigree, &case_uuid)?;
+        let (this_counts_data, this_carrier_data) = handle_record(
+            &input_record,
+            &input_header,
+            &pedigree,
+            &case_uuid,
+            karyotypes,
+        )?;
         // Obtain annonars variant key from current allele for RocksDB lookup.
         let vcf_var = annonars::common::keys::Var::from_vcf_allele(&input_record, 0);
         let key: Vec<u8> = vcf_var.clone().into();
+        let contribution_key = contribution_key(&case_uuid, &key);
 
         let max_retries = 10;
         let mut retries = 0;
@@ -199,8 +378,20 @@ async fn import_vcf(
                         e
                     )
                 })?.map(|buffer| ds::CarrierList::from_vec(&buffer)).unwrap_or_default();
+            let mut db_contribution_data = transaction
+                .get_cf(&cf_contributions, contribution_key.clone())
+                .map_err(|e| {
+                    anyhow::anyhow!(
+                        "problem acessing contribution data for variant {:?}: {}",
+                        &vcf_var,
+                        e
+                    )
+                })?
+                .map(|buffer| ds::Counts::from_vec(&buffer))
+                .unwrap_or_default();
 
             // Aggregate the data.
+            db_contribution_data.aggregate(this_counts_data.clone());
             db_counts_data.aggregate(this_counts_data);
             db_carrier_data.aggregate(this_carrier_data);
 
@@ -223,6 +414,34 @@ async fn import_vcf(
                         e
                     )
                 })?;
+            transaction
+                .put_cf(
+                    &cf_contributions,
+                    contribution_key.clone(),
+                    &db_contribution_data.to_vec(),
+                )
+                .map_err(|e| {
+                    anyhow::anyhow!(
+                        "problem writing contribution data for variant {:?}: {}",
+                        &vcf_var,
+                        e
+                    )
+                })?;
+            if next_record.is_none() {
+                transaction
+                    .put_cf(
+                        &cf_meta,
+                        completion_key(&case_uuid),
+                        common::worker_version(),
+                    )
+                    .map_err(|e| {
+                        anyhow::anyhow!(
+                            "problem marking import of case {} as complete: {}",
+                            &case_uuid,
+                            e
+                        )
+                    })?;
+            }
 
             let res = transaction.commit();
             match res {
@@ -258,29 +477,74 @@ async fn import_vcf(
     Ok(())
 }
 
+/// Read the case UUID from the header of the VCF file at `path_input`.
+async fn read_case_uuid(path_input: &str) -> Result<uuid::Uuid, anyhow::Error> {
+    let mut input_reader = open_vcf_reader(path_input)
+        .await
+        .map_err(|e| anyhow::anyhow!("could not open file {} for reading: {}", path_input, e))?;
+    let input_header = input_reader.read_header().await?;
+    let (_, case_uuid) = common::extract_pedigree_and_case_uuid(&input_header)?;
+    Ok(case_uuid)
+}
+
+/// Build a Tokio scheduler for the VCF file `path_input` as we need it to wait / block
+/// for the reading of the file in the current Rayon thread.
+fn build_runtime(path_input: &str) -> Result<tokio::runtime::Runtime, anyhow::Error> {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "building Tokio runtime for VCF file {} failed: {}",
+                path_input,
+                e
+            )
+        })
+}
+
+/// Fail if two of the VCF files in `path_input` belong to the same case.
+///
+/// The files are imported concurrently such that both files of a case would be imported.
+fn check_unique_cases(path_input: &[&str]) -> Result<(), anyhow::Error> {
+    let case_uuids = path_input
+        .par_iter()
+        .map(|path_input| build_runtime(path_input)?.block_on(read_case_uuid(path_input)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut seen = std::collections::HashMap::new();
+    for (path_input, case_uuid) in path_input.iter().zip(case_uuids) {
+        if let Some(other) = seen.insert(case_uuid, path_input) {
+            anyhow::bail!(
+                "case {} occurs in both {} and {}",
+                case_uuid,
+                other,
+                path_input
+            );
+        }
+    }
+    Ok(())
+}
+
 /// Perform the parallel import of VCF files.
 fn vcf_import(
     db: &Arc<rocksdb::TransactionDB<rocksdb::MultiThreaded>>,
     path_input: &[&str],
     cf_counts: &str,
     cf_carriers: &str,
+    cf_contributions: &str,
+    karyotypes: &Karyotypes,
 ) -> Result<(), anyhow::Error> {
+    check_unique_cases(path_input)?;
     path_input
         .par_iter()
         .map(|path_input| {
-            // We create a Tokio scheduler for the current file as we need it
-            // to wait / block for the VCF import running in the current Rayon
-            // thread.
-            tokio::runtime::Builder::new_current_thread()
-                .build()
-                .map_err(|e| {
-                    anyhow::anyhow!(
-                        "building Tokio runtime for VCF file {} failed: {}",
-                        path_input,
-                        e
-                    )
-                })?
-                .block_on(import_vcf(db, path_input, cf_counts, cf_carriers))
+            build_runtime(path_input)?
+                .block_on(import_vcf(
+                    db,
+                    path_input,
+                    cf_counts,
+                    cf_carriers,
+                    cf_contributions,
+                    karyotypes,
+                ))
                 .map_err(|e| anyhow::anyhow!("processing VCF file {} failed: {}", path_input, e))
         })
         .collect::<Result<Vec<_>, _>>()
@@ -320,6 +584,12 @@ pub fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow:
             }
         })
         .collect::<Vec<_>>();
+    let karyotypes = Karyotypes::from_optional_path(args.path_karyotypes.as_deref())?;
+
+    let _lock = common::lock::LockFile::acquire(
+        &args.path_out_rocksdb,
+        std::time::Duration::from_secs(args.stale_lock_hours * 3600),
+    )?;
 
     tracing::info!("Opening RocksDB...");
     let options = rocksdb_utils_lookup::tune_options(
@@ -327,7 +597,12 @@ pub fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow:
         args.path_wal_dir.as_ref().map(|s| s.as_ref()),
     );
     let tx_options = rocksdb::TransactionDBOptions::default();
-    let cf_names = &["meta", &args.cf_counts, &args.cf_carriers];
+    let cf_names = &[
+        "meta",
+        &args.cf_counts,
+        &args.cf_carriers,
+        &args.cf_contributions,
+    ];
     let cf_descriptors = cf_names
         .iter()
         .map(|name| rocksdb::ColumnFamilyDescriptor::new(*name, options.clone()))
@@ -348,10 +623,33 @@ pub fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow:
         db.put_cf(&cf_meta, "db-name", "seqvars-aggregation")?;
         tracing::info!("... done opening RocksDB");
 
+        for case_uuid in &args.remove_case {
+            tracing::info!("Removing case {} ...", case_uuid);
+            let count_variants = remove_case(
+                &db,
+                case_uuid,
+                &args.cf_counts,
+                &args.cf_carriers,
+                &args.cf_contributions,
+            )?;
+            tracing::info!(
+                "... done removing case {} from {} variants",
+                case_uuid,
+                count_variants.separate_with_commas()
+            );
+        }
+
         tracing::info!("Importing VCF files ...");
         let before_import = std::time::Instant::now();
         let paths = path_input.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
-        vcf_import(&db, &paths, &args.cf_counts, &args.cf_carriers)?;
+        vcf_import(
+            &db,
+            &paths,
+            &args.cf_counts,
+            &args.cf_carriers,
+            &args.cf_contributions,
+            &karyotypes,
+        )?;
         tracing::info!(
             "... done importing VCF files in {:?}",
             before_import.elapsed()
@@ -400,10 +698,146 @@ mod test {
             let record = record.unwrap();
             let (pedigree, case_uuid) = common::extract_pedigree_and_case_uuid(&header).unwrap();
             let (counts, carriers) =
-                super::handle_record(&record, &header, &pedigree, &case_uuid).unwrap();
+                super::handle_record(&record, &header, &pedigree, &case_uuid, &Default::default())
+                    .unwrap();
 
             insta::assert_debug_snapshot!(counts);
             insta::assert_debug_snapshot!(carriers);
         }
     }
+
+    #[rstest::rstest]
+    #[case::x_default("X", "", vec![ds::Genotype::HemiAlt, ds::Genotype::Het])]
+    #[case::x_karyotypes(
+        "X",
+        "Case_1_father-N1-DNA1-WGS1\tXXY\nCase_1_index-N1-DNA1-WGS1\tX0\n",
+        vec![ds::Genotype::Het, ds::Genotype::HemiAlt]
+    )]
+    #[case::y_default("Y", "", vec![ds::Genotype::HemiAlt])]
+    #[case::y_karyotypes(
+        "Y",
+        "Case_1_father-N1-DNA1-WGS1\tX0\nCase_1_index-N1-DNA1-WGS1\tXXY\n",
+        vec![ds::Genotype::HemiAlt]
+    )]
+    fn handle_record_karyotypes(
+        #[case] chrom: &str,
+        #[case] karyotypes: &str,
+        #[case] expected: Vec<ds::Genotype>,
+    ) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_karyotypes = tmpdir.join("karyotypes.tsv").to_str().unwrap().to_string();
+        std::fs::write(&path_karyotypes, karyotypes)?;
+        let karyotypes = Karyotypes::from_path(&path_karyotypes)?;
+
+        let mut vcf_reader = vcf::reader::Builder::default()
+            .build_from_path("tests/seqvars/aggregate/ingest.vcf")?;
+        let header = vcf_reader.read_header()?;
+        let record = vcf::Record::try_from((
+            &header,
+            format!("{}\t100\t.\tA\tG\t.\t.\t.\tGT\t0/1\t0/1\t0/0", chrom).as_str(),
+        ))?;
+        let (pedigree, case_uuid) = common::extract_pedigree_and_case_uuid(&header)?;
+
+        let (_, carriers) =
+            super::handle_record(&record, &header, &pedigree, &case_uuid, &karyotypes)?;
+
+        assert_eq!(
+            carriers
+                .carriers
+                .iter()
+                .map(|carrier| carrier.genotype)
+                .collect::<Vec<_>>(),
+            expected
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn add_and_remove_case() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_out_rocksdb = tmpdir.join("rocksdb").to_str().unwrap().to_string();
+        let args = |path_input: Vec<String>, remove_case: Vec<uuid::Uuid>| Args {
+            genomebuild: crate::common::GenomeRelease::Grch37,
+            path_out_rocksdb: path_out_rocksdb.clone(),
+            path_input,
+            cf_counts: "counts".into(),
+            cf_carriers: "carriers".into(),
+            cf_contributions: "contributions".into(),
+            remove_case,
+            num_threads: None,
+            path_wal_dir: None,
+            path_karyotypes: None,
+            stale_lock_hours: 48,
+        };
+        // Count the entries of the counts and contributions column families.
+        let count_entries = || -> Result<(usize, usize), anyhow::Error> {
+            let db = rocksdb::DB::open_cf_for_read_only(
+                &rocksdb::Options::default(),
+                &path_out_rocksdb,
+                ["meta", "counts", "carriers", "contributions"],
+                false,
+            )?;
+            let count = |name: &str| {
+                db.iterator_cf(db.cf_handle(name).unwrap(), rocksdb::IteratorMode::Start)
+                    .count()
+            };
+            Ok((count("counts"), count("contributions")))
+        };
+        // Read the values of the counts column family.
+        let read_counts = || -> Result<Vec<Box<[u8]>>, anyhow::Error> {
+            let db = rocksdb::DB::open_cf_for_read_only(
+                &rocksdb::Options::default(),
+                &path_out_rocksdb,
+                ["meta", "counts", "carriers", "contributions"],
+                false,
+            )?;
+            db.iterator_cf(
+                db.cf_handle("counts").unwrap(),
+                rocksdb::IteratorMode::Start,
+            )
+            .map(|item| Ok(item?.1))
+            .collect()
+        };
+        let path_input = vec!["tests/seqvars/aggregate/ingest.vcf".to_string()];
+
+        // Two files of the same case are rejected.
+        assert!(run(
+            &Default::default(),
+            &args(vec![path_input[0].clone(), path_input[0].clone()], vec![])
+        )
+        .is_err());
+
+        run(&Default::default(), &args(path_input.clone(), vec![]))?;
+        let added = count_entries()?;
+        assert!(added.0 > 0);
+        assert_eq!(added.0, added.1);
+        let added_counts = read_counts()?;
+
+        // Adding the same case again is skipped.
+        run(&Default::default(), &args(path_input.clone(), vec![]))?;
+        assert_eq!(count_entries()?, added);
+
+        // An incomplete import is replaced instead of being counted twice.
+        {
+            let db = rocksdb::DB::open_cf(
+                &rocksdb::Options::default(),
+                &path_out_rocksdb,
+                ["meta", "counts", "carriers", "contributions"],
+            )?;
+            db.delete_cf(
+                db.cf_handle("meta").unwrap(),
+                super::completion_key(&uuid::Uuid::nil()),
+            )?;
+        }
+        run(&Default::default(), &args(path_input, vec![]))?;
+        assert_eq!(count_entries()?, added);
+        assert_eq!(read_counts()?, added_counts);
+
+        run(&Default::default(), &args(vec![], vec![uuid::Uuid::nil()]))?;
+        assert_eq!(count_entries()?, (0, 0));
+        assert!(run(&Default::default(), &args(vec![], vec![uuid::Uuid::nil()])).is_err());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,79 @@
+//! Reading of the in-house carrier counts from the RocksDB built by `seqvars aggregate`.
+
+use std::path::Path;
+
+use crate::common::kv::VariantKvStore;
+
+use super::ds;
+
+/// In-house frequency database built by `seqvars aggregate`.
+pub struct InhouseDb {
+    /// The opened store, usually RocksDB.
+    store: Box<dyn VariantKvStore>,
+    /// Name of the column family with the counts.
+    cf_counts: String,
+}
+
+impl InhouseDb {
+    /// Construct from `store` with the counts in table `cf_counts`.
+    pub fn new(store: Box<dyn VariantKvStore>, cf_counts: &str) -> Self {
+        Self {
+            store,
+            cf_counts: cf_counts.to_string(),
+        }
+    }
+
+    /// Open the database at `path` for reading the counts from column family `cf_counts`.
+    pub fn open<P: AsRef<Path>>(path: P, cf_counts: &str) -> Result<Self, anyhow::Error> {
+        let options = rocksdb::Options::default();
+        let db = rocksdb::DB::open_cf_for_read_only(&options, path.as_ref(), [cf_counts], false)
+            .map_err(|e| {
+                anyhow::anyhow!("could not open in-house RocksDB {:?}: {}", path.as_ref(), e)
+            })?;
+        Ok(Self::new(Box::new(db), cf_counts))
+    }
+
+    /// Return the counts for the RocksDB `key` of an `annonars::common::keys::Var`, if any.
+    pub fn counts_by_key(&self, key: &[u8]) -> Result<Option<ds::Counts>, anyhow::Error> {
+        Ok(self
+            .store
+            .get(&self.cf_counts, key)
+            .map_err(|e| anyhow::anyhow!("problem querying in-house database: {}", e))?
+            .map(|raw_value| ds::Counts::from_vec(&raw_value)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::seqvars::aggregate::ds;
+
+    #[test]
+    fn counts_by_key() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let key: Vec<u8> = annonars::common::keys::Var::from("1", 100, "A", "G").into();
+        {
+            let mut options = rocksdb::Options::default();
+            options.create_if_missing(true);
+            options.create_missing_column_families(true);
+            let db = rocksdb::DB::open_cf(&options, &*tmpdir, ["counts"])?;
+            let counts = ds::Counts {
+                count_an: 20,
+                count_het: 3,
+                count_hom: 1,
+                count_hemi: 0,
+            };
+            db.put_cf(&db.cf_handle("counts").unwrap(), &key, counts.to_vec())?;
+        }
+
+        let db = super::InhouseDb::open(&*tmpdir, "counts")?;
+        let found = db.counts_by_key(&key)?.expect("counts not found");
+        assert_eq!(
+            (found.count_an, found.count_het, found.count_hom),
+            (20, 3, 1)
+        );
+        let missing: Vec<u8> = annonars::common::keys::Var::from("1", 100, "A", "T").into();
+        assert!(db.counts_by_key(&missing)?.is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+//! Implementation of `seqvars annotate-region` subcommand.
+//!
+//! This annotates all variants in the given regions of a VCF file in the same way as
+//! `seqvars ingest` and prints the annotation, e.g., for debugging annotation questions
+//! without running a full case query.  The input may be an ingested or a raw VCF file;
+//! existing annotations are ignored and the variants are annotated afresh.
+
+use std::io::Write as _;
+
+use futures::TryStreamExt as _;
+use indexmap::{IndexMap, IndexSet};
+use thousands::Separable;
+
+use crate::{
+    common::{noodles::open_vcf_reader, GenomeRelease},
+    seqvars::ingest::{
+        alleles,
+        regions::{self, RecordStream, Regions},
+        RecordAnnotator,
+    },
+};
+
+/// Output format of `seqvars annotate-region`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Tab-separated table with one column per `INFO` field.
+    #[default]
+    Table,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Command line arguments for `seqvars annotate-region` subcommand.
+#[derive(Debug, Clone, clap::Parser)]
+#[command(author, version, about = "annotate all variants in a region of a VCF file", long_about = None)]
+pub struct Args {
+    /// The assumed genome build.
+    #[clap(long)]
+    pub genomebuild: GenomeRelease,
+    /// The path to the mehari database.
+    #[clap(long)]
+    pub path_mehari_db: String,
+    /// Path to the input VCF file, ingested or raw.
+    ///
+    /// The file is read through its tabix or CSI index if there is one.
+    #[clap(long)]
+    pub path_in: String,
+    /// Regions to annotate as `CHROM` or `CHROM:START-END`; can be given multiple times.
+    #[clap(long, required = true)]
+    pub region: Vec<String>,
+    /// The output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::default())]
+    pub output_format: OutputFormat,
+    /// Path to the output file, standard output if not given.
+    #[clap(long)]
+    pub path_out: Option<String>,
+}
+
+/// The annotation of one variant allele.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AnnotatedVariant {
+    /// The variant as `CHROM:POS:REF:ALT`.
+    pub variant: String,
+    /// The `INFO` fields written by `seqvars ingest` for the variant.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub info: IndexMap<String, String>,
+}
+
+/// Annotate `var` with `annotator`.
+fn annotate(
+    annotator: &RecordAnnotator,
+    var: annonars::common::keys::Var,
+) -> Result<AnnotatedVariant, anyhow::Error> {
+    let variant = format!(
+        "{}:{}:{}:{}",
+        var.chrom, var.pos, var.reference, var.alternative
+    );
+
+    // The annotation is written to the `INFO` fields of an empty record.
+    let mut record = noodles_vcf::Record::default();
+    annotator.annotate(&mut annotator.lookups(false), var, &mut record)?;
+    let info = record
+        .info()
+        .keys()
+        .zip(record.info().values())
+        .map(|(key, value)| {
+            (
+                key.to_string(),
+                value.map(|v| v.to_string()).unwrap_or_default(),
+            )
+        })
+        .collect();
+    Ok(AnnotatedVariant { variant, info })
+}
+
+/// Write `variants` as a table with one column per `INFO` field to `writer`.
+///
+/// Missing fields are written as `.`.
+fn write_table<W: std::io::Write>(
+    writer: &mut W,
+    variants: &[AnnotatedVariant],
+) -> Result<(), anyhow::Error> {
+    let keys = variants
+        .iter()
+        .flat_map(|variant| variant.info.keys())
+        .collect::<IndexSet<_>>();
+    writeln!(
+        writer,
+        "{}",
+        std::iter::once("variant")
+            .chain(keys.iter().map(|key| key.as_str()))
+            .collect::<Vec<_>>()
+            .join("\t")
+    )?;
+    for variant in variants {
+        writeln!(
+            writer,
+            "{}",
+            std::iter::once(variant.variant.as_str())
+                .chain(keys.iter().map(|key| {
+                    variant
+                        .info
+                        .get(key.as_str())
+                        .map(|value| value.as_str())
+                        .unwrap_or(".")
+                }))
+                .collect::<Vec<_>>()
+                .join("\t")
+        )?;
+    }
+    Ok(())
+}
+
+/// Main entry point for `seqvars annotate-region` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    let before_anything = std::time::Instant::now();
+    tracing::info!("args_common = {:#?}", &args_common);
+    tracing::info!("args = {:#?}", &args);
+
+    let regions = Regions::from_args(&args.region, None)?.unwrap_or_default();
+    let annotator = RecordAnnotator::new(&args.path_mehari_db, args.genomebuild, 0)?;
+
+    let mut reader = open_vcf_reader(&args.path_in)
+        .await
+        .map_err(|e| anyhow::anyhow!("could not open {}: {}", args.path_in, e))?;
+    let header = reader
+        .read_header()
+        .await
+        .map_err(|e| anyhow::anyhow!("problem reading header of {}: {}", args.path_in, e))?;
+    let mut records = match regions::find_index(&args.path_in) {
+        Some(path_index) => {
+            regions::query_records(&args.path_in, &path_index, header.clone(), regions).await?
+        }
+        None => {
+            let records: RecordStream = Box::pin(
+                reader
+                    .records(&header)
+                    .map_err(|e| anyhow::anyhow!("problem reading input VCF file: {}", e)),
+            );
+            regions::filter_records(records, regions)
+        }
+    };
+
+    tracing::info!("annotating variants in {:?}...", &args.region);
+    let mut variants = Vec::new();
+    while let Some(record) = records.try_next().await? {
+        for (allele_no, alt_allele) in record.alternate_bases().iter().enumerate() {
+            if alleles::SkipReason::of(alt_allele).is_some() {
+                continue;
+            }
+            let var = annonars::common::keys::Var::from_vcf_allele(&record, allele_no);
+            variants.push(annotate(&annotator, var)?);
+        }
+    }
+    tracing::info!(
+        "... annotated {} variant alleles",
+        variants.len().separate_with_commas()
+    );
+
+    let mut writer: Box<dyn std::io::Write> = match args.path_out.as_ref() {
+        Some(path_out) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path_out)
+                .map_err(|e| anyhow::anyhow!("could not create {}: {}", path_out, e))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    match args.output_format {
+        OutputFormat::Table => write_table(&mut writer, &variants)?,
+        OutputFormat::Json => {
+            for variant in &variants {
+                serde_json::to_writer(&mut writer, variant)?;
+                writeln!(writer)?;
+            }
+        }
+    }
+    writer.flush()?;
+
+    tracing::info!(
+        "All of `seqvars annotate-region` completed in {:?}",
+        before_anything.elapsed()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::common::GenomeRelease;
+
+    use super::{AnnotatedVariant, OutputFormat};
+
+    #[test]
+    fn write_table() -> Result<(), anyhow::Error> {
+        let variants = vec![
+            AnnotatedVariant {
+                variant: "17:100:A:C".into(),
+                info: [("ANN".to_string(), "C|x".to_string())].into(),
+            },
+            AnnotatedVariant {
+                variant: "17:200:G:T".into(),
+                info: [
+                    ("gnomad_exomes_an".to_string(), "10".to_string()),
+                    ("ANN".to_string(), "T|y".to_string()),
+                ]
+                .into(),
+            },
+        ];
+
+        let mut buf = Vec::new();
+        super::write_table(&mut buf, &variants)?;
+
+        assert_eq!(
+            String::from_utf8(buf)?,
+            "variant\tANN\tgnomad_exomes_an\n17:100:A:C\tC|x\t.\n17:200:G:T\tT|y\t10\n"
+        );
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case("tests/seqvars/ingest/NA12878_dragen.vcf")]
+    #[case("tests/seqvars/ingest/NA12878_dragen.vcf.gz")]
+    #[tokio::test]
+    async fn run_region(#[case] path_in: &str) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_out = tmpdir.join("out.jsonl");
+
+        let args = super::Args {
+            genomebuild: GenomeRelease::Grch37,
+            path_mehari_db: "tests/seqvars/ingest/db".into(),
+            path_in: path_in.into(),
+            region: vec!["17:41244000-41245300".into()],
+            output_format: OutputFormat::Json,
+            path_out: Some(path_out.to_str().unwrap().into()),
+        };
+        super::run(&Default::default(), &args).await?;
+
+        let variants = std::fs::read_to_string(&path_out)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<AnnotatedVariant>, _>>()?;
+        assert_eq!(
+            variants
+                .iter()
+                .map(|variant| variant.variant.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "17:41244000:T:C",
+                "17:41244435:T:C",
+                "17:41244936:G:A",
+                "17:41245237:A:G"
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,405 @@
+//! Implementation of `seqvars export-parquet` subcommand.
+//!
+//! This writes the annotated variants of an ingested VCF file as a Parquet file with
+//! typed columns for cohort analytics.  There is one row per variant with the position,
+//! alleles, population frequencies, and the consequence on the most severely affected
+//! gene, followed by the GT/DP/GQ/AD columns of each sample, e.g., `index_gt`.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{ArrayRef, Float32Builder, Int32Builder, ListBuilder, StringBuilder},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    record_batch::RecordBatch,
+};
+use futures::TryStreamExt as _;
+use mehari::common::noodles::open_vcf_reader;
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use thousands::Separable;
+
+use crate::{common, seqvars::query::schema::SequenceVariant};
+
+/// Command line arguments for `seqvars export-parquet` subcommand.
+#[derive(Debug, clap::Parser)]
+#[command(author, version, about = "export an ingested variant VCF to Parquet", long_about = None)]
+pub struct Args {
+    /// Path to input file.
+    #[clap(long)]
+    pub path_in: String,
+    /// Path to output Parquet file.
+    #[clap(long)]
+    pub path_out: String,
+    /// Number of records per row group.
+    #[clap(long, default_value_t = 65_536)]
+    pub batch_size: usize,
+}
+
+/// Names of the integer count columns, in the order of the schema.
+const COUNT_COLUMNS: &[&str] = &[
+    "gnomad_exomes_an",
+    "gnomad_exomes_hom",
+    "gnomad_exomes_het",
+    "gnomad_exomes_hemi",
+    "gnomad_genomes_an",
+    "gnomad_genomes_hom",
+    "gnomad_genomes_het",
+    "gnomad_genomes_hemi",
+    "helix_an",
+    "helix_hom",
+    "helix_het",
+    "inhouse_an",
+    "inhouse_hom",
+    "inhouse_het",
+    "inhouse_hemi",
+];
+
+/// Build the Arrow schema for the given samples.
+///
+/// The frequency columns `gnomad_exomes_af` and `gnomad_genomes_af` follow the
+/// respective count columns.
+pub fn build_schema(samples: &[String]) -> SchemaRef {
+    let mut fields = vec![
+        Field::new("chromosome", DataType::Utf8, false),
+        Field::new("pos", DataType::Int32, false),
+        Field::new("reference", DataType::Utf8, false),
+        Field::new("alternative", DataType::Utf8, false),
+    ];
+    for (i, name) in COUNT_COLUMNS.iter().enumerate() {
+        fields.push(Field::new(*name, DataType::Int32, false));
+        if i == 3 {
+            fields.push(Field::new("gnomad_exomes_af", DataType::Float32, false));
+        } else if i == 7 {
+            fields.push(Field::new("gnomad_genomes_af", DataType::Float32, false));
+        }
+    }
+    fields.extend([
+        Field::new("gene_symbol", DataType::Utf8, true),
+        Field::new("gene_id", DataType::Utf8, true),
+        Field::new(
+            "consequences",
+            DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+            false,
+        ),
+        Field::new("hgvs_t", DataType::Utf8, true),
+        Field::new("hgvs_p", DataType::Utf8, true),
+    ]);
+    for sample in samples {
+        fields.push(Field::new(format!("{}_gt", sample), DataType::Utf8, true));
+        fields.push(Field::new(format!("{}_dp", sample), DataType::Int32, true));
+        fields.push(Field::new(
+            format!("{}_gq", sample),
+            DataType::Float32,
+            true,
+        ));
+        fields.push(Field::new(format!("{}_ad", sample), DataType::Int32, true));
+    }
+    Arc::new(Schema::new(fields))
+}
+
+/// Allele frequency from the carrier counts, 0 if there are no alleles.
+fn allele_freq(an: i32, hom: i32, het: i32, hemi: i32) -> f32 {
+    if an > 0 {
+        (2 * hom + het + hemi) as f32 / an as f32
+    } else {
+        0.0
+    }
+}
+
+/// Builders for the columns of one sample.
+#[derive(Default)]
+struct SampleBuilders {
+    gt: StringBuilder,
+    dp: Int32Builder,
+    gq: Float32Builder,
+    ad: Int32Builder,
+}
+
+/// Accumulate records into `RecordBatch`es.
+struct BatchBuilder {
+    /// The schema of the batches.
+    schema: SchemaRef,
+    /// The names of the samples, in the order of the schema.
+    samples: Vec<String>,
+    /// Number of records in the current batch.
+    len: usize,
+    chromosome: StringBuilder,
+    pos: Int32Builder,
+    reference: StringBuilder,
+    alternative: StringBuilder,
+    /// Builders for the integer count columns, in the order of `COUNT_COLUMNS`.
+    counts: Vec<Int32Builder>,
+    gnomad_exomes_af: Float32Builder,
+    gnomad_genomes_af: Float32Builder,
+    gene_symbol: StringBuilder,
+    gene_id: StringBuilder,
+    consequences: ListBuilder<StringBuilder>,
+    hgvs_t: StringBuilder,
+    hgvs_p: StringBuilder,
+    sample_builders: Vec<SampleBuilders>,
+}
+
+impl BatchBuilder {
+    /// Construct for the given samples.
+    fn new(samples: &[String]) -> Self {
+        Self {
+            schema: build_schema(samples),
+            samples: samples.to_vec(),
+            len: 0,
+            chromosome: Default::default(),
+            pos: Default::default(),
+            reference: Default::default(),
+            alternative: Default::default(),
+            counts: COUNT_COLUMNS.iter().map(|_| Default::default()).collect(),
+            gnomad_exomes_af: Default::default(),
+            gnomad_genomes_af: Default::default(),
+            gene_symbol: Default::default(),
+            gene_id: Default::default(),
+            consequences: ListBuilder::new(StringBuilder::new()),
+            hgvs_t: Default::default(),
+            hgvs_p: Default::default(),
+            sample_builders: samples.iter().map(|_| Default::default()).collect(),
+        }
+    }
+
+    /// Append `seqvar` to the current batch.
+    fn append(&mut self, seqvar: &SequenceVariant) {
+        self.len += 1;
+        self.chromosome.append_value(&seqvar.chrom);
+        self.pos.append_value(seqvar.pos);
+        self.reference.append_value(&seqvar.reference);
+        self.alternative.append_value(&seqvar.alternative);
+
+        let counts = [
+            seqvar.gnomad_exomes_an,
+            seqvar.gnomad_exomes_hom,
+            seqvar.gnomad_exomes_het,
+            seqvar.gnomad_exomes_hemi,
+            seqvar.gnomad_genomes_an,
+            seqvar.gnomad_genomes_hom,
+            seqvar.gnomad_genomes_het,
+            seqvar.gnomad_genomes_hemi,
+            seqvar.helix_an,
+            seqvar.helix_hom,
+            seqvar.helix_het,
+            seqvar.inhouse_an,
+            seqvar.inhouse_hom,
+            seqvar.inhouse_het,
+            seqvar.inhouse_hemi,
+        ];
+        for (builder, count) in self.counts.iter_mut().zip(counts) {
+            builder.append_value(count);
+        }
+        self.gnomad_exomes_af.append_value(allele_freq(
+            seqvar.gnomad_exomes_an,
+            seqvar.gnomad_exomes_hom,
+            seqvar.gnomad_exomes_het,
+            seqvar.gnomad_exomes_hemi,
+        ));
+        self.gnomad_genomes_af.append_value(allele_freq(
+            seqvar.gnomad_genomes_an,
+            seqvar.gnomad_genomes_hom,
+            seqvar.gnomad_genomes_het,
+            seqvar.gnomad_genomes_hemi,
+        ));
+
+        // The ANN fields are sorted by severity, so the first one is the most severe.
+        let ann_field = seqvar.ann_fields.first();
+        self.gene_symbol
+            .append_option(ann_field.map(|ann_field| &ann_field.gene_symbol));
+        self.gene_id
+            .append_option(ann_field.map(|ann_field| &ann_field.gene_id));
+        for consequence in ann_field
+            .map(|ann_field| ann_field.consequences.as_slice())
+            .unwrap_or_default()
+        {
+            self.consequences
+                .values()
+                .append_value(consequence.to_string());
+        }
+        self.consequences.append(true);
+        self.hgvs_t
+            .append_option(ann_field.and_then(|ann_field| ann_field.hgvs_t.as_ref()));
+        self.hgvs_p
+            .append_option(ann_field.and_then(|ann_field| ann_field.hgvs_p.as_ref()));
+
+        for (sample, builders) in self.samples.iter().zip(self.sample_builders.iter_mut()) {
+            let call_info = seqvar.call_info.get(sample);
+            builders
+                .gt
+                .append_option(call_info.and_then(|call_info| call_info.genotype.as_ref()));
+            builders
+                .dp
+                .append_option(call_info.and_then(|call_info| call_info.dp));
+            builders
+                .gq
+                .append_option(call_info.and_then(|call_info| call_info.quality));
+            builders
+                .ad
+                .append_option(call_info.and_then(|call_info| call_info.ad));
+        }
+    }
+
+    /// Finish the current batch and reset the builders.
+    fn finish(&mut self) -> Result<RecordBatch, anyhow::Error> {
+        self.len = 0;
+        let mut counts = self.counts.iter_mut();
+        let mut count_columns = |n: usize| -> Vec<ArrayRef> {
+            counts
+                .by_ref()
+                .take(n)
+                .map(|builder| Arc::new(builder.finish()) as ArrayRef)
+                .collect()
+        };
+
+        let mut columns: Vec<ArrayRef> = Vec::new();
+        columns.push(Arc::new(self.chromosome.finish()));
+        columns.push(Arc::new(self.pos.finish()));
+        columns.push(Arc::new(self.reference.finish()));
+        columns.push(Arc::new(self.alternative.finish()));
+        columns.extend(count_columns(4));
+        columns.push(Arc::new(self.gnomad_exomes_af.finish()));
+        columns.extend(count_columns(4));
+        columns.push(Arc::new(self.gnomad_genomes_af.finish()));
+        columns.extend(count_columns(7));
+        columns.push(Arc::new(self.gene_symbol.finish()));
+        columns.push(Arc::new(self.gene_id.finish()));
+        columns.push(Arc::new(self.consequences.finish()));
+        columns.push(Arc::new(self.hgvs_t.finish()));
+        columns.push(Arc::new(self.hgvs_p.finish()));
+        for builders in self.sample_builders.iter_mut() {
+            columns.push(Arc::new(builders.gt.finish()));
+            columns.push(Arc::new(builders.dp.finish()));
+            columns.push(Arc::new(builders.gq.finish()));
+            columns.push(Arc::new(builders.ad.finish()));
+        }
+
+        RecordBatch::try_new(self.schema.clone(), columns)
+            .map_err(|e| anyhow::anyhow!("could not build record batch: {}", e))
+    }
+}
+
+/// Main entry point for `seqvars export-parquet` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    let before_anything = std::time::Instant::now();
+    tracing::info!("args_common = {:#?}", &args_common);
+    tracing::info!("args = {:#?}", &args);
+
+    tracing::info!("opening input file...");
+    let mut reader = open_vcf_reader(&args.path_in)
+        .await
+        .map_err(|e| anyhow::anyhow!("could not open input file: {}", e))?;
+    let header = reader
+        .read_header()
+        .await
+        .map_err(|e| anyhow::anyhow!("problem reading header: {}", e))?;
+    let samples = header.sample_names().iter().cloned().collect::<Vec<_>>();
+
+    let partial_guard = common::partial::PartialOutputGuard::new(
+        &args.path_out,
+        common::partial::OnFailure::Remove,
+    );
+    {
+        tracing::info!("writing Parquet file...");
+        let mut batch_builder = BatchBuilder::new(&samples);
+        let file =
+            common::io::std::open_write_maybe_bgzf(&args.path_out, args_common.compression_level)?;
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut writer = ArrowWriter::try_new(file, batch_builder.schema.clone(), Some(props))
+            .map_err(|e| anyhow::anyhow!("could not create Parquet writer: {}", e))?;
+
+        let mut count_records = 0usize;
+        let mut records = reader.records(&header);
+        while let Some(record) = records
+            .try_next()
+            .await
+            .map_err(|e| anyhow::anyhow!("problem reading record: {}", e))?
+        {
+            let seqvar = SequenceVariant::from_vcf(&record, &header)
+                .map_err(|e| anyhow::anyhow!("could not parse VCF record: {}", e))?;
+            batch_builder.append(&seqvar);
+            count_records += 1;
+            if batch_builder.len >= args.batch_size {
+                writer
+                    .write(&batch_builder.finish()?)
+                    .map_err(|e| anyhow::anyhow!("could not write record batch: {}", e))?;
+            }
+        }
+        if batch_builder.len > 0 {
+            writer
+                .write(&batch_builder.finish()?)
+                .map_err(|e| anyhow::anyhow!("could not write record batch: {}", e))?;
+        }
+        writer
+            .close()
+            .map_err(|e| anyhow::anyhow!("could not finish Parquet file: {}", e))?;
+        tracing::info!(
+            "... done writing {} records",
+            count_records.separate_with_commas()
+        );
+    }
+    partial_guard.finish();
+
+    tracing::info!(
+        "All of `seqvars export-parquet` completed in {:?}",
+        before_anything.elapsed()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn build_schema() {
+        let schema = super::build_schema(&["index".to_string()]);
+
+        let names = schema
+            .fields()
+            .iter()
+            .map(|field| field.name().as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names.len(), 4 + super::COUNT_COLUMNS.len() + 2 + 5 + 4);
+        assert_eq!(
+            &names[..4],
+            &["chromosome", "pos", "reference", "alternative"]
+        );
+        assert_eq!(names[8], "gnomad_exomes_af");
+        assert_eq!(names[13], "gnomad_genomes_af");
+        assert_eq!(names[21..24], ["gene_symbol", "gene_id", "consequences"]);
+        assert_eq!(
+            &names[names.len() - 4..],
+            &["index_gt", "index_dp", "index_gq", "index_ad"]
+        );
+    }
+
+    #[tokio::test]
+    async fn smoke_test() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_out = format!("{}/out.parquet", tmpdir.to_string_lossy());
+
+        let args = super::Args {
+            path_in: "tests/seqvars/prefilter/ingest.vcf".into(),
+            path_out: path_out.clone(),
+            batch_size: 1,
+        };
+        super::run(&crate::common::Args::default(), &args).await?;
+
+        let reader =
+            ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path_out)?)?.build()?;
+        let mut count_rows = 0;
+        for batch in reader {
+            let batch = batch?;
+            assert!(batch
+                .schema()
+                .field_with_name("Case_1_index-N1-DNA1-WGS1_gt")
+                .is_ok());
+            count_rows += batch.num_rows();
+        }
+        assert_eq!(count_rows, 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+//! Accounting of the alternate alleles that `seqvars ingest` cannot annotate.
+//!
+//! Only alleles given as bases are annotated and written out.  Spanning deletions (`*`)
+//! and the reference blocks of gVCF files (`<NON_REF>`, `<*>`) are expected in the output
+//! of the common callers and skipped silently.  Other symbolic alleles and breakends are
+//! unexpected in small variant calls; they are skipped with a warning or rejected in
+//! `--strict` mode.  The skipped alleles are counted by reason for the allele report.
+//!
+//! Records of gVCF files with only `<NON_REF>` alleles are homozygous reference blocks;
+//! they are skipped as a whole and only their number and length are reported.
+
+use noodles_vcf as vcf;
+use vcf::record::alternate_bases::{allele::Symbol, Allele};
+
+/// The reason for skipping an alternate allele.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    strum::Display,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SkipReason {
+    /// Spanning deletion (`*`).
+    SpanningDeletion,
+    /// Reference block of gVCF files (`<NON_REF>` or `<*>`).
+    NonRef,
+    /// Other symbolic allele, e.g., `<DEL>`.
+    Symbolic,
+    /// Breakend, e.g., `G]17:198982]`.
+    Breakend,
+}
+
+impl SkipReason {
+    /// Return the reason for skipping `allele`, `None` if it can be annotated.
+    pub fn of(allele: &Allele) -> Option<Self> {
+        match allele {
+            Allele::Bases(_) => None,
+            Allele::OverlappingDeletion => Some(Self::SpanningDeletion),
+            Allele::Symbol(Symbol::Unspecified) => Some(Self::NonRef),
+            Allele::Symbol(Symbol::NonstructuralVariant(name)) if name == "NON_REF" => {
+                Some(Self::NonRef)
+            }
+            Allele::Symbol(_) => Some(Self::Symbolic),
+            Allele::Breakend(_) => Some(Self::Breakend),
+        }
+    }
+
+    /// Return whether alleles skipped for this reason are expected in small variant calls.
+    pub fn is_expected(&self) -> bool {
+        matches!(self, Self::SpanningDeletion | Self::NonRef)
+    }
+}
+
+/// Return whether `input_record` is a homozygous reference block of a gVCF file.
+pub fn is_reference_block(input_record: &vcf::Record) -> bool {
+    let alternate_bases = input_record.alternate_bases();
+    !alternate_bases.is_empty()
+        && alternate_bases
+            .iter()
+            .all(|allele| SkipReason::of(allele) == Some(SkipReason::NonRef))
+}
+
+/// Per-run report of the processed and skipped alleles, written as JSON.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Report {
+    /// Number of input records.
+    pub count_records: usize,
+    /// Number of input records without alternate allele.
+    pub count_records_without_alt: usize,
+    /// Number of input records that are gVCF reference blocks.
+    pub count_reference_blocks: usize,
+    /// Number of bases covered by the gVCF reference blocks.
+    pub count_reference_block_bases: usize,
+    /// Number of alternate alleles in the input records.
+    pub count_alleles: usize,
+    /// Number of alleles written out.
+    pub count_written: usize,
+    /// Number of skipped alleles by reason.
+    pub skipped: indexmap::IndexMap<SkipReason, usize>,
+    /// The first skipped unexpected alleles as `CHROM:POS:REF:ALT`, for debugging.
+    pub unexpected_examples: Vec<String>,
+}
+
+/// Maximal number of examples in `Report::unexpected_examples`.
+const MAX_EXAMPLES: usize = 10;
+
+impl Report {
+    /// Register `input_record` before processing its alleles.
+    pub fn register_record(&mut self, input_record: &vcf::Record) {
+        self.count_records += 1;
+        self.count_alleles += input_record.alternate_bases().len();
+        if input_record.alternate_bases().is_empty() {
+            self.count_records_without_alt += 1;
+        }
+    }
+
+    /// Register the gVCF reference block `input_record` that is skipped as a whole.
+    pub fn register_reference_block(&mut self, input_record: &vcf::Record) {
+        self.count_records += 1;
+        self.count_reference_blocks += 1;
+        let start = usize::from(input_record.position());
+        let end = input_record.end().map(usize::from).unwrap_or(start);
+        self.count_reference_block_bases += end.saturating_sub(start) + 1;
+    }
+
+    /// Register that `allele` of `input_record` is skipped for `reason`.
+    ///
+    /// Returns an error in `strict` mode if the allele is unexpected.
+    pub fn register_skipped(
+        &mut self,
+        input_record: &vcf::Record,
+        allele: &Allele,
+        reason: SkipReason,
+        strict: bool,
+    ) -> Result<(), anyhow::Error> {
+        *self.skipped.entry(reason).or_default() += 1;
+        if reason.is_expected() {
+            return Ok(());
+        }
+
+        let description = format!(
+            "{}:{}:{}:{}",
+            input_record.chromosome(),
+            input_record.position(),
+            input_record.reference_bases(),
+            allele
+        );
+        if strict {
+            anyhow::bail!(
+                "{}: unexpected allele {} ({}) in strict mode",
+                crate::codes::INGEST_UNEXPECTED_ALLELE,
+                &description,
+                reason
+            );
+        }
+        if self.unexpected_examples.len() < MAX_EXAMPLES {
+            tracing::warn!(
+                "{}: skipping unexpected allele {} ({})",
+                crate::codes::INGEST_UNEXPECTED_ALLELE_SKIPPED,
+                &description,
+                reason
+            );
+            self.unexpected_examples.push(description);
+        }
+        Ok(())
+    }
+
+    /// Add the counts of `other`, e.g., from another thread.
+    pub fn merge(&mut self, other: Report) {
+        self.count_records += other.count_records;
+        self.count_records_without_alt += other.count_records_without_alt;
+        self.count_reference_blocks += other.count_reference_blocks;
+        self.count_reference_block_bases += other.count_reference_block_bases;
+        self.count_alleles += other.count_alleles;
+        self.count_written += other.count_written;
+        for (reason, count) in other.skipped {
+            *self.skipped.entry(reason).or_default() += count;
+        }
+        for example in other.unexpected_examples {
+            if self.unexpected_examples.len() < MAX_EXAMPLES {
+                self.unexpected_examples.push(example);
+            }
+        }
+    }
+
+    /// Log a summary of the skipped alleles.
+    pub fn log_summary(&self) {
+        if self.count_reference_blocks > 0 {
+            tracing::info!(
+                "skipped {} gVCF reference blocks covering {} bases",
+                self.count_reference_blocks,
+                self.count_reference_block_bases
+            );
+        }
+        for (reason, count) in &self.skipped {
+            tracing::info!("skipped {} alleles: {}", reason, count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use noodles_vcf as vcf;
+
+    use super::{Report, SkipReason};
+
+    #[rstest::rstest]
+    #[case("A", None)]
+    #[case("ACGT", None)]
+    #[case("*", Some(SkipReason::SpanningDeletion))]
+    #[case("<NON_REF>", Some(SkipReason::NonRef))]
+    #[case("<*>", Some(SkipReason::NonRef))]
+    #[case("<DEL>", Some(SkipReason::Symbolic))]
+    #[case("<CN:0>", Some(SkipReason::Symbolic))]
+    #[case("G]17:198982]", Some(SkipReason::Breakend))]
+    fn skip_reason(#[case] allele: &str, #[case] expected: Option<SkipReason>) {
+        let allele: vcf::record::alternate_bases::Allele = allele.parse().unwrap();
+        assert_eq!(SkipReason::of(&allele), expected);
+    }
+
+    #[rstest::rstest]
+    #[case("<NON_REF>", true)]
+    #[case("<*>", true)]
+    #[case("G,<NON_REF>", false)]
+    #[case("G", false)]
+    #[case(".", false)]
+    fn is_reference_block(#[case] alts: &str, #[case] expected: bool) {
+        assert_eq!(super::is_reference_block(&record(alts)), expected);
+    }
+
+    fn record(alts: &str) -> vcf::Record {
+        let header = vcf::Header::default();
+        vcf::Record::try_from((&header, format!("1\t100\t.\tA\t{}\t.\t.\t.", alts).as_str()))
+            .unwrap()
+    }
+
+    #[test]
+    fn report() -> Result<(), anyhow::Error> {
+        let mut report = Report::default();
+        for (alts, strict) in [
+            ("G,*", true),
+            ("G,<NON_REF>", true),
+            ("<NON_REF>", true),
+            ("<DEL>", false),
+        ] {
+            let input_record = record(alts);
+            if super::is_reference_block(&input_record) {
+                report.register_reference_block(&input_record);
+                continue;
+            }
+            report.register_record(&input_record);
+            for allele in input_record.alternate_bases().iter() {
+                if let Some(reason) = SkipReason::of(allele) {
+                    report.register_skipped(&input_record, allele, reason, strict)?;
+                } else {
+                    report.count_written += 1;
+                }
+            }
+        }
+        insta::assert_yaml_snapshot!(report);
+
+        let input_record = record("<DEL>");
+        let allele = &input_record.alternate_bases()[0];
+        assert!(report
+            .register_skipped(&input_record, allele, SkipReason::Symbolic, true)
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+//! Adaptive sizing of the record batches passed between the tasks of the `async` I/O backend.
+//!
+//! The annotation cost per record varies widely, e.g., between dense exonic regions and
+//! empty intergenic stretches.  With a fixed number of records per batch, the annotation
+//! is busy with one batch for long in dense regions while the writer idles, and the
+//! channel overhead dominates in sparse regions.  We thus measure the cost per record and
+//! size the batches such that annotating one batch takes about a target duration.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Weight of the latest observation in the smoothed cost per record.
+const SMOOTHING: f64 = 0.3;
+
+/// Batch size that is adjusted from the observed annotation cost per record.
+///
+/// The size is shared via an atomic so the reading task picks up the adjustments of the
+/// annotation thread for the next batch.
+#[derive(Debug, Clone)]
+pub struct BatchSizer {
+    /// Target duration of annotating one batch.
+    target: Duration,
+    /// Minimal number of records per batch.
+    min_size: usize,
+    /// Maximal number of records per batch.
+    max_size: usize,
+    /// Smoothed annotation cost per record in seconds, `None` before the first batch.
+    cost_per_record: Option<f64>,
+    /// The current batch size.
+    size: Arc<AtomicUsize>,
+}
+
+impl BatchSizer {
+    /// Construct with the `initial_size`, clamped to `min_size..=max_size`.
+    pub fn new(target: Duration, initial_size: usize, min_size: usize, max_size: usize) -> Self {
+        Self {
+            target,
+            min_size,
+            max_size,
+            cost_per_record: None,
+            size: Arc::new(AtomicUsize::new(initial_size.clamp(min_size, max_size))),
+        }
+    }
+
+    /// Return the current batch size.
+    pub fn size(&self) -> usize {
+        self.size.load(Ordering::Relaxed)
+    }
+
+    /// Return a handle to the current batch size for the reading task.
+    pub fn shared_size(&self) -> Arc<AtomicUsize> {
+        self.size.clone()
+    }
+
+    /// Register that annotating `count` records took `elapsed` and adjust the batch size.
+    pub fn observe(&mut self, count: usize, elapsed: Duration) {
+        if count == 0 {
+            return;
+        }
+        let cost = elapsed.as_secs_f64() / count as f64;
+        let cost = match self.cost_per_record {
+            Some(prev) => SMOOTHING * cost + (1.0 - SMOOTHING) * prev,
+            None => cost,
+        };
+        self.cost_per_record = Some(cost);
+
+        let size = if cost > 0.0 {
+            // The cast saturates for very small costs.
+            (self.target.as_secs_f64() / cost).round() as usize
+        } else {
+            self.max_size
+        };
+        let size = size.clamp(self.min_size, self.max_size);
+        if size != self.size() {
+            tracing::debug!(
+                "adjusting batch size to {} records ({:.1}us per record)",
+                size,
+                cost * 1e6
+            );
+        }
+        self.size.store(size, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::BatchSizer;
+
+    #[test]
+    fn observe() {
+        let mut sizer = BatchSizer::new(Duration::from_millis(100), 1_000, 10, 10_000);
+        let shared_size = sizer.shared_size();
+        assert_eq!(sizer.size(), 1_000);
+
+        // 1ms per record yields 100 records for 100ms.
+        sizer.observe(1_000, Duration::from_secs(1));
+        assert_eq!(sizer.size(), 100);
+        assert_eq!(shared_size.load(std::sync::atomic::Ordering::Relaxed), 100);
+
+        // The cost is smoothed: 0.3 * 0.1ms + 0.7 * 1ms = 0.73ms per record.
+        sizer.observe(100, Duration::from_millis(10));
+        assert_eq!(sizer.size(), 137);
+
+        // Empty batches are ignored.
+        sizer.observe(0, Duration::from_secs(1));
+        assert_eq!(sizer.size(), 137);
+
+        // The size is clamped.
+        for _ in 0..100 {
+            sizer.observe(100, Duration::ZERO);
+        }
+        assert_eq!(sizer.size(), 10_000);
+        for _ in 0..100 {
+            sizer.observe(1, Duration::from_secs(1));
+        }
+        assert_eq!(sizer.size(), 10);
+    }
+}
@@ -0,0 +1,434 @@
+//! Implementation of the hidden `devtools bench-ingest` subcommand.
+//!
+//! A synthetic VCF file of configurable size is generated and run through the annotation
+//! of `seqvars ingest` with the time spent in each stage measured so that performance
+//! regressions can be tracked.  The reference alleles are random, so the consequence
+//! prediction is representative in cost but not in its results.
+
+use std::io::Write as _;
+
+use futures::TryStreamExt as _;
+use noodles_vcf as vcf;
+use rand::{Rng as _, SeedableRng as _};
+use thousands::Separable as _;
+use tokio::io::AsyncWriteExt as _;
+
+use super::{alleles, header, KeptInfoKeys, KnownFormatKeys, RecordAnnotator, StageTimes};
+use crate::common::{
+    noodles::{open_vcf_reader, open_vcf_writer},
+    worker_version, GenomeRelease,
+};
+
+/// Command line arguments for `devtools bench-ingest` subcommand.
+#[derive(Debug, Clone, clap::Parser)]
+#[command(author, version, about = "benchmark seqvars ingest on synthetic VCF", long_about = None)]
+pub struct Args {
+    /// The assumed genome build.
+    #[clap(long, default_value = "grch37")]
+    pub genomebuild: GenomeRelease,
+    /// The path to the mehari database.
+    #[clap(long)]
+    pub path_mehari_db: String,
+    /// Directory to write the synthetic input and the output VCF file to.
+    #[clap(long)]
+    pub path_work: String,
+    /// Path to write the JSON report with the timings to; optional.
+    #[clap(long)]
+    pub path_report: Option<String>,
+    /// Number of records to generate.
+    #[clap(long, default_value = "100000")]
+    pub num_records: usize,
+    /// Number of samples to generate.
+    #[clap(long, default_value = "3")]
+    pub num_samples: usize,
+    /// Ploidy of the generated genotypes.
+    #[clap(long, default_value = "2", value_parser = clap::value_parser!(u8).range(1..=2))]
+    pub ploidy: u8,
+    /// Fraction of generated records with two alternate alleles.
+    #[clap(long, default_value = "0.05")]
+    pub multiallelic_fraction: f64,
+    /// Fraction of generated alternate alleles that are insertions.
+    #[clap(long, default_value = "0.1")]
+    pub indel_fraction: f64,
+    /// Seed for the random number generator.
+    #[clap(long, default_value = "42")]
+    pub seed: u64,
+    /// Number of transcript bins to cache, see `seqvars ingest --csq-cache-size`.
+    #[clap(long, default_value = "10000")]
+    pub csq_cache_size: usize,
+}
+
+/// The benchmark result, written as JSON.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Report {
+    /// Number of generated input records.
+    pub count_records: usize,
+    /// Number of written output records.
+    pub count_written: usize,
+    /// Seconds spent in each stage, in order of the pipeline.
+    pub seconds: indexmap::IndexMap<String, f64>,
+    /// Written records per second of the whole pipeline, excluding generation and setup.
+    pub records_per_second: f64,
+}
+
+/// The canonical chromosomes of `genomebuild` with their lengths.
+fn chromosomes(genomebuild: GenomeRelease) -> Vec<(String, usize)> {
+    let assembly = biocommons_bioutils::assemblies::Assembly::from(genomebuild);
+    biocommons_bioutils::assemblies::ASSEMBLY_INFOS[assembly]
+        .sequences
+        .iter()
+        .filter_map(|seq| {
+            let name = annonars::common::cli::canonicalize(&seq.name);
+            let is_canonical = name == "X"
+                || name == "Y"
+                || name
+                    .parse::<u8>()
+                    .map(|no| (1..=22).contains(&no))
+                    .unwrap_or(false);
+            is_canonical.then(|| {
+                let name = match genomebuild {
+                    GenomeRelease::Grch37 => name,
+                    GenomeRelease::Grch38 => format!("chr{}", name),
+                };
+                (name, seq.length)
+            })
+        })
+        .collect()
+}
+
+/// Return a random base other than `other`.
+fn random_base(rng: &mut rand::rngs::StdRng, other: &[char]) -> char {
+    loop {
+        let base = ['A', 'C', 'G', 'T'][rng.gen_range(0..4)];
+        if !other.contains(&base) {
+            return base;
+        }
+    }
+}
+
+/// Write a synthetic VCF file as configured in `args` to `writer`.
+///
+/// The records are spread evenly over the canonical chromosomes at random distances.
+pub fn generate<W: std::io::Write>(args: &Args, writer: &mut W) -> Result<(), anyhow::Error> {
+    for (name, fraction) in [
+        ("multi-allelic", args.multiallelic_fraction),
+        ("indel", args.indel_fraction),
+    ] {
+        if !(0.0..=1.0).contains(&fraction) {
+            anyhow::bail!("{} fraction must be between 0 and 1: {}", name, fraction);
+        }
+    }
+    let mut rng = rand::rngs::StdRng::seed_from_u64(args.seed);
+    let chromosomes = chromosomes(args.genomebuild);
+
+    writeln!(writer, "##fileformat=VCFv4.2")?;
+    writeln!(
+        writer,
+        "##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">"
+    )?;
+    writeln!(
+        writer,
+        "##FORMAT=<ID=AD,Number=R,Type=Integer,Description=\"Allelic depths\">"
+    )?;
+    writeln!(
+        writer,
+        "##FORMAT=<ID=DP,Number=1,Type=Integer,Description=\"Read depth\">"
+    )?;
+    writeln!(
+        writer,
+        "##FORMAT=<ID=GQ,Number=1,Type=Integer,Description=\"Genotype quality\">"
+    )?;
+    // The output header needs a recognized variant caller.
+    writeln!(
+        writer,
+        "##GATKCommandLine.HaplotypeCaller=<ID=HaplotypeCaller,Version=4.4.0.0,\
+         CommandLineOptions=\"synthetic\">"
+    )?;
+    for (name, length) in &chromosomes {
+        writeln!(writer, "##contig=<ID={},length={}>", name, length)?;
+    }
+    write!(
+        writer,
+        "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT"
+    )?;
+    for i in 0..args.num_samples {
+        write!(writer, "\tsample-{}", i + 1)?;
+    }
+    writeln!(writer)?;
+
+    let per_chrom = args.num_records.div_ceil(chromosomes.len().max(1));
+    let mut count = 0;
+    for (name, length) in &chromosomes {
+        // The steps are bounded so all records fit on the chromosome.
+        let max_step = (length / (per_chrom + 1)).max(1);
+        let mut pos = 0;
+        for _ in 0..per_chrom {
+            if count == args.num_records {
+                return Ok(());
+            }
+            pos += rng.gen_range(1..=max_step);
+            if pos >= *length {
+                break;
+            }
+            count += 1;
+
+            let reference = random_base(&mut rng, &[]);
+            let num_alts = if rng.gen_bool(args.multiallelic_fraction) {
+                2
+            } else {
+                1
+            };
+            let mut alts: Vec<String> = Vec::new();
+            let mut used = vec![reference];
+            for _ in 0..num_alts {
+                let base = random_base(&mut rng, &used);
+                used.push(base);
+                alts.push(if rng.gen_bool(args.indel_fraction) {
+                    format!("{}{}", reference, base)
+                } else {
+                    base.to_string()
+                });
+            }
+
+            write!(
+                writer,
+                "{}\t{}\t.\t{}\t{}\t.\tPASS\t.\tGT:AD:DP:GQ",
+                name,
+                pos,
+                reference,
+                alts.join(",")
+            )?;
+            for _ in 0..args.num_samples {
+                let mut gt = (0..args.ploidy)
+                    .map(|_| {
+                        if rng.gen_bool(0.5) {
+                            0
+                        } else {
+                            rng.gen_range(1..=num_alts)
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                gt.sort();
+                let dp: usize = rng.gen_range(10..60);
+                let mut ad = vec![0; num_alts + 1];
+                for allele in &gt {
+                    ad[*allele] += dp / gt.len();
+                }
+                write!(
+                    writer,
+                    "\t{}:{}:{}:{}",
+                    gt.iter()
+                        .map(|a| a.to_string())
+                        .collect::<Vec<_>>()
+                        .join("/"),
+                    ad.iter()
+                        .map(|a| a.to_string())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                    dp,
+                    rng.gen_range(20..=99)
+                )?;
+            }
+            writeln!(writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Main entry point for `devtools bench-ingest` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<Report, anyhow::Error> {
+    tracing::info!("args_common = {:#?}", &args_common);
+    tracing::info!("args = {:#?}", &args);
+    let mut seconds = indexmap::IndexMap::new();
+
+    tracing::info!("generating synthetic VCF...");
+    let start = std::time::Instant::now();
+    std::fs::create_dir_all(&args.path_work)
+        .map_err(|e| anyhow::anyhow!("could not create {}: {}", &args.path_work, e))?;
+    let path_in = format!("{}/synthetic.vcf", &args.path_work);
+    let path_out = format!("{}/synthetic.ingested.vcf.gz", &args.path_work);
+    {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(&path_in)?);
+        generate(args, &mut writer)?;
+        writer.flush()?;
+    }
+    seconds.insert("generate".to_string(), start.elapsed().as_secs_f64());
+
+    tracing::info!("loading databases...");
+    let start = std::time::Instant::now();
+    let annotator =
+        RecordAnnotator::new(&args.path_mehari_db, args.genomebuild, args.csq_cache_size)?
+            .with_stage_times();
+    let mut lookups = annotator.lookups(false);
+    seconds.insert("setup".to_string(), start.elapsed().as_secs_f64());
+
+    let mut reader = open_vcf_reader(&path_in).await?;
+    let input_header = reader.read_header().await?;
+    let pedigree = mehari::ped::PedigreeByName {
+        individuals: input_header
+            .sample_names()
+            .iter()
+            .map(|name| {
+                (
+                    name.clone(),
+                    mehari::ped::Individual {
+                        name: name.clone(),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect(),
+    };
+    let output_header = header::build_output_header(
+        &input_header,
+        &Some(pedigree),
+        args.genomebuild,
+        "20240101",
+        Some(&uuid::Uuid::nil()),
+        worker_version(),
+    )?;
+    let idx_output_to_input = super::build_idx_output_to_input(&output_header, &input_header);
+    let known_format_keys = KnownFormatKeys::default();
+    let kept_info_keys = KeptInfoKeys::default();
+
+    tracing::info!("running the pipeline...");
+    let mut writer = open_vcf_writer(&path_out, None).await?;
+    writer.write_header(&output_header).await?;
+    let mut report = alleles::Report::default();
+    let mut prev = std::time::Instant::now();
+    let mut time_parse = std::time::Duration::ZERO;
+    let mut time_write = std::time::Duration::ZERO;
+    let start_pipeline = std::time::Instant::now();
+    let mut records = reader.records(&input_header);
+    loop {
+        let start = std::time::Instant::now();
+        let Some(input_record) = records
+            .try_next()
+            .await
+            .map_err(|e| anyhow::anyhow!("problem reading synthetic VCF: {}", e))?
+        else {
+            break;
+        };
+        time_parse += start.elapsed();
+
+        let output_records = annotator.process_record(
+            &mut lookups,
+            &input_record,
+            &idx_output_to_input,
+            &known_format_keys,
+            &kept_info_keys,
+            &mut report,
+            false,
+            &mut prev,
+        )?;
+
+        let start = std::time::Instant::now();
+        for output_record in &output_records {
+            writer.write_record(output_record).await?;
+        }
+        time_write += start.elapsed();
+    }
+    let start = std::time::Instant::now();
+    crate::flush_and_shutdown!(writer);
+    time_write += start.elapsed();
+    let time_pipeline = start_pipeline.elapsed();
+
+    let StageTimes {
+        split,
+        freq,
+        clinvar,
+        csq,
+    } = annotator.stage_times().unwrap_or_default();
+    for (stage, elapsed) in [
+        ("parse", time_parse),
+        ("split", split),
+        ("freq", freq),
+        ("clinvar", clinvar),
+        ("csq", csq),
+        ("write", time_write),
+        ("total", time_pipeline),
+    ] {
+        seconds.insert(stage.to_string(), elapsed.as_secs_f64());
+    }
+
+    let result = Report {
+        count_records: report.count_records,
+        count_written: report.count_written,
+        records_per_second: report.count_written as f64 / time_pipeline.as_secs_f64(),
+        seconds,
+    };
+    for (stage, seconds) in &result.seconds {
+        tracing::info!("{:>10}: {:.3}s", stage, seconds);
+    }
+    tracing::info!(
+        "wrote {} of {} records at {:.0} records per second",
+        result.count_written.separate_with_commas(),
+        result.count_records.separate_with_commas(),
+        result.records_per_second
+    );
+
+    if let Some(path_report) = args.path_report.as_ref() {
+        serde_json::to_writer_pretty(std::fs::File::create(path_report)?, &result)
+            .map_err(|e| anyhow::anyhow!("could not write benchmark report: {}", e))?;
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use noodles_vcf as vcf;
+
+    use crate::common::GenomeRelease;
+
+    fn args(path_work: &str) -> super::Args {
+        super::Args {
+            genomebuild: GenomeRelease::Grch37,
+            path_mehari_db: "tests/seqvars/ingest/db".into(),
+            path_work: path_work.into(),
+            path_report: None,
+            num_records: 100,
+            num_samples: 2,
+            ploidy: 2,
+            multiallelic_fraction: 0.2,
+            indel_fraction: 0.1,
+            seed: 42,
+            csq_cache_size: 100,
+        }
+    }
+
+    #[test]
+    fn generate() -> Result<(), anyhow::Error> {
+        let mut buf = Vec::new();
+        super::generate(&args("."), &mut buf)?;
+
+        let mut reader = vcf::Reader::new(buf.as_slice());
+        let header = reader.read_header()?;
+        assert_eq!(header.sample_names().len(), 2);
+        let records = reader.records(&header).collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(records.len(), 100);
+        assert!(records
+            .iter()
+            .any(|record| record.alternate_bases().len() == 2));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn run() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let args = args(tmpdir.to_str().unwrap());
+
+        let report = super::run(&Default::default(), &args).await?;
+
+        assert_eq!(report.count_records, 100);
+        assert!(report.count_written > 100);
+        assert_eq!(
+            report.seconds.keys().cloned().collect::<Vec<_>>(),
+            vec!["generate", "setup", "parse", "split", "freq", "clinvar", "csq", "write", "total"]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+//! Cache around the consequence prediction for skipping variants far from any transcript.
+//!
+//! Most variants of a genome are intergenic and the consequence predictor traverses the
+//! transcript interval trees only to find that no transcript is close.  Variants are
+//! dense in many regions, so we cache per `(chrom, tx bin)` whether any transcript is
+//! within the padding used by the predictor, and skip the prediction for variants in bins
+//! without transcripts.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    sync::Arc,
+};
+
+use hgvs::data::interface::Provider as _;
+use mehari::annotate::seqvars::{
+    ann::AnnField,
+    csq::{ConsequencePredictor, VcfVariant, ALT_ALN_METHOD, PADDING},
+    provider::Provider as MehariProvider,
+};
+
+use super::tx_policy::{SelectedTxs, TxPolicy};
+
+/// Size of the transcript bins.
+pub const BIN_SIZE: i32 = 1_000;
+
+/// Cache hit statistics.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// Number of lookups answered from the cache.
+    pub hits: usize,
+    /// Number of lookups that had to query the transcript interval trees.
+    pub misses: usize,
+    /// Number of predictions that were skipped because there is no transcript in the bin.
+    pub skipped: usize,
+}
+
+/// Consequence predictor with a cache of the `(chrom, tx bin)`s without transcripts.
+pub struct CachedPredictor {
+    /// The wrapped predictor.
+    predictor: ConsequencePredictor,
+    /// The provider of the predictor, for querying the transcript interval trees.
+    provider: Arc<MehariProvider>,
+    /// Mapping from chromosome name to accession.
+    chrom_to_acc: HashMap<String, String>,
+    /// Whether there is a transcript in the bin, `None` if caching is disabled.
+    cache: Option<RefCell<quick_cache::unsync::Cache<(String, i32), bool>>>,
+    /// Cache hit statistics.
+    stats: Cell<Stats>,
+    /// The transcripts to keep the consequences of.
+    selected_txs: SelectedTxs,
+}
+
+impl CachedPredictor {
+    /// Wrap `predictor` that uses `provider`, caching up to `capacity` bins; `0` disables
+    /// the cache.
+    pub fn new(
+        predictor: ConsequencePredictor,
+        provider: Arc<MehariProvider>,
+        assembly: biocommons_bioutils::assemblies::Assembly,
+        capacity: usize,
+    ) -> Self {
+        let chrom_to_acc = provider
+            .get_assembly_map(assembly)
+            .into_iter()
+            .flat_map(|(acc, chrom)| {
+                let chrom = chrom.strip_prefix("chr").unwrap_or(&chrom).to_string();
+                [(format!("chr{}", chrom), acc.clone()), (chrom, acc)]
+            })
+            .collect();
+        Self {
+            predictor,
+            provider,
+            chrom_to_acc,
+            cache: (capacity > 0).then(|| RefCell::new(quick_cache::unsync::Cache::new(capacity))),
+            stats: Default::default(),
+            selected_txs: Default::default(),
+        }
+    }
+
+    /// Only keep the consequences of the transcripts selected by `tx_policy`.
+    pub fn with_tx_policy(self, tx_policy: TxPolicy) -> Self {
+        let selected_txs = SelectedTxs::new(&self.provider.tx_seq_db, tx_policy);
+        Self {
+            selected_txs,
+            ..self
+        }
+    }
+
+    /// Return the provider of the predictor.
+    pub fn provider(&self) -> &Arc<MehariProvider> {
+        &self.provider
+    }
+
+    /// Return the cache hit statistics.
+    pub fn stats(&self) -> Stats {
+        self.stats.get()
+    }
+
+    /// Return whether there is a transcript close to any variant in the bin `bin` of
+    /// `chrom`, `None` if this cannot be determined.
+    fn bin_has_txs(&self, chrom: &str, bin: i32) -> Option<bool> {
+        let mut cache = self.cache.as_ref()?.borrow_mut();
+        let key = (chrom.to_string(), bin);
+        if let Some(has_txs) = cache.get(&key).copied() {
+            self.update_stats(|stats| stats.hits += 1);
+            return Some(has_txs);
+        }
+
+        self.update_stats(|stats| stats.misses += 1);
+        let chrom_acc = self.chrom_to_acc.get(chrom)?;
+        let has_txs = !self
+            .provider
+            .get_tx_for_region(
+                chrom_acc,
+                ALT_ALN_METHOD,
+                bin * BIN_SIZE - PADDING,
+                (bin + 1) * BIN_SIZE + PADDING,
+            )
+            .ok()?
+            .is_empty();
+        cache.insert(key, has_txs);
+        Some(has_txs)
+    }
+
+    /// Apply `f` to the statistics.
+    fn update_stats(&self, f: impl FnOnce(&mut Stats)) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+
+    /// Predict the consequences of `var`, see `ConsequencePredictor::predict`.
+    ///
+    /// Returns an empty list without running the prediction if no transcript is close to
+    /// the bin of the variant.  Only the consequences of the transcripts selected with
+    /// `with_tx_policy()` are returned.
+    pub fn predict(&self, var: &VcfVariant) -> Result<Option<Vec<AnnField>>, anyhow::Error> {
+        let var_start = var.position - 1;
+        let var_end = var_start + var.reference.len() as i32;
+        let bin = var_start.max(0) / BIN_SIZE;
+        if var_end <= (bin + 1) * BIN_SIZE && self.bin_has_txs(&var.chromosome, bin) == Some(false)
+        {
+            self.update_stats(|stats| stats.skipped += 1);
+            return Ok(Some(Vec::new()));
+        }
+        let mut ann_fields = self.predictor.predict(var)?;
+        if let Some(ann_fields) = ann_fields.as_mut() {
+            self.selected_txs.retain(ann_fields);
+        }
+        Ok(ann_fields)
+    }
+}
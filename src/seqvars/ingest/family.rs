@@ -0,0 +1,258 @@
+//! Normalized family structure written by `seqvars ingest`.
+//!
+//! The pedigree from the PED file is combined with the HPO terms from the command line
+//! and the samples of the input files and written to a `<path-out>.family.json` sidecar
+//! file, so the server importer does not need to parse PED files itself.
+
+use noodles_vcf as vcf;
+
+use crate::seqvars::qc::sex::Sex;
+
+/// Suffix of the sidecar JSON file with the family structure.
+pub const SIDECAR_SUFFIX: &str = "family.json";
+
+/// Affection status of an individual.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Affected {
+    /// Affected.
+    Affected,
+    /// Unaffected.
+    Unaffected,
+    /// Unknown.
+    Unknown,
+}
+
+impl From<mehari::ped::Disease> for Affected {
+    fn from(disease: mehari::ped::Disease) -> Self {
+        match disease {
+            mehari::ped::Disease::Affected => Affected::Affected,
+            mehari::ped::Disease::Unaffected => Affected::Unaffected,
+            mehari::ped::Disease::Unknown => Affected::Unknown,
+        }
+    }
+}
+
+/// Kind of a parent-child relationship.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelationshipKind {
+    /// The parent is the father of the child.
+    Father,
+    /// The parent is the mother of the child.
+    Mother,
+}
+
+/// A parent-child relationship.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Relationship {
+    /// Name of the parent.
+    pub parent: String,
+    /// Name of the child.
+    pub child: String,
+    /// Kind of the relationship.
+    pub kind: RelationshipKind,
+}
+
+/// One individual of the family.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Individual {
+    /// Name of the individual, same as the sample name in the output file.
+    pub name: String,
+    /// Family from the PED file.
+    pub family: String,
+    /// Name of the father, if in the family.
+    pub father: Option<String>,
+    /// Name of the mother, if in the family.
+    pub mother: Option<String>,
+    /// Sex from the PED file.
+    pub sex: Sex,
+    /// Affection status from the PED file.
+    pub affected: Affected,
+    /// HPO terms from the command line.
+    pub hpo_terms: Vec<String>,
+    /// Input files with genotypes of the individual, empty for individuals that were
+    /// written with no-call genotypes.
+    pub files: Vec<String>,
+}
+
+/// The family structure of a case.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Family {
+    /// The case UUID.
+    pub case_uuid: uuid::Uuid,
+    /// The individuals in the order of the output file.
+    pub individuals: Vec<Individual>,
+    /// The parent-child relationships.
+    pub relationships: Vec<Relationship>,
+}
+
+/// Parse the `SAMPLE=HP:NNNNNNN` values of `--hpo-terms` into the terms by sample.
+fn parse_hpo_terms(
+    values: &[String],
+    pedigree: &mehari::ped::PedigreeByName,
+) -> Result<indexmap::IndexMap<String, Vec<String>>, anyhow::Error> {
+    let mut result = indexmap::IndexMap::<String, Vec<String>>::new();
+    for value in values {
+        let (sample, term) = value.split_once('=').ok_or_else(|| {
+            anyhow::anyhow!(
+                "{}: invalid HPO term {:?}, expected SAMPLE=HP:NNNNNNN",
+                crate::codes::INGEST_INVALID_HPO_TERM,
+                value
+            )
+        })?;
+        let digits = term.strip_prefix("HP:").ok_or_else(|| {
+            anyhow::anyhow!(
+                "{}: invalid HPO term id {:?} for {}",
+                crate::codes::INGEST_INVALID_HPO_TERM,
+                term,
+                sample
+            )
+        })?;
+        if digits.len() != 7 || !digits.chars().all(|c| c.is_ascii_digit()) {
+            anyhow::bail!(
+                "{}: invalid HPO term id {:?} for {}",
+                crate::codes::INGEST_INVALID_HPO_TERM,
+                term,
+                sample
+            );
+        }
+        if !pedigree.individuals.contains_key(sample) {
+            anyhow::bail!(
+                "{}: sample {} of HPO term {} is not in the pedigree",
+                crate::codes::INGEST_INVALID_HPO_TERM,
+                sample,
+                term
+            );
+        }
+        let terms = result.entry(sample.to_string()).or_default();
+        if !terms.iter().any(|t| t == term) {
+            terms.push(term.to_string());
+        }
+    }
+    Ok(result)
+}
+
+impl Family {
+    /// Build from the reconciled `pedigree`, the `hpo_terms` values from the command line,
+    /// and the `input_headers` of the files at `paths_in`.
+    pub fn new(
+        case_uuid: uuid::Uuid,
+        pedigree: &mehari::ped::PedigreeByName,
+        hpo_terms: &[String],
+        paths_in: &[String],
+        input_headers: &[vcf::Header],
+    ) -> Result<Self, anyhow::Error> {
+        let mut hpo_terms = parse_hpo_terms(hpo_terms, pedigree)?;
+
+        let mut individuals = Vec::new();
+        let mut relationships = Vec::new();
+        for individual in pedigree.individuals.values() {
+            let files = paths_in
+                .iter()
+                .zip(input_headers.iter())
+                .filter(|(_, header)| header.sample_names().contains(&individual.name))
+                .map(|(path, _)| path.clone())
+                .collect::<Vec<_>>();
+            for (parent, kind) in [
+                (&individual.father, RelationshipKind::Father),
+                (&individual.mother, RelationshipKind::Mother),
+            ] {
+                if let Some(parent) = parent {
+                    relationships.push(Relationship {
+                        parent: parent.clone(),
+                        child: individual.name.clone(),
+                        kind,
+                    });
+                }
+            }
+            individuals.push(Individual {
+                name: individual.name.clone(),
+                family: individual.family.clone(),
+                father: individual.father.clone(),
+                mother: individual.mother.clone(),
+                sex: individual.sex.into(),
+                affected: individual.disease.into(),
+                hpo_terms: hpo_terms.shift_remove(&individual.name).unwrap_or_default(),
+                files,
+            });
+        }
+
+        Ok(Self {
+            case_uuid,
+            individuals,
+            relationships,
+        })
+    }
+
+    /// Write the family structure to the sidecar file of `path_out`.
+    pub fn write_sidecar(&self, path_out: &str) -> Result<(), anyhow::Error> {
+        let path = format!("{}.{}", path_out, SIDECAR_SUFFIX);
+        serde_json::to_writer_pretty(std::fs::File::create(&path)?, self)
+            .map_err(|e| anyhow::anyhow!("could not write family structure to {}: {}", &path, e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use noodles_vcf as vcf;
+
+    use super::{Affected, Family, RelationshipKind};
+    use crate::seqvars::qc::sex::Sex;
+
+    #[test]
+    fn new() -> Result<(), anyhow::Error> {
+        let pedigree = mehari::ped::PedigreeByName::from_path("tests/seqvars/ingest/Case_1.ped")?;
+        let index = "Case_1_index-N1-DNA1-WGS1";
+        let father = "Case_1_father-N1-DNA1-WGS1";
+        let mother = "Case_1_mother-N1-DNA1-WGS1";
+        let header_index = vcf::Header::builder().add_sample_name(index).build();
+        let header_parents = vcf::Header::builder()
+            .add_sample_name(father)
+            .add_sample_name(mother)
+            .build();
+
+        let family = Family::new(
+            uuid::Uuid::nil(),
+            &pedigree,
+            &[
+                format!("{}=HP:0001250", index),
+                format!("{}=HP:0000118", index),
+                format!("{}=HP:0001250", index),
+            ],
+            &["index.vcf".into(), "parents.vcf".into()],
+            &[header_index, header_parents],
+        )?;
+
+        assert_eq!(family.individuals.len(), 3);
+        let individual = &family.individuals[0];
+        assert_eq!(individual.name, index);
+        assert_eq!(individual.father.as_deref(), Some(father));
+        assert_eq!(individual.sex, Sex::Female);
+        assert_eq!(individual.affected, Affected::Affected);
+        assert_eq!(individual.hpo_terms, vec!["HP:0001250", "HP:0000118"]);
+        assert_eq!(individual.files, vec!["index.vcf"]);
+        assert_eq!(family.individuals[1].affected, Affected::Unaffected);
+        assert_eq!(family.individuals[1].files, vec!["parents.vcf"]);
+        assert!(family.individuals[1].hpo_terms.is_empty());
+
+        assert_eq!(family.relationships.len(), 2);
+        assert_eq!(family.relationships[1].parent, mother);
+        assert_eq!(family.relationships[1].child, index);
+        assert_eq!(family.relationships[1].kind, RelationshipKind::Mother);
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case("Case_1_index-N1-DNA1-WGS1:HP:0001250")]
+    #[case("Case_1_index-N1-DNA1-WGS1=HP:123")]
+    #[case("Case_1_index-N1-DNA1-WGS1=0001250")]
+    #[case("unknown=HP:0001250")]
+    fn new_invalid_hpo_terms(#[case] value: &str) -> Result<(), anyhow::Error> {
+        let pedigree = mehari::ped::PedigreeByName::from_path("tests/seqvars/ingest/Case_1.ped")?;
+        assert!(Family::new(uuid::Uuid::nil(), &pedigree, &[value.into()], &[], &[]).is_err());
+
+        Ok(())
+    }
+}
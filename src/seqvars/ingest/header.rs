@@ -208,13 +208,106 @@ fn add_contigs_38(builder: vcf::header::Builder) -> Result<vcf::header::Builder,
     Ok(builder)
 }
 
+/// Policy for handling mismatches between the pedigree and the input samples.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SampleMismatchPolicy {
+    /// Fail if the samples differ.
+    #[default]
+    Error,
+    /// Drop pedigree members missing from the input and input samples missing from
+    /// the pedigree.
+    WarnDrop,
+    /// Write no-call genotypes for pedigree members missing from the input and drop input
+    /// samples missing from the pedigree.
+    WarnFillNocall,
+}
+
+/// Reconcile `pedigree` with the samples in `input_header` according to `policy`.
+///
+/// Returns the pedigree with the individuals to write out.  Parent references to
+/// dropped individuals are removed.
+pub fn reconcile_pedigree(
+    mut pedigree: mehari::ped::PedigreeByName,
+    input_header: &vcf::Header,
+    policy: SampleMismatchPolicy,
+) -> Result<mehari::ped::PedigreeByName, anyhow::Error> {
+    let ped_only = pedigree
+        .individuals
+        .keys()
+        .filter(|name| !input_header.sample_names().contains(*name))
+        .cloned()
+        .collect::<Vec<_>>();
+    let input_only = input_header
+        .sample_names()
+        .iter()
+        .filter(|name| !pedigree.individuals.contains_key(*name))
+        .cloned()
+        .collect::<Vec<_>>();
+    if ped_only.is_empty() && input_only.is_empty() {
+        return Ok(pedigree);
+    }
+
+    match policy {
+        SampleMismatchPolicy::Error => anyhow::bail!(
+            "{}: pedigree individuals missing from input: {:?}, input samples missing from \
+            pedigree: {:?}",
+            crate::codes::INGEST_SAMPLE_MISMATCH,
+            &ped_only,
+            &input_only
+        ),
+        SampleMismatchPolicy::WarnDrop => {
+            tracing::warn!(
+                "{}: dropping pedigree individuals missing from input: {:?}",
+                crate::codes::INGEST_PEDIGREE_INDIVIDUAL_DROPPED,
+                &ped_only
+            );
+            let dropped = ped_only.into_iter().collect::<HashSet<_>>();
+            pedigree
+                .individuals
+                .retain(|name, _| !dropped.contains(name));
+            for individual in pedigree.individuals.values_mut() {
+                if matches!(&individual.father, Some(father) if dropped.contains(father)) {
+                    individual.father = None;
+                }
+                if matches!(&individual.mother, Some(mother) if dropped.contains(mother)) {
+                    individual.mother = None;
+                }
+            }
+        }
+        SampleMismatchPolicy::WarnFillNocall => {
+            if !ped_only.is_empty() {
+                tracing::warn!(
+                    "{}: writing no-call genotypes for pedigree individuals missing from \
+                    input: {:?}",
+                    crate::codes::INGEST_PEDIGREE_INDIVIDUAL_NOCALL,
+                    &ped_only
+                );
+            }
+        }
+    }
+    if !input_only.is_empty() {
+        tracing::warn!(
+            "{}: dropping input samples missing from pedigree: {:?}",
+            crate::codes::INGEST_SAMPLE_MISSING_FROM_PEDIGREE,
+            &input_only
+        );
+    }
+
+    Ok(pedigree)
+}
+
 /// Generate the output header from the input header.
+///
+/// The `pedigree` must have been reconciled with the input samples with
+/// `reconcile_pedigree()`; input samples missing from the pedigree are dropped.  Without
+/// `case_uuid`, i.e., with `--annotate-only`, no case UUID is written and inputs from
+/// unknown variant callers are accepted.
 pub fn build_output_header(
     input_header: &vcf::Header,
     pedigree: &Option<mehari::ped::PedigreeByName>,
     genomebuild: GenomeRelease,
     file_date: &str,
-    case_uuid: &uuid::Uuid,
+    case_uuid: Option<&uuid::Uuid>,
     worker_version: &str,
 ) -> Result<vcf::Header, anyhow::Error> {
     use vcf::header::record::value::{
@@ -345,33 +438,23 @@ pub fn build_output_header(
     .map_err(|e| anyhow::anyhow!("problem adding contigs: {}", e))?;
 
     if let Some(pedigree) = pedigree {
-        let ped_idv = pedigree
-            .individuals
-            .iter()
-            .map(|(name, _)| name.clone())
-            .collect::<HashSet<_>>();
-        let input_idv = input_header
+        // Write the individuals in the order of the input samples, followed by the
+        // individuals that are missing from the input.
+        let names = input_header
             .sample_names()
             .iter()
-            .cloned()
-            .collect::<HashSet<_>>();
-        if !ped_idv.eq(&input_idv) {
-            anyhow::bail!(
-                "pedigree individuals = {:?} != input individuals: {:?}",
-                &ped_idv,
-                &input_idv
-            )
-        }
+            .filter(|name| pedigree.individuals.contains_key(*name))
+            .chain(
+                pedigree
+                    .individuals
+                    .keys()
+                    .filter(|name| !input_header.sample_names().contains(*name)),
+            );
 
         let mut sample_names = Vec::new();
-        for name in input_header.sample_names() {
-            let i = pedigree
-                .individuals
-                .get(name)
-                .expect("checked equality above");
-            if input_header.sample_names().contains(&i.name) {
-                sample_names.push(i.name.clone());
-            }
+        for name in names {
+            let i = &pedigree.individuals[name];
+            sample_names.push(i.name.clone());
 
             // Add SAMPLE entry.
             builder = builder.insert(
@@ -415,23 +498,30 @@ pub fn build_output_header(
 
     use vcf::header::record::value::map::Other;
 
-    let orig_caller = VariantCaller::guess(input_header)
-        .ok_or_else(|| anyhow::anyhow!("unable to guess original variant caller"))?;
+    let orig_caller = match (VariantCaller::guess(input_header), case_uuid) {
+        (Some(orig_caller), _) => orig_caller,
+        (None, None) => VariantCaller::Other,
+        (None, Some(_)) => anyhow::bail!(
+            "{}: unable to guess original variant caller",
+            crate::codes::INGEST_UNKNOWN_VARIANT_CALLER
+        ),
+    };
 
-    let builder = builder
-        .insert(
+    if let Some(case_uuid) = case_uuid {
+        builder = builder.insert(
             "x-varfish-case-uuid".parse()?,
             vcf::header::record::Value::String(case_uuid.to_string()),
-        )?
-        .insert(
-            "x-varfish-version".parse()?,
-            vcf::header::record::Value::Map(
-                String::from("varfish-server-worker"),
-                Map::<Other>::builder()
-                    .insert("Version".parse()?, worker_version)
-                    .build()?,
-            ),
         )?;
+    }
+    let builder = builder.insert(
+        "x-varfish-version".parse()?,
+        vcf::header::record::Value::Map(
+            String::from("varfish-server-worker"),
+            Map::<Other>::builder()
+                .insert("Version".parse()?, worker_version)
+                .build()?,
+        ),
+    )?;
 
     let builder = match &orig_caller {
         VariantCaller::GatkHaplotypeCaller { version }
@@ -477,13 +567,136 @@ pub fn build_output_header(
     Ok(builder.build())
 }
 
+/// Copy the `FORMAT` definitions of the `extra_keys` from `input_header` to
+/// `output_header`, keeping the ones already present in `output_header`.
+pub fn add_extra_formats(
+    output_header: &mut vcf::Header,
+    input_header: &vcf::Header,
+    extra_keys: &[String],
+) -> Result<(), anyhow::Error> {
+    for extra_key in extra_keys {
+        let key: vcf::record::genotypes::keys::Key = extra_key
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid FORMAT key {}: {}", extra_key, e))?;
+        if output_header.formats().contains_key(&key) {
+            continue;
+        }
+        let format = input_header.formats().get(&key).ok_or_else(|| {
+            anyhow::anyhow!(
+                "{}: extra FORMAT key {} not defined in input header",
+                crate::codes::INGEST_UNDEFINED_FORMAT_KEY,
+                &key
+            )
+        })?;
+        output_header.formats_mut().insert(key, format.clone());
+    }
+
+    Ok(())
+}
+
+/// Copy the `INFO` definitions of the `keep_keys` from `input_header` to `output_header`.
+///
+/// Keys that are already defined in `output_header` are written by the ingest itself and
+/// cannot be kept.
+pub fn add_kept_infos(
+    output_header: &mut vcf::Header,
+    input_header: &vcf::Header,
+    keep_keys: &[String],
+) -> Result<(), anyhow::Error> {
+    for keep_key in keep_keys {
+        let key: vcf::record::info::field::Key = keep_key
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid INFO key {}: {}", keep_key, e))?;
+        if output_header.infos().contains_key(&key) {
+            anyhow::bail!("kept INFO key {} is written by the ingest", &key);
+        }
+        let info = input_header.infos().get(&key).ok_or_else(|| {
+            anyhow::anyhow!(
+                "{}: kept INFO key {} not defined in input header",
+                crate::codes::INGEST_UNDEFINED_INFO_KEY,
+                &key
+            )
+        })?;
+        output_header.infos_mut().insert(key, info.clone());
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use mehari::ped::PedigreeByName;
     use noodles_vcf as vcf;
     use rstest::rstest;
 
-    use super::VariantCaller;
+    use super::{SampleMismatchPolicy, VariantCaller};
+
+    fn trio_pedigree() -> PedigreeByName {
+        use mehari::ped::{Disease, Individual, Sex};
+
+        PedigreeByName {
+            individuals: [
+                ("index", Some("father")),
+                ("father", None),
+                ("mother", None),
+            ]
+            .into_iter()
+            .map(|(name, father)| {
+                (
+                    name.to_string(),
+                    Individual {
+                        family: "FAM".into(),
+                        name: name.into(),
+                        father: father.map(Into::into),
+                        mother: (name == "index").then(|| "mother".into()),
+                        sex: Sex::Unknown,
+                        disease: Disease::Unknown,
+                    },
+                )
+            })
+            .collect(),
+        }
+    }
+
+    #[rstest]
+    #[case(&["index", "father", "mother"], SampleMismatchPolicy::Error, Some(vec!["index:father:mother", "father::", "mother::"]))]
+    #[case(&["index", "mother"], SampleMismatchPolicy::Error, None)]
+    #[case(&["index", "mother"], SampleMismatchPolicy::WarnDrop, Some(vec!["index::mother", "mother::"]))]
+    #[case(&["index", "mother", "sibling"], SampleMismatchPolicy::WarnDrop, Some(vec!["index::mother", "mother::"]))]
+    #[case(&["index", "mother"], SampleMismatchPolicy::WarnFillNocall, Some(vec!["index:father:mother", "father::", "mother::"]))]
+    fn reconcile_pedigree(
+        #[case] samples: &[&str],
+        #[case] policy: SampleMismatchPolicy,
+        #[case] expected: Option<Vec<&str>>,
+    ) {
+        let input_header = samples
+            .iter()
+            .fold(vcf::Header::builder(), |builder, sample| {
+                builder.add_sample_name(*sample)
+            })
+            .build();
+
+        let result = super::reconcile_pedigree(trio_pedigree(), &input_header, policy)
+            .ok()
+            .map(|pedigree| {
+                pedigree
+                    .individuals
+                    .values()
+                    .map(|i| {
+                        format!(
+                            "{}:{}:{}",
+                            i.name,
+                            i.father.clone().unwrap_or_default(),
+                            i.mother.clone().unwrap_or_default()
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            });
+        assert_eq!(
+            result,
+            expected.map(|e| e.into_iter().map(String::from).collect())
+        );
+    }
 
     #[rstest]
     #[case("tests/seqvars/ingest/clair3_glnexus.vcf")]
@@ -523,7 +736,7 @@ mod test {
             &Some(pedigree),
             crate::common::GenomeRelease::Grch37,
             "20230421",
-            &uuid::Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            Some(&uuid::Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap()),
             "x.y.z",
         )?;
 
@@ -565,7 +778,7 @@ mod test {
             &Some(pedigree),
             crate::common::GenomeRelease::Grch38,
             "20230421",
-            &uuid::Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            Some(&uuid::Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap()),
             "x.y.z",
         )?;
 
@@ -586,4 +799,76 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn add_extra_formats() -> Result<(), anyhow::Error> {
+        let input_vcf_header = noodles_vcf::reader::Builder::default()
+            .build_from_path("tests/seqvars/ingest/example_gatk_hc.3.7-0.vcf")?
+            .read_header()?;
+        let mut output_vcf_header = vcf::Header::default();
+
+        super::add_extra_formats(
+            &mut output_vcf_header,
+            &input_vcf_header,
+            &["PL".into(), "GQ".into()],
+        )?;
+        assert_eq!(
+            output_vcf_header
+                .formats()
+                .keys()
+                .map(|k| k.as_ref())
+                .collect::<Vec<_>>(),
+            vec!["PL", "GQ"]
+        );
+        assert_eq!(
+            output_vcf_header.formats().get("PL").map(|f| f.number()),
+            Some(vcf::header::Number::G)
+        );
+        assert!(super::add_extra_formats(
+            &mut output_vcf_header,
+            &input_vcf_header,
+            &["XX".into()]
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn add_kept_infos() -> Result<(), anyhow::Error> {
+        let input_vcf_header = noodles_vcf::reader::Builder::default()
+            .build_from_path("tests/seqvars/ingest/example_dragen.07.021.624.3.10.4.vcf")?
+            .read_header()?;
+        let mut output_vcf_header = vcf::Header::default();
+
+        super::add_kept_infos(
+            &mut output_vcf_header,
+            &input_vcf_header,
+            &["FractionInformativeReads".into(), "AF".into()],
+        )?;
+        assert_eq!(
+            output_vcf_header
+                .infos()
+                .keys()
+                .map(|k| k.as_ref())
+                .collect::<Vec<_>>(),
+            vec!["FractionInformativeReads", "AF"]
+        );
+        assert_eq!(
+            output_vcf_header.infos().get("AF").map(|f| f.number()),
+            Some(vcf::header::Number::A)
+        );
+        // Already defined in the output header.
+        assert!(
+            super::add_kept_infos(&mut output_vcf_header, &input_vcf_header, &["AF".into()])
+                .is_err()
+        );
+        // Not defined in the input header.
+        assert!(
+            super::add_kept_infos(&mut output_vcf_header, &input_vcf_header, &["XX".into()])
+                .is_err()
+        );
+
+        Ok(())
+    }
 }
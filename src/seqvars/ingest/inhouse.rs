@@ -0,0 +1,116 @@
+//! Annotation of the in-house carrier counts from the RocksDB built by `seqvars aggregate`.
+
+use noodles_vcf as vcf;
+
+use crate::seqvars::aggregate::ds;
+
+/// Add the `INFO` definitions of the in-house counts to `output_header`.
+///
+/// These are the `INHOUSE_*` summary fields and the `inhouse_*` fields read by
+/// `seqvars query`.
+pub fn add_inhouse_infos(output_header: &mut vcf::Header) -> Result<(), anyhow::Error> {
+    use vcf::header::record::value::{
+        map::{info::Type, Info},
+        Map,
+    };
+    use vcf::header::Number;
+
+    for (key, description) in [
+        (
+            "INHOUSE_CARRIERS",
+            "Number of het., hom., and hemi. alt. carriers in the in-house database",
+        ),
+        (
+            "INHOUSE_HOM",
+            "Number of hom. alt. carriers in the in-house database",
+        ),
+        (
+            "INHOUSE_HET",
+            "Number of het. alt. carriers in the in-house database",
+        ),
+        ("inhouse_an", "Number of alleles in the in-house database"),
+        (
+            "inhouse_hom",
+            "Number of hom. alt. carriers in the in-house database",
+        ),
+        (
+            "inhouse_het",
+            "Number of het. alt. carriers in the in-house database",
+        ),
+        (
+            "inhouse_hemi",
+            "Number of hemi. alt. carriers in the in-house database",
+        ),
+    ] {
+        output_header.infos_mut().insert(
+            key.parse()?,
+            Map::<Info>::new(Number::Count(1), Type::Integer, description),
+        );
+    }
+
+    Ok(())
+}
+
+/// Write the in-house `counts` into the `INFO` fields of `output_record`.
+///
+/// The `inhouse_*` keys match the ones read by `seqvars query`, e.g.,
+/// `INFO/gnomad_exomes_*`.
+pub fn annotate_counts(counts: &ds::Counts, output_record: &mut vcf::Record) {
+    use vcf::record::info::field::Value;
+
+    for (key, value) in [
+        (
+            "INHOUSE_CARRIERS",
+            counts.count_het + counts.count_hom + counts.count_hemi,
+        ),
+        ("INHOUSE_HOM", counts.count_hom),
+        ("INHOUSE_HET", counts.count_het),
+        ("inhouse_an", counts.count_an),
+        ("inhouse_hom", counts.count_hom),
+        ("inhouse_het", counts.count_het),
+        ("inhouse_hemi", counts.count_hemi),
+    ] {
+        output_record.info_mut().insert(
+            key.parse().expect("invalid key in source code"),
+            Some(Value::Integer(value as i32)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use noodles_vcf as vcf;
+
+    use crate::seqvars::aggregate::ds;
+
+    #[test]
+    fn annotate_counts() -> Result<(), anyhow::Error> {
+        let mut header = vcf::Header::default();
+        super::add_inhouse_infos(&mut header)?;
+        assert_eq!(header.infos().len(), 7);
+
+        let mut record = vcf::Record::builder()
+            .set_chromosome("X".parse()?)
+            .set_position(vcf::record::Position::from(100))
+            .set_reference_bases("A".parse()?)
+            .set_alternate_bases("G".parse()?)
+            .build()?;
+        super::annotate_counts(
+            &ds::Counts {
+                count_an: 30,
+                count_het: 3,
+                count_hom: 1,
+                count_hemi: 2,
+            },
+            &mut record,
+        );
+
+        assert_eq!(
+            record.to_string(),
+            "X\t100\t.\tA\tG\t.\t.\tINHOUSE_CARRIERS=6;INHOUSE_HOM=1;INHOUSE_HET=3;\
+            inhouse_an=30;inhouse_hom=1;inhouse_het=3;inhouse_hemi=2"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,539 @@
+//! On-the-fly positional merge of multiple input VCF files for `seqvars ingest`.
+//!
+//! Trio cases often arrive as one single-sample VCF file per individual.  The records
+//! of all files are split into one record per alternate allele and merged by position,
+//! similar to `bcftools norm -m- | bcftools merge -m none`: records with the same
+//! position and alleles are combined into one record and samples without a record at a
+//! site get a missing genotype.
+
+use std::collections::VecDeque;
+
+use futures::StreamExt as _;
+use noodles_vcf as vcf;
+
+use super::regions::RecordStream;
+
+/// Build the merged header from the `headers` of the input files.
+///
+/// The first header is used as the base; the samples of all inputs are concatenated and
+/// the `INFO`, `FORMAT`, and `contig` header records missing from the first header are
+/// added.
+pub fn merge_headers(headers: &[vcf::Header]) -> Result<vcf::Header, anyhow::Error> {
+    let mut header = headers
+        .first()
+        .ok_or_else(|| anyhow::anyhow!("no input header"))?
+        .clone();
+    for other in headers.iter().skip(1) {
+        let duplicates = other
+            .sample_names()
+            .iter()
+            .filter(|name| header.sample_names().contains(*name))
+            .collect::<Vec<_>>();
+        if !duplicates.is_empty() {
+            anyhow::bail!("samples in more than one input file: {:?}", &duplicates);
+        }
+        header
+            .sample_names_mut()
+            .extend(other.sample_names().iter().cloned());
+
+        for (key, info) in other.infos() {
+            if !header.infos().contains_key(key) {
+                header.infos_mut().insert(key.clone(), info.clone());
+            }
+        }
+        for (key, format) in other.formats() {
+            if !header.formats().contains_key(key) {
+                header.formats_mut().insert(key.clone(), format.clone());
+            }
+        }
+        for (key, contig) in other.contigs() {
+            if !header.contigs().contains_key(key) {
+                header.contigs_mut().insert(key.clone(), contig.clone());
+            }
+        }
+    }
+    Ok(header)
+}
+
+/// Sort key of a single-allele record: contig index in the merged header, position,
+/// reference, and the alternate allele.
+type SiteKey = (usize, usize, String, String);
+
+/// Return the sort key of `record` in the merged `header`.
+fn site_key(header: &vcf::Header, record: &vcf::Record) -> Result<SiteKey, anyhow::Error> {
+    let chrom = record.chromosome().to_string();
+    let contig_idx = header
+        .contigs()
+        .get_index_of(chrom.as_str())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "contig {} is not declared in the input headers, required for merging",
+                &chrom
+            )
+        })?;
+    Ok((
+        contig_idx,
+        usize::from(record.position()),
+        record.reference_bases().to_string(),
+        record.alternate_bases().to_string(),
+    ))
+}
+
+/// Split `record` with the input `header` into one record per alternate allele.
+///
+/// The `GT` is recoded with `recode_gt()` and the `INFO` and `FORMAT` values with
+/// `Number=A`, `R`, or `G` are restricted to the allele, as for the output records.
+fn split_alleles(header: &vcf::Header, record: vcf::Record) -> Vec<vcf::Record> {
+    use vcf::record::genotypes::{keys::key, sample::Value};
+
+    let allele_count = record.alternate_bases().len() + 1;
+    if allele_count <= 2 {
+        return vec![record];
+    }
+
+    (1..allele_count)
+        .map(|allele_no| {
+            let mut split = record.clone();
+            *split.alternate_bases_mut() = vcf::record::AlternateBases::from(vec![record
+                .alternate_bases()[allele_no - 1]
+                .clone()]);
+            *split.info_mut() = record
+                .info()
+                .as_ref()
+                .iter()
+                .filter_map(|(key, value)| {
+                    let value = match (value, header.infos().get(key)) {
+                        (Some(value), Some(info)) => Some(super::decompose_info_value(
+                            value,
+                            info.number(),
+                            allele_no,
+                            allele_count,
+                        )?),
+                        (value, _) => value.clone(),
+                    };
+                    Some((key.clone(), value))
+                })
+                .collect();
+
+            let keys = record.genotypes().keys().clone();
+            let values = record
+                .genotypes()
+                .values()
+                .map(|sample| {
+                    keys.iter()
+                        .map(|k| {
+                            let value = sample.get(k).flatten()?;
+                            match (value, header.formats().get(k)) {
+                                (Value::String(gt), _) if *k == key::GENOTYPE => {
+                                    Some(Value::String(super::recode_gt(gt, allele_no)))
+                                }
+                                (value, Some(format)) => super::decompose_format_value(
+                                    value,
+                                    format.number(),
+                                    allele_no,
+                                    allele_count,
+                                ),
+                                (value, None) => Some(value.clone()),
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            *split.genotypes_mut() = vcf::record::Genotypes::new(keys, values);
+
+            split
+        })
+        .collect()
+}
+
+/// Merge the `FILTER` values of the records at one site.
+///
+/// The result is `PASS` if any record passes and the union of the failed filters
+/// otherwise.
+fn merge_filters<'r>(
+    filters: impl Iterator<Item = &'r vcf::record::Filters>,
+) -> Option<vcf::record::Filters> {
+    use vcf::record::Filters;
+
+    let mut result = None;
+    for filters in filters {
+        result = match (result, filters) {
+            (Some(Filters::Pass), _) | (_, Filters::Pass) => Some(Filters::Pass),
+            (Some(Filters::Fail(mut names)), Filters::Fail(other)) => {
+                names.extend(other.iter().cloned());
+                Some(Filters::Fail(names))
+            }
+            (None, Filters::Fail(names)) => Some(Filters::Fail(names.clone())),
+        };
+    }
+    result
+}
+
+/// Build the merged record from the `records` at one site, given by input file index.
+///
+/// The samples are written in the order of the input files, `sample_counts` gives the
+/// number of samples per input file.  Samples of input files without a record at the site
+/// are written with a missing genotype (`./.`).
+///
+/// `QUAL` is the maximum of the records, `FILTER` is merged with `merge_filters()`, and
+/// `INFO` is the union of the fields with the value of the first record for each key,
+/// except for `INFO/DP`, which is summed.
+fn merge_record(
+    records: &[(usize, vcf::Record)],
+    sample_counts: &[usize],
+) -> Result<vcf::Record, anyhow::Error> {
+    use vcf::record::genotypes::{keys::key, sample::Value, Keys};
+
+    let mut record = records
+        .first()
+        .map(|(_, record)| record.clone())
+        .expect("at least one record must be given");
+
+    *record.quality_score_mut() = records
+        .iter()
+        .filter_map(|(_, record)| record.quality_score())
+        .max_by(|a, b| f32::from(*a).total_cmp(&f32::from(*b)));
+    *record.filters_mut() =
+        merge_filters(records.iter().filter_map(|(_, record)| record.filters()));
+    for (_, other) in records.iter().skip(1) {
+        let info = record.info_mut();
+        for (key, value) in other.info().as_ref() {
+            if let Some(existing) = info.get_mut(key) {
+                if *key == vcf::record::info::field::key::TOTAL_DEPTH {
+                    if let (
+                        Some(vcf::record::info::field::Value::Integer(sum)),
+                        Some(vcf::record::info::field::Value::Integer(depth)),
+                    ) = (existing, value)
+                    {
+                        *sum += depth;
+                    }
+                }
+            } else {
+                info.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    let mut keys = Vec::new();
+    for (_, input_record) in records {
+        for key in input_record.genotypes().keys().iter() {
+            if !keys.contains(key) {
+                keys.push(key.clone());
+            }
+        }
+    }
+
+    let mut values = Vec::new();
+    for (input_idx, sample_count) in sample_counts.iter().enumerate() {
+        let input_record = records
+            .iter()
+            .find(|(idx, _)| *idx == input_idx)
+            .map(|(_, record)| record);
+        for sample_idx in 0..*sample_count {
+            let input_sample =
+                input_record.and_then(|record| record.genotypes().get_index(sample_idx));
+            let sample_values = keys
+                .iter()
+                .map(|k| match input_sample.as_ref() {
+                    Some(input_sample) => input_sample.get(k).flatten().cloned(),
+                    None if *k == key::GENOTYPE => Some(Value::String("./.".into())),
+                    None => None,
+                })
+                .collect::<Vec<_>>();
+            values.push(sample_values);
+        }
+    }
+
+    *record.genotypes_mut() = vcf::record::Genotypes::new(
+        Keys::try_from(keys).map_err(|e| anyhow::anyhow!("invalid genotype keys: {}", e))?,
+        values,
+    );
+    Ok(record)
+}
+
+/// State of the merge in `merge_records()`.
+struct MergeState<'a> {
+    /// The record streams of the input files.
+    inputs: Vec<RecordStream<'a>>,
+    /// The headers of the input files.
+    input_headers: Vec<vcf::Header>,
+    /// The single-allele records of each input at its current position, sorted by key.
+    queues: Vec<VecDeque<(SiteKey, vcf::Record)>>,
+    /// The first record of each input after its current position, if read already.
+    lookaheads: Vec<Option<(SiteKey, vcf::Record)>>,
+    /// Whether the input has been read completely.
+    done: Vec<bool>,
+    /// Number of samples in each input.
+    sample_counts: Vec<usize>,
+    /// The merged header.
+    header: &'a vcf::Header,
+}
+
+impl MergeState<'_> {
+    /// Read the next record of input `idx` with its key, if any.
+    async fn read(&mut self, idx: usize) -> Result<Option<(SiteKey, vcf::Record)>, anyhow::Error> {
+        if self.done[idx] {
+            return Ok(None);
+        }
+        match self.inputs[idx].next().await.transpose()? {
+            Some(record) => Ok(Some((site_key(self.header, &record)?, record))),
+            None => {
+                self.done[idx] = true;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Fill the queue of each input that is empty with all records at its next position.
+    async fn fill(&mut self) -> Result<(), anyhow::Error> {
+        for idx in 0..self.inputs.len() {
+            if !self.queues[idx].is_empty() {
+                continue;
+            }
+            let first = match self.lookaheads[idx].take() {
+                Some(head) => Some(head),
+                None => self.read(idx).await?,
+            };
+            let Some((first_key, first_record)) = first else {
+                continue;
+            };
+            let mut records = vec![first_record];
+            while let Some((key, record)) = self.read(idx).await? {
+                if (key.0, key.1) == (first_key.0, first_key.1) {
+                    records.push(record);
+                } else if (key.0, key.1) < (first_key.0, first_key.1) {
+                    anyhow::bail!(
+                        "input file #{} is not sorted in the order of the contig header lines",
+                        idx
+                    );
+                } else {
+                    self.lookaheads[idx] = Some((key, record));
+                    break;
+                }
+            }
+
+            let mut queue = records
+                .into_iter()
+                .flat_map(|record| split_alleles(&self.input_headers[idx], record))
+                .map(|record| Ok((site_key(self.header, &record)?, record)))
+                .collect::<Result<Vec<_>, anyhow::Error>>()?;
+            queue.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+            self.queues[idx] = queue.into();
+        }
+        Ok(())
+    }
+
+    /// Return the merged record at the next site, if any.
+    async fn next_record(&mut self) -> Result<Option<vcf::Record>, anyhow::Error> {
+        self.fill().await?;
+        let Some(min_key) = self
+            .queues
+            .iter()
+            .filter_map(|queue| queue.front())
+            .map(|(key, _)| key)
+            .min()
+            .cloned()
+        else {
+            return Ok(None);
+        };
+
+        let mut records = Vec::new();
+        for (idx, queue) in self.queues.iter_mut().enumerate() {
+            if queue.front().map(|(key, _)| key) == Some(&min_key) {
+                let (_, record) = queue.pop_front().expect("checked above");
+                records.push((idx, record));
+            }
+        }
+
+        merge_record(&records, &self.sample_counts).map(Some)
+    }
+}
+
+/// Merge the record streams `inputs` of the input files with the given `input_headers`.
+///
+/// The records must be sorted by position and in the order of the contigs in the merged
+/// `header` built with `merge_headers()`.
+pub fn merge_records<'a>(
+    inputs: Vec<RecordStream<'a>>,
+    input_headers: &[vcf::Header],
+    header: &'a vcf::Header,
+) -> RecordStream<'a> {
+    let state = MergeState {
+        queues: inputs.iter().map(|_| VecDeque::new()).collect(),
+        lookaheads: inputs.iter().map(|_| None).collect(),
+        done: vec![false; inputs.len()],
+        inputs,
+        input_headers: input_headers.to_vec(),
+        sample_counts: input_headers
+            .iter()
+            .map(|header| header.sample_names().len())
+            .collect(),
+        header,
+    };
+    Box::pin(futures::stream::try_unfold(state, |mut state| async move {
+        Ok(state.next_record().await?.map(|record| (record, state)))
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use futures::TryStreamExt as _;
+    use noodles_vcf as vcf;
+
+    use crate::seqvars::ingest::regions::RecordStream;
+
+    /// Build a header with the contigs `1` and `2` and the given samples.
+    fn header(samples: &[&str]) -> vcf::Header {
+        use vcf::header::record::value::{
+            map::{Contig, Format, Info},
+            Map,
+        };
+        use vcf::record::{genotypes::keys::key, info::field::key as info_key};
+
+        samples
+            .iter()
+            .fold(
+                vcf::Header::builder()
+                    .add_contig("1".parse().unwrap(), Map::<Contig>::new())
+                    .add_contig("2".parse().unwrap(), Map::<Contig>::new())
+                    .add_info(
+                        info_key::TOTAL_DEPTH,
+                        Map::<Info>::from(&info_key::TOTAL_DEPTH),
+                    )
+                    .add_format(key::GENOTYPE, Map::<Format>::from(&key::GENOTYPE)),
+                |builder, sample| builder.add_sample_name(*sample),
+            )
+            .build()
+    }
+
+    /// Parse the records `lines` with `header` into a record stream.
+    fn records<'a>(header: &vcf::Header, lines: &[&str]) -> RecordStream<'a> {
+        let records = lines
+            .iter()
+            .map(|line| {
+                vcf::Record::try_from((header, *line)).map_err(|e| anyhow::anyhow!("{}", e))
+            })
+            .collect::<Vec<_>>();
+        Box::pin(futures::stream::iter(records))
+    }
+
+    #[tokio::test]
+    async fn merge_trio() -> Result<(), anyhow::Error> {
+        let headers = vec![header(&["index"]), header(&["father"]), header(&["mother"])];
+        let merged_header = super::merge_headers(&headers)?;
+        assert_eq!(
+            merged_header.sample_names().iter().collect::<Vec<_>>(),
+            vec!["index", "father", "mother"]
+        );
+
+        let inputs = vec![
+            records(
+                &headers[0],
+                &[
+                    "1\t100\t.\tA\tG\t.\t.\t.\tGT\t0/1",
+                    "1\t200\t.\tC\tT\t.\t.\t.\tGT\t1/1",
+                    "2\t50\t.\tG\tGA\t.\t.\t.\tGT\t0/1",
+                ],
+            ),
+            records(
+                &headers[1],
+                &[
+                    "1\t100\t.\tA\tG\t.\t.\t.\tGT\t0/1",
+                    "1\t200\t.\tC\tA\t.\t.\t.\tGT\t0/1",
+                ],
+            ),
+            records(&headers[2], &["2\t50\t.\tG\tGA\t.\t.\t.\tGT\t1/1"]),
+        ];
+        let merged = super::merge_records(inputs, &headers, &merged_header)
+            .map_ok(|record| record.to_string())
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        assert_eq!(
+            merged,
+            vec![
+                "1\t100\t.\tA\tG\t.\t.\t.\tGT\t0/1\t0/1\t./.",
+                "1\t200\t.\tC\tA\t.\t.\t.\tGT\t./.\t0/1\t./.",
+                "1\t200\t.\tC\tT\t.\t.\t.\tGT\t1/1\t./.\t./.",
+                "2\t50\t.\tG\tGA\t.\t.\t.\tGT\t0/1\t./.\t1/1",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn merge_multiallelic() -> Result<(), anyhow::Error> {
+        let headers = vec![header(&["index"]), header(&["father"])];
+        let merged_header = super::merge_headers(&headers)?;
+        let inputs = vec![
+            records(
+                &headers[0],
+                &[
+                    "1\t100\t.\tA\tT,G\t30\tPASS\tDP=10\tGT\t1/2",
+                    "1\t100\t.\tA\tC\t20\tq10\tDP=10\tGT\t0/1",
+                ],
+            ),
+            records(&headers[1], &["1\t100\t.\tA\tT\t50\tq10\tDP=5\tGT\t0/1"]),
+        ];
+        let merged = super::merge_records(inputs, &headers, &merged_header)
+            .map_ok(|record| record.to_string())
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        assert_eq!(
+            merged,
+            vec![
+                "1\t100\t.\tA\tC\t20\tq10\tDP=10\tGT\t0/1\t./.",
+                "1\t100\t.\tA\tG\t30\tPASS\tDP=10\tGT\t0/1\t./.",
+                "1\t100\t.\tA\tT\t50\tPASS\tDP=15\tGT\t1/0\t0/1",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_filters() {
+        use vcf::record::Filters;
+
+        let fail = |names: &[&str]| Filters::try_from_iter(names.iter().copied()).unwrap();
+        assert_eq!(super::merge_filters(std::iter::empty()), None);
+        assert_eq!(
+            super::merge_filters([fail(&["q10"]), Filters::Pass].iter()),
+            Some(Filters::Pass)
+        );
+        assert_eq!(
+            super::merge_filters([fail(&["q10"]), fail(&["s50", "q10"])].iter()),
+            Some(fail(&["q10", "s50"]))
+        );
+    }
+
+    #[tokio::test]
+    async fn merge_unsorted() -> Result<(), anyhow::Error> {
+        let headers = vec![header(&["index"]), header(&["father"])];
+        let merged_header = super::merge_headers(&headers)?;
+        let inputs = vec![
+            records(
+                &headers[0],
+                &[
+                    "1\t200\t.\tC\tT\t.\t.\t.\tGT\t1/1",
+                    "1\t100\t.\tA\tG\t.\t.\t.\tGT\t0/1",
+                ],
+            ),
+            records(&headers[1], &["1\t150\t.\tA\tG\t.\t.\t.\tGT\t0/1"]),
+        ];
+        let result = super::merge_records(inputs, &headers, &merged_header)
+            .try_collect::<Vec<_>>()
+            .await;
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_headers_duplicate_samples() {
+        let headers = vec![header(&["index"]), header(&["index"])];
+        assert!(super::merge_headers(&headers).is_err());
+    }
+}
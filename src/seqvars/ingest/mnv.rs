@@ -0,0 +1,231 @@
+//! Merging of adjacent phased SNVs into multi-nucleotide variants (MNVs).
+//!
+//! Two SNVs on the same haplotype within one codon can jointly encode a different amino
+//! acid change than either of them alone.  Runs of up to `MAX_MNV_LEN` SNVs at adjacent
+//! positions are thus merged into one MNV record if they are on the same haplotypes in all
+//! samples, such that the consequence is predicted for the MNV as a whole.  The input has
+//! no reference sequence, so SNVs with a gap in between cannot be merged.
+//!
+//! Two records are on the same haplotypes in a sample if their genotypes are equal and,
+//! for heterozygous genotypes, phased with the same phase set.  The `FORMAT` and `INFO`
+//! fields of the merged record are taken from the first SNV.
+
+use futures::TryStreamExt as _;
+use noodles_vcf as vcf;
+use vcf::record::{alternate_bases::Allele, genotypes::keys::key};
+
+use super::regions::RecordStream;
+
+/// Maximal number of SNVs to merge, i.e., the length of a codon.
+pub const MAX_MNV_LEN: usize = 3;
+
+/// Return whether `record` is a biallelic SNV.
+fn is_snv(record: &vcf::Record) -> bool {
+    record.reference_bases().len() == 1
+        && record.alternate_bases().len() == 1
+        && matches!(&record.alternate_bases()[0], Allele::Bases(bases) if bases.len() == 1)
+}
+
+/// Return whether the samples of `lhs` and `rhs` are on the same haplotypes.
+fn same_haplotypes(lhs: &vcf::Record, rhs: &vcf::Record) -> bool {
+    lhs.genotypes()
+        .values()
+        .zip(rhs.genotypes().values())
+        .all(|(lhs, rhs)| {
+            let gt = |sample: &vcf::record::genotypes::Sample<'_>| match sample.get(&key::GENOTYPE)
+            {
+                Some(Some(vcf::record::genotypes::sample::Value::String(gt))) => Some(gt.clone()),
+                _ => None,
+            };
+            let (Some(lhs_gt), Some(rhs_gt)) = (gt(&lhs), gt(&rhs)) else {
+                return false;
+            };
+            if lhs_gt != rhs_gt {
+                return false;
+            }
+            let het = lhs_gt.contains('0') && lhs_gt.contains('1');
+            !het || (lhs_gt.contains('|')
+                && lhs.get(&key::PHASE_SET).flatten() == rhs.get(&key::PHASE_SET).flatten())
+        })
+}
+
+/// Return whether `record` extends the run of SNVs ending in `last`.
+fn extends(last: &vcf::Record, record: &vcf::Record) -> bool {
+    is_snv(record)
+        && record.chromosome() == last.chromosome()
+        && usize::from(record.position()) == usize::from(last.position()) + 1
+        && same_haplotypes(last, record)
+}
+
+/// Merge the adjacent SNVs in `run` into one MNV record.
+fn merge_run(mut run: Vec<vcf::Record>) -> Result<vcf::Record, anyhow::Error> {
+    if run.len() == 1 {
+        return Ok(run.pop().expect("checked above"));
+    }
+    let reference = run
+        .iter()
+        .map(|record| record.reference_bases().to_string())
+        .collect::<String>();
+    let alternative = run
+        .iter()
+        .map(|record| record.alternate_bases().to_string())
+        .collect::<String>();
+
+    let mut result = run.swap_remove(0);
+    *result.reference_bases_mut() = reference
+        .parse()
+        .map_err(|e| anyhow::anyhow!("invalid reference bases {}: {}", &reference, e))?;
+    *result.alternate_bases_mut() = alternative
+        .parse()
+        .map_err(|e| anyhow::anyhow!("invalid alternate bases {}: {}", &alternative, e))?;
+    Ok(result)
+}
+
+/// State for merging the SNVs of a record stream.
+struct MnvState<'a> {
+    /// The input records.
+    input: RecordStream<'a>,
+    /// The record read after the last run, if any.
+    pending: Option<vcf::Record>,
+}
+
+impl MnvState<'_> {
+    /// Return the next record, merging adjacent phased SNVs.
+    async fn next_record(&mut self) -> Result<Option<vcf::Record>, anyhow::Error> {
+        let first = match self.pending.take() {
+            Some(record) => record,
+            None => match self.input.try_next().await? {
+                Some(record) => record,
+                None => return Ok(None),
+            },
+        };
+        if !is_snv(&first) {
+            return Ok(Some(first));
+        }
+
+        let mut run = vec![first];
+        while run.len() < MAX_MNV_LEN {
+            match self.input.try_next().await? {
+                Some(record) if extends(run.last().expect("not empty"), &record) => {
+                    run.push(record)
+                }
+                Some(record) => {
+                    self.pending = Some(record);
+                    break;
+                }
+                None => break,
+            }
+        }
+        merge_run(run).map(Some)
+    }
+}
+
+/// Merge the adjacent phased SNVs of the sorted `records` into MNV records.
+pub fn merge_phased_snvs(records: RecordStream<'_>) -> RecordStream<'_> {
+    let state = MnvState {
+        input: records,
+        pending: None,
+    };
+    Box::pin(futures::stream::try_unfold(state, |mut state| async move {
+        Ok(state.next_record().await?.map(|record| (record, state)))
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use futures::TryStreamExt as _;
+    use noodles_vcf as vcf;
+
+    use crate::seqvars::ingest::regions::RecordStream;
+
+    /// Build a header with the contig `1` and the samples `index` and `father`.
+    fn header() -> vcf::Header {
+        use vcf::header::record::value::{
+            map::{Contig, Format},
+            Map,
+        };
+        use vcf::record::genotypes::keys::key;
+
+        vcf::Header::builder()
+            .add_contig("1".parse().unwrap(), Map::<Contig>::new())
+            .add_format(key::GENOTYPE, Map::<Format>::from(&key::GENOTYPE))
+            .add_format(key::PHASE_SET, Map::<Format>::from(&key::PHASE_SET))
+            .add_sample_name("index")
+            .add_sample_name("father")
+            .build()
+    }
+
+    /// Merge the records `lines` and return them as `POS:REF:ALT`.
+    async fn merge(lines: &[&str]) -> Result<Vec<String>, anyhow::Error> {
+        let header = header();
+        let records = lines
+            .iter()
+            .map(|line| {
+                vcf::Record::try_from((&header, *line)).map_err(|e| anyhow::anyhow!("{}", e))
+            })
+            .collect::<Vec<_>>();
+        let records: RecordStream = Box::pin(futures::stream::iter(records));
+
+        Ok(super::merge_phased_snvs(records)
+            .try_collect::<Vec<_>>()
+            .await?
+            .iter()
+            .map(|record| {
+                format!(
+                    "{}:{}:{}",
+                    usize::from(record.position()),
+                    record.reference_bases(),
+                    record.alternate_bases()
+                )
+            })
+            .collect())
+    }
+
+    #[rstest::rstest]
+    #[case::phased(
+        &["0|1:7", "0|0:7"],
+        &["0|1:7", "0|0:7"],
+        vec!["100:AC:GT"]
+    )]
+    #[case::unphased_carrier(&["1/1:.", "0/1:."], &["1/1:.", "0/1:."], vec!["100:A:G", "101:C:T"])]
+    #[case::hom(&["1/1:.", "0/0:."], &["1/1:.", "0/0:."], vec!["100:AC:GT"])]
+    #[case::other_haplotype(&["0|1:7", "0|0:7"], &["1|0:7", "0|0:7"], vec!["100:A:G", "101:C:T"])]
+    #[case::other_phase_set(&["0|1:7", "0|0:7"], &["0|1:9", "0|0:9"], vec!["100:A:G", "101:C:T"])]
+    #[case::unphased(&["0/1:.", "0/0:."], &["0/1:.", "0/0:."], vec!["100:A:G", "101:C:T"])]
+    #[tokio::test]
+    async fn merge_pair(
+        #[case] first: &[&str],
+        #[case] second: &[&str],
+        #[case] expected: Vec<&str>,
+    ) -> Result<(), anyhow::Error> {
+        let lines = [
+            format!("1\t100\t.\tA\tG\t.\tPASS\t.\tGT:PS\t{}", first.join("\t")),
+            format!("1\t101\t.\tC\tT\t.\tPASS\t.\tGT:PS\t{}", second.join("\t")),
+        ];
+        let lines = lines.iter().map(String::as_str).collect::<Vec<_>>();
+
+        assert_eq!(merge(&lines).await?, expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn merge_runs() -> Result<(), anyhow::Error> {
+        let lines = [
+            "1\t100\t.\tA\tG\t.\tPASS\t.\tGT\t1/1\t0/0",
+            "1\t101\t.\tC\tT\t.\tPASS\t.\tGT\t1/1\t0/0",
+            "1\t102\t.\tG\tA\t.\tPASS\t.\tGT\t1/1\t0/0",
+            "1\t103\t.\tT\tC\t.\tPASS\t.\tGT\t1/1\t0/0",
+            "1\t105\t.\tT\tC\t.\tPASS\t.\tGT\t1/1\t0/0",
+            "1\t106\t.\tTA\tT\t.\tPASS\t.\tGT\t1/1\t0/0",
+            "1\t107\t.\tA\tC,G\t.\tPASS\t.\tGT\t1/2\t0/0",
+        ];
+
+        assert_eq!(
+            merge(&lines).await?,
+            vec!["100:ACG:GTA", "103:T:C", "105:T:C", "106:TA:T", "107:A:C,G"]
+        );
+
+        Ok(())
+    }
+}
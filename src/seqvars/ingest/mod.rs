@@ -1,32 +1,61 @@
 //! Implementation of `seqvars ingest` subcommand.
 
-use std::sync::{Arc, OnceLock};
+use std::sync::Arc;
 
 use crate::{
-    common::{self, worker_version, GenomeRelease},
+    common::{
+        self,
+        kv::{
+            http::{HttpStore, Recordings},
+            VariantKvStore,
+        },
+        noodles::{open_vcf_reader, open_vcf_writer_with_counters, WriteIndex},
+        perf::RocksDbCounters,
+        worker_version, GenomeRelease,
+    },
     flush_and_shutdown,
 };
 use futures::TryStreamExt;
 use mehari::{
     annotate::seqvars::provider::Provider as MehariProvider,
-    common::noodles::{open_vcf_reader, open_vcf_writer, AsyncVcfReader, AsyncVcfWriter},
+    common::noodles::{AsyncVcfReader, AsyncVcfWriter},
 };
 use noodles_vcf as vcf;
 use thousands::Separable;
 use tokio::io::AsyncWriteExt;
 
+pub mod alleles;
+pub mod batching;
+pub mod bench;
+pub mod csq_cache;
+pub mod family;
 pub mod header;
+pub mod inhouse;
+pub mod merge;
+pub mod mnv;
+pub mod prefetch;
+pub mod quick_qc;
+pub mod refine;
+pub mod regions;
+pub mod resume;
+pub mod split_cohort;
+pub mod stats;
+pub mod tx_policy;
+
+use regions::RecordStream;
 
 /// Command line arguments for `seqvars ingest` subcommand.
-#[derive(Debug, clap::Parser)]
+#[derive(Debug, Clone, clap::Parser)]
 #[command(author, version, about = "ingest sequence variant VCF", long_about = None)]
 pub struct Args {
     /// Value to write to `##fileDate`.
     #[arg(long)]
     pub file_date: String,
-    /// The case UUID to write out.
-    #[clap(long)]
-    pub case_uuid: uuid::Uuid,
+    /// The case UUID to write out; not used with `--annotate-only`.
+    ///
+    /// Defaults to the identifier of `--path-phenopacket` if it is a UUID.
+    #[clap(long, required_unless_present_any = ["annotate_only", "path_phenopacket"])]
+    pub case_uuid: Option<uuid::Uuid>,
     /// The assumed genome build.
     #[clap(long)]
     pub genomebuild: GenomeRelease,
@@ -34,19 +63,172 @@ pub struct Args {
     /// The path to the mehari database.
     #[clap(long)]
     pub path_mehari_db: String,
-    /// Path to the pedigree file.
-    #[clap(long)]
-    pub path_ped: String,
-    /// Path to input file.
-    #[clap(long)]
-    pub path_in: String,
+    /// Path to the pedigree file; not used with `--annotate-only`.
+    #[clap(long, required_unless_present_any = ["annotate_only", "path_phenopacket"])]
+    pub path_ped: Option<String>,
+    /// How to handle samples that are only in the pedigree or only in the input file.
+    #[clap(long, value_enum, default_value_t = header::SampleMismatchPolicy::default())]
+    pub sample_mismatch_policy: header::SampleMismatchPolicy,
+    /// HPO terms of the individuals for the `<path-out>.family.json` file, e.g.,
+    /// `index=HP:0001250,index=HP:0000118`.
+    #[clap(long, value_delimiter = ',')]
+    pub hpo_terms: Vec<String>,
+    /// Path to a GA4GH phenopacket JSON file (a `Family` or a single `Phenopacket`) to read
+    /// the pedigree, the HPO terms, and the case UUID from instead of `--path-ped` and
+    /// `--hpo-terms`.
+    #[clap(long, conflicts_with_all = ["path_ped", "hpo_terms"])]
+    pub path_phenopacket: Option<String>,
+    /// Only annotate the input without case semantics, e.g., for research cohorts.
+    ///
+    /// No pedigree or case UUID is needed, all samples of the input are written in their
+    /// order, and no `<path-out>.family.json` file is written.
+    #[clap(
+        long,
+        conflicts_with_all = ["case_uuid", "path_ped", "hpo_terms", "path_phenopacket"]
+    )]
+    pub annotate_only: bool,
+    /// Path to input files, may be `s3://` or `http(s)://` URLs; multiple files are merged
+    /// by position on the fly.
+    #[clap(long, required = true)]
+    pub path_in: Vec<String>,
     /// Path to output file.
     #[clap(long)]
     pub path_out: String,
 
+    /// Additional `FORMAT` keys to copy from the input, e.g., `VAF,PL,SB`; values of keys
+    /// with `Number=A`, `R`, or `G` are restricted to the written allele.
+    #[clap(long, value_delimiter = ',')]
+    pub extra_format_keys: Vec<String>,
+    /// `INFO` keys to copy from the input, e.g., `FractionInformativeReads`; values of keys
+    /// with `Number=A`, `R`, or `G` are restricted to the written allele.
+    #[clap(long, value_delimiter = ',')]
+    pub keep_info_keys: Vec<String>,
+    /// Refine the diploid genotypes from `FORMAT/PL` (or `FORMAT/GL`) and gnomAD allele
+    /// frequency priors and write the posteriors as `FORMAT/PP`.
+    #[clap(long, conflicts_with = "skip_freq")]
+    pub refine_genotypes: bool,
+    /// Do not annotate the gnomAD and HelixMtDb frequencies and do not open their database.
+    #[clap(long)]
+    pub skip_freq: bool,
+    /// Do not annotate the ClinVar information and do not open its database.
+    #[clap(long)]
+    pub skip_clinvar: bool,
+    /// Do not predict the variant consequences (`INFO/ANN`) and do not load the
+    /// transcripts.
+    #[clap(long)]
+    pub skip_csq: bool,
+    /// The transcripts to write the consequences in `INFO/ANN` for.
+    #[clap(long, value_enum, default_value_t = tx_policy::TxPolicy::default())]
+    pub tx_policy: tx_policy::TxPolicy,
+    /// Optional path to the in-house frequency RocksDB built by `seqvars aggregate` for
+    /// writing the `INFO/inhouse_{an,hom,het,hemi}` counts.
+    #[clap(long)]
+    pub path_inhouse_db: Option<String>,
+    /// Column family name for the count data in the in-house RocksDB.
+    #[clap(long, default_value = "counts")]
+    pub inhouse_cf_counts: String,
+    /// Merge runs of adjacent SNVs on the same haplotypes into MNV records, e.g., for the
+    /// consequence of two phased SNVs in one codon.
+    #[clap(long)]
+    pub merge_phased_snvs: bool,
+
+    /// Restrict to the given regions, e.g., `chr1:100-200,chr2`.
+    #[clap(long, value_delimiter = ',')]
+    pub regions: Vec<String>,
+    /// Restrict to the regions from the given BED file.
+    #[clap(long)]
+    pub path_regions_bed: Option<String>,
+
     /// Maximal number of variants to write out; optional.
     #[clap(long)]
     pub max_var_count: Option<usize>,
+    /// The I/O backend to use.
+    #[clap(long, value_enum, default_value_t = IoBackend::default())]
+    pub io_backend: IoBackend,
+    /// Walk the frequency and ClinVar databases with iterators (one per contig) in
+    /// lock-step with the coordinate-sorted input instead of one point lookup per record;
+    /// with `--io-backend async`, each batch is checked on the contigs in parallel.
+    #[clap(long)]
+    pub rocksdb_prefetch: bool,
+    /// Where to look up the frequencies and ClinVar information.
+    #[clap(long, value_enum, default_value_t = AnnotationBackend::default())]
+    pub annotation_backend: AnnotationBackend,
+    /// Base URL of the annonars server for `--annotation-backend http`.
+    #[clap(long)]
+    pub annotation_url: Option<String>,
+    /// Maximal number of concurrent requests to the annonars server.
+    #[clap(long, default_value = "64")]
+    pub annotation_batch_size: usize,
+    /// Number of retries of failed requests to the annonars server.
+    #[clap(long, default_value = "3")]
+    pub annotation_max_retries: u32,
+    /// Directory to record the responses of the annonars server in and to replay them from
+    /// on re-runs.
+    #[clap(long)]
+    pub annotation_recordings: Option<String>,
+    /// Only replay the responses from `--annotation-recordings` and fail for missing ones
+    /// instead of querying the annonars server, e.g., for deterministic tests.
+    #[clap(long, requires = "annotation_recordings")]
+    pub annotation_replay: bool,
+    /// Number of transcript bins to cache for skipping the consequence prediction far from
+    /// any transcript; `0` disables the cache.
+    #[clap(long, default_value = "10000")]
+    pub csq_cache_size: usize,
+    /// The index to write for bgzip-compressed output.
+    #[clap(long, value_enum, default_value_t = WriteIndex::default())]
+    pub write_index: WriteIndex,
+    /// Resume from the `<path-out>.partial` file left by a failed run with the same
+    /// arguments; the partial output is ignored if its header differs.
+    #[clap(long)]
+    pub resume_from_partial: bool,
+    /// Re-read the output file after writing and check the records against the header.
+    #[clap(long)]
+    pub validate_output: bool,
+    /// Fail on unexpected allele forms, e.g., symbolic alleles such as `<DEL>`, instead of
+    /// skipping them.
+    #[clap(long)]
+    pub strict: bool,
+    /// Path to write the JSON report with the counts of skipped alleles by reason to.
+    #[clap(long)]
+    pub path_allele_report: Option<String>,
+
+    /// Only compute approximate QC metrics from a sample of the input records and write
+    /// them to `--path-quick-qc` instead of ingesting.
+    #[clap(long, requires = "path_quick_qc")]
+    pub quick_qc: bool,
+    /// Path to the QC metrics JSON file written with `--quick-qc`.
+    #[clap(long)]
+    pub path_quick_qc: Option<String>,
+    /// Use every Nth record for `--quick-qc`.
+    #[clap(long, default_value = "100")]
+    pub quick_qc_every: usize,
+    /// Use the first N records of each contig for `--quick-qc` instead of every Nth.
+    #[clap(long)]
+    pub quick_qc_first_per_contig: Option<usize>,
+    /// Maximal number of seconds to spend on `--quick-qc`.
+    #[clap(long, default_value = "60")]
+    pub quick_qc_max_seconds: u64,
+}
+
+/// Selection of the I/O backend for `seqvars ingest`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum IoBackend {
+    /// Read, annotate, and write one record after another.
+    #[default]
+    Sequential,
+    /// Overlap decompression, annotation, and compression using tokio tasks.
+    Async,
+}
+
+/// Selection of the frequency and ClinVar annotation backend for `seqvars ingest`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum AnnotationBackend {
+    /// Query the RocksDB databases in `--path-mehari-db`.
+    #[default]
+    Local,
+    /// Query the annonars server at `--annotation-url`; requests are batched with the
+    /// `async` I/O backend only.
+    Http,
 }
 
 /// Return path component fo rth egiven assembly.
@@ -69,6 +251,10 @@ struct KnownFormatKeys {
         vcf::record::genotypes::keys::Key,
         vcf::record::genotypes::keys::Key,
     >,
+    /// Additional keys copied from the input with their `Number` from the input header.
+    extra_keys: indexmap::IndexMap<vcf::record::genotypes::keys::Key, vcf::header::Number>,
+    /// Whether to refine the genotypes and write `FORMAT/PP`, see [`refine`].
+    refine_genotypes: bool,
 }
 
 impl Default for KnownFormatKeys {
@@ -96,11 +282,44 @@ impl Default for KnownFormatKeys {
             )]
             .into_iter()
             .collect(),
+            extra_keys: Default::default(),
+            refine_genotypes: false,
         }
     }
 }
 
 impl KnownFormatKeys {
+    /// Construct with the additional `extra_keys` that must be defined in `input_header`.
+    fn with_extra_keys(
+        extra_keys: &[String],
+        input_header: &vcf::Header,
+    ) -> Result<Self, anyhow::Error> {
+        let mut result = Self::default();
+        for extra_key in extra_keys {
+            let key: vcf::record::genotypes::keys::Key = extra_key
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid FORMAT key {}: {}", extra_key, e))?;
+            if result.known_keys.contains(&key) {
+                tracing::warn!(
+                    "{}: ignoring extra FORMAT key {} that is always copied",
+                    crate::codes::INGEST_EXTRA_FORMAT_KEY_IGNORED,
+                    &key
+                );
+                continue;
+            }
+            let format = input_header.formats().get(&key).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{}: extra FORMAT key {} not defined in input header",
+                    crate::codes::INGEST_UNDEFINED_FORMAT_KEY,
+                    &key
+                )
+            })?;
+            result.known_keys.push(key.clone());
+            result.extra_keys.insert(key, format.number());
+        }
+        Ok(result)
+    }
+
     /// Map from known to output key.
     pub fn known_to_output(
         &self,
@@ -110,11 +329,35 @@ impl KnownFormatKeys {
     }
 }
 
-/// The known `FORMAT` keys.
-static KNOWN_FORMAT_KEYS: OnceLock<KnownFormatKeys> = OnceLock::new();
-
-/// Regular expression for parsing `GT` values.
-static GT_RE: OnceLock<regex::Regex> = OnceLock::new();
+/// Recode the `GT` value `gt` for the split-off allele `allele_no`.
+///
+/// The current allele becomes "1" and all other alleles, including the other alternate
+/// alleles, become "0" (reference); missing alleles stay missing.  The separators and the
+/// order of the alleles are kept as they are so this works for haploid (e.g., chrY or
+/// chrMT), diploid, and phased genotypes alike.  For phased genotypes, each allele thus
+/// stays on its haplotype, e.g., `1|2` becomes `1|0` and `0|1` in the records for the
+/// first and second alternate allele, consistent with the `PS` copied to both.
+fn recode_gt(gt: &str, allele_no: usize) -> String {
+    let curr_allele = format!("{}", allele_no);
+    let recode = |allele: &str| {
+        if allele == "." {
+            "."
+        } else if allele == curr_allele {
+            "1"
+        } else {
+            "0"
+        }
+    };
+    let mut result = String::with_capacity(gt.len());
+    let mut start = 0;
+    for (pos, sep) in gt.match_indices(['/', '|']) {
+        result.push_str(recode(&gt[start..pos]));
+        result.push_str(sep);
+        start = pos + sep.len();
+    }
+    result.push_str(recode(&gt[start..]));
+    result
+}
 
 /// Transform the ``FORMAT`` key if known.
 fn transform_format_value(
@@ -123,19 +366,6 @@ fn transform_format_value(
     allele_no: usize,
     sample: &vcf::record::genotypes::Sample<'_>,
 ) -> Option<Option<vcf::record::genotypes::sample::Value>> {
-    let gt_re = GT_RE
-        .get_or_init(|| regex::Regex::new(r"([^\|]+)([/|])([^\|]+)").expect("could not parse RE"));
-
-    let curr_allele = format!("{}", allele_no);
-
-    fn transform_allele(allele_to_transform: &str, curr_allele: &str) -> &'static str {
-        if allele_to_transform == curr_allele {
-            "1"
-        } else {
-            "0"
-        }
-    }
-
     if let Some(value) = value {
         Some(Some(match key.as_ref() {
             "GT" => {
@@ -148,27 +378,7 @@ fn transform_format_value(
                     vcf::record::genotypes::sample::Value::String(gt) => gt.clone(),
                     _ => unreachable!("FORMAT/GT must be string"),
                 };
-                if ["./.", ".|.", "."].contains(&gt.as_str()) {
-                    // no need to transform no-call
-                    vcf::record::genotypes::sample::Value::String(gt)
-                } else {
-                    // transform all others
-                    let gt_captures = gt_re
-                        .captures(&gt)
-                        .unwrap_or_else(|| panic!("FORMAT/GT cannot be parsed: {}", &gt));
-                    let gt_1 = gt_captures.get(1).expect("must be capture").as_str();
-                    let gt_2 = gt_captures.get(2).expect("must be capture").as_str();
-                    let gt_3 = gt_captures.get(3).expect("must be capture").as_str();
-
-                    let new_gt = format!(
-                        "{}{}{}",
-                        transform_allele(gt_1, &curr_allele),
-                        gt_2,
-                        transform_allele(gt_3, &curr_allele),
-                    );
-
-                    vcf::record::genotypes::sample::Value::String(new_gt)
-                }
+                vcf::record::genotypes::sample::Value::String(recode_gt(&gt, allele_no))
             }
             "AD" => {
                 let dp = match sample
@@ -220,6 +430,155 @@ fn transform_format_value(
     }
 }
 
+/// `INFO` keys copied from the input with their `Number` from the input header.
+#[derive(Debug, Default)]
+struct KeptInfoKeys {
+    /// The keys with their `Number`, in the order given on the command line.
+    keys: indexmap::IndexMap<vcf::record::info::field::Key, vcf::header::Number>,
+}
+
+impl KeptInfoKeys {
+    /// Construct for `keep_keys` that must be defined in `input_header`.
+    fn new(keep_keys: &[String], input_header: &vcf::Header) -> Result<Self, anyhow::Error> {
+        let mut keys = indexmap::IndexMap::new();
+        for keep_key in keep_keys {
+            let key: vcf::record::info::field::Key = keep_key
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid INFO key {}: {}", keep_key, e))?;
+            let info = input_header.infos().get(&key).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{}: kept INFO key {} not defined in input header",
+                    crate::codes::INGEST_UNDEFINED_INFO_KEY,
+                    &key
+                )
+            })?;
+            let number = info.number();
+            keys.insert(key, number);
+        }
+        Ok(Self { keys })
+    }
+
+    /// Copy the kept `INFO` fields of `input_record` to `output_record` for allele
+    /// `allele_no`.
+    fn copy_info(
+        &self,
+        input_record: &vcf::Record,
+        output_record: &mut vcf::Record,
+        allele_no: usize,
+    ) {
+        let allele_count = input_record.alternate_bases().len() + 1;
+        for (key, number) in &self.keys {
+            if let Some(Some(value)) = input_record.info().get(key) {
+                if let Some(value) = decompose_info_value(value, *number, allele_no, allele_count) {
+                    output_record.info_mut().insert(key.clone(), Some(value));
+                }
+            }
+        }
+    }
+}
+
+/// Return the indices of the entries for allele `allele_no` of `allele_count` alleles
+/// (including the reference) in an array of `len` values with `number`.
+///
+/// Returns `None` if the whole array is to be kept.
+fn allele_indices(
+    number: vcf::header::Number,
+    allele_no: usize,
+    allele_count: usize,
+    len: usize,
+) -> Option<Vec<usize>> {
+    use vcf::header::Number;
+
+    match number {
+        Number::A => Some(vec![allele_no - 1]),
+        Number::R => Some(vec![0, allele_no]),
+        Number::G if len == allele_count => {
+            // haploid
+            Some(vec![0, allele_no])
+        }
+        Number::G => {
+            // diploid, genotype `j/k` is at `k * (k + 1) / 2 + j`
+            let het = allele_no * (allele_no + 1) / 2;
+            Some(vec![0, het, het + allele_no])
+        }
+        Number::Count(_) | Number::Unknown => None,
+    }
+}
+
+/// Select the entries at `indices` from `values`, `None` if any index is out of range.
+fn select_indices<T: Clone>(values: &[T], indices: &[usize]) -> Option<Vec<T>> {
+    indices
+        .iter()
+        .map(|idx| values.get(*idx).cloned())
+        .collect()
+}
+
+/// Restrict the `value` of a kept `INFO` key with `number` to allele `allele_no` of
+/// `allele_count` alleles (including the reference).
+///
+/// Same as `decompose_format_value` but for `INFO` values.
+fn decompose_info_value(
+    value: &vcf::record::info::field::Value,
+    number: vcf::header::Number,
+    allele_no: usize,
+    allele_count: usize,
+) -> Option<vcf::record::info::field::Value> {
+    use vcf::record::info::field::{value::Array, Value};
+
+    let Value::Array(array) = value else {
+        return Some(value.clone());
+    };
+    let len = match array {
+        Array::Integer(values) => values.len(),
+        Array::Float(values) => values.len(),
+        Array::Character(values) => values.len(),
+        Array::String(values) => values.len(),
+    };
+    let Some(indices) = allele_indices(number, allele_no, allele_count, len) else {
+        return Some(value.clone());
+    };
+    Some(Value::Array(match array {
+        Array::Integer(values) => Array::Integer(select_indices(values, &indices)?),
+        Array::Float(values) => Array::Float(select_indices(values, &indices)?),
+        Array::Character(values) => Array::Character(select_indices(values, &indices)?),
+        Array::String(values) => Array::String(select_indices(values, &indices)?),
+    }))
+}
+
+/// Restrict the `value` of an extra `FORMAT` key with `number` to allele `allele_no` of
+/// `allele_count` alleles (including the reference).
+///
+/// Values of keys with `Number=A`, `R`, or `G` are restricted to the entries for the
+/// written allele; all other values are copied verbatim.  Arrays of unexpected length
+/// are written as missing.
+fn decompose_format_value(
+    value: &vcf::record::genotypes::sample::Value,
+    number: vcf::header::Number,
+    allele_no: usize,
+    allele_count: usize,
+) -> Option<vcf::record::genotypes::sample::Value> {
+    use vcf::record::genotypes::sample::{value::Array, Value};
+
+    let Value::Array(array) = value else {
+        return Some(value.clone());
+    };
+    let len = match array {
+        Array::Integer(values) => values.len(),
+        Array::Float(values) => values.len(),
+        Array::Character(values) => values.len(),
+        Array::String(values) => values.len(),
+    };
+    let Some(indices) = allele_indices(number, allele_no, allele_count, len) else {
+        return Some(value.clone());
+    };
+    Some(Value::Array(match array {
+        Array::Integer(values) => Array::Integer(select_indices(values, &indices)?),
+        Array::Float(values) => Array::Float(select_indices(values, &indices)?),
+        Array::Character(values) => Array::Character(select_indices(values, &indices)?),
+        Array::String(values) => Array::String(select_indices(values, &indices)?),
+    }))
+}
+
 /// Copy the `FORMAT/GQ` fields for all samples.
 ///
 /// The implementation assumes that there are no duplicates in the output keys when mapped
@@ -247,6 +606,16 @@ fn copy_format(
         .iter()
         .copied()
         .map(|input_idx| {
+            if input_idx == usize::MAX {
+                // Write no-call for samples missing from the input.
+                return keys_from_input_known
+                    .iter()
+                    .map(|key| {
+                        (*key == vcf::record::genotypes::keys::key::GENOTYPE)
+                            .then(|| vcf::record::genotypes::sample::Value::String("./.".into()))
+                    })
+                    .collect::<Vec<_>>();
+            }
             let sample = input_record
                 .genotypes()
                 .get_index(input_idx)
@@ -261,6 +630,15 @@ fn copy_format(
                         value
                     } else if known_format_keys.output_keys.contains(key) {
                         input_value.cloned()
+                    } else if let Some(number) = known_format_keys.extra_keys.get(key) {
+                        input_value.and_then(|value| {
+                            decompose_format_value(
+                                value,
+                                *number,
+                                allele_no,
+                                input_record.alternate_bases().len() + 1,
+                            )
+                        })
                     } else {
                         unreachable!("don't know how to handle key: {:?}", key)
                     }
@@ -277,98 +655,387 @@ fn copy_format(
     Ok(builder.set_genotypes(genotypes))
 }
 
-/// Process the variants from `input_reader` to `output_writer`.
-async fn process_variants(
-    output_writer: &mut AsyncVcfWriter,
-    input_reader: &mut AsyncVcfReader,
-    output_header: &vcf::Header,
-    input_header: &vcf::Header,
-    args: &Args,
-) -> Result<(), anyhow::Error> {
-    // Open the frequency RocksDB database in read only mode.
-    tracing::info!("Opening frequency database");
-    let rocksdb_path = format!(
-        "{}/{}/seqvars/freqs/rocksdb",
-        &args.path_mehari_db,
-        path_component(args.genomebuild)
-    );
-    tracing::debug!("RocksDB path = {}", &rocksdb_path);
-    let options = rocksdb::Options::default();
-    let db_freq = rocksdb::DB::open_cf_for_read_only(
-        &options,
-        &rocksdb_path,
-        ["meta", "autosomal", "gonosomal", "mitochondrial"],
-        false,
-    )?;
-
-    let cf_autosomal = db_freq.cf_handle("autosomal").unwrap();
-    let cf_gonosomal = db_freq.cf_handle("gonosomal").unwrap();
-    let cf_mtdna = db_freq.cf_handle("mitochondrial").unwrap();
-
-    // Open the ClinVar RocksDB database in read only mode.
-    tracing::info!("Opening ClinVar database");
-    let rocksdb_path = format!(
-        "{}/{}/seqvars/clinvar/rocksdb",
-        &args.path_mehari_db,
-        path_component(args.genomebuild)
-    );
-    tracing::debug!("RocksDB path = {}", &rocksdb_path);
-    let options = rocksdb::Options::default();
-    let db_clinvar =
-        rocksdb::DB::open_cf_for_read_only(&options, &rocksdb_path, ["meta", "clinvar"], false)?;
-
-    let cf_clinvar = db_clinvar.cf_handle("clinvar").unwrap();
-
-    // Open the serialized transcripts.
-    tracing::info!("Opening transcript database");
-    let tx_db = mehari::annotate::seqvars::load_tx_db(&format!(
-        "{}/{}/txs.bin.zst",
-        &args.path_mehari_db,
-        path_component(args.genomebuild)
-    ))?;
-    tracing::info!("Building transcript interval trees ...");
-    let assembly = if args.genomebuild == GenomeRelease::Grch37 {
-        biocommons_bioutils::assemblies::Assembly::Grch37p10
-    } else {
-        biocommons_bioutils::assemblies::Assembly::Grch38
-    };
-    let provider = Arc::new(MehariProvider::new(tx_db, assembly, Default::default()));
-    let predictor = mehari::annotate::seqvars::csq::ConsequencePredictor::new(
-        provider,
-        assembly,
-        Default::default(),
-    );
-    tracing::info!("... done building transcript interval trees");
+/// Initial number of input records to pass between the tasks of the `async` I/O backend
+/// at once; adjusted by `batching::BatchSizer` while running.
+const ASYNC_BATCH_SIZE: usize = 1_000;
+
+/// Minimal number of input records per batch of the `async` I/O backend.
+const ASYNC_BATCH_SIZE_MIN: usize = 64;
+
+/// Maximal number of input records per batch of the `async` I/O backend.
+const ASYNC_BATCH_SIZE_MAX: usize = 20_000;
+
+/// Target duration of annotating one batch of the `async` I/O backend.
+const ASYNC_BATCH_TARGET: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Number of batches that may be in flight between the tasks of the `async` I/O backend.
+const ASYNC_CHANNEL_CAPACITY: usize = 16;
+
+/// The frequency and ClinVar databases used for annotating the output records.
+enum AnnotationDbs {
+    /// Local RocksDB databases.
+    Local {
+        /// Frequency RocksDB database, unless skipped.
+        db_freq: Option<rocksdb::DB>,
+        /// ClinVar RocksDB database, unless skipped.
+        db_clinvar: Option<rocksdb::DB>,
+        /// Options of the frequency database, for the statistics.
+        options_freq: rocksdb::Options,
+        /// Options of the ClinVar database, for the statistics.
+        options_clinvar: rocksdb::Options,
+    },
+    /// Remote annonars server.
+    Http {
+        /// The client of the server.
+        store: HttpStore,
+        /// Whether to skip the frequencies.
+        skip_freq: bool,
+        /// Whether to skip the ClinVar information.
+        skip_clinvar: bool,
+    },
+}
 
-    // Build mapping from output sample index to input sample index.
-    let idx_output_to_input = {
-        let output_sample_to_idx = output_header
-            .sample_names()
-            .iter()
-            .enumerate()
-            .map(|(idx, name)| (name, idx))
-            .collect::<std::collections::HashMap<_, _>>();
-        let mut res = vec![usize::MAX; output_header.sample_names().len()];
-        for (input_idx, sample) in input_header.sample_names().iter().enumerate() {
-            res[output_sample_to_idx[sample]] = input_idx;
-        }
-        res
-    };
+impl AnnotationDbs {
+    /// Open the local databases in `path_mehari_db`, except for the skipped ones.
+    fn open_local(
+        path_mehari_db: &str,
+        genomebuild: GenomeRelease,
+        skip_freq: bool,
+        skip_clinvar: bool,
+    ) -> Result<Self, anyhow::Error> {
+        // Open the frequency RocksDB database in read only mode.
+        let mut options_freq = rocksdb::Options::default();
+        options_freq.enable_statistics();
+        let db_freq = if skip_freq {
+            tracing::info!("Skipping frequency database");
+            None
+        } else {
+            tracing::info!("Opening frequency database");
+            let rocksdb_path = format!(
+                "{}/{}/seqvars/freqs/rocksdb",
+                path_mehari_db,
+                path_component(genomebuild)
+            );
+            tracing::debug!("RocksDB path = {}", &rocksdb_path);
+            Some(rocksdb::DB::open_cf_for_read_only(
+                &options_freq,
+                &rocksdb_path,
+                ["meta", "autosomal", "gonosomal", "mitochondrial"],
+                false,
+            )?)
+        };
 
-    // Read through input file, construct output records, and annotate these.
-    let start = std::time::Instant::now();
-    let mut prev = std::time::Instant::now();
-    let mut total_written = 0usize;
-    let mut records = input_reader.records(input_header);
-    let known_format_keys = KNOWN_FORMAT_KEYS.get_or_init(Default::default);
-    while let Some(input_record) = records
-        .try_next()
-        .await
-        .map_err(|e| anyhow::anyhow!("problem reading input VCF file: {}", e))?
-    {
+        // Open the ClinVar RocksDB database in read only mode.
+        let mut options_clinvar = rocksdb::Options::default();
+        options_clinvar.enable_statistics();
+        let db_clinvar = if skip_clinvar {
+            tracing::info!("Skipping ClinVar database");
+            None
+        } else {
+            tracing::info!("Opening ClinVar database");
+            let rocksdb_path = format!(
+                "{}/{}/seqvars/clinvar/rocksdb",
+                path_mehari_db,
+                path_component(genomebuild)
+            );
+            tracing::debug!("RocksDB path = {}", &rocksdb_path);
+            Some(rocksdb::DB::open_cf_for_read_only(
+                &options_clinvar,
+                &rocksdb_path,
+                ["meta", "clinvar"],
+                false,
+            )?)
+        };
+
+        Ok(AnnotationDbs::Local {
+            db_freq,
+            db_clinvar,
+            options_freq,
+            options_clinvar,
+        })
+    }
+
+    /// Log the RocksDB counters of the local databases; does nothing for the remote server.
+    fn log_perf_counters(&self) {
+        let AnnotationDbs::Local {
+            db_freq,
+            db_clinvar,
+            options_freq,
+            options_clinvar,
+        } = self
+        else {
+            return;
+        };
+        for (name, db, options) in [
+            ("frequencies", db_freq, options_freq),
+            ("ClinVar", db_clinvar, options_clinvar),
+        ] {
+            if db.is_none() {
+                continue;
+            }
+            if let Some(statistics) = options.get_statistics() {
+                RocksDbCounters::from_statistics(&statistics).log(name);
+            }
+        }
+    }
+}
+
+/// Time spent in the stages of `RecordAnnotator::process_record`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct StageTimes {
+    /// Splitting the input records into one record per allele.
+    pub split: std::time::Duration,
+    /// Frequency lookups.
+    pub freq: std::time::Duration,
+    /// ClinVar lookups.
+    pub clinvar: std::time::Duration,
+    /// Consequence prediction.
+    pub csq: std::time::Duration,
+}
+
+/// Bundle of the databases and predictor used for annotating the output records.
+pub(crate) struct RecordAnnotator {
+    /// Frequency and ClinVar databases.
+    dbs: AnnotationDbs,
+    /// Predictor for the variant consequences, unless skipped.
+    predictor: Option<csq_cache::CachedPredictor>,
+    /// In-house frequency database, if configured with `with_inhouse_db()`.
+    inhouse_db: Option<crate::seqvars::aggregate::reader::InhouseDb>,
+    /// Time spent in the stages, if enabled with `with_stage_times()`.
+    stage_times: Option<std::cell::RefCell<StageTimes>>,
+}
+
+impl RecordAnnotator {
+    /// Open the databases and build the transcript interval trees as configured in `args`.
+    fn with_args(args: &Args) -> Result<Self, anyhow::Error> {
+        let dbs = match args.annotation_backend {
+            AnnotationBackend::Local => AnnotationDbs::open_local(
+                &args.path_mehari_db,
+                args.genomebuild,
+                args.skip_freq,
+                args.skip_clinvar,
+            )?,
+            AnnotationBackend::Http => {
+                let url = match args.annotation_url.as_deref() {
+                    Some(url) => url,
+                    None if args.annotation_replay => "",
+                    None => anyhow::bail!("--annotation-url is required for the http backend"),
+                };
+                tracing::info!("Using annonars server at {}", url);
+                let store = HttpStore::new(
+                    url,
+                    args.genomebuild,
+                    args.annotation_batch_size,
+                    args.annotation_max_retries,
+                )?;
+                AnnotationDbs::Http {
+                    store: match args.annotation_recordings.as_deref() {
+                        Some(dir) => {
+                            tracing::info!("Using recorded annonars responses in {}", dir);
+                            store.with_recordings(Recordings::new(dir, args.annotation_replay))
+                        }
+                        None => store,
+                    },
+                    skip_freq: args.skip_freq,
+                    skip_clinvar: args.skip_clinvar,
+                }
+            }
+        };
+        let result = Self::with_dbs(
+            dbs,
+            &args.path_mehari_db,
+            args.genomebuild,
+            (!args.skip_csq).then_some(args.csq_cache_size),
+        )?
+        .with_tx_policy(args.tx_policy);
+        match args.path_inhouse_db.as_deref() {
+            Some(path) => {
+                tracing::info!("Opening in-house database {}", path);
+                Ok(
+                    result.with_inhouse_db(crate::seqvars::aggregate::reader::InhouseDb::open(
+                        path,
+                        &args.inhouse_cf_counts,
+                    )?),
+                )
+            }
+            None => Ok(result),
+        }
+    }
+
+    /// Open the databases in `path_mehari_db` and build the transcript interval trees,
+    /// caching up to `csq_cache_size` transcript bins.
+    pub(crate) fn new(
+        path_mehari_db: &str,
+        genomebuild: GenomeRelease,
+        csq_cache_size: usize,
+    ) -> Result<Self, anyhow::Error> {
+        let dbs = AnnotationDbs::open_local(path_mehari_db, genomebuild, false, false)?;
+        Self::with_dbs(dbs, path_mehari_db, genomebuild, Some(csq_cache_size))
+    }
+
+    /// Build the transcript interval trees from `path_mehari_db` and use `dbs` for the
+    /// frequency and ClinVar lookups.
+    ///
+    /// The consequence prediction is skipped if `csq_cache_size` is `None`.
+    fn with_dbs(
+        dbs: AnnotationDbs,
+        path_mehari_db: &str,
+        genomebuild: GenomeRelease,
+        csq_cache_size: Option<usize>,
+    ) -> Result<Self, anyhow::Error> {
+        let Some(csq_cache_size) = csq_cache_size else {
+            tracing::info!("Skipping transcript database");
+            return Ok(Self {
+                dbs,
+                predictor: None,
+                inhouse_db: None,
+                stage_times: None,
+            });
+        };
+
+        // Open the serialized transcripts.
+        tracing::info!("Opening transcript database");
+        let tx_db = mehari::annotate::seqvars::load_tx_db(&format!(
+            "{}/{}/txs.bin.zst",
+            path_mehari_db,
+            path_component(genomebuild)
+        ))?;
+        tracing::info!("Building transcript interval trees ...");
+        let assembly = if genomebuild == GenomeRelease::Grch37 {
+            biocommons_bioutils::assemblies::Assembly::Grch37p10
+        } else {
+            biocommons_bioutils::assemblies::Assembly::Grch38
+        };
+        let provider = Arc::new(MehariProvider::new(tx_db, assembly, Default::default()));
+        let predictor = csq_cache::CachedPredictor::new(
+            mehari::annotate::seqvars::csq::ConsequencePredictor::new(
+                provider.clone(),
+                assembly,
+                Default::default(),
+            ),
+            provider,
+            assembly,
+            csq_cache_size,
+        );
+        tracing::info!("... done building transcript interval trees");
+
+        Ok(Self {
+            dbs,
+            predictor: Some(predictor),
+            inhouse_db: None,
+            stage_times: None,
+        })
+    }
+
+    /// Only write the consequences of the transcripts selected by `tx_policy`.
+    fn with_tx_policy(self, tx_policy: tx_policy::TxPolicy) -> Self {
+        Self {
+            predictor: self
+                .predictor
+                .map(|predictor| predictor.with_tx_policy(tx_policy)),
+            ..self
+        }
+    }
+
+    /// Also annotate the in-house counts from `inhouse_db`.
+    pub(crate) fn with_inhouse_db(
+        self,
+        inhouse_db: crate::seqvars::aggregate::reader::InhouseDb,
+    ) -> Self {
+        Self {
+            inhouse_db: Some(inhouse_db),
+            ..self
+        }
+    }
+
+    /// Enable measuring the time spent in the stages, see `stage_times()`.
+    pub(crate) fn with_stage_times(self) -> Self {
+        Self {
+            stage_times: Some(Default::default()),
+            ..self
+        }
+    }
+
+    /// Return the time spent in the stages if enabled with `with_stage_times()`.
+    pub(crate) fn stage_times(&self) -> Option<StageTimes> {
+        self.stage_times.as_ref().map(|times| *times.borrow())
+    }
+
+    /// Return the start time of a stage if measuring is enabled.
+    fn start_stage(&self) -> Option<std::time::Instant> {
+        self.stage_times.is_some().then(std::time::Instant::now)
+    }
+
+    /// Add the time since `start` to the stage times with `add`.
+    fn end_stage(
+        &self,
+        start: Option<std::time::Instant>,
+        add: impl FnOnce(&mut StageTimes, std::time::Duration),
+    ) {
+        if let (Some(start), Some(times)) = (start, self.stage_times.as_ref()) {
+            add(&mut times.borrow_mut(), start.elapsed());
+        }
+    }
+
+    /// Log the hit statistics of the consequence prediction cache.
+    fn log_csq_cache_stats(&self) {
+        let Some(predictor) = self.predictor.as_ref() else {
+            return;
+        };
+        let csq_cache::Stats {
+            hits,
+            misses,
+            skipped,
+        } = predictor.stats();
+        tracing::info!(
+            "consequence cache: {} hits, {} misses, {} predictions skipped",
+            hits.separate_with_commas(),
+            misses.separate_with_commas(),
+            skipped.separate_with_commas()
+        );
+    }
+
+    /// Log the performance counters of the local RocksDB databases.
+    pub(crate) fn log_perf_counters(&self) {
+        self.dbs.log_perf_counters();
+    }
+
+    /// Split `input_record` into one annotated output record per alternate allele.
+    ///
+    /// Alleles that cannot be annotated, e.g., spanning deletions (`*`), are skipped and
+    /// counted in `report`; unexpected ones fail in `strict` mode.  The time of the last
+    /// progress message is kept in `prev`.
+    #[allow(clippy::too_many_arguments)]
+    fn process_record(
+        &self,
+        lookups: &mut prefetch::Lookups<'_>,
+        input_record: &vcf::Record,
+        idx_output_to_input: &[usize],
+        known_format_keys: &KnownFormatKeys,
+        kept_info_keys: &KeptInfoKeys,
+        report: &mut alleles::Report,
+        strict: bool,
+        prev: &mut std::time::Instant,
+    ) -> Result<Vec<vcf::Record>, anyhow::Error> {
+        let mut result = Vec::new();
+
+        // Skip the reference blocks of gVCF files as a whole; the `<NON_REF>` alleles of
+        // the variant records are skipped below.
+        if alleles::is_reference_block(input_record) {
+            report.register_reference_block(input_record);
+            return Ok(result);
+        }
+
+        report.register_record(input_record);
         for (allele_no, alt_allele) in input_record.alternate_bases().iter().enumerate() {
             let allele_no = allele_no + 1;
+            if let Some(reason) = alleles::SkipReason::of(alt_allele) {
+                report.register_skipped(input_record, alt_allele, reason, strict)?;
+                continue;
+            }
+
             // Construct record with first few fields describing one variant allele.
+            let start = self.start_stage();
             let builder = vcf::Record::builder()
                 .set_chromosome(input_record.chromosome().clone())
                 .set_position(input_record.position())
@@ -377,119 +1044,525 @@ async fn process_variants(
 
             // Copy over the well-known FORMAT fields and construct output record.
             let builder = copy_format(
-                &input_record,
+                input_record,
                 builder,
-                &idx_output_to_input,
+                idx_output_to_input,
                 allele_no,
                 known_format_keys,
             )?;
 
             let mut output_record = builder.build()?;
+            kept_info_keys.copy_info(input_record, &mut output_record, allele_no);
+            self.end_stage(start, |times, elapsed| times.split += elapsed);
 
             // Obtain annonars variant key from current allele for RocksDB lookup.
             let vcf_var = annonars::common::keys::Var::from_vcf_allele(&output_record, 0);
 
-            // Skip records with a deletion as alternative allele.
-            if vcf_var.alternative == "*" {
-                continue;
-            }
-
             if prev.elapsed().as_secs() >= 60 {
                 tracing::info!("at {:?}", &vcf_var);
-                prev = std::time::Instant::now();
+                *prev = std::time::Instant::now();
             }
 
-            // Only attempt lookups into RocksDB for canonical contigs.
-            if annonars::common::cli::is_canonical(vcf_var.chrom.as_str()) {
-                // Build key for RocksDB database from `vcf_var`.
-                let key: Vec<u8> = vcf_var.clone().into();
-
-                // Annotate with frequency.
-                if mehari::annotate::seqvars::CHROM_AUTO.contains(vcf_var.chrom.as_str()) {
-                    mehari::annotate::seqvars::annotate_record_auto(
-                        &db_freq,
-                        &cf_autosomal,
-                        &key,
-                        &mut output_record,
-                    )?;
-                } else if mehari::annotate::seqvars::CHROM_XY.contains(vcf_var.chrom.as_str()) {
-                    mehari::annotate::seqvars::annotate_record_xy(
-                        &db_freq,
-                        &cf_gonosomal,
-                        &key,
-                        &mut output_record,
-                    )?;
-                } else if mehari::annotate::seqvars::CHROM_MT.contains(vcf_var.chrom.as_str()) {
-                    mehari::annotate::seqvars::annotate_record_mt(
-                        &db_freq,
-                        &cf_mtdna,
-                        &key,
-                        &mut output_record,
-                    )?;
-                } else {
-                    tracing::trace!(
-                        "Record @{:?} on non-canonical chromosome, skipping.",
-                        &vcf_var
-                    );
+            self.annotate(lookups, vcf_var, &mut output_record)?;
+            if known_format_keys.refine_genotypes {
+                refine::refine_genotypes(
+                    input_record,
+                    allele_no,
+                    idx_output_to_input,
+                    &mut output_record,
+                );
+            }
+            result.push(output_record);
+            report.count_written += 1;
+        }
+
+        Ok(result)
+    }
+
+    /// Return the transcript provider of the consequence predictor, unless skipped.
+    pub(crate) fn provider(&self) -> Option<&Arc<MehariProvider>> {
+        self.predictor
+            .as_ref()
+            .map(|predictor| predictor.provider())
+    }
+
+    /// Return the lookups into the databases, see [`prefetch::Lookups`].
+    pub(crate) fn lookups(&self, prefetch: bool) -> prefetch::Lookups<'_> {
+        match &self.dbs {
+            AnnotationDbs::Local {
+                db_freq,
+                db_clinvar,
+                ..
+            } => prefetch::Lookups::new(db_freq.as_ref(), db_clinvar.as_ref(), prefetch),
+            AnnotationDbs::Http {
+                store,
+                skip_freq,
+                skip_clinvar,
+            } => prefetch::Lookups::with_stores(
+                (!skip_freq).then_some(store as &dyn VariantKvStore),
+                (!skip_clinvar).then_some(store as &dyn VariantKvStore),
+            ),
+        }
+    }
+
+    /// Fetch the values for the alleles of `input_records` from the annonars server in one
+    /// batch, or check their presence in the local databases with the sorted cursors of
+    /// `lookups`, if any; does nothing if frequencies and ClinVar are skipped.
+    fn prefetch(
+        &self,
+        lookups: &mut prefetch::Lookups<'_>,
+        input_records: &[vcf::Record],
+    ) -> Result<(), anyhow::Error> {
+        let mut vars = Vec::new();
+        for input_record in input_records {
+            for (allele_no, alt_allele) in input_record.alternate_bases().iter().enumerate() {
+                if alleles::SkipReason::of(alt_allele).is_some() {
+                    continue;
+                }
+                let var = annonars::common::keys::Var::from_vcf_allele(input_record, allele_no);
+                if annonars::common::cli::is_canonical(var.chrom.as_str()) {
+                    vars.push(var);
                 }
+            }
+        }
+        match &self.dbs {
+            AnnotationDbs::Http {
+                store,
+                skip_freq,
+                skip_clinvar,
+            } if !(*skip_freq && *skip_clinvar) => {
+                let keys = vars.into_iter().map(Into::into).collect::<Vec<_>>();
+                store.prefetch(&keys)
+            }
+            AnnotationDbs::Http { .. } => Ok(()),
+            AnnotationDbs::Local { .. } => lookups.prefetch(&vars),
+        }
+    }
 
-                // Annotate with ClinVar information.
-                mehari::annotate::seqvars::annotate_record_clinvar(
-                    &db_clinvar,
-                    &cf_clinvar,
-                    &key,
-                    &mut output_record,
-                )?;
+    /// Annotate `output_record` with frequencies, ClinVar, and variant effects.
+    pub(crate) fn annotate(
+        &self,
+        lookups: &mut prefetch::Lookups<'_>,
+        vcf_var: annonars::common::keys::Var,
+        output_record: &mut vcf::Record,
+    ) -> Result<(), anyhow::Error> {
+        // Only attempt lookups into RocksDB for canonical contigs.
+        if annonars::common::cli::is_canonical(vcf_var.chrom.as_str()) {
+            // Build key for RocksDB database from `vcf_var`.
+            let key: Vec<u8> = vcf_var.clone().into();
+
+            // Annotate with frequency.
+            let start = self.start_stage();
+            if let Some(table) = prefetch::Table::frequencies_for(vcf_var.chrom.as_str()) {
+                lookups.get_mut(table).annotate(&key, output_record)?;
+            } else {
+                tracing::trace!(
+                    "Record @{:?} on non-canonical chromosome, skipping.",
+                    &vcf_var
+                );
+            }
+
+            if let Some(inhouse_db) = self.inhouse_db.as_ref() {
+                if let Some(counts) = inhouse_db.counts_by_key(&key)? {
+                    inhouse::annotate_counts(&counts, output_record);
+                }
             }
 
-            let annonars::common::keys::Var {
-                chrom,
-                pos,
+            self.end_stage(start, |times, elapsed| times.freq += elapsed);
+
+            // Annotate with ClinVar information.
+            let start = self.start_stage();
+            lookups.clinvar.annotate(&key, output_record)?;
+            self.end_stage(start, |times, elapsed| times.clinvar += elapsed);
+        }
+
+        let annonars::common::keys::Var {
+            chrom,
+            pos,
+            reference,
+            alternative,
+        } = vcf_var;
+
+        // Annotate with variant effect.
+        let start = self.start_stage();
+        let Some(predictor) = self.predictor.as_ref() else {
+            return Ok(());
+        };
+        if let Some(ann_fields) =
+            predictor.predict(&mehari::annotate::seqvars::csq::VcfVariant {
+                chromosome: chrom,
+                position: pos,
                 reference,
                 alternative,
-            } = vcf_var;
-
-            // Annotate with variant effect.
-            if let Some(ann_fields) =
-                predictor.predict(&mehari::annotate::seqvars::csq::VcfVariant {
-                    chromosome: chrom,
-                    position: pos,
-                    reference,
-                    alternative,
-                })?
-            {
-                if !ann_fields.is_empty() {
-                    output_record.info_mut().insert(
-                        "ANN".parse()?,
-                        Some(vcf::record::info::field::Value::Array(
-                            vcf::record::info::field::value::Array::String(
-                                ann_fields.iter().map(|ann| Some(ann.to_string())).collect(),
-                            ),
-                        )),
-                    );
-                }
+            })?
+        {
+            if !ann_fields.is_empty() {
+                output_record.info_mut().insert(
+                    "ANN".parse()?,
+                    Some(vcf::record::info::field::Value::Array(
+                        vcf::record::info::field::value::Array::String(
+                            ann_fields.iter().map(|ann| Some(ann.to_string())).collect(),
+                        ),
+                    )),
+                );
             }
+        }
+        self.end_stage(start, |times, elapsed| times.csq += elapsed);
+
+        Ok(())
+    }
+}
+
+/// Build mapping from output sample index to input sample index.
+///
+/// Output samples missing from the input are mapped to `usize::MAX`.
+fn build_idx_output_to_input(
+    output_header: &vcf::Header,
+    input_header: &vcf::Header,
+) -> Vec<usize> {
+    let output_sample_to_idx = output_header
+        .sample_names()
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| (name, idx))
+        .collect::<std::collections::HashMap<_, _>>();
+    let mut res = vec![usize::MAX; output_header.sample_names().len()];
+    for (input_idx, sample) in input_header.sample_names().iter().enumerate() {
+        if let Some(output_idx) = output_sample_to_idx.get(sample) {
+            res[*output_idx] = input_idx;
+        }
+    }
+    res
+}
+
+/// Process the variants from `input_reader` to `output_writer`.
+///
+/// Returns the report of the processed and skipped alleles.
+async fn process_variants(
+    output_writer: &mut AsyncVcfWriter,
+    input_records: RecordStream<'_>,
+    output_header: &vcf::Header,
+    input_header: &vcf::Header,
+    args: &Args,
+    shutdown: &common::shutdown::ShutdownSignal,
+) -> Result<(alleles::Report, stats::Stats), anyhow::Error> {
+    let idx_output_to_input = build_idx_output_to_input(output_header, input_header);
+    let mut known_format_keys =
+        KnownFormatKeys::with_extra_keys(&args.extra_format_keys, input_header)?;
+    known_format_keys.refine_genotypes = args.refine_genotypes;
+    let kept_info_keys = KeptInfoKeys::new(&args.keep_info_keys, input_header)?;
+
+    // Read through input file, construct output records, and annotate these.
+    let start = std::time::Instant::now();
+    let (report, stats) = match args.io_backend {
+        IoBackend::Sequential => {
+            let annotator = RecordAnnotator::with_args(args)?;
+            process_variants_sequential(
+                output_writer,
+                input_records,
+                &annotator,
+                &idx_output_to_input,
+                &known_format_keys,
+                &kept_info_keys,
+                args.max_var_count,
+                args.rocksdb_prefetch,
+                args.strict,
+                shutdown,
+            )
+            .await?
+        }
+        IoBackend::Async => {
+            process_variants_async(
+                output_writer,
+                input_records,
+                args,
+                idx_output_to_input,
+                known_format_keys,
+                kept_info_keys,
+                shutdown.clone(),
+            )
+            .await?
+        }
+    };
+    tracing::info!(
+        "... annotated {} records in {:?}",
+        report.count_written.separate_with_commas(),
+        start.elapsed()
+    );
+    report.log_summary();
+
+    Ok((report, stats))
+}
 
+/// Read, annotate, and write the records one after another.
+///
+/// Stops reading once `shutdown` has been requested.
+///
+/// Returns the report of the processed and skipped alleles and the statistics of the
+/// written records.
+#[allow(clippy::too_many_arguments)]
+async fn process_variants_sequential(
+    output_writer: &mut AsyncVcfWriter,
+    mut input_records: RecordStream<'_>,
+    annotator: &RecordAnnotator,
+    idx_output_to_input: &[usize],
+    known_format_keys: &KnownFormatKeys,
+    kept_info_keys: &KeptInfoKeys,
+    max_var_count: Option<usize>,
+    rocksdb_prefetch: bool,
+    strict: bool,
+    shutdown: &common::shutdown::ShutdownSignal,
+) -> Result<(alleles::Report, stats::Stats), anyhow::Error> {
+    let mut lookups = annotator.lookups(rocksdb_prefetch);
+    let mut prev = std::time::Instant::now();
+    let mut report = alleles::Report::default();
+    let mut stats = stats::Stats::default();
+    while let Some(input_record) = input_records.try_next().await? {
+        if shutdown.is_requested() {
+            break;
+        }
+        for output_record in annotator.process_record(
+            &mut lookups,
+            &input_record,
+            idx_output_to_input,
+            known_format_keys,
+            kept_info_keys,
+            &mut report,
+            strict,
+            &mut prev,
+        )? {
             // Write out the record.
             output_writer.write_record(&output_record).await?;
-            total_written += 1;
+            stats.register(&output_record)?;
         }
-        if let Some(max_var_count) = args.max_var_count {
-            if total_written >= max_var_count {
-                tracing::warn!(
-                    "Stopping after {} records as requested by --max-var-count",
-                    total_written
-                );
+        if max_var_count_reached(max_var_count, report.count_written) {
+            break;
+        }
+    }
+    annotator.log_csq_cache_stats();
+    annotator.log_perf_counters();
+
+    Ok((report, stats))
+}
+
+/// Overlap reading, annotation, and writing of records.
+///
+/// Reading (including decompression) and writing (including bgzf compression) run as
+/// concurrent futures on the current task while the annotation runs on a blocking thread.
+/// The stages are connected with bounded channels that carry batches of records.  The
+/// batch size follows the observed annotation cost per record so batches take about the
+/// same time to annotate in dense and in sparse regions.  Once `shutdown` has been
+/// requested, reading stops and the records already read are annotated and written.
+///
+/// Returns the report of the processed and skipped alleles and the statistics of the
+/// written records.
+async fn process_variants_async(
+    output_writer: &mut AsyncVcfWriter,
+    mut input_records: RecordStream<'_>,
+    args: &Args,
+    idx_output_to_input: Vec<usize>,
+    known_format_keys: KnownFormatKeys,
+    kept_info_keys: KeptInfoKeys,
+    shutdown: common::shutdown::ShutdownSignal,
+) -> Result<(alleles::Report, stats::Stats), anyhow::Error> {
+    let (input_tx, mut input_rx) =
+        tokio::sync::mpsc::channel::<Vec<vcf::Record>>(ASYNC_CHANNEL_CAPACITY);
+    let (output_tx, mut output_rx) =
+        tokio::sync::mpsc::channel::<Vec<vcf::Record>>(ASYNC_CHANNEL_CAPACITY);
+
+    let mut batch_sizer = batching::BatchSizer::new(
+        ASYNC_BATCH_TARGET,
+        ASYNC_BATCH_SIZE,
+        ASYNC_BATCH_SIZE_MIN,
+        ASYNC_BATCH_SIZE_MAX,
+    );
+    let batch_size = batch_sizer.shared_size();
+
+    // The annotation runs on a blocking thread; it stops when the input channel is closed,
+    // when `max_var_count` has been reached, or when the writer has gone away.
+    let annotation = {
+        let args = args.clone();
+        tokio::task::spawn_blocking(move || -> Result<alleles::Report, anyhow::Error> {
+            let annotator = RecordAnnotator::with_args(&args)?;
+            let mut lookups = annotator.lookups(args.rocksdb_prefetch);
+            let mut prev = std::time::Instant::now();
+            let mut report = alleles::Report::default();
+            while let Some(input_records) = input_rx.blocking_recv() {
+                let batch_start = std::time::Instant::now();
+                annotator.prefetch(&mut lookups, &input_records)?;
+                let mut output_records = Vec::with_capacity(input_records.len());
+                let mut done = false;
+                for input_record in &input_records {
+                    let records = annotator.process_record(
+                        &mut lookups,
+                        input_record,
+                        &idx_output_to_input,
+                        &known_format_keys,
+                        &kept_info_keys,
+                        &mut report,
+                        args.strict,
+                        &mut prev,
+                    )?;
+                    output_records.extend(records);
+                    if max_var_count_reached(args.max_var_count, report.count_written) {
+                        done = true;
+                        break;
+                    }
+                }
+                batch_sizer.observe(input_records.len(), batch_start.elapsed());
+                if output_tx.blocking_send(output_records).is_err() || done {
+                    break;
+                }
+            }
+            annotator.log_csq_cache_stats();
+            annotator.log_perf_counters();
+            Ok(report)
+        })
+    };
+
+    let read = async move {
+        let mut batch = Vec::with_capacity(ASYNC_BATCH_SIZE);
+        while let Some(input_record) = input_records.try_next().await? {
+            if shutdown.is_requested() {
                 break;
             }
+            batch.push(input_record);
+            let size = batch_size.load(std::sync::atomic::Ordering::Relaxed);
+            if batch.len() >= size {
+                let batch = std::mem::replace(&mut batch, Vec::with_capacity(size));
+                if input_tx.send(batch).await.is_err() {
+                    // The annotation has stopped early, no need to read further.
+                    return Ok(());
+                }
+            }
+        }
+        if !batch.is_empty() {
+            // Ignore errors, the annotation may have stopped early.
+            let _ = input_tx.send(batch).await;
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+
+    let write = async {
+        let mut stats = stats::Stats::default();
+        while let Some(output_records) = output_rx.recv().await {
+            for output_record in &output_records {
+                output_writer.write_record(output_record).await?;
+                stats.register(output_record)?;
+            }
         }
+        Ok::<_, anyhow::Error>(stats)
+    };
+
+    let (res_read, res_write) = tokio::join!(read, write);
+    let report = annotation
+        .await
+        .map_err(|e| anyhow::anyhow!("annotation task failed: {}", e))??;
+    res_read?;
+    let stats = res_write?;
+    Ok((report, stats))
+}
+
+/// Return whether `max_var_count` is set and has been reached by `total_written`.
+fn max_var_count_reached(max_var_count: Option<usize>, total_written: usize) -> bool {
+    if let Some(max_var_count) = max_var_count {
+        if total_written >= max_var_count {
+            tracing::warn!(
+                "{}: stopping after {} records as requested by --max-var-count",
+                crate::codes::INGEST_MAX_VAR_COUNT_REACHED,
+                total_written
+            );
+            return true;
+        }
+    }
+    false
+}
+
+/// Open the stream of input records, restricted to `regions` if given.
+///
+/// The records of multiple input files are merged on the fly into records with the
+/// samples of the merged `input_header`.
+async fn open_input_records<'a>(
+    args: &Args,
+    regions: Option<regions::Regions>,
+    input_readers: &'a mut [AsyncVcfReader],
+    input_headers: &'a [vcf::Header],
+    input_header: &'a vcf::Header,
+) -> Result<RecordStream<'a>, anyhow::Error> {
+    let mut streams = Vec::new();
+    for ((path_in, input_reader), header) in args
+        .path_in
+        .iter()
+        .zip(input_readers.iter_mut())
+        .zip(input_headers.iter())
+    {
+        streams.push(match (regions.clone(), regions::find_index(path_in)) {
+            (Some(regions), Some(path_index)) => {
+                regions::query_records(path_in, &path_index, header.clone(), regions).await?
+            }
+            (regions, _) => {
+                let records: RecordStream = Box::pin(
+                    input_reader
+                        .records(header)
+                        .map_err(|e| anyhow::anyhow!("problem reading input VCF file: {}", e)),
+                );
+                if let Some(regions) = regions {
+                    regions::filter_records(records, regions)
+                } else {
+                    records
+                }
+            }
+        });
     }
+    let records = if streams.len() == 1 {
+        streams.pop().expect("checked above")
+    } else {
+        merge::merge_records(streams, input_headers, input_header)
+    };
+    Ok(if args.merge_phased_snvs {
+        mnv::merge_phased_snvs(records)
+    } else {
+        records
+    })
+}
+
+/// Compute the approximate QC metrics for `--quick-qc` and write them out.
+async fn run_quick_qc(
+    args: &Args,
+    regions: Option<regions::Regions>,
+    input_readers: &mut [AsyncVcfReader],
+    input_headers: &[vcf::Header],
+    input_header: &vcf::Header,
+) -> Result<(), anyhow::Error> {
+    let path_quick_qc = args
+        .path_quick_qc
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("--quick-qc requires --path-quick-qc"))?;
+    let sampling = if let Some(n) = args.quick_qc_first_per_contig {
+        quick_qc::Sampling::FirstPerContig(n)
+    } else {
+        quick_qc::Sampling::Every(args.quick_qc_every)
+    };
+
+    tracing::info!("computing quick QC metrics with {:?}...", sampling);
+    let input_records =
+        open_input_records(args, regions, input_readers, input_headers, input_header).await?;
+    let metrics = quick_qc::compute(
+        input_records,
+        input_header,
+        sampling,
+        std::time::Duration::from_secs(args.quick_qc_max_seconds),
+    )
+    .await?;
     tracing::info!(
-        "... annotated {} records in {:?}",
-        total_written.separate_with_commas(),
-        start.elapsed()
+        "... done sampling {} of {} records",
+        metrics.count_sampled.separate_with_commas(),
+        metrics.count_read.separate_with_commas()
     );
 
+    serde_json::to_writer_pretty(std::fs::File::create(path_quick_qc)?, &metrics)
+        .map_err(|e| anyhow::anyhow!("could not write quick QC metrics: {}", e))?;
+
     Ok(())
 }
 
@@ -501,60 +1574,240 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
 
     common::trace_rss_now();
 
-    tracing::info!("loading pedigree...");
-    let pedigree = mehari::ped::PedigreeByName::from_path(&args.path_ped)
-        .map_err(|e| anyhow::anyhow!("problem parsing PED file: {}", e))?;
-    tracing::info!("pedigre = {:#?}", &pedigree);
+    let phenopacket = args
+        .path_phenopacket
+        .as_deref()
+        .map(common::phenopacket::CaseMetadata::from_path)
+        .transpose()?;
+    let pedigree = if args.annotate_only {
+        tracing::info!("annotating only, without pedigree");
+        None
+    } else if let Some(phenopacket) = phenopacket.as_ref() {
+        tracing::info!("pedigree from phenopacket = {:#?}", &phenopacket.pedigree);
+        Some(phenopacket.pedigree.clone())
+    } else {
+        tracing::info!("loading pedigree...");
+        let path_ped = args
+            .path_ped
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("--path-ped is required unless --annotate-only"))?;
+        let pedigree = mehari::ped::PedigreeByName::from_path(path_ped)
+            .map_err(|e| anyhow::anyhow!("problem parsing PED file: {}", e))?;
+        tracing::info!("pedigre = {:#?}", &pedigree);
+        Some(pedigree)
+    };
+    let case_uuid = if args.annotate_only {
+        None
+    } else {
+        Some(
+            args.case_uuid
+                .or_else(|| {
+                    phenopacket
+                        .as_ref()
+                        .and_then(|phenopacket| phenopacket.case_uuid)
+                })
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "--case-uuid is required unless --annotate-only or given as the \
+                        identifier of --path-phenopacket"
+                    )
+                })?,
+        )
+    };
+    // The HPO terms of the phenopacket are passed on as `SAMPLE=HP:NNNNNNN` values.
+    let hpo_terms = if let Some(phenopacket) = phenopacket.as_ref() {
+        phenopacket
+            .hpo_terms
+            .iter()
+            .flat_map(|(sample, terms)| {
+                terms.iter().map(move |term| format!("{}={}", sample, term))
+            })
+            .collect()
+    } else {
+        args.hpo_terms.clone()
+    };
 
-    tracing::info!("opening input file...");
-    let mut input_reader = open_vcf_reader(&args.path_in)
-        .await
-        .map_err(|e| anyhow::anyhow!("could not build VCF reader: {}", e))?;
+    let regions = regions::Regions::from_args(&args.regions, args.path_regions_bed.as_deref())?;
+
+    tracing::info!("opening input files...");
+    let mut input_readers = Vec::new();
+    let mut input_headers = Vec::new();
+    for path_in in &args.path_in {
+        let mut input_reader = open_vcf_reader(path_in)
+            .await
+            .map_err(|e| anyhow::anyhow!("could not build VCF reader for {}: {}", path_in, e))?;
+        let mut input_header = input_reader
+            .read_header()
+            .await
+            .map_err(|e| anyhow::anyhow!("problem reading VCF header of {}: {}", path_in, e))?;
+        // Work around glnexus issue with RNC.
+        if let Some(format) = input_header.formats_mut().get_mut("RNC") {
+            *format.number_mut() = vcf::header::Number::Count(1);
+            *format.type_mut() = vcf::header::record::value::map::format::Type::String;
+        }
+        input_readers.push(input_reader);
+        input_headers.push(input_header);
+    }
 
     tracing::info!("processing header...");
-    let mut input_header = input_reader
-        .read_header()
-        .await
-        .map_err(|e| anyhow::anyhow!("problem reading VCF header: {}", e))?;
-    let output_header = header::build_output_header(
+    let input_header = merge::merge_headers(&input_headers)?;
+    let pedigree = pedigree
+        .map(|pedigree| {
+            header::reconcile_pedigree(pedigree, &input_header, args.sample_mismatch_policy)
+        })
+        .transpose()?;
+    let family = match (case_uuid, pedigree.as_ref()) {
+        (Some(case_uuid), Some(pedigree)) => Some(family::Family::new(
+            case_uuid,
+            pedigree,
+            &hpo_terms,
+            &args.path_in,
+            &input_headers,
+        )?),
+        _ => None,
+    };
+    let mut output_header = header::build_output_header(
         &input_header,
-        &Some(pedigree),
+        &pedigree,
         args.genomebuild,
         &args.file_date,
-        &args.case_uuid,
+        case_uuid.as_ref(),
         worker_version(),
     )
     .map_err(|e| anyhow::anyhow!("problem building output header: {}", e))?;
+    header::add_extra_formats(&mut output_header, &input_header, &args.extra_format_keys)?;
+    if args.path_inhouse_db.is_some() {
+        inhouse::add_inhouse_infos(&mut output_header)?;
+    }
+    header::add_kept_infos(&mut output_header, &input_header, &args.keep_info_keys)?;
+    if args.refine_genotypes {
+        refine::add_posterior_format(&mut output_header);
+    }
 
-    // Work around glnexus issue with RNC.
-    if let Some(format) = input_header.formats_mut().get_mut("RNC") {
-        *format.number_mut() = vcf::header::Number::Count(1);
-        *format.type_mut() = vcf::header::record::value::map::format::Type::String;
+    if args.quick_qc {
+        return run_quick_qc(
+            args,
+            regions,
+            &mut input_readers,
+            &input_headers,
+            &input_header,
+        )
+        .await;
     }
 
     // Use output file helper.
     let out_path_helper = crate::common::s3::OutputPathHelper::new(&args.path_out)?;
+    let path_partial = common::partial::partial_path(out_path_helper.path_out());
+    let partial_guard = common::partial::PartialOutputGuard::new(
+        out_path_helper.path_out(),
+        common::partial::OnFailure::Rename,
+    );
+    let shutdown = common::shutdown::ShutdownSignal::install();
 
     {
-        let mut output_writer = open_vcf_writer(out_path_helper.path_out()).await?;
+        let (mut output_writer, write_counters) = open_vcf_writer_with_counters(
+            out_path_helper.path_out(),
+            args_common.compression_level,
+        )
+        .await?;
         output_writer
             .write_header(&output_header)
             .await
             .map_err(|e| anyhow::anyhow!("problem writing header: {}", e))?;
 
-        process_variants(
+        let mut stats = stats::Stats::default();
+        let (count_copied, resume_point) =
+            if args.resume_from_partial && std::path::Path::new(&path_partial).exists() {
+                tracing::info!("copying records from {}...", &path_partial);
+                let (count_copied, resume_point) = resume::copy_partial(
+                    &path_partial,
+                    common::io::tokio::OutputCompression::from_path(&args.path_out),
+                    &output_header,
+                    &mut output_writer,
+                    &mut stats,
+                )
+                .await?;
+                tracing::info!(
+                    "... copied {} records, resuming at {:?}",
+                    count_copied.separate_with_commas(),
+                    &resume_point
+                );
+                (count_copied, resume_point)
+            } else {
+                if args.resume_from_partial {
+                    tracing::warn!(
+                        "{}: no partial output {} to resume from",
+                        crate::codes::INGEST_NO_PARTIAL_OUTPUT,
+                        &path_partial
+                    );
+                }
+                (0, None)
+            };
+
+        let input_records = open_input_records(
+            args,
+            regions,
+            &mut input_readers,
+            &input_headers,
+            &input_header,
+        )
+        .await?;
+        let input_records = if let Some(resume_point) = resume_point {
+            resume::skip_to(input_records, resume_point)
+        } else {
+            input_records
+        };
+
+        let args = Args {
+            max_var_count: args
+                .max_var_count
+                .map(|max_var_count| max_var_count.saturating_sub(count_copied)),
+            ..args.clone()
+        };
+        let (report, stats_processed) = process_variants(
             &mut output_writer,
-            &mut input_reader,
+            input_records,
             &output_header,
             &input_header,
-            args,
+            &args,
+            &shutdown,
         )
         .await?;
+        stats.merge(stats_processed);
+        if let Some(path_allele_report) = args.path_allele_report.as_ref() {
+            serde_json::to_writer_pretty(std::fs::File::create(path_allele_report)?, &report)
+                .map_err(|e| anyhow::anyhow!("could not write allele report: {}", e))?;
+        }
 
         flush_and_shutdown!(output_writer);
+        write_counters.log(out_path_helper.path_out());
+        if shutdown.is_requested() {
+            // The output ends with complete bgzf blocks and is kept by `partial_guard` for
+            // `--resume-from-partial`.
+            return Err(common::shutdown::Interrupted.into());
+        }
+
+        tracing::info!("writing record statistics...");
+        stats
+            .write_to_header(out_path_helper.path_out(), args_common.compression_level)
+            .await?;
+        stats.write_sidecar(out_path_helper.path_out())?;
+        if let Some(family) = family.as_ref() {
+            family.write_sidecar(out_path_helper.path_out())?;
+        }
     }
 
-    out_path_helper.create_tbi_for_bgzf().await?;
+    if args.validate_output {
+        common::validate::validate_vcf(out_path_helper.path_out()).await?;
+    }
+    out_path_helper
+        .create_index_for_bgzf(args.write_index.resolve(&output_header))
+        .await?;
+    partial_guard.finish();
+    if std::path::Path::new(&path_partial).exists() {
+        std::fs::remove_file(&path_partial)
+            .map_err(|e| anyhow::anyhow!("could not remove {}: {}", &path_partial, e))?;
+    }
     out_path_helper.upload_for_s3().await?;
 
     tracing::info!(
@@ -567,9 +1820,123 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
 #[cfg(test)]
 mod test {
 
+    use noodles_vcf as vcf;
     use rstest::rstest;
 
     use crate::common::GenomeRelease;
+    use crate::seqvars::query::{
+        interpreter::frequency,
+        schema::{CaseQuery, SequenceVariant},
+    };
+
+    #[rstest]
+    #[case("0/1", 1, "0/1")]
+    #[case("1|2", 1, "1|0")]
+    #[case("1|2", 2, "0|1")]
+    #[case("2/2", 2, "1/1")]
+    #[case("1", 1, "1")]
+    #[case("0", 1, "0")]
+    #[case("2", 1, "0")]
+    #[case("0/1/2", 2, "0/0/1")]
+    #[case("10|1", 10, "1|0")]
+    #[case("./.", 1, "./.")]
+    #[case(".", 1, ".")]
+    #[case(".|2", 1, ".|0")]
+    #[case(".|2", 2, ".|1")]
+    #[case("1/.", 1, "1/.")]
+    fn recode_gt(#[case] gt: &str, #[case] allele_no: usize, #[case] expected: &str) {
+        assert_eq!(super::recode_gt(gt, allele_no), expected);
+    }
+
+    #[rstest]
+    #[case(1, "GT:PS\t1|0:100\t0|0:100\t.|1:200")]
+    #[case(2, "GT:PS\t0|1:100\t1|0:100\t.|0:200")]
+    fn copy_format_phased(
+        #[case] allele_no: usize,
+        #[case] expected: &str,
+    ) -> Result<(), anyhow::Error> {
+        let input_record: vcf::Record =
+            "1\t100\t.\tA\tC,G\t.\t.\t.\tGT:PS\t1|2:100\t2|0:100\t.|1:200".parse()?;
+        let builder = vcf::Record::builder()
+            .set_chromosome(input_record.chromosome().clone())
+            .set_position(input_record.position())
+            .set_reference_bases(input_record.reference_bases().clone());
+
+        let output_record = super::copy_format(
+            &input_record,
+            builder,
+            &[0, 1, 2],
+            allele_no,
+            &Default::default(),
+        )?
+        .build()?;
+
+        assert_eq!(output_record.genotypes().to_string(), expected);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(vcf::header::Number::A, &[1, 2], 2, Some(vec![2]))]
+    #[case(vcf::header::Number::R, &[10, 1, 2], 2, Some(vec![10, 2]))]
+    #[case(vcf::header::Number::G, &[0, 1, 2, 3, 4, 5], 1, Some(vec![0, 1, 2]))]
+    #[case(vcf::header::Number::G, &[0, 1, 2, 3, 4, 5], 2, Some(vec![0, 3, 5]))]
+    #[case(vcf::header::Number::G, &[0, 1, 2], 2, Some(vec![0, 2]))]
+    #[case(vcf::header::Number::Unknown, &[1, 2, 3], 2, Some(vec![1, 2, 3]))]
+    #[case(vcf::header::Number::R, &[10, 1], 2, None)]
+    fn decompose_format_value(
+        #[case] number: vcf::header::Number,
+        #[case] values: &[i32],
+        #[case] allele_no: usize,
+        #[case] expected: Option<Vec<i32>>,
+    ) {
+        use vcf::record::genotypes::sample::{value::Array, Value};
+
+        let to_value = |values: &[i32]| {
+            Value::Array(Array::Integer(values.iter().copied().map(Some).collect()))
+        };
+        assert_eq!(
+            super::decompose_format_value(&to_value(values), number, allele_no, 3),
+            expected.as_deref().map(to_value)
+        );
+    }
+
+    #[rstest]
+    #[case(1, "FractionInformativeReads=0.9;AF=0.5;RD=10,1")]
+    #[case(2, "FractionInformativeReads=0.9;AF=0.25;RD=10,2")]
+    fn copy_info(#[case] allele_no: usize, #[case] expected: &str) -> Result<(), anyhow::Error> {
+        let header: vcf::Header = [
+            "##fileformat=VCFv4.3",
+            r#"##INFO=<ID=AF,Number=A,Type=Float,Description="Allele frequency">"#,
+            r#"##INFO=<ID=DB,Number=0,Type=Flag,Description="dbSNP membership">"#,
+            r#"##INFO=<ID=FractionInformativeReads,Number=1,Type=Float,Description="Fraction">"#,
+            r#"##INFO=<ID=RD,Number=R,Type=Integer,Description="Read depths">"#,
+            "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO",
+            "",
+        ]
+        .join("\n")
+        .parse()?;
+        let input_record = vcf::Record::try_from((
+            &header,
+            "1\t100\t.\tA\tC,G\t.\t.\tAF=0.5,0.25;DB;FractionInformativeReads=0.9;RD=10,1,2",
+        ))?;
+        let mut output_record = vcf::Record::builder()
+            .set_chromosome(input_record.chromosome().clone())
+            .set_position(input_record.position())
+            .set_reference_bases(input_record.reference_bases().clone())
+            .build()?;
+
+        let kept_info_keys = super::KeptInfoKeys::new(
+            &["FractionInformativeReads".into(), "AF".into(), "RD".into()],
+            &header,
+        )?;
+        kept_info_keys.copy_info(&input_record, &mut output_record, allele_no);
+
+        assert_eq!(output_record.info().to_string(), expected);
+        assert!(super::KeptInfoKeys::new(&["XX".into()], &header).is_err());
+
+        Ok(())
+    }
 
     #[rstest]
     #[case::clair3_glnexus("tests/seqvars/ingest/clair3_glnexus.vcf")]
@@ -591,17 +1958,52 @@ mod test {
         let args_common = Default::default();
         let args = super::Args {
             file_date: String::from("20230421"),
-            case_uuid: uuid::Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            case_uuid: Some(uuid::Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap()),
             max_var_count: None,
+            io_backend: Default::default(),
+            rocksdb_prefetch: false,
+            annotation_backend: Default::default(),
+            annotation_url: None,
+            annotation_batch_size: 64,
+            annotation_max_retries: 3,
+            annotation_recordings: None,
+            annotation_replay: false,
+            csq_cache_size: 10_000,
+            write_index: Default::default(),
+            resume_from_partial: false,
+            validate_output: false,
+            strict: false,
+            path_allele_report: None,
+            quick_qc: false,
+            path_quick_qc: None,
+            quick_qc_every: 100,
+            quick_qc_first_per_contig: None,
+            quick_qc_max_seconds: 60,
             path_mehari_db: "tests/seqvars/ingest/db".into(),
-            path_ped: path.replace(".vcf", ".ped"),
+            path_ped: Some(path.replace(".vcf", ".ped")),
+            sample_mismatch_policy: Default::default(),
+            hpo_terms: vec![],
+            path_phenopacket: None,
+            annotate_only: false,
             genomebuild: GenomeRelease::Grch37,
-            path_in: path.into(),
+            path_in: vec![path.into()],
             path_out: tmpdir
                 .join("out.vcf")
                 .to_str()
                 .expect("invalid path")
                 .into(),
+            extra_format_keys: vec![],
+            keep_info_keys: vec![],
+            refine_genotypes: false,
+            skip_freq: false,
+            skip_clinvar: false,
+            skip_csq: false,
+            tx_policy: Default::default(),
+            path_inhouse_db: None,
+            inhouse_cf_counts: "counts".into(),
+            merge_phased_snvs: false,
+            regions: vec![],
+            path_regions_bed: None,
         };
         super::run(&args_common, &args).await?;
 
@@ -624,19 +2026,487 @@ mod test {
         let args_common = Default::default();
         let args = super::Args {
             file_date: String::from("20230421"),
-            case_uuid: uuid::Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+            case_uuid: Some(uuid::Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap()),
             max_var_count: None,
+            io_backend: Default::default(),
+            rocksdb_prefetch: false,
+            annotation_backend: Default::default(),
+            annotation_url: None,
+            annotation_batch_size: 64,
+            annotation_max_retries: 3,
+            annotation_recordings: None,
+            annotation_replay: false,
+            csq_cache_size: 10_000,
+            write_index: Default::default(),
+            resume_from_partial: false,
+            validate_output: false,
+            strict: false,
+            path_allele_report: None,
+            quick_qc: false,
+            path_quick_qc: None,
+            quick_qc_every: 100,
+            quick_qc_first_per_contig: None,
+            quick_qc_max_seconds: 60,
             path_mehari_db: "tests/seqvars/ingest/db".into(),
-            path_ped,
+            path_ped: Some(path_ped),
+            sample_mismatch_policy: Default::default(),
+            hpo_terms: vec![],
+            path_phenopacket: None,
+            annotate_only: false,
             genomebuild: GenomeRelease::Grch37,
-            path_in,
+            path_in: vec![path_in],
             path_out,
+            extra_format_keys: vec![],
+            keep_info_keys: vec![],
+            refine_genotypes: false,
+            skip_freq: false,
+            skip_clinvar: false,
+            skip_csq: false,
+            tx_policy: Default::default(),
+            path_inhouse_db: None,
+            inhouse_cf_counts: "counts".into(),
+            merge_phased_snvs: false,
+            regions: vec![],
+            path_regions_bed: None,
         };
         super::run(&args_common, &args).await?;
 
-        let mut buffer: Vec<u8> = Vec::new();
-        hxdmp::hexdump(&crate::common::read_to_bytes(&args.path_out)?, &mut buffer)?;
-        insta::assert_snapshot!(String::from_utf8_lossy(&buffer));
+        // Compare the decompressed content rather than the raw bgzf bytes.
+        let mut buffer = Vec::new();
+        std::io::Read::read_to_end(
+            &mut flate2::read::MultiGzDecoder::new(std::fs::File::open(&args.path_out)?),
+            &mut buffer,
+        )?;
+        insta::assert_snapshot!(String::from_utf8(buffer)?);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::vcf("tests/seqvars/ingest/NA12878_dragen.vcf", "out.vcf")]
+    #[case::vcf_gz("tests/seqvars/ingest/NA12878_dragen.vcf.gz", "out.vcf.gz")]
+    #[tokio::test]
+    async fn result_equal_across_backends(
+        #[case] path_in: &str,
+        #[case] name_out: &str,
+    ) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+
+        let args_common = Default::default();
+        let mut outputs = Vec::new();
+        for (io_backend, rocksdb_prefetch) in [
+            (super::IoBackend::Sequential, false),
+            (super::IoBackend::Async, false),
+            (super::IoBackend::Sequential, true),
+        ] {
+            let path_out = tmpdir.join(format!(
+                "{:?}-{}-{}",
+                io_backend, rocksdb_prefetch, name_out
+            ));
+            let args = super::Args {
+                file_date: String::from("20230421"),
+                case_uuid: Some(
+                    uuid::Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap(),
+                ),
+                max_var_count: None,
+                io_backend,
+                rocksdb_prefetch,
+                annotation_backend: Default::default(),
+                annotation_url: None,
+                annotation_batch_size: 64,
+                annotation_max_retries: 3,
+                annotation_recordings: None,
+                annotation_replay: false,
+                csq_cache_size: 10_000,
+                write_index: Default::default(),
+                resume_from_partial: false,
+                validate_output: false,
+                strict: false,
+                path_allele_report: None,
+                quick_qc: false,
+                path_quick_qc: None,
+                quick_qc_every: 100,
+                quick_qc_first_per_contig: None,
+                quick_qc_max_seconds: 60,
+                path_mehari_db: "tests/seqvars/ingest/db".into(),
+                path_ped: Some("tests/seqvars/ingest/NA12878_dragen.ped".into()),
+                sample_mismatch_policy: Default::default(),
+                hpo_terms: vec![],
+                path_phenopacket: None,
+                annotate_only: false,
+                genomebuild: GenomeRelease::Grch37,
+                path_in: vec![path_in.into()],
+                path_out: path_out.to_str().expect("invalid path").into(),
+                extra_format_keys: vec![],
+                keep_info_keys: vec![],
+                refine_genotypes: false,
+                skip_freq: false,
+                skip_clinvar: false,
+                skip_csq: false,
+                tx_policy: Default::default(),
+                path_inhouse_db: None,
+                inhouse_cf_counts: "counts".into(),
+                merge_phased_snvs: false,
+                regions: vec![],
+                path_regions_bed: None,
+            };
+            super::run(&args_common, &args).await?;
+            outputs.push(crate::common::read_to_bytes(&args.path_out)?);
+        }
+
+        assert_eq!(outputs[0], outputs[1]);
+        assert_eq!(outputs[0], outputs[2]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn annotate_only() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+
+        // Remove the caller information to check that unknown callers are accepted.
+        let path_in = tmpdir.join("in.vcf");
+        std::fs::write(
+            &path_in,
+            std::fs::read_to_string("tests/seqvars/ingest/Case_1.vcf")?
+                .lines()
+                .filter(|line| !line.starts_with("##GATKCommandLine"))
+                .map(|line| format!("{}\n", line))
+                .collect::<String>(),
+        )?;
+
+        let args_common = Default::default();
+        let args = super::Args {
+            file_date: String::from("20230421"),
+            case_uuid: None,
+            max_var_count: None,
+            io_backend: Default::default(),
+            rocksdb_prefetch: false,
+            annotation_backend: Default::default(),
+            annotation_url: None,
+            annotation_batch_size: 64,
+            annotation_max_retries: 3,
+            annotation_recordings: None,
+            annotation_replay: false,
+            csq_cache_size: 10_000,
+            write_index: Default::default(),
+            resume_from_partial: false,
+            validate_output: false,
+            strict: false,
+            path_allele_report: None,
+            quick_qc: false,
+            path_quick_qc: None,
+            quick_qc_every: 100,
+            quick_qc_first_per_contig: None,
+            quick_qc_max_seconds: 60,
+            path_mehari_db: "tests/seqvars/ingest/db".into(),
+            path_ped: None,
+            sample_mismatch_policy: Default::default(),
+            hpo_terms: vec![],
+            path_phenopacket: None,
+            annotate_only: true,
+            genomebuild: GenomeRelease::Grch37,
+            path_in: vec![path_in.to_str().expect("invalid path").into()],
+            path_out: tmpdir
+                .join("out.vcf")
+                .to_str()
+                .expect("invalid path")
+                .into(),
+            extra_format_keys: vec![],
+            keep_info_keys: vec![],
+            refine_genotypes: false,
+            skip_freq: false,
+            skip_clinvar: false,
+            skip_csq: false,
+            tx_policy: Default::default(),
+            path_inhouse_db: None,
+            inhouse_cf_counts: "counts".into(),
+            merge_phased_snvs: false,
+            regions: vec![],
+            path_regions_bed: None,
+        };
+        super::run(&args_common, &args).await?;
+
+        let output = std::fs::read_to_string(&args.path_out)?;
+        assert!(!output.contains("##x-varfish-case-uuid"));
+        assert!(!output.contains("##PEDIGREE"));
+        assert!(output.contains("Name=Other"));
+        assert!(output.contains(
+            "\tCase_1_father-N1-DNA1-WGS1\tCase_1_index-N1-DNA1-WGS1\tCase_1_mother-N1-DNA1-WGS1\n"
+        ));
+        assert!(output.lines().any(|line| !line.starts_with('#')));
+        assert!(!std::path::Path::new(&format!(
+            "{}.{}",
+            &args.path_out,
+            super::family::SIDECAR_SUFFIX
+        ))
+        .exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn refine_genotypes() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+
+        let path_in = "tests/seqvars/ingest/Case_1.vcf";
+
+        let args_common = Default::default();
+        let args = super::Args {
+            file_date: String::from("20230421"),
+            case_uuid: None,
+            max_var_count: None,
+            io_backend: Default::default(),
+            rocksdb_prefetch: false,
+            annotation_backend: Default::default(),
+            annotation_url: None,
+            annotation_batch_size: 64,
+            annotation_max_retries: 3,
+            annotation_recordings: None,
+            annotation_replay: false,
+            csq_cache_size: 10_000,
+            write_index: Default::default(),
+            resume_from_partial: false,
+            validate_output: false,
+            strict: false,
+            path_allele_report: None,
+            quick_qc: false,
+            path_quick_qc: None,
+            quick_qc_every: 100,
+            quick_qc_first_per_contig: None,
+            quick_qc_max_seconds: 60,
+            path_mehari_db: "tests/seqvars/ingest/db".into(),
+            path_ped: None,
+            sample_mismatch_policy: Default::default(),
+            hpo_terms: vec![],
+            path_phenopacket: None,
+            annotate_only: true,
+            genomebuild: GenomeRelease::Grch37,
+            path_in: vec![path_in.into()],
+            path_out: tmpdir
+                .join("out.vcf")
+                .to_str()
+                .expect("invalid path")
+                .into(),
+            extra_format_keys: vec![],
+            keep_info_keys: vec![],
+            refine_genotypes: true,
+            skip_freq: false,
+            skip_clinvar: false,
+            skip_csq: false,
+            tx_policy: Default::default(),
+            path_inhouse_db: None,
+            inhouse_cf_counts: "counts".into(),
+            merge_phased_snvs: false,
+            regions: vec![],
+            path_regions_bed: None,
+        };
+        super::run(&args_common, &args).await?;
+
+        let output = std::fs::read_to_string(&args.path_out)?;
+        assert!(output.contains("##FORMAT=<ID=PP,Number=G,Type=Integer"));
+        let records = output
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect::<Vec<_>>();
+        assert!(!records.is_empty());
+        assert!(records
+            .iter()
+            .all(|line| line.split('\t').nth(8) == Some("GT:AD:DP:GQ:PP")));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn inhouse_round_trip() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+
+        let path_in = "tests/seqvars/ingest/Case_1.vcf";
+        {
+            let mut options = rocksdb::Options::default();
+            options.create_if_missing(true);
+            options.create_missing_column_families(true);
+            let db = rocksdb::DB::open_cf(&options, tmpdir.join("inhouse"), ["counts"])?;
+            let key: Vec<u8> = annonars::common::keys::Var::from("17", 41249263, "G", "A").into();
+            let counts = crate::seqvars::aggregate::ds::Counts {
+                count_an: 20,
+                count_het: 3,
+                count_hom: 1,
+                count_hemi: 0,
+            };
+            db.put_cf(&db.cf_handle("counts").unwrap(), key, counts.to_vec())?;
+        }
+
+        let args_common = Default::default();
+        let args = super::Args {
+            file_date: String::from("20230421"),
+            case_uuid: None,
+            max_var_count: None,
+            io_backend: Default::default(),
+            rocksdb_prefetch: false,
+            annotation_backend: Default::default(),
+            annotation_url: None,
+            annotation_batch_size: 64,
+            annotation_max_retries: 3,
+            annotation_recordings: None,
+            annotation_replay: false,
+            csq_cache_size: 10_000,
+            write_index: Default::default(),
+            resume_from_partial: false,
+            validate_output: false,
+            strict: false,
+            path_allele_report: None,
+            quick_qc: false,
+            path_quick_qc: None,
+            quick_qc_every: 100,
+            quick_qc_first_per_contig: None,
+            quick_qc_max_seconds: 60,
+            path_mehari_db: "tests/seqvars/ingest/db".into(),
+            path_ped: None,
+            sample_mismatch_policy: Default::default(),
+            hpo_terms: vec![],
+            path_phenopacket: None,
+            annotate_only: true,
+            genomebuild: GenomeRelease::Grch37,
+            path_in: vec![path_in.into()],
+            path_out: tmpdir
+                .join("out.vcf")
+                .to_str()
+                .expect("invalid path")
+                .into(),
+            extra_format_keys: vec![],
+            keep_info_keys: vec![],
+            refine_genotypes: false,
+            skip_freq: false,
+            skip_clinvar: true,
+            skip_csq: true,
+            tx_policy: Default::default(),
+            path_inhouse_db: Some(
+                tmpdir
+                    .join("inhouse")
+                    .to_str()
+                    .expect("invalid path")
+                    .into(),
+            ),
+            inhouse_cf_counts: "counts".into(),
+            merge_phased_snvs: false,
+            regions: vec![],
+            path_regions_bed: None,
+        };
+        super::run(&args_common, &args).await?;
+
+        let content = std::fs::read_to_string(&args.path_out)?;
+        assert!(content.contains("##INFO=<ID=INHOUSE_CARRIERS,"));
+        assert!(content
+            .lines()
+            .any(|line| line.contains("INHOUSE_CARRIERS=4;INHOUSE_HOM=1;INHOUSE_HET=3")));
+
+        // The counts must be read back by `seqvars query` and used by its frequency filter.
+        let mut reader = vcf::reader::Builder::default().build_from_path(&args.path_out)?;
+        let header = reader.read_header()?;
+        let seqvars = reader
+            .records(&header)
+            .map(|record| SequenceVariant::from_vcf(&record?, &header))
+            .collect::<Result<Vec<_>, _>>()?;
+        let seqvar = seqvars
+            .iter()
+            .find(|seqvar| seqvar.pos == 41249263)
+            .expect("variant not found");
+        assert_eq!(
+            (
+                seqvar.inhouse_an,
+                seqvar.inhouse_hom,
+                seqvar.inhouse_het,
+                seqvar.inhouse_hemi
+            ),
+            (20, 1, 3, 0)
+        );
+        assert!(seqvars
+            .iter()
+            .filter(|seqvar| seqvar.pos != 41249263)
+            .all(|seqvar| seqvar.inhouse_an == 0));
+
+        let query = |inhouse_carriers| CaseQuery {
+            inhouse_enabled: true,
+            inhouse_carriers: Some(inhouse_carriers),
+            ..Default::default()
+        };
+        assert!(!frequency::passes(&query(3), seqvar)?);
+        assert!(frequency::passes(&query(4), seqvar)?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn skip_sources() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+
+        let path_in = "tests/seqvars/ingest/Case_1.vcf";
+
+        let args_common = Default::default();
+        let args = super::Args {
+            file_date: String::from("20230421"),
+            case_uuid: None,
+            max_var_count: None,
+            io_backend: Default::default(),
+            rocksdb_prefetch: false,
+            annotation_backend: Default::default(),
+            annotation_url: None,
+            annotation_batch_size: 64,
+            annotation_max_retries: 3,
+            annotation_recordings: None,
+            annotation_replay: false,
+            csq_cache_size: 10_000,
+            write_index: Default::default(),
+            resume_from_partial: false,
+            validate_output: false,
+            strict: false,
+            path_allele_report: None,
+            quick_qc: false,
+            path_quick_qc: None,
+            quick_qc_every: 100,
+            quick_qc_first_per_contig: None,
+            quick_qc_max_seconds: 60,
+            path_mehari_db: "tests/seqvars/ingest/db".into(),
+            path_ped: None,
+            sample_mismatch_policy: Default::default(),
+            hpo_terms: vec![],
+            path_phenopacket: None,
+            annotate_only: true,
+            genomebuild: GenomeRelease::Grch37,
+            path_in: vec![path_in.into()],
+            path_out: tmpdir
+                .join("out.vcf")
+                .to_str()
+                .expect("invalid path")
+                .into(),
+            extra_format_keys: vec![],
+            keep_info_keys: vec![],
+            refine_genotypes: false,
+            skip_freq: true,
+            skip_clinvar: true,
+            skip_csq: true,
+            tx_policy: Default::default(),
+            path_inhouse_db: None,
+            inhouse_cf_counts: "counts".into(),
+            merge_phased_snvs: false,
+            regions: vec![],
+            path_regions_bed: None,
+        };
+        super::run(&args_common, &args).await?;
+
+        let output = std::fs::read_to_string(&args.path_out)?;
+        let records = output
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect::<Vec<_>>();
+        assert!(!records.is_empty());
+        for record in records {
+            let info = record.split('\t').nth(7).unwrap();
+            assert!(!info.contains("ANN="));
+            assert!(!info.contains("gnomad_"));
+            assert!(!info.contains("clinvar"));
+        }
 
         Ok(())
     }
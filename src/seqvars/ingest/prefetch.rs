@@ -0,0 +1,674 @@
+//! Lookups into the frequency and ClinVar RocksDB databases.
+//!
+//! Both the input VCF file and the databases are sorted by coordinate.  Instead of one
+//! random point lookup per record, the presence of the keys can thus be checked by walking
+//! RocksDB iterators in lock-step with the VCF stream (sorted-merge join).  There is one
+//! iterator per contig, such that interleaved contigs do not reposition each other's
+//! iterator, and the keys of a batch are checked on the contigs in parallel.  An iterator
+//! is repositioned with a `seek` when the next key is behind it (e.g., in the next batch
+//! after a restart) or too far ahead of it.
+//!
+//! Records are annotated from local databases with the functions of `mehari`.  Other
+//! stores than RocksDB only return the raw values, which are decoded here in the same way.
+
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use annonars::freqs::serialized::{auto, mt, xy};
+use noodles_vcf as vcf;
+use prost::Message;
+use rayon::prelude::*;
+use vcf::record::info::field;
+
+use crate::common::kv::VariantKvStore;
+
+/// Number of iterator steps to try before falling back to a `seek`.
+const MAX_STEPS: usize = 16;
+
+/// Number of leading bytes of the keys that encode the contig.
+const CONTIG_PREFIX_LEN: usize = 2;
+
+/// Return the contig prefix of `key`.
+fn contig_of(key: &[u8]) -> &[u8] {
+    &key[..CONTIG_PREFIX_LEN.min(key.len())]
+}
+
+/// The column families used for annotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Table {
+    /// Autosomal frequencies.
+    Autosomal,
+    /// Gonosomal frequencies.
+    Gonosomal,
+    /// Mitochondrial frequencies.
+    Mitochondrial,
+    /// ClinVar records.
+    Clinvar,
+}
+
+impl Table {
+    /// Return the frequency table for the (canonical) chromosome `chrom`, if any.
+    pub fn frequencies_for(chrom: &str) -> Option<Self> {
+        if mehari::annotate::seqvars::CHROM_AUTO.contains(chrom) {
+            Some(Table::Autosomal)
+        } else if mehari::annotate::seqvars::CHROM_XY.contains(chrom) {
+            Some(Table::Gonosomal)
+        } else if mehari::annotate::seqvars::CHROM_MT.contains(chrom) {
+            Some(Table::Mitochondrial)
+        } else {
+            None
+        }
+    }
+
+    /// Return the name of the column family.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Table::Autosomal => "autosomal",
+            Table::Gonosomal => "gonosomal",
+            Table::Mitochondrial => "mitochondrial",
+            Table::Clinvar => "clinvar",
+        }
+    }
+
+    /// Annotate `vcf_record` with `mehari` from the value for `key` in `db`, if any.
+    fn annotate_from_db(
+        &self,
+        db: &rocksdb::DB,
+        key: &[u8],
+        vcf_record: &mut vcf::Record,
+    ) -> Result<(), anyhow::Error> {
+        let key = key.to_vec();
+        let cf = db
+            .cf_handle(self.name())
+            .unwrap_or_else(|| panic!("column family {} must exist", self.name()));
+        match self {
+            Table::Autosomal => {
+                mehari::annotate::seqvars::annotate_record_auto(db, &cf, &key, vcf_record)
+            }
+            Table::Gonosomal => {
+                mehari::annotate::seqvars::annotate_record_xy(db, &cf, &key, vcf_record)
+            }
+            Table::Mitochondrial => {
+                mehari::annotate::seqvars::annotate_record_mt(db, &cf, &key, vcf_record)
+            }
+            Table::Clinvar => {
+                mehari::annotate::seqvars::annotate_record_clinvar(db, &cf, &key, vcf_record)
+            }
+        }
+    }
+
+    /// Annotate `vcf_record` from the raw `value` of a store other than RocksDB.
+    fn annotate_from_value(
+        &self,
+        value: &[u8],
+        vcf_record: &mut vcf::Record,
+    ) -> Result<(), anyhow::Error> {
+        match self {
+            Table::Autosomal => annotate_auto(value, vcf_record),
+            Table::Gonosomal => annotate_xy(value, vcf_record),
+            Table::Mitochondrial => annotate_mt(value, vcf_record),
+            Table::Clinvar => annotate_clinvar(value, vcf_record)?,
+        }
+        Ok(())
+    }
+}
+
+/// Cursor for looking up keys in ascending order with a forward iterator.
+pub struct SortedCursor<'a> {
+    /// The underlying raw iterator.
+    iter: rocksdb::DBRawIteratorWithThreadMode<'a, rocksdb::DB>,
+    /// The previously looked up key, if any.
+    prev_key: Option<Vec<u8>>,
+}
+
+impl<'a> SortedCursor<'a> {
+    /// Create a new cursor on the column family `cf` of `db`.
+    pub fn new(db: &'a rocksdb::DB, cf: &Arc<rocksdb::BoundColumnFamily<'a>>) -> Self {
+        Self {
+            iter: db.raw_iterator_cf(cf),
+            prev_key: None,
+        }
+    }
+
+    /// Position the iterator at `key` or after it and return whether `key` exists.
+    fn seek_to(&mut self, key: &[u8]) -> Result<bool, anyhow::Error> {
+        let in_order = self.iter.valid()
+            && self
+                .prev_key
+                .as_ref()
+                .map(|prev_key| prev_key.as_slice() <= key)
+                .unwrap_or(false);
+        if in_order {
+            let mut steps = 0;
+            while steps < MAX_STEPS && self.iter.key().map(|k| k < key).unwrap_or(false) {
+                self.iter.next();
+                steps += 1;
+            }
+            if self.iter.key().map(|k| k < key).unwrap_or(false) {
+                self.iter.seek(key);
+            }
+        } else {
+            self.iter.seek(key);
+        }
+        self.iter
+            .status()
+            .map_err(|e| anyhow::anyhow!("problem iterating RocksDB: {}", e))?;
+        self.prev_key = Some(key.to_vec());
+
+        Ok(self.iter.key() == Some(key))
+    }
+
+    /// Return whether `key` exists.
+    pub fn contains(&mut self, key: &[u8]) -> Result<bool, anyhow::Error> {
+        self.seek_to(key)
+    }
+
+    /// Return the value for `key`, if any.
+    pub fn get(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>, anyhow::Error> {
+        Ok(if self.seek_to(key)? {
+            self.iter.value().map(|value| value.to_vec())
+        } else {
+            None
+        })
+    }
+}
+
+/// Sorted cursors on one column family, one per contig.
+pub struct ShardedCursors<'a> {
+    /// The database to iterate.
+    db: &'a rocksdb::DB,
+    /// The column family to iterate.
+    table: Table,
+    /// The cursors by contig prefix of the keys, created on first use.
+    shards: HashMap<Vec<u8>, SortedCursor<'a>>,
+    /// Presence of the keys checked by the last call to `prefetch()`.
+    prefetched: HashMap<Vec<u8>, bool>,
+}
+
+impl<'a> ShardedCursors<'a> {
+    /// Create new cursors on `table` of `db`.
+    pub fn new(db: &'a rocksdb::DB, table: Table) -> Self {
+        Self {
+            db,
+            table,
+            shards: HashMap::new(),
+            prefetched: HashMap::new(),
+        }
+    }
+
+    /// Create the cursor for `contig` unless it exists.
+    fn ensure_shard(&mut self, contig: &[u8]) {
+        if !self.shards.contains_key(contig) {
+            let cf = self
+                .db
+                .cf_handle(self.table.name())
+                .unwrap_or_else(|| panic!("column family {} must exist", self.table.name()));
+            self.shards
+                .insert(contig.to_vec(), SortedCursor::new(self.db, &cf));
+        }
+    }
+
+    /// Return whether `key` exists, using the result of the last `prefetch()` if any.
+    pub fn contains(&mut self, key: &[u8]) -> Result<bool, anyhow::Error> {
+        if let Some(found) = self.prefetched.get(key) {
+            return Ok(*found);
+        }
+        let contig = contig_of(key);
+        self.ensure_shard(contig);
+        self.shards
+            .get_mut(contig)
+            .expect("created above")
+            .contains(key)
+    }
+
+    /// Check the presence of `keys` on their contigs in parallel for the next calls to
+    /// `contains()`.
+    pub fn prefetch(&mut self, keys: &[Vec<u8>]) -> Result<(), anyhow::Error> {
+        let mut by_contig: HashMap<&[u8], Vec<&[u8]>> = HashMap::new();
+        for key in keys {
+            by_contig.entry(contig_of(key)).or_default().push(key);
+        }
+        for contig in by_contig.keys() {
+            self.ensure_shard(contig);
+        }
+        let found = self
+            .shards
+            .par_iter_mut()
+            .filter_map(|(contig, cursor)| {
+                by_contig
+                    .get(contig.as_slice())
+                    .map(|keys| (cursor, keys.clone()))
+            })
+            .map(|(cursor, mut keys)| {
+                keys.sort_unstable();
+                keys.dedup();
+                keys.into_iter()
+                    .map(|key| Ok((key.to_vec(), cursor.contains(key)?)))
+                    .collect::<Result<Vec<_>, anyhow::Error>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.prefetched = found.into_iter().flatten().collect();
+
+        Ok(())
+    }
+}
+
+/// Lookup strategy for one column family.
+pub enum Lookup<'a> {
+    /// Annotation from a local RocksDB with `mehari`.
+    RocksDb {
+        /// The database to query.
+        db: &'a rocksdb::DB,
+        /// The column family to query.
+        table: Table,
+        /// Cursors to check the presence of the keys first, if enabled.
+        cursors: Option<ShardedCursors<'a>>,
+    },
+    /// One point lookup per key in any key-value store.
+    Point {
+        /// The store to query.
+        store: &'a dyn VariantKvStore,
+        /// The table (column family) to query.
+        table: Table,
+    },
+    /// No lookups, e.g., for skipped annotation sources.
+    Skipped,
+}
+
+impl<'a> Lookup<'a> {
+    /// Create a new lookup into `table` of `db`, with sorted cursors if `prefetch` is set.
+    pub fn new(db: &'a rocksdb::DB, table: Table, prefetch: bool) -> Self {
+        Lookup::RocksDb {
+            db,
+            table,
+            cursors: prefetch.then(|| ShardedCursors::new(db, table)),
+        }
+    }
+
+    /// Create a new point lookup into `table` of `store`.
+    pub fn point(store: &'a dyn VariantKvStore, table: Table) -> Self {
+        Lookup::Point { store, table }
+    }
+
+    /// Check the presence of `keys` in parallel if sorted cursors are enabled, see
+    /// `ShardedCursors::prefetch()`.
+    pub fn prefetch(&mut self, keys: &[Vec<u8>]) -> Result<(), anyhow::Error> {
+        match self {
+            Lookup::RocksDb {
+                cursors: Some(cursors),
+                ..
+            } => cursors.prefetch(keys),
+            _ => Ok(()),
+        }
+    }
+
+    /// Annotate `vcf_record` with the value for `key`, if any.
+    pub fn annotate(
+        &mut self,
+        key: &[u8],
+        vcf_record: &mut vcf::Record,
+    ) -> Result<(), anyhow::Error> {
+        match self {
+            Lookup::RocksDb { db, table, cursors } => {
+                if let Some(cursors) = cursors.as_mut() {
+                    if !cursors.contains(key)? {
+                        return Ok(());
+                    }
+                }
+                table.annotate_from_db(db, key, vcf_record)
+            }
+            Lookup::Point { store, table } => match store.get(table.name(), key)? {
+                Some(value) => table.annotate_from_value(&value, vcf_record),
+                None => Ok(()),
+            },
+            Lookup::Skipped => Ok(()),
+        }
+    }
+}
+
+/// Lookups into the column families used for annotation.
+pub struct Lookups<'a> {
+    /// Autosomal frequencies.
+    pub autosomal: Lookup<'a>,
+    /// Gonosomal frequencies.
+    pub gonosomal: Lookup<'a>,
+    /// Mitochondrial frequencies.
+    pub mitochondrial: Lookup<'a>,
+    /// ClinVar records.
+    pub clinvar: Lookup<'a>,
+}
+
+impl<'a> Lookups<'a> {
+    /// Create the lookups into `db_freq` and `db_clinvar`; missing databases are skipped.
+    pub fn new(
+        db_freq: Option<&'a rocksdb::DB>,
+        db_clinvar: Option<&'a rocksdb::DB>,
+        prefetch: bool,
+    ) -> Self {
+        let lookup = |db: Option<&'a rocksdb::DB>, table: Table| {
+            db.map(|db| Lookup::new(db, table, prefetch))
+                .unwrap_or(Lookup::Skipped)
+        };
+        Self {
+            autosomal: lookup(db_freq, Table::Autosomal),
+            gonosomal: lookup(db_freq, Table::Gonosomal),
+            mitochondrial: lookup(db_freq, Table::Mitochondrial),
+            clinvar: lookup(db_clinvar, Table::Clinvar),
+        }
+    }
+
+    /// Create point lookups into `store_freq` and `store_clinvar`, e.g., in-memory stores;
+    /// missing stores are skipped.
+    pub fn with_stores(
+        store_freq: Option<&'a dyn VariantKvStore>,
+        store_clinvar: Option<&'a dyn VariantKvStore>,
+    ) -> Self {
+        let lookup = |store: Option<&'a dyn VariantKvStore>, table: Table| {
+            store
+                .map(|store| Lookup::point(store, table))
+                .unwrap_or(Lookup::Skipped)
+        };
+        Self {
+            autosomal: lookup(store_freq, Table::Autosomal),
+            gonosomal: lookup(store_freq, Table::Gonosomal),
+            mitochondrial: lookup(store_freq, Table::Mitochondrial),
+            clinvar: lookup(store_clinvar, Table::Clinvar),
+        }
+    }
+
+    /// Return the lookup for `table`.
+    pub fn get_mut(&mut self, table: Table) -> &mut Lookup<'a> {
+        match table {
+            Table::Autosomal => &mut self.autosomal,
+            Table::Gonosomal => &mut self.gonosomal,
+            Table::Mitochondrial => &mut self.mitochondrial,
+            Table::Clinvar => &mut self.clinvar,
+        }
+    }
+
+    /// Check the presence of the keys of `vars` (on canonical contigs) in the frequency and
+    /// ClinVar tables, see `Lookup::prefetch()`.
+    pub fn prefetch(&mut self, vars: &[annonars::common::keys::Var]) -> Result<(), anyhow::Error> {
+        let mut keys_by_table: HashMap<Table, Vec<Vec<u8>>> = HashMap::new();
+        for var in vars {
+            let key: Vec<u8> = var.clone().into();
+            if let Some(table) = Table::frequencies_for(var.chrom.as_str()) {
+                keys_by_table.entry(table).or_default().push(key.clone());
+            }
+            keys_by_table.entry(Table::Clinvar).or_default().push(key);
+        }
+        for (table, keys) in keys_by_table {
+            self.get_mut(table).prefetch(&keys)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Insert the integer INFO field `key` with `value` into `vcf_record`.
+fn insert_count(vcf_record: &mut vcf::Record, key: &str, value: u32) {
+    vcf_record.info_mut().insert(
+        field::Key::from_str(key).expect("invalid key in source code"),
+        Some(field::Value::Integer(value as i32)),
+    );
+}
+
+/// Annotate record on autosomal chromosome with gnomAD exomes/genomes.
+fn annotate_auto(value: &[u8], vcf_record: &mut vcf::Record) {
+    let record = auto::Record::from_buf(value);
+    for (prefix, counts) in [
+        ("gnomad_exomes", &record.gnomad_exomes),
+        ("gnomad_genomes", &record.gnomad_genomes),
+    ] {
+        insert_count(vcf_record, &format!("{}_an", prefix), counts.an);
+        insert_count(vcf_record, &format!("{}_hom", prefix), counts.ac_hom);
+        insert_count(vcf_record, &format!("{}_het", prefix), counts.ac_het);
+    }
+}
+
+/// Annotate record on gonosomal chromosome with gnomAD exomes/genomes.
+fn annotate_xy(value: &[u8], vcf_record: &mut vcf::Record) {
+    let record = xy::Record::from_buf(value);
+    for (prefix, counts) in [
+        ("gnomad_exomes", &record.gnomad_exomes),
+        ("gnomad_genomes", &record.gnomad_genomes),
+    ] {
+        insert_count(vcf_record, &format!("{}_an", prefix), counts.an);
+        insert_count(vcf_record, &format!("{}_hom", prefix), counts.ac_hom);
+        insert_count(vcf_record, &format!("{}_het", prefix), counts.ac_het);
+        insert_count(vcf_record, &format!("{}_hemi", prefix), counts.ac_hemi);
+    }
+}
+
+/// Annotate record on mitochondrial genome with gnomAD mtDNA and HelixMtDb.
+fn annotate_mt(value: &[u8], vcf_record: &mut vcf::Record) {
+    let record = mt::Record::from_buf(value);
+    for (prefix, counts) in [
+        ("helix", &record.helixmtdb),
+        ("gnomad_genomes", &record.gnomad_mtdna),
+    ] {
+        insert_count(vcf_record, &format!("{}_an", prefix), counts.an);
+        insert_count(vcf_record, &format!("{}_hom", prefix), counts.ac_hom);
+        insert_count(vcf_record, &format!("{}_het", prefix), counts.ac_het);
+    }
+}
+
+/// Annotate record with ClinVar information.
+fn annotate_clinvar(value: &[u8], vcf_record: &mut vcf::Record) -> Result<(), anyhow::Error> {
+    let annonars::pbs::clinvar::minimal::Record {
+        vcv,
+        reference_assertions,
+        ..
+    } = annonars::pbs::clinvar::minimal::Record::decode(&mut std::io::Cursor::new(value))?;
+    if let Some(reference_assertion) = reference_assertions.into_iter().next() {
+        let clinical_significance: annonars::clinvar_minimal::cli::reading::ClinicalSignificance =
+            reference_assertion.clinical_significance.into();
+        vcf_record.info_mut().insert(
+            field::Key::from_str("clinvar_clinsig")?,
+            Some(field::Value::String(clinical_significance.to_string())),
+        );
+        vcf_record.info_mut().insert(
+            field::Key::from_str("clinvar_rcv")?,
+            Some(field::Value::String(reference_assertion.rcv)),
+        );
+        vcf_record.info_mut().insert(
+            field::Key::from_str("clinvar_vcv")?,
+            Some(field::Value::String(vcv)),
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use noodles_vcf as vcf;
+    use prost::Message;
+
+    use super::{Lookup, ShardedCursors, SortedCursor, Table};
+    use crate::common::kv::InMemoryStore;
+
+    /// Create a temporary database with all column families and the given `keys` in each.
+    fn build_db(
+        tmpdir: &temp_testdir::TempDir,
+        keys: &[&[u8]],
+    ) -> Result<rocksdb::DB, anyhow::Error> {
+        let mut options = rocksdb::Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+        let db = rocksdb::DB::open_cf(
+            &options,
+            &**tmpdir,
+            ["autosomal", "gonosomal", "mitochondrial", "clinvar"],
+        )?;
+        for key in keys {
+            db.put_cf(&db.cf_handle("autosomal").unwrap(), key, key)?;
+        }
+        Ok(db)
+    }
+
+    /// Build a key on `contig` at `pos`.
+    fn key(contig: &[u8], pos: u32) -> Vec<u8> {
+        [contig, &pos.to_be_bytes()].concat()
+    }
+
+    #[test]
+    fn lookups_with_stores() -> Result<(), anyhow::Error> {
+        let key: Vec<u8> = annonars::common::keys::Var::from("1", 100, "A", "G").into();
+        let value = (1..=8u32).flat_map(|i| i.to_le_bytes()).collect::<Vec<_>>();
+        let mut store_freq =
+            InMemoryStore::with_tables(["autosomal", "gonosomal", "mitochondrial"]);
+        store_freq.insert("autosomal", key.clone(), value);
+        let store_clinvar = InMemoryStore::with_tables(["clinvar"]);
+
+        let mut lookups = super::Lookups::with_stores(Some(&store_freq), Some(&store_clinvar));
+        let mut record = vcf::Record::default();
+        lookups.autosomal.annotate(&key, &mut record)?;
+        assert_eq!(record.info().len(), 6);
+        let mut record = vcf::Record::default();
+        lookups.gonosomal.annotate(&key, &mut record)?;
+        lookups.clinvar.annotate(&key, &mut record)?;
+        assert!(record.info().is_empty());
+
+        let mut lookups = super::Lookups::with_stores(None, Some(&store_clinvar));
+        lookups.autosomal.annotate(&key, &mut record)?;
+        assert!(record.info().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn sorted_cursor_matches_point_lookups() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let stored: Vec<Vec<u8>> = (0..100u32).map(|i| key(b"01", i * 10)).collect();
+        let db = build_db(
+            &tmpdir,
+            &stored.iter().map(|k| k.as_slice()).collect::<Vec<_>>(),
+        )?;
+
+        // Mostly ascending with gaps, hits, misses, a big jump, and going back.
+        let queries: Vec<Vec<u8>> = [0u32, 5, 10, 10, 20, 500, 510, 990, 2000, 30, 40, 45]
+            .iter()
+            .map(|pos| key(b"01", *pos))
+            .chain(std::iter::once(b"02".to_vec()))
+            .collect();
+
+        let cf = db.cf_handle("autosomal").unwrap();
+        let mut cursor = SortedCursor::new(&db, &cf);
+        for query in &queries {
+            let expected = db.get_cf(&cf, query)?;
+            assert_eq!(cursor.get(query)?, expected, "query = {:?}", query);
+            assert_eq!(expected.is_some(), stored.contains(query));
+        }
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case::sequential(false)]
+    #[case::prefetch(true)]
+    fn sharded_cursors(#[case] prefetch: bool) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let stored: Vec<Vec<u8>> = (0..50u32)
+            .flat_map(|i| [key(b"01", i * 10), key(b"0X", i * 20)])
+            .collect();
+        let db = build_db(
+            &tmpdir,
+            &stored.iter().map(|k| k.as_slice()).collect::<Vec<_>>(),
+        )?;
+
+        // Interleaved contigs, as for unsorted or merged input.
+        let queries: Vec<Vec<u8>> = [0u32, 10, 15, 20, 40, 490, 1000]
+            .iter()
+            .flat_map(|pos| [key(b"01", *pos), key(b"0X", *pos)])
+            .collect();
+
+        let mut cursors = ShardedCursors::new(&db, Table::Autosomal);
+        if prefetch {
+            cursors.prefetch(&queries)?;
+        }
+        for query in &queries {
+            assert_eq!(
+                cursors.contains(query)?,
+                stored.contains(query),
+                "query = {:?}",
+                query
+            );
+        }
+        assert_eq!(cursors.shards.len(), 2);
+
+        Ok(())
+    }
+
+    /// Annotate a fresh record with `mehari` and with our lookups from the same value and
+    /// compare the results.
+    fn compare_with_mehari(table: Table, value: &[u8]) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let db = build_db(&tmpdir, &[])?;
+        let key: Vec<u8> = annonars::common::keys::Var::from("1", 100, "A", "G").into();
+        db.put_cf(&db.cf_handle(table.name()).unwrap(), &key, value)?;
+        let mut store = InMemoryStore::with_tables([table.name()]);
+        store.insert(table.name(), key.clone(), value.to_vec());
+
+        let mut expected = vcf::Record::default();
+        let cf_handle = db.cf_handle(table.name()).unwrap();
+        match table {
+            Table::Autosomal => mehari::annotate::seqvars::annotate_record_auto(
+                &db,
+                &cf_handle,
+                &key,
+                &mut expected,
+            )?,
+            Table::Gonosomal => {
+                mehari::annotate::seqvars::annotate_record_xy(&db, &cf_handle, &key, &mut expected)?
+            }
+            Table::Mitochondrial => {
+                mehari::annotate::seqvars::annotate_record_mt(&db, &cf_handle, &key, &mut expected)?
+            }
+            Table::Clinvar => mehari::annotate::seqvars::annotate_record_clinvar(
+                &db,
+                &cf_handle,
+                &key,
+                &mut expected,
+            )?,
+        }
+        for mut lookup in [
+            Lookup::new(&db, table, false),
+            Lookup::new(&db, table, true),
+            Lookup::point(&store, table),
+        ] {
+            let mut actual = vcf::Record::default();
+            lookup.annotate(&key, &mut actual)?;
+            assert_eq!(actual.info().to_string(), expected.info().to_string());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn annotate_freqs() -> Result<(), anyhow::Error> {
+        let value = (1..=8u32).flat_map(|i| i.to_le_bytes()).collect::<Vec<_>>();
+        compare_with_mehari(Table::Autosomal, &value)?;
+        compare_with_mehari(Table::Gonosomal, &value)?;
+        let value = (1..=6u32).flat_map(|i| i.to_le_bytes()).collect::<Vec<_>>();
+        compare_with_mehari(Table::Mitochondrial, &value)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn annotate_clinvar() -> Result<(), anyhow::Error> {
+        let value = annonars::pbs::clinvar::minimal::Record {
+            vcv: "VCV000012345".into(),
+            reference_assertions: vec![annonars::pbs::clinvar::minimal::ReferenceAssertion {
+                rcv: "RCV000012345".into(),
+                clinical_significance: 2,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+        .encode_to_vec();
+        compare_with_mehari(Table::Clinvar, &value)?;
+
+        Ok(())
+    }
+}
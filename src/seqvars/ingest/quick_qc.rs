@@ -0,0 +1,368 @@
+//! Approximate QC metrics from a sample of the input records (`--quick-qc`).
+//!
+//! This allows for an early sanity check of the input before running the full ingest.
+//! Next to the overall metrics, per-chromosome metrics are emitted in a columnar layout
+//! so the server can plot a karyotype overview without reprocessing the VCF.
+
+use std::time::{Duration, Instant};
+
+use futures::TryStreamExt;
+use noodles_vcf as vcf;
+use vcf::record::genotypes::{keys::key, sample::Value};
+
+use super::{alleles, regions::RecordStream};
+
+/// How to select the records for the QC metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sampling {
+    /// Use every Nth record.
+    Every(usize),
+    /// Use the first N records of each contig.
+    FirstPerContig(usize),
+}
+
+/// Approximate per-sample QC metrics.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SampleMetrics {
+    /// Number of hom. ref. calls.
+    pub count_hom_ref: usize,
+    /// Number of het. calls.
+    pub count_het: usize,
+    /// Number of hom. alt. calls.
+    pub count_hom_alt: usize,
+    /// Number of (partial) no-calls.
+    pub count_no_call: usize,
+    /// Mean depth of the calls with depth.
+    pub mean_dp: Option<f64>,
+    /// Mean genotype quality of the calls with genotype quality.
+    pub mean_gq: Option<f64>,
+    /// Sum of the depths, for computing the mean.
+    #[serde(skip)]
+    sum_dp: (f64, usize),
+    /// Sum of the genotype qualities, for computing the mean.
+    #[serde(skip)]
+    sum_gq: (f64, usize),
+}
+
+/// Per-sample counts on one chromosome, for computing the karyotype metrics.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct SampleChromCounts {
+    /// Number of het. calls.
+    count_het: usize,
+    /// Number of hom. alt. calls.
+    count_hom_alt: usize,
+    /// Sum of the depths, for computing the mean.
+    sum_dp: (f64, usize),
+}
+
+/// Counts on one chromosome, for computing the karyotype metrics.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct ChromCounts {
+    /// Number of sampled records.
+    count_records: usize,
+    /// Per-sample counts, in the order of the samples.
+    samples: Vec<SampleChromCounts>,
+}
+
+/// Per-chromosome series of one sample, indexed like `Karyotype::chromosomes`.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct KaryotypeSeries {
+    /// Number of non-ref. calls.
+    pub count_variants: Vec<usize>,
+    /// Fraction of het. calls among the non-ref. calls.
+    pub het_rate: Vec<Option<f64>>,
+    /// Mean depth relative to the mean autosomal depth of the sample.
+    pub dp_ratio: Vec<Option<f64>>,
+}
+
+/// Approximate per-chromosome QC metrics.
+///
+/// The metrics are stored as one array per metric so they can be passed to a plotting
+/// library as they are.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Karyotype {
+    /// The chromosome names, in order of appearance.
+    pub chromosomes: Vec<String>,
+    /// Number of sampled records.
+    pub count_records: Vec<usize>,
+    /// Per-sample series.
+    pub samples: indexmap::IndexMap<String, KaryotypeSeries>,
+}
+
+/// Approximate QC metrics of the sampled records.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Metrics {
+    /// Number of records read.
+    pub count_read: usize,
+    /// Number of records sampled for the metrics.
+    pub count_sampled: usize,
+    /// Whether sampling was stopped because the time limit was reached.
+    pub timed_out: bool,
+    /// Number of sampled SNVs.
+    pub count_snvs: usize,
+    /// Number of sampled indels and other variants.
+    pub count_indels: usize,
+    /// Number of sampled multi-allelic records.
+    pub count_multiallelic: usize,
+    /// Transition/transversion ratio of the sampled SNVs.
+    pub ti_tv_ratio: Option<f64>,
+    /// Number of sampled transitions, for computing the ratio.
+    #[serde(skip)]
+    count_transitions: usize,
+    /// Per-sample metrics.
+    pub samples: indexmap::IndexMap<String, SampleMetrics>,
+    /// Per-chromosome metrics.
+    pub karyotype: Karyotype,
+    /// Per-chromosome counts, for computing the karyotype metrics.
+    #[serde(skip)]
+    chrom_counts: indexmap::IndexMap<String, ChromCounts>,
+}
+
+/// Whether the SNV `reference` > `alternative` is a transition.
+fn is_transition(reference: &str, alternative: &str) -> bool {
+    matches!(
+        (reference, alternative),
+        ("A", "G") | ("G", "A") | ("C", "T") | ("T", "C")
+    )
+}
+
+/// Whether `chrom` is an autosome.
+fn is_autosome(chrom: &str) -> bool {
+    annonars::common::cli::canonicalize(chrom)
+        .parse::<u8>()
+        .map(|no| (1..=22).contains(&no))
+        .unwrap_or(false)
+}
+
+impl Metrics {
+    /// Construct for the samples in `header`.
+    pub fn new(header: &vcf::Header) -> Self {
+        Self {
+            samples: header
+                .sample_names()
+                .iter()
+                .map(|name| (name.clone(), Default::default()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Register one sampled `record`.
+    pub fn register(&mut self, record: &vcf::Record) {
+        self.count_sampled += 1;
+
+        let reference = record.reference_bases().to_string();
+        let alternatives = record.alternate_bases();
+        if alternatives.len() > 1 {
+            self.count_multiallelic += 1;
+        }
+        let alternative = alternatives
+            .iter()
+            .next()
+            .map(|allele| allele.to_string())
+            .unwrap_or_default();
+        if reference.len() == 1 && alternative.len() == 1 {
+            self.count_snvs += 1;
+            if is_transition(&reference, &alternative) {
+                self.count_transitions += 1;
+            }
+        } else {
+            self.count_indels += 1;
+        }
+
+        let count_samples = self.samples.len();
+        let chrom_counts = self
+            .chrom_counts
+            .entry(record.chromosome().to_string())
+            .or_insert_with(|| ChromCounts {
+                count_records: 0,
+                samples: vec![Default::default(); count_samples],
+            });
+        chrom_counts.count_records += 1;
+
+        for ((sample, metrics), counts) in record
+            .genotypes()
+            .values()
+            .zip(self.samples.values_mut())
+            .zip(chrom_counts.samples.iter_mut())
+        {
+            match sample.genotype() {
+                Some(Ok(genotype)) => {
+                    let positions = genotype
+                        .iter()
+                        .map(|allele| allele.position())
+                        .collect::<Vec<_>>();
+                    if positions.iter().any(Option::is_none) {
+                        metrics.count_no_call += 1;
+                    } else if positions.iter().all(|pos| *pos == Some(0)) {
+                        metrics.count_hom_ref += 1;
+                    } else if positions.windows(2).all(|w| w[0] == w[1]) {
+                        metrics.count_hom_alt += 1;
+                        counts.count_hom_alt += 1;
+                    } else {
+                        metrics.count_het += 1;
+                        counts.count_het += 1;
+                    }
+                }
+                _ => metrics.count_no_call += 1,
+            }
+            if let Some(Some(Value::Integer(dp))) = sample.get(&key::READ_DEPTH) {
+                metrics.sum_dp.0 += *dp as f64;
+                metrics.sum_dp.1 += 1;
+                counts.sum_dp.0 += *dp as f64;
+                counts.sum_dp.1 += 1;
+            }
+            match sample.get(&key::CONDITIONAL_GENOTYPE_QUALITY) {
+                Some(Some(Value::Integer(gq))) => {
+                    metrics.sum_gq.0 += *gq as f64;
+                    metrics.sum_gq.1 += 1;
+                }
+                Some(Some(Value::Float(gq))) => {
+                    metrics.sum_gq.0 += *gq as f64;
+                    metrics.sum_gq.1 += 1;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Compute the ratios and means.
+    pub fn finalize(mut self) -> Self {
+        let count_transversions = self.count_snvs - self.count_transitions;
+        self.ti_tv_ratio = (count_transversions > 0)
+            .then(|| self.count_transitions as f64 / count_transversions as f64);
+        let mean = |(sum, count): (f64, usize)| (count > 0).then(|| sum / count as f64);
+        for metrics in self.samples.values_mut() {
+            metrics.mean_dp = mean(metrics.sum_dp);
+            metrics.mean_gq = mean(metrics.sum_gq);
+        }
+
+        // Normalize the per-chromosome depths by the autosomal depth of each sample.
+        let mut autosomal_dp = vec![(0.0, 0); self.samples.len()];
+        for (_, counts) in self
+            .chrom_counts
+            .iter()
+            .filter(|(chrom, _)| is_autosome(chrom))
+        {
+            for (sum_dp, sample_counts) in autosomal_dp.iter_mut().zip(counts.samples.iter()) {
+                sum_dp.0 += sample_counts.sum_dp.0;
+                sum_dp.1 += sample_counts.sum_dp.1;
+            }
+        }
+
+        let mut karyotype = Karyotype {
+            chromosomes: self.chrom_counts.keys().cloned().collect(),
+            count_records: self
+                .chrom_counts
+                .values()
+                .map(|counts| counts.count_records)
+                .collect(),
+            samples: Default::default(),
+        };
+        for (i, name) in self.samples.keys().enumerate() {
+            let autosomal_mean_dp = mean(autosomal_dp[i]).filter(|dp| *dp > 0.0);
+            let mut series = KaryotypeSeries::default();
+            for counts in self.chrom_counts.values() {
+                let sample_counts = &counts.samples[i];
+                let count_variants = sample_counts.count_het + sample_counts.count_hom_alt;
+                series.count_variants.push(count_variants);
+                series.het_rate.push(
+                    (count_variants > 0)
+                        .then(|| sample_counts.count_het as f64 / count_variants as f64),
+                );
+                series.dp_ratio.push(
+                    mean(sample_counts.sum_dp)
+                        .zip(autosomal_mean_dp)
+                        .map(|(dp, autosomal_dp)| dp / autosomal_dp),
+                );
+            }
+            karyotype.samples.insert(name.clone(), series);
+        }
+        self.karyotype = karyotype;
+
+        self
+    }
+}
+
+/// Compute the approximate QC metrics from the records sampled from `records`.
+///
+/// Sampling stops after `max_duration` so the result is available quickly even for
+/// large inputs.
+pub async fn compute(
+    mut records: RecordStream<'_>,
+    header: &vcf::Header,
+    sampling: Sampling,
+    max_duration: Duration,
+) -> Result<Metrics, anyhow::Error> {
+    let start = Instant::now();
+    let mut metrics = Metrics::new(header);
+    let mut contig = String::new();
+    let mut count_contig = 0;
+    while let Some(record) = records.try_next().await? {
+        if start.elapsed() > max_duration {
+            tracing::warn!(
+                "{}: stopping quick QC after {:?}",
+                crate::codes::INGEST_QUICK_QC_TIMED_OUT,
+                max_duration
+            );
+            metrics.timed_out = true;
+            break;
+        }
+        if alleles::is_reference_block(&record) {
+            // gVCF reference blocks are no calls of variants.
+            continue;
+        }
+
+        let is_sampled = match sampling {
+            Sampling::Every(n) => metrics.count_read % n.max(1) == 0,
+            Sampling::FirstPerContig(n) => {
+                let record_contig = record.chromosome().to_string();
+                if record_contig != contig {
+                    contig = record_contig;
+                    count_contig = 0;
+                }
+                count_contig += 1;
+                count_contig <= n
+            }
+        };
+        metrics.count_read += 1;
+        if is_sampled {
+            metrics.register(&record);
+        }
+    }
+
+    Ok(metrics.finalize())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use futures::TryStreamExt;
+
+    use super::Sampling;
+
+    #[rstest::rstest]
+    #[case("every_1", Sampling::Every(1))]
+    #[case("every_3", Sampling::Every(3))]
+    #[case("first_per_contig_2", Sampling::FirstPerContig(2))]
+    #[tokio::test]
+    async fn compute(#[case] label: &str, #[case] sampling: Sampling) -> Result<(), anyhow::Error> {
+        mehari::common::set_snapshot_suffix!("{}", label);
+
+        let mut reader =
+            mehari::common::noodles::open_vcf_reader("tests/seqvars/ingest/Case_1.vcf").await?;
+        let header = reader.read_header().await?;
+        let records: super::RecordStream = Box::pin(
+            reader
+                .records(&header)
+                .map_err(|e| anyhow::anyhow!("problem reading VCF: {}", e)),
+        );
+
+        let metrics = super::compute(records, &header, sampling, Duration::from_secs(60)).await?;
+
+        insta::assert_yaml_snapshot!(metrics);
+
+        Ok(())
+    }
+}
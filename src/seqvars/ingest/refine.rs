@@ -0,0 +1,247 @@
+//! Refinement of diploid genotypes from the genotype likelihoods and population priors.
+//!
+//! Similar to GATK `CalculateGenotypePosteriors`, the `FORMAT/PL` (or `FORMAT/GL`) values
+//! of the written allele are combined with Hardy-Weinberg priors from the gnomAD allele
+//! frequency.  The genotype with the highest posterior is written as `FORMAT/GT` and the
+//! posteriors are written as phred-scaled `FORMAT/PP`.  This mostly turns low-depth `0/1`
+//! calls at positions never seen in the population back into `0/0`, which improves the
+//! specificity of de novo calls in trios.
+
+use noodles_vcf as vcf;
+use vcf::record::genotypes::{keys::key, sample::value::Array, sample::Value};
+
+/// Minimal allele frequency used for the priors.
+///
+/// This is about the rate of de novo mutations per site and generation times a generous
+/// factor such that strong evidence in the likelihoods still wins for novel variants.
+pub const MIN_PRIOR_AF: f64 = 1e-4;
+
+/// Maximal phred-scaled value written to `FORMAT/PP`.
+const MAX_PHRED: f64 = 255.0;
+
+/// Add the `FORMAT/PP` definition to `output_header`.
+pub fn add_posterior_format(output_header: &mut vcf::Header) {
+    use vcf::header::record::value::{map::Format, Map};
+
+    output_header.formats_mut().insert(
+        key::ROUNDED_GENOTYPE_POSTERIOR_PROBABILITIES,
+        Map::<Format>::from(&key::ROUNDED_GENOTYPE_POSTERIOR_PROBABILITIES),
+    );
+}
+
+/// Return the alternate allele frequency from the gnomAD counts in the `INFO` fields of
+/// `record`, pooling exomes and genomes.
+///
+/// Returns `None` if there are no counts, e.g., for variants not in gnomAD.
+pub fn population_af(record: &vcf::Record) -> Option<f64> {
+    let count = |key: &str| -> i32 {
+        match record
+            .info()
+            .get(&key.parse().expect("invalid key in source code"))
+        {
+            Some(Some(vcf::record::info::field::Value::Integer(value))) => *value,
+            _ => 0,
+        }
+    };
+
+    let mut an = 0;
+    let mut ac = 0;
+    for prefix in ["gnomad_exomes", "gnomad_genomes"] {
+        an += count(&format!("{}_an", prefix));
+        ac += count(&format!("{}_het", prefix))
+            + 2 * count(&format!("{}_hom", prefix))
+            + count(&format!("{}_hemi", prefix));
+    }
+
+    (an > 0).then(|| ac as f64 / an as f64)
+}
+
+/// Return the posterior probabilities of `0/0`, `0/1`, and `1/1` from the phred-scaled
+/// likelihoods `pl` and the alternate allele frequency `af`.
+///
+/// Unknown frequencies are treated as `MIN_PRIOR_AF`.
+pub fn posteriors(pl: [f64; 3], af: Option<f64>) -> [f64; 3] {
+    let af = af
+        .unwrap_or(MIN_PRIOR_AF)
+        .clamp(MIN_PRIOR_AF, 1.0 - MIN_PRIOR_AF);
+    let priors = [(1.0 - af) * (1.0 - af), 2.0 * af * (1.0 - af), af * af];
+
+    // Normalize relative to the best likelihood for numeric stability.
+    let min_pl = pl.iter().copied().fold(f64::INFINITY, f64::min);
+    let mut result = [0.0; 3];
+    for ((p, pl), prior) in result.iter_mut().zip(pl).zip(priors) {
+        *p = 10f64.powf(-(pl - min_pl) / 10.0) * prior;
+    }
+    let sum: f64 = result.iter().sum();
+    result.iter_mut().for_each(|p| *p /= sum);
+    result
+}
+
+/// Return the phred-scaled `FORMAT/PP` values for the `posteriors`, normalized such that
+/// the best genotype has value `0`.
+fn phred_scaled(posteriors: [f64; 3]) -> Vec<Option<i32>> {
+    let max_p = posteriors.iter().copied().fold(0.0, f64::max);
+    posteriors
+        .iter()
+        .map(|p| {
+            let phred = -10.0 * (p / max_p).log10();
+            Some(phred.min(MAX_PHRED).round() as i32)
+        })
+        .collect()
+}
+
+/// Return the phred-scaled likelihoods of `0/0`, `0/1`, and `1/1` for allele `allele_no`
+/// of `allele_count` alleles (including the reference) from `FORMAT/PL` or `FORMAT/GL`
+/// of `sample`.
+///
+/// Returns `None` for missing values and for non-diploid samples.
+fn sample_pl(
+    sample: &vcf::record::genotypes::Sample<'_>,
+    allele_no: usize,
+    allele_count: usize,
+) -> Option<[f64; 3]> {
+    let values: Vec<f64> = if let Some(Some(Value::Array(Array::Integer(pl)))) =
+        sample.get(&key::ROUNDED_GENOTYPE_LIKELIHOODS)
+    {
+        pl.iter()
+            .map(|value| value.map(f64::from))
+            .collect::<Option<_>>()?
+    } else if let Some(Some(Value::Array(Array::Float(gl)))) =
+        sample.get(&key::GENOTYPE_LIKELIHOODS)
+    {
+        gl.iter()
+            .map(|value| value.map(|gl| -10.0 * f64::from(gl)))
+            .collect::<Option<_>>()?
+    } else {
+        return None;
+    };
+
+    // Haploid samples have one likelihood per allele.
+    if values.len() == allele_count {
+        return None;
+    }
+    let indices = super::allele_indices(vcf::header::Number::G, allele_no, allele_count, 0)?;
+    let selected = super::select_indices(&values, &indices)?;
+    Some([selected[0], selected[1], selected[2]])
+}
+
+/// Return the refined unphased diploid genotype for `gt` given the `posteriors`.
+///
+/// Phased, non-diploid, and (partially) missing genotypes are kept as they are.
+fn refine_gt(gt: &str, posteriors: [f64; 3]) -> String {
+    let alleles = gt.split('/').collect::<Vec<_>>();
+    if alleles.len() != 2 || alleles.contains(&".") {
+        return gt.to_string();
+    }
+    let best = (0..3)
+        .max_by(|a, b| posteriors[*a].total_cmp(&posteriors[*b]))
+        .expect("posteriors are not empty");
+    ["0/0", "0/1", "1/1"][best].to_string()
+}
+
+/// Refine the genotypes of `output_record` for allele `allele_no` of `input_record` and
+/// write the posteriors to `FORMAT/PP`.
+///
+/// Must be called after `output_record` has been annotated with the gnomAD frequencies.
+/// Samples without likelihoods keep their genotype and get a missing `FORMAT/PP`.
+pub fn refine_genotypes(
+    input_record: &vcf::Record,
+    allele_no: usize,
+    idx_output_to_input: &[usize],
+    output_record: &mut vcf::Record,
+) {
+    let af = population_af(output_record);
+    let allele_count = input_record.alternate_bases().len() + 1;
+
+    let mut keys = output_record
+        .genotypes()
+        .keys()
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    keys.push(key::ROUNDED_GENOTYPE_POSTERIOR_PROBABILITIES);
+    let idx_gt = keys.iter().position(|k| *k == key::GENOTYPE);
+
+    let values = output_record
+        .genotypes()
+        .values()
+        .zip(idx_output_to_input.iter().copied())
+        .map(|(output_sample, input_idx)| {
+            let mut values = output_sample.values().to_vec();
+            let posteriors = input_record
+                .genotypes()
+                .get_index(input_idx)
+                .and_then(|input_sample| sample_pl(&input_sample, allele_no, allele_count))
+                .map(|pl| posteriors(pl, af));
+            if let (Some(posteriors), Some(idx_gt)) = (posteriors, idx_gt) {
+                if let Some(Some(Value::String(gt))) = values.get_mut(idx_gt) {
+                    *gt = refine_gt(gt, posteriors);
+                }
+            }
+            values.push(
+                posteriors.map(|posteriors| Value::Array(Array::Integer(phred_scaled(posteriors)))),
+            );
+            values
+        })
+        .collect::<Vec<_>>();
+
+    *output_record.genotypes_mut() = vcf::record::Genotypes::new(
+        vcf::record::genotypes::Keys::try_from(keys).expect("invalid keys"),
+        values,
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::novel_weak_het([20.0, 0.0, 60.0], None, "0/0")]
+    #[case::novel_strong_het([200.0, 0.0, 300.0], None, "0/1")]
+    #[case::common_weak_het([20.0, 0.0, 60.0], Some(0.3), "0/1")]
+    #[case::common_hom([60.0, 10.0, 0.0], Some(0.5), "1/1")]
+    fn refine_gt(#[case] pl: [f64; 3], #[case] af: Option<f64>, #[case] expected: &str) {
+        let posteriors = super::posteriors(pl, af);
+        assert!((posteriors.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert_eq!(super::refine_gt("0/1", posteriors), expected);
+    }
+
+    #[rstest]
+    #[case("0|1")]
+    #[case("./1")]
+    #[case("1")]
+    fn refine_gt_kept(#[case] gt: &str) {
+        assert_eq!(super::refine_gt(gt, [1.0, 0.0, 0.0]), gt);
+    }
+
+    #[test]
+    fn phred_scaled() {
+        assert_eq!(
+            super::phred_scaled([0.9, 0.09, 0.01]),
+            vec![Some(0), Some(10), Some(20)]
+        );
+    }
+
+    #[test]
+    fn population_af() -> Result<(), anyhow::Error> {
+        use noodles_vcf::record::info::field::Value;
+
+        let mut record = noodles_vcf::Record::default();
+        assert_eq!(super::population_af(&record), None);
+
+        for (key, value) in [
+            ("gnomad_exomes_an", 60),
+            ("gnomad_exomes_het", 4),
+            ("gnomad_exomes_hom", 1),
+            ("gnomad_genomes_an", 40),
+            ("gnomad_genomes_het", 2),
+        ] {
+            record
+                .info_mut()
+                .insert(key.parse()?, Some(Value::Integer(value)));
+        }
+        assert_eq!(super::population_af(&record), Some(0.08));
+
+        Ok(())
+    }
+}
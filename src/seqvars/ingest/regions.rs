@@ -0,0 +1,453 @@
+//! Restriction of `seqvars ingest` to genomic regions.
+
+use std::{path::Path, pin::Pin};
+
+use annonars::common::cli::canonicalize;
+use futures::{Stream, TryStreamExt};
+use mehari::common::io::std::open_read_maybe_gz;
+use noodles_bgzf as bgzf;
+use noodles_core::Position;
+use noodles_csi::{
+    self as csi, binning_index::index::reference_sequence::bin::Chunk, BinningIndex,
+};
+use noodles_tabix as tabix;
+use noodles_vcf as vcf;
+
+/// Boxed stream of input records.
+pub type RecordStream<'a> = Pin<Box<dyn Stream<Item = Result<vcf::Record, anyhow::Error>> + 'a>>;
+
+/// A genomic region with 1-based, inclusive coordinates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    /// Chromosome name.
+    pub chrom: String,
+    /// Range of the region, `None` for the whole chromosome.
+    pub range: Option<(i32, i32)>,
+}
+
+impl std::str::FromStr for Region {
+    type Err = anyhow::Error;
+
+    /// Parse from `CHROM` or `CHROM:START-END`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (chrom, range) = if let Some((chrom, range)) = s.rsplit_once(':') {
+            let (start, end) = range
+                .split_once('-')
+                .ok_or_else(|| anyhow::anyhow!("invalid region range in {:?}", s))?;
+            let start: i32 = start
+                .replace(',', "")
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid region start in {:?}: {}", s, e))?;
+            let end: i32 = end
+                .replace(',', "")
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid region end in {:?}: {}", s, e))?;
+            if start < 1 || end < start {
+                anyhow::bail!("invalid region coordinates in {:?}", s);
+            }
+            (chrom, Some((start, end)))
+        } else {
+            (s, None)
+        };
+        if chrom.is_empty() {
+            anyhow::bail!("empty chromosome name in region {:?}", s);
+        }
+
+        Ok(Self {
+            chrom: chrom.to_string(),
+            range,
+        })
+    }
+}
+
+/// Load regions from the BED file at `path`.
+///
+/// Only the first three columns are used, `track` and `browser` lines are skipped.
+pub fn load_bed(path: &Path) -> Result<Vec<Region>, anyhow::Error> {
+    tracing::debug!("loading regions from {:?}...", path);
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false) // BED has no header
+        .comment(Some(b'#'))
+        .delimiter(b'\t')
+        .flexible(true)
+        .from_reader(open_read_maybe_gz(path.to_str().unwrap())?);
+    let mut result = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let chrom = &record[0];
+        if chrom.starts_with("track") || chrom.starts_with("browser") {
+            continue;
+        }
+        if record.len() < 3 {
+            anyhow::bail!("BED record with less than three columns: {:?}", &record);
+        }
+        let begin: i32 = record[1]
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid BED begin in {:?}: {}", &record, e))?;
+        let end: i32 = record[2]
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid BED end in {:?}: {}", &record, e))?;
+        if begin < 0 || end <= begin {
+            anyhow::bail!("invalid BED coordinates in {:?}", &record);
+        }
+        result.push(Region {
+            chrom: chrom.to_string(),
+            range: Some((begin + 1, end)),
+        });
+    }
+    tracing::debug!("... done loading {} regions", result.len());
+
+    Ok(result)
+}
+
+/// Set of regions to restrict the ingestion to.
+///
+/// The regions are merged per chromosome so that each position is covered at most once.
+#[derive(Debug, Clone, Default)]
+pub struct Regions {
+    /// Sorted and disjoint 1-based, inclusive intervals by canonical chromosome name.
+    by_chrom: indexmap::IndexMap<String, Vec<(i32, i32)>>,
+}
+
+impl Regions {
+    /// Construct from the given regions.
+    pub fn new(regions: &[Region]) -> Self {
+        let mut by_chrom = indexmap::IndexMap::<String, Vec<(i32, i32)>>::new();
+        for region in regions {
+            by_chrom
+                .entry(canonicalize(&region.chrom))
+                .or_default()
+                .push(region.range.unwrap_or((1, i32::MAX)));
+        }
+        for intervals in by_chrom.values_mut() {
+            intervals.sort();
+            let mut merged: Vec<(i32, i32)> = Vec::with_capacity(intervals.len());
+            for &(start, end) in intervals.iter() {
+                match merged.last_mut() {
+                    Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+                    _ => merged.push((start, end)),
+                }
+            }
+            *intervals = merged;
+        }
+
+        Self { by_chrom }
+    }
+
+    /// Construct from the command line arguments, `None` if no regions were given.
+    pub fn from_args(
+        regions: &[String],
+        path_regions_bed: Option<&str>,
+    ) -> Result<Option<Self>, anyhow::Error> {
+        if regions.is_empty() && path_regions_bed.is_none() {
+            return Ok(None);
+        }
+
+        let mut result = regions
+            .iter()
+            .map(|region| region.parse())
+            .collect::<Result<Vec<Region>, _>>()?;
+        if let Some(path_regions_bed) = path_regions_bed {
+            result.extend(load_bed(Path::new(path_regions_bed))?);
+        }
+
+        Ok(Some(Self::new(&result)))
+    }
+
+    /// Return whether the 1-based, inclusive interval `start..=end` on `chrom` overlaps with
+    /// any of the regions.
+    pub fn overlaps(&self, chrom: &str, start: i32, end: i32) -> bool {
+        if let Some(intervals) = self.by_chrom.get(&canonicalize(chrom)) {
+            // The intervals are disjoint, so their ends are sorted as well.
+            let idx = intervals.partition_point(|&(_, interval_end)| interval_end < start);
+            intervals
+                .get(idx)
+                .map(|&(interval_start, _)| interval_start <= end)
+                .unwrap_or(false)
+        } else {
+            false
+        }
+    }
+
+    /// Return whether the reference allele of `record` overlaps with any of the regions.
+    pub fn overlaps_record(&self, record: &vcf::Record) -> bool {
+        let start = usize::from(record.position()) as i32;
+        let end = start + record.reference_bases().len() as i32 - 1;
+        self.overlaps(&record.chromosome().to_string(), start, end.max(start))
+    }
+
+    /// Return the merged chunks of `index` covering the regions.
+    pub fn chunks<I>(&self, index: &I) -> Result<Vec<Chunk>, anyhow::Error>
+    where
+        I: BinningIndex,
+    {
+        let reference_sequence_names = index
+            .header()
+            .ok_or_else(|| anyhow::anyhow!("index has no header"))?
+            .reference_sequence_names();
+
+        let mut chunks = Vec::new();
+        for (reference_sequence_id, name) in reference_sequence_names.iter().enumerate() {
+            if let Some(intervals) = self.by_chrom.get(&canonicalize(name)) {
+                for &(start, end) in intervals {
+                    let start = Position::try_from(start as usize)?;
+                    let interval = if end == i32::MAX {
+                        (start..).into()
+                    } else {
+                        (start..=Position::try_from(end as usize)?).into()
+                    };
+                    chunks.extend(index.query(reference_sequence_id, interval)?);
+                }
+            }
+        }
+
+        Ok(csi::binning_index::merge_chunks(&chunks))
+    }
+}
+
+/// Return the path to the tabix or CSI index of `path_in`, if any.
+pub fn find_index(path_in: &str) -> Option<String> {
+    if !path_in.ends_with(".gz") && !path_in.ends_with(".bgz") {
+        return None;
+    }
+    [format!("{}.tbi", path_in), format!("{}.csi", path_in)]
+        .into_iter()
+        .find(|path| Path::new(path).exists())
+}
+
+/// State for reading records from the index chunks.
+enum State {
+    /// Seek to the start of the next chunk.
+    Seek,
+    /// Read records until the given chunk end.
+    Read(bgzf::VirtualPosition),
+    /// All chunks have been read.
+    Done,
+}
+
+/// Context for reading records from the index chunks.
+struct Context {
+    /// The VCF reader on the bgzip-compressed file.
+    reader: vcf::AsyncReader<bgzf::AsyncReader<tokio::fs::File>>,
+    /// The header to use for parsing the records.
+    header: vcf::Header,
+    /// The remaining chunks.
+    chunks: std::vec::IntoIter<Chunk>,
+    /// The regions to filter to.
+    regions: Regions,
+    /// The current state.
+    state: State,
+}
+
+/// Open the indexed file at `path_in` and return the records overlapping with `regions`.
+///
+/// The records are read from the chunks of the index at `path_index`, so the file does
+/// not need to be scanned completely.  The chunks are merged such that each record is
+/// yielded at most once and in the order of the file.
+pub async fn query_records(
+    path_in: &str,
+    path_index: &str,
+    header: vcf::Header,
+    regions: Regions,
+) -> Result<RecordStream<'static>, anyhow::Error> {
+    tracing::info!("using index {} for region-restricted reading", path_index);
+    let chunks = if path_index.ends_with(".csi") {
+        let index = csi::r#async::read(path_index)
+            .await
+            .map_err(|e| anyhow::anyhow!("could not read CSI index {}: {}", path_index, e))?;
+        regions.chunks(&index)?
+    } else {
+        let index = tabix::r#async::read(path_index)
+            .await
+            .map_err(|e| anyhow::anyhow!("could not read tabix index {}: {}", path_index, e))?;
+        regions.chunks(&index)?
+    };
+
+    let reader = vcf::AsyncReader::new(bgzf::AsyncReader::new(
+        tokio::fs::File::open(path_in)
+            .await
+            .map_err(|e| anyhow::anyhow!("could not open {}: {}", path_in, e))?,
+    ));
+    let ctx = Context {
+        reader,
+        header,
+        chunks: chunks.into_iter(),
+        regions,
+        state: State::Seek,
+    };
+
+    Ok(Box::pin(futures::stream::try_unfold(
+        ctx,
+        |mut ctx| async move {
+            loop {
+                match ctx.state {
+                    State::Seek => {
+                        ctx.state = match ctx.chunks.next() {
+                            Some(chunk) => {
+                                ctx.reader.seek(chunk.start()).await?;
+                                State::Read(chunk.end())
+                            }
+                            None => State::Done,
+                        };
+                    }
+                    State::Read(chunk_end) => {
+                        let mut record = vcf::Record::default();
+                        if ctx.reader.read_record(&ctx.header, &mut record).await? == 0 {
+                            ctx.state = State::Seek;
+                            continue;
+                        }
+                        if ctx.reader.virtual_position() >= chunk_end {
+                            ctx.state = State::Seek;
+                        }
+                        if ctx.regions.overlaps_record(&record) {
+                            return Ok(Some((record, ctx)));
+                        }
+                    }
+                    State::Done => return Ok(None),
+                }
+            }
+        },
+    )))
+}
+
+/// Filter the records of `records` to those overlapping with `regions`.
+///
+/// This is used for input files without index and scans through all records.
+pub fn filter_records<'a>(records: RecordStream<'a>, regions: Regions) -> RecordStream<'a> {
+    Box::pin(
+        records.try_filter(move |record| futures::future::ready(regions.overlaps_record(record))),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use futures::TryStreamExt;
+    use mehari::common::noodles::open_vcf_reader;
+
+    use super::{Region, Regions};
+
+    #[rstest::rstest]
+    #[case("chr1:100-200", "chr1", Some((100, 200)))]
+    #[case("chr1:1,000-2,000", "chr1", Some((1000, 2000)))]
+    #[case("chr2", "chr2", None)]
+    fn region_from_str(
+        #[case] s: &str,
+        #[case] chrom: &str,
+        #[case] range: Option<(i32, i32)>,
+    ) -> Result<(), anyhow::Error> {
+        let region: Region = s.parse()?;
+        assert_eq!(region.chrom, chrom);
+        assert_eq!(region.range, range);
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case("chr1:200-100")]
+    #[case("chr1:0-100")]
+    #[case(":1-2")]
+    fn region_from_str_invalid(#[case] s: &str) {
+        assert!(s.parse::<Region>().is_err());
+    }
+
+    #[rstest::rstest]
+    #[case("1", 100, 100, true)]
+    #[case("chr1", 150, 160, true)]
+    #[case("1", 201, 249, false)]
+    #[case("1", 90, 99, false)]
+    #[case("1", 90, 250, true)]
+    #[case("1", 301, 400, true)]
+    #[case("2", 1_000_000, 1_000_000, true)]
+    #[case("3", 100, 100, false)]
+    fn regions_overlaps(
+        #[case] chrom: &str,
+        #[case] start: i32,
+        #[case] end: i32,
+        #[case] expected: bool,
+    ) -> Result<(), anyhow::Error> {
+        let regions = Regions::new(&[
+            "chr1:100-200".parse()?,
+            "1:250-300".parse()?,
+            "1:150-180".parse()?,
+            "1:301-400".parse()?,
+            "chr2".parse()?,
+        ]);
+        assert_eq!(regions.overlaps(chrom, start, end), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn regions_merged() -> Result<(), anyhow::Error> {
+        let regions = Regions::new(&[
+            "chr1:100-200".parse()?,
+            "1:150-300".parse()?,
+            "1:301-400".parse()?,
+            "1:500-600".parse()?,
+        ]);
+        assert_eq!(regions.by_chrom["1"], vec![(100, 400), (500, 600)]);
+        Ok(())
+    }
+
+    #[test]
+    fn load_bed() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("regions.bed");
+        std::fs::write(
+            &path,
+            "track name=targets\n# comment\n17\t41196311\t41277500\tBRCA1\nMT\t0\t100\n",
+        )?;
+
+        let regions = super::load_bed(&path)?;
+        assert_eq!(
+            regions,
+            vec![
+                Region {
+                    chrom: "17".into(),
+                    range: Some((41196312, 41277500)),
+                },
+                Region {
+                    chrom: "MT".into(),
+                    range: Some((1, 100)),
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    /// Query via the index must yield the same records as filtering a full scan.
+    #[rstest::rstest]
+    #[case(&["17:41244000-41247000"])]
+    #[case(&["MT"])]
+    #[case(&["17:41244000-41245000", "chrM:1-1000", "17:41244500-41247000"])]
+    #[case(&["1"])]
+    #[tokio::test]
+    async fn query_equals_filter(#[case] regions: &[&str]) -> Result<(), anyhow::Error> {
+        let path_in = "tests/seqvars/ingest/NA12878_dragen.vcf.gz";
+        let regions = Regions::new(
+            &regions
+                .iter()
+                .map(|region| region.parse())
+                .collect::<Result<Vec<Region>, _>>()?,
+        );
+
+        let mut reader = open_vcf_reader(path_in).await?;
+        let header = reader.read_header().await?;
+        let records: super::RecordStream = Box::pin(
+            reader
+                .records(&header)
+                .map_err(|e| anyhow::anyhow!("problem reading VCF: {}", e)),
+        );
+        let expected = super::filter_records(records, regions.clone())
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let path_index = super::find_index(path_in).expect("index must exist");
+        let actual = super::query_records(path_in, &path_index, header.clone(), regions)
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        assert_eq!(actual, expected);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+//! Resuming `seqvars ingest` from a partially written output file.
+
+use std::pin::Pin;
+
+use async_compression::tokio::bufread::ZstdDecoder;
+use futures::TryStreamExt;
+use mehari::common::noodles::AsyncVcfWriter;
+use noodles_bgzf as bgzf;
+use noodles_vcf as vcf;
+use tokio::io::AsyncBufRead;
+
+use super::{regions::RecordStream, stats::Stats};
+use crate::common::io::tokio::OutputCompression;
+
+/// Position of the first input record to process when resuming.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumePoint {
+    /// Chromosome name as in the input file.
+    pub chrom: String,
+    /// 1-based position.
+    pub pos: usize,
+}
+
+/// Open the (partial) output file at `path` with the given `compression`.
+///
+/// The compression cannot be guessed from the `.partial` suffix.
+pub(crate) async fn open_output(
+    path: &str,
+    compression: OutputCompression,
+) -> Result<vcf::AsyncReader<Pin<Box<dyn AsyncBufRead>>>, anyhow::Error> {
+    let file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| anyhow::anyhow!("could not open partial output {}: {}", path, e))?;
+    let inner: Pin<Box<dyn AsyncBufRead>> = match compression {
+        OutputCompression::Plain => Box::pin(tokio::io::BufReader::new(file)),
+        OutputCompression::Bgzf => Box::pin(bgzf::AsyncReader::new(file)),
+        OutputCompression::Zstd => Box::pin(tokio::io::BufReader::new(ZstdDecoder::new(
+            tokio::io::BufReader::new(file),
+        ))),
+    };
+    Ok(vcf::AsyncReader::new(inner))
+}
+
+/// Return the VCF text of `header`.
+fn header_text(header: &vcf::Header) -> Result<String, anyhow::Error> {
+    let mut writer = vcf::Writer::new(Vec::new());
+    writer.write_header(header)?;
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+/// Copy the complete records from the partial output at `path` to `writer`.
+///
+/// Nothing is copied if the header of the partial output differs from `output_header`,
+/// e.g., after changing the arguments or the worker version, as the records would not
+/// match the header.  Reading stops at the first error, e.g., in a truncated bgzf block or
+/// zstd frame.  The records at the last position read are dropped as they may be
+/// incomplete.  The copied records are registered in `stats`.  Returns the number of
+/// copied records and the point to resume from, `None` if no record was read.
+pub async fn copy_partial(
+    path: &str,
+    compression: OutputCompression,
+    output_header: &vcf::Header,
+    writer: &mut AsyncVcfWriter,
+    stats: &mut Stats,
+) -> Result<(usize, Option<ResumePoint>), anyhow::Error> {
+    let mut reader = open_output(path, compression).await?;
+    let header = reader
+        .read_header()
+        .await
+        .map_err(|e| anyhow::anyhow!("could not read header of partial output {}: {}", path, e))?;
+    if header_text(&header)? != header_text(output_header)? {
+        tracing::warn!(
+            "{}: header of partial output {} differs from output header, not resuming",
+            crate::codes::INGEST_PARTIAL_OUTPUT_HEADER_MISMATCH,
+            path
+        );
+        return Ok((0, None));
+    }
+
+    let mut count = 0;
+    let mut group: Vec<vcf::Record> = Vec::new();
+    loop {
+        let mut record = vcf::Record::default();
+        match reader.read_record(&header, &mut record).await {
+            Ok(0) => break,
+            Ok(_) => (),
+            Err(e) => {
+                tracing::warn!(
+                    "{}: stopping to read partial output {}: {}",
+                    crate::codes::INGEST_PARTIAL_OUTPUT_TRUNCATED,
+                    path,
+                    e
+                );
+                break;
+            }
+        }
+
+        let same_pos = group.first().map_or(true, |first| {
+            first.chromosome() == record.chromosome() && first.position() == record.position()
+        });
+        if !same_pos {
+            for record in group.drain(..) {
+                writer.write_record(&record).await?;
+                stats.register(&record)?;
+                count += 1;
+            }
+        }
+        group.push(record);
+    }
+
+    Ok((
+        count,
+        group.first().map(|record| ResumePoint {
+            chrom: record.chromosome().to_string(),
+            pos: usize::from(record.position()),
+        }),
+    ))
+}
+
+/// Skip the records from `records` before `resume_point`.
+pub fn skip_to(records: RecordStream<'_>, resume_point: ResumePoint) -> RecordStream<'_> {
+    Box::pin(records.try_skip_while(move |record| {
+        futures::future::ready(Ok(!(record.chromosome().to_string() == resume_point.chrom
+            && usize::from(record.position()) >= resume_point.pos)))
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use futures::TryStreamExt;
+    use tokio::io::AsyncWriteExt;
+
+    use crate::common::io::tokio::OutputCompression;
+
+    /// Write the first `count` records of `path_in` to `path_out`, truncating the last
+    /// record line.
+    fn write_truncated(path_in: &str, path_out: &str, count: usize) -> Result<(), anyhow::Error> {
+        let input = std::fs::read_to_string(path_in)?;
+        let header = input.lines().take_while(|line| line.starts_with('#'));
+        let records = input.lines().filter(|line| !line.starts_with('#'));
+        let mut output = header
+            .chain(records.take(count))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if count > 0 {
+            output.truncate(output.len() - 10);
+        }
+        std::fs::write(path_out, output)?;
+        Ok(())
+    }
+
+    /// Return the header of `path`.
+    async fn read_header(path: &str) -> Result<noodles_vcf::Header, anyhow::Error> {
+        Ok(mehari::common::noodles::open_vcf_reader(path)
+            .await?
+            .read_header()
+            .await?)
+    }
+
+    #[rstest::rstest]
+    #[case::no_records(0, 0, None)]
+    #[case::one_record(1, 0, Some(41_249_263))]
+    #[case::three_records(3, 2, Some(41_252_691))]
+    #[tokio::test]
+    async fn copy_partial(
+        #[case] count: usize,
+        #[case] expected_count: usize,
+        #[case] expected_pos: Option<usize>,
+    ) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_partial = tmpdir.join("out.vcf.partial").to_str().unwrap().to_string();
+        let path_out = tmpdir.join("out.vcf").to_str().unwrap().to_string();
+        write_truncated("tests/seqvars/add_sample/case.vcf", &path_partial, count)?;
+
+        let header = read_header("tests/seqvars/add_sample/case.vcf").await?;
+        let mut writer = mehari::common::noodles::open_vcf_writer(&path_out).await?;
+        writer.write_header(&header).await?;
+        let (actual_count, resume_point) = super::copy_partial(
+            &path_partial,
+            OutputCompression::Plain,
+            &header,
+            &mut writer,
+            &mut Default::default(),
+        )
+        .await?;
+        crate::flush_and_shutdown!(writer);
+
+        assert_eq!(actual_count, expected_count);
+        assert_eq!(
+            resume_point,
+            expected_pos.map(|pos| super::ResumePoint {
+                chrom: "17".into(),
+                pos
+            })
+        );
+        assert_eq!(
+            std::fs::read_to_string(&path_out)?
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .count(),
+            expected_count
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn copy_partial_header_mismatch() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_partial = tmpdir.join("out.vcf.partial").to_str().unwrap().to_string();
+        let path_out = tmpdir.join("out.vcf").to_str().unwrap().to_string();
+        write_truncated("tests/seqvars/add_sample/case.vcf", &path_partial, 3)?;
+
+        let header = read_header("tests/seqvars/add_sample/sibling.vcf").await?;
+        let mut writer = mehari::common::noodles::open_vcf_writer(&path_out).await?;
+        writer.write_header(&header).await?;
+        let result = super::copy_partial(
+            &path_partial,
+            OutputCompression::Plain,
+            &header,
+            &mut writer,
+            &mut Default::default(),
+        )
+        .await?;
+        crate::flush_and_shutdown!(writer);
+
+        assert_eq!(result, (0, None));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn skip_to() -> Result<(), anyhow::Error> {
+        let mut reader =
+            mehari::common::noodles::open_vcf_reader("tests/seqvars/add_sample/case.vcf").await?;
+        let header = reader.read_header().await?;
+        let records: super::RecordStream = Box::pin(
+            reader
+                .records(&header)
+                .map_err(|e| anyhow::anyhow!("problem reading VCF: {}", e)),
+        );
+
+        let records = super::skip_to(
+            records,
+            super::ResumePoint {
+                chrom: "17".into(),
+                pos: 41_252_692,
+            },
+        )
+        .try_collect::<Vec<_>>()
+        .await?;
+
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| usize::from(record.position()))
+                .collect::<Vec<_>>(),
+            vec![41_252_693, 41_252_695]
+        );
+
+        Ok(())
+    }
+}
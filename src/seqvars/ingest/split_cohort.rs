@@ -0,0 +1,400 @@
+//! Implementation of `seqvars split-cohort` subcommand.
+//!
+//! A joint-called VCF file of many families is ingested into one output file per case in
+//! a single pass.  Each input record is split into alleles and annotated once for the
+//! samples of all cases; the annotated records are then restricted to the samples of
+//! each case.  Records in which no individual of a case carries the allele are not
+//! written for that case.
+
+use futures::TryStreamExt as _;
+use noodles_vcf as vcf;
+use thousands::Separable as _;
+use tokio::io::AsyncWriteExt as _;
+
+use crate::{
+    common::{
+        self,
+        noodles::{open_vcf_reader, open_vcf_writer, WriteIndex},
+        worker_version, GenomeRelease,
+    },
+    flush_and_shutdown,
+};
+
+use super::{alleles, family, header, stats, KeptInfoKeys, KnownFormatKeys, RecordAnnotator};
+
+/// Command line arguments for `seqvars split-cohort` subcommand.
+#[derive(Debug, Clone, clap::Parser)]
+#[command(author, version, about = "split joint cohort VCF into per-case ingested VCFs", long_about = None)]
+pub struct Args {
+    /// Value to write to `##fileDate`.
+    #[arg(long)]
+    pub file_date: String,
+    /// The assumed genome build.
+    #[clap(long)]
+    pub genomebuild: GenomeRelease,
+
+    /// The path to the mehari database.
+    #[clap(long)]
+    pub path_mehari_db: String,
+    /// Path to the pedigree file with the individuals of all families.
+    #[clap(long)]
+    pub path_ped: String,
+    /// Path to the headerless TSV file with the columns family name from the pedigree,
+    /// case UUID, and path to the output file; one line per case.
+    #[clap(long)]
+    pub path_families: String,
+    /// How to handle individuals of a family that are missing from the input file.
+    #[clap(long, value_enum, default_value_t = header::SampleMismatchPolicy::default())]
+    pub sample_mismatch_policy: header::SampleMismatchPolicy,
+    /// Path to the joint-called input file.
+    #[clap(long)]
+    pub path_in: String,
+
+    /// Walk the frequency and ClinVar databases with iterators in lock-step with the
+    /// coordinate-sorted input instead of one point lookup per record.
+    #[clap(long)]
+    pub rocksdb_prefetch: bool,
+    /// Number of transcript bins to cache for skipping the consequence prediction far from
+    /// any transcript; `0` disables the cache.
+    #[clap(long, default_value = "10000")]
+    pub csq_cache_size: usize,
+    /// The index to write for bgzip-compressed output.
+    #[clap(long, value_enum, default_value_t = WriteIndex::default())]
+    pub write_index: WriteIndex,
+}
+
+/// One line of the `--path-families` file.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct FamilyMapping {
+    /// Name of the family in the pedigree file.
+    pub family: String,
+    /// The case UUID to write out.
+    pub case_uuid: uuid::Uuid,
+    /// Path to the output file of the case.
+    pub path_out: String,
+}
+
+/// Load the family mappings from the TSV file at `path`.
+pub fn load_families(path: &str) -> Result<Vec<FamilyMapping>, anyhow::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b'\t')
+        .comment(Some(b'#'))
+        .from_path(path)
+        .map_err(|e| anyhow::anyhow!("could not open families file {}: {}", path, e))?;
+    let families = reader
+        .deserialize()
+        .collect::<Result<Vec<FamilyMapping>, _>>()
+        .map_err(|e| anyhow::anyhow!("could not parse families file {}: {}", path, e))?;
+    let mut seen = std::collections::HashSet::new();
+    for mapping in &families {
+        if !seen.insert(&mapping.family) {
+            anyhow::bail!("duplicate family {} in {}", &mapping.family, path);
+        }
+    }
+    Ok(families)
+}
+
+/// Restrict `pedigree` to the individuals of `family`.
+fn family_pedigree(
+    pedigree: &mehari::ped::PedigreeByName,
+    family: &str,
+) -> Result<mehari::ped::PedigreeByName, anyhow::Error> {
+    let mut result = pedigree.clone();
+    result
+        .individuals
+        .retain(|_, individual| individual.family == family);
+    if result.individuals.is_empty() {
+        anyhow::bail!("family {} not found in pedigree", family);
+    }
+    Ok(result)
+}
+
+/// Return whether the `GT` of any sample in `genotypes` carries the alternate allele.
+fn carries_allele(
+    genotypes: &[Vec<Option<vcf::record::genotypes::sample::Value>>],
+    idx_gt: usize,
+) -> bool {
+    genotypes.iter().any(|values| {
+        matches!(
+            values.get(idx_gt),
+            Some(Some(vcf::record::genotypes::sample::Value::String(gt)))
+                if gt.split(['/', '|']).any(|allele| allele == "1")
+        )
+    })
+}
+
+/// One case written by `seqvars split-cohort`.
+struct Case {
+    /// The family structure of the case.
+    family: family::Family,
+    /// The output file helper.
+    out_path_helper: common::s3::OutputPathHelper,
+    /// The output header.
+    output_header: vcf::Header,
+    /// The indices of the samples of the case in the annotated cohort records.
+    samples: std::ops::Range<usize>,
+    /// The statistics of the written records.
+    stats: stats::Stats,
+}
+
+/// Main entry point for `seqvars split-cohort` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    let before_anything = std::time::Instant::now();
+    tracing::info!("args_common = {:#?}", &args_common);
+    tracing::info!("args = {:#?}", &args);
+
+    tracing::info!("loading pedigree and families...");
+    let pedigree = mehari::ped::PedigreeByName::from_path(&args.path_ped)
+        .map_err(|e| anyhow::anyhow!("problem parsing PED file: {}", e))?;
+    let families = load_families(&args.path_families)?;
+    tracing::info!("... done loading {} families", families.len());
+
+    tracing::info!("opening input file...");
+    let mut input_reader = open_vcf_reader(&args.path_in)
+        .await
+        .map_err(|e| anyhow::anyhow!("could not build VCF reader for {}: {}", &args.path_in, e))?;
+    let mut input_header = input_reader
+        .read_header()
+        .await
+        .map_err(|e| anyhow::anyhow!("problem reading VCF header of {}: {}", &args.path_in, e))?;
+    // Work around glnexus issue with RNC.
+    if let Some(format) = input_header.formats_mut().get_mut("RNC") {
+        *format.number_mut() = vcf::header::Number::Count(1);
+        *format.type_mut() = vcf::header::record::value::map::format::Type::String;
+    }
+
+    tracing::info!("processing headers...");
+    let mut cases = Vec::new();
+    let mut idx_output_to_input = Vec::new();
+    for mapping in &families {
+        // Reconcile against the input samples of the family only.
+        let family_pedigree = family_pedigree(&pedigree, &mapping.family)?;
+        let mut family_input_header = input_header.clone();
+        family_input_header
+            .sample_names_mut()
+            .retain(|name| family_pedigree.individuals.contains_key(name));
+        let family_pedigree = header::reconcile_pedigree(
+            family_pedigree,
+            &family_input_header,
+            args.sample_mismatch_policy,
+        )?;
+        let family = family::Family::new(
+            mapping.case_uuid,
+            &family_pedigree,
+            &[],
+            &[args.path_in.clone()],
+            &[family_input_header.clone()],
+        )?;
+        let output_header = header::build_output_header(
+            &family_input_header,
+            &Some(family_pedigree),
+            args.genomebuild,
+            &args.file_date,
+            Some(&mapping.case_uuid),
+            worker_version(),
+        )
+        .map_err(|e| anyhow::anyhow!("problem building output header: {}", e))?;
+
+        let start = idx_output_to_input.len();
+        idx_output_to_input.extend(super::build_idx_output_to_input(
+            &output_header,
+            &input_header,
+        ));
+        cases.push(Case {
+            family,
+            out_path_helper: common::s3::OutputPathHelper::new(&mapping.path_out)?,
+            output_header,
+            samples: start..idx_output_to_input.len(),
+            stats: Default::default(),
+        });
+    }
+
+    let mut output_writers = Vec::new();
+    for case in &cases {
+        let mut output_writer = open_vcf_writer(
+            case.out_path_helper.path_out(),
+            args_common.compression_level,
+        )
+        .await?;
+        output_writer
+            .write_header(&case.output_header)
+            .await
+            .map_err(|e| anyhow::anyhow!("problem writing header: {}", e))?;
+        output_writers.push(output_writer);
+    }
+
+    tracing::info!("splitting and annotating records...");
+    let start = std::time::Instant::now();
+    let annotator =
+        RecordAnnotator::new(&args.path_mehari_db, args.genomebuild, args.csq_cache_size)?;
+    let mut lookups = annotator.lookups(args.rocksdb_prefetch);
+    let known_format_keys = KnownFormatKeys::default();
+    let kept_info_keys = KeptInfoKeys::new(&[], &input_header)?;
+    let mut prev = std::time::Instant::now();
+    let mut report = alleles::Report::default();
+    let mut records = input_reader.records(&input_header);
+    while let Some(input_record) = records
+        .try_next()
+        .await
+        .map_err(|e| anyhow::anyhow!("problem reading input VCF file: {}", e))?
+    {
+        for cohort_record in annotator.process_record(
+            &mut lookups,
+            &input_record,
+            &idx_output_to_input,
+            &known_format_keys,
+            &kept_info_keys,
+            &mut report,
+            false,
+            &mut prev,
+        )? {
+            let keys = cohort_record.genotypes().keys().clone();
+            let idx_gt = keys
+                .get_index_of(&vcf::record::genotypes::keys::key::GENOTYPE)
+                .ok_or_else(|| anyhow::anyhow!("record without GT: {}", &cohort_record))?;
+            let values = cohort_record
+                .genotypes()
+                .values()
+                .map(|sample| sample.values().to_vec())
+                .collect::<Vec<_>>();
+            for (case, output_writer) in cases.iter_mut().zip(output_writers.iter_mut()) {
+                let case_values = &values[case.samples.clone()];
+                if !carries_allele(case_values, idx_gt) {
+                    continue;
+                }
+                let mut output_record = cohort_record.clone();
+                *output_record.genotypes_mut() =
+                    vcf::record::Genotypes::new(keys.clone(), case_values.to_vec());
+                output_writer.write_record(&output_record).await?;
+                case.stats.register(&output_record)?;
+            }
+        }
+    }
+    annotator.log_csq_cache_stats();
+    annotator.log_perf_counters();
+    tracing::info!(
+        "... annotated {} records in {:?}",
+        report.count_written.separate_with_commas(),
+        start.elapsed()
+    );
+    report.log_summary();
+
+    for (case, output_writer) in cases.iter().zip(output_writers.into_iter()) {
+        let path_out = case.out_path_helper.path_out();
+        flush_and_shutdown!(output_writer);
+        tracing::info!(
+            "wrote {} records for case {} to {}",
+            case.stats.count_total.separate_with_commas(),
+            &case.family.case_uuid,
+            path_out
+        );
+        case.stats
+            .write_to_header(path_out, args_common.compression_level)
+            .await?;
+        case.stats.write_sidecar(path_out)?;
+        case.family.write_sidecar(path_out)?;
+        case.out_path_helper
+            .create_index_for_bgzf(args.write_index.resolve(&case.output_header))
+            .await?;
+        case.out_path_helper.upload_for_s3().await?;
+    }
+
+    tracing::info!(
+        "All of `seqvars split-cohort` completed in {:?}",
+        before_anything.elapsed()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::common::GenomeRelease;
+
+    #[test]
+    fn load_families() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("families.tsv");
+        std::fs::write(
+            &path,
+            "# family\tcase_uuid\tpath_out\n\
+            FAM_1\t00000000-0000-0000-0000-000000000001\tfam_1.vcf\n\
+            FAM_2\t00000000-0000-0000-0000-000000000002\tfam_2.vcf.gz\n",
+        )?;
+
+        let families = super::load_families(path.to_str().unwrap())?;
+        assert_eq!(families.len(), 2);
+        assert_eq!(families[1].family, "FAM_2");
+        assert_eq!(families[1].path_out, "fam_2.vcf.gz");
+
+        std::fs::write(
+            &path,
+            "FAM_1\t00000000-0000-0000-0000-000000000001\tfam_1.vcf\n\
+            FAM_1\t00000000-0000-0000-0000-000000000002\tfam_2.vcf\n",
+        )?;
+        assert!(super::load_families(path.to_str().unwrap()).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn smoke_test() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_out = |name: &str| tmpdir.join(name).to_string_lossy().to_string();
+        let path_families = path_out("families.tsv");
+        std::fs::write(
+            &path_families,
+            format!(
+                "FAM_1\t00000000-0000-0000-0000-000000000001\t{}\n\
+                FAM_2\t00000000-0000-0000-0000-000000000002\t{}\n",
+                path_out("fam_1.vcf"),
+                path_out("fam_2.vcf")
+            ),
+        )?;
+
+        let args = super::Args {
+            file_date: String::from("20230421"),
+            genomebuild: GenomeRelease::Grch37,
+            path_mehari_db: "tests/seqvars/ingest/db".into(),
+            path_ped: "tests/seqvars/ingest/Case_1.cohort.ped".into(),
+            path_families,
+            sample_mismatch_policy: Default::default(),
+            path_in: "tests/seqvars/ingest/Case_1.vcf".into(),
+            rocksdb_prefetch: false,
+            csq_cache_size: 10_000,
+            write_index: Default::default(),
+        };
+        super::run(&Default::default(), &args).await?;
+
+        for (name, samples) in [
+            (
+                "fam_1.vcf",
+                vec!["Case_1_index-N1-DNA1-WGS1", "Case_1_mother-N1-DNA1-WGS1"],
+            ),
+            ("fam_2.vcf", vec!["Case_1_father-N1-DNA1-WGS1"]),
+        ] {
+            let output = std::fs::read_to_string(path_out(name))?;
+            let header_line = output
+                .lines()
+                .find(|line| line.starts_with("#CHROM"))
+                .expect("no header line");
+            assert_eq!(&header_line.split('\t').collect::<Vec<_>>()[9..], samples);
+            let records = output
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .collect::<Vec<_>>();
+            assert!(!records.is_empty());
+            for record in records {
+                let gts = record
+                    .split('\t')
+                    .skip(9)
+                    .map(|sample| sample.split(':').next().unwrap())
+                    .collect::<Vec<_>>();
+                assert!(gts.iter().any(|gt| gt.contains('1')), "{}", record);
+            }
+            assert!(std::path::Path::new(&path_out(&format!("{}.family.json", name))).exists());
+        }
+
+        Ok(())
+    }
+}
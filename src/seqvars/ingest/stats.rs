@@ -0,0 +1,170 @@
+//! Statistics of the records written by `seqvars ingest`.
+//!
+//! The counts are written as `##x-varfish-stats=` JSON line into the header of the output
+//! file and into a `<path-out>.stats.json` sidecar file so the importer does not need to
+//! re-scan the output.  As the header is written before the records, the output file is
+//! rewritten once all records are known.
+
+use mehari::annotate::seqvars::ann::{AnnField, Consequence};
+use noodles_vcf as vcf;
+
+use super::resume;
+use crate::common::{io::tokio::OutputCompression, noodles::open_vcf_writer};
+
+/// Key of the header line with the statistics.
+pub const HEADER_KEY: &str = "x-varfish-stats";
+
+/// Suffix of the sidecar JSON file with the statistics.
+pub const SIDECAR_SUFFIX: &str = "stats.json";
+
+/// Counts of the written records.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Stats {
+    /// Total number of written records.
+    pub count_total: usize,
+    /// Number of records by contig, in order of appearance.
+    pub by_contig: indexmap::IndexMap<String, usize>,
+    /// Number of records by consequence of the first (most severe) annotation.
+    pub by_consequence: indexmap::IndexMap<Consequence, usize>,
+}
+
+impl Stats {
+    /// Register the written `record`.
+    pub fn register(&mut self, record: &vcf::Record) -> Result<(), anyhow::Error> {
+        self.count_total += 1;
+        *self
+            .by_contig
+            .entry(record.chromosome().to_string())
+            .or_default() += 1;
+
+        let key_ann: vcf::record::info::field::Key = "ANN".parse()?;
+        if let Some(Some(vcf::record::info::field::Value::Array(
+            vcf::record::info::field::value::Array::String(anns),
+        ))) = record.info().get(&key_ann)
+        {
+            if let Some(Some(ann)) = anns.first() {
+                let ann: AnnField = ann.parse().map_err(|e| {
+                    anyhow::anyhow!("failed to parse ANN field from {}: {}", ann, e)
+                })?;
+                for csq in ann.consequences {
+                    *self.by_consequence.entry(csq).or_default() += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add the counts from `other`.
+    pub fn merge(&mut self, other: Stats) {
+        self.count_total += other.count_total;
+        for (contig, count) in other.by_contig {
+            *self.by_contig.entry(contig).or_default() += count;
+        }
+        for (csq, count) in other.by_consequence {
+            *self.by_consequence.entry(csq).or_default() += count;
+        }
+    }
+
+    /// Write the statistics to the sidecar file of `path_out`.
+    pub fn write_sidecar(&self, path_out: &str) -> Result<(), anyhow::Error> {
+        let path = format!("{}.{}", path_out, SIDECAR_SUFFIX);
+        serde_json::to_writer_pretty(std::fs::File::create(&path)?, self)
+            .map_err(|e| anyhow::anyhow!("could not write statistics to {}: {}", &path, e))
+    }
+
+    /// Rewrite the output file at `path_out` with the statistics in the header.
+    pub async fn write_to_header(
+        &self,
+        path_out: &str,
+        compression_level: Option<i32>,
+    ) -> Result<(), anyhow::Error> {
+        // Write to a hidden file next to the output with the same extension so the
+        // compression matches.
+        let path = std::path::Path::new(path_out);
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("no file name in {}", path_out))?
+            .to_string_lossy();
+        let path_tmp = path
+            .with_file_name(format!(".{}", file_name))
+            .to_string_lossy()
+            .to_string();
+
+        {
+            let mut reader =
+                resume::open_output(path_out, OutputCompression::from_path(path_out)).await?;
+            let mut header = reader
+                .read_header()
+                .await
+                .map_err(|e| anyhow::anyhow!("could not read header of {}: {}", path_out, e))?;
+            header.insert(
+                HEADER_KEY.parse().map_err(|e| anyhow::anyhow!("{}", e))?,
+                vcf::header::record::Value::from(
+                    serde_json::to_string(self)
+                        .map_err(|e| anyhow::anyhow!("failed to serialize statistics: {}", e))?,
+                ),
+            )?;
+
+            let mut writer = open_vcf_writer(&path_tmp, compression_level).await?;
+            writer
+                .write_header(&header)
+                .await
+                .map_err(|e| anyhow::anyhow!("could not write header to {}: {}", &path_tmp, e))?;
+            let mut record = vcf::Record::default();
+            while reader.read_record(&header, &mut record).await? != 0 {
+                writer.write_record(&record).await?;
+            }
+            crate::flush_and_shutdown!(writer);
+        }
+
+        std::fs::rename(&path_tmp, path_out)
+            .map_err(|e| anyhow::anyhow!("could not rename {} to {}: {}", &path_tmp, path_out, e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::seqvars::ann::Consequence;
+    use noodles_vcf as vcf;
+
+    use super::Stats;
+
+    #[tokio::test]
+    async fn register_and_write_to_header() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_out = tmpdir.join("out.vcf").to_string_lossy().to_string();
+        std::fs::copy("tests/seqvars/aggregate/ingest.vcf", &path_out)?;
+
+        let mut stats = Stats::default();
+        {
+            let mut reader = vcf::reader::Builder::default().build_from_path(&path_out)?;
+            let header = reader.read_header()?;
+            for record in reader.records(&header) {
+                stats.register(&record?)?;
+            }
+        }
+        assert_eq!(stats.count_total, 2);
+        assert_eq!(
+            stats.by_contig.clone().into_iter().collect::<Vec<_>>(),
+            vec![(String::from("17"), 1), (String::from("MT"), 1)]
+        );
+        assert_eq!(
+            stats.by_consequence.get(&Consequence::IntronVariant),
+            Some(&1)
+        );
+
+        stats.write_to_header(&path_out, None).await?;
+        stats.write_sidecar(&path_out)?;
+
+        let mut reader = vcf::reader::Builder::default().build_from_path(&path_out)?;
+        let header = reader.read_header()?;
+        assert!(header.other_records().contains_key(super::HEADER_KEY));
+        assert_eq!(reader.records(&header).count(), 2);
+        let sidecar: Stats =
+            serde_json::from_reader(std::fs::File::open(format!("{}.stats.json", &path_out))?)?;
+        assert_eq!(sidecar, stats);
+
+        Ok(())
+    }
+}
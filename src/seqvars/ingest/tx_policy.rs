@@ -0,0 +1,119 @@
+//! Restriction of the consequences in `INFO/ANN` to selected transcripts.
+//!
+//! The transcripts are selected by their tags in the mehari transcript database, e.g., MANE
+//! Select, such that only the clinically relevant consequences are written.  Annotations
+//! that do not refer to a transcript are always kept.
+
+use std::collections::HashSet;
+
+use mehari::{
+    annotate::seqvars::ann::{AnnField, FeatureType, SoFeature},
+    pbs::txs::{TranscriptTag, TxSeqDatabase},
+};
+
+/// Selection of the transcripts to write consequences for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TxPolicy {
+    /// All transcripts.
+    #[default]
+    All,
+    /// MANE Select transcripts only.
+    Mane,
+    /// MANE Select and MANE Plus Clinical transcripts.
+    ManePlusClinical,
+    /// Canonical transcripts, i.e., MANE Select, RefSeq Select, or Ensembl canonical.
+    Canonical,
+}
+
+impl TxPolicy {
+    /// Return whether a transcript with the given `tags` is selected.
+    pub fn admits(&self, tags: &[TranscriptTag]) -> bool {
+        let has = |tag: TranscriptTag| tags.contains(&tag);
+        match self {
+            TxPolicy::All => true,
+            TxPolicy::Mane => has(TranscriptTag::ManeSelect),
+            TxPolicy::ManePlusClinical => {
+                has(TranscriptTag::ManeSelect) || has(TranscriptTag::ManePlusClinical)
+            }
+            TxPolicy::Canonical => {
+                has(TranscriptTag::ManeSelect)
+                    || has(TranscriptTag::RefSeqSelect)
+                    || has(TranscriptTag::EnsemblCanonical)
+            }
+        }
+    }
+}
+
+/// The accessions of the transcripts selected by a `TxPolicy`; all by default.
+#[derive(Debug, Clone, Default)]
+pub struct SelectedTxs {
+    /// The selected accessions, `None` if all transcripts are selected.
+    tx_ids: Option<HashSet<String>>,
+}
+
+impl SelectedTxs {
+    /// Select the transcripts of `tx_seq_db` by `tx_policy`.
+    pub fn new(tx_seq_db: &TxSeqDatabase, tx_policy: TxPolicy) -> Self {
+        if tx_policy == TxPolicy::All {
+            return Self { tx_ids: None };
+        }
+        let tx_ids = tx_seq_db
+            .tx_db
+            .as_ref()
+            .map(|tx_db| {
+                tx_db
+                    .transcripts
+                    .iter()
+                    .filter(|tx| tx_policy.admits(&tx.tags().collect::<Vec<_>>()))
+                    .map(|tx| tx.id.clone())
+                    .collect::<HashSet<_>>()
+            })
+            .unwrap_or_default();
+        tracing::info!(
+            "Restricting consequences to {} transcripts ({:?})",
+            tx_ids.len(),
+            tx_policy
+        );
+        Self {
+            tx_ids: Some(tx_ids),
+        }
+    }
+
+    /// Remove the consequences of the transcripts that are not selected from `ann_fields`.
+    pub fn retain(&self, ann_fields: &mut Vec<AnnField>) {
+        let Some(tx_ids) = self.tx_ids.as_ref() else {
+            return;
+        };
+        ann_fields.retain(|ann_field| {
+            ann_field.feature_type
+                != FeatureType::SoTerm {
+                    term: SoFeature::Transcript,
+                }
+                || tx_ids.contains(&ann_field.feature_id)
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::pbs::txs::TranscriptTag;
+
+    use super::TxPolicy;
+
+    #[rstest::rstest]
+    #[case::all_untagged(TxPolicy::All, &[], true)]
+    #[case::mane_select(TxPolicy::Mane, &[TranscriptTag::ManeSelect], true)]
+    #[case::mane_plus_clinical_only(TxPolicy::Mane, &[TranscriptTag::ManePlusClinical], false)]
+    #[case::mane_plus_clinical(
+        TxPolicy::ManePlusClinical,
+        &[TranscriptTag::Basic, TranscriptTag::ManePlusClinical],
+        true
+    )]
+    #[case::mane_plus_clinical_basic(TxPolicy::ManePlusClinical, &[TranscriptTag::Basic], false)]
+    #[case::canonical_refseq(TxPolicy::Canonical, &[TranscriptTag::RefSeqSelect], true)]
+    #[case::canonical_ensembl(TxPolicy::Canonical, &[TranscriptTag::EnsemblCanonical], true)]
+    #[case::canonical_basic(TxPolicy::Canonical, &[TranscriptTag::Basic], false)]
+    fn admits(#[case] tx_policy: TxPolicy, #[case] tags: &[TranscriptTag], #[case] expected: bool) {
+        assert_eq!(tx_policy.admits(tags), expected);
+    }
+}
@@ -0,0 +1,313 @@
+//! Implementation of `seqvars lookup` subcommand.
+//!
+//! This maps HGVS or SPDI descriptions, e.g., of variants from the literature, to genomic
+//! coordinates with the transcript database and annotates them in the same way as
+//! `seqvars ingest`.  Optionally, the genotypes of the case are looked up in the ingested
+//! VCF file of the case.
+
+use std::{
+    io::{BufRead as _, Write as _},
+    sync::Arc,
+};
+
+use futures::TryStreamExt as _;
+use indexmap::IndexMap;
+use mehari::common::noodles::open_vcf_reader;
+use thousands::Separable;
+
+use crate::{
+    common::{
+        reference::{FastaReference, Reference},
+        GenomeRelease,
+    },
+    seqvars::ingest::RecordAnnotator,
+};
+
+pub mod parse;
+
+/// Command line arguments for `seqvars lookup` subcommand.
+#[derive(Debug, Clone, clap::Parser)]
+#[command(author, version, about = "look up and annotate HGVS/SPDI descriptions", long_about = None)]
+pub struct Args {
+    /// The assumed genome build.
+    #[clap(long)]
+    pub genomebuild: GenomeRelease,
+    /// The path to the mehari database.
+    #[clap(long)]
+    pub path_mehari_db: String,
+
+    /// HGVS (`g.`, `m.`, `c.`, `n.`) or SPDI descriptions to look up.
+    #[clap(long)]
+    pub hgvs: Vec<String>,
+    /// Path to file with one HGVS or SPDI description per line.
+    #[clap(long)]
+    pub path_hgvs: Option<String>,
+    /// Path to the indexed reference FASTA file; optional.
+    ///
+    /// Required for descriptions without reference bases and for indels, which are
+    /// normalized with it for finding them in the frequency and ClinVar databases and in
+    /// the case.
+    #[clap(long)]
+    pub path_reference: Option<String>,
+    /// Path to the ingested VCF file of a case to look up the genotypes in; optional.
+    #[clap(long)]
+    pub path_case: Option<String>,
+    /// Path to the output JSONL file, standard output if not given.
+    #[clap(long)]
+    pub path_out: Option<String>,
+}
+
+/// The result of looking up one description.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LookupResult {
+    /// The input description.
+    pub input: String,
+    /// The variant as `CHROM:POS:REF:ALT`, if the description could be mapped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    /// The error message if the description could not be mapped or annotated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The `INFO` fields written by `seqvars ingest` for the variant.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub info: IndexMap<String, String>,
+    /// The genotypes of the case samples, if the variant is found in the case.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub case_genotypes: IndexMap<String, String>,
+}
+
+/// Return the key for matching variants in the case.
+fn var_key(var: &annonars::common::keys::Var) -> String {
+    format!(
+        "{}:{}:{}:{}",
+        annonars::common::cli::canonicalize(&var.chrom),
+        var.pos,
+        var.reference,
+        var.alternative
+    )
+}
+
+/// Map the description `input` to the genome and annotate it with `annotator`.
+fn lookup(
+    input: &str,
+    genome_release: GenomeRelease,
+    mapper: &hgvs::mapper::assembly::Mapper,
+    annotator: &RecordAnnotator,
+    reference: Option<&mut dyn Reference>,
+) -> Result<(annonars::common::keys::Var, IndexMap<String, String>), anyhow::Error> {
+    let located = match parse::parse_spdi(genome_release, input) {
+        Some(located) => located?,
+        None => parse::parse_hgvs(genome_release, mapper, input)?,
+    };
+    let var = parse::to_var(&located, reference)?;
+
+    // The annotation is written to the `INFO` fields of an empty record.
+    let mut record = noodles_vcf::Record::default();
+    annotator.annotate(&mut annotator.lookups(false), var.clone(), &mut record)?;
+    let info = record
+        .info()
+        .keys()
+        .zip(record.info().values())
+        .map(|(key, value)| {
+            (
+                key.to_string(),
+                value.map(|v| v.to_string()).unwrap_or_default(),
+            )
+        })
+        .collect();
+    Ok((var, info))
+}
+
+/// Fill the genotypes of the case in `path_case` into `results`.
+async fn lookup_case(path_case: &str, results: &mut [LookupResult]) -> Result<(), anyhow::Error> {
+    let mut reader = open_vcf_reader(path_case)
+        .await
+        .map_err(|e| anyhow::anyhow!("could not open case file {}: {}", path_case, e))?;
+    let header = reader
+        .read_header()
+        .await
+        .map_err(|e| anyhow::anyhow!("problem reading header of {}: {}", path_case, e))?;
+    let mut records = reader.records(&header);
+    while let Some(record) = records
+        .try_next()
+        .await
+        .map_err(|e| anyhow::anyhow!("problem reading record: {}", e))?
+    {
+        let key = var_key(&annonars::common::keys::Var::from_vcf_allele(&record, 0));
+        for result in results
+            .iter_mut()
+            .filter(|result| result.variant.as_ref() == Some(&key))
+        {
+            for (name, sample) in header
+                .sample_names()
+                .iter()
+                .zip(record.genotypes().values())
+            {
+                let gt = sample
+                    .get(&noodles_vcf::record::genotypes::keys::key::GENOTYPE)
+                    .flatten()
+                    .map(|value| value.to_string())
+                    .unwrap_or_else(|| ".".into());
+                result.case_genotypes.insert(name.clone(), gt);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Main entry point for `seqvars lookup` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    let before_anything = std::time::Instant::now();
+    tracing::info!("args_common = {:#?}", &args_common);
+    tracing::info!("args = {:#?}", &args);
+
+    let mut inputs = args.hgvs.clone();
+    if let Some(path_hgvs) = args.path_hgvs.as_ref() {
+        let reader = std::fs::File::open(path_hgvs)
+            .map(std::io::BufReader::new)
+            .map_err(|e| anyhow::anyhow!("could not open {}: {}", path_hgvs, e))?;
+        for line in reader.lines() {
+            let line = line?;
+            if !line.trim().is_empty() && !line.starts_with('#') {
+                inputs.push(line.trim().to_string());
+            }
+        }
+    }
+
+    let annotator = RecordAnnotator::new(&args.path_mehari_db, args.genomebuild, 0)?;
+    let mapper = hgvs::mapper::assembly::Mapper::new(
+        hgvs::mapper::assembly::Config {
+            assembly: args.genomebuild.into(),
+            alt_aln_method: mehari::annotate::seqvars::csq::ALT_ALN_METHOD.to_string(),
+            normalize: false,
+            replace_reference: false,
+            strict_bounds: false,
+            renormalize_g: false,
+            genome_seq_available: false,
+            ..Default::default()
+        },
+        Arc::clone(
+            annotator
+                .provider()
+                .expect("consequence prediction is not skipped"),
+        ) as Arc<_>,
+    );
+    let mut reference = args
+        .path_reference
+        .as_deref()
+        .map(FastaReference::from_path)
+        .transpose()?;
+
+    tracing::info!("looking up {} descriptions...", inputs.len());
+    let mut results = inputs
+        .iter()
+        .map(|input| {
+            let reference = reference
+                .as_mut()
+                .map(|reference| reference as &mut dyn Reference);
+            match lookup(input, args.genomebuild, &mapper, &annotator, reference) {
+                Ok((var, info)) => LookupResult {
+                    input: input.clone(),
+                    variant: Some(var_key(&var)),
+                    info,
+                    ..Default::default()
+                },
+                Err(e) => LookupResult {
+                    input: input.clone(),
+                    error: Some(e.to_string()),
+                    ..Default::default()
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+    tracing::info!(
+        "... mapped {} of {} descriptions",
+        results
+            .iter()
+            .filter(|result| result.variant.is_some())
+            .count()
+            .separate_with_commas(),
+        results.len().separate_with_commas()
+    );
+
+    if let Some(path_case) = args.path_case.as_ref() {
+        tracing::info!("looking up genotypes in {}...", path_case);
+        lookup_case(path_case, &mut results).await?;
+    }
+
+    let mut writer: Box<dyn std::io::Write> = match args.path_out.as_ref() {
+        Some(path_out) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path_out)
+                .map_err(|e| anyhow::anyhow!("could not create {}: {}", path_out, e))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    for result in &results {
+        serde_json::to_writer(&mut writer, result)?;
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+
+    tracing::info!(
+        "All of `seqvars lookup` completed in {:?}",
+        before_anything.elapsed()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::common::GenomeRelease;
+
+    use super::LookupResult;
+
+    #[tokio::test]
+    async fn smoke_test() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_out = tmpdir.join("out.jsonl");
+
+        let args_common = Default::default();
+        let args = super::Args {
+            genomebuild: GenomeRelease::Grch37,
+            path_mehari_db: "tests/seqvars/ingest/db".into(),
+            hgvs: vec![
+                "NM_007294.4:c.591C>T".into(),
+                "NC_000017.10:g.41249263G>A".into(),
+                "NM_007294.4:c.442-800_442-795delATTATA".into(),
+            ],
+            path_hgvs: None,
+            path_reference: None,
+            path_case: Some("tests/seqvars/ingest/Case_1.vcf".into()),
+            path_out: Some(path_out.to_str().expect("invalid path").into()),
+        };
+        super::run(&args_common, &args).await?;
+
+        let results = std::fs::read_to_string(&path_out)?
+            .lines()
+            .map(serde_json::from_str::<LookupResult>)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(results.len(), 3);
+        // The transcript description is projected to the genome.
+        assert_eq!(results[0].variant.as_deref(), Some("17:41249263:G:A"));
+        assert_eq!(results[0].error, None);
+        assert!(!results[0].info.is_empty());
+        assert_eq!(
+            results[0]
+                .case_genotypes
+                .get("Case_1_mother-N1-DNA1-WGS1")
+                .map(String::as_str),
+            Some("0/1")
+        );
+        assert_eq!(results[1].variant, results[0].variant);
+        assert_eq!(results[1].info, results[0].info);
+        // Indels are not looked up without the reference genome.
+        assert_eq!(results[2].variant, None);
+        assert!(results[2]
+            .error
+            .as_deref()
+            .unwrap_or_default()
+            .contains("--path-reference"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,390 @@
+//! Parsing of HGVS and SPDI descriptions into VCF-style variants.
+
+use std::str::FromStr as _;
+
+use biocommons_bioutils::assemblies::ASSEMBLY_INFOS;
+use hgvs::{
+    mapper::assembly::Mapper,
+    parser::{HgvsVariant, NaEdit},
+};
+
+use crate::common::{reference::Reference, GenomeRelease};
+
+/// The change of a located variant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// Replacement of the reference by the given sequence (empty for deletions).
+    Alt(String),
+    /// Duplication of the reference.
+    Dup,
+    /// Inversion of the reference.
+    Inv,
+}
+
+/// A variant located on a chromosome, with the reference bases if known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Located {
+    /// Canonical chromosome name.
+    pub chrom: String,
+    /// 1-based position of the first reference base.
+    pub start: i32,
+    /// 1-based position of the last reference base, `start - 1` for insertions.
+    pub end: i32,
+    /// Reference bases, `None` if not given in the description.
+    pub reference: Option<String>,
+    /// The change.
+    pub change: Change,
+}
+
+/// Return the canonical chromosome name for the RefSeq `accession` or chromosome name.
+fn chrom_for_accession(genome_release: GenomeRelease, accession: &str) -> Option<String> {
+    let canonical = annonars::common::cli::canonicalize(accession);
+    ASSEMBLY_INFOS[genome_release.into()]
+        .sequences
+        .iter()
+        .map(|record| {
+            (
+                annonars::common::cli::canonicalize(&record.name),
+                &record.refseq_ac,
+            )
+        })
+        .find(|(name, refseq_ac)| *refseq_ac == accession || *name == canonical)
+        .map(|(name, _)| name)
+}
+
+/// Parse SPDI notation `SEQ:POS:DEL:INS`, e.g., `NC_000007.14:117559592:C:T`.
+///
+/// The position is 0-based and the deletion may be given as sequence or length.  Returns
+/// `None` if `value` is not in SPDI notation.
+pub fn parse_spdi(
+    genome_release: GenomeRelease,
+    value: &str,
+) -> Option<Result<Located, anyhow::Error>> {
+    let fields = value.split(':').collect::<Vec<_>>();
+    let [sequence, pos, deletion, insertion] = fields.as_slice() else {
+        return None;
+    };
+    let pos = pos.parse::<i32>().ok()?;
+    let is_seq = |s: &str| s.bytes().all(|b| b"ACGTNacgtn".contains(&b));
+    if !is_seq(insertion) || !(is_seq(deletion) || deletion.parse::<i32>().is_ok()) {
+        return None;
+    }
+
+    Some((|| {
+        let chrom = chrom_for_accession(genome_release, sequence)
+            .ok_or_else(|| anyhow::anyhow!("unknown sequence: {}", sequence))?;
+        let (length, reference) = match deletion.parse::<i32>() {
+            Ok(length) => (length, (length == 0).then(String::new)),
+            Err(_) => (deletion.len() as i32, Some(deletion.to_ascii_uppercase())),
+        };
+        Ok(Located {
+            chrom,
+            start: pos + 1,
+            end: pos + length,
+            reference,
+            change: Change::Alt(insertion.to_ascii_uppercase()),
+        })
+    })())
+}
+
+/// Locate the genomic edit `edit` at `[start, end]` on `accession`.
+fn locate_genomic(
+    genome_release: GenomeRelease,
+    accession: &str,
+    start: Option<i32>,
+    end: Option<i32>,
+    edit: &NaEdit,
+) -> Result<Located, anyhow::Error> {
+    let chrom = chrom_for_accession(genome_release, accession)
+        .ok_or_else(|| anyhow::anyhow!("unknown sequence: {}", accession))?;
+    let (Some(start), Some(end)) = (start, end.or(start)) else {
+        anyhow::bail!("uncertain positions are not supported");
+    };
+    let known = |reference: &str| (!reference.is_empty()).then(|| reference.to_string());
+    let (start, end, reference, change) = match edit {
+        NaEdit::RefAlt {
+            reference,
+            alternative,
+        } => (
+            start,
+            end,
+            known(reference),
+            Change::Alt(alternative.clone()),
+        ),
+        NaEdit::NumAlt { alternative, .. } => (start, end, None, Change::Alt(alternative.clone())),
+        NaEdit::DelRef { reference } => (start, end, known(reference), Change::Alt(String::new())),
+        NaEdit::DelNum { .. } => (start, end, None, Change::Alt(String::new())),
+        NaEdit::Ins { alternative } => (
+            start + 1,
+            start,
+            Some(String::new()),
+            Change::Alt(alternative.clone()),
+        ),
+        NaEdit::Dup { reference } => (start, end, known(reference), Change::Dup),
+        NaEdit::InvRef { reference } => (start, end, known(reference), Change::Inv),
+        NaEdit::InvNum { .. } => (start, end, None, Change::Inv),
+    };
+    Ok(Located {
+        chrom,
+        start,
+        end,
+        reference,
+        change,
+    })
+}
+
+/// Parse the HGVS description `value` and project `c.` and `n.` descriptions to the genome
+/// with `mapper`.
+pub fn parse_hgvs(
+    genome_release: GenomeRelease,
+    mapper: &Mapper,
+    value: &str,
+) -> Result<Located, anyhow::Error> {
+    let variant = HgvsVariant::from_str(value)
+        .map_err(|e| anyhow::anyhow!("could not parse HGVS description: {}", e))?;
+    let variant = match &variant {
+        HgvsVariant::CdsVariant { .. } | HgvsVariant::TxVariant { .. } => {
+            // Fill in the reference from the transcript so it is validated and carried over.
+            let variant = mapper
+                .replace_reference(variant)
+                .map_err(|e| anyhow::anyhow!("could not get reference from transcript: {}", e))?;
+            let projected = if matches!(variant, HgvsVariant::CdsVariant { .. }) {
+                mapper.c_to_g(&variant)
+            } else {
+                mapper.n_to_g(&variant)
+            };
+            projected.map_err(|e| anyhow::anyhow!("could not project to genome: {}", e))?
+        }
+        HgvsVariant::GenomeVariant { .. } | HgvsVariant::MtVariant { .. } => variant,
+        HgvsVariant::ProtVariant { .. } | HgvsVariant::RnaVariant { .. } => {
+            anyhow::bail!("only g., m., c., and n. descriptions are supported")
+        }
+    };
+
+    match &variant {
+        HgvsVariant::GenomeVariant {
+            accession,
+            loc_edit,
+            ..
+        } => locate_genomic(
+            genome_release,
+            &accession.value,
+            loc_edit.loc.inner().start,
+            loc_edit.loc.inner().end,
+            loc_edit.edit.inner(),
+        ),
+        HgvsVariant::MtVariant {
+            accession,
+            loc_edit,
+            ..
+        } => locate_genomic(
+            genome_release,
+            &accession.value,
+            loc_edit.loc.inner().start,
+            loc_edit.loc.inner().end,
+            loc_edit.edit.inner(),
+        ),
+        _ => anyhow::bail!("projection did not yield a genomic description"),
+    }
+}
+
+/// Convert `located` into a normalized VCF-style variant.
+///
+/// If the `reference` genome is given, the reference bases are filled in and the variant
+/// is left-shifted and padded with the preceding base as in the databases.  Otherwise, the
+/// reference bases must be given in the description and indels are rejected as they cannot
+/// be normalized for the lookup in the databases.
+pub fn to_var(
+    located: &Located,
+    mut reference: Option<&mut dyn Reference>,
+) -> Result<annonars::common::keys::Var, anyhow::Error> {
+    let ref_bases = match (&located.reference, reference.as_mut()) {
+        (Some(ref_bases), _) => ref_bases.clone(),
+        (None, Some(reference)) => reference.bases(&located.chrom, located.start, located.end)?,
+        (None, None) => anyhow::bail!(
+            "reference bases not given in description, use --path-reference to fill them in"
+        ),
+    };
+    let alt_bases = match &located.change {
+        Change::Alt(alt_bases) => alt_bases.clone(),
+        Change::Dup => ref_bases.repeat(2),
+        Change::Inv => hgvs::sequences::revcomp(&ref_bases),
+    };
+    if ref_bases == alt_bases {
+        anyhow::bail!("description does not change the reference");
+    }
+
+    let mut pos = located.start;
+    let mut ref_bases = ref_bases.into_bytes();
+    let mut alt_bases = alt_bases.into_bytes();
+    loop {
+        let mut changed = false;
+        if !ref_bases.is_empty() && !alt_bases.is_empty() && ref_bases.last() == alt_bases.last() {
+            ref_bases.pop();
+            alt_bases.pop();
+            changed = true;
+        }
+        if ref_bases.is_empty() || alt_bases.is_empty() {
+            if let Some(reference) = reference.as_mut().filter(|_| pos > 1) {
+                let base = reference
+                    .bases(&located.chrom, pos - 1, pos - 1)?
+                    .into_bytes();
+                pos -= 1;
+                ref_bases.splice(0..0, base.iter().copied());
+                alt_bases.splice(0..0, base.iter().copied());
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    if (ref_bases.is_empty() || alt_bases.is_empty()) && reference.is_none() {
+        anyhow::bail!("indels need the reference genome for normalization, use --path-reference");
+    }
+    let common_prefix = ref_bases
+        .iter()
+        .zip(alt_bases.iter())
+        .take_while(|(r, a)| r == a)
+        .count()
+        .min(ref_bases.len().min(alt_bases.len()).saturating_sub(1));
+    ref_bases.drain(..common_prefix);
+    alt_bases.drain(..common_prefix);
+    pos += common_prefix as i32;
+
+    Ok(annonars::common::keys::Var {
+        chrom: located.chrom.clone(),
+        pos,
+        reference: String::from_utf8(ref_bases)?,
+        alternative: String::from_utf8(alt_bases)?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use hgvs::parser::NaEdit;
+
+    use super::{Change, Located, Reference};
+    use crate::common::GenomeRelease;
+
+    /// Reference with `GCATTTTGCA` at the start of every chromosome.
+    struct TestReference;
+
+    impl Reference for TestReference {
+        fn bases(&mut self, _chrom: &str, start: i32, end: i32) -> Result<String, anyhow::Error> {
+            Ok("GCATTTTGCA"[(start - 1) as usize..end as usize].to_string())
+        }
+    }
+
+    #[rstest::rstest]
+    #[case("NC_000007.14:117559592:C:T", Some((117559593, 117559593, Some("C"), "T")))]
+    #[case("7:99:2:", Some((100, 101, None, "")))]
+    #[case("NC_000007.14:100:0:AG", Some((101, 100, Some(""), "AG")))]
+    #[case("NC_000007.14:100:XY:T", None)]
+    #[case("NM_000492.4:c.1521_1523del", None)]
+    fn parse_spdi(#[case] value: &str, #[case] expected: Option<(i32, i32, Option<&str>, &str)>) {
+        let result = super::parse_spdi(GenomeRelease::Grch38, value).map(|located| {
+            let located = located.unwrap();
+            assert_eq!(located.chrom, "7");
+            let Change::Alt(alt) = located.change else {
+                panic!("unexpected change")
+            };
+            (located.start, located.end, located.reference, alt)
+        });
+        assert_eq!(
+            result,
+            expected.map(|(start, end, reference, alt)| (
+                start,
+                end,
+                reference.map(String::from),
+                alt.to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn locate_genomic_insertion() {
+        let located = super::locate_genomic(
+            GenomeRelease::Grch37,
+            "NC_000007.13",
+            Some(100),
+            Some(101),
+            &NaEdit::Ins {
+                alternative: "TT".into(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            located,
+            Located {
+                chrom: "7".into(),
+                start: 101,
+                end: 100,
+                reference: Some(String::new()),
+                change: Change::Alt("TT".into()),
+            }
+        );
+    }
+
+    #[rstest::rstest]
+    // substitution, the reference is taken from the description
+    #[case(5, 5, Some("T"), Change::Alt("G".into()), true, "7:5:T:G")]
+    // deletion of `TT` at 6-7 is left-shifted to 4-5 and padded with `A` at 3
+    #[case(6, 7, None, Change::Alt(String::new()), true, "7:3:ATT:A")]
+    // duplication of the 3'-most `T` is left-shifted in the same way
+    #[case(7, 7, None, Change::Dup, true, "7:3:A:AT")]
+    // common prefix of a replacement is removed
+    #[case(8, 9, Some("GC"), Change::Alt("GT".into()), false, "7:9:C:T")]
+    fn to_var(
+        #[case] start: i32,
+        #[case] end: i32,
+        #[case] reference: Option<&str>,
+        #[case] change: Change,
+        #[case] with_reference: bool,
+        #[case] expected: &str,
+    ) {
+        let located = Located {
+            chrom: "7".into(),
+            start,
+            end,
+            reference: reference.map(String::from),
+            change,
+        };
+        let mut test_reference = TestReference;
+        let var = super::to_var(
+            &located,
+            with_reference.then_some(&mut test_reference as &mut dyn Reference),
+        )
+        .unwrap();
+        assert_eq!(
+            format!(
+                "{}:{}:{}:{}",
+                var.chrom, var.pos, var.reference, var.alternative
+            ),
+            expected
+        );
+    }
+
+    #[rstest::rstest]
+    // reference bases not given
+    #[case(3, 4, None, Change::Alt("GT".into()))]
+    // deletion with the reference bases given
+    #[case(6, 7, Some("TT"), Change::Alt(String::new()))]
+    // insertion
+    #[case(101, 100, Some(""), Change::Alt("TT".into()))]
+    fn to_var_requires_reference(
+        #[case] start: i32,
+        #[case] end: i32,
+        #[case] reference: Option<&str>,
+        #[case] change: Change,
+    ) {
+        let located = Located {
+            chrom: "7".into(),
+            start,
+            end,
+            reference: reference.map(String::from),
+            change,
+        };
+        assert!(super::to_var(&located, None).is_err());
+    }
+}
@@ -1,4 +1,11 @@
+pub mod add_sample;
 pub mod aggregate;
+pub mod annotate_region;
+pub mod export_parquet;
 pub mod ingest;
+pub mod lookup;
 pub mod prefilter;
+pub mod prefilter_stats;
+pub mod qc;
 pub mod query;
+pub mod special_loci;
@@ -7,14 +7,17 @@ use mehari::{
     annotate::seqvars::ann::AnnField,
     common::{
         io::std::is_gz,
-        noodles::{open_vcf_reader, open_vcf_writer, AsyncVcfReader, AsyncVcfWriter},
+        noodles::{open_vcf_reader, AsyncVcfReader, AsyncVcfWriter},
     },
 };
 use noodles_vcf as vcf;
 use thousands::Separable;
 use tokio::io::AsyncWriteExt;
 
-use crate::{common, flush_and_shutdown};
+use crate::{
+    common::{self, noodles::open_vcf_writer},
+    flush_and_shutdown,
+};
 
 /// Arguments for the `seqvars prefilter` subcommand.
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -40,6 +43,10 @@ pub struct Args {
 }
 
 /// Load prefilter params from strings or files with such strings.
+///
+/// All configurations are applied in a single pass over the input, so their output
+/// paths must be distinct.  Outputs are written as plain or bgzip-compressed VCF; BCF is
+/// rejected as `seqvars query` cannot read it.
 fn load_params(params: &[String]) -> Result<Vec<PrefilterParams>, anyhow::Error> {
     let mut result = Vec::new();
 
@@ -66,9 +73,30 @@ fn load_params(params: &[String]) -> Result<Vec<PrefilterParams>, anyhow::Error>
         }
     }
 
+    let mut seen = std::collections::HashSet::new();
+    for params in &result {
+        if params.path_out.ends_with(".bcf") {
+            anyhow::bail!(
+                "BCF output is not supported by seqvars query, use .vcf or .vcf.gz: {}",
+                &params.path_out
+            );
+        }
+        if !seen.insert(params.path_out.as_str()) {
+            anyhow::bail!("duplicate prefilter output path: {}", &params.path_out);
+        }
+    }
+
     Ok(result)
 }
 
+/// Return the paths of the output files given in `args.params`.
+pub fn output_paths(args: &Args) -> Result<Vec<String>, anyhow::Error> {
+    Ok(load_params(&args.params)?
+        .into_iter()
+        .map(|params| params.path_out)
+        .collect())
+}
+
 /// Extract an `i32` from a VCF record's `INFO`.
 fn get_info_i32(input_record: &vcf::Record, key: &str) -> Result<i32, anyhow::Error> {
     use vcf::record::info::field::Key;
@@ -88,7 +116,9 @@ fn get_info_i32(input_record: &vcf::Record, key: &str) -> Result<i32, anyhow::Er
 /// Extract largest population frequency and exon distance from input_record.
 ///
 /// Note that all variants on chrMT will be returned.
-fn get_freq_and_distance(input_record: &vcf::Record) -> Result<(f64, Option<i32>), anyhow::Error> {
+pub fn get_freq_and_distance(
+    input_record: &vcf::Record,
+) -> Result<(f64, Option<i32>), anyhow::Error> {
     if annonars::common::cli::canonicalize(&input_record.chromosome().to_string()) == "MT" {
         return Ok((0.0, Some(0))); // all variants on chrMT are returned
     }
@@ -213,6 +243,15 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
         .await
         .map_err(|e| anyhow::anyhow!("problem reading header: {}", e))?;
 
+    let partial_guards = params_list
+        .iter()
+        .map(|params| {
+            common::partial::PartialOutputGuard::new(
+                &params.path_out,
+                common::partial::OnFailure::Remove,
+            )
+        })
+        .collect::<Vec<_>>();
     {
         tracing::info!("opening output files...");
         let mut output_writers = Vec::new();
@@ -232,7 +271,8 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
                 ),
             )?;
 
-            let mut writer = open_vcf_writer(&params.path_out).await?;
+            let mut writer =
+                open_vcf_writer(&params.path_out, args_common.compression_level).await?;
             writer.write_header(&header).await.map_err(|e| {
                 anyhow::anyhow!("could not write header to {}: {}", &params.path_out, e)
             })?;
@@ -268,6 +308,10 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
         }
     }
 
+    partial_guards
+        .into_iter()
+        .for_each(common::partial::PartialOutputGuard::finish);
+
     tracing::info!(
         "All of `seqvars ingest` completed in {:?}",
         before_anything.elapsed()
@@ -277,6 +321,24 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
 
 #[cfg(test)]
 mod test {
+    #[rstest::rstest]
+    #[case::duplicate(
+        &[
+            r#"{"path_out": "out.vcf", "max_freq": 0.01, "max_exon_dist": 200}"#,
+            r#"{"path_out": "out.vcf", "max_freq": 0.0, "max_exon_dist": 20}"#,
+        ],
+        "duplicate prefilter output path: out.vcf"
+    )]
+    #[case::bcf(
+        &[r#"{"path_out": "out.bcf", "max_freq": 0.01, "max_exon_dist": 200}"#],
+        "BCF output is not supported by seqvars query, use .vcf or .vcf.gz: out.bcf"
+    )]
+    fn load_params_invalid(#[case] params: &[&str], #[case] expected: &str) {
+        let params = params.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let err = super::load_params(&params).unwrap_err();
+        assert_eq!(err.to_string(), expected);
+    }
+
     #[tokio::test]
     async fn single_output_arg() -> Result<(), anyhow::Error> {
         let tmpdir = temp_testdir::TempDir::default();
@@ -0,0 +1,415 @@
+//! Implementation of `seqvars prefilter-stats` subcommand.
+//!
+//! This computes case-level QC metrics from an ingested VCF file: the Ts/Tv ratio, the
+//! number of SNVs and indels, and per sample the het./hom. ratio, the depth and genotype
+//! quality distributions, and the chrX/chrY calls for checking the sex.  The metrics are
+//! written as JSON for display in VarFish Server.
+
+use futures::TryStreamExt as _;
+use mehari::common::noodles::open_vcf_reader;
+use noodles_vcf as vcf;
+use thousands::Separable;
+use vcf::record::genotypes::{keys::key, sample::Value};
+
+use crate::common::{self, GenomeRelease};
+
+/// Command line arguments for `seqvars prefilter-stats` subcommand.
+#[derive(Debug, clap::Parser)]
+#[command(author, version, about = "compute QC metrics of an ingested variant VCF", long_about = None)]
+pub struct Args {
+    /// The assumed genome build.
+    #[clap(long)]
+    pub genomebuild: GenomeRelease,
+    /// Path to input file.
+    #[clap(long)]
+    pub path_in: String,
+    /// Path to output JSON file.
+    #[clap(long)]
+    pub path_out: String,
+}
+
+/// Pseudoautosomal regions on chrX (1-based, inclusive) on GRCh37.
+const PAR_X_37: [(i32, i32); 2] = [(60_001, 2_699_520), (154_931_044, 155_260_560)];
+/// Pseudoautosomal regions on chrY (1-based, inclusive) on GRCh37.
+const PAR_Y_37: [(i32, i32); 2] = [(10_001, 2_649_520), (59_034_050, 59_363_566)];
+/// Pseudoautosomal regions on chrX (1-based, inclusive) on GRCh38.
+const PAR_X_38: [(i32, i32); 2] = [(10_001, 2_781_479), (155_701_383, 156_030_895)];
+/// Pseudoautosomal regions on chrY (1-based, inclusive) on GRCh38.
+const PAR_Y_38: [(i32, i32); 2] = [(10_001, 2_781_479), (56_887_903, 57_217_415)];
+
+/// Lower bounds of the depth histogram bins.
+const DP_BINS: &[i32] = &[0, 5, 10, 15, 20, 30, 40, 50, 75, 100];
+/// Lower bounds of the genotype quality histogram bins.
+const GQ_BINS: &[i32] = &[0, 10, 20, 30, 40, 50, 60, 70, 80, 90];
+
+/// Histogram with bins given by their lower bounds, the last bin is open.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Histogram {
+    /// Lower bounds of the bins.
+    pub bins: Vec<i32>,
+    /// Number of values in each bin.
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    /// Construct with the lower bounds `bins`.
+    pub fn new(bins: &[i32]) -> Self {
+        Self {
+            bins: bins.to_vec(),
+            counts: vec![0; bins.len()],
+        }
+    }
+
+    /// Count `value`; values below the first bin are counted in the first bin.
+    pub fn register(&mut self, value: i32) {
+        let idx = self
+            .bins
+            .iter()
+            .rposition(|lower| *lower <= value)
+            .unwrap_or(0);
+        self.counts[idx] += 1;
+    }
+}
+
+/// Calls on a sex chromosome outside of the pseudoautosomal regions.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SexChromCalls {
+    /// Number of het. calls.
+    pub count_het: usize,
+    /// Number of hom. alt. (or hemizygous) calls.
+    pub count_hom_alt: usize,
+    /// Fraction of het. calls among het. and hom. alt. calls.
+    pub het_ratio: Option<f64>,
+}
+
+impl SexChromCalls {
+    /// Register one call with genotype `gt`.
+    fn register(&mut self, gt: common::Genotype) {
+        match gt {
+            common::Genotype::Het => self.count_het += 1,
+            common::Genotype::HomAlt => self.count_hom_alt += 1,
+            _ => (),
+        }
+    }
+
+    /// Compute the het. ratio.
+    fn finalize(&mut self) {
+        let total = self.count_het + self.count_hom_alt;
+        self.het_ratio = (total > 0).then(|| self.count_het as f64 / total as f64);
+    }
+}
+
+/// QC metrics of one sample.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SampleStats {
+    /// Number of hom. ref. calls.
+    pub count_hom_ref: usize,
+    /// Number of het. calls.
+    pub count_het: usize,
+    /// Number of hom. alt. calls.
+    pub count_hom_alt: usize,
+    /// Number of (partial) no-calls.
+    pub count_no_call: usize,
+    /// Ratio of het. to hom. alt. calls.
+    pub het_hom_ratio: Option<f64>,
+    /// Distribution of the depth of the calls.
+    pub dp_histogram: Histogram,
+    /// Distribution of the genotype quality of the calls.
+    pub gq_histogram: Histogram,
+    /// Calls on chrX outside of the pseudoautosomal regions.
+    pub chrx: SexChromCalls,
+    /// Calls on chrY outside of the pseudoautosomal regions.
+    pub chry: SexChromCalls,
+}
+
+impl Default for SampleStats {
+    fn default() -> Self {
+        Self {
+            count_hom_ref: 0,
+            count_het: 0,
+            count_hom_alt: 0,
+            count_no_call: 0,
+            het_hom_ratio: None,
+            dp_histogram: Histogram::new(DP_BINS),
+            gq_histogram: Histogram::new(GQ_BINS),
+            chrx: Default::default(),
+            chry: Default::default(),
+        }
+    }
+}
+
+/// Case-level QC metrics.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Stats {
+    /// Number of records.
+    pub count_records: usize,
+    /// Number of SNVs.
+    pub count_snvs: usize,
+    /// Number of MNVs.
+    pub count_mnvs: usize,
+    /// Number of indels.
+    pub count_indels: usize,
+    /// Number of transitions among the SNVs.
+    pub count_transitions: usize,
+    /// Number of transversions among the SNVs.
+    pub count_transversions: usize,
+    /// Transition/transversion ratio of the SNVs.
+    pub ti_tv_ratio: Option<f64>,
+    /// Per-sample metrics.
+    pub samples: indexmap::IndexMap<String, SampleStats>,
+}
+
+/// The location of a record with respect to the sex chromosomes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    /// Autosomes, mitochondrial genome, and pseudoautosomal regions.
+    Other,
+    /// chrX outside of the pseudoautosomal regions.
+    X,
+    /// chrY outside of the pseudoautosomal regions.
+    Y,
+}
+
+/// Return the location of `pos` on `chrom` on `genome_release`.
+pub fn location(genome_release: GenomeRelease, chrom: &str, pos: i32) -> Location {
+    let (location, pars) = match (
+        genome_release,
+        annonars::common::cli::canonicalize(chrom).as_str(),
+    ) {
+        (GenomeRelease::Grch37, "X") => (Location::X, PAR_X_37),
+        (GenomeRelease::Grch37, "Y") => (Location::Y, PAR_Y_37),
+        (GenomeRelease::Grch38, "X") => (Location::X, PAR_X_38),
+        (GenomeRelease::Grch38, "Y") => (Location::Y, PAR_Y_38),
+        _ => return Location::Other,
+    };
+    if pars.iter().any(|(start, end)| *start <= pos && pos <= *end) {
+        Location::Other
+    } else {
+        location
+    }
+}
+
+impl Stats {
+    /// Construct for the samples in `header`.
+    pub fn new(header: &vcf::Header) -> Self {
+        Self {
+            samples: header
+                .sample_names()
+                .iter()
+                .map(|name| (name.clone(), Default::default()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Register one ingested, bi-allelic `record`.
+    pub fn register(
+        &mut self,
+        genome_release: GenomeRelease,
+        record: &vcf::Record,
+    ) -> Result<(), anyhow::Error> {
+        self.count_records += 1;
+
+        let reference = record.reference_bases().to_string();
+        let alternative = record
+            .alternate_bases()
+            .iter()
+            .next()
+            .map(|allele| allele.to_string())
+            .unwrap_or_default();
+        match (reference.len(), alternative.len()) {
+            (1, 1) => {
+                self.count_snvs += 1;
+                if matches!(
+                    (reference.as_str(), alternative.as_str()),
+                    ("A", "G") | ("G", "A") | ("C", "T") | ("T", "C")
+                ) {
+                    self.count_transitions += 1;
+                } else {
+                    self.count_transversions += 1;
+                }
+            }
+            (r, a) if r == a => self.count_mnvs += 1,
+            _ => self.count_indels += 1,
+        }
+
+        let location = location(
+            genome_release,
+            &record.chromosome().to_string(),
+            usize::from(record.position()) as i32,
+        );
+        for (sample, stats) in record.genotypes().values().zip(self.samples.values_mut()) {
+            let gt = match sample.get(&key::GENOTYPE) {
+                Some(Some(Value::String(gt))) => gt.parse()?,
+                _ => common::Genotype::WithNoCall,
+            };
+            match gt {
+                common::Genotype::HomRef => stats.count_hom_ref += 1,
+                common::Genotype::Het => stats.count_het += 1,
+                common::Genotype::HomAlt => stats.count_hom_alt += 1,
+                common::Genotype::WithNoCall => stats.count_no_call += 1,
+            }
+            match location {
+                Location::X => stats.chrx.register(gt),
+                Location::Y => stats.chry.register(gt),
+                Location::Other => (),
+            }
+
+            if gt == common::Genotype::WithNoCall {
+                continue;
+            }
+            if let Some(Some(Value::Integer(dp))) = sample.get(&key::READ_DEPTH) {
+                stats.dp_histogram.register(*dp);
+            }
+            match sample.get(&key::CONDITIONAL_GENOTYPE_QUALITY) {
+                Some(Some(Value::Integer(gq))) => stats.gq_histogram.register(*gq),
+                Some(Some(Value::Float(gq))) => stats.gq_histogram.register(*gq as i32),
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compute the ratios.
+    pub fn finalize(mut self) -> Self {
+        self.ti_tv_ratio = (self.count_transversions > 0)
+            .then(|| self.count_transitions as f64 / self.count_transversions as f64);
+        for stats in self.samples.values_mut() {
+            stats.het_hom_ratio = (stats.count_hom_alt > 0)
+                .then(|| stats.count_het as f64 / stats.count_hom_alt as f64);
+            stats.chrx.finalize();
+            stats.chry.finalize();
+        }
+        self
+    }
+}
+
+/// Main entry point for `seqvars prefilter-stats` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    let before_anything = std::time::Instant::now();
+    tracing::info!("args_common = {:#?}", &args_common);
+    tracing::info!("args = {:#?}", &args);
+
+    tracing::info!("opening input file...");
+    let mut reader = open_vcf_reader(&args.path_in)
+        .await
+        .map_err(|e| anyhow::anyhow!("could not open input file: {}", e))?;
+    let header = reader
+        .read_header()
+        .await
+        .map_err(|e| anyhow::anyhow!("problem reading header: {}", e))?;
+
+    tracing::info!("computing QC metrics...");
+    let mut stats = Stats::new(&header);
+    let mut records = reader.records(&header);
+    while let Some(record) = records
+        .try_next()
+        .await
+        .map_err(|e| anyhow::anyhow!("problem reading record: {}", e))?
+    {
+        stats.register(args.genomebuild, &record)?;
+    }
+    let stats = stats.finalize();
+    tracing::info!(
+        "... done computing QC metrics of {} records",
+        stats.count_records.separate_with_commas()
+    );
+
+    serde_json::to_writer_pretty(
+        crate::common::io::std::open_write_maybe_bgzf(
+            &args.path_out,
+            args_common.compression_level,
+        )?,
+        &stats,
+    )
+    .map_err(|e| anyhow::anyhow!("could not write QC metrics: {}", e))?;
+
+    tracing::info!(
+        "All of `seqvars prefilter-stats` completed in {:?}",
+        before_anything.elapsed()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use noodles_vcf as vcf;
+
+    use crate::common::GenomeRelease;
+
+    use super::Location;
+
+    #[rstest::rstest]
+    #[case(GenomeRelease::Grch37, "1", 1_000_000, Location::Other)]
+    #[case(GenomeRelease::Grch37, "X", 100_000, Location::Other)]
+    #[case(GenomeRelease::Grch37, "X", 3_000_000, Location::X)]
+    #[case(GenomeRelease::Grch38, "chrX", 2_781_479, Location::Other)]
+    #[case(GenomeRelease::Grch38, "chrX", 2_781_480, Location::X)]
+    #[case(GenomeRelease::Grch38, "chrY", 3_000_000, Location::Y)]
+    #[case(GenomeRelease::Grch38, "chrY", 57_000_000, Location::Other)]
+    fn location(
+        #[case] genome_release: GenomeRelease,
+        #[case] chrom: &str,
+        #[case] pos: i32,
+        #[case] expected: Location,
+    ) {
+        assert_eq!(super::location(genome_release, chrom, pos), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(-1, 0)]
+    #[case(0, 0)]
+    #[case(4, 0)]
+    #[case(5, 1)]
+    #[case(99, 8)]
+    #[case(1000, 9)]
+    fn histogram(#[case] value: i32, #[case] expected_idx: usize) {
+        let mut histogram = super::Histogram::new(super::DP_BINS);
+        histogram.register(value);
+        assert_eq!(histogram.counts[expected_idx], 1);
+        assert_eq!(histogram.counts.iter().sum::<usize>(), 1);
+    }
+
+    #[test]
+    fn register_sex_chroms() -> Result<(), anyhow::Error> {
+        use vcf::header::record::value::{map::Format, Map};
+        use vcf::record::genotypes::keys::key;
+
+        let header = vcf::Header::builder()
+            .add_format(key::GENOTYPE, Map::<Format>::from(&key::GENOTYPE))
+            .add_sample_name("father")
+            .add_sample_name("mother")
+            .build();
+        let mut stats = super::Stats::new(&header);
+        for line in [
+            "X\t3000000\t.\tA\tG\t.\t.\t.\tGT\t1\t0/1",
+            "X\t3000100\t.\tC\tA\t.\t.\t.\tGT\t1\t1/1",
+            "X\t100000\t.\tC\tA\t.\t.\t.\tGT\t0/1\t0/1",
+            "Y\t3000000\t.\tAC\tA\t.\t.\t.\tGT\t1\t./.",
+        ] {
+            let record = vcf::Record::try_from((&header, line))?;
+            stats.register(GenomeRelease::Grch37, &record)?;
+        }
+        insta::assert_yaml_snapshot!(stats.finalize());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn smoke_test() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_out = tmpdir.join("stats.json");
+
+        let args = super::Args {
+            genomebuild: GenomeRelease::Grch37,
+            path_in: "tests/seqvars/query/Case_1.ingested.vcf".into(),
+            path_out: path_out.to_str().unwrap().into(),
+        };
+        super::run(&crate::common::Args::default(), &args).await?;
+
+        insta::assert_snapshot!(std::fs::read_to_string(&path_out)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,7 @@
+//! Implementation of `seqvars qc *` subcommands.
+//!
+//! These commands compute quality control metrics from ingested VCF files that allow to
+//! catch problems with the samples, e.g., swaps, before interpretation.
+
+pub mod relatedness;
+pub mod sex;
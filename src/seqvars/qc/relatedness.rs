@@ -0,0 +1,489 @@
+//! Implementation of `seqvars qc relatedness` subcommand.
+//!
+//! The pairwise kinship coefficients are estimated with the robust KING estimator
+//! (Manichaikul et al., 2010) on common autosomal SNVs:
+//!
+//! ```text
+//! phi = (N_het,het - 2 * N_hom_ref,hom_alt) / (N_het(i) + N_het(j))
+//! ```
+//!
+//! The expected values are 0.5 for duplicates, 0.25 for first degree relatives, 0.125 for
+//! second degree relatives, and so on.  In addition, the Mendelian error rate is computed
+//! for each trio of the pedigree.  Mismatches to the relations from the pedigree indicate
+//! sample swaps.
+
+use futures::TryStreamExt as _;
+use mehari::common::noodles::open_vcf_reader;
+use noodles_vcf as vcf;
+use thousands::Separable;
+use vcf::record::genotypes::{keys::key, sample::Value};
+
+use crate::common;
+
+/// Command line arguments for `seqvars qc relatedness` subcommand.
+#[derive(Debug, clap::Parser)]
+#[command(author, version, about = "estimate relatedness and Mendelian errors of an ingested VCF", long_about = None)]
+pub struct Args {
+    /// Path to input file.
+    #[clap(long)]
+    pub path_in: String,
+    /// Path to the pedigree file; optional.
+    ///
+    /// If not given, the pedigree from the header of the input file is used.
+    #[clap(long)]
+    pub path_ped: Option<String>,
+    /// Path to output JSON file.
+    #[clap(long)]
+    pub path_out: String,
+    /// Minimal population frequency of the SNVs used for relatedness estimation.
+    #[clap(long, default_value_t = 0.01)]
+    pub min_af: f64,
+    /// Minimal depth of genotype calls to consider.
+    #[clap(long, default_value_t = 10)]
+    pub min_dp: i32,
+    /// Minimal genotype quality of genotype calls to consider.
+    #[clap(long, default_value_t = 20)]
+    pub min_gq: i32,
+}
+
+/// Degree of relationship.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Degree {
+    /// Duplicate samples or monozygotic twins.
+    Duplicate,
+    /// First degree, parent-child or full siblings.
+    First,
+    /// Second degree, e.g., grandparent-grandchild.
+    Second,
+    /// Third degree, e.g., first cousins.
+    Third,
+    /// Unrelated.
+    Unrelated,
+}
+
+impl Degree {
+    /// Return the degree for kinship coefficient `phi`, using the thresholds from KING.
+    pub fn from_kinship(phi: f64) -> Self {
+        if phi > 0.354 {
+            Self::Duplicate
+        } else if phi > 0.177 {
+            Self::First
+        } else if phi > 0.0884 {
+            Self::Second
+        } else if phi > 0.0442 {
+            Self::Third
+        } else {
+            Self::Unrelated
+        }
+    }
+}
+
+/// Relatedness of one pair of samples.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Pair {
+    /// Name of the first sample.
+    pub sample_a: String,
+    /// Name of the second sample.
+    pub sample_b: String,
+    /// Number of sites called in both samples.
+    pub count_sites: usize,
+    /// Number of sites that are het. in both samples.
+    pub count_het_het: usize,
+    /// Number of sites that are hom. ref. in one and hom. alt. in the other sample.
+    pub count_ibs0: usize,
+    /// Number of het. sites of the first sample.
+    pub count_het_a: usize,
+    /// Number of het. sites of the second sample.
+    pub count_het_b: usize,
+    /// The estimated kinship coefficient.
+    pub kinship: Option<f64>,
+    /// The degree of relationship derived from the kinship coefficient.
+    pub degree: Option<Degree>,
+    /// The degree of relationship expected from the pedigree, if known.
+    pub expected_degree: Option<Degree>,
+    /// Whether the estimated degree does not match the expected one.
+    pub mismatch: bool,
+}
+
+/// Mendelian errors of one trio.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Trio {
+    /// Name of the child.
+    pub child: String,
+    /// Name of the father.
+    pub father: String,
+    /// Name of the mother.
+    pub mother: String,
+    /// Number of sites called in all samples and not hom. ref. in all samples.
+    pub count_informative: usize,
+    /// Number of sites with a Mendelian error.
+    pub count_errors: usize,
+    /// Fraction of the informative sites with a Mendelian error.
+    pub error_rate: Option<f64>,
+}
+
+/// The relatedness report written as JSON.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Report {
+    /// Number of sites used for the relatedness estimation.
+    pub count_relatedness_sites: usize,
+    /// Pairwise relatedness of all samples.
+    pub pairs: Vec<Pair>,
+    /// Mendelian errors of the trios in the pedigree.
+    pub trios: Vec<Trio>,
+}
+
+/// Return the degree of relationship of `a` and `b` expected from `pedigree`.
+///
+/// Only parent-child and full sibling relations are considered.
+fn expected_degree(pedigree: &mehari::ped::PedigreeByName, a: &str, b: &str) -> Option<Degree> {
+    let a = pedigree.individuals.get(a)?;
+    let b = pedigree.individuals.get(b)?;
+    let is_parent = |parent: &mehari::ped::Individual, child: &mehari::ped::Individual| {
+        child.father.as_deref() == Some(parent.name.as_str())
+            || child.mother.as_deref() == Some(parent.name.as_str())
+    };
+    let are_siblings =
+        a.father.is_some() && a.mother.is_some() && a.father == b.father && a.mother == b.mother;
+    (is_parent(a, b) || is_parent(b, a) || are_siblings).then_some(Degree::First)
+}
+
+/// Return whether child alt. allele count `c` is consistent with parental counts `f` and `m`.
+fn is_mendelian(c: u8, f: u8, m: u8) -> bool {
+    let transmitted = |p: u8| match p {
+        0 => &[0u8][..],
+        1 => &[0u8, 1u8][..],
+        _ => &[1u8][..],
+    };
+    transmitted(f)
+        .iter()
+        .any(|a| transmitted(m).iter().any(|b| a + b == c))
+}
+
+/// Accumulation of the counts for the report.
+#[derive(Debug, Default)]
+pub struct Counter {
+    /// Indices of the pairs of samples, with the counts.
+    pairs: Vec<(usize, usize, Pair)>,
+    /// Indices of child, father, and mother, with the counts.
+    trios: Vec<(usize, usize, usize, Trio)>,
+    /// Number of sites used for the relatedness estimation.
+    count_relatedness_sites: usize,
+}
+
+impl Counter {
+    /// Construct for `samples` with relations from `pedigree`.
+    pub fn new(samples: &[String], pedigree: &mehari::ped::PedigreeByName) -> Self {
+        let mut pairs = Vec::new();
+        for i in 0..samples.len() {
+            for j in (i + 1)..samples.len() {
+                pairs.push((
+                    i,
+                    j,
+                    Pair {
+                        sample_a: samples[i].clone(),
+                        sample_b: samples[j].clone(),
+                        count_sites: 0,
+                        count_het_het: 0,
+                        count_ibs0: 0,
+                        count_het_a: 0,
+                        count_het_b: 0,
+                        kinship: None,
+                        degree: None,
+                        expected_degree: expected_degree(pedigree, &samples[i], &samples[j]),
+                        mismatch: false,
+                    },
+                ));
+            }
+        }
+
+        let idx = |name: &Option<String>| {
+            name.as_ref()
+                .and_then(|name| samples.iter().position(|sample| sample == name))
+        };
+        let mut trios = Vec::new();
+        for (c, sample) in samples.iter().enumerate() {
+            if let Some(individual) = pedigree.individuals.get(sample) {
+                if let (Some(f), Some(m)) = (idx(&individual.father), idx(&individual.mother)) {
+                    trios.push((
+                        c,
+                        f,
+                        m,
+                        Trio {
+                            child: samples[c].clone(),
+                            father: samples[f].clone(),
+                            mother: samples[m].clone(),
+                            count_informative: 0,
+                            count_errors: 0,
+                            error_rate: None,
+                        },
+                    ));
+                }
+            }
+        }
+
+        Self {
+            pairs,
+            trios,
+            count_relatedness_sites: 0,
+        }
+    }
+
+    /// Register the alt. allele counts of the samples at one autosomal site.
+    ///
+    /// The counts are `None` for no-calls and filtered calls.  Only common SNVs are used
+    /// for relatedness estimation as given by `for_relatedness`.
+    pub fn register(&mut self, alt_counts: &[Option<u8>], for_relatedness: bool) {
+        if for_relatedness {
+            self.count_relatedness_sites += 1;
+            for (i, j, pair) in self.pairs.iter_mut() {
+                if let (Some(a), Some(b)) = (alt_counts[*i], alt_counts[*j]) {
+                    pair.count_sites += 1;
+                    match (a, b) {
+                        (1, 1) => pair.count_het_het += 1,
+                        (0, 2) | (2, 0) => pair.count_ibs0 += 1,
+                        _ => (),
+                    }
+                    if a == 1 {
+                        pair.count_het_a += 1;
+                    }
+                    if b == 1 {
+                        pair.count_het_b += 1;
+                    }
+                }
+            }
+        }
+
+        for (c, f, m, trio) in self.trios.iter_mut() {
+            if let (Some(c), Some(f), Some(m)) = (alt_counts[*c], alt_counts[*f], alt_counts[*m]) {
+                if c + f + m == 0 {
+                    continue;
+                }
+                trio.count_informative += 1;
+                if !is_mendelian(c, f, m) {
+                    trio.count_errors += 1;
+                }
+            }
+        }
+    }
+
+    /// Compute the coefficients and rates and return the report.
+    pub fn finalize(self) -> Report {
+        Report {
+            count_relatedness_sites: self.count_relatedness_sites,
+            pairs: self
+                .pairs
+                .into_iter()
+                .map(|(_, _, mut pair)| {
+                    let count_het = pair.count_het_a + pair.count_het_b;
+                    if count_het > 0 {
+                        let phi = (pair.count_het_het as f64 - 2.0 * pair.count_ibs0 as f64)
+                            / count_het as f64;
+                        let degree = Degree::from_kinship(phi);
+                        pair.kinship = Some(phi);
+                        pair.degree = Some(degree);
+                        pair.mismatch = pair
+                            .expected_degree
+                            .map(|expected| expected != degree)
+                            .unwrap_or_default();
+                    }
+                    pair
+                })
+                .collect(),
+            trios: self
+                .trios
+                .into_iter()
+                .map(|(_, _, _, mut trio)| {
+                    trio.error_rate = (trio.count_informative > 0)
+                        .then(|| trio.count_errors as f64 / trio.count_informative as f64);
+                    trio
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Return the alt. allele counts of the samples in `record`, `None` for no-calls and calls
+/// failing the thresholds from `args`.
+fn alt_counts(record: &vcf::Record, args: &Args) -> Result<Vec<Option<u8>>, anyhow::Error> {
+    let mut result = Vec::new();
+    for sample in record.genotypes().values() {
+        let gt = match sample.get(&key::GENOTYPE) {
+            Some(Some(Value::String(gt))) => gt.parse()?,
+            _ => common::Genotype::WithNoCall,
+        };
+        let dp_ok = match sample.get(&key::READ_DEPTH) {
+            Some(Some(Value::Integer(dp))) => *dp >= args.min_dp,
+            _ => true,
+        };
+        let gq_ok = match sample.get(&key::CONDITIONAL_GENOTYPE_QUALITY) {
+            Some(Some(Value::Integer(gq))) => *gq >= args.min_gq,
+            Some(Some(Value::Float(gq))) => *gq >= args.min_gq as f32,
+            _ => true,
+        };
+        result.push(match gt {
+            _ if !dp_ok || !gq_ok => None,
+            common::Genotype::HomRef => Some(0),
+            common::Genotype::Het => Some(1),
+            common::Genotype::HomAlt => Some(2),
+            common::Genotype::WithNoCall => None,
+        });
+    }
+    Ok(result)
+}
+
+/// Main entry point for `seqvars qc relatedness` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    let before_anything = std::time::Instant::now();
+    tracing::info!("args_common = {:#?}", &args_common);
+    tracing::info!("args = {:#?}", &args);
+
+    tracing::info!("opening input file...");
+    let mut reader = open_vcf_reader(&args.path_in)
+        .await
+        .map_err(|e| anyhow::anyhow!("could not open input file: {}", e))?;
+    let header = reader
+        .read_header()
+        .await
+        .map_err(|e| anyhow::anyhow!("problem reading header: {}", e))?;
+    let pedigree = if let Some(path_ped) = args.path_ped.as_ref() {
+        mehari::ped::PedigreeByName::from_path(path_ped)
+            .map_err(|e| anyhow::anyhow!("problem parsing PED file: {}", e))?
+    } else {
+        common::extract_pedigree_and_case_uuid(&header)?.0
+    };
+
+    tracing::info!("counting genotypes...");
+    let samples = header.sample_names().iter().cloned().collect::<Vec<_>>();
+    let mut counter = Counter::new(&samples, &pedigree);
+    let mut records = reader.records(&header);
+    while let Some(record) = records
+        .try_next()
+        .await
+        .map_err(|e| anyhow::anyhow!("problem reading record: {}", e))?
+    {
+        let chrom = annonars::common::cli::canonicalize(&record.chromosome().to_string());
+        if matches!(chrom.as_str(), "X" | "Y" | "MT") {
+            continue;
+        }
+        let is_snv = record.reference_bases().len() == 1
+            && record
+                .alternate_bases()
+                .iter()
+                .all(|allele| allele.to_string().len() == 1);
+        let (frequency, _) = crate::seqvars::prefilter::get_freq_and_distance(&record)?;
+        counter.register(
+            &alt_counts(&record, args)?,
+            is_snv && frequency >= args.min_af,
+        );
+    }
+    let report = counter.finalize();
+    tracing::info!(
+        "... done using {} sites for relatedness estimation",
+        report.count_relatedness_sites.separate_with_commas()
+    );
+    for pair in report.pairs.iter().filter(|pair| pair.mismatch) {
+        tracing::warn!(
+            "{}: relatedness of {} and {} does not match pedigree: {:?} vs. {:?}",
+            crate::codes::QC_RELATEDNESS_PEDIGREE_MISMATCH,
+            &pair.sample_a,
+            &pair.sample_b,
+            &pair.degree,
+            &pair.expected_degree
+        );
+    }
+
+    serde_json::to_writer_pretty(
+        crate::common::io::std::open_write_maybe_bgzf(
+            &args.path_out,
+            args_common.compression_level,
+        )?,
+        &report,
+    )
+    .map_err(|e| anyhow::anyhow!("could not write relatedness report: {}", e))?;
+
+    tracing::info!(
+        "All of `seqvars qc relatedness` completed in {:?}",
+        before_anything.elapsed()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Degree;
+
+    #[rstest::rstest]
+    #[case(0.5, Degree::Duplicate)]
+    #[case(0.25, Degree::First)]
+    #[case(0.125, Degree::Second)]
+    #[case(0.0625, Degree::Third)]
+    #[case(0.0, Degree::Unrelated)]
+    #[case(-0.2, Degree::Unrelated)]
+    fn degree_from_kinship(#[case] phi: f64, #[case] expected: Degree) {
+        assert_eq!(Degree::from_kinship(phi), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(0, 0, 0, true)]
+    #[case(1, 0, 1, true)]
+    #[case(1, 0, 0, false)]
+    #[case(2, 1, 1, true)]
+    #[case(2, 2, 0, false)]
+    #[case(0, 2, 1, false)]
+    #[case(1, 2, 2, false)]
+    fn is_mendelian(#[case] c: u8, #[case] f: u8, #[case] m: u8, #[case] expected: bool) {
+        assert_eq!(super::is_mendelian(c, f, m), expected);
+    }
+
+    #[test]
+    fn counter() -> Result<(), anyhow::Error> {
+        let pedigree =
+            mehari::ped::PedigreeByName::from_path("tests/seqvars/add_sample/Case_1.ped")
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+        let samples = [
+            "Case_1_father-N1-DNA1-WGS1",
+            "Case_1_index-N1-DNA1-WGS1",
+            "Case_1_mother-N1-DNA1-WGS1",
+        ]
+        .map(String::from);
+        let mut counter = super::Counter::new(&samples, &pedigree);
+        // father, index, mother
+        for (alt_counts, for_relatedness) in [
+            ([Some(1), Some(1), Some(0)], true),
+            ([Some(1), Some(0), Some(1)], true),
+            ([Some(0), Some(1), Some(1)], true),
+            ([Some(2), Some(1), Some(0)], true),
+            ([Some(2), Some(2), Some(1)], true),
+            ([Some(0), Some(2), Some(0)], false),
+            ([None, Some(1), Some(1)], false),
+            ([Some(0), Some(0), Some(0)], false),
+        ] {
+            counter.register(&alt_counts, for_relatedness);
+        }
+        insta::assert_yaml_snapshot!(counter.finalize());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn smoke_test() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_out = tmpdir.join("relatedness.json");
+
+        let args = super::Args {
+            path_in: "tests/seqvars/query/Case_1.ingested.vcf".into(),
+            path_ped: None,
+            path_out: path_out.to_str().unwrap().into(),
+            min_af: 0.01,
+            min_dp: 10,
+            min_gq: 20,
+        };
+        super::run(&crate::common::Args::default(), &args).await?;
+
+        insta::assert_snapshot!(std::fs::read_to_string(&path_out)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,398 @@
+//! Implementation of `seqvars qc sex` subcommand.
+//!
+//! The genetic sex of each sample is inferred from the fraction of het. calls on chrX
+//! outside of the pseudoautosomal regions and from the depth of the calls on chrY relative
+//! to the autosomes.  Conflicts with the sex declared in the pedigree are logged as
+//! warnings and flagged in the JSON result.
+
+use futures::TryStreamExt as _;
+use mehari::common::noodles::open_vcf_reader;
+use noodles_vcf as vcf;
+use vcf::record::genotypes::{keys::key, sample::Value};
+
+use crate::{
+    common::{self, GenomeRelease},
+    seqvars::prefilter_stats::{location, Location},
+};
+
+/// Command line arguments for `seqvars qc sex` subcommand.
+#[derive(Debug, clap::Parser)]
+#[command(author, version, about = "infer genetic sex of samples in an ingested VCF", long_about = None)]
+pub struct Args {
+    /// The assumed genome build.
+    #[clap(long)]
+    pub genomebuild: GenomeRelease,
+    /// Path to input file.
+    #[clap(long)]
+    pub path_in: String,
+    /// Path to the pedigree file; optional.
+    ///
+    /// If not given, the pedigree from the header of the input file is used.
+    #[clap(long)]
+    pub path_ped: Option<String>,
+    /// Path to output JSON file.
+    #[clap(long)]
+    pub path_out: String,
+    /// Minimal number of non-ref. chrX calls for inferring the sex.
+    #[clap(long, default_value_t = 20)]
+    pub min_chrx_calls: usize,
+    /// Maximal fraction of het. chrX calls for male samples.
+    #[clap(long, default_value_t = 0.1)]
+    pub max_male_het_ratio: f64,
+    /// Minimal fraction of het. chrX calls for female samples.
+    #[clap(long, default_value_t = 0.25)]
+    pub min_female_het_ratio: f64,
+    /// Minimal ratio of chrY to autosomal depth for male samples, if chrY calls exist.
+    #[clap(long, default_value_t = 0.2)]
+    pub min_male_chry_dp_ratio: f64,
+}
+
+/// Sex of a sample, declared or inferred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sex {
+    /// Male.
+    Male,
+    /// Female.
+    Female,
+    /// Unknown or not inferrable.
+    Unknown,
+}
+
+impl From<mehari::ped::Sex> for Sex {
+    fn from(sex: mehari::ped::Sex) -> Self {
+        match sex {
+            mehari::ped::Sex::Male => Sex::Male,
+            mehari::ped::Sex::Female => Sex::Female,
+            mehari::ped::Sex::Unknown => Sex::Unknown,
+        }
+    }
+}
+
+/// Running sum for computing a mean.
+#[derive(Debug, Default, Clone, Copy)]
+struct Mean {
+    sum: f64,
+    count: usize,
+}
+
+impl Mean {
+    /// Add `value`.
+    fn register(&mut self, value: f64) {
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// Return the mean, `None` if no value was added.
+    fn value(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+/// Counts of one sample.
+#[derive(Debug, Default, Clone)]
+struct Counts {
+    chrx_het: usize,
+    chrx_hom_alt: usize,
+    autosomal_dp: Mean,
+    chry_dp: Mean,
+}
+
+/// The sex check result of one sample.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SampleResult {
+    /// The sample name.
+    pub sample: String,
+    /// The sex declared in the pedigree.
+    pub declared_sex: Sex,
+    /// The inferred genetic sex.
+    pub inferred_sex: Sex,
+    /// Number of het. calls on chrX outside of the pseudoautosomal regions.
+    pub chrx_het: usize,
+    /// Number of hom. alt. calls on chrX outside of the pseudoautosomal regions.
+    pub chrx_hom_alt: usize,
+    /// Fraction of het. calls among the non-ref. chrX calls.
+    pub chrx_het_ratio: Option<f64>,
+    /// Mean depth of the autosomal calls.
+    pub autosomal_mean_dp: Option<f64>,
+    /// Mean depth of the calls on chrY outside of the pseudoautosomal regions.
+    pub chry_mean_dp: Option<f64>,
+    /// Ratio of the chrY to the autosomal mean depth.
+    pub chry_dp_ratio: Option<f64>,
+    /// Whether the inferred and the declared sex conflict.
+    pub conflict: bool,
+}
+
+/// Accumulation of the per-sample counts.
+#[derive(Debug)]
+pub struct Counter {
+    /// The assumed genome build.
+    genome_release: GenomeRelease,
+    /// The sample names.
+    samples: Vec<String>,
+    /// The counts, in the order of `samples`.
+    counts: Vec<Counts>,
+}
+
+impl Counter {
+    /// Construct for `samples`.
+    pub fn new(genome_release: GenomeRelease, samples: &[String]) -> Self {
+        Self {
+            genome_release,
+            samples: samples.to_vec(),
+            counts: vec![Default::default(); samples.len()],
+        }
+    }
+
+    /// Register one ingested `record`.
+    pub fn register(&mut self, record: &vcf::Record) -> Result<(), anyhow::Error> {
+        let chrom = annonars::common::cli::canonicalize(&record.chromosome().to_string());
+        if chrom == "MT" {
+            return Ok(());
+        }
+        let location = location(
+            self.genome_release,
+            &chrom,
+            usize::from(record.position()) as i32,
+        );
+        for (sample, counts) in record.genotypes().values().zip(self.counts.iter_mut()) {
+            let gt = match sample.get(&key::GENOTYPE) {
+                Some(Some(Value::String(gt))) => gt.parse()?,
+                _ => common::Genotype::WithNoCall,
+            };
+            if gt == common::Genotype::WithNoCall {
+                continue;
+            }
+            let dp = match sample.get(&key::READ_DEPTH) {
+                Some(Some(Value::Integer(dp))) => Some(*dp as f64),
+                _ => None,
+            };
+            match location {
+                Location::X => match gt {
+                    common::Genotype::Het => counts.chrx_het += 1,
+                    common::Genotype::HomAlt => counts.chrx_hom_alt += 1,
+                    _ => (),
+                },
+                Location::Y => {
+                    if let Some(dp) = dp {
+                        counts.chry_dp.register(dp);
+                    }
+                }
+                Location::Other => {
+                    if let (Some(dp), false) = (dp, matches!(chrom.as_str(), "X" | "Y")) {
+                        counts.autosomal_dp.register(dp);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Infer the sex of the samples and compare to `pedigree`.
+    pub fn finalize(
+        self,
+        args: &Args,
+        pedigree: &mehari::ped::PedigreeByName,
+    ) -> Vec<SampleResult> {
+        self.samples
+            .into_iter()
+            .zip(self.counts)
+            .map(|(sample, counts)| {
+                let chrx_calls = counts.chrx_het + counts.chrx_hom_alt;
+                let chrx_het_ratio =
+                    (chrx_calls > 0).then(|| counts.chrx_het as f64 / chrx_calls as f64);
+                let autosomal_mean_dp = counts.autosomal_dp.value();
+                let chry_mean_dp = counts.chry_dp.value();
+                let chry_dp_ratio = match (chry_mean_dp, autosomal_mean_dp) {
+                    (Some(chry), Some(autosomal)) if autosomal > 0.0 => Some(chry / autosomal),
+                    _ => None,
+                };
+
+                let inferred_sex = match chrx_het_ratio {
+                    Some(_) if chrx_calls < args.min_chrx_calls => Sex::Unknown,
+                    Some(ratio) if ratio <= args.max_male_het_ratio => match chry_dp_ratio {
+                        Some(ratio) if ratio < args.min_male_chry_dp_ratio => Sex::Unknown,
+                        _ => Sex::Male,
+                    },
+                    Some(ratio) if ratio >= args.min_female_het_ratio => match chry_dp_ratio {
+                        Some(ratio) if ratio >= args.min_male_chry_dp_ratio => Sex::Unknown,
+                        _ => Sex::Female,
+                    },
+                    _ => Sex::Unknown,
+                };
+                let declared_sex = pedigree
+                    .individuals
+                    .get(&sample)
+                    .map(|individual| Sex::from(individual.sex))
+                    .unwrap_or(Sex::Unknown);
+                let conflict = declared_sex != Sex::Unknown
+                    && inferred_sex != Sex::Unknown
+                    && declared_sex != inferred_sex;
+
+                SampleResult {
+                    sample,
+                    declared_sex,
+                    inferred_sex,
+                    chrx_het: counts.chrx_het,
+                    chrx_hom_alt: counts.chrx_hom_alt,
+                    chrx_het_ratio,
+                    autosomal_mean_dp,
+                    chry_mean_dp,
+                    chry_dp_ratio,
+                    conflict,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Main entry point for `seqvars qc sex` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    let before_anything = std::time::Instant::now();
+    tracing::info!("args_common = {:#?}", &args_common);
+    tracing::info!("args = {:#?}", &args);
+
+    tracing::info!("opening input file...");
+    let mut reader = open_vcf_reader(&args.path_in)
+        .await
+        .map_err(|e| anyhow::anyhow!("could not open input file: {}", e))?;
+    let header = reader
+        .read_header()
+        .await
+        .map_err(|e| anyhow::anyhow!("problem reading header: {}", e))?;
+    let pedigree = if let Some(path_ped) = args.path_ped.as_ref() {
+        mehari::ped::PedigreeByName::from_path(path_ped)
+            .map_err(|e| anyhow::anyhow!("problem parsing PED file: {}", e))?
+    } else {
+        common::extract_pedigree_and_case_uuid(&header)?.0
+    };
+
+    tracing::info!("counting genotypes...");
+    let samples = header.sample_names().iter().cloned().collect::<Vec<_>>();
+    let mut counter = Counter::new(args.genomebuild, &samples);
+    let mut records = reader.records(&header);
+    while let Some(record) = records
+        .try_next()
+        .await
+        .map_err(|e| anyhow::anyhow!("problem reading record: {}", e))?
+    {
+        counter.register(&record)?;
+    }
+    let results = counter.finalize(args, &pedigree);
+    for result in &results {
+        if result.conflict {
+            tracing::warn!(
+                "{}: sample {} declared as {:?} but inferred as {:?}",
+                crate::codes::QC_SEX_PEDIGREE_MISMATCH,
+                &result.sample,
+                result.declared_sex,
+                result.inferred_sex
+            );
+        } else {
+            tracing::info!(
+                "sample {} declared as {:?}, inferred as {:?}",
+                &result.sample,
+                result.declared_sex,
+                result.inferred_sex
+            );
+        }
+    }
+
+    serde_json::to_writer_pretty(
+        crate::common::io::std::open_write_maybe_bgzf(
+            &args.path_out,
+            args_common.compression_level,
+        )?,
+        &results,
+    )
+    .map_err(|e| anyhow::anyhow!("could not write sex check result: {}", e))?;
+
+    tracing::info!(
+        "All of `seqvars qc sex` completed in {:?}",
+        before_anything.elapsed()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use noodles_vcf as vcf;
+
+    use crate::common::GenomeRelease;
+
+    fn args(path_out: &str) -> super::Args {
+        super::Args {
+            genomebuild: GenomeRelease::Grch37,
+            path_in: "tests/seqvars/query/Case_1.ingested.vcf".into(),
+            path_ped: None,
+            path_out: path_out.into(),
+            min_chrx_calls: 2,
+            max_male_het_ratio: 0.1,
+            min_female_het_ratio: 0.25,
+            min_male_chry_dp_ratio: 0.2,
+        }
+    }
+
+    #[test]
+    fn counter() -> Result<(), anyhow::Error> {
+        use vcf::header::record::value::{map::Format, Map};
+        use vcf::record::genotypes::keys::key;
+
+        let header = vcf::Header::builder()
+            .add_format(key::GENOTYPE, Map::<Format>::from(&key::GENOTYPE))
+            .add_format(key::READ_DEPTH, Map::<Format>::from(&key::READ_DEPTH))
+            .add_sample_name("father")
+            .add_sample_name("mother")
+            .add_sample_name("index")
+            .build();
+        let pedigree = mehari::ped::PedigreeByName {
+            individuals: [
+                ("father", mehari::ped::Sex::Male),
+                ("mother", mehari::ped::Sex::Female),
+                ("index", mehari::ped::Sex::Female),
+            ]
+            .into_iter()
+            .map(|(name, sex)| {
+                (
+                    name.to_string(),
+                    mehari::ped::Individual {
+                        name: name.to_string(),
+                        sex,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect(),
+        };
+        let mut counter = super::Counter::new(
+            GenomeRelease::Grch37,
+            &["father", "mother", "index"].map(String::from),
+        );
+        for line in [
+            "1\t1000000\t.\tA\tG\t.\t.\t.\tGT:DP\t0/1:30\t0/1:30\t0/1:30",
+            "X\t3000000\t.\tA\tG\t.\t.\t.\tGT:DP\t1:15\t0/1:30\t1/1:15",
+            "X\t3000100\t.\tC\tA\t.\t.\t.\tGT:DP\t1:15\t0/1:30\t1/1:15",
+            "X\t3000200\t.\tC\tA\t.\t.\t.\tGT:DP\t1:15\t1/1:30\t1/1:15",
+            "Y\t3000000\t.\tA\tC\t.\t.\t.\tGT:DP\t1:14\t./.:.\t0/0:14",
+        ] {
+            let record = vcf::Record::try_from((&header, line))?;
+            counter.register(&record)?;
+        }
+        insta::assert_yaml_snapshot!(counter.finalize(&args("out.json"), &pedigree));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn smoke_test() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_out = tmpdir.join("sex.json");
+
+        let args = args(path_out.to_str().unwrap());
+        super::run(&crate::common::Args::default(), &args).await?;
+
+        insta::assert_snapshot!(std::fs::read_to_string(&path_out)?);
+
+        Ok(())
+    }
+}
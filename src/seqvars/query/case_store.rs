@@ -0,0 +1,570 @@
+//! Storage backends for the sequence variants of a case.
+//!
+//! The query engine reads the variants of a case through the `CaseStore` trait.  The
+//! default backend reads the ingested VCF file front to back.  Alternatively, the
+//! variants can be materialized into a per-case RocksDB keyed by position so that
+//! subsequent queries can be run without re-parsing the VCF file and region queries
+//! only read the records in the regions.  The per-case RocksDB records the path, size,
+//! and modification time of the VCF file it was built from and is rebuilt when these
+//! change.
+
+use std::{path::Path, pin::Pin};
+
+use futures::{Stream, StreamExt, TryStreamExt};
+use mehari::{annotate::seqvars::CHROM_TO_CHROM_NO, common::noodles::open_vcf_reader};
+use noodles_vcf as vcf;
+
+use super::schema::{GenomicRegion, SequenceVariant};
+use crate::common;
+
+/// Alias for the stream of sequence variants read from a case store.
+pub type VariantStream<'a> =
+    Pin<Box<dyn Stream<Item = Result<SequenceVariant, anyhow::Error>> + 'a>>;
+
+/// Access to the sequence variants of a case.
+pub trait CaseStore {
+    /// Stream all variants of the case in coordinate order.
+    fn variants(&self) -> VariantStream<'_>;
+
+    /// Stream the variants of the case that may overlap with any of `regions`.
+    ///
+    /// Implementations may return more variants than overlapping with the regions;
+    /// the query interpreter applies the regions allow list in any case.
+    fn variants_in_regions(&self, regions: &[GenomicRegion]) -> VariantStream<'_>;
+}
+
+/// Case store that reads the variants from an ingested VCF file.
+#[derive(Debug, Clone)]
+pub struct VcfCaseStore {
+    /// Path to the ingested VCF file.
+    path: String,
+}
+
+impl VcfCaseStore {
+    /// Construct new store for the VCF file at `path`.
+    pub fn new(path: &str) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl CaseStore for VcfCaseStore {
+    fn variants(&self) -> VariantStream<'_> {
+        let path = self.path.clone();
+        let open = async move {
+            let mut reader = open_vcf_reader(&path)
+                .await
+                .map_err(|e| anyhow::anyhow!("could not open file {} for reading: {}", path, e))?;
+            let header = reader.read_header().await?;
+            Ok::<_, anyhow::Error>(futures::stream::try_unfold(
+                (reader, header),
+                |(mut reader, header)| async move {
+                    let mut record = vcf::Record::default();
+                    if reader
+                        .read_record(&header, &mut record)
+                        .await
+                        .map_err(|e| anyhow::anyhow!("could not read VCF record: {}", e))?
+                        == 0
+                    {
+                        return Ok(None);
+                    }
+                    let seqvar = SequenceVariant::from_vcf(&record, &header)
+                        .map_err(|e| anyhow::anyhow!("could not parse VCF record: {}", e))?;
+                    Ok(Some((seqvar, (reader, header))))
+                },
+            ))
+        };
+
+        Box::pin(futures::stream::once(open).try_flatten())
+    }
+
+    fn variants_in_regions(&self, regions: &[GenomicRegion]) -> VariantStream<'_> {
+        // Without an index, we have to read the whole file.
+        let regions = Regions::new(regions);
+        Box::pin(
+            self.variants()
+                .try_filter(move |seqvar| futures::future::ready(regions.may_contain(seqvar))),
+        )
+    }
+}
+
+/// Name of the column family with meta information.
+const CF_META: &str = "meta";
+/// Name of the column family with the variants.
+const CF_VARIANTS: &str = "variants";
+/// Value of the `db-name` meta entry.
+const DB_NAME: &str = "seqvars-case";
+/// Name of the meta entry with the maximal reference allele length.
+const META_MAX_REFERENCE_LENGTH: &str = "max-reference-length";
+/// Name of the meta entry with the fingerprint of the source VCF file.
+const META_SOURCE_FINGERPRINT: &str = "source-fingerprint";
+
+/// Return the fingerprint of the file at `path`, i.e., its canonical path, size, and
+/// modification time.
+pub fn source_fingerprint(path: &str) -> Result<String, anyhow::Error> {
+    let metadata = std::fs::metadata(path)
+        .map_err(|e| anyhow::anyhow!("could not read metadata of {}: {}", path, e))?;
+    let modified = metadata
+        .modified()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(format!(
+        "{}\t{}\t{}",
+        std::fs::canonicalize(path)?.display(),
+        metadata.len(),
+        modified.as_nanos()
+    ))
+}
+
+/// Case store backed by a per-case RocksDB.
+///
+/// The variants are stored as JSON in the `variants` column family, keyed by
+/// chromosome, 1-based position, reference, and alternative allele.  The chromosome is
+/// encoded by its number for the canonical chromosomes and by its name after these for
+/// other contigs (e.g., `GL000220.1` or decoys), see `chrom_key()`.
+pub struct RocksDbCaseStore {
+    /// The opened database.
+    db: rocksdb::DB,
+    /// Maximal length of any reference allele, used for region queries.
+    max_reference_length: i32,
+    /// Fingerprint of the source VCF file, see `source_fingerprint()`, if recorded.
+    source_fingerprint: Option<String>,
+}
+
+impl RocksDbCaseStore {
+    /// Return whether `path` looks like a per-case RocksDB.
+    pub fn exists(path: &Path) -> bool {
+        path.join("CURRENT").exists()
+    }
+
+    /// Open an existing per-case RocksDB at `path` for reading.
+    pub fn open(path: &Path) -> Result<Self, anyhow::Error> {
+        let options = rocksdb::Options::default();
+        let db = rocksdb::DB::open_cf_for_read_only(&options, path, [CF_META, CF_VARIANTS], false)
+            .map_err(|e| anyhow::anyhow!("could not open case RocksDB {:?}: {}", path, e))?;
+
+        let (max_reference_length, source_fingerprint) = {
+            let cf_meta = db.cf_handle(CF_META).expect("checked when opening");
+            let db_name = db.get_cf(&cf_meta, "db-name")?;
+            if db_name.as_deref() != Some(DB_NAME.as_bytes()) {
+                anyhow::bail!("{:?} is not a per-case sequence variant RocksDB", path);
+            }
+            let max_reference_length = db
+                .get_cf(&cf_meta, META_MAX_REFERENCE_LENGTH)?
+                .map(|value| String::from_utf8_lossy(&value).parse())
+                .transpose()?
+                .unwrap_or_default();
+            let source_fingerprint = db
+                .get_cf(&cf_meta, META_SOURCE_FINGERPRINT)?
+                .map(|value| String::from_utf8_lossy(&value).to_string());
+            (max_reference_length, source_fingerprint)
+        };
+
+        Ok(Self {
+            db,
+            max_reference_length,
+            source_fingerprint,
+        })
+    }
+
+    /// Return the fingerprint of the source VCF file, if recorded.
+    pub fn source_fingerprint(&self) -> Option<&str> {
+        self.source_fingerprint.as_deref()
+    }
+
+    /// Materialize the variants from `source` into a new per-case RocksDB at `path`,
+    /// recording `source_fingerprint`, see `source_fingerprint()`.
+    pub async fn materialize(
+        path: &Path,
+        source: &dyn CaseStore,
+        source_fingerprint: &str,
+    ) -> Result<usize, anyhow::Error> {
+        let options = rocksdb_utils_lookup::tune_options(rocksdb::Options::default(), None);
+        let db = rocksdb::DB::open_cf(&options, path, [CF_META, CF_VARIANTS])
+            .map_err(|e| anyhow::anyhow!("could not create case RocksDB {:?}: {}", path, e))?;
+
+        let mut count = 0;
+        let mut max_reference_length = 0;
+        {
+            let cf_variants = db.cf_handle(CF_VARIANTS).expect("just created");
+            let mut variants = source.variants();
+            while let Some(seqvar) = variants.try_next().await? {
+                max_reference_length =
+                    std::cmp::max(max_reference_length, seqvar.reference.len() as i32);
+                db.put_cf(
+                    &cf_variants,
+                    variant_key(&seqvar),
+                    serde_json::to_vec(&seqvar)?,
+                )?;
+                count += 1;
+            }
+        }
+
+        let cf_meta = db.cf_handle(CF_META).expect("just created");
+        db.put_cf(&cf_meta, "varfish-worker-version", common::worker_version())?;
+        db.put_cf(&cf_meta, "db-name", DB_NAME)?;
+        db.put_cf(
+            &cf_meta,
+            META_MAX_REFERENCE_LENGTH,
+            max_reference_length.to_string(),
+        )?;
+        db.put_cf(&cf_meta, META_SOURCE_FINGERPRINT, source_fingerprint)?;
+        db.flush_cf(&cf_meta)?;
+        db.flush_cf(&db.cf_handle(CF_VARIANTS).expect("just created"))?;
+
+        Ok(count)
+    }
+
+    /// Iterate the variants with keys in `[from, to)`, `to` may be `None` for the end.
+    fn variants_between(
+        &self,
+        from: Vec<u8>,
+        to: Option<Vec<u8>>,
+    ) -> impl Iterator<Item = Result<SequenceVariant, anyhow::Error>> + '_ {
+        let cf_variants = self
+            .db
+            .cf_handle(CF_VARIANTS)
+            .expect("checked when opening");
+        self.db
+            .iterator_cf(
+                &cf_variants,
+                rocksdb::IteratorMode::From(&from, rocksdb::Direction::Forward),
+            )
+            .take_while(move |item| match (item, to.as_ref()) {
+                (Ok((key, _)), Some(to)) => key.as_ref() < to.as_slice(),
+                _ => true,
+            })
+            .map(|item| {
+                let (_, value) = item?;
+                Ok(serde_json::from_slice(&value)?)
+            })
+    }
+}
+
+impl CaseStore for RocksDbCaseStore {
+    fn variants(&self) -> VariantStream<'_> {
+        Box::pin(futures::stream::iter(
+            self.variants_between(Vec::new(), None),
+        ))
+    }
+
+    fn variants_in_regions(&self, regions: &[GenomicRegion]) -> VariantStream<'_> {
+        let ranges = Regions::new(regions).key_ranges(self.max_reference_length);
+        Box::pin(futures::stream::iter(ranges).flat_map(move |(from, to)| {
+            futures::stream::iter(self.variants_between(from, Some(to)))
+        }))
+    }
+}
+
+/// Open the case store for the query input at `path_input`.
+///
+/// If `path_case_rocksdb` is given, the per-case RocksDB is used and created from
+/// `path_input` first if it does not exist yet or was built from a different version of
+/// `path_input`, see `source_fingerprint()`.
+pub async fn open(
+    path_input: &str,
+    path_case_rocksdb: Option<&str>,
+) -> Result<Box<dyn CaseStore>, anyhow::Error> {
+    let vcf_store = VcfCaseStore::new(path_input);
+    let Some(path_case_rocksdb) = path_case_rocksdb else {
+        return Ok(Box::new(vcf_store));
+    };
+
+    let path = Path::new(path_case_rocksdb);
+    let fingerprint = source_fingerprint(path_input)?;
+    if RocksDbCaseStore::exists(path) {
+        let store = RocksDbCaseStore::open(path)?;
+        if store.source_fingerprint() == Some(fingerprint.as_str()) {
+            tracing::info!("Using existing case RocksDB {:?}", path);
+            return Ok(Box::new(store));
+        }
+        tracing::info!(
+            "Case RocksDB {:?} was not built from the current {}, removing it",
+            path,
+            path_input
+        );
+        drop(store);
+        std::fs::remove_dir_all(path)?;
+    }
+
+    tracing::info!(
+        "Materializing {} into case RocksDB {:?}...",
+        path_input,
+        path
+    );
+    let before_materializing = std::time::Instant::now();
+    let count = RocksDbCaseStore::materialize(path, &vcf_store, &fingerprint).await?;
+    tracing::info!(
+        "... done materializing {} records in {:?}",
+        count,
+        before_materializing.elapsed()
+    );
+
+    Ok(Box::new(RocksDbCaseStore::open(path)?))
+}
+
+/// Key prefix of contigs that are not canonical chromosomes, sorts after these.
+const OTHER_CONTIG_PREFIX: u8 = u8::MAX;
+
+/// Return the key prefix of `chrom`.
+///
+/// This is the chromosome number for canonical chromosomes.  Other contigs are keyed by
+/// `OTHER_CONTIG_PREFIX` and their canonicalized, NUL-terminated name.
+fn chrom_key(chrom: &str) -> Vec<u8> {
+    let canonical = annonars::common::cli::canonicalize(chrom);
+    match CHROM_TO_CHROM_NO
+        .get(chrom)
+        .or_else(|| CHROM_TO_CHROM_NO.get(&canonical))
+    {
+        Some(chrom_no) => vec![*chrom_no as u8],
+        None => {
+            let mut result = vec![OTHER_CONTIG_PREFIX];
+            result.extend_from_slice(canonical.as_bytes());
+            result.push(0);
+            result
+        }
+    }
+}
+
+/// Return the key of the position `pos` on the chromosome with key prefix `chrom_key`.
+fn position_key(chrom_key: &[u8], pos: i32) -> Vec<u8> {
+    let mut result = chrom_key.to_vec();
+    result.extend_from_slice(&(std::cmp::max(pos, 0) as u32).to_be_bytes());
+    result
+}
+
+/// Return the key of `seqvar` in the variants column family.
+fn variant_key(seqvar: &SequenceVariant) -> Vec<u8> {
+    let mut result = position_key(&chrom_key(&seqvar.chrom), seqvar.pos);
+    result.extend_from_slice(seqvar.reference.as_bytes());
+    result.push(b':');
+    result.extend_from_slice(seqvar.alternative.as_bytes());
+    result
+}
+
+/// Merged, 1-based regions by chromosome key prefix, see `chrom_key()`.
+#[derive(Debug, Clone, Default)]
+struct Regions {
+    /// Ranges by chromosome key prefix, sorted and non-overlapping.
+    ranges: Vec<(Vec<u8>, i32, i32)>,
+}
+
+impl Regions {
+    /// Construct from genomic regions.
+    fn new(regions: &[GenomicRegion]) -> Self {
+        let mut ranges = regions
+            .iter()
+            .map(|region| {
+                let chrom_key = chrom_key(&region.chrom);
+                match region.range.as_ref() {
+                    Some(range) => (chrom_key, range.start, range.end),
+                    None => (chrom_key, 1, i32::MAX),
+                }
+            })
+            .collect::<Vec<_>>();
+        ranges.sort();
+
+        let mut merged: Vec<(Vec<u8>, i32, i32)> = Vec::new();
+        for (chrom_key, start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if last.0 == chrom_key && start <= last.2.saturating_add(1) => {
+                    last.2 = std::cmp::max(last.2, end);
+                }
+                _ => merged.push((chrom_key, start, end)),
+            }
+        }
+
+        Self { ranges: merged }
+    }
+
+    /// Return whether `seqvar` may overlap with any of the regions.
+    fn may_contain(&self, seqvar: &SequenceVariant) -> bool {
+        let chrom_key = chrom_key(&seqvar.chrom);
+        let end = seqvar.pos + seqvar.reference.len() as i32 - 1;
+        self.ranges
+            .iter()
+            .any(|(key, start, stop)| *key == chrom_key && *start <= end && *stop >= seqvar.pos)
+    }
+
+    /// Return the key ranges `[from, to)` to read, given the maximal reference length.
+    fn key_ranges(&self, max_reference_length: i32) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.ranges
+            .iter()
+            .map(|(chrom_key, start, end)| {
+                (
+                    position_key(
+                        chrom_key,
+                        start.saturating_sub(max_reference_length.saturating_sub(1)),
+                    ),
+                    position_key(chrom_key, end.saturating_add(1)),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::TryStreamExt;
+
+    use super::{CaseStore, RocksDbCaseStore, VcfCaseStore};
+    use crate::seqvars::query::schema::{GenomicRegion, Range, SequenceVariant};
+
+    fn build_seqvar(chrom: &str, pos: i32, reference: &str, alternative: &str) -> SequenceVariant {
+        SequenceVariant {
+            chrom: chrom.into(),
+            pos,
+            reference: reference.into(),
+            alternative: alternative.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Case store with fixed variants for testing.
+    struct FixedCaseStore(Vec<SequenceVariant>);
+
+    impl CaseStore for FixedCaseStore {
+        fn variants(&self) -> super::VariantStream<'_> {
+            Box::pin(futures::stream::iter(self.0.iter().cloned().map(Ok)))
+        }
+
+        fn variants_in_regions(&self, _regions: &[GenomicRegion]) -> super::VariantStream<'_> {
+            self.variants()
+        }
+    }
+
+    fn coordinates(seqvars: &[SequenceVariant]) -> Vec<(String, i32)> {
+        seqvars
+            .iter()
+            .map(|seqvar| (seqvar.chrom.clone(), seqvar.pos))
+            .collect()
+    }
+
+    #[rstest::rstest]
+    #[case::whole_chrom(vec![("2", None)], vec![("2", 50)])]
+    #[case::range(vec![("1", Some((100, 200)))], vec![("1", 100), ("1", 200)])]
+    #[case::deletion_overlapping(vec![("1", Some((1_003, 1_010)))], vec![("1", 1_000)])]
+    #[case::merged(
+        vec![("chr1", Some((150, 250))), ("1", Some((90, 160)))],
+        vec![("1", 100), ("1", 200)]
+    )]
+    #[case::none(vec![("X", None)], vec![])]
+    #[case::other_contig(vec![("GL000220.1", Some((1, 20)))], vec![("GL000220.1", 10)])]
+    #[case::other_contig_chr_prefix(vec![("chrGL000220.1", None)], vec![("GL000220.1", 10)])]
+    #[tokio::test]
+    async fn rocksdb_case_store(
+        #[case] regions: Vec<(&str, Option<(i32, i32)>)>,
+        #[case] expected: Vec<(&str, i32)>,
+    ) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("case.rocksdb");
+
+        let source = FixedCaseStore(vec![
+            build_seqvar("2", 50, "A", "G"),
+            build_seqvar("1", 200, "C", "T"),
+            build_seqvar("1", 100, "A", "G"),
+            build_seqvar("1", 1_000, "ACGTACG", "A"),
+            build_seqvar("1", 300, "C", "T"),
+            build_seqvar("GL000220.1", 10, "A", "T"),
+        ]);
+        assert!(!RocksDbCaseStore::exists(&path));
+        assert_eq!(
+            RocksDbCaseStore::materialize(&path, &source, "fingerprint").await?,
+            6
+        );
+        assert!(RocksDbCaseStore::exists(&path));
+
+        let store = RocksDbCaseStore::open(&path)?;
+        assert_eq!(store.source_fingerprint(), Some("fingerprint"));
+        let all = store.variants().try_collect::<Vec<_>>().await?;
+        assert_eq!(
+            coordinates(&all),
+            vec![
+                ("1".to_string(), 100),
+                ("1".to_string(), 200),
+                ("1".to_string(), 300),
+                ("1".to_string(), 1_000),
+                ("2".to_string(), 50),
+                ("GL000220.1".to_string(), 10)
+            ]
+        );
+
+        let regions = regions
+            .into_iter()
+            .map(|(chrom, range)| GenomicRegion {
+                chrom: chrom.into(),
+                range: range.map(|(start, end)| Range { start, end }),
+            })
+            .collect::<Vec<_>>();
+        let selected = store
+            .variants_in_regions(&regions)
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(
+            coordinates(&selected),
+            expected
+                .into_iter()
+                .map(|(chrom, pos)| (chrom.to_string(), pos))
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn vcf_case_store() -> Result<(), anyhow::Error> {
+        let store = VcfCaseStore::new("tests/seqvars/query/Case_1.ingested.vcf");
+        let all = store.variants().try_collect::<Vec<_>>().await?;
+        let first = all.first().expect("file is not empty").clone();
+
+        let regions = vec![GenomicRegion {
+            chrom: first.chrom.clone(),
+            range: Some(Range {
+                start: first.pos,
+                end: first.pos,
+            }),
+        }];
+        let selected = store
+            .variants_in_regions(&regions)
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert!(!selected.is_empty());
+        assert!(selected.len() <= all.len());
+        assert!(selected
+            .iter()
+            .all(|seqvar| seqvar.chrom == first.chrom && seqvar.pos <= first.pos));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn open_rebuilds_outdated_rocksdb() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_input = tmpdir.join("case.vcf").to_str().unwrap().to_string();
+        let path_rocksdb = tmpdir.join("case.rocksdb").to_str().unwrap().to_string();
+        let content = std::fs::read_to_string("tests/seqvars/query/Case_1.ingested.vcf")?;
+
+        std::fs::write(&path_input, &content)?;
+        let store = super::open(&path_input, Some(&path_rocksdb)).await?;
+        let count = store.variants().try_collect::<Vec<_>>().await?.len();
+        assert!(count > 1);
+        drop(store);
+
+        // Unchanged input re-uses the database.
+        let store = super::open(&path_input, Some(&path_rocksdb)).await?;
+        assert_eq!(store.variants().try_collect::<Vec<_>>().await?.len(), count);
+        drop(store);
+
+        // Changed input leads to rebuilding the database.
+        let first_records = content
+            .lines()
+            .take_while(|line| line.starts_with('#'))
+            .chain(content.lines().find(|line| !line.starts_with('#')))
+            .map(|line| format!("{}\n", line))
+            .collect::<String>();
+        std::fs::write(&path_input, first_records)?;
+        let store = super::open(&path_input, Some(&path_rocksdb)).await?;
+        assert_eq!(store.variants().try_collect::<Vec<_>>().await?.len(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+//! Compound heterozygous analysis of the variants in one gene.
+//!
+//! Two heterozygous variants of the index are in trans if they have been inherited
+//! from different parents according to the trio genotypes or, if the parents do not
+//! allow to tell, if they lie on different haplotypes of the same phase set.
+
+use itertools::Itertools;
+
+use crate::common::Genotype;
+
+use super::schema::SequenceVariant;
+
+/// Origin of the alternative allele of a heterozygous variant in the index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AlleleOrigin {
+    /// Inherited from the parent with the given index in the parents list.
+    ///
+    /// If only one parent is given, the index `1` refers to the missing parent.
+    Parent(usize),
+    /// On the given haplotype (`0` or `1`) of the given phase set.
+    Haplotype { phase_set: i32, haplotype: usize },
+    /// The origin cannot be determined.
+    Unknown,
+}
+
+impl AlleleOrigin {
+    /// Whether the alleles with origins `self` and `other` are in trans.
+    pub fn is_trans(&self, other: &Self) -> bool {
+        match (self, other) {
+            (AlleleOrigin::Parent(a), AlleleOrigin::Parent(b)) => a != b,
+            (
+                AlleleOrigin::Haplotype {
+                    phase_set: ps_a,
+                    haplotype: a,
+                },
+                AlleleOrigin::Haplotype {
+                    phase_set: ps_b,
+                    haplotype: b,
+                },
+            ) => ps_a == ps_b && a != b,
+            _ => false,
+        }
+    }
+}
+
+/// Parse the genotype of `sample` in `seqvar`, `None` if there is no call.
+fn sample_genotype(
+    seqvar: &SequenceVariant,
+    sample: &str,
+) -> Result<Option<Genotype>, anyhow::Error> {
+    seqvar
+        .call_info
+        .get(sample)
+        .and_then(|call_info| call_info.genotype.as_ref())
+        .map(|gt| {
+            gt.parse()
+                .map_err(|e| anyhow::anyhow!("could not parse genotype of {}: {}", sample, e))
+        })
+        .transpose()
+}
+
+/// Determine the origin of the alternative allele of `seqvar` in the heterozygous
+/// `index`, using the genotypes of `parents` and falling back to the phasing.
+pub fn allele_origin(
+    seqvar: &SequenceVariant,
+    index: &str,
+    parents: &[String],
+) -> Result<AlleleOrigin, anyhow::Error> {
+    if sample_genotype(seqvar, index)? != Some(Genotype::Het) {
+        return Ok(AlleleOrigin::Unknown);
+    }
+
+    let parent_gts = parents
+        .iter()
+        .map(|parent| sample_genotype(seqvar, parent))
+        .collect::<Result<Vec<_>, _>>()?;
+    let het_parents = parent_gts
+        .iter()
+        .positions(|gt| *gt == Some(Genotype::Het))
+        .collect::<Vec<_>>();
+    let count_ref = parent_gts
+        .iter()
+        .filter(|gt| **gt == Some(Genotype::HomRef))
+        .count();
+    match (parents.len(), het_parents.as_slice(), count_ref) {
+        (1, [], 1) => return Ok(AlleleOrigin::Parent(1)),
+        (n, [i], count_ref) if count_ref + 1 == n => return Ok(AlleleOrigin::Parent(*i)),
+        _ => (),
+    }
+
+    let call_info = seqvar.call_info.get(index).expect("checked above");
+    let haplotype = match call_info.genotype.as_deref() {
+        Some("1|0") => 0,
+        Some("0|1") => 1,
+        _ => return Ok(AlleleOrigin::Unknown),
+    };
+    Ok(call_info
+        .phasing_id
+        .map_or(AlleleOrigin::Unknown, |phase_set| AlleleOrigin::Haplotype {
+            phase_set,
+            haplotype,
+        }))
+}
+
+/// Return the pairs of indices of the variants in `seqvars` that are compound
+/// heterozygous in `index`.
+pub fn comphet_pairs(
+    seqvars: &[SequenceVariant],
+    index: &str,
+    parents: &[String],
+) -> Result<Vec<(usize, usize)>, anyhow::Error> {
+    let origins = seqvars
+        .iter()
+        .map(|seqvar| allele_origin(seqvar, index, parents))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(origins
+        .iter()
+        .enumerate()
+        .tuple_combinations()
+        .filter(|((_, a), (_, b))| a.is_trans(b))
+        .map(|((i, _), (j, _))| (i, j))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::AlleleOrigin;
+    use crate::seqvars::query::{
+        fixtures::{call_info, call_infos, TRIO},
+        schema::{CallInfo, SequenceVariant},
+    };
+
+    /// Construct variant with genotypes given as `index,parent,...` and phase set.
+    fn seqvar(gts: &str, phasing_id: Option<i32>) -> SequenceVariant {
+        SequenceVariant {
+            call_info: call_infos(TRIO.into_iter().zip(gts.split(',')).map(|(sample, gt)| {
+                (
+                    sample,
+                    CallInfo {
+                        phasing_id,
+                        ..call_info(gt)
+                    },
+                )
+            })),
+            ..Default::default()
+        }
+    }
+
+    #[rstest::rstest]
+    #[case::paternal("0/1,0/1,0/0", &["father", "mother"], None, AlleleOrigin::Parent(0))]
+    #[case::maternal("0/1,0/0,0/1", &["father", "mother"], None, AlleleOrigin::Parent(1))]
+    #[case::both_het("0/1,0/1,0/1", &["father", "mother"], None, AlleleOrigin::Unknown)]
+    #[case::no_call("0/1,./.,0/1", &["father", "mother"], None, AlleleOrigin::Unknown)]
+    #[case::duo_het("0/1,0/1", &["father"], None, AlleleOrigin::Parent(0))]
+    #[case::duo_ref("0/1,0/0", &["father"], None, AlleleOrigin::Parent(1))]
+    #[case::hom_alt("1/1,0/1,0/1", &["father", "mother"], None, AlleleOrigin::Unknown)]
+    #[case::unphased("0/1", &[], Some(7), AlleleOrigin::Unknown)]
+    #[case::phased_no_ps("0|1", &[], None, AlleleOrigin::Unknown)]
+    #[case::phased("0|1", &[], Some(7), AlleleOrigin::Haplotype { phase_set: 7, haplotype: 1 })]
+    #[case::phase_fallback(
+        "1|0,0/1,0/1",
+        &["father", "mother"],
+        Some(7),
+        AlleleOrigin::Haplotype { phase_set: 7, haplotype: 0 },
+    )]
+    fn allele_origin(
+        #[case] gts: &str,
+        #[case] parents: &[&str],
+        #[case] phasing_id: Option<i32>,
+        #[case] expected: AlleleOrigin,
+    ) -> Result<(), anyhow::Error> {
+        let parents = parents.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            super::allele_origin(&seqvar(gts, phasing_id), "index", &parents)?,
+            expected
+        );
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case::trio(vec![("0/1,0/1,0/0", None), ("0/1,0/0,0/1", None)], vec![(0, 1)])]
+    #[case::trio_cis(vec![("0/1,0/1,0/0", None), ("0/1,0/1,0/0", None)], vec![])]
+    #[case::trio_three(
+        vec![("0/1,0/1,0/0", None), ("0/1,0/1,0/0", None), ("0/1,0/0,0/1", None)],
+        vec![(0, 2), (1, 2)],
+    )]
+    #[case::phased(vec![("0|1", Some(1)), ("1|0", Some(1))], vec![(0, 1)])]
+    #[case::phased_cis(vec![("0|1", Some(1)), ("0|1", Some(1))], vec![])]
+    #[case::phase_sets_differ(vec![("0|1", Some(1)), ("1|0", Some(2))], vec![])]
+    fn comphet_pairs(
+        #[case] gts: Vec<(&str, Option<i32>)>,
+        #[case] expected: Vec<(usize, usize)>,
+    ) -> Result<(), anyhow::Error> {
+        let seqvars = gts
+            .iter()
+            .map(|(gts, phasing_id)| seqvar(gts, *phasing_id))
+            .collect::<Vec<_>>();
+        let parents = if gts[0].0.contains(',') {
+            vec!["father".to_string(), "mother".to_string()]
+        } else {
+            vec![]
+        };
+
+        assert_eq!(super::comphet_pairs(&seqvars, "index", &parents)?, expected);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+//! Selection of de novo candidates for `seqvars query --mode denovo`.
+
+use mehari::ped::{Disease, PedigreeByName};
+
+use crate::common::{karyotype::Karyotype, Chrom, Genotype};
+
+use super::schema::{CallInfo, SequenceVariant};
+
+/// The index and its parents to look for de novo variants in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trio {
+    /// Name of the index sample.
+    pub index: String,
+    /// Name of the father sample.
+    pub father: String,
+    /// Name of the mother sample.
+    pub mother: String,
+}
+
+impl Trio {
+    /// Find the trio in `pedigree` for the given `index` or, if `None`, for the first
+    /// affected individual with both parents in the pedigree.
+    pub fn from_pedigree(
+        pedigree: &PedigreeByName,
+        index: Option<&str>,
+    ) -> Result<Self, anyhow::Error> {
+        let with_parents = |name: &str| {
+            let individual = pedigree.individuals.get(name)?;
+            match (individual.father.as_ref(), individual.mother.as_ref()) {
+                (Some(father), Some(mother))
+                    if pedigree.individuals.contains_key(father)
+                        && pedigree.individuals.contains_key(mother) =>
+                {
+                    Some(Self {
+                        index: name.to_string(),
+                        father: father.clone(),
+                        mother: mother.clone(),
+                    })
+                }
+                _ => None,
+            }
+        };
+
+        if let Some(index) = index {
+            with_parents(index).ok_or_else(|| {
+                anyhow::anyhow!("index {} does not have both parents in the pedigree", index)
+            })
+        } else {
+            pedigree
+                .individuals
+                .values()
+                .filter(|individual| individual.disease == Disease::Affected)
+                .find_map(|individual| with_parents(&individual.name))
+                .ok_or_else(|| {
+                    anyhow::anyhow!("no affected individual with both parents in the pedigree")
+                })
+        }
+    }
+}
+
+/// Filter for de novo candidates in a trio.
+#[derive(Debug, Clone)]
+pub struct DenovoFilter {
+    /// The trio to check.
+    pub trio: Trio,
+    /// Minimal depth of the reference calls of the parents.
+    pub min_dp: i32,
+    /// Minimal genotype quality of the reference calls of the parents.
+    pub min_gq: f32,
+    /// Karyotype of the index, if known; diploid gonosomes are assumed otherwise.
+    pub index_karyotype: Option<Karyotype>,
+}
+
+impl DenovoFilter {
+    /// Whether `call_info` is a hom. ref. call with sufficient depth and quality.
+    fn is_confident_ref(&self, call_info: Option<&CallInfo>) -> Result<bool, anyhow::Error> {
+        let Some(call_info) = call_info else {
+            return Ok(false);
+        };
+        Ok(genotype(call_info)? == Some(Genotype::HomRef)
+            && call_info.dp.map_or(false, |dp| dp >= self.min_dp)
+            && call_info.quality.map_or(false, |gq| gq >= self.min_gq))
+    }
+
+    /// Whether `seqvar` is called in the index and confidently ref. in the parents.
+    ///
+    /// The index must be het. unless the chromosome is haploid in its karyotype where
+    /// the call may also be hom. alt.  Only the parents that can transmit the chromosome
+    /// to the index are checked, e.g., only the mother on chrX for an XY index.
+    pub fn is_candidate(&self, seqvar: &SequenceVariant) -> Result<bool, anyhow::Error> {
+        let chrom: Chrom = annonars::common::cli::canonicalize(&seqvar.chrom).parse()?;
+        let (ploidy_x, ploidy_y) = self.index_karyotype.map_or((2, 0), |karyotype| {
+            (karyotype.ploidy(Chrom::X), karyotype.ploidy(Chrom::Y))
+        });
+        let ploidy = match chrom {
+            Chrom::Auto => 2,
+            Chrom::X => ploidy_x,
+            Chrom::Y if self.index_karyotype.is_none() => 2,
+            Chrom::Y => ploidy_y,
+        };
+
+        let index_gt = seqvar
+            .call_info
+            .get(&self.trio.index)
+            .map(genotype)
+            .transpose()?
+            .flatten();
+        let index_ok = match (ploidy, index_gt) {
+            (0, _) => false,
+            (1, Some(Genotype::Het | Genotype::HomAlt)) => true,
+            (_, gt) => gt == Some(Genotype::Het),
+        };
+        let check_father = !(chrom == Chrom::X && ploidy_x == 1 && ploidy_y > 0);
+        let check_mother = chrom != Chrom::Y;
+        Ok(index_ok
+            && (!check_father || self.is_confident_ref(seqvar.call_info.get(&self.trio.father))?)
+            && (!check_mother || self.is_confident_ref(seqvar.call_info.get(&self.trio.mother))?))
+    }
+}
+
+/// Parse the genotype of `call_info`, `None` if there is no genotype.
+fn genotype(call_info: &CallInfo) -> Result<Option<Genotype>, anyhow::Error> {
+    call_info
+        .genotype
+        .as_ref()
+        .map(|gt| gt.parse())
+        .transpose()
+        .map_err(|e| anyhow::anyhow!("could not parse genotype: {}", e))
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::ped::{Disease, Individual, PedigreeByName, Sex};
+
+    use super::{DenovoFilter, Trio};
+    use crate::common::karyotype::Karyotype;
+    use crate::seqvars::query::{
+        fixtures::{call_info, call_infos},
+        schema::{CallInfo, SequenceVariant},
+    };
+
+    fn pedigree() -> PedigreeByName {
+        let individual = |name: &str, father: Option<&str>, mother: Option<&str>, disease| {
+            (
+                name.to_string(),
+                Individual {
+                    family: "FAM".into(),
+                    name: name.into(),
+                    father: father.map(|s| s.to_string()),
+                    mother: mother.map(|s| s.to_string()),
+                    sex: Sex::Unknown,
+                    disease,
+                },
+            )
+        };
+        PedigreeByName {
+            individuals: [
+                individual("father", None, None, Disease::Unaffected),
+                individual("mother", None, None, Disease::Unaffected),
+                individual(
+                    "sibling",
+                    Some("father"),
+                    Some("mother"),
+                    Disease::Unaffected,
+                ),
+                individual("index", Some("father"), Some("mother"), Disease::Affected),
+                individual("other", Some("father"), None, Disease::Affected),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+
+    fn trio(index: &str) -> Trio {
+        Trio {
+            index: index.into(),
+            father: "father".into(),
+            mother: "mother".into(),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case::first_affected(None, Some(trio("index")))]
+    #[case::explicit(Some("sibling"), Some(trio("sibling")))]
+    #[case::missing_parent(Some("other"), None)]
+    #[case::no_parents(Some("father"), None)]
+    #[case::unknown(Some("unknown"), None)]
+    fn trio_from_pedigree(#[case] index: Option<&str>, #[case] expected: Option<Trio>) {
+        assert_eq!(Trio::from_pedigree(&pedigree(), index).ok(), expected);
+    }
+
+    #[rstest::rstest]
+    #[case::auto_default("1", "0/1", "0/1", "0/0", None, false)]
+    #[case::x_default("X", "0/1", "0/1", "0/0", None, false)]
+    #[case::x_xy_father_ignored("X", "1/1", "1/1", "0/0", Some(Karyotype::Xy), true)]
+    #[case::x_xy_mother_het("X", "1/1", "0/0", "0/1", Some(Karyotype::Xy), false)]
+    #[case::x_x0_hom_alt("X", "1/1", "0/0", "0/0", Some(Karyotype::X0), true)]
+    #[case::x_x0_father_het("X", "1/1", "1/1", "0/0", Some(Karyotype::X0), false)]
+    #[case::x_xxy_het("X", "0/1", "0/0", "0/0", Some(Karyotype::Xxy), true)]
+    #[case::x_xxy_hom_alt("X", "1/1", "0/0", "0/0", Some(Karyotype::Xxy), false)]
+    #[case::y_xy_mother_ignored("Y", "1/1", "0/0", "1/1", Some(Karyotype::Xy), true)]
+    #[case::y_xx("Y", "1/1", "0/0", "0/0", Some(Karyotype::Xx), false)]
+    fn is_candidate_karyotype(
+        #[case] chrom: &str,
+        #[case] index_gt: &str,
+        #[case] father_gt: &str,
+        #[case] mother_gt: &str,
+        #[case] index_karyotype: Option<Karyotype>,
+        #[case] expected: bool,
+    ) -> Result<(), anyhow::Error> {
+        let filter = DenovoFilter {
+            trio: trio("index"),
+            min_dp: 10,
+            min_gq: 20.0,
+            index_karyotype,
+        };
+        let seqvar = SequenceVariant {
+            chrom: chrom.into(),
+            call_info: call_infos(
+                [
+                    ("index", index_gt),
+                    ("father", father_gt),
+                    ("mother", mother_gt),
+                ]
+                .map(|(name, gt)| {
+                    (
+                        name,
+                        CallInfo {
+                            dp: Some(30),
+                            quality: Some(50.0),
+                            ..call_info(gt)
+                        },
+                    )
+                }),
+            ),
+            ..Default::default()
+        };
+
+        assert_eq!(filter.is_candidate(&seqvar)?, expected);
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case::candidate("0/1", ("0/0", 20, 30.0), ("0/0", 15, 40.0), true)]
+    #[case::index_hom_alt("1/1", ("0/0", 20, 30.0), ("0/0", 15, 40.0), false)]
+    #[case::father_het("0/1", ("0/1", 20, 30.0), ("0/0", 15, 40.0), false)]
+    #[case::father_low_dp("0/1", ("0/0", 5, 30.0), ("0/0", 15, 40.0), false)]
+    #[case::mother_low_gq("0/1", ("0/0", 20, 30.0), ("0/0", 15, 10.0), false)]
+    #[case::mother_no_call("0/1", ("0/0", 20, 30.0), ("./.", 15, 40.0), false)]
+    fn is_candidate(
+        #[case] index_gt: &str,
+        #[case] father: (&str, i32, f32),
+        #[case] mother: (&str, i32, f32),
+        #[case] expected: bool,
+    ) -> Result<(), anyhow::Error> {
+        let filter = DenovoFilter {
+            trio: trio("index"),
+            min_dp: 10,
+            min_gq: 20.0,
+            index_karyotype: None,
+        };
+        let seqvar = SequenceVariant {
+            chrom: "1".into(),
+            call_info: call_infos(
+                [
+                    ("index", (index_gt, 30, 50.0)),
+                    ("father", father),
+                    ("mother", mother),
+                ]
+                .map(|(name, (gt, dp, gq))| {
+                    (
+                        name,
+                        CallInfo {
+                            dp: Some(dp),
+                            quality: Some(gq),
+                            ..call_info(gt)
+                        },
+                    )
+                }),
+            ),
+            ..Default::default()
+        };
+
+        assert_eq!(filter.is_candidate(&seqvar)?, expected);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+//! Fixtures shared by the tests of the `seqvars query` modules.
+
+use indexmap::IndexMap;
+
+use super::schema::CallInfo;
+
+/// Names of the trio samples, in the order of the genotypes given to `genotypes()`.
+pub const TRIO: [&str; 3] = ["index", "father", "mother"];
+
+/// Return the `CallInfo` with the genotype `gt` and no further fields set.
+pub fn call_info(gt: &str) -> CallInfo {
+    CallInfo {
+        genotype: Some(gt.into()),
+        ..Default::default()
+    }
+}
+
+/// Build the `call_info` of a `SequenceVariant` from `(sample, call info)` pairs.
+pub fn call_infos<'a, I>(calls: I) -> IndexMap<String, CallInfo>
+where
+    I: IntoIterator<Item = (&'a str, CallInfo)>,
+{
+    calls
+        .into_iter()
+        .map(|(sample, call_info)| (sample.to_string(), call_info))
+        .collect()
+}
+
+/// Build the `call_info` of a `SequenceVariant` from the comma-separated genotypes `gts`
+/// of `samples`, e.g., `0/1,0/0,0/1` for `TRIO`.
+///
+/// Samples without a genotype in `gts` are left out, e.g., the mother for `0/1,0/1`.
+pub fn genotypes(samples: &[&str], gts: &str) -> IndexMap<String, CallInfo> {
+    call_infos(
+        samples
+            .iter()
+            .copied()
+            .zip(gts.split(','))
+            .map(|(sample, gt)| (sample, call_info(gt))),
+    )
+}
@@ -0,0 +1,113 @@
+//! Lookup of in-house carrier counts in the RocksDB built by `seqvars aggregate`.
+
+use crate::seqvars::aggregate::{ds, reader::InhouseDb};
+
+use super::schema::SequenceVariant;
+
+/// Return the counts of `inhouse_db` for `seqvar`, if any.
+pub fn counts(
+    inhouse_db: &InhouseDb,
+    seqvar: &SequenceVariant,
+) -> Result<Option<ds::Counts>, anyhow::Error> {
+    let key: Vec<u8> = annonars::common::keys::Var::from(
+        &annonars::common::cli::canonicalize(&seqvar.chrom),
+        seqvar.pos,
+        &seqvar.reference,
+        &seqvar.alternative,
+    )
+    .into();
+    inhouse_db.counts_by_key(&key)
+}
+
+/// Copy the in-house counts from `inhouse_db` into `seqvar`; variants not in the database
+/// get zero counts.
+pub fn annotate(
+    inhouse_db: &InhouseDb,
+    seqvar: SequenceVariant,
+) -> Result<SequenceVariant, anyhow::Error> {
+    let counts = counts(inhouse_db, &seqvar)?.unwrap_or_default();
+    Ok(SequenceVariant {
+        inhouse_an: counts.count_an as i32,
+        inhouse_het: counts.count_het as i32,
+        inhouse_hom: counts.count_hom as i32,
+        inhouse_hemi: counts.count_hemi as i32,
+        ..seqvar
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        common::kv::InMemoryStore,
+        seqvars::{
+            aggregate::{ds, reader::InhouseDb},
+            query::schema::SequenceVariant,
+        },
+    };
+
+    #[test]
+    fn annotate() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        {
+            let mut options = rocksdb::Options::default();
+            options.create_if_missing(true);
+            options.create_missing_column_families(true);
+            let db = rocksdb::DB::open_cf(&options, &*tmpdir, ["counts"])?;
+            let key: Vec<u8> = annonars::common::keys::Var::from("1", 100, "A", "G").into();
+            let counts = ds::Counts {
+                count_an: 20,
+                count_het: 3,
+                count_hom: 1,
+                count_hemi: 0,
+            };
+            db.put_cf(&db.cf_handle("counts").unwrap(), key, counts.to_vec())?;
+        }
+
+        let db = InhouseDb::open(&*tmpdir, "counts")?;
+        let seqvar = |chrom: &str, alternative: &str| SequenceVariant {
+            chrom: chrom.into(),
+            pos: 100,
+            reference: "A".into(),
+            alternative: alternative.into(),
+            ..Default::default()
+        };
+
+        let found = super::annotate(&db, seqvar("chr1", "G"))?;
+        assert_eq!(
+            (found.inhouse_an, found.inhouse_het, found.inhouse_hom),
+            (20, 3, 1)
+        );
+        let missing = super::annotate(&db, seqvar("1", "T"))?;
+        assert_eq!((missing.inhouse_an, missing.inhouse_het), (0, 0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn annotate_in_memory() -> Result<(), anyhow::Error> {
+        let mut store = InMemoryStore::with_tables(["counts"]);
+        let key: Vec<u8> = annonars::common::keys::Var::from("X", 100, "A", "G").into();
+        let counts = ds::Counts {
+            count_an: 10,
+            count_het: 1,
+            count_hom: 0,
+            count_hemi: 2,
+        };
+        store.insert("counts", key, counts.to_vec());
+
+        let db = InhouseDb::new(Box::new(store), "counts");
+        let found = super::annotate(
+            &db,
+            SequenceVariant {
+                chrom: "chrX".into(),
+                pos: 100,
+                reference: "A".into(),
+                alternative: "G".into(),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!((found.inhouse_an, found.inhouse_hemi), (10, 2));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,401 @@
+//! Explanation of the filter decisions for a `SequenceVariant`.
+//!
+//! This is used for debugging why a variant is (not) part of the result set.
+
+use serde_json::json;
+
+use crate::seqvars::query::schema::{CaseQuery, FailChoice, SequenceVariant};
+
+use super::{consequences, frequency, genes_allowlist, genotype, quality, regions_allowlist};
+
+/// A variant to explain the filter decisions for, given as `CHROM:POS:REF:ALT`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Target {
+    /// Canonical chromosome name.
+    pub chrom: String,
+    /// 1-based position.
+    pub pos: i32,
+    /// Reference allele.
+    pub reference: String,
+    /// Alternative allele.
+    pub alternative: String,
+}
+
+impl std::str::FromStr for Target {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split(':').collect::<Vec<_>>();
+        if parts.len() != 4 {
+            anyhow::bail!("invalid variant {:?}, must be CHROM:POS:REF:ALT", s);
+        }
+        Ok(Self {
+            chrom: annonars::common::cli::canonicalize(parts[0]),
+            pos: parts[1]
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid position in variant {:?}: {}", s, e))?,
+            reference: parts[2].to_string(),
+            alternative: parts[3].to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}",
+            &self.chrom, self.pos, &self.reference, &self.alternative
+        )
+    }
+}
+
+impl From<&SequenceVariant> for Target {
+    fn from(seqvar: &SequenceVariant) -> Self {
+        Self {
+            chrom: annonars::common::cli::canonicalize(&seqvar.chrom),
+            pos: seqvar.pos,
+            reference: seqvar.reference.clone(),
+            alternative: seqvar.alternative.clone(),
+        }
+    }
+}
+
+/// The decision of a single filter.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Decision {
+    /// Name of the filter, e.g., `frequency.gnomad_exomes`.
+    pub filter: String,
+    /// The relevant settings (thresholds) from the query.
+    pub settings: serde_json::Value,
+    /// The values observed for the variant.
+    pub observed: serde_json::Value,
+    /// Whether the variant passes the filter.
+    pub pass: bool,
+}
+
+impl Decision {
+    /// Construct with the given filter name, settings, and observed values.
+    pub fn new<S, O>(
+        filter: &str,
+        settings: S,
+        observed: O,
+        pass: bool,
+    ) -> Result<Self, anyhow::Error>
+    where
+        S: serde::Serialize,
+        O: serde::Serialize,
+    {
+        Ok(Self {
+            filter: filter.to_string(),
+            settings: serde_json::to_value(settings)?,
+            observed: serde_json::to_value(observed)?,
+            pass,
+        })
+    }
+
+    /// Construct a failing decision for a filter that could not be evaluated.
+    fn error<S>(filter: &str, settings: S, error: anyhow::Error) -> Result<Self, anyhow::Error>
+    where
+        S: serde::Serialize,
+    {
+        Self::new(
+            filter,
+            settings,
+            json!({ "error": error.to_string() }),
+            false,
+        )
+    }
+}
+
+/// The explanation of the filter decisions for one variant.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Explanation {
+    /// The variant as `CHROM:POS:REF:ALT`.
+    pub variant: String,
+    /// Whether the variant was found in the input file.
+    pub found_in_input: bool,
+    /// Whether the variant passes all variant-level filters.
+    ///
+    /// Note that variants may still be removed by the gene-level recessive mode
+    /// filter or when truncating the result set.
+    pub pass_all: bool,
+    /// The decisions of the individual filters.
+    pub decisions: Vec<Decision>,
+}
+
+impl Explanation {
+    /// Construct from the given decisions.
+    pub fn new(target: &Target, decisions: Vec<Decision>) -> Self {
+        Self {
+            variant: target.to_string(),
+            found_in_input: true,
+            pass_all: decisions.iter().all(|decision| decision.pass),
+            decisions,
+        }
+    }
+
+    /// Construct for a variant that was not found in the input file.
+    pub fn not_found(target: &Target) -> Self {
+        Self {
+            variant: target.to_string(),
+            found_in_input: false,
+            pass_all: false,
+            decisions: Vec::new(),
+        }
+    }
+}
+
+/// Compute the decisions of all filters that do not need database access.
+///
+/// Other than `QueryInterpreter::passes()`, all filters are evaluated so the trace
+/// is complete.
+pub fn decisions(
+    query: &CaseQuery,
+    hgnc_allowlist: &Option<std::collections::HashSet<String>>,
+    pedigree: &mehari::ped::PedigreeByName,
+    karyotypes: &crate::common::karyotype::Karyotypes,
+    seqvar: &SequenceVariant,
+) -> Result<Vec<Decision>, anyhow::Error> {
+    let mut result = Vec::new();
+
+    // Frequency filter, evaluated for each database by disabling the others.
+    let frequency_query = CaseQuery {
+        gnomad_exomes_enabled: false,
+        gnomad_genomes_enabled: false,
+        helixmtdb_enabled: false,
+        ..query.clone()
+    };
+    result.push(Decision::new(
+        "frequency.gnomad_exomes",
+        json!({
+            "enabled": query.gnomad_exomes_enabled,
+            "frequency": query.gnomad_exomes_frequency,
+            "heterozygous": query.gnomad_exomes_heterozygous,
+            "homozygous": query.gnomad_exomes_homozygous,
+            "hemizygous": query.gnomad_exomes_hemizygous,
+        }),
+        json!({
+            "frequency": seqvar.gnomad_exomes_af(),
+            "heterozygous": seqvar.gnomad_exomes_het,
+            "homozygous": seqvar.gnomad_exomes_hom,
+            "hemizygous": seqvar.gnomad_exomes_hemi,
+        }),
+        frequency::passes(
+            &CaseQuery {
+                gnomad_exomes_enabled: query.gnomad_exomes_enabled,
+                ..frequency_query.clone()
+            },
+            seqvar,
+        )?,
+    )?);
+    result.push(Decision::new(
+        "frequency.gnomad_genomes",
+        json!({
+            "enabled": query.gnomad_genomes_enabled,
+            "frequency": query.gnomad_genomes_frequency,
+            "heterozygous": query.gnomad_genomes_heterozygous,
+            "homozygous": query.gnomad_genomes_homozygous,
+            "hemizygous": query.gnomad_genomes_hemizygous,
+        }),
+        json!({
+            "frequency": seqvar.gnomad_genomes_af(),
+            "heterozygous": seqvar.gnomad_genomes_het,
+            "homozygous": seqvar.gnomad_genomes_hom,
+            "hemizygous": seqvar.gnomad_genomes_hemi,
+        }),
+        frequency::passes(
+            &CaseQuery {
+                gnomad_genomes_enabled: query.gnomad_genomes_enabled,
+                ..frequency_query.clone()
+            },
+            seqvar,
+        )?,
+    )?);
+    result.push(Decision::new(
+        "frequency.helixmtdb",
+        json!({
+            "enabled": query.helixmtdb_enabled,
+            "frequency": query.helixmtdb_frequency,
+            "heteroplasmic": query.helixmtdb_heteroplasmic,
+            "homoplasmic": query.helixmtdb_homoplasmic,
+        }),
+        json!({
+            "frequency": seqvar.helixmtdb_af(),
+            "heteroplasmic": seqvar.helix_het,
+            "homoplasmic": seqvar.helix_hom,
+        }),
+        frequency::passes(
+            &CaseQuery {
+                helixmtdb_enabled: query.helixmtdb_enabled,
+                ..frequency_query
+            },
+            seqvar,
+        )?,
+    )?);
+
+    result.push(Decision::new(
+        "consequences",
+        &query.consequences,
+        seqvar
+            .ann_fields
+            .iter()
+            .map(|ann_field| &ann_field.consequences)
+            .collect::<Vec<_>>(),
+        consequences::passes(query, seqvar)?,
+    )?);
+
+    // Quality filter, one decision for each sample.
+    let mut no_call_samples = Vec::new();
+    for (sample_name, quality_settings) in &query.quality {
+        let filter = format!("quality.{}", sample_name);
+        if let Some(call_info) = seqvar.call_info.get(sample_name) {
+            let fail = quality::passes_for_sample(quality_settings, call_info);
+            if fail == Some(FailChoice::NoCall) {
+                no_call_samples.push(sample_name.as_str());
+            }
+            result.push(Decision::new(
+                &filter,
+                quality_settings,
+                json!({ "call_info": call_info, "fail": fail }),
+                fail != Some(FailChoice::Drop),
+            )?);
+        } else {
+            result.push(Decision::error(
+                &filter,
+                quality_settings,
+                anyhow::anyhow!("sample {} not found in call info", sample_name),
+            )?);
+        }
+    }
+
+    result.push(Decision::new(
+        "genes_allowlist",
+        hgnc_allowlist,
+        seqvar
+            .ann_fields
+            .iter()
+            .map(|ann_field| &ann_field.gene_id)
+            .collect::<Vec<_>>(),
+        genes_allowlist::passes(hgnc_allowlist, seqvar),
+    )?);
+    result.push(Decision::new(
+        "regions_allowlist",
+        &query.genomic_regions,
+        json!({
+            "chrom": &seqvar.chrom,
+            "start": seqvar.pos,
+            "end": seqvar.pos + seqvar.reference.len() as i32 - 1,
+        }),
+        regions_allowlist::passes(query, seqvar),
+    )?);
+
+    let genotypes = seqvar
+        .call_info
+        .iter()
+        .map(|(sample_name, call_info)| (sample_name, &call_info.genotype))
+        .collect::<indexmap::IndexMap<_, _>>();
+    result.push(
+        match genotype::passes(query, pedigree, karyotypes, seqvar, &no_call_samples) {
+            Ok(pass) => Decision::new(
+                "genotype",
+                &query.genotype,
+                json!({ "genotypes": genotypes, "no_call_samples": no_call_samples }),
+                pass,
+            ),
+            Err(e) => Decision::error("genotype", &query.genotype, e),
+        }?,
+    );
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::seqvars::ann::{AnnField, Consequence};
+
+    use crate::seqvars::query::schema::{
+        CallInfo, CaseQuery, FailChoice, GenotypeChoice, QualitySettings, SequenceVariant,
+    };
+
+    #[rstest::rstest]
+    #[case("chr1:100:A:G", "1:100:A:G")]
+    #[case("X:1:AC:A", "X:1:AC:A")]
+    #[case("chrM:16000:T:C", "MT:16000:T:C")]
+    fn target_from_str(#[case] s: &str, #[case] expected: &str) -> Result<(), anyhow::Error> {
+        let target: super::Target = s.parse()?;
+        assert_eq!(target.to_string(), expected);
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case("1:100:A")]
+    #[case("1:x:A:G")]
+    fn target_from_str_invalid(#[case] s: &str) {
+        assert!(s.parse::<super::Target>().is_err());
+    }
+
+    #[test]
+    fn decisions() -> Result<(), anyhow::Error> {
+        let query = CaseQuery {
+            consequences: vec![Consequence::MissenseVariant],
+            quality: vec![(
+                String::from("index"),
+                QualitySettings {
+                    dp_het: Some(10),
+                    fail: FailChoice::Drop,
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            genotype: vec![(String::from("index"), Some(GenotypeChoice::Het))]
+                .into_iter()
+                .collect(),
+            gnomad_exomes_enabled: true,
+            gnomad_exomes_frequency: Some(0.001),
+            gnomad_genomes_enabled: true,
+            gnomad_genomes_frequency: Some(0.001),
+            ..Default::default()
+        };
+        let seqvar = SequenceVariant {
+            chrom: "1".into(),
+            pos: 100,
+            reference: "A".into(),
+            alternative: "G".into(),
+            ann_fields: vec![AnnField {
+                consequences: vec![Consequence::MissenseVariant],
+                gene_id: "HGNC:1".into(),
+                ..Default::default()
+            }],
+            gnomad_exomes_an: 1000,
+            gnomad_exomes_het: 5,
+            gnomad_genomes_an: 1000,
+            call_info: vec![(
+                String::from("index"),
+                CallInfo {
+                    genotype: Some("0/1".into()),
+                    dp: Some(8),
+                    ad: Some(4),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+
+        let decisions = super::decisions(
+            &query,
+            &None,
+            &Default::default(),
+            &Default::default(),
+            &seqvar,
+        )?;
+        let explanation = super::Explanation::new(&(&seqvar).into(), decisions);
+        assert!(!explanation.pass_all);
+        insta::assert_yaml_snapshot!(explanation);
+
+        Ok(())
+    }
+}
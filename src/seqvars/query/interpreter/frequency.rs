@@ -1,4 +1,4 @@
-use crate::seqvars::query::schema::{CaseQuery, SequenceVariant};
+use crate::seqvars::query::schema::{CaseQuery, FrequencyThresholds, SequenceVariant};
 
 /// Determine whether the `SequenceVariant` passes the frequency filter.
 pub fn passes(query: &CaseQuery, s: &SequenceVariant) -> Result<bool, anyhow::Error> {
@@ -54,9 +54,81 @@ pub fn passes(query: &CaseQuery, s: &SequenceVariant) -> Result<bool, anyhow::Er
         return Ok(false);
     }
 
+    if q.inhouse_enabled
+        && (q.inhouse_carriers.is_some()
+            && s.inhouse_het + s.inhouse_hom + s.inhouse_hemi
+                > q.inhouse_carriers.expect("tested before")
+            || q.inhouse_heterozygous.is_some()
+                && s.inhouse_het > q.inhouse_heterozygous.expect("tested before")
+            || q.inhouse_homozygous.is_some()
+                && s.inhouse_hom > q.inhouse_homozygous.expect("tested before")
+            || !is_mtdna
+                && q.inhouse_hemizygous.is_some()
+                && s.inhouse_hemi > q.inhouse_hemizygous.expect("tested before"))
+    {
+        tracing::trace!(
+            "variant {:?} fails in-house frequency filter {:?}",
+            s,
+            &q.inhouse_carriers
+        );
+        return Ok(false);
+    }
+
+    let canonical_chrom = annonars::common::cli::canonicalize(&s.chrom);
+    let thresholds = match canonical_chrom.as_str() {
+        "MT" => &q.frequency_thresholds.mtdna,
+        "X" | "Y" => &q.frequency_thresholds.gonosomal,
+        _ => &q.frequency_thresholds.autosomal,
+    };
+    let is_gonosomal = matches!(canonical_chrom.as_str(), "X" | "Y");
+    if !passes_thresholds(thresholds, s, is_mtdna, is_gonosomal) {
+        tracing::trace!(
+            "variant {:?} fails frequency thresholds {:?}",
+            s,
+            thresholds
+        );
+        return Ok(false);
+    }
+
     Ok(true)
 }
 
+/// Determine whether the `SequenceVariant` passes the per-chromosome class `thresholds`.
+///
+/// gnomAD exomes are not considered for chrMT.  On the gonosomes, the hemizygous carriers
+/// are counted towards the homozygous ones and towards the in-house carriers.
+fn passes_thresholds(
+    thresholds: &FrequencyThresholds,
+    s: &SequenceVariant,
+    is_mtdna: bool,
+    is_gonosomal: bool,
+) -> bool {
+    let max_af = if is_mtdna {
+        s.gnomad_genomes_af()
+    } else {
+        s.gnomad_exomes_af().max(s.gnomad_genomes_af())
+    };
+    let max_hom = if is_mtdna {
+        s.gnomad_genomes_hom
+    } else if is_gonosomal {
+        (s.gnomad_exomes_hom + s.gnomad_exomes_hemi)
+            .max(s.gnomad_genomes_hom + s.gnomad_genomes_hemi)
+    } else {
+        s.gnomad_exomes_hom.max(s.gnomad_genomes_hom)
+    };
+    let inhouse_carriers = if is_gonosomal {
+        s.inhouse_het + s.inhouse_hom + s.inhouse_hemi
+    } else {
+        s.inhouse_het + s.inhouse_hom
+    };
+
+    !(thresholds.max_af.map_or(false, |max| max_af > max)
+        || thresholds.max_hom.map_or(false, |max| max_hom > max)
+        || thresholds
+            .max_inhouse_carriers
+            .map_or(false, |max| inhouse_carriers > max))
+}
+
 #[cfg(test)]
 mod test {
     use mehari::annotate::seqvars::ann::{AnnField, Consequence};
@@ -466,4 +538,105 @@ mod test {
 
         Ok(())
     }
+
+    #[rstest]
+    #[case::auto_pass("1", (1000, 1, 0, 0), (0, 0, 0), true)]
+    #[case::auto_af("1", (1000, 3, 0, 0), (0, 0, 0), false)]
+    #[case::auto_hom("1", (1000, 0, 1, 0), (0, 0, 0), false)]
+    #[case::auto_inhouse_pass("1", (1000, 0, 0, 0), (1, 1, 0), true)]
+    #[case::auto_inhouse("1", (1000, 0, 0, 0), (2, 1, 0), false)]
+    #[case::x_gonosomal_pass("X", (1000, 3, 0, 0), (0, 0, 0), true)]
+    #[case::x_gonosomal_hemi_pass("X", (1000, 0, 1, 1), (0, 0, 0), true)]
+    #[case::x_gonosomal_hemi("chrX", (1000, 0, 1, 2), (0, 0, 0), false)]
+    #[case::y_gonosomal_hom("chrY", (1000, 0, 3, 0), (0, 0, 0), false)]
+    #[case::x_gonosomal_inhouse_pass("X", (1000, 0, 0, 0), (1, 1, 1), true)]
+    #[case::x_gonosomal_inhouse_hemi("chrX", (1000, 0, 0, 0), (1, 1, 2), false)]
+    #[case::mt_pass("MT", (1000, 0, 1, 0), (0, 0, 0), true)]
+    #[case::mt_af("chrM", (1000, 0, 30, 0), (0, 0, 0), false)]
+    #[case::mt_inhouse_pass("MT", (1000, 0, 0, 0), (5, 0, 0), true)]
+    #[case::mt_inhouse("chrM", (1000, 0, 0, 0), (3, 3, 0), false)]
+    fn passes_frequency_thresholds(
+        #[case] chrom: &str,
+        #[case] gnomad_genomes: (i32, i32, i32, i32),
+        #[case] inhouse: (i32, i32, i32),
+        #[case] expected_pass_all: bool,
+    ) -> Result<(), anyhow::Error> {
+        use crate::seqvars::query::schema::{FrequencyThresholds, FrequencyThresholdsByChrom};
+
+        let query = CaseQuery {
+            frequency_thresholds: FrequencyThresholdsByChrom {
+                autosomal: FrequencyThresholds {
+                    max_af: Some(0.002),
+                    max_hom: Some(0),
+                    max_inhouse_carriers: Some(2),
+                },
+                gonosomal: FrequencyThresholds {
+                    max_af: Some(0.01),
+                    max_hom: Some(2),
+                    max_inhouse_carriers: Some(3),
+                },
+                mtdna: FrequencyThresholds {
+                    max_af: Some(0.05),
+                    max_hom: None,
+                    max_inhouse_carriers: Some(5),
+                },
+            },
+            ..Default::default()
+        };
+        let (an, het, hom, hemi) = gnomad_genomes;
+        let (inhouse_het, inhouse_hom, inhouse_hemi) = inhouse;
+        let seq_var = SequenceVariant {
+            chrom: chrom.into(),
+            gnomad_genomes_an: an,
+            gnomad_genomes_het: het,
+            gnomad_genomes_hom: hom,
+            gnomad_genomes_hemi: hemi,
+            inhouse_het,
+            inhouse_hom,
+            inhouse_hemi,
+            ..Default::default()
+        };
+
+        assert_eq!(super::passes(&query, &seq_var)?, expected_pass_all);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::disabled(false, (5, 5, 5), (Some(1), Some(1), Some(1), Some(1)), true)]
+    #[case::no_thresholds(true, (5, 5, 5), (None, None, None, None), true)]
+    #[case::carriers_pass(true, (1, 1, 0), (Some(2), None, None, None), true)]
+    #[case::carriers_fail(true, (1, 1, 1), (Some(2), None, None, None), false)]
+    #[case::het_fail(true, (2, 0, 0), (None, Some(1), None, None), false)]
+    #[case::hom_fail(true, (0, 2, 0), (None, None, Some(1), None), false)]
+    #[case::hemi_fail(true, (0, 0, 2), (None, None, None, Some(1)), false)]
+    fn passes_frequency_inhouse(
+        #[case] query_inhouse_enabled: bool,
+        #[case] seqvar_inhouse: (i32, i32, i32),
+        #[case] query_inhouse: (Option<i32>, Option<i32>, Option<i32>, Option<i32>),
+        #[case] expected_pass_all: bool,
+    ) -> Result<(), anyhow::Error> {
+        let (carriers, heterozygous, homozygous, hemizygous) = query_inhouse;
+        let query = CaseQuery {
+            inhouse_enabled: query_inhouse_enabled,
+            inhouse_carriers: carriers,
+            inhouse_heterozygous: heterozygous,
+            inhouse_homozygous: homozygous,
+            inhouse_hemizygous: hemizygous,
+            ..Default::default()
+        };
+        let (inhouse_het, inhouse_hom, inhouse_hemi) = seqvar_inhouse;
+        let seq_var = SequenceVariant {
+            chrom: "X".into(),
+            inhouse_an: 100,
+            inhouse_het,
+            inhouse_hom,
+            inhouse_hemi,
+            ..Default::default()
+        };
+
+        assert_eq!(super::passes(&query, &seq_var)?, expected_pass_all);
+
+        Ok(())
+    }
 }
@@ -1,12 +1,29 @@
-use crate::seqvars::query::schema::{CaseQuery, GenotypeChoice, SequenceVariant};
+use mehari::ped::{Disease, PedigreeByName, Sex};
+
+use crate::{
+    common::{karyotype::Karyotypes, Chrom},
+    seqvars::query::schema::{CaseQuery, GenotypeChoice, InheritanceMode, SequenceVariant},
+};
 
 /// Determine whether the `SequenceVariant` passes the genotype filter.
+///
+/// The `pedigree` and `karyotypes` are only used for the built-in inheritance modes.
 pub fn passes(
     query: &CaseQuery,
+    pedigree: &PedigreeByName,
+    karyotypes: &Karyotypes,
     seqvar: &SequenceVariant,
     no_call_samples: &[&str],
 ) -> Result<bool, anyhow::Error> {
-    let result = if query.recessive_mode() {
+    let result = if let Some(inheritance_mode) = query.inheritance_mode {
+        passes_inheritance_mode(
+            inheritance_mode,
+            pedigree,
+            karyotypes,
+            seqvar,
+            no_call_samples,
+        )?
+    } else if query.recessive_mode() {
         let index_sample = query.index_sample().ok_or_else(|| {
             anyhow::anyhow!("recessive mode requires an index sample, but none was found")
         })?;
@@ -178,6 +195,93 @@ fn passes_recessive_modes(
     }
 }
 
+/// Handle case of a built-in inheritance mode.
+///
+/// The genotype choice of each sequenced individual is derived from its sex and
+/// affection status in the `pedigree`.  On chrX, the sex is taken from the karyotype in
+/// `karyotypes` such that, e.g., an X0 female is hemizygous and an XXY male may be a
+/// carrier.  Individuals with unknown affection status are not constrained.
+fn passes_inheritance_mode(
+    inheritance_mode: InheritanceMode,
+    pedigree: &PedigreeByName,
+    karyotypes: &Karyotypes,
+    seqvar: &SequenceVariant,
+    no_call_samples: &[&str],
+) -> Result<bool, anyhow::Error> {
+    if !pedigree
+        .individuals
+        .values()
+        .any(|individual| individual.disease == Disease::Affected)
+    {
+        anyhow::bail!(
+            "inheritance mode {:?} requires an affected individual in the pedigree",
+            inheritance_mode
+        );
+    }
+
+    let chrom = annonars::common::cli::canonicalize(&seqvar.chrom);
+    let expected_chrom = match inheritance_mode {
+        InheritanceMode::XLinkedRecessive | InheritanceMode::XLinkedDominant => "X",
+        InheritanceMode::Mitochondrial => "MT",
+    };
+    if chrom != expected_chrom {
+        tracing::trace!(
+            "variant {:?} not on chr{} for {:?}",
+            seqvar,
+            expected_chrom,
+            inheritance_mode
+        );
+        return Ok(false);
+    }
+
+    // Mothers of affected individuals transmit mitochondrial variants.
+    let is_mother_of_affected = |name: &str| {
+        pedigree.individuals.values().any(|individual| {
+            individual.disease == Disease::Affected && individual.mother.as_deref() == Some(name)
+        })
+    };
+
+    for (name, individual) in pedigree.individuals.iter() {
+        let Some(call_info) = seqvar.call_info.get(name) else {
+            continue; // not sequenced
+        };
+        let genotype = if no_call_samples.contains(&name.as_str()) {
+            "."
+        } else {
+            call_info.genotype.as_deref().unwrap_or(".")
+        };
+        let sex = karyotypes
+            .get(name, individual.sex)
+            .map(|karyotype| karyotype.effective_sex(Chrom::X))
+            .unwrap_or(individual.sex);
+        let genotype_choice = match (inheritance_mode, individual.disease, sex) {
+            (InheritanceMode::XLinkedRecessive, Disease::Affected, _) => GenotypeChoice::Hom,
+            (InheritanceMode::XLinkedRecessive, Disease::Unaffected, Sex::Male) => {
+                GenotypeChoice::Ref
+            }
+            (InheritanceMode::XLinkedRecessive, Disease::Unaffected, _) => GenotypeChoice::NonHom,
+            (InheritanceMode::XLinkedDominant, Disease::Affected, _) => GenotypeChoice::Variant,
+            (InheritanceMode::XLinkedDominant, Disease::Unaffected, _) => GenotypeChoice::Ref,
+            (InheritanceMode::Mitochondrial, Disease::Affected, _) => GenotypeChoice::Variant,
+            (InheritanceMode::Mitochondrial, _, _) if is_mother_of_affected(name) => {
+                GenotypeChoice::Variant
+            }
+            _ => GenotypeChoice::Any,
+        };
+        if !genotype_choice.matches(genotype)? {
+            tracing::trace!(
+                "variant {:?} fails {:?} on sample {}",
+                seqvar,
+                inheritance_mode,
+                name
+            );
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
 /// Handle case if the mode is not "recessive".  Note that this actually includes the
 /// homozygous recessive mode.
 fn passes_non_recessive_mode(
@@ -225,12 +329,15 @@ fn passes_non_recessive_mode(
 
 #[cfg(test)]
 mod test {
+    use mehari::ped::{Disease, Individual, PedigreeByName, Sex};
     use rstest::rstest;
 
+    use crate::common::karyotype::{Karyotype, Karyotypes};
+    use crate::seqvars::query::fixtures::genotypes;
     use crate::seqvars::query::schema::{
         CallInfo,
         GenotypeChoice::{self, *},
-        SequenceVariant,
+        InheritanceMode, SequenceVariant,
     };
 
     static INDEX_NAME: &str = "sample";
@@ -644,4 +751,171 @@ mod test {
 
         Ok(())
     }
+
+    /// Pedigree with unaffected parents and an affected index of the given sex.
+    fn trio_pedigree(index_sex: Sex, mother_disease: Disease) -> PedigreeByName {
+        let individual = |name: &str, sex, disease, parents: bool| {
+            (
+                name.to_string(),
+                Individual {
+                    family: "FAM".into(),
+                    name: name.into(),
+                    father: parents.then(|| FATHER_NAME.to_string()),
+                    mother: parents.then(|| MOTHER_NAME.to_string()),
+                    sex,
+                    disease,
+                },
+            )
+        };
+        PedigreeByName {
+            individuals: [
+                individual(INDEX_NAME, index_sex, Disease::Affected, true),
+                individual(FATHER_NAME, Sex::Male, Disease::Unaffected, false),
+                individual(MOTHER_NAME, Sex::Female, mother_disease, false),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+
+    #[rstest]
+    // x-linked recessive
+    #[case("X", "1,0,0/1", Sex::Male, InheritanceMode::XLinkedRecessive, true)]
+    #[case(
+        "chrX",
+        "1/1,0,0/1",
+        Sex::Male,
+        InheritanceMode::XLinkedRecessive,
+        true
+    )]
+    #[case("X", "1/1,0,0/1", Sex::Female, InheritanceMode::XLinkedRecessive, true)]
+    #[case(
+        "X",
+        "0/1,0,0/1",
+        Sex::Female,
+        InheritanceMode::XLinkedRecessive,
+        false
+    )]
+    #[case("X", "1,1,0/1", Sex::Male, InheritanceMode::XLinkedRecessive, false)]
+    #[case("X", "1,0,1/1", Sex::Male, InheritanceMode::XLinkedRecessive, false)]
+    #[case(
+        "1",
+        "1/1,0/0,0/1",
+        Sex::Male,
+        InheritanceMode::XLinkedRecessive,
+        false
+    )]
+    // x-linked dominant
+    #[case("X", "0/1,0,0/0", Sex::Female, InheritanceMode::XLinkedDominant, true)]
+    #[case("X", "1,0,0/0", Sex::Male, InheritanceMode::XLinkedDominant, true)]
+    #[case("X", "0/1,0,0/1", Sex::Female, InheritanceMode::XLinkedDominant, false)]
+    #[case("X", "0/0,0,0/0", Sex::Female, InheritanceMode::XLinkedDominant, false)]
+    // mitochondrial
+    #[case("MT", "1,0,1", Sex::Male, InheritanceMode::Mitochondrial, true)]
+    #[case("chrM", "1,1,1", Sex::Female, InheritanceMode::Mitochondrial, true)]
+    #[case("MT", "1,1,0", Sex::Male, InheritanceMode::Mitochondrial, false)]
+    #[case("MT", "0,0,1", Sex::Male, InheritanceMode::Mitochondrial, false)]
+    #[case("X", "1,0,1", Sex::Male, InheritanceMode::Mitochondrial, false)]
+    fn passes_inheritance_mode_trio(
+        #[case] chrom: &str,
+        #[case] sample_gts: &str,
+        #[case] index_sex: Sex,
+        #[case] inheritance_mode: InheritanceMode,
+        #[case] expected: bool,
+    ) -> Result<(), anyhow::Error> {
+        let pedigree = trio_pedigree(index_sex, Disease::Unaffected);
+        let seq_var = SequenceVariant {
+            chrom: chrom.into(),
+            call_info: genotypes(&[INDEX_NAME, FATHER_NAME, MOTHER_NAME], sample_gts),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            super::passes_inheritance_mode(
+                inheritance_mode,
+                &pedigree,
+                &Default::default(),
+                &seq_var,
+                &[]
+            )?,
+            expected,
+            "chrom = {}, sample_gts = {}, index_sex = {:?}, mode = {:?}",
+            chrom,
+            sample_gts,
+            index_sex,
+            inheritance_mode
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::father_xy_carrier("1,1,0/1", None, false)]
+    #[case::father_xxy_carrier("1,0/1,0/1", Some(Karyotype::Xxy), true)]
+    #[case::father_xxy_hom("1,1/1,0/1", Some(Karyotype::Xxy), false)]
+    #[case::mother_x0_carrier("1,0,1", Some(Karyotype::X0), false)]
+    #[case::mother_x0_ref("1,0,0", Some(Karyotype::X0), true)]
+    fn passes_inheritance_mode_karyotypes(
+        #[case] sample_gts: &str,
+        #[case] karyotype: Option<Karyotype>,
+        #[case] expected: bool,
+    ) -> Result<(), anyhow::Error> {
+        let pedigree = trio_pedigree(Sex::Male, Disease::Unaffected);
+        // Declare the karyotype for the father if XXY and for the mother if X0.
+        let karyotypes = Karyotypes {
+            by_sample: karyotype
+                .map(|karyotype| {
+                    let name = if karyotype == Karyotype::X0 {
+                        MOTHER_NAME
+                    } else {
+                        FATHER_NAME
+                    };
+                    (name.to_string(), karyotype)
+                })
+                .into_iter()
+                .collect(),
+        };
+        let seq_var = SequenceVariant {
+            chrom: "X".into(),
+            call_info: genotypes(&[INDEX_NAME, FATHER_NAME, MOTHER_NAME], sample_gts),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            super::passes_inheritance_mode(
+                InheritanceMode::XLinkedRecessive,
+                &pedigree,
+                &karyotypes,
+                &seq_var,
+                &[]
+            )?,
+            expected,
+            "sample_gts = {}, karyotype = {:?}",
+            sample_gts,
+            karyotype
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn passes_inheritance_mode_requires_affected() {
+        let pedigree = trio_pedigree(Sex::Male, Disease::Unaffected);
+        let pedigree = PedigreeByName {
+            individuals: pedigree
+                .individuals
+                .into_iter()
+                .filter(|(name, _)| name != INDEX_NAME)
+                .collect(),
+        };
+
+        assert!(super::passes_inheritance_mode(
+            InheritanceMode::XLinkedDominant,
+            &pedigree,
+            &Default::default(),
+            &Default::default(),
+            &[]
+        )
+        .is_err());
+    }
 }
@@ -2,16 +2,23 @@
 
 use std::collections::HashSet;
 
+use crate::strucvars::query::genes::RemappedSymbol;
+
 mod clinvar;
 mod consequences;
-mod frequency;
+pub mod explain;
+pub(crate) mod frequency;
 mod genes_allowlist;
 mod genotype;
+mod paralogs;
+mod protein_domains;
 mod quality;
 mod regions_allowlist;
 
 use super::{
     annonars::Annotator,
+    paralogs::ParalogLoci,
+    protein_domains::ProteinDomains,
     schema::{CaseQuery, SequenceVariant},
 };
 
@@ -23,13 +30,50 @@ pub struct QueryInterpreter {
     pub query: CaseQuery,
     /// Gene allowlist with HGNC IDs.
     pub hgnc_allowlist: Option<HashSet<String>>,
+    /// Pedigree of the case, used by the built-in inheritance modes.
+    pub pedigree: mehari::ped::PedigreeByName,
+    /// Declared non-standard karyotypes, used by the built-in inheritance modes.
+    pub karyotypes: crate::common::karyotype::Karyotypes,
+    /// Protein domains per transcript, if loaded.
+    pub protein_domains: Option<ProteinDomains>,
+    /// Gene loci with problematic paralogy.
+    pub paralog_loci: ParalogLoci,
+    /// Outdated symbols of the gene allow list that were remapped.
+    pub remapped_gene_symbols: Vec<RemappedSymbol>,
+}
+
+/// The filters applied by `QueryInterpreter::passes()`, in order of evaluation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum::Display, strum::EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum Filter {
+    /// Population frequency filter.
+    Frequency,
+    /// Molecular consequences filter.
+    Consequences,
+    /// Per-sample quality filter.
+    Quality,
+    /// Gene allowlist filter.
+    GenesAllowlist,
+    /// Genomic regions allowlist filter.
+    RegionsAllowlist,
+    /// Paralog loci filter.
+    ParalogLoci,
+    /// Genotype filter.
+    Genotype,
+    /// ClinVar membership filter.
+    Clinvar,
+    /// Protein domain filter.
+    ProteinDomain,
 }
 
-/// Result type for `QueryInterpreter::passes_genotype()`.
+/// Result type for `QueryInterpreter::passes()`.
 #[derive(Debug, Default)]
 pub struct PassesResult {
     /// Whether genotype passes for all samples.
     pub pass_all: bool,
+    /// The filters that the variant fails, in order of evaluation; only the first one
+    /// unless all filters are evaluated.
+    pub failed_filters: Vec<Filter>,
 }
 
 impl QueryInterpreter {
@@ -38,44 +82,165 @@ impl QueryInterpreter {
         QueryInterpreter {
             query,
             hgnc_allowlist,
+            pedigree: Default::default(),
+            karyotypes: Default::default(),
+            protein_domains: None,
+            paralog_loci: Default::default(),
+            remapped_gene_symbols: Vec::new(),
+        }
+    }
+
+    /// Use the given `pedigree` for the built-in inheritance modes.
+    pub fn with_pedigree(self, pedigree: mehari::ped::PedigreeByName) -> Self {
+        QueryInterpreter { pedigree, ..self }
+    }
+
+    /// Use the given `karyotypes` for the built-in inheritance modes.
+    pub fn with_karyotypes(self, karyotypes: crate::common::karyotype::Karyotypes) -> Self {
+        QueryInterpreter { karyotypes, ..self }
+    }
+
+    /// Use the given `paralog_loci` for the paralog loci filter.
+    pub fn with_paralog_loci(self, paralog_loci: ParalogLoci) -> Self {
+        QueryInterpreter {
+            paralog_loci,
+            ..self
+        }
+    }
+
+    /// Record the outdated symbols of the gene allow list that were remapped.
+    pub fn with_remapped_gene_symbols(self, remapped_gene_symbols: Vec<RemappedSymbol>) -> Self {
+        QueryInterpreter {
+            remapped_gene_symbols,
+            ..self
+        }
+    }
+
+    /// Use the given `protein_domains` for the protein domain filter.
+    pub fn with_protein_domains(self, protein_domains: Option<ProteinDomains>) -> Self {
+        QueryInterpreter {
+            protein_domains,
+            ..self
         }
     }
 
     /// Determine whether the annotated `SequenceVariant` passes all criteria.
+    ///
+    /// The evaluation stops at the first failing filter unless `evaluate_all` is set, e.g.,
+    /// for computing the impact of each filter.
     pub fn passes(
         &self,
         seqvar: &SequenceVariant,
         annotator: &Annotator,
+        evaluate_all: bool,
     ) -> Result<PassesResult, anyhow::Error> {
-        // Check the filters first that are cheap to compute.
-        let pass_frequency = frequency::passes(&self.query, seqvar)?;
-        let pass_consequences = consequences::passes(&self.query, seqvar)?;
+        // The genotype filter needs the quality filter output as input.
         let res_quality = quality::passes(&self.query, seqvar)?;
-        let pass_genes_allowlist = genes_allowlist::passes(&self.hgnc_allowlist, seqvar);
-        let pass_regions_allowlist = regions_allowlist::passes(&self.query, seqvar);
-        if !pass_frequency
-            || !pass_consequences
-            || !res_quality.pass
-            || !pass_genes_allowlist
-            || !pass_regions_allowlist
-        {
-            return Ok(PassesResult { pass_all: false });
+        let no_call_samples = res_quality
+            .no_call_samples
+            .iter()
+            .map(|s| s.as_str())
+            .collect::<Vec<_>>();
+        // Check the filters first that are cheap to compute, the ClinVar filter needs a
+        // database lookup.
+        let filters: [(Filter, &dyn Fn() -> Result<bool, anyhow::Error>); 9] = [
+            (Filter::Frequency, &|| {
+                frequency::passes(&self.query, seqvar)
+            }),
+            (Filter::Consequences, &|| {
+                consequences::passes(&self.query, seqvar)
+            }),
+            (Filter::Quality, &|| Ok(res_quality.pass)),
+            (Filter::GenesAllowlist, &|| {
+                Ok(genes_allowlist::passes(&self.hgnc_allowlist, seqvar))
+            }),
+            (Filter::RegionsAllowlist, &|| {
+                Ok(regions_allowlist::passes(&self.query, seqvar))
+            }),
+            (Filter::ParalogLoci, &|| {
+                Ok(paralogs::passes(&self.query, &self.paralog_loci, seqvar))
+            }),
+            (Filter::Genotype, &|| {
+                genotype::passes(
+                    &self.query,
+                    &self.pedigree,
+                    &self.karyotypes,
+                    seqvar,
+                    &no_call_samples,
+                )
+            }),
+            (Filter::Clinvar, &|| {
+                clinvar::passes(&self.query, annotator, seqvar)
+            }),
+            (Filter::ProteinDomain, &|| {
+                protein_domains::passes(&self.query, self.protein_domains.as_ref(), seqvar)
+            }),
+        ];
+
+        let mut failed_filters = Vec::new();
+        for (filter, passes) in filters {
+            if !passes()? {
+                failed_filters.push(filter);
+                if !evaluate_all {
+                    break;
+                }
+            }
         }
-        // Now also check the genotype that needs the quality filter output as input.
-        if !genotype::passes(
+        Ok(PassesResult {
+            pass_all: failed_filters.is_empty(),
+            failed_filters,
+        })
+    }
+
+    /// Explain the decisions of all filters for the annotated `SequenceVariant`.
+    pub fn explain(
+        &self,
+        seqvar: &SequenceVariant,
+        annotator: &Annotator,
+    ) -> Result<explain::Explanation, anyhow::Error> {
+        let mut decisions = explain::decisions(
             &self.query,
+            &self.hgnc_allowlist,
+            &self.pedigree,
+            &self.karyotypes,
             seqvar,
-            &res_quality
-                .no_call_samples
+        )?;
+        decisions.push(explain::Decision::new(
+            "clinvar",
+            serde_json::json!({
+                "require_in_clinvar": self.query.require_in_clinvar,
+                "include_benign": self.query.clinvar_include_benign,
+                "include_likely_benign": self.query.clinvar_include_likely_benign,
+                "include_uncertain_significance": self.query.clinvar_include_uncertain_significance,
+                "include_likely_pathogenic": self.query.clinvar_include_likely_pathogenic,
+                "include_pathogenic": self.query.clinvar_include_pathogenic,
+            }),
+            serde_json::Value::Null,
+            clinvar::passes(&self.query, annotator, seqvar)?,
+        )?);
+        decisions.push(explain::Decision::new(
+            "paralog_loci",
+            serde_json::json!({
+                "paralog_loci": self.query.paralog_loci,
+            }),
+            self.paralog_loci
+                .overlapping(seqvar)
                 .iter()
-                .map(|s| s.as_str())
+                .map(|locus| locus.gene)
                 .collect::<Vec<_>>(),
-        )? {
-            return Ok(PassesResult { pass_all: false });
-        }
-        // If we passed until here, check the presence in ClinVar which needs a database lookup.
-        Ok(PassesResult {
-            pass_all: clinvar::passes(&self.query, annotator, seqvar)?,
-        })
+            paralogs::passes(&self.query, &self.paralog_loci, seqvar),
+        )?);
+        decisions.push(explain::Decision::new(
+            "protein_domain",
+            serde_json::json!({
+                "require_in_protein_domain": self.query.require_in_protein_domain,
+            }),
+            self.protein_domains
+                .as_ref()
+                .map(|protein_domains| protein_domains.domains_for(seqvar))
+                .unwrap_or_default(),
+            protein_domains::passes(&self.query, self.protein_domains.as_ref(), seqvar)?,
+        )?);
+        Ok(explain::Explanation::new(&seqvar.into(), decisions))
     }
 }
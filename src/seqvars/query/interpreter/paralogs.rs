@@ -0,0 +1,56 @@
+use crate::seqvars::query::{
+    paralogs::ParalogLoci,
+    schema::{CaseQuery, ParalogLociChoice, SequenceVariant},
+};
+
+/// Determine whether the `SequenceVariant` passes the paralog loci filter.
+pub fn passes(query: &CaseQuery, paralog_loci: &ParalogLoci, seqvar: &SequenceVariant) -> bool {
+    if query.paralog_loci != ParalogLociChoice::Exclude {
+        return true;
+    }
+
+    let result = paralog_loci.overlapping(seqvar).is_empty();
+    if !result {
+        tracing::trace!(
+            "variant {:?} fails paralog loci filter from query {:?}",
+            seqvar,
+            query
+        );
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        common::GenomeRelease,
+        seqvars::query::{
+            paralogs::ParalogLoci,
+            schema::{CaseQuery, ParalogLociChoice, SequenceVariant},
+        },
+    };
+
+    #[rstest::rstest]
+    #[case(ParalogLociChoice::Include, 70230000, true)]
+    #[case(ParalogLociChoice::Flag, 70230000, true)]
+    #[case(ParalogLociChoice::Exclude, 70230000, false)]
+    #[case(ParalogLociChoice::Exclude, 80000000, true)]
+    fn passes(#[case] paralog_loci: ParalogLociChoice, #[case] pos: i32, #[case] expected: bool) {
+        let query = CaseQuery {
+            paralog_loci,
+            ..Default::default()
+        };
+        let seqvar = SequenceVariant {
+            chrom: "5".into(),
+            pos,
+            reference: "A".into(),
+            alternative: "G".into(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            super::passes(&query, &ParalogLoci::new(GenomeRelease::Grch37), &seqvar),
+            expected
+        );
+    }
+}
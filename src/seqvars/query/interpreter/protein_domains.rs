@@ -0,0 +1,93 @@
+use crate::seqvars::query::{
+    protein_domains::ProteinDomains,
+    schema::{CaseQuery, SequenceVariant},
+};
+
+/// Determine whether the `SequenceVariant` passes the protein domain filter.
+pub fn passes(
+    query: &CaseQuery,
+    protein_domains: Option<&ProteinDomains>,
+    seqvar: &SequenceVariant,
+) -> Result<bool, anyhow::Error> {
+    if !query.require_in_protein_domain {
+        return Ok(true);
+    }
+
+    let protein_domains = protein_domains.ok_or_else(|| {
+        anyhow::anyhow!("query requires protein domains but none have been loaded")
+    })?;
+    let result = !protein_domains.domains_for(seqvar).is_empty();
+    if !result {
+        tracing::trace!(
+            "variant {:?} fails protein domain filter from query {:?}",
+            seqvar,
+            query
+        );
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::seqvars::ann::{AnnField, Consequence, Pos};
+
+    use crate::seqvars::query::{
+        protein_domains::ProteinDomains,
+        schema::{CaseQuery, SequenceVariant},
+    };
+
+    #[rstest::rstest]
+    #[case(false, 100, true)]
+    #[case(false, 500, true)]
+    #[case(true, 100, false)]
+    #[case(true, 500, true)]
+    fn passes(
+        #[case] require_in_protein_domain: bool,
+        #[case] protein_pos: i32,
+        #[case] expected: bool,
+    ) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("domains.tsv");
+        std::fs::write(
+            &path,
+            "transcript_id\tsource\taccession\tname\tstart\tend\n\
+             NM_000492.4\tPfam\tPF00005\tABC_tran\t423\t571\n",
+        )?;
+        let protein_domains = ProteinDomains::from_path(path.to_str().unwrap())?;
+
+        let query = CaseQuery {
+            require_in_protein_domain,
+            ..Default::default()
+        };
+        let seqvar = SequenceVariant {
+            reference: "A".into(),
+            alternative: "G".into(),
+            ann_fields: vec![AnnField {
+                consequences: vec![Consequence::MissenseVariant],
+                feature_id: "NM_000492.4".into(),
+                protein_pos: Some(Pos {
+                    ord: protein_pos,
+                    total: None,
+                }),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            super::passes(&query, Some(&protein_domains), &seqvar)?,
+            expected
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn passes_without_domains() {
+        let query = CaseQuery {
+            require_in_protein_domain: true,
+            ..Default::default()
+        };
+        assert!(super::passes(&query, None, &Default::default()).is_err());
+    }
+}
@@ -57,7 +57,7 @@ pub fn passes(query: &CaseQuery, seqvar: &SequenceVariant) -> Result<PassOrNoCal
 }
 
 /// Return failure code (or None for all-pass) for one sample's call info.
-fn passes_for_sample(
+pub fn passes_for_sample(
     quality_settings: &QualitySettings,
     call_info: &CallInfo,
 ) -> Option<FailChoice> {
@@ -1,8 +1,18 @@
 //! Code implementing the "seqvars query" sub command.
 
 pub mod annonars;
+pub mod case_store;
+pub mod comphet;
+pub mod denovo;
+#[cfg(test)]
+pub mod fixtures;
+pub mod inhouse;
 pub mod interpreter;
 pub mod output;
+pub mod paralogs;
+pub mod phenotype;
+pub mod presets;
+pub mod protein_domains;
 pub mod schema;
 pub mod sorting;
 
@@ -14,16 +24,23 @@ use ext_sort::LimitedBufferBuilder;
 use ext_sort::{ExternalSorter, ExternalSorterBuilder};
 use futures::TryStreamExt;
 use itertools::Itertools;
-use mehari::common::noodles::open_vcf_reader;
 
 use mehari::annotate::seqvars::CHROM_TO_CHROM_NO;
 use rand_core::{RngCore, SeedableRng};
+use strum::IntoEnumIterator;
 use thousands::Separable;
 use uuid::Uuid;
 
 use crate::common;
+use crate::seqvars::aggregate::reader::InhouseDb;
 use crate::seqvars::query::schema::GenotypeChoice;
-use crate::{common::trace_rss_now, common::GenomeRelease};
+use crate::{
+    common::cytoband::Cytobands,
+    common::reference::{FastaReference, Reference},
+    common::trace_rss_now,
+    common::GenomeRelease,
+    common::OutputFormat,
+};
 
 use self::annonars::Annotator;
 use self::schema::CaseQuery;
@@ -31,7 +48,7 @@ use self::schema::SequenceVariant;
 use self::sorting::{ByCoordinate, ByHgncId};
 
 /// Command line arguments for `seqvars query` sub command.
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(author, version, about = "Run query for seqvars", long_about = None)]
 pub struct Args {
     /// Genome release to assume.
@@ -41,6 +58,8 @@ pub struct Args {
     #[arg(long)]
     pub result_set_id: Option<String>,
     /// The case UUID.
+    ///
+    /// Defaults to the identifier of `--path-phenopacket` if it is a UUID.
     #[arg(long)]
     pub case_uuid_id: Option<uuid::Uuid>,
     /// Path to worker database to use for querying.
@@ -55,33 +74,181 @@ pub struct Args {
     /// Path to the output TSV file.
     #[arg(long)]
     pub path_output: String,
+    /// Format of the output file.
+    #[arg(long, value_enum, default_value_t = OutputFormat::default())]
+    pub output_format: OutputFormat,
 
     /// Optional maximal number of total records to write out.
+    ///
+    /// If the query JSON also specifies `max_results` then the smaller value is used.
     #[arg(long)]
     pub max_results: Option<usize>,
+    /// Optional path to write the result set information JSON to.
+    #[arg(long)]
+    pub path_output_info: Option<String>,
+    /// Optional path to write the per-gene summary TSV file to.
+    #[arg(long)]
+    pub path_gene_summary: Option<String>,
+    /// Explain the filter decisions for the given variants, e.g., `chr1:12345:A:G`.
+    #[arg(long, value_delimiter = ',', requires = "path_output_explain")]
+    pub explain: Vec<String>,
+    /// Explain the filter decisions for all variants.
+    #[arg(long, requires = "path_output_explain")]
+    pub explain_all: bool,
+    /// Path to write the filter decision explanations JSONL file to.
+    #[arg(long)]
+    pub path_output_explain: Option<String>,
+    /// Write out all records together with the filters they fail instead of dropping them.
+    #[arg(long)]
+    pub soft_filter: bool,
+    /// Evaluate all filters for each record and write the number of records removed by
+    /// each filter to the result set information.
+    #[arg(long)]
+    pub filter_impact: bool,
+    /// The analysis mode.
+    #[arg(long, value_enum, default_value = "default")]
+    pub mode: QueryMode,
+    /// Name of the index for `--mode denovo`.
+    ///
+    /// Defaults to the first affected individual with both parents in the pedigree.
+    #[arg(long)]
+    pub denovo_index: Option<String>,
+    /// Minimal depth of the parents' reference calls in `--mode denovo`.
+    #[arg(long, default_value = "10")]
+    pub denovo_min_dp: i32,
+    /// Minimal genotype quality of the parents' reference calls in `--mode denovo`.
+    #[arg(long, default_value = "20")]
+    pub denovo_min_gq: f32,
+    /// Optional path to write the ranked de novo candidates TSV file to in `--mode denovo`.
+    #[arg(long)]
+    pub path_denovo_candidates: Option<String>,
+    /// Optional path to a TSV file with the karyotypes of samples with non-standard
+    /// sex chromosome karyotypes, e.g., `XXY` or `X0`.
+    #[arg(long)]
+    pub path_karyotypes: Option<String>,
+    /// Optional path to a per-case RocksDB to read the variants from.
+    ///
+    /// The RocksDB is created from `--path-input` if it does not exist yet or was built
+    /// from a different version of the file.  It allows re-running queries without
+    /// re-reading the VCF and to only read the variants in the query's genomic regions.
+    #[arg(long)]
+    pub path_case_rocksdb: Option<String>,
+    /// Optional path to the in-house frequency RocksDB built by `seqvars aggregate`.
+    #[arg(long)]
+    pub path_inhouse_rocksdb: Option<String>,
+    /// Column family name for the count data in the in-house RocksDB.
+    #[arg(long, default_value = "counts")]
+    pub inhouse_cf_counts: String,
     /// Optional seed for RNG.
     #[arg(long)]
     pub rng_seed: Option<u64>,
     /// Maximal distance to TAD to consider (unused, but required when loading database).
     #[arg(long, default_value_t = 10_000)]
     pub max_tad_distance: i32,
+    /// Add the cytogenetic band of each variant to the output.
+    #[arg(long)]
+    pub with_cytobands: bool,
+    /// Path to the indexed reference FASTA file; optional.
+    ///
+    /// Required for the genomic HGVS descriptions of deletions and insertions.
+    #[arg(long)]
+    pub path_reference: Option<String>,
+    /// Optional path to a TSV file with protein domains per transcript, e.g., from Pfam or
+    /// InterPro, for annotating and filtering missense and in-frame variants.
+    #[arg(long)]
+    pub path_protein_domains: Option<String>,
+    /// HPO terms of the case for the phenotype score of the genes, e.g.,
+    /// `HP:0001250,HP:0001263`.
+    #[arg(long, value_delimiter = ',')]
+    pub hpo_terms: Vec<String>,
+    /// Optional path to a GA4GH phenopacket JSON file (a `Family` or a single `Phenopacket`)
+    /// with further HPO terms of the affected individuals of the case.
+    #[arg(long)]
+    pub path_phenopacket: Option<String>,
+}
+
+/// The analysis mode of `seqvars query`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QueryMode {
+    /// Apply the query only.
+    #[default]
+    Default,
+    /// Additionally require the variants to be de novo candidates in a trio.
+    Denovo,
 }
 
 /// Utility struct to store statistics about counts.
-#[derive(Debug, Default)]
-struct QueryStats {
+#[derive(Debug)]
+pub(crate) struct QueryStats {
     pub count_passed: usize,
     pub count_total: usize,
+    pub count_results: usize,
+    pub count_written: usize,
+    pub count_results_soft_failed: usize,
+    pub count_written_soft_failed: usize,
+    pub result_set_truncated: bool,
     pub by_consequence: indexmap::IndexMap<mehari::annotate::seqvars::ann::Consequence, usize>,
+    pub filter_impact: indexmap::IndexMap<String, output::FilterImpact>,
+}
+
+/// Name of the gene-level recessive mode filter in `QueryStats::filter_impact`.
+const FILTER_RECESSIVE_MODE: &str = "recessive_mode";
+
+/// Name of the de novo mode filter in `QueryStats::filter_impact`.
+const FILTER_DENOVO_MODE: &str = "denovo_mode";
+
+impl Default for QueryStats {
+    fn default() -> Self {
+        Self {
+            count_passed: 0,
+            count_total: 0,
+            count_results: 0,
+            count_written: 0,
+            count_results_soft_failed: 0,
+            count_written_soft_failed: 0,
+            result_set_truncated: false,
+            by_consequence: Default::default(),
+            filter_impact: interpreter::Filter::iter()
+                .map(|filter| filter.to_string())
+                .chain([FILTER_RECESSIVE_MODE, FILTER_DENOVO_MODE].map(String::from))
+                .map(|name| (name, Default::default()))
+                .collect(),
+        }
+    }
+}
+
+impl QueryStats {
+    /// Register the filters failed by one record, in order of evaluation.
+    fn register_failed_filters<S: ToString>(&mut self, failed_filters: &[S]) {
+        for (i, filter) in failed_filters.iter().enumerate() {
+            let impact = self.filter_impact.entry(filter.to_string()).or_default();
+            if i == 0 {
+                impact.first_failing += 1;
+            }
+            impact.marginal += 1;
+        }
+    }
+}
+
+/// Result of the gene-level recessive mode check, see `passes_for_gene()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct GenePasses {
+    /// Whether the variants of the gene pass.
+    pub pass: bool,
+    /// Pairs of indices of the compound heterozygous variants.
+    pub comphet_pairs: Vec<(usize, usize)>,
 }
 
 /// Checks whether the variants pass through the query interpreter.
 ///
-/// This function is only relevant if the query uses recessive mode.
+/// This function is only relevant if the query uses recessive mode.  In compound
+/// heterozygous mode, the index must carry one allele from each parent.  The parental
+/// origin is derived from the genotypes of the parents or, failing that, from the
+/// phasing of the index genotypes.
 fn passes_for_gene(
     query: &CaseQuery,
-    seqvars: &Vec<SequenceVariant>,
-) -> Result<bool, anyhow::Error> {
+    seqvars: &[SequenceVariant],
+) -> Result<GenePasses, anyhow::Error> {
     #[derive(Debug, Clone, PartialEq, Eq)]
     enum Mode {
         ComphetRecessive,
@@ -109,18 +276,21 @@ fn passes_for_gene(
             }
             _ => (),
         });
-
-    eprintln!(
+    tracing::trace!(
         "mode = {:?}, index_name = {:?}, parents = {:?}",
-        mode, &index_name, &parents
+        mode,
+        &index_name,
+        &parents
     );
 
     // No special handling for non-recessive mode.
     if mode == Mode::Other {
-        return Ok(true);
+        return Ok(GenePasses {
+            pass: true,
+            ..Default::default()
+        });
     }
 
-    let mut seen_het_parents = Vec::new();
     for seqvar in seqvars {
         // Get parsed index genotype.
         let index_gt: common::Genotype = seqvar
@@ -133,80 +303,191 @@ fn passes_for_gene(
             .parse()
             .map_err(|e| anyhow::anyhow!("could not parse index genotype: {}", e))?;
 
-        eprintln!(
-            "seqvar = {:?}, index_gt = {:?}, mode = {:?}",
-            &seqvar, &index_gt, mode
-        );
         if mode == Mode::Recessive && index_gt == common::Genotype::HomAlt {
             // if hom. recessive is allowed then we are done
-            return Ok(true);
-        } else if mode != Mode::Recessive && index_gt != common::Genotype::Het {
-            // it only makese sense to continue in recessive mode if the index is het.
-            return Ok(false);
-        }
-
-        // Otherwise, the index must be Het and we have to check which parent is also het.
-        // At this point, only one parent can be het.
-        let parent_gts = parents
-            .iter()
-            .map(|parent_name| {
-                seqvar
-                    .call_info
-                    .get(parent_name)
-                    .expect("no call info for parent")
-                    .genotype
-                    .as_ref()
-                    .expect("no GT for parent")
-                    .parse::<common::Genotype>()
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-        let het_parents = parents
-            .iter()
-            .zip(parent_gts.iter())
-            .filter(|(_, gt)| **gt == common::Genotype::Het)
-            .map(|(name, _)| name.clone())
-            .collect::<Vec<_>>();
-        assert!(het_parents.len() <= 1);
-        eprintln!("het_parents = {:?}", &het_parents);
-        if let Some(parent) = het_parents.first() {
-            if !seen_het_parents.contains(parent) {
-                seen_het_parents.push(parent.clone());
-            }
+            return Ok(GenePasses {
+                pass: true,
+                ..Default::default()
+            });
+        } else if mode == Mode::ComphetRecessive && index_gt != common::Genotype::Het {
+            // it only makes sense to continue in comp. het. mode if the index is het.
+            return Ok(GenePasses::default());
         }
+    }
 
-        eprintln!("seen_het_parents = {:?}", &seen_het_parents);
+    // Otherwise, look for pairs of het. variants inherited from different parents.
+    let comphet_pairs = comphet::comphet_pairs(seqvars, &index_name, &parents)?;
+    tracing::trace!("comphet_pairs = {:?}", &comphet_pairs);
+    Ok(GenePasses {
+        pass: !comphet_pairs.is_empty(),
+        comphet_pairs,
+    })
+}
 
-        // If the number of seen het. parents is equal to the number of parents, we are done.
-        if seen_het_parents.len() == parents.len() {
-            return Ok(true);
+/// Apply the gene-level recessive mode filter to the records of one gene.
+///
+/// Only records passing all other filters are considered.  In soft-filter mode, the
+/// records failing the recessive mode filter are kept and the failure is recorded.
+/// The records of compound heterozygous pairs are flagged with their partners.
+fn filter_gene_group(
+    query: &CaseQuery,
+    mut group: Vec<ByHgncId>,
+    soft_filter: bool,
+    stats: &mut QueryStats,
+) -> Result<Vec<ByHgncId>, anyhow::Error> {
+    let passing_idxs = group
+        .iter()
+        .positions(|record| record.failed_filters.is_empty())
+        .collect::<Vec<_>>();
+    if passing_idxs.is_empty() {
+        return Ok(group);
+    }
+    let passing = passing_idxs
+        .iter()
+        .map(|idx| group[*idx].seqvar.clone())
+        .collect::<Vec<_>>();
+    let passes = passes_for_gene(query, &passing)?;
+    if passes.pass {
+        for (i, j) in passes.comphet_pairs {
+            let (i, j) = (passing_idxs[i], passing_idxs[j]);
+            let partner_i = interpreter::explain::Target::from(&group[i].seqvar).to_string();
+            let partner_j = interpreter::explain::Target::from(&group[j].seqvar).to_string();
+            group[i].comphet_partners.push(partner_j);
+            group[j].comphet_partners.push(partner_i);
         }
+        return Ok(group);
     }
 
-    // one of the recessive modes was active and we did not find all parents
-    Ok(false)
+    for record in group
+        .iter_mut()
+        .filter(|record| record.failed_filters.is_empty())
+    {
+        stats.register_failed_filters(&[FILTER_RECESSIVE_MODE]);
+        record
+            .failed_filters
+            .push(FILTER_RECESSIVE_MODE.to_string());
+    }
+    if soft_filter {
+        Ok(group)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Number of distinct result ranks, see `result_rank()`.
+const RESULT_RANK_COUNT: usize = 5;
+
+/// Return the rank of `seqvar` used for truncating the result set; lower is better.
+///
+/// The rank is given by the highest putative impact over all annotations, e.g., of all
+/// transcripts, with records without annotation being ranked last.
+fn result_rank(seqvar: &SequenceVariant) -> usize {
+    use mehari::annotate::seqvars::ann::PutativeImpact;
+
+    seqvar
+        .ann_fields
+        .iter()
+        .map(|ann| match ann.putative_impact {
+            PutativeImpact::High => 0,
+            PutativeImpact::Moderate => 1,
+            PutativeImpact::Low => 2,
+            PutativeImpact::Modifier => 3,
+        })
+        .min()
+        .unwrap_or(4)
+}
+
+/// Number of distinct truncation ranks, see `truncation_rank()`.
+const TRUNCATION_RANK_COUNT: usize = 2 * RESULT_RANK_COUNT;
+
+/// Return the rank of `record` used for truncating the result set; lower is better.
+///
+/// Records failing filters in `--soft-filter` mode are ranked after all passing records
+/// so they never take the place of passing records; within both groups, the records are
+/// ranked by `result_rank()`.
+fn truncation_rank(record: &ByCoordinate) -> usize {
+    let offset = if record.failed_filters.is_empty() {
+        0
+    } else {
+        RESULT_RANK_COUNT
+    };
+    offset + result_rank(&record.seqvar)
+}
+
+/// Compute the number of records to keep for each rank given the number of records
+/// with each rank in `counts` so that at most `max_results` records are kept.
+///
+/// Records with better rank are kept first.
+fn rank_limits<const N: usize>(counts: &[usize; N], max_results: usize) -> [usize; N] {
+    let mut remaining = max_results;
+    let mut result = [0; N];
+    for (limit, count) in result.iter_mut().zip(counts.iter()) {
+        *limit = std::cmp::min(*count, remaining);
+        remaining -= *limit;
+    }
+    result
 }
 
 /// Run the `args.path_input` VCF file and run through the given `interpreter` writing to
 /// `args.path_output`.
-async fn run_query(
+pub(crate) async fn run_query(
     interpreter: &interpreter::QueryInterpreter,
     args: &Args,
     annotator: &annonars::Annotator,
+    cytobands: Option<&Cytobands>,
+    gene_hpo_sets: &phenotype::GeneHpoSets,
     rng: &mut rand::rngs::StdRng,
 ) -> Result<QueryStats, anyhow::Error> {
     let tmp_dir = tempfile::TempDir::new()?;
 
+    // Load the HPO terms of the case for the phenotype score.
+    let case_terms = phenotype::load_case_terms(&args.hpo_terms, args.path_phenopacket.as_deref())?;
+    if !case_terms.is_empty() && gene_hpo_sets.is_empty() {
+        tracing::warn!("HPO terms given but no gene-HPO annotations in the database");
+    }
+
     let chrom_to_chrom_no = &CHROM_TO_CHROM_NO;
     let mut stats = QueryStats::default();
 
     // Buffer for generating UUIDs.
     let mut uuid_buf = [0u8; 16];
 
-    // Open VCF file, create reader, and read header.
-    let mut input_reader = open_vcf_reader(&args.path_input).await.map_err(|e| {
-        anyhow::anyhow!("could not open file {} for reading: {}", args.path_input, e)
-    })?;
-    let input_header = input_reader.read_header().await?;
+    // Open the case store, reading from the VCF file or the per-case RocksDB.
+    let case_store = case_store::open(&args.path_input, args.path_case_rocksdb.as_deref()).await?;
+
+    // Optionally, open the in-house frequency database.
+    let inhouse_db = args
+        .path_inhouse_rocksdb
+        .as_ref()
+        .map(|path| InhouseDb::open(path, &args.inhouse_cf_counts))
+        .transpose()?;
+
+    // Optionally, write out explanations of the filter decisions.
+    let mut explainer = Explainer::with_args(args)?;
+
+    // In de novo mode, select the trio from the pedigree in the input header.
+    let denovo_filter = if args.mode == QueryMode::Denovo {
+        let mut reader = mehari::common::noodles::open_vcf_reader(&args.path_input).await?;
+        let header = reader.read_header().await?;
+        let (pedigree, _) = common::extract_pedigree_and_case_uuid(&header)?;
+        let trio = denovo::Trio::from_pedigree(&pedigree, args.denovo_index.as_deref())?;
+        let index_karyotype = interpreter
+            .karyotypes
+            .get(&trio.index, pedigree.individuals[&trio.index].sex);
+        tracing::info!(
+            "looking for de novo variants in {:?} with index karyotype {:?}",
+            &trio,
+            &index_karyotype
+        );
+        Some(denovo::DenovoFilter {
+            trio,
+            min_dp: args.denovo_min_dp,
+            min_gq: args.denovo_min_gq,
+            index_karyotype,
+        })
+    } else {
+        None
+    };
 
     let path_unsorted = tmp_dir.path().join("unsorted.jsonl");
     let path_by_hgnc = tmp_dir.path().join("by_hgnc_filtered.jsonl");
@@ -220,18 +501,43 @@ async fn run_query(
             .map(std::io::BufWriter::new)
             .map_err(|e| anyhow::anyhow!("could not create temporary unsorted file: {}", e))?;
 
-        let mut records = input_reader.records(&input_header);
-        while let Some(input_record) = records
-            .try_next()
-            .await
-            .map_err(|e| anyhow::anyhow!("could not read VCF record: {}", e))?
-        {
+        // Only read the variants in the genomic regions unless all variants are to be
+        // explained.
+        let mut records = match interpreter.query.genomic_regions.as_ref() {
+            Some(regions) if !regions.is_empty() && explainer.is_none() => {
+                case_store.variants_in_regions(regions)
+            }
+            _ => case_store.variants(),
+        };
+        while let Some(record_seqvar) = records.try_next().await? {
+            let record_seqvar = if let Some(inhouse_db) = inhouse_db.as_ref() {
+                inhouse::annotate(inhouse_db, record_seqvar)?
+            } else {
+                record_seqvar
+            };
             stats.count_total += 1;
-            let record_seqvar = SequenceVariant::from_vcf(&input_record, &input_header)
-                .map_err(|e| anyhow::anyhow!("could not parse VCF record: {}", e))?;
             tracing::debug!("processing record {:?}", record_seqvar);
 
-            if interpreter.passes(&record_seqvar, annotator)?.pass_all {
+            if let Some(explainer) = explainer.as_mut() {
+                explainer.explain(interpreter, &record_seqvar, annotator)?;
+            }
+            let passes = interpreter.passes(
+                &record_seqvar,
+                annotator,
+                args.filter_impact || args.soft_filter,
+            )?;
+            let mut failed_filters = passes
+                .failed_filters
+                .iter()
+                .map(|filter| filter.to_string())
+                .collect::<Vec<_>>();
+            if let Some(denovo_filter) = denovo_filter.as_ref() {
+                if !denovo_filter.is_candidate(&record_seqvar)? {
+                    failed_filters.push(FILTER_DENOVO_MODE.to_string());
+                }
+            }
+            stats.register_failed_filters(&failed_filters);
+            if failed_filters.is_empty() {
                 stats.count_passed += 1;
                 if let Some(ann) = record_seqvar.ann_fields.first() {
                     ann.consequences.iter().for_each(|csq| {
@@ -242,18 +548,23 @@ async fn run_query(
                             .or_insert(1);
                     })
                 }
-                writeln!(
-                    tmp_unsorted,
-                    "{}",
-                    serde_json::to_string(&sorting::ByHgncId::from(record_seqvar))?
-                )
-                .map_err(|e| anyhow::anyhow!("could not write record to unsorted: {}", e))?;
+            }
+            if failed_filters.is_empty() || args.soft_filter {
+                let by_hgnc_id = sorting::ByHgncId {
+                    failed_filters,
+                    ..sorting::ByHgncId::from(record_seqvar)
+                };
+                writeln!(tmp_unsorted, "{}", serde_json::to_string(&by_hgnc_id)?)
+                    .map_err(|e| anyhow::anyhow!("could not write record to unsorted: {}", e))?;
             }
         }
         tmp_unsorted.into_inner()?.sync_all().map_err(|e| {
             anyhow::anyhow!("could not flush temporary output file unsorted: {}", e)
         })?;
     }
+    if let Some(explainer) = explainer {
+        explainer.finish()?;
+    }
 
     let elem_count = 10_000; // at most 10k records in memory
 
@@ -286,26 +597,27 @@ async fn run_query(
             }))
             .map_err(|e| anyhow::anyhow!("problem sorting temporary unsorted file: {}", e))?;
 
-        sorted_iter
+        let groups = sorted_iter
             .map(|res| res.expect("problem reading line after sorting by HGNC ID"))
-            .group_by(|by_hgnc_id| by_hgnc_id.hgnc_id.clone())
-            .into_iter()
-            .map(|(_, group)| {
-                group
-                    .map(|ByHgncId { seqvar, .. }| seqvar)
-                    .collect::<Vec<_>>()
-            })
-            .filter(|seqvars| passes_for_gene(&interpreter.query, seqvars).unwrap())
-            .for_each(|seqvars| {
-                seqvars.into_iter().for_each(|seqvar| {
-                    writeln!(
-                        tmp_by_hgnc_filtered,
-                        "{}",
-                        serde_json::to_string(&sorting::ByCoordinate::from(seqvar)).unwrap()
-                    )
-                    .expect("could not write record to by_hgnc_filtered");
-                })
-            });
+            .group_by(|by_hgnc_id| by_hgnc_id.hgnc_id.clone());
+        for (_, group) in groups.into_iter() {
+            let group = filter_gene_group(
+                &interpreter.query,
+                group.collect(),
+                args.soft_filter,
+                &mut stats,
+            )?;
+            for by_hgnc_id in group {
+                writeln!(
+                    tmp_by_hgnc_filtered,
+                    "{}",
+                    serde_json::to_string(&sorting::ByCoordinate::from(by_hgnc_id))?
+                )
+                .map_err(|e| {
+                    anyhow::anyhow!("could not write record to by_hgnc_filtered: {}", e)
+                })?;
+            }
+        }
         tmp_by_hgnc_filtered.flush().map_err(|e| {
             anyhow::anyhow!(
                 "could not flush temporary output file by_hgnc_filtered: {}",
@@ -342,9 +654,13 @@ async fn run_query(
 
         sorted_iter
             .map(|res| res.expect("problem reading line after sorting by HGNC ID"))
-            .for_each(|ByCoordinate { seqvar, .. }| {
-                writeln!(tmp_by_coord, "{}", serde_json::to_string(&seqvar).unwrap())
-                    .expect("could not write record to by_coord");
+            .for_each(|by_coordinate| {
+                writeln!(
+                    tmp_by_coord,
+                    "{}",
+                    serde_json::to_string(&by_coordinate).unwrap()
+                )
+                .expect("could not write record to by_coord");
             });
 
         tmp_by_coord.flush().map_err(|e| {
@@ -356,27 +672,238 @@ async fn run_query(
     }
 
     // Finally, perform annotation of the record using the annonars library and write it
-    // in TSV format, ready for import into the database.  However, in recessive mode, we
-    // have to do a second pass to properly collect compound heterozygous variants.
+    // in the selected output format, by default TSV ready for import into the database.
+
+    let mut writer = output::Writer::new(&args.path_output, args.output_format)?;
+
+    // If the number of results is limited then count the records by rank first so we
+    // can keep the top-ranked records in coordinate order.
+    let max_results = match (interpreter.query.max_results, args.max_results) {
+        (Some(a), Some(b)) => Some(std::cmp::min(a, b)),
+        (a, b) => a.or(b),
+    };
+    let mut limits = if let Some(max_results) = max_results {
+        let mut counts = [0; TRUNCATION_RANK_COUNT];
+        for_each_record(&path_by_coord, |record| {
+            counts[truncation_rank(&record)] += 1;
+            Ok(())
+        })?;
+        let count_total: usize = counts.iter().sum();
+        if count_total > max_results {
+            tracing::warn!(
+                "{}: truncating result set of {} records to {} records",
+                crate::codes::SEQVARS_QUERY_RESULT_TRUNCATED,
+                count_total.separate_with_commas(),
+                max_results.separate_with_commas()
+            );
+            stats.result_set_truncated = true;
+        }
+        Some(rank_limits(&counts, max_results))
+    } else {
+        None
+    };
+
+    // Optionally, accumulate the per-gene summary of the written records.
+    let mut gene_summary = args
+        .path_gene_summary
+        .as_ref()
+        .map(|_| output::gene_summary::Accumulator::new(interpreter.query.index_sample()));
+    // Optionally, collect the de novo candidates.
+    let mut denovo_candidates = match (denovo_filter, args.path_denovo_candidates.as_ref()) {
+        (Some(denovo_filter), Some(_)) => {
+            Some(output::denovo::Accumulator::new(denovo_filter.trio))
+        }
+        _ => None,
+    };
+
+    // Optionally, open the reference genome for the genomic HGVS descriptions of indels.
+    let mut reference = args
+        .path_reference
+        .as_deref()
+        .map(FastaReference::from_path)
+        .transpose()?;
+
+    for_each_record(&path_by_coord, |record| {
+        let soft_failed = !record.failed_filters.is_empty();
+        if soft_failed {
+            stats.count_results_soft_failed += 1;
+        } else {
+            stats.count_results += 1;
+        }
+        if let Some(limits) = limits.as_mut() {
+            let limit = &mut limits[truncation_rank(&record)];
+            if *limit == 0 {
+                return Ok(());
+            }
+            *limit -= 1;
+        }
+
+        if soft_failed {
+            stats.count_written_soft_failed += 1;
+        } else {
+            stats.count_written += 1;
+        }
+        let phenotype_score = if case_terms.is_empty() {
+            None
+        } else {
+            record
+                .seqvar
+                .ann_fields
+                .first()
+                .and_then(|ann| gene_hpo_sets.score(&ann.gene_id, &case_terms))
+        };
+        let payload = create_payload_and_write_record(
+            &record,
+            phenotype_score,
+            annotator,
+            cytobands,
+            reference
+                .as_mut()
+                .map(|reference| reference as &mut dyn Reference),
+            interpreter.protein_domains.as_ref(),
+            (interpreter.query.paralog_loci == schema::ParalogLociChoice::Flag)
+                .then_some(&interpreter.paralog_loci),
+            chrom_to_chrom_no,
+            &mut writer,
+            args,
+            rng,
+            &mut uuid_buf,
+        )?;
+        if let Some(gene_summary) = gene_summary.as_mut() {
+            // Records failing filters in soft-filter mode do not count towards the summary.
+            if record.failed_filters.is_empty() {
+                gene_summary.register(&record.seqvar, result_rank(&record.seqvar), &payload)?;
+            }
+        }
+        if let Some(denovo_candidates) = denovo_candidates.as_mut() {
+            if record.failed_filters.is_empty() {
+                denovo_candidates.register(&record.seqvar, result_rank(&record.seqvar));
+            }
+        }
+        Ok(())
+    })?;
+
+    writer.flush()?;
+
+    if let (Some(gene_summary), Some(path_gene_summary)) = (gene_summary, &args.path_gene_summary) {
+        gene_summary.write_tsv(path_gene_summary)?;
+    }
+    if let (Some(denovo_candidates), Some(path_denovo_candidates)) =
+        (denovo_candidates, &args.path_denovo_candidates)
+    {
+        denovo_candidates.write_tsv(path_denovo_candidates)?;
+    }
+
+    Ok(stats)
+}
 
-    let mut csv_writer = csv::WriterBuilder::new()
-        .has_headers(true)
-        .delimiter(b'\t')
-        .quote_style(csv::QuoteStyle::Never)
-        .from_path(&args.path_output)?;
+/// Write explanations of the filter decisions for selected or all records.
+struct Explainer {
+    /// The variants to explain, `None` to explain all.
+    targets: Option<std::collections::HashSet<interpreter::explain::Target>>,
+    /// The variants that have been explained so far.
+    seen: std::collections::HashSet<interpreter::explain::Target>,
+    /// Writer for the explanations JSONL file.
+    writer: std::io::BufWriter<std::fs::File>,
+}
 
-    let tmp_by_coord = std::fs::File::open(&path_by_coord)
+impl Explainer {
+    /// Construct from command line arguments, `None` if no explanation was requested.
+    fn with_args(args: &Args) -> Result<Option<Self>, anyhow::Error> {
+        let path = match args.path_output_explain.as_ref() {
+            Some(path) if args.explain_all || !args.explain.is_empty() => path,
+            _ => return Ok(None),
+        };
+        let targets = if args.explain_all {
+            None
+        } else {
+            Some(
+                args.explain
+                    .iter()
+                    .map(|target| target.parse())
+                    .collect::<Result<_, _>>()?,
+            )
+        };
+        let writer = std::fs::File::create(path)
+            .map(std::io::BufWriter::new)
+            .map_err(|e| anyhow::anyhow!("could not create explanation file {}: {}", path, e))?;
+
+        Ok(Some(Self {
+            targets,
+            seen: Default::default(),
+            writer,
+        }))
+    }
+
+    /// Explain `seqvar` if it has been selected.
+    fn explain(
+        &mut self,
+        interpreter: &interpreter::QueryInterpreter,
+        seqvar: &SequenceVariant,
+        annotator: &Annotator,
+    ) -> Result<(), anyhow::Error> {
+        let target = interpreter::explain::Target::from(seqvar);
+        if let Some(targets) = self.targets.as_ref() {
+            if !targets.contains(&target) {
+                return Ok(());
+            }
+            self.seen.insert(target);
+        }
+        self.write(&interpreter.explain(seqvar, annotator)?)
+    }
+
+    /// Write out explanations for the selected variants not found in the input.
+    fn finish(mut self) -> Result<(), anyhow::Error> {
+        if let Some(targets) = self.targets.take() {
+            let seen = std::mem::take(&mut self.seen);
+            for target in targets.iter().filter(|target| !seen.contains(target)) {
+                tracing::warn!(
+                    "{}: variant {} to explain not found in input",
+                    crate::codes::SEQVARS_QUERY_EXPLAIN_NOT_FOUND,
+                    target
+                );
+                self.write(&interpreter::explain::Explanation::not_found(target))?;
+            }
+        }
+        self.writer
+            .flush()
+            .map_err(|e| anyhow::anyhow!("could not flush explanation file: {}", e))
+    }
+
+    /// Write one explanation as JSONL.
+    fn write(
+        &mut self,
+        explanation: &interpreter::explain::Explanation,
+    ) -> Result<(), anyhow::Error> {
+        writeln!(self.writer, "{}", serde_json::to_string(explanation)?)
+            .map_err(|e| anyhow::anyhow!("could not write explanation: {}", e))
+    }
+}
+
+/// Read the JSONL file at `path` with `ByCoordinate` records and call `func` on each.
+fn for_each_record<P, F>(path: P, mut func: F) -> Result<(), anyhow::Error>
+where
+    P: AsRef<std::path::Path>,
+    F: FnMut(ByCoordinate) -> Result<(), anyhow::Error>,
+{
+    let reader = std::fs::File::open(path.as_ref())
         .map(std::io::BufReader::new)
-        .map_err(|e| anyhow::anyhow!("could not open temporary by_coord file: {}", e))?;
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "could not open temporary file {}: {}",
+                path.as_ref().display(),
+                e
+            )
+        })?;
 
-    for line in tmp_by_coord.lines() {
+    for line in reader.lines() {
         // get next line into a String
         let line = if let Ok(line) = line {
             line
         } else {
             anyhow::bail!("error reading line from input file")
         };
-        let seqvar: SequenceVariant = serde_json::from_str(&line).map_err(|e| {
+        let record: ByCoordinate = serde_json::from_str(&line).map_err(|e| {
             anyhow::anyhow!(
                 "error parsing line from input file: {:?} (line: {:?})",
                 e,
@@ -384,44 +911,84 @@ async fn run_query(
             )
         })?;
 
-        create_payload_and_write_record(
-            seqvar,
-            annotator,
-            chrom_to_chrom_no,
-            &mut csv_writer,
-            args,
-            rng,
-            &mut uuid_buf,
-        )?;
+        func(record)?;
     }
 
-    Ok(stats)
+    Ok(())
 }
 
 /// Create output payload and write the record to the output file.
+///
+/// Returns the payload that was written.
+#[allow(clippy::too_many_arguments)]
 fn create_payload_and_write_record(
-    seqvar: SequenceVariant,
+    record: &ByCoordinate,
+    phenotype_score: Option<f32>,
     annotator: &Annotator,
+    cytobands: Option<&Cytobands>,
+    reference: Option<&mut dyn Reference>,
+    protein_domains: Option<&protein_domains::ProteinDomains>,
+    paralog_loci: Option<&paralogs::ParalogLoci>,
     chrom_to_chrom_no: &CHROM_TO_CHROM_NO,
-    csv_writer: &mut csv::Writer<std::fs::File>,
+    writer: &mut output::Writer,
     args: &Args,
     rng: &mut rand::rngs::StdRng,
     uuid_buf: &mut [u8; 16],
-) -> Result<(), anyhow::Error> {
+) -> Result<output::Payload, anyhow::Error> {
+    let seqvar = &record.seqvar;
     let result_payload = output::PayloadBuilder::default()
         .case_uuid(args.case_uuid_id.unwrap_or_default())
         .gene_related(
-            output::gene_related::Record::with_seqvar_and_annotator(&seqvar, annotator)
+            output::gene_related::Record::with_seqvar_and_annotator(seqvar, annotator)
                 .map_err(|e| anyhow::anyhow!("problem creating gene-related payload: {}", e))?,
         )
         .variant_related(
-            output::variant_related::Record::with_seqvar_and_annotator(&seqvar, annotator)
+            output::variant_related::Record::with_seqvar_and_annotator(seqvar, annotator)
                 .map_err(|e| anyhow::anyhow!("problem creating variant-related payload: {}", e))?,
         )
+        .hgvs(
+            output::hgvs::Record::with_seqvar(args.genome_release, seqvar, reference)
+                .map_err(|e| anyhow::anyhow!("problem creating HGVS descriptions: {}", e))?,
+        )
         .call_related(
-            output::call_related::Record::with_seqvar(&seqvar)
+            output::call_related::Record::with_seqvar(seqvar)
                 .map_err(|e| anyhow::anyhow!("problem creating call-related payload: {}", e))?,
         )
+        .paralog_caution(
+            paralog_loci
+                .map(|paralog_loci| {
+                    paralog_loci
+                        .overlapping(seqvar)
+                        .into_iter()
+                        .map(paralogs::Caution::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        )
+        .protein_domains(
+            protein_domains
+                .map(|protein_domains| {
+                    protein_domains
+                        .domains_for(seqvar)
+                        .into_iter()
+                        .cloned()
+                        .collect()
+                })
+                .unwrap_or_default(),
+        )
+        .cytoband(cytobands.and_then(|cytobands| {
+            cytobands
+                .band_for(&seqvar.chrom, seqvar.pos as usize)
+                .map(|band| {
+                    format!(
+                        "{}{}",
+                        ::annonars::common::cli::canonicalize(&seqvar.chrom),
+                        band.name
+                    )
+                })
+        }))
+        .failed_filters(record.failed_filters.clone())
+        .comphet_partners(record.comphet_partners.clone())
         .build()
         .map_err(|e| anyhow::anyhow!("could not build payload: {}", e))?;
     eprintln!("result_payload = {:?}", &result_payload);
@@ -433,39 +1000,145 @@ fn create_payload_and_write_record(
         reference,
         alternative,
         ..
-    } = seqvar;
-    csv_writer
-        .serialize(
-            &output::RecordBuilder::default()
-                .smallvariantqueryresultset_id(args.result_set_id.clone().unwrap_or(".".into()))
-                .sodar_uuid(Uuid::from_bytes({
-                    rng.fill_bytes(uuid_buf);
-                    *uuid_buf
-                }))
-                .release(match args.genome_release {
-                    GenomeRelease::Grch37 => "GRCh37".into(),
-                    GenomeRelease::Grch38 => "GRCh38".into(),
-                })
-                .chromosome_no(
-                    *chrom_to_chrom_no
-                        .get(&chromosome)
-                        .expect("invalid chromosome") as i32,
-                )
-                .chromosome(chromosome)
-                .start(start)
-                .end(end)
-                .bin(bin)
-                .reference(reference)
-                .alternative(alternative)
-                .payload(
-                    serde_json::to_string(&result_payload)
-                        .map_err(|e| anyhow::anyhow!("could not serialize payload: {}", e))?,
+    } = seqvar.clone();
+    writer.write(
+        &output::RecordBuilder::default()
+            .smallvariantqueryresultset_id(args.result_set_id.clone().unwrap_or(".".into()))
+            .sodar_uuid(Uuid::from_bytes({
+                rng.fill_bytes(uuid_buf);
+                *uuid_buf
+            }))
+            .release(match args.genome_release {
+                GenomeRelease::Grch37 => "GRCh37".into(),
+                GenomeRelease::Grch38 => "GRCh38".into(),
+            })
+            .chromosome_no(
+                *chrom_to_chrom_no
+                    .get(&chromosome)
+                    .expect("invalid chromosome") as i32,
+            )
+            .chromosome(chromosome)
+            .start(start)
+            .end(end)
+            .bin(bin)
+            .reference(reference)
+            .alternative(alternative)
+            .phenotype_score(phenotype_score)
+            .payload(
+                serde_json::to_string(&result_payload)
+                    .map_err(|e| anyhow::anyhow!("could not serialize payload: {}", e))?,
+            )
+            .build()
+            .map_err(|e| anyhow::anyhow!("could not build record: {}", e))?,
+        &result_payload,
+    )?;
+    Ok(result_payload)
+}
+
+/// The databases used for running queries, loaded once.
+pub struct QueryDbs {
+    /// The worker databases, for genes and cytobands.
+    pub in_memory_dbs: crate::strucvars::query::InMemoryDbs,
+    /// The annonars databases.
+    pub annotator: Annotator,
+    /// The protein domains, if given.
+    pub protein_domains: Option<protein_domains::ProteinDomains>,
+    /// The gene-HPO annotations, empty if not in the database.
+    pub gene_hpo_sets: phenotype::GeneHpoSets,
+}
+
+/// Load the worker and annonars databases from `path_db` and the protein domains from
+/// `path_protein_domains`, if given.
+pub fn load_query_dbs(
+    path_db: &str,
+    genome_release: GenomeRelease,
+    max_tad_distance: i32,
+    path_protein_domains: Option<&str>,
+) -> Result<QueryDbs, anyhow::Error> {
+    tracing::info!("Loading worker databases...");
+    let before_loading = Instant::now();
+    let path_worker_db = format!("{}/worker", path_db);
+    let in_memory_dbs =
+        crate::strucvars::query::load_databases(&path_worker_db, genome_release, max_tad_distance)
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "could not load worker databases from {}: {}",
+                    path_worker_db,
+                    e
                 )
-                .build()
-                .map_err(|e| anyhow::anyhow!("could not build record: {}", e))?,
-        )
-        .map_err(|e| anyhow::anyhow!("could not write record: {}", e))?;
-    Ok(())
+            })?;
+    let annotator = annonars::Annotator::with_path(path_db, genome_release)?;
+    let protein_domains = path_protein_domains
+        .map(protein_domains::ProteinDomains::from_path)
+        .transpose()?;
+    let gene_hpo_sets = phenotype::GeneHpoSets::load(&path_worker_db)?;
+    tracing::info!(
+        "...done loading databases in {:?}",
+        before_loading.elapsed()
+    );
+
+    Ok(QueryDbs {
+        in_memory_dbs,
+        annotator,
+        protein_domains,
+        gene_hpo_sets,
+    })
+}
+
+/// Read the pedigree from the header of the VCF file at `path_input`.
+pub(crate) async fn read_pedigree(
+    path_input: &str,
+) -> Result<mehari::ped::PedigreeByName, anyhow::Error> {
+    let mut reader = mehari::common::noodles::open_vcf_reader(path_input).await?;
+    let header = reader.read_header().await?;
+    Ok(common::extract_pedigree_and_case_uuid(&header)?.0)
+}
+
+/// Build the interpreter for `query` on the VCF file at `path_input`.
+///
+/// The pedigree is read from the header of `path_input` if the query uses one of the
+/// built-in inheritance modes.
+pub async fn build_interpreter(
+    query: CaseQuery,
+    path_input: &str,
+    genome_release: GenomeRelease,
+    query_dbs: &QueryDbs,
+) -> Result<interpreter::QueryInterpreter, anyhow::Error> {
+    if query.require_in_protein_domain && query_dbs.protein_domains.is_none() {
+        anyhow::bail!("query requires protein domains but --path-protein-domains is not given");
+    }
+
+    tracing::info!("Translating gene allow list...");
+    let translated = if let Some(gene_allowlist) = &query.gene_allowlist {
+        if gene_allowlist.is_empty() {
+            None
+        } else {
+            Some(crate::strucvars::query::translate_gene_allowlist(
+                gene_allowlist,
+                &query_dbs.in_memory_dbs,
+            ))
+        }
+    } else {
+        None
+    };
+    let remapped_gene_symbols = translated
+        .as_ref()
+        .map(|translated| translated.remapped_symbols.clone())
+        .unwrap_or_default();
+    let hgnc_allowlist = translated.map(|translated| translated.hgnc_ids);
+
+    // The built-in inheritance modes need the pedigree from the input header.
+    let pedigree = if query.inheritance_mode.is_some() {
+        read_pedigree(path_input).await?
+    } else {
+        Default::default()
+    };
+
+    Ok(interpreter::QueryInterpreter::new(query, hgnc_allowlist)
+        .with_remapped_gene_symbols(remapped_gene_symbols)
+        .with_pedigree(pedigree)
+        .with_protein_domains(query_dbs.protein_domains.clone())
+        .with_paralog_loci(paralogs::ParalogLoci::new(genome_release)))
 }
 
 /// Main entry point for `seqvars query` sub command.
@@ -474,6 +1147,15 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
     tracing::info!("args_common = {:?}", &args_common);
     tracing::info!("args = {:?}", &args);
 
+    // Take the case UUID from the phenopacket if not given.
+    let args = &match (args.case_uuid_id, args.path_phenopacket.as_deref()) {
+        (None, Some(path_phenopacket)) => Args {
+            case_uuid_id: common::phenopacket::CaseMetadata::from_path(path_phenopacket)?.case_uuid,
+            ..args.clone()
+        },
+        _ => args.clone(),
+    };
+
     // Initialize the random number generator from command line seed if given or local entropy
     // source.
     let mut rng = if let Some(rng_seed) = args.rng_seed {
@@ -483,65 +1165,90 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
     };
 
     tracing::info!("Loading query...");
-    let query: schema::CaseQuery =
-        serde_json::from_reader(std::fs::File::open(&args.path_query_json)?)?;
+    let query = presets::resolve(
+        serde_json::from_reader(std::fs::File::open(&args.path_query_json)?)?,
+        &args.path_input,
+    )
+    .await?;
     tracing::info!(
         "... done loading query = {}",
         &serde_json::to_string(&query)?
     );
 
-    tracing::info!("Loading worker databases...");
-    let before_loading = Instant::now();
-    let path_worker_db = format!("{}/worker", &args.path_db);
-    let in_memory_dbs = crate::strucvars::query::load_databases(
-        &path_worker_db,
+    let query_dbs = load_query_dbs(
+        &args.path_db,
         args.genome_release,
         args.max_tad_distance,
-    )
-    .map_err(|e| {
-        anyhow::anyhow!(
-            "could not load worker databases from {}: {}",
-            path_worker_db,
-            e
-        )
-    })?;
-    let annotator = annonars::Annotator::with_path(&args.path_db, args.genome_release)?;
-    tracing::info!(
-        "...done loading databases in {:?}",
-        before_loading.elapsed()
-    );
+        args.path_protein_domains.as_deref(),
+    )?;
 
     trace_rss_now();
 
-    tracing::info!("Translating gene allow list...");
-    let hgnc_allowlist = if let Some(gene_allowlist) = &query.gene_allowlist {
-        if gene_allowlist.is_empty() {
-            None
-        } else {
-            Some(crate::strucvars::query::translate_gene_allowlist(
-                gene_allowlist,
-                &in_memory_dbs,
-            ))
-        }
-    } else {
-        None
-    };
+    let interpreter = build_interpreter(query, &args.path_input, args.genome_release, &query_dbs)
+        .await?
+        .with_karyotypes(common::karyotype::Karyotypes::from_optional_path(
+            args.path_karyotypes.as_deref(),
+        )?);
 
     tracing::info!("Running queries...");
     let before_query = Instant::now();
+    let partial_guard = crate::common::partial::PartialOutputGuard::new(
+        &args.path_output,
+        crate::common::partial::OnFailure::Remove,
+    );
     let query_stats = run_query(
-        &interpreter::QueryInterpreter::new(query, hgnc_allowlist),
+        &interpreter,
         args,
-        &annotator,
+        &query_dbs.annotator,
+        args.with_cytobands
+            .then_some(&query_dbs.in_memory_dbs.cytobands),
+        &query_dbs.gene_hpo_sets,
         &mut rng,
     )
     .await?;
+    partial_guard.finish();
     tracing::info!("... done running query in {:?}", before_query.elapsed());
     tracing::info!(
         "summary: {} records passed out of {}",
         query_stats.count_passed.separate_with_commas(),
         query_stats.count_total.separate_with_commas()
     );
+    if query_stats.result_set_truncated {
+        tracing::warn!(
+            "{}: result set truncated: wrote {} passing and {} soft-failed records",
+            crate::codes::SEQVARS_QUERY_RESULT_TRUNCATED,
+            query_stats.count_written.separate_with_commas(),
+            query_stats.count_written_soft_failed.separate_with_commas()
+        );
+    }
+    if let Some(path_output_info) = &args.path_output_info {
+        let info = output::ResultSetInfo {
+            result_set_truncated: query_stats.result_set_truncated,
+            count_total: query_stats.count_results,
+            count_written: query_stats.count_written,
+            count_total_soft_failed: query_stats.count_results_soft_failed,
+            count_written_soft_failed: query_stats.count_written_soft_failed,
+            filter_impact: if args.filter_impact {
+                query_stats.filter_impact.clone()
+            } else {
+                Default::default()
+            },
+            remapped_gene_symbols: interpreter.remapped_gene_symbols.clone(),
+        };
+        serde_json::to_writer_pretty(std::fs::File::create(path_output_info)?, &info)
+            .map_err(|e| anyhow::anyhow!("could not write result set info: {}", e))?;
+    }
+    if args.filter_impact {
+        tracing::info!("records removed by filter (first failing / marginal)");
+        for (filter, impact) in query_stats.filter_impact.iter() {
+            tracing::info!(
+                "{} -- {} / {}",
+                filter,
+                impact.first_failing.separate_with_commas(),
+                impact.marginal.separate_with_commas()
+            );
+        }
+    }
     tracing::info!("passing records by effect type");
     for (effect, count) in query_stats.by_consequence.iter() {
         tracing::info!("{:?} -- {}", effect, count);
@@ -558,6 +1265,7 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
 
 #[cfg(test)]
 mod test {
+    use mehari::annotate::seqvars::ann::{AnnField, PutativeImpact};
     use rstest::rstest;
 
     use super::schema::{CallInfo, SequenceVariant};
@@ -644,11 +1352,239 @@ mod test {
             })
             .collect::<Vec<_>>();
 
-        assert_eq!(super::passes_for_gene(&query, &seqvars)?, passes);
+        assert_eq!(super::passes_for_gene(&query, &seqvars)?.pass, passes);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::passes(
+        vec!["0/1,0/1,0/0", "0/1,0/0,0/1"],
+        vec![vec![], vec![]],
+        false,
+        vec![vec![], vec![]]
+    )]
+    #[case::fails(
+        vec!["0/1,0/1,0/0", "0/1,0/1,0/0"],
+        vec![vec![], vec![]],
+        false,
+        vec![]
+    )]
+    #[case::fails_soft(
+        vec!["0/1,0/1,0/0", "0/1,0/1,0/0"],
+        vec![vec![], vec![]],
+        true,
+        vec![vec!["recessive_mode"], vec!["recessive_mode"]]
+    )]
+    #[case::fails_soft_with_failed(
+        vec!["0/1,0/1,0/0", "0/1,0/0,0/1"],
+        vec![vec![], vec!["frequency"]],
+        true,
+        vec![vec!["recessive_mode"], vec!["frequency"]]
+    )]
+    fn filter_gene_group(
+        #[case] trio_gts: Vec<&str>,
+        #[case] failed_filters: Vec<Vec<&str>>,
+        #[case] soft_filter: bool,
+        #[case] expected: Vec<Vec<&str>>,
+    ) -> Result<(), anyhow::Error> {
+        let query = CaseQuery {
+            genotype: vec![
+                ("index".into(), Some(GenotypeChoice::ComphetIndex)),
+                ("father".into(), Some(GenotypeChoice::RecessiveParent)),
+                ("mother".into(), Some(GenotypeChoice::RecessiveParent)),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let group = trio_gts
+            .iter()
+            .zip(failed_filters.iter())
+            .map(|(gts, failed_filters)| {
+                let gts: Vec<&str> = gts.split(',').collect();
+                let seqvar = SequenceVariant {
+                    call_info: ["index", "father", "mother"]
+                        .iter()
+                        .zip(gts.iter())
+                        .map(|(sample, gt)| {
+                            (
+                                sample.to_string(),
+                                CallInfo {
+                                    genotype: Some(gt.to_string()),
+                                    ..Default::default()
+                                },
+                            )
+                        })
+                        .collect(),
+                    ..Default::default()
+                };
+                super::ByHgncId {
+                    failed_filters: failed_filters.iter().map(|f| f.to_string()).collect(),
+                    ..super::ByHgncId::from(seqvar)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut stats = super::QueryStats::default();
+        let result = super::filter_gene_group(&query, group, soft_filter, &mut stats)?;
+
+        assert_eq!(
+            result
+                .iter()
+                .map(|record| record.failed_filters.clone())
+                .collect::<Vec<_>>(),
+            expected
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn filter_gene_group_comphet_partners() -> Result<(), anyhow::Error> {
+        let query = CaseQuery {
+            genotype: vec![
+                ("index".into(), Some(GenotypeChoice::ComphetIndex)),
+                ("father".into(), Some(GenotypeChoice::RecessiveParent)),
+                ("mother".into(), Some(GenotypeChoice::RecessiveParent)),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let group = [
+            (100, "0/1,0/1,0/0", vec![]),
+            (200, "0/1,0/0,0/1", vec!["frequency"]),
+            (300, "0/1,0/0,0/1", vec![]),
+        ]
+        .into_iter()
+        .map(|(pos, gts, failed_filters)| {
+            let seqvar = SequenceVariant {
+                chrom: "1".into(),
+                pos,
+                reference: "A".into(),
+                alternative: "G".into(),
+                call_info: ["index", "father", "mother"]
+                    .iter()
+                    .zip(gts.split(','))
+                    .map(|(sample, gt)| {
+                        (
+                            sample.to_string(),
+                            CallInfo {
+                                genotype: Some(gt.to_string()),
+                                ..Default::default()
+                            },
+                        )
+                    })
+                    .collect(),
+                ..Default::default()
+            };
+            super::ByHgncId {
+                failed_filters: failed_filters.iter().map(|f| f.to_string()).collect(),
+                ..super::ByHgncId::from(seqvar)
+            }
+        })
+        .collect::<Vec<_>>();
+
+        let mut stats = super::QueryStats::default();
+        let result = super::filter_gene_group(&query, group, true, &mut stats)?;
+
+        assert_eq!(
+            result
+                .iter()
+                .map(|record| record.comphet_partners.clone())
+                .collect::<Vec<_>>(),
+            vec![vec!["1:300:A:G"], vec![], vec!["1:100:A:G"]]
+        );
 
         Ok(())
     }
 
+    #[rstest]
+    #[case::no_annotation(&[], 4)]
+    #[case::first(&[PutativeImpact::High, PutativeImpact::Modifier], 0)]
+    #[case::later_transcript(&[PutativeImpact::Modifier, PutativeImpact::Low, PutativeImpact::High], 0)]
+    #[case::moderate(&[PutativeImpact::Modifier, PutativeImpact::Moderate], 1)]
+    #[case::modifier(&[PutativeImpact::Modifier], 3)]
+    fn result_rank(#[case] impacts: &[PutativeImpact], #[case] expected: usize) {
+        let seqvar = SequenceVariant {
+            ann_fields: impacts
+                .iter()
+                .map(|putative_impact| AnnField {
+                    putative_impact: *putative_impact,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        assert_eq!(super::result_rank(&seqvar), expected);
+    }
+
+    #[rstest]
+    #[case::passing_high(&[PutativeImpact::High], &[], 0)]
+    #[case::passing_none(&[], &[], 4)]
+    #[case::soft_failed_high(&[PutativeImpact::High], &["frequency"], 5)]
+    #[case::soft_failed_modifier(&[PutativeImpact::Modifier], &["frequency"], 8)]
+    fn truncation_rank(
+        #[case] impacts: &[PutativeImpact],
+        #[case] failed_filters: &[&str],
+        #[case] expected: usize,
+    ) {
+        let record = super::ByCoordinate {
+            failed_filters: failed_filters.iter().map(|f| f.to_string()).collect(),
+            ..super::ByCoordinate::from(SequenceVariant {
+                ann_fields: impacts
+                    .iter()
+                    .map(|putative_impact| AnnField {
+                        putative_impact: *putative_impact,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            })
+        };
+
+        assert_eq!(super::truncation_rank(&record), expected);
+    }
+
+    #[test]
+    fn rank_limits_soft_failed_last() {
+        // Soft-failed HIGH impact records are only kept after passing MODIFIER ones.
+        let counts = [0, 0, 0, 2, 0, 3, 0, 0, 0, 0];
+        assert_eq!(
+            super::rank_limits(&counts, 4),
+            [0, 0, 0, 2, 0, 2, 0, 0, 0, 0]
+        );
+    }
+
+    #[rstest]
+    #[case([1, 2, 3, 4, 5], 100, [1, 2, 3, 4, 5])]
+    #[case([1, 2, 3, 4, 5], 15, [1, 2, 3, 4, 5])]
+    #[case([1, 2, 3, 4, 5], 5, [1, 2, 2, 0, 0])]
+    #[case([0, 2, 0, 4, 5], 3, [0, 2, 0, 1, 0])]
+    #[case([1, 2, 3, 4, 5], 0, [0, 0, 0, 0, 0])]
+    fn rank_limits(
+        #[case] counts: [usize; super::RESULT_RANK_COUNT],
+        #[case] max_results: usize,
+        #[case] expected: [usize; super::RESULT_RANK_COUNT],
+    ) {
+        assert_eq!(super::rank_limits(&counts, max_results), expected);
+    }
+
+    #[test]
+    fn register_failed_filters() {
+        use super::interpreter::Filter;
+
+        let mut stats = super::QueryStats::default();
+        stats.register_failed_filters(&[Filter::Frequency, Filter::Genotype]);
+        stats.register_failed_filters(&[Filter::Genotype]);
+        stats.register_failed_filters::<Filter>(&[]);
+        stats.register_failed_filters(&[super::FILTER_RECESSIVE_MODE]);
+
+        insta::assert_yaml_snapshot!(stats.filter_impact);
+    }
+
     #[tracing_test::traced_test]
     #[rstest::rstest]
     #[case("tests/seqvars/query/Case_1.ingested.vcf")]
@@ -669,9 +1605,31 @@ mod test {
             path_query_json,
             path_input,
             path_output,
+            output_format: crate::common::OutputFormat::Tsv,
             max_results: None,
+            path_output_info: None,
+            path_gene_summary: None,
+            explain: vec![],
+            explain_all: false,
+            path_output_explain: None,
+            soft_filter: false,
+            filter_impact: false,
+            mode: super::QueryMode::Default,
+            denovo_index: None,
+            denovo_min_dp: 10,
+            denovo_min_gq: 20.0,
+            path_denovo_candidates: None,
+            path_karyotypes: None,
+            path_case_rocksdb: None,
+            path_inhouse_rocksdb: None,
+            inhouse_cf_counts: "counts".into(),
             rng_seed: Some(42),
             max_tad_distance: 10_000,
+            with_cytobands: false,
+            path_reference: None,
+            path_protein_domains: None,
+            hpo_terms: vec![],
+            path_phenopacket: None,
             result_set_id: None,
             case_uuid_id: None,
         };
@@ -0,0 +1,202 @@
+//! Ranked de novo candidate list of `seqvars query --mode denovo`.
+
+use crate::seqvars::query::{
+    denovo::Trio,
+    schema::{CallInfo, SequenceVariant},
+};
+
+/// A de novo candidate record.
+///
+/// These records are written to TSV, best candidates first.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Record {
+    /// 1-based rank of the candidate.
+    pub rank: usize,
+    /// The variant as `CHROM:POS:REF:ALT`.
+    pub variant: String,
+    /// Gene symbol of the first annotation, if any.
+    pub gene_symbol: Option<String>,
+    /// Putative impact of the first annotation, if any.
+    pub putative_impact: Option<mehari::annotate::seqvars::ann::PutativeImpact>,
+    /// Genotype quality of the index.
+    pub index_gq: Option<f32>,
+    /// Depth of the index.
+    pub index_dp: Option<i32>,
+    /// Alternate allele depth of the index.
+    pub index_ad: Option<i32>,
+    /// Alternate allele fraction of the index.
+    pub index_aaf: Option<f32>,
+    /// Genotype quality of the father.
+    pub father_gq: Option<f32>,
+    /// Depth of the father.
+    pub father_dp: Option<i32>,
+    /// Alternate allele depth of the father.
+    pub father_ad: Option<i32>,
+    /// Genotype quality of the mother.
+    pub mother_gq: Option<f32>,
+    /// Depth of the mother.
+    pub mother_dp: Option<i32>,
+    /// Alternate allele depth of the mother.
+    pub mother_ad: Option<i32>,
+}
+
+/// Accumulate the de novo candidates from the written result records.
+#[derive(Debug)]
+pub struct Accumulator {
+    /// The trio of the candidates.
+    trio: Trio,
+    /// The candidates with their result rank, see `seqvars::query::result_rank()`.
+    records: Vec<(usize, Record)>,
+}
+
+impl Accumulator {
+    /// Construct for the given trio.
+    pub fn new(trio: Trio) -> Self {
+        Self {
+            trio,
+            records: Vec::new(),
+        }
+    }
+
+    /// Register a written result record with the given `rank`; lower is better.
+    pub fn register(&mut self, seqvar: &SequenceVariant, rank: usize) {
+        let call_info = |name: &str| seqvar.call_info.get(name).cloned().unwrap_or_default();
+        let CallInfo {
+            quality: index_gq,
+            dp: index_dp,
+            ad: index_ad,
+            ..
+        } = call_info(&self.trio.index);
+        let father = call_info(&self.trio.father);
+        let mother = call_info(&self.trio.mother);
+        let ann = seqvar.ann_fields.first();
+
+        self.records.push((
+            rank,
+            Record {
+                rank: 0,
+                variant: format!(
+                    "{}:{}:{}:{}",
+                    &seqvar.chrom, seqvar.pos, &seqvar.reference, &seqvar.alternative
+                ),
+                gene_symbol: ann.map(|ann| ann.gene_symbol.clone()),
+                putative_impact: ann.map(|ann| ann.putative_impact),
+                index_gq,
+                index_dp,
+                index_ad,
+                index_aaf: match (index_ad, index_dp) {
+                    (Some(ad), Some(dp)) if dp > 0 => Some(ad as f32 / dp as f32),
+                    _ => None,
+                },
+                father_gq: father.quality,
+                father_dp: father.dp,
+                father_ad: father.ad,
+                mother_gq: mother.quality,
+                mother_dp: mother.dp,
+                mother_ad: mother.ad,
+            },
+        ));
+    }
+
+    /// Return the candidates ranked by result rank and then by index genotype quality.
+    pub fn finalize(mut self) -> Vec<Record> {
+        self.records.sort_by(|(rank_a, a), (rank_b, b)| {
+            rank_a.cmp(rank_b).then_with(|| {
+                b.index_gq
+                    .unwrap_or_default()
+                    .total_cmp(&a.index_gq.unwrap_or_default())
+            })
+        });
+        self.records
+            .into_iter()
+            .enumerate()
+            .map(|(i, (_, record))| Record {
+                rank: i + 1,
+                ..record
+            })
+            .collect()
+    }
+
+    /// Write the ranked candidates as TSV to `path`.
+    pub fn write_tsv<P>(self, path: P) -> Result<(), anyhow::Error>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let mut csv_writer = csv::WriterBuilder::new()
+            .has_headers(true)
+            .delimiter(b'\t')
+            .quote_style(csv::QuoteStyle::Never)
+            .from_path(path.as_ref())?;
+        for record in self.finalize() {
+            csv_writer
+                .serialize(&record)
+                .map_err(|e| anyhow::anyhow!("could not write de novo candidate: {}", e))?;
+        }
+        csv_writer
+            .flush()
+            .map_err(|e| anyhow::anyhow!("could not flush de novo candidates file: {}", e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::seqvars::ann::{AnnField, PutativeImpact};
+
+    use crate::seqvars::query::{
+        denovo::Trio,
+        fixtures::{call_info, call_infos},
+        schema::{CallInfo, SequenceVariant},
+    };
+
+    fn build(pos: i32, impact: PutativeImpact, index_gq: f32) -> SequenceVariant {
+        SequenceVariant {
+            chrom: "1".into(),
+            pos,
+            reference: "A".into(),
+            alternative: "T".into(),
+            ann_fields: vec![AnnField {
+                gene_symbol: "GENE1".into(),
+                putative_impact: impact,
+                ..Default::default()
+            }],
+            call_info: call_infos(
+                [
+                    ("index", "0/1", index_gq, 30, 14),
+                    ("father", "0/0", 40.0, 25, 0),
+                    ("mother", "0/0", 35.0, 20, 1),
+                ]
+                .map(|(name, gt, gq, dp, ad)| {
+                    (
+                        name,
+                        CallInfo {
+                            quality: Some(gq),
+                            dp: Some(dp),
+                            ad: Some(ad),
+                            ..call_info(gt)
+                        },
+                    )
+                }),
+            ),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn accumulate() {
+        let mut accumulator = super::Accumulator::new(Trio {
+            index: "index".into(),
+            father: "father".into(),
+            mother: "mother".into(),
+        });
+        for (pos, impact, rank, index_gq) in [
+            (100, PutativeImpact::Moderate, 1, 50.0),
+            (200, PutativeImpact::High, 0, 30.0),
+            (300, PutativeImpact::Moderate, 1, 60.0),
+        ] {
+            accumulator.register(&build(pos, impact, index_gq), rank);
+        }
+
+        insta::assert_yaml_snapshot!(accumulator.finalize());
+    }
+}
@@ -0,0 +1,227 @@
+//! Gene-level summary of the query results.
+
+use crate::{common::Genotype, seqvars::query::schema::SequenceVariant};
+
+use super::Payload;
+
+/// A gene-level summary record.
+///
+/// These records are written to TSV for the gene-centric triage view of the server.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Record {
+    /// HGNC gene ID.
+    pub hgnc_id: String,
+    /// HGNC gene symbol.
+    pub hgnc_symbol: String,
+    /// Number of result variants in the gene.
+    pub count_variants: usize,
+    /// Number of variants heterozygous in the index (dominant / compound heterozygous).
+    pub count_het: usize,
+    /// Number of variants homozygous alternative in the index (recessive).
+    pub count_hom_alt: usize,
+    /// Whether there are at least two heterozygous variants in the index.
+    pub comphet_candidate: bool,
+    /// Best variant in the gene as `CHROM:POS:REF:ALT`.
+    pub best_variant: String,
+    /// Putative impact of the best variant, if any.
+    pub best_putative_impact: Option<mehari::annotate::seqvars::ann::PutativeImpact>,
+    /// The JSON-serialized maximal precomputed scores of the variants in the gene.
+    pub max_scores: String,
+}
+
+/// Per-gene state while accumulating the summary.
+#[derive(Debug, Default)]
+struct GeneState {
+    /// The record to write out, `max_scores` is filled on finalization.
+    record: Record,
+    /// Rank of the best variant, see `seqvars::query::result_rank()`.
+    best_rank: usize,
+    /// Maximal precomputed scores.
+    max_scores: indexmap::IndexMap<String, f64>,
+}
+
+/// Accumulate gene-level summary records from the written result records.
+#[derive(Debug, Default)]
+pub struct Accumulator {
+    /// Name of the index sample; the first sample is used if `None`.
+    index_sample: Option<String>,
+    /// The per-gene state, in order of first occurrence.
+    genes: indexmap::IndexMap<String, GeneState>,
+}
+
+impl Accumulator {
+    /// Construct with the given index sample name.
+    pub fn new(index_sample: Option<String>) -> Self {
+        Self {
+            index_sample,
+            genes: Default::default(),
+        }
+    }
+
+    /// Register a written result record.
+    ///
+    /// Records without gene-related information are ignored.  Lower `rank` is better,
+    /// the first variant with the best rank is kept as the gene's best variant.
+    pub fn register(
+        &mut self,
+        seqvar: &SequenceVariant,
+        rank: usize,
+        payload: &Payload,
+    ) -> Result<(), anyhow::Error> {
+        let identity = if let Some(gene_related) = payload.gene_related.as_ref() {
+            &gene_related.identity
+        } else {
+            return Ok(());
+        };
+
+        let state = self
+            .genes
+            .entry(identity.hgnc_id.clone())
+            .or_insert_with(|| GeneState {
+                record: Record {
+                    hgnc_id: identity.hgnc_id.clone(),
+                    hgnc_symbol: identity.hgnc_symbol.clone(),
+                    ..Default::default()
+                },
+                best_rank: usize::MAX,
+                max_scores: Default::default(),
+            });
+
+        state.record.count_variants += 1;
+        if rank < state.best_rank {
+            state.best_rank = rank;
+            state.record.best_variant = format!(
+                "{}:{}:{}:{}",
+                &seqvar.chrom, seqvar.pos, &seqvar.reference, &seqvar.alternative
+            );
+            state.record.best_putative_impact =
+                seqvar.ann_fields.first().map(|ann| ann.putative_impact);
+        }
+
+        let call_info = if let Some(index_sample) = self.index_sample.as_ref() {
+            seqvar.call_info.get(index_sample)
+        } else {
+            seqvar.call_info.values().next()
+        };
+        let genotype = call_info
+            .and_then(|call_info| call_info.genotype.as_ref())
+            .map(|gt| gt.parse::<Genotype>())
+            .transpose()?;
+        match genotype {
+            Some(Genotype::Het) => state.record.count_het += 1,
+            Some(Genotype::HomAlt) => state.record.count_hom_alt += 1,
+            _ => (),
+        }
+        state.record.comphet_candidate = state.record.count_het >= 2;
+
+        for (key, value) in payload.variant_related.precomputed_scores.iter() {
+            if let Some(value) = value.as_f64() {
+                state
+                    .max_scores
+                    .entry(key.clone())
+                    .and_modify(|max| *max = max.max(value))
+                    .or_insert(value);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Return the summary records in order of first occurrence of the genes.
+    pub fn finalize(self) -> Result<Vec<Record>, anyhow::Error> {
+        self.genes
+            .into_values()
+            .map(|state| {
+                Ok(Record {
+                    max_scores: serde_json::to_string(&state.max_scores)?,
+                    ..state.record
+                })
+            })
+            .collect()
+    }
+
+    /// Write the summary records as TSV to `path`.
+    pub fn write_tsv<P>(self, path: P) -> Result<(), anyhow::Error>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let mut csv_writer = csv::WriterBuilder::new()
+            .has_headers(true)
+            .delimiter(b'\t')
+            .quote_style(csv::QuoteStyle::Never)
+            .from_path(path.as_ref())?;
+        for record in self.finalize()? {
+            csv_writer
+                .serialize(&record)
+                .map_err(|e| anyhow::anyhow!("could not write gene summary record: {}", e))?;
+        }
+        csv_writer
+            .flush()
+            .map_err(|e| anyhow::anyhow!("could not flush gene summary file: {}", e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::seqvars::ann::{AnnField, PutativeImpact};
+
+    use crate::seqvars::query::{
+        output::{gene_related, variant_related, Payload},
+        schema::{CallInfo, SequenceVariant},
+    };
+
+    fn build(pos: i32, gt: &str, impact: PutativeImpact, revel: f64) -> (SequenceVariant, Payload) {
+        let seqvar = SequenceVariant {
+            chrom: "1".into(),
+            pos,
+            reference: "A".into(),
+            alternative: "T".into(),
+            ann_fields: vec![AnnField {
+                putative_impact: impact,
+                ..Default::default()
+            }],
+            call_info: vec![(
+                String::from("index"),
+                CallInfo {
+                    genotype: Some(gt.into()),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let payload = Payload {
+            gene_related: Some(gene_related::Record {
+                identity: gene_related::Identity::new("HGNC:1".into(), "GENE1".into()),
+                ..Default::default()
+            }),
+            variant_related: variant_related::Record {
+                precomputed_scores: vec![(String::from("revel"), serde_json::json!(revel))]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        (seqvar, payload)
+    }
+
+    #[test]
+    fn accumulate() -> Result<(), anyhow::Error> {
+        let mut accumulator = super::Accumulator::new(Some("index".into()));
+        for (pos, gt, impact, rank, revel) in [
+            (100, "0/1", PutativeImpact::Moderate, 1, 0.5),
+            (200, "0/1", PutativeImpact::High, 0, 0.25),
+            (300, "1/1", PutativeImpact::Low, 2, 0.75),
+        ] {
+            let (seqvar, payload) = build(pos, gt, impact, revel);
+            accumulator.register(&seqvar, rank, &payload)?;
+        }
+
+        insta::assert_yaml_snapshot!(accumulator.finalize()?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,421 @@
+//! HGVS descriptions of the result variants.
+//!
+//! The consequence prediction in the ingest step already projects the variants onto the
+//! transcripts with the `hgvs` crate, including the 3' shifting and the conversion of
+//! insertions of the preceding sequence into duplications.  However, the annotation only
+//! carries the transcript-relative part of the description and mehari abbreviates the
+//! protein change with one-letter amino acid codes.  The code in this module builds the full
+//! descriptions in the notation expected by the reports.
+
+use std::str::FromStr as _;
+
+use biocommons_bioutils::assemblies::ASSEMBLY_INFOS;
+use hgvs::parser::{HgvsVariant, NoRef};
+use mehari::annotate::seqvars::ann::AnnField;
+
+use crate::{
+    common::{reference::Reference, GenomeRelease},
+    seqvars::query::schema::SequenceVariant,
+};
+
+/// HGVS descriptions for a `ResultPayload`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Record {
+    /// Genomic description, e.g., `NC_000007.14:g.117559593C>T`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hgvs_g: Option<String>,
+    /// Descriptions on the transcripts of the annotation, i.e., the ones selected with
+    /// `--tx-policy` on ingest.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transcripts: Vec<Transcript>,
+}
+
+/// HGVS descriptions on one transcript.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Transcript {
+    /// Transcript description, e.g., `NM_000492.4:c.1521_1523del`.
+    pub hgvs_t: String,
+    /// Protein description with three-letter codes, e.g., `p.(Phe508del)`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hgvs_p: Option<String>,
+}
+
+impl Record {
+    /// Construct given a `SequenceVariant`, with the transcript and protein descriptions
+    /// of all annotation records.
+    ///
+    /// The `reference` genome is needed for describing deletions and insertions, see
+    /// `genomic()`.  Returns `None` if no description can be built.
+    pub fn with_seqvar(
+        genome_release: GenomeRelease,
+        seqvar: &SequenceVariant,
+        reference: Option<&mut dyn Reference>,
+    ) -> Result<Option<Self>, anyhow::Error> {
+        let mut transcripts: Vec<Transcript> = Vec::new();
+        for ann in &seqvar.ann_fields {
+            if let Some(hgvs_t) = transcript(ann) {
+                if transcripts.iter().all(|tx| tx.hgvs_t != hgvs_t) {
+                    transcripts.push(Transcript {
+                        hgvs_t,
+                        hgvs_p: ann.hgvs_p.as_deref().map(protein),
+                    });
+                }
+            }
+        }
+        let result = Self {
+            hgvs_g: genomic(genome_release, seqvar, reference)?,
+            transcripts,
+        };
+        Ok((result != Self::default()).then_some(result))
+    }
+}
+
+/// Number of reference bases to read at once when shifting indels.
+const SHIFT_CHUNK_LEN: i32 = 100;
+
+/// Shift the inserted or deleted bases `seq` towards the 3' end of `chrom`.
+///
+/// `next` is the position of the first reference base after the indel.  The bases are
+/// rotated in place and the number of positions shifted is returned.
+fn shift_3p(
+    reference: &mut dyn Reference,
+    chrom: &str,
+    seq: &mut [u8],
+    next: i32,
+) -> Result<i32, anyhow::Error> {
+    let mut shift = 0;
+    loop {
+        let start = next + shift;
+        let bases = reference.bases(chrom, start, start + SHIFT_CHUNK_LEN - 1)?;
+        for base in bases.bytes() {
+            if base != seq[0] {
+                return Ok(shift);
+            }
+            seq.rotate_left(1);
+            shift += 1;
+        }
+        if bases.len() < SHIFT_CHUNK_LEN as usize {
+            return Ok(shift);
+        }
+    }
+}
+
+/// Build the genomic description of `seqvar`.
+///
+/// Substitutions and replacements are described as `>` and `delins`.  Deletions and
+/// insertions are shifted to their 3'-most position as required by the HGVS nomenclature
+/// and insertions of the preceding bases are described as duplications.  This needs the
+/// `reference` genome; without it, `None` is returned for deletions and insertions.
+pub fn genomic(
+    genome_release: GenomeRelease,
+    seqvar: &SequenceVariant,
+    reference: Option<&mut dyn Reference>,
+) -> Result<Option<String>, anyhow::Error> {
+    let SequenceVariant {
+        chrom,
+        pos,
+        reference: ref_allele,
+        alternative: alt_allele,
+        ..
+    } = seqvar;
+    let chrom = annonars::common::cli::canonicalize(chrom);
+    let Some(accession) = ASSEMBLY_INFOS[genome_release.into()]
+        .sequences
+        .iter()
+        .find(|record| annonars::common::cli::canonicalize(&record.name) == chrom)
+        .map(|record| &record.refseq_ac)
+    else {
+        return Ok(None);
+    };
+    let kind = if chrom == "MT" { "m" } else { "g" };
+
+    // Trim the common prefix, e.g., the padding base of indels, and the common suffix.
+    let prefix = ref_allele
+        .bytes()
+        .zip(alt_allele.bytes())
+        .take_while(|(r, a)| r == a)
+        .count();
+    let suffix = ref_allele[prefix..]
+        .bytes()
+        .rev()
+        .zip(alt_allele[prefix..].bytes().rev())
+        .take_while(|(r, a)| r == a)
+        .count();
+    let deleted = &ref_allele[prefix..ref_allele.len() - suffix];
+    let inserted = &alt_allele[prefix..alt_allele.len() - suffix];
+    let start = *pos + prefix as i32;
+    let range = |start: i32, len: usize| {
+        if len == 1 {
+            format!("{}", start)
+        } else {
+            format!("{}_{}", start, start + len as i32 - 1)
+        }
+    };
+
+    let change = match (deleted.len(), inserted.len()) {
+        (0, 0) => return Ok(None),
+        (1, 1) => format!("{}{}>{}", start, deleted, inserted),
+        (_, 0) | (0, _) => {
+            let Some(reference) = reference else {
+                return Ok(None);
+            };
+            if inserted.is_empty() {
+                let mut deleted = deleted.as_bytes().to_vec();
+                let next = start + deleted.len() as i32;
+                let start = start + shift_3p(reference, &chrom, &mut deleted, next)?;
+                format!("{}del", range(start, deleted.len()))
+            } else {
+                let mut inserted = inserted.as_bytes().to_vec();
+                let start = start + shift_3p(reference, &chrom, &mut inserted, start)?;
+                let len = inserted.len() as i32;
+                let preceding = if start > len {
+                    reference.bases(&chrom, start - len, start - 1)?
+                } else {
+                    String::new()
+                };
+                if preceding.as_bytes() == inserted.as_slice() {
+                    format!("{}dup", range(start - len, inserted.len()))
+                } else {
+                    format!("{}_{}ins{}", start - 1, start, String::from_utf8(inserted)?)
+                }
+            }
+        }
+        (len, _) => format!("{}delins{}", range(start, len), inserted),
+    };
+    Ok(Some(format!("{}:{}.{}", accession, kind, change)))
+}
+
+/// Build the transcript description from the annotation `ann`.
+///
+/// The description is parsed and written out again without the deleted reference bases, as
+/// recommended by the HGVS nomenclature (e.g., `c.123del` rather than `c.123delA`).
+pub fn transcript(ann: &AnnField) -> Option<String> {
+    let hgvs_t = ann.hgvs_t.as_ref()?;
+    let description = format!("{}:{}", ann.feature_id, hgvs_t);
+    Some(match HgvsVariant::from_str(&description) {
+        Ok(variant) => format!("{}", NoRef(&variant)),
+        Err(e) => {
+            tracing::warn!(
+                "{}: could not parse HGVS description {}: {}",
+                crate::codes::SEQVARS_QUERY_INVALID_HGVS,
+                &description,
+                e
+            );
+            description
+        }
+    })
+}
+
+/// Convert the protein description `hgvs_p` from mehari to three-letter amino acid codes.
+///
+/// Mehari abbreviates all amino acids including the termination codon (`*`).  The keywords
+/// of the description (`del`, `ins`, `dup`, `fs`, `ext`) are lower case so every upper case
+/// letter is an amino acid.
+pub fn protein(hgvs_p: &str) -> String {
+    let mut result = String::with_capacity(hgvs_p.len() * 2);
+    let (prefix, change) = hgvs_p.split_at(if hgvs_p.starts_with("p.") { 2 } else { 0 });
+    result.push_str(prefix);
+    for c in change.chars() {
+        if c.is_ascii_uppercase() || c == '*' {
+            match hgvs::sequences::aa1_to_aa3(c.encode_utf8(&mut [0; 4])) {
+                Ok(aa3) => result.push_str(&aa3),
+                Err(_) => result.push(c),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::seqvars::ann::AnnField;
+
+    use crate::{
+        common::{reference::Reference, GenomeRelease},
+        seqvars::query::schema::SequenceVariant,
+    };
+
+    /// Reference with the sequence of `chr7` starting at position 1.
+    struct TestReference(&'static str);
+
+    impl Reference for TestReference {
+        fn bases(&mut self, chrom: &str, start: i32, end: i32) -> Result<String, anyhow::Error> {
+            assert_eq!(chrom, "7");
+            let len = self.0.len() as i32;
+            Ok(self.0[(start - 1).min(len) as usize..end.min(len) as usize].to_string())
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(
+        GenomeRelease::Grch38,
+        "chr7",
+        117559593,
+        "C",
+        "T",
+        Some("NC_000007.14:g.117559593C>T")
+    )]
+    #[case(
+        GenomeRelease::Grch37,
+        "7",
+        100,
+        "CAG",
+        "CTT",
+        Some("NC_000007.13:g.101_102delinsTT")
+    )]
+    #[case(
+        GenomeRelease::Grch37,
+        "MT",
+        3243,
+        "A",
+        "G",
+        Some("NC_012920.1:m.3243A>G")
+    )]
+    #[case(
+        GenomeRelease::Grch38,
+        "chr7",
+        7,
+        "CAG",
+        "T",
+        Some("NC_000007.14:g.7_9delinsT")
+    )]
+    #[case(
+        GenomeRelease::Grch38,
+        "chr7",
+        7,
+        "C",
+        "GTT",
+        Some("NC_000007.14:g.7delinsGTT")
+    )]
+    #[case(GenomeRelease::Grch38, "chr7", 2, "AT", "A", None)]
+    #[case(GenomeRelease::Grch38, "chr7", 2, "A", "AT", None)]
+    #[case(GenomeRelease::Grch38, "chr7", 2, "A", "A", None)]
+    fn genomic(
+        #[case] genome_release: GenomeRelease,
+        #[case] chrom: &str,
+        #[case] pos: i32,
+        #[case] reference: &str,
+        #[case] alternative: &str,
+        #[case] expected: Option<&str>,
+    ) -> Result<(), anyhow::Error> {
+        let seqvar = SequenceVariant {
+            chrom: chrom.into(),
+            pos,
+            reference: reference.into(),
+            alternative: alternative.into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            super::genomic(genome_release, &seqvar, None)?.as_deref(),
+            expected
+        );
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case::del_shifted(2, "AT", "A", "NC_000007.14:g.5del")]
+    #[case::del_repeat_shifted(6, "ACAG", "A", "NC_000007.14:g.11_13del")]
+    #[case::dup(2, "A", "AT", "NC_000007.14:g.5dup")]
+    #[case::dup_repeat(6, "A", "ACAG", "NC_000007.14:g.11_13dup")]
+    #[case::ins(14, "T", "TAA", "NC_000007.14:g.14_15insAA")]
+    #[case::del_contig_end(13, "CTG", "C", "NC_000007.14:g.14_15del")]
+    #[case::substitution(7, "C", "T", "NC_000007.14:g.7C>T")]
+    fn genomic_with_reference(
+        #[case] pos: i32,
+        #[case] reference: &str,
+        #[case] alternative: &str,
+        #[case] expected: &str,
+    ) -> Result<(), anyhow::Error> {
+        let seqvar = SequenceVariant {
+            chrom: "chr7".into(),
+            pos,
+            reference: reference.into(),
+            alternative: alternative.into(),
+            ..Default::default()
+        };
+        let mut reference = TestReference("GATTTACAGCAGCTG");
+        assert_eq!(
+            super::genomic(GenomeRelease::Grch38, &seqvar, Some(&mut reference))?.as_deref(),
+            Some(expected)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn with_seqvar_all_transcripts() -> Result<(), anyhow::Error> {
+        let ann = |feature_id: &str, hgvs_t: &str, hgvs_p: Option<&str>| AnnField {
+            feature_id: feature_id.into(),
+            hgvs_t: Some(hgvs_t.into()),
+            hgvs_p: hgvs_p.map(String::from),
+            ..Default::default()
+        };
+        let seqvar = SequenceVariant {
+            chrom: "chr7".into(),
+            pos: 117559593,
+            reference: "C".into(),
+            alternative: "T".into(),
+            ann_fields: vec![
+                ann("NM_000492.4", "c.1521C>T", Some("p.(F508=)")),
+                ann("NM_000492.4", "c.1521C>T", Some("p.(F508=)")),
+                ann("NR_149084.1", "n.1600C>T", None),
+                AnnField::default(),
+            ],
+            ..Default::default()
+        };
+
+        let record = super::Record::with_seqvar(GenomeRelease::Grch38, &seqvar, None)?;
+
+        assert_eq!(
+            record,
+            Some(super::Record {
+                hgvs_g: Some("NC_000007.14:g.117559593C>T".into()),
+                transcripts: vec![
+                    super::Transcript {
+                        hgvs_t: "NM_000492.4:c.1521C>T".into(),
+                        hgvs_p: Some("p.(Phe508=)".into()),
+                    },
+                    super::Transcript {
+                        hgvs_t: "NR_149084.1:n.1600C>T".into(),
+                        hgvs_p: None,
+                    },
+                ],
+            })
+        );
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case("c.1521_1523delCTT", "NM_000492.4:c.1521_1523del")]
+    #[case("c.35dup", "NM_000492.4:c.35dup")]
+    #[case("c.35_36insT", "NM_000492.4:c.35_36insT")]
+    #[case("c.*12A>G", "NM_000492.4:c.*12A>G")]
+    #[case("c.-3_-2delinsGT", "NM_000492.4:c.-3_-2delinsGT")]
+    #[case("n.100+2T>A", "NM_000492.4:n.100+2T>A")]
+    fn transcript(#[case] hgvs_t: &str, #[case] expected: &str) {
+        let ann = AnnField {
+            feature_id: "NM_000492.4".into(),
+            hgvs_t: Some(hgvs_t.into()),
+            ..Default::default()
+        };
+        assert_eq!(super::transcript(&ann).as_deref(), Some(expected));
+    }
+
+    #[rstest::rstest]
+    #[case("p.(F508del)", "p.(Phe508del)")]
+    #[case("p.(R117H)", "p.(Arg117His)")]
+    #[case("p.(W1282*)", "p.(Trp1282Ter)")]
+    #[case("p.(L5Sfs*12)", "p.(Leu5SerfsTer12)")]
+    #[case("p.(*110Qext*17)", "p.(Ter110GlnextTer17)")]
+    #[case("p.(E12_K13insG)", "p.(Glu12_Lys13insGly)")]
+    #[case("p.M1?", "p.Met1?")]
+    #[case("p.?", "p.?")]
+    #[case("p.(=)", "p.(=)")]
+    fn protein(#[case] hgvs_p: &str, #[case] expected: &str) {
+        assert_eq!(super::protein(hgvs_p), expected);
+    }
+}
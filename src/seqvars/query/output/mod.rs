@@ -1,10 +1,17 @@
 //! Data structureds for writing the output.
 
+pub mod denovo;
 pub mod gene_related;
+pub mod gene_summary;
+pub mod hgvs;
 pub mod variant_related;
 
 pub mod call_related;
 
+use std::io::Write;
+
+use crate::common::{pbs, OutputFormat};
+
 /// A result record from the query.
 ///
 /// These records are written to TSV for import into the database.   They contain the
@@ -34,6 +41,10 @@ pub struct Record {
     pub smallvariantqueryresultset_id: String,
     /// The JSON-serialized `ResultPayload`.
     pub payload: String,
+    /// Phenotype score of the gene for the HPO terms of the case, only set with
+    /// `--hpo-terms` or `--path-phenopacket`.
+    #[builder(default)]
+    pub phenotype_score: Option<f32>,
 }
 
 /// The structured result information of the result record.
@@ -49,4 +60,291 @@ pub struct Payload {
     pub variant_related: variant_related::Record,
     /// Genotypes call related, always present.
     pub call_related: call_related::Record,
+    /// HGVS descriptions of the variant, if any.
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hgvs: Option<hgvs::Record>,
+    /// Caution flags for variants in gene loci with problematic paralogy, unless disabled
+    /// in the query.
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paralog_caution: Vec<crate::seqvars::query::paralogs::Caution>,
+    /// The protein domains affected by missense and in-frame variants, only set with
+    /// `--path-protein-domains`.
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub protein_domains: Vec<crate::seqvars::query::protein_domains::Domain>,
+    /// The cytogenetic band of the variant, only set with `--with-cytobands`.
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cytoband: Option<String>,
+    /// Names of the filters failed by the record, only set in soft-filter mode.
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_filters: Vec<String>,
+    /// The partner variants as `CHROM:POS:REF:ALT` if the record is part of a compound
+    /// heterozygous pair, only set in recessive mode.
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comphet_partners: Vec<String>,
+}
+
+/// Information about the written result set.
+///
+/// This is written as JSON next to the result records so the importer can tell whether
+/// the result set has been truncated because of `max_results` and which filters removed
+/// how many records.
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ResultSetInfo {
+    /// Whether the result set has been truncated.
+    pub result_set_truncated: bool,
+    /// Total number of records that passed the filters.
+    pub count_total: usize,
+    /// Number of records passing the filters that have been written.
+    pub count_written: usize,
+    /// Total number of records failing filters with `--soft-filter`.
+    #[serde(default)]
+    pub count_total_soft_failed: usize,
+    /// Number of records failing filters that have been written with `--soft-filter`.
+    ///
+    /// When truncating, these are only written after all passing records.
+    #[serde(default)]
+    pub count_written_soft_failed: usize,
+    /// Number of records removed by each filter, with `--filter-impact`.
+    #[serde(default, skip_serializing_if = "indexmap::IndexMap::is_empty")]
+    pub filter_impact: indexmap::IndexMap<String, FilterImpact>,
+    /// Outdated symbols of the gene allow list that were remapped to approved ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remapped_gene_symbols: Vec<crate::strucvars::query::genes::RemappedSymbol>,
+}
+
+/// Number of records removed by one filter.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct FilterImpact {
+    /// Number of records for which this is the first failing filter.
+    pub first_failing: usize,
+    /// Number of records failing this filter, independent of the other filters.
+    pub marginal: usize,
+}
+
+/// Writer for result records in the selected `OutputFormat`.
+pub enum Writer {
+    /// Write TSV file.
+    Tsv(Box<csv::Writer<std::fs::File>>),
+    /// Write JSONL file.
+    Jsonl(std::io::BufWriter<std::fs::File>),
+    /// Write length-delimited protobuf messages.
+    Protobuf(std::io::BufWriter<std::fs::File>),
+}
+
+impl Writer {
+    /// Create writer for the file at `path` in the given format.
+    pub fn new(path: &str, output_format: OutputFormat) -> Result<Self, anyhow::Error> {
+        let create = || {
+            std::fs::File::create(path)
+                .map(std::io::BufWriter::new)
+                .map_err(|e| anyhow::anyhow!("could not create output file {}: {}", path, e))
+        };
+        Ok(match output_format {
+            OutputFormat::Tsv => Self::Tsv(Box::new(
+                csv::WriterBuilder::new()
+                    .has_headers(true)
+                    .delimiter(b'\t')
+                    .quote_style(csv::QuoteStyle::Never)
+                    .from_path(path)?,
+            )),
+            OutputFormat::Jsonl => Self::Jsonl(create()?),
+            OutputFormat::Protobuf => Self::Protobuf(create()?),
+        })
+    }
+
+    /// Write `record` whose `payload` member is the serialization of `payload`.
+    pub fn write(&mut self, record: &Record, payload: &Payload) -> Result<(), anyhow::Error> {
+        match self {
+            Self::Tsv(writer) => writer
+                .serialize(record)
+                .map_err(|e| anyhow::anyhow!("could not write record: {}", e)),
+            Self::Jsonl(writer) => {
+                let mut value = serde_json::to_value(record)?;
+                value["payload"] = serde_json::to_value(payload)
+                    .map_err(|e| anyhow::anyhow!("could not serialize payload: {}", e))?;
+                writeln!(writer, "{}", value)
+                    .map_err(|e| anyhow::anyhow!("could not write record: {}", e))
+            }
+            Self::Protobuf(writer) => {
+                let buf = prost::Message::encode_length_delimited_to_vec(&Self::to_protobuf(
+                    record, payload,
+                )?);
+                writer
+                    .write_all(&buf)
+                    .map_err(|e| anyhow::anyhow!("could not write record: {}", e))
+            }
+        }
+    }
+
+    /// Convert `record` with `payload` into its protobuf representation.
+    fn to_protobuf(
+        record: &Record,
+        payload: &Payload,
+    ) -> Result<pbs::SeqvarsResultRecord, anyhow::Error> {
+        let frequency = &payload.variant_related.frequency;
+        let nuclear = [
+            ("gnomad_genomes", &frequency.gnomad_genomes),
+            ("gnomad_exomes", &frequency.gnomad_exomes),
+            ("inhouse", &frequency.inhouse),
+        ]
+        .into_iter()
+        .filter_map(|(database, freq)| {
+            freq.as_ref().map(|freq| pbs::Frequency {
+                database: database.into(),
+                allele_freq: freq.allele_freq,
+                allele_count: freq.allele_count,
+                het_carriers: freq.het_carriers,
+                hom_carriers: freq.hom_carriers,
+                hemi_carriers: freq.hemi_carriers,
+            })
+        });
+        let mtdna = [
+            ("gnomad_mtdna", &frequency.gnomad_mtdna),
+            ("helixmtdb", &frequency.helixmtdb),
+        ]
+        .into_iter()
+        .filter_map(|(database, freq)| {
+            freq.as_ref().map(|freq| pbs::Frequency {
+                database: database.into(),
+                allele_freq: freq.allele_freq,
+                allele_count: freq.allele_count,
+                het_carriers: freq.het_carriers,
+                hom_carriers: freq.hom_carriers,
+                hemi_carriers: 0,
+            })
+        });
+
+        Ok(pbs::SeqvarsResultRecord {
+            sodar_uuid: record.sodar_uuid.to_string(),
+            release: record.release.clone(),
+            chromosome: record.chromosome.clone(),
+            chromosome_no: record.chromosome_no,
+            start: record.start,
+            end: record.end,
+            bin: record.bin,
+            reference: record.reference.clone(),
+            alternative: record.alternative.clone(),
+            result_set_id: record.smallvariantqueryresultset_id.clone(),
+            calls: payload
+                .call_related
+                .call_info
+                .iter()
+                .map(|(sample, call_info)| pbs::SeqvarsCall {
+                    sample: sample.clone(),
+                    gt: call_info.gt.clone(),
+                    dp: call_info.dp,
+                    ad: call_info.ad,
+                    gq: call_info.gq,
+                })
+                .collect(),
+            frequencies: nuclear.chain(mtdna).collect(),
+            gene: payload
+                .gene_related
+                .as_ref()
+                .map(|gene_related| -> Result<_, anyhow::Error> {
+                    Ok(pbs::GeneConsequence {
+                        hgnc_id: gene_related.identity.hgnc_id.clone(),
+                        hgnc_symbol: gene_related.identity.hgnc_symbol.clone(),
+                        hgvs_t: gene_related.consequences.hgvs_t.clone(),
+                        hgvs_p: gene_related.consequences.hgvs_p.clone(),
+                        consequences: gene_related
+                            .consequences
+                            .consequences
+                            .iter()
+                            .map(|csq| {
+                                serde_json::to_value(csq)
+                                    .map(|value| value.as_str().unwrap_or_default().to_string())
+                            })
+                            .collect::<Result<_, _>>()?,
+                    })
+                })
+                .transpose()?,
+            payload_json: record.payload.clone(),
+            phenotype_score: record.phenotype_score,
+        })
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> Result<(), anyhow::Error> {
+        match self {
+            Self::Tsv(writer) => writer.flush(),
+            Self::Jsonl(writer) | Self::Protobuf(writer) => writer.flush(),
+        }
+        .map_err(|e| anyhow::anyhow!("could not flush output file: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[rstest::rstest]
+    #[case::tsv(crate::common::OutputFormat::Tsv)]
+    #[case::jsonl(crate::common::OutputFormat::Jsonl)]
+    #[case::protobuf(crate::common::OutputFormat::Protobuf)]
+    fn writer(#[case] output_format: crate::common::OutputFormat) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_output = format!("{}/out", tmpdir.to_string_lossy());
+
+        let payload = super::Payload {
+            call_related: super::call_related::Record {
+                call_info: indexmap::indexmap! {
+                    String::from("index") => super::call_related::CallInfo {
+                        gt: Some("0/1".into()),
+                        dp: Some(30),
+                        ..Default::default()
+                    },
+                },
+            },
+            ..Default::default()
+        };
+        let record = super::Record {
+            release: "GRCh37".into(),
+            chromosome: "1".into(),
+            chromosome_no: 1,
+            start: 1_000,
+            end: 1_000,
+            reference: "A".into(),
+            alternative: "G".into(),
+            payload: serde_json::to_string(&payload)?,
+            ..Default::default()
+        };
+
+        let mut writer = super::Writer::new(&path_output, output_format)?;
+        writer.write(&record, &payload)?;
+        writer.flush()?;
+        drop(writer);
+
+        let output = std::fs::read(&path_output)?;
+        match output_format {
+            crate::common::OutputFormat::Tsv => {
+                let output = String::from_utf8(output)?;
+                assert_eq!(output.lines().count(), 2);
+                assert!(output.starts_with("sodar_uuid\t"));
+            }
+            crate::common::OutputFormat::Jsonl => {
+                let value: serde_json::Value = serde_json::from_slice(&output)?;
+                assert_eq!(
+                    value["payload"]["call_related"]["call_info"]["index"]["dp"],
+                    30
+                );
+            }
+            crate::common::OutputFormat::Protobuf => {
+                let pb: crate::common::pbs::SeqvarsResultRecord =
+                    prost::Message::decode_length_delimited(output.as_slice())?;
+                assert_eq!(pb.start, 1_000);
+                assert_eq!(pb.reference, "A");
+                assert_eq!(pb.calls.len(), 1);
+                assert_eq!(pb.calls[0].gt.as_deref(), Some("0/1"));
+                assert_eq!(pb.calls[0].dp, Some(30));
+                assert_eq!(pb.payload_json, record.payload);
+            }
+        }
+
+        Ok(())
+    }
 }
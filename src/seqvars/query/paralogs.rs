@@ -0,0 +1,171 @@
+//! Gene loci with problematic paralogy, e.g., `SMN1`/`SMN2` or `PMS2`/`PMS2CL`.
+//!
+//! Short reads from these loci cannot be placed reliably on the gene or its paralogs or
+//! pseudogenes so naive variant calls there are frequently wrong.  The loci are bundled
+//! with the worker and cover the genes (1-based, inclusive coordinates); variants in them
+//! are flagged or removed as selected in the query.
+
+use crate::common::GenomeRelease;
+
+use super::schema::SequenceVariant;
+
+/// A gene locus with problematic paralogy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locus {
+    /// Canonical chromosome name, without `chr` prefix.
+    pub chrom: &'static str,
+    /// 1-based start position.
+    pub start: i32,
+    /// 1-based, inclusive end position.
+    pub end: i32,
+    /// The gene symbol.
+    pub gene: &'static str,
+    /// The symbols of the paralogs and pseudogenes of the gene.
+    pub paralogs: &'static [&'static str],
+}
+
+/// Loci with problematic paralogy on GRCh37.
+pub const LOCI_37: &[Locus] = &[
+    locus("1", 155204239, 155214653, "GBA", &["GBAP1"]),
+    locus("5", 69345350, 69373422, "SMN2", &["SMN1"]),
+    locus("5", 70220768, 70248842, "SMN1", &["SMN2"]),
+    locus("6", 32006042, 32009447, "CYP21A2", &["CYP21A1P"]),
+    locus("7", 6012870, 6048756, "PMS2", &["PMS2CL"]),
+    locus("7", 74188309, 74203659, "NCF1", &["NCF1B", "NCF1C"]),
+    locus("15", 43891761, 43910998, "STRC", &["STRCP1"]),
+    locus("16", 222846, 223709, "HBA2", &["HBA1"]),
+    locus("16", 226679, 227521, "HBA1", &["HBA2"]),
+    locus(
+        "16",
+        2138711,
+        2185899,
+        "PKD1",
+        &["PKD1P1", "PKD1P5", "PKD1P6"],
+    ),
+    locus("22", 42522501, 42526908, "CYP2D6", &["CYP2D7"]),
+    locus("X", 153770459, 153793261, "IKBKG", &["IKBKGP1"]),
+];
+
+/// Loci with problematic paralogy on GRCh38.
+pub const LOCI_38: &[Locus] = &[
+    locus("1", 155234452, 155244627, "GBA", &["GBAP1"]),
+    locus("5", 70049523, 70077595, "SMN2", &["SMN1"]),
+    locus("5", 70924941, 70953015, "SMN1", &["SMN2"]),
+    locus("6", 32038265, 32041670, "CYP21A2", &["CYP21A1P"]),
+    locus("7", 5973239, 6009125, "PMS2", &["PMS2CL"]),
+    locus("7", 74773962, 74789315, "NCF1", &["NCF1B", "NCF1C"]),
+    locus("15", 43599563, 43618800, "STRC", &["STRCP1"]),
+    locus("16", 172876, 173710, "HBA2", &["HBA1"]),
+    locus("16", 176680, 177522, "HBA1", &["HBA2"]),
+    locus(
+        "16",
+        2088708,
+        2135898,
+        "PKD1",
+        &["PKD1P1", "PKD1P5", "PKD1P6"],
+    ),
+    locus("22", 42126499, 42130881, "CYP2D6", &["CYP2D7"]),
+    locus("X", 154542212, 154565046, "IKBKG", &["IKBKGP1"]),
+];
+
+/// Helper for the definition of `LOCI_37` and `LOCI_38`.
+const fn locus(
+    chrom: &'static str,
+    start: i32,
+    end: i32,
+    gene: &'static str,
+    paralogs: &'static [&'static str],
+) -> Locus {
+    Locus {
+        chrom,
+        start,
+        end,
+        gene,
+        paralogs,
+    }
+}
+
+/// Caution flag for a variant in a locus with problematic paralogy.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Caution {
+    /// The gene symbol.
+    pub gene: String,
+    /// The symbols of the paralogs and pseudogenes of the gene.
+    pub paralogs: Vec<String>,
+}
+
+impl From<&Locus> for Caution {
+    fn from(locus: &Locus) -> Self {
+        Self {
+            gene: locus.gene.to_string(),
+            paralogs: locus.paralogs.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+/// The loci with problematic paralogy for one genome release.
+#[derive(Debug, Default, Clone)]
+pub struct ParalogLoci {
+    /// The loci.
+    loci: &'static [Locus],
+}
+
+impl ParalogLoci {
+    /// Construct with the bundled loci for `genome_release`.
+    pub fn new(genome_release: GenomeRelease) -> Self {
+        Self {
+            loci: match genome_release {
+                GenomeRelease::Grch37 => LOCI_37,
+                GenomeRelease::Grch38 => LOCI_38,
+            },
+        }
+    }
+
+    /// Return the loci overlapping `seqvar`.
+    pub fn overlapping(&self, seqvar: &SequenceVariant) -> Vec<&'static Locus> {
+        let chrom = annonars::common::cli::canonicalize(&seqvar.chrom);
+        let start = seqvar.pos;
+        let end = start + (seqvar.reference.len() as i32 - 1).max(0);
+        self.loci
+            .iter()
+            .filter(|locus| locus.chrom == chrom && locus.start <= end && start <= locus.end)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{common::GenomeRelease, seqvars::query::schema::SequenceVariant};
+
+    #[rstest::rstest]
+    #[case(GenomeRelease::Grch37, "5", 70220768, "A", vec!["SMN1"])]
+    #[case(GenomeRelease::Grch37, "chr5", 70248842, "A", vec!["SMN1"])]
+    #[case(GenomeRelease::Grch37, "5", 70248843, "A", vec![])]
+    #[case(GenomeRelease::Grch37, "5", 70220760, "AAAAAAAAAA", vec!["SMN1"])]
+    #[case(GenomeRelease::Grch38, "chr5", 70220768, "A", vec![])]
+    #[case(GenomeRelease::Grch38, "chr7", 6000000, "A", vec!["PMS2"])]
+    #[case(GenomeRelease::Grch38, "chrX", 154550000, "A", vec!["IKBKG"])]
+    fn overlapping(
+        #[case] genome_release: GenomeRelease,
+        #[case] chrom: &str,
+        #[case] pos: i32,
+        #[case] reference: &str,
+        #[case] expected: Vec<&str>,
+    ) {
+        let seqvar = SequenceVariant {
+            chrom: chrom.into(),
+            pos,
+            reference: reference.into(),
+            alternative: "G".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            super::ParalogLoci::new(genome_release)
+                .overlapping(&seqvar)
+                .iter()
+                .map(|locus| locus.gene)
+                .collect::<Vec<_>>(),
+            expected
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! Phenotype-based prioritization of genes by the HPO terms of the case.
+//!
+//! The gene-HPO annotations are read from `noref/genes/hpo.tsv` in the worker database, a
+//! TSV file with the header line `hgnc_id hpo_id`.  The annotations must already be
+//! propagated along the ontology (true path rule), i.e., list each term together with its
+//! ancestors, such that matching terms need no ontology at query time.
+//!
+//! The score of a gene is the information content of the case terms annotated to the gene
+//! divided by the information content of all case terms, from `0` (no match) to `1`
+//! (all terms match).  The information content of a term is `ln(N / n)` for `n` of the
+//! `N` annotated genes having the term, so specific terms weigh more than general ones.
+
+use std::collections::{HashMap, HashSet};
+
+use mehari::common::io::std::open_read_maybe_gz;
+
+/// One line of the gene-HPO annotations TSV file.
+#[derive(Debug, serde::Deserialize)]
+struct Row {
+    /// HGNC gene ID, e.g., `HGNC:1100`.
+    hgnc_id: String,
+    /// HPO term ID, e.g., `HP:0001250`.
+    hpo_id: String,
+}
+
+/// Gene-HPO annotations with the information content of the terms.
+#[derive(Debug, Default, Clone)]
+pub struct GeneHpoSets {
+    /// The HPO terms by HGNC gene ID.
+    by_gene: HashMap<String, HashSet<String>>,
+    /// The information content by HPO term.
+    information_content: HashMap<String, f64>,
+}
+
+impl GeneHpoSets {
+    /// Load the annotations from the TSV file at `path`, optionally gzip-compressed.
+    pub fn from_path(path: &str) -> Result<Self, anyhow::Error> {
+        tracing::debug!("loading gene-HPO annotations from {}...", path);
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .comment(Some(b'#'))
+            .delimiter(b'\t')
+            .from_reader(open_read_maybe_gz(path)?);
+        let mut by_gene = HashMap::<String, HashSet<String>>::new();
+        for row in reader.deserialize() {
+            let row: Row =
+                row.map_err(|e| anyhow::anyhow!("invalid gene-HPO annotation in {}: {}", path, e))?;
+            by_gene.entry(row.hgnc_id).or_default().insert(row.hpo_id);
+        }
+        let result = Self::new(by_gene);
+        tracing::debug!(
+            "... done loading HPO annotations of {} genes",
+            result.by_gene.len()
+        );
+        Ok(result)
+    }
+
+    /// Load the annotations from the worker database at `path_worker_db`.
+    ///
+    /// The annotations are optional; empty annotations are returned if the file is
+    /// missing.
+    pub fn load(path_worker_db: &str) -> Result<Self, anyhow::Error> {
+        let path = format!("{}/noref/genes/hpo.tsv", path_worker_db);
+        if std::path::Path::new(&path).exists() {
+            Self::from_path(&path)
+        } else {
+            tracing::info!("  no gene-HPO annotations at {}, skipping", &path);
+            Ok(Self::default())
+        }
+    }
+
+    /// Construct from the HPO terms by HGNC gene ID.
+    fn new(by_gene: HashMap<String, HashSet<String>>) -> Self {
+        let mut counts = HashMap::<String, usize>::new();
+        for term in by_gene.values().flatten() {
+            *counts.entry(term.clone()).or_default() += 1;
+        }
+        let count_genes = by_gene.len() as f64;
+        let information_content = counts
+            .into_iter()
+            .map(|(term, count)| (term, (count_genes / count as f64).ln()))
+            .collect();
+        Self {
+            by_gene,
+            information_content,
+        }
+    }
+
+    /// Return whether there are no annotations.
+    pub fn is_empty(&self) -> bool {
+        self.by_gene.is_empty()
+    }
+
+    /// Return the phenotype score of the gene `hgnc_id` for the `case_terms`.
+    ///
+    /// Returns `None` if none of the case terms is annotated to any gene.
+    pub fn score(&self, hgnc_id: &str, case_terms: &[String]) -> Option<f32> {
+        let gene_terms = self.by_gene.get(hgnc_id);
+        let mut total = 0.0;
+        let mut matched = 0.0;
+        for term in case_terms {
+            let Some(information_content) = self.information_content.get(term) else {
+                continue;
+            };
+            total += information_content;
+            if gene_terms
+                .map(|terms| terms.contains(term))
+                .unwrap_or_default()
+            {
+                matched += information_content;
+            }
+        }
+        (total > 0.0).then(|| (matched / total) as f32)
+    }
+}
+
+/// Return whether `term` is a valid HPO term ID, e.g., `HP:0001250`.
+fn is_valid_term(term: &str) -> bool {
+    term.strip_prefix("HP:")
+        .map(|digits| digits.len() == 7 && digits.chars().all(|c| c.is_ascii_digit()))
+        .unwrap_or_default()
+}
+
+/// Return the HPO terms of the case from the `hpo_terms` and the observed phenotypic
+/// features of the affected individuals in the phenopacket JSON file at
+/// `path_phenopacket`, without duplicates.
+pub fn load_case_terms(
+    hpo_terms: &[String],
+    path_phenopacket: Option<&str>,
+) -> Result<Vec<String>, anyhow::Error> {
+    let mut result = Vec::new();
+    for term in hpo_terms {
+        if !is_valid_term(term) {
+            anyhow::bail!(
+                "{}: invalid HPO term id {:?}, expected HP:NNNNNNN",
+                crate::codes::SEQVARS_QUERY_INVALID_HPO_TERM,
+                term
+            );
+        }
+        result.push(term.clone());
+    }
+    if let Some(path_phenopacket) = path_phenopacket {
+        let metadata = crate::common::phenopacket::CaseMetadata::from_path(path_phenopacket)?;
+        result.extend(
+            metadata
+                .affected_hpo_terms()
+                .into_iter()
+                .filter(|term| is_valid_term(term)),
+        );
+    }
+
+    let mut seen = HashSet::new();
+    result.retain(|term| seen.insert(term.clone()));
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{HashMap, HashSet};
+
+    use super::GeneHpoSets;
+
+    /// Build the annotations of three genes; `HP:0000118` is annotated to all of them.
+    fn gene_hpo_sets() -> GeneHpoSets {
+        let genes = [
+            ("HGNC:1", vec!["HP:0000118", "HP:0001250"]),
+            ("HGNC:2", vec!["HP:0000118", "HP:0001250", "HP:0001263"]),
+            ("HGNC:3", vec!["HP:0000118"]),
+        ];
+        GeneHpoSets::new(
+            genes
+                .into_iter()
+                .map(|(gene, terms)| {
+                    (
+                        gene.to_string(),
+                        terms.into_iter().map(String::from).collect::<HashSet<_>>(),
+                    )
+                })
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+
+    #[rstest::rstest]
+    #[case::full_match("HGNC:2", &["HP:0001250", "HP:0001263"], Some(1.0))]
+    #[case::partial_match("HGNC:1", &["HP:0001250", "HP:0001263"], Some(0.2696))]
+    #[case::general_term_only("HGNC:3", &["HP:0000118", "HP:0001250"], Some(0.0))]
+    #[case::unknown_gene("HGNC:4", &["HP:0001250"], Some(0.0))]
+    #[case::unknown_terms("HGNC:1", &["HP:0000001"], None)]
+    fn score(#[case] hgnc_id: &str, #[case] case_terms: &[&str], #[case] expected: Option<f32>) {
+        let case_terms = case_terms
+            .iter()
+            .map(|term| term.to_string())
+            .collect::<Vec<_>>();
+        let score = gene_hpo_sets().score(hgnc_id, &case_terms);
+        match (score, expected) {
+            (Some(score), Some(expected)) => assert!((score - expected).abs() < 1e-4),
+            _ => assert_eq!(score, expected),
+        }
+    }
+
+    #[test]
+    fn load_case_terms() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("phenopacket.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "id": "case",
+                "subject": {"id": "index"},
+                "phenotypicFeatures": [
+                    {"type": {"id": "HP:0001250", "label": "Seizure"}},
+                    {"type": {"id": "HP:0001263", "label": "Global developmental delay"}},
+                    {"type": {"id": "HP:0000252", "label": "Microcephaly"}, "excluded": true}
+                ]
+            }"#,
+        )?;
+
+        let terms = super::load_case_terms(&["HP:0001250".into()], path.to_str())?;
+        assert_eq!(terms, vec!["HP:0001250", "HP:0001263"]);
+
+        assert!(super::load_case_terms(&["HP:123".into()], None).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+//! Named query settings presets with pedigree-aware genotype defaults.
+//!
+//! A query JSON may name a preset with the `preset` key, e.g.,
+//! `{"preset": "de-novo", "gnomad_exomes_frequency": 0.001}`.  The genotype choices of
+//! the preset are computed from the pedigree of the case at runtime.  The settings of the
+//! preset are used as the defaults and all other keys of the query JSON override them.
+
+use indexmap::IndexMap;
+use mehari::ped::{Disease, PedigreeByName, Sex};
+use strum::IntoEnumIterator as _;
+use strum_macros::{Display, EnumIter, EnumString};
+
+use super::schema::{CaseQuery, GenomicRegion, GenotypeChoice};
+
+/// Key of the preset name in the query JSON.
+pub const PRESET_KEY: &str = crate::strucvars::query::presets::PRESET_KEY;
+
+/// The bundled presets.
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    EnumIter,
+    EnumString,
+    Display,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum Preset {
+    /// Rare het. variants in the index that are absent in both parents.
+    DeNovo,
+    /// Hom. or comp. het. variants in the index with carrier parents.
+    Recessive,
+    /// Rare het. variants in all affected and absent in all unaffected individuals.
+    Dominant,
+    /// Variants on chrMT shared by the affected individuals and their mothers.
+    Mitochondrial,
+    /// X-linked recessive variants; hemizygous or hom. alt. in affected individuals.
+    XLinked,
+}
+
+impl Preset {
+    /// Return the query settings of the preset without the genotype choices.
+    pub fn case_query(self) -> CaseQuery {
+        let gnomad = |frequency: f32, homozygous: i32| CaseQuery {
+            gnomad_exomes_enabled: true,
+            gnomad_exomes_frequency: Some(frequency),
+            gnomad_exomes_homozygous: Some(homozygous),
+            gnomad_genomes_enabled: true,
+            gnomad_genomes_frequency: Some(frequency),
+            gnomad_genomes_homozygous: Some(homozygous),
+            inhouse_enabled: true,
+            inhouse_carriers: Some(20),
+            ..Default::default()
+        };
+        match self {
+            Preset::DeNovo => gnomad(0.0001, 0),
+            Preset::Recessive => gnomad(0.01, 20),
+            Preset::Dominant => gnomad(0.0002, 0),
+            Preset::Mitochondrial => CaseQuery {
+                helixmtdb_enabled: true,
+                helixmtdb_frequency: Some(0.01),
+                helixmtdb_homoplasmic: Some(20),
+                genomic_regions: Some(vec![GenomicRegion {
+                    chrom: "MT".into(),
+                    range: None,
+                }]),
+                ..Default::default()
+            },
+            Preset::XLinked => CaseQuery {
+                gnomad_exomes_hemizygous: Some(20),
+                gnomad_genomes_hemizygous: Some(20),
+                genomic_regions: Some(vec![GenomicRegion {
+                    chrom: "X".into(),
+                    range: None,
+                }]),
+                ..gnomad(0.01, 20)
+            },
+        }
+    }
+
+    /// Return the genotype choices of the preset for the individuals in `pedigree`.
+    pub fn genotype(
+        self,
+        pedigree: &PedigreeByName,
+    ) -> Result<IndexMap<String, Option<GenotypeChoice>>, anyhow::Error> {
+        let index = index(self, pedigree)?;
+        let is_parent_of_index = |name: &str| {
+            index.father.as_deref() == Some(name) || index.mother.as_deref() == Some(name)
+        };
+        let is_mother_of_affected = |name: &str| {
+            pedigree.individuals.values().any(|individual| {
+                individual.disease == Disease::Affected
+                    && individual.mother.as_deref() == Some(name)
+            })
+        };
+
+        Ok(pedigree
+            .individuals
+            .iter()
+            .map(|(name, individual)| {
+                let is_index = name == &index.name;
+                let choice = match (self, individual.disease, individual.sex) {
+                    (Preset::DeNovo, _, _) if is_index => GenotypeChoice::Het,
+                    (Preset::DeNovo, _, _) if is_parent_of_index(name) => GenotypeChoice::Ref,
+                    (Preset::Recessive, _, _) if is_index => GenotypeChoice::RecessiveIndex,
+                    (Preset::Recessive, _, _) if is_parent_of_index(name) => {
+                        GenotypeChoice::RecessiveParent
+                    }
+                    (Preset::Dominant, Disease::Affected, _) => GenotypeChoice::Het,
+                    (Preset::Dominant, Disease::Unaffected, _) => GenotypeChoice::Ref,
+                    (Preset::Mitochondrial, Disease::Affected, _) => GenotypeChoice::Variant,
+                    (Preset::Mitochondrial, _, _) if is_mother_of_affected(name) => {
+                        GenotypeChoice::Variant
+                    }
+                    (Preset::XLinked, Disease::Affected, _) => GenotypeChoice::Hom,
+                    (Preset::XLinked, Disease::Unaffected, Sex::Male) => GenotypeChoice::Ref,
+                    (Preset::XLinked, Disease::Unaffected, _) => GenotypeChoice::NonHom,
+                    _ => GenotypeChoice::Any,
+                };
+                (name.clone(), Some(choice))
+            })
+            .collect())
+    }
+}
+
+/// Return the index individual for `preset`.
+///
+/// This is the first affected individual with both parents in the pedigree, or the first
+/// affected individual if there is no such individual.
+fn index(
+    preset: Preset,
+    pedigree: &PedigreeByName,
+) -> Result<&mehari::ped::Individual, anyhow::Error> {
+    let mut affected = pedigree
+        .individuals
+        .values()
+        .filter(|individual| individual.disease == Disease::Affected)
+        .peekable();
+    let first = affected.peek().copied().ok_or_else(|| {
+        anyhow::anyhow!(
+            "preset {} requires an affected individual in the pedigree",
+            preset
+        )
+    })?;
+    let has_parent = |parent: &Option<String>| {
+        parent
+            .as_ref()
+            .map(|parent| pedigree.individuals.contains_key(parent))
+            .unwrap_or(false)
+    };
+    Ok(affected
+        .find(|individual| has_parent(&individual.father) && has_parent(&individual.mother))
+        .unwrap_or(first))
+}
+
+/// Return the query settings of all presets by name, without the genotype choices.
+pub fn all() -> IndexMap<String, CaseQuery> {
+    Preset::iter()
+        .map(|preset| (preset.to_string(), preset.case_query()))
+        .collect()
+}
+
+/// Parse the query JSON `value`, resolving the preset named by its `preset` key with the
+/// genotype choices for `pedigree`.
+pub fn resolve_with_pedigree(
+    mut value: serde_json::Value,
+    pedigree: &PedigreeByName,
+) -> Result<CaseQuery, anyhow::Error> {
+    let preset = value
+        .as_object_mut()
+        .and_then(|query| query.remove(PRESET_KEY));
+    let value = match preset {
+        None => value,
+        Some(name) => {
+            let preset = parse_preset(&name)?;
+            let base = serde_json::to_value(CaseQuery {
+                genotype: preset.genotype(pedigree)?,
+                ..preset.case_query()
+            })
+            .map_err(|e| anyhow::anyhow!("could not serialize preset: {}", e))?;
+            crate::common::overlay_json(base, value)
+        }
+    };
+    serde_json::from_value(value).map_err(|e| anyhow::anyhow!("invalid query JSON: {}", e))
+}
+
+/// Parse the query JSON `value`, resolving the preset named by its `preset` key.
+///
+/// The pedigree is only read from the header of `path_input` if a preset is given.
+pub async fn resolve(
+    value: serde_json::Value,
+    path_input: &str,
+) -> Result<CaseQuery, anyhow::Error> {
+    let pedigree = if value.get(PRESET_KEY).is_some() {
+        super::read_pedigree(path_input).await?
+    } else {
+        Default::default()
+    };
+    resolve_with_pedigree(value, &pedigree)
+}
+
+/// Parse the preset name from the JSON value `name`.
+fn parse_preset(name: &serde_json::Value) -> Result<Preset, anyhow::Error> {
+    let name = name
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("preset name must be a string: {}", name))?;
+    name.parse()
+        .map_err(|_| anyhow::anyhow!("unknown seqvars query preset: {}", name))
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::ped::{Disease, Individual, PedigreeByName, Sex};
+
+    use super::Preset;
+    use crate::seqvars::query::schema::GenotypeChoice::{self, *};
+
+    /// Trio with affected son and unaffected parents, and an unaffected brother.
+    fn pedigree() -> PedigreeByName {
+        let individual = |name: &str, parents: Option<(&str, &str)>, sex, disease| Individual {
+            family: "FAM".into(),
+            name: name.into(),
+            father: parents.map(|(father, _)| father.into()),
+            mother: parents.map(|(_, mother)| mother.into()),
+            sex,
+            disease,
+        };
+        PedigreeByName {
+            individuals: [
+                individual("father", None, Sex::Male, Disease::Unaffected),
+                individual("mother", None, Sex::Female, Disease::Unaffected),
+                individual(
+                    "brother",
+                    Some(("father", "mother")),
+                    Sex::Male,
+                    Disease::Unaffected,
+                ),
+                individual(
+                    "index",
+                    Some(("father", "mother")),
+                    Sex::Male,
+                    Disease::Affected,
+                ),
+            ]
+            .into_iter()
+            .map(|individual| (individual.name.clone(), individual))
+            .collect(),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(Preset::DeNovo, [Ref, Ref, Any, Het])]
+    #[case(Preset::Recessive, [RecessiveParent, RecessiveParent, Any, RecessiveIndex])]
+    #[case(Preset::Dominant, [Ref, Ref, Ref, Het])]
+    #[case(Preset::Mitochondrial, [Any, Variant, Any, Variant])]
+    #[case(Preset::XLinked, [Ref, NonHom, Ref, Hom])]
+    fn genotype(
+        #[case] preset: Preset,
+        #[case] expected: [GenotypeChoice; 4],
+    ) -> Result<(), anyhow::Error> {
+        let genotype = preset.genotype(&pedigree())?;
+
+        assert_eq!(
+            genotype.values().cloned().collect::<Vec<_>>(),
+            expected.into_iter().map(Some).collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn genotype_no_affected() {
+        let mut pedigree = pedigree();
+        pedigree.individuals["index"].disease = Disease::Unknown;
+
+        assert!(Preset::DeNovo.genotype(&pedigree).is_err());
+    }
+
+    #[test]
+    fn resolve_with_overrides() -> Result<(), anyhow::Error> {
+        let query = super::resolve_with_pedigree(
+            serde_json::json!({
+                "preset": "de-novo",
+                "gnomad_exomes_frequency": 0.001,
+            }),
+            &pedigree(),
+        )?;
+
+        assert_eq!(query.gnomad_exomes_frequency, Some(0.001));
+        assert_eq!(query.gnomad_genomes_frequency, Some(0.0001));
+        assert_eq!(query.genotype.get("index"), Some(&Some(Het)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_without_preset() -> Result<(), anyhow::Error> {
+        let query = super::resolve_with_pedigree(serde_json::json!({}), &Default::default())?;
+
+        assert_eq!(query, Default::default());
+
+        Ok(())
+    }
+}
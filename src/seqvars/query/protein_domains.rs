@@ -0,0 +1,217 @@
+//! Protein domains (e.g., Pfam or InterPro) per transcript.
+//!
+//! The domains are read from a TSV file with the header line
+//! `transcript_id source accession name start end` where `start` and `end` are the
+//! 1-based, inclusive amino acid positions of the domain in the protein of the
+//! transcript.  Missense and in-frame variants are annotated with the domains that
+//! overlap the changed amino acids of the first annotated transcript.
+
+use std::collections::HashMap;
+
+use mehari::{
+    annotate::seqvars::ann::{AnnField, Consequence},
+    common::io::std::open_read_maybe_gz,
+};
+
+use super::schema::SequenceVariant;
+
+/// One protein domain of a transcript.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Domain {
+    /// The source database, e.g., `Pfam` or `InterPro`.
+    pub source: String,
+    /// The accession in the source database, e.g., `PF00069`.
+    pub accession: String,
+    /// The name of the domain, e.g., `Pkinase`.
+    pub name: String,
+    /// The 1-based start amino acid position.
+    pub start: i32,
+    /// The 1-based, inclusive end amino acid position.
+    pub end: i32,
+}
+
+/// One line of the protein domains TSV file.
+#[derive(Debug, serde::Deserialize)]
+struct Row {
+    /// The transcript accession, with or without version.
+    transcript_id: String,
+    /// See `Domain::source`.
+    source: String,
+    /// See `Domain::accession`.
+    accession: String,
+    /// See `Domain::name`.
+    name: String,
+    /// See `Domain::start`.
+    start: i32,
+    /// See `Domain::end`.
+    end: i32,
+}
+
+/// The consequences for which the domains are annotated.
+const CONSEQUENCES: &[Consequence] = &[
+    Consequence::MissenseVariant,
+    Consequence::ConservativeInframeDeletion,
+    Consequence::ConservativeInframeInsertion,
+    Consequence::DisruptiveInframeDeletion,
+    Consequence::DisruptiveInframeInsertion,
+];
+
+/// Return the transcript accession `tx_id` without the version.
+fn strip_version(tx_id: &str) -> &str {
+    tx_id.split_once('.').map(|(acc, _)| acc).unwrap_or(tx_id)
+}
+
+/// Protein domains by transcript accession without version.
+#[derive(Debug, Default, Clone)]
+pub struct ProteinDomains {
+    /// The domains by transcript accession without version.
+    by_tx: HashMap<String, Vec<Domain>>,
+}
+
+impl ProteinDomains {
+    /// Load the protein domains from the TSV file at `path`, optionally gzip-compressed.
+    pub fn from_path(path: &str) -> Result<Self, anyhow::Error> {
+        tracing::debug!("loading protein domains from {}...", path);
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .comment(Some(b'#'))
+            .delimiter(b'\t')
+            .from_reader(open_read_maybe_gz(path)?);
+        let mut result = Self::default();
+        for row in reader.deserialize() {
+            let row: Row =
+                row.map_err(|e| anyhow::anyhow!("invalid protein domain in {}: {}", path, e))?;
+            if row.start < 1 || row.end < row.start {
+                anyhow::bail!("invalid protein domain coordinates in {:?}", &row);
+            }
+            result
+                .by_tx
+                .entry(strip_version(&row.transcript_id).to_string())
+                .or_default()
+                .push(Domain {
+                    source: row.source,
+                    accession: row.accession,
+                    name: row.name,
+                    start: row.start,
+                    end: row.end,
+                });
+        }
+        tracing::debug!(
+            "... done loading protein domains of {} transcripts",
+            result.by_tx.len()
+        );
+        Ok(result)
+    }
+
+    /// Return the domains of the first annotated transcript of `seqvar` that overlap the
+    /// changed amino acids.
+    ///
+    /// Only missense and in-frame variants are annotated.
+    pub fn domains_for(&self, seqvar: &SequenceVariant) -> Vec<&Domain> {
+        let Some((start, end)) = seqvar
+            .ann_fields
+            .first()
+            .and_then(|ann| protein_range(ann, seqvar))
+        else {
+            return Vec::new();
+        };
+        let tx_id = strip_version(&seqvar.ann_fields[0].feature_id);
+        self.by_tx
+            .get(tx_id)
+            .map(|domains| {
+                domains
+                    .iter()
+                    .filter(|domain| domain.start <= end && start <= domain.end)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Return the 1-based, inclusive range of the amino acids changed by `seqvar` in the
+/// protein of the transcript of `ann`, if it is a missense or in-frame variant.
+///
+/// The annotation only has the first changed amino acid; for deletions, the end is
+/// derived from the number of deleted bases.
+fn protein_range(ann: &AnnField, seqvar: &SequenceVariant) -> Option<(i32, i32)> {
+    if !ann
+        .consequences
+        .iter()
+        .any(|csq| CONSEQUENCES.contains(csq))
+    {
+        return None;
+    }
+    let start = ann.protein_pos.as_ref()?.ord;
+    let deleted = seqvar
+        .reference
+        .len()
+        .saturating_sub(seqvar.alternative.len()) as i32;
+    Some((start, start + (deleted / 3).max(1) - 1))
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::seqvars::ann::{AnnField, Consequence, Pos};
+
+    use crate::seqvars::query::schema::SequenceVariant;
+
+    fn seqvar(
+        consequence: Consequence,
+        protein_pos: i32,
+        reference: &str,
+        alternative: &str,
+    ) -> SequenceVariant {
+        SequenceVariant {
+            reference: reference.into(),
+            alternative: alternative.into(),
+            ann_fields: vec![AnnField {
+                consequences: vec![consequence],
+                feature_id: "NM_000492.4".into(),
+                protein_pos: Some(Pos {
+                    ord: protein_pos,
+                    total: Some(1480),
+                }),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[rstest::rstest]
+    #[case(Consequence::MissenseVariant, 100, "A", "G", vec![])]
+    #[case(Consequence::MissenseVariant, 450, "A", "G", vec!["PF00005"])]
+    #[case(Consequence::MissenseVariant, 500, "A", "G", vec!["PF00005", "IPR003439"])]
+    #[case(Consequence::SynonymousVariant, 500, "A", "G", vec![])]
+    #[case(Consequence::ConservativeInframeDeletion, 478, "ACTTTGA", "A", vec!["PF00005"])]
+    #[case(Consequence::ConservativeInframeDeletion, 480, "ACTTTGA", "A", vec!["PF00005", "IPR003439"])]
+    fn domains_for(
+        #[case] consequence: Consequence,
+        #[case] protein_pos: i32,
+        #[case] reference: &str,
+        #[case] alternative: &str,
+        #[case] expected: Vec<&str>,
+    ) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("domains.tsv");
+        std::fs::write(
+            &path,
+            "transcript_id\tsource\taccession\tname\tstart\tend\n\
+             NM_000492.3\tPfam\tPF00005\tABC_tran\t423\t571\n\
+             NM_000492\tInterPro\tIPR003439\tABC_transporter-like\t481\t600\n\
+             NM_000000.1\tPfam\tPF00069\tPkinase\t1\t1000\n",
+        )?;
+        let domains = super::ProteinDomains::from_path(path.to_str().unwrap())?;
+
+        let seqvar = seqvar(consequence, protein_pos, reference, alternative);
+        assert_eq!(
+            domains
+                .domains_for(&seqvar)
+                .iter()
+                .map(|domain| domain.accession.as_str())
+                .collect::<Vec<_>>(),
+            expected
+        );
+
+        Ok(())
+    }
+}
@@ -2,6 +2,11 @@
 
 use noodles_vcf as vcf;
 
+use crate::common::record::{Value, Variant};
+
+/// Version of the query JSON schema, incremented on incompatible changes.
+pub const SCHEMA_VERSION: u32 = 1;
+
 /// Enumeration for recessive mode queries.
 #[derive(
     serde::Serialize, serde::Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy,
@@ -15,6 +20,53 @@ pub enum RecessiveMode {
     CompoundRecessive,
 }
 
+/// Built-in inheritance models that replace the per-sample genotype choices.
+///
+/// The sex and affection status of the samples are taken from the pedigree.
+#[derive(
+    serde::Serialize, serde::Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy,
+)]
+pub enum InheritanceMode {
+    /// X-linked recessive; affected males hemizygous, affected females hom. alt.,
+    /// unaffected males ref. and unaffected females not hom. alt.
+    #[serde(rename = "x-linked-recessive")]
+    XLinkedRecessive,
+    /// X-linked dominant; affected individuals carry the variant, unaffected are ref.
+    #[serde(rename = "x-linked-dominant")]
+    XLinkedDominant,
+    /// Mitochondrial; affected individuals and their mothers carry the variant.
+    #[serde(rename = "mitochondrial")]
+    Mitochondrial,
+}
+
+/// Handling of variants in gene loci with problematic paralogy, see
+/// `crate::seqvars::query::paralogs`.
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+)]
+pub enum ParalogLociChoice {
+    /// Include the variants without caution flag.
+    #[serde(rename = "include")]
+    Include,
+    /// Include the variants with caution flag.
+    #[default]
+    #[serde(rename = "flag")]
+    Flag,
+    /// Exclude the variants.
+    #[serde(rename = "exclude")]
+    Exclude,
+}
+
 /// Choices for failing quality thresholds on genotypes.
 #[derive(
     serde::Serialize,
@@ -153,6 +205,33 @@ pub struct GenomicRegion {
     pub range: Option<Range>,
 }
 
+/// Population frequency thresholds for one class of chromosomes.
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone, Default)]
+#[serde(default)]
+pub struct FrequencyThresholds {
+    /// Maximal allele frequency in gnomAD exomes and genomes.
+    pub max_af: Option<f32>,
+    /// Maximal number of homozygous carriers in gnomAD exomes and genomes; on chrX and
+    /// chrY, the hemizygous carriers are counted as well.
+    pub max_hom: Option<i32>,
+    /// Maximal number of in-house carriers; on chrX and chrY, the hemizygous carriers are
+    /// counted as well.
+    pub max_inhouse_carriers: Option<i32>,
+}
+
+/// Population frequency thresholds, separately for autosomal, gonosomal, and
+/// mitochondrial records.
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone, Default)]
+#[serde(default)]
+pub struct FrequencyThresholdsByChrom {
+    /// Thresholds for the autosomes.
+    pub autosomal: FrequencyThresholds,
+    /// Thresholds for chrX and chrY.
+    pub gonosomal: FrequencyThresholds,
+    /// Thresholds for chrMT.
+    pub mtdna: FrequencyThresholds,
+}
+
 /// Data structure with a single query.
 #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
 #[serde(default)]
@@ -164,6 +243,8 @@ pub struct CaseQuery {
     pub quality: indexmap::IndexMap<String, QualitySettings>,
     /// Genotype choice for each individual.
     pub genotype: indexmap::IndexMap<String, Option<GenotypeChoice>>,
+    /// Built-in inheritance model to use instead of the genotype choices, if any.
+    pub inheritance_mode: Option<InheritanceMode>,
 
     /// Whether to include coding transcripts.
     pub transcripts_coding: bool,
@@ -199,6 +280,11 @@ pub struct CaseQuery {
     /// Whether to include uncertain significance ClinVar variants.
     pub clinvar_include_uncertain_significance: bool,
 
+    /// Whether to require missense or in-frame variants in an annotated protein domain.
+    pub require_in_protein_domain: bool,
+    /// Handling of variants in gene loci with problematic paralogy.
+    pub paralog_loci: ParalogLociChoice,
+
     /// Whether to enable filtration by gnomAD exomes.
     pub gnomad_exomes_enabled: bool,
     /// Whether to enable filtration by gnomAD genomes
@@ -241,6 +327,16 @@ pub struct CaseQuery {
     pub helixmtdb_heteroplasmic: Option<i32>,
     /// Maximal number of homozygous carriers in HelixMtDb.
     pub helixmtdb_homoplasmic: Option<i32>,
+
+    /// Population frequency thresholds by chromosome class, independent of the
+    /// per-database settings above.
+    pub frequency_thresholds: FrequencyThresholdsByChrom,
+
+    /// Maximal number of records to write out; optional.
+    ///
+    /// If more records pass the filters, only the top-ranked records are written
+    /// and the result set is marked as truncated.
+    pub max_results: Option<usize>,
 }
 
 impl Default for CaseQuery {
@@ -254,6 +350,7 @@ impl Default for CaseQuery {
             helixmtdb_enabled: Default::default(),
             quality: Default::default(),
             genotype: Default::default(),
+            inheritance_mode: Default::default(),
             transcripts_coding: true,
             transcripts_noncoding: true,
             var_type_snv: true,
@@ -268,6 +365,8 @@ impl Default for CaseQuery {
             clinvar_include_likely_benign: true,
             clinvar_include_likely_pathogenic: true,
             clinvar_include_uncertain_significance: true,
+            require_in_protein_domain: Default::default(),
+            paralog_loci: Default::default(),
             gnomad_exomes_frequency: Default::default(),
             gnomad_exomes_heterozygous: Default::default(),
             gnomad_exomes_homozygous: Default::default(),
@@ -283,6 +382,8 @@ impl Default for CaseQuery {
             helixmtdb_frequency: Default::default(),
             helixmtdb_heteroplasmic: Default::default(),
             helixmtdb_homoplasmic: Default::default(),
+            frequency_thresholds: Default::default(),
+            max_results: Default::default(),
         }
     }
 }
@@ -389,139 +490,97 @@ pub struct SequenceVariant {
 impl SequenceVariant {
     /// Convert from VCF record.
     pub fn from_vcf(record: &vcf::Record, header: &vcf::Header) -> Result<Self, anyhow::Error> {
-        let chrom = record.chromosome().to_string();
-        let pos: usize = record.position().into();
-        let pos = pos as i32;
+        Self::from_variant(&Variant::from_vcf(header, record), header.sample_names())
+    }
 
-        let reference = record.reference_bases().to_string();
-        let alternative = record.alternate_bases()[0].to_string();
+    /// Convert from the internal record model, `sample_names` in the order of the calls.
+    pub fn from_variant(
+        variant: &Variant,
+        sample_names: &indexmap::IndexSet<String>,
+    ) -> Result<Self, anyhow::Error> {
+        let alternative = variant
+            .alternatives
+            .first()
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("no alternate allele in {:?}", variant))?;
 
-        let call_info = Self::build_call_info(record, header)?;
-        let ann_fields = Self::extract_ann_fields(record)?;
+        let call_info = Self::build_call_info(variant, sample_names);
+        let ann_fields = Self::extract_ann_fields(variant)?;
 
         let result = Self {
-            chrom,
-            pos,
-            reference,
+            chrom: variant.chrom.clone(),
+            pos: variant.pos,
+            reference: variant.reference.clone(),
             alternative,
             call_info,
             ann_fields,
             ..Default::default()
         };
 
-        Self::with_freqs(result, record)
+        Ok(Self::with_freqs(result, variant))
     }
 
     /// Build call information.
     fn build_call_info(
-        record: &vcf::Record,
-        header: &vcf::Header,
-    ) -> Result<indexmap::IndexMap<String, CallInfo>, anyhow::Error> {
-        let mut result = indexmap::IndexMap::new();
-
-        for (name, sample) in header
-            .sample_names()
+        variant: &Variant,
+        sample_names: &indexmap::IndexSet<String>,
+    ) -> indexmap::IndexMap<String, CallInfo> {
+        sample_names
             .iter()
-            .zip(record.genotypes().values())
-        {
-            let genotype = if let Some(Some(vcf::record::genotypes::sample::Value::String(gt))) =
-                sample.get(&vcf::record::genotypes::keys::key::GENOTYPE)
-            {
-                Some(gt.clone())
-            } else {
-                None
-            };
-            let quality =
-                if let Some(Some(vcf::record::genotypes::sample::Value::Integer(quality))) =
-                    sample.get(&vcf::record::genotypes::keys::key::CONDITIONAL_GENOTYPE_QUALITY)
-                {
-                    Some(*quality as f32)
-                } else {
-                    None
+            .zip(variant.calls.iter())
+            .map(|(name, call)| {
+                let genotype = match call.get("GT") {
+                    Some(Value::String(gt)) => Some(gt.clone()),
+                    _ => None,
                 };
-            let dp = if let Some(Some(vcf::record::genotypes::sample::Value::Integer(dp))) =
-                sample.get(&vcf::record::genotypes::keys::key::READ_DEPTH)
-            {
-                Some(*dp)
-            } else {
-                None
-            };
-            let ad = if let Some(Some(vcf::record::genotypes::sample::Value::Array(
-                vcf::record::genotypes::sample::value::Array::Integer(ad),
-            ))) = sample.get(&vcf::record::genotypes::keys::key::READ_DEPTHS)
-            {
-                Some(ad[1].expect("empty AD?"))
-            } else {
-                None
-            };
-            let phase_set = if let Some(Some(vcf::record::genotypes::sample::Value::Integer(id))) =
-                sample.get(&vcf::record::genotypes::keys::key::PHASE_SET)
-            {
-                Some(*id)
-            } else {
-                None
-            };
-
-            result.insert(
-                name.clone(),
-                CallInfo {
-                    genotype,
-                    quality,
-                    dp,
-                    ad,
-                    phasing_id: phase_set,
-                },
-            );
-        }
-
-        Ok(result)
+                let ad = match call.get("AD") {
+                    Some(Value::IntegerArray(ad)) => ad.get(1).copied().flatten(),
+                    _ => None,
+                };
+                let phasing_id = match call.get("PS") {
+                    Some(Value::Integer(id)) => Some(*id),
+                    _ => None,
+                };
+                (
+                    name.clone(),
+                    CallInfo {
+                        genotype,
+                        quality: call.gq(),
+                        dp: call.dp(),
+                        ad,
+                        phasing_id,
+                    },
+                )
+            })
+            .collect()
     }
 
     /// Extract `INFO/ANN` entries
     fn extract_ann_fields(
-        record: &vcf::Record,
+        variant: &Variant,
     ) -> Result<Vec<mehari::annotate::seqvars::ann::AnnField>, anyhow::Error> {
-        if let Some(Some(ann)) = record.info().get(
-            &"ANN"
-                .parse::<vcf::record::info::field::Key>()
-                .expect("invalid key INFO/ANN?"),
-        ) {
-            if let vcf::record::info::field::Value::Array(
-                vcf::record::info::field::value::Array::String(ann),
-            ) = ann
-            {
-                ann.iter()
-                    .flatten()
-                    .map(|s| s.parse::<mehari::annotate::seqvars::ann::AnnField>())
-                    .collect::<Result<Vec<_>, _>>()
-                    .map_err(|e| anyhow::anyhow!("problem parsing ANN: {}", e))
-            } else {
-                anyhow::bail!("invalid type of INFO/ANN")
-            }
-        } else {
-            Ok(Vec::default())
+        match variant.info.get("ANN") {
+            Some(Some(Value::StringArray(ann))) => ann
+                .iter()
+                .flatten()
+                .map(|s| s.parse::<mehari::annotate::seqvars::ann::AnnField>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| anyhow::anyhow!("problem parsing ANN: {}", e)),
+            Some(Some(_)) => anyhow::bail!("invalid type of INFO/ANN"),
+            _ => Ok(Vec::default()),
         }
     }
 
-    /// Copy the frequencies from `record` to `result`.
-    fn with_freqs(
-        result: SequenceVariant,
-        record: &vcf::Record,
-    ) -> Result<SequenceVariant, anyhow::Error> {
-        use vcf::record::info::field::Key;
-        use vcf::record::info::field::Value;
-
+    /// Copy the frequencies from `variant` to `result`.
+    fn with_freqs(result: SequenceVariant, variant: &Variant) -> SequenceVariant {
         macro_rules! extract_key {
             ($key:ident) => {
-                let $key = if let Some(Some(Value::Integer($key))) = record.info().get(
-                    &stringify!($key)
-                        .parse::<Key>()
-                        .expect(&format!("could not parse key: {:?}", stringify!($key))),
-                ) {
-                    *$key
-                } else {
-                    0
-                };
+                let $key =
+                    if let Some(Some(Value::Integer($key))) = variant.info.get(stringify!($key)) {
+                        *$key
+                    } else {
+                        0
+                    };
             };
         }
 
@@ -539,7 +598,12 @@ impl SequenceVariant {
         extract_key!(helix_hom);
         extract_key!(helix_het);
 
-        Ok(SequenceVariant {
+        extract_key!(inhouse_an);
+        extract_key!(inhouse_hom);
+        extract_key!(inhouse_het);
+        extract_key!(inhouse_hemi);
+
+        SequenceVariant {
             gnomad_exomes_an,
             gnomad_exomes_hom,
             gnomad_exomes_het,
@@ -551,8 +615,12 @@ impl SequenceVariant {
             helix_an,
             helix_hom,
             helix_het,
+            inhouse_an,
+            inhouse_hom,
+            inhouse_het,
+            inhouse_hemi,
             ..result
-        })
+        }
     }
 
     /// Return allele frequency in gnomAD exomes.
@@ -7,6 +7,12 @@ use super::schema::SequenceVariant;
 pub struct ByHgncId {
     pub hgnc_id: String,
     pub seqvar: SequenceVariant,
+    /// Names of the failed filters, only non-empty in soft-filter mode.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_filters: Vec<String>,
+    /// Partner variants of compound heterozygous pairs, only set in recessive mode.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comphet_partners: Vec<String>,
 }
 
 impl From<SequenceVariant> for ByHgncId {
@@ -18,6 +24,8 @@ impl From<SequenceVariant> for ByHgncId {
                 String::new()
             },
             seqvar: val,
+            failed_filters: Vec::new(),
+            comphet_partners: Vec::new(),
         }
     }
 }
@@ -47,6 +55,12 @@ impl Ord for ByHgncId {
 pub struct ByCoordinate {
     pub coordinate: (String, i32),
     pub seqvar: SequenceVariant,
+    /// Names of the failed filters, only non-empty in soft-filter mode.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_filters: Vec<String>,
+    /// Partner variants of compound heterozygous pairs, only set in recessive mode.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comphet_partners: Vec<String>,
 }
 
 impl From<SequenceVariant> for ByCoordinate {
@@ -54,6 +68,18 @@ impl From<SequenceVariant> for ByCoordinate {
         Self {
             coordinate: (val.chrom.clone(), val.pos),
             seqvar: val,
+            failed_filters: Vec::new(),
+            comphet_partners: Vec::new(),
+        }
+    }
+}
+
+impl From<ByHgncId> for ByCoordinate {
+    fn from(val: ByHgncId) -> Self {
+        Self {
+            failed_filters: val.failed_filters,
+            comphet_partners: val.comphet_partners,
+            ..Self::from(val.seqvar)
         }
     }
 }
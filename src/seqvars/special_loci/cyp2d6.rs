@@ -0,0 +1,76 @@
+//! Parsing of the output of the CYP2D6 star allele caller Cyrius.
+//!
+//! Cyrius writes a TSV file with the columns `Sample`, `Genotype`, and `Filter`.  The
+//! genotype is given as a pair of star alleles, e.g., `*1/*4` or `*2/*68+*4`, and as
+//! `None` if no call could be made.
+
+use super::{Call, CallerParser};
+
+/// One row of the Cyrius output.
+#[derive(Debug, Clone, serde::Deserialize)]
+struct CallerRecord {
+    #[serde(rename = "Sample")]
+    sample: String,
+    #[serde(rename = "Genotype")]
+    genotype: String,
+    #[serde(rename = "Filter")]
+    filter: String,
+}
+
+/// The CYP2D6 star allele call of one sample.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Cyp2d6Call {
+    /// The star allele diplotype, `None` for no-calls.
+    pub genotype: Option<String>,
+    /// The filter value of the call, `PASS` for good calls.
+    pub filter: String,
+}
+
+/// Parser for the output of Cyrius.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Parser;
+
+impl CallerParser for Parser {
+    fn name(&self) -> &'static str {
+        "cyp2d6"
+    }
+
+    fn parse(&self, path: &str) -> Result<Vec<Call>, anyhow::Error> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(b'\t')
+            .from_path(path)
+            .map_err(|e| anyhow::anyhow!("could not open CYP2D6 caller output {}: {}", path, e))?;
+        let mut result = Vec::new();
+        for record in reader.deserialize() {
+            let record: CallerRecord = record.map_err(|e| {
+                anyhow::anyhow!("could not parse CYP2D6 caller output {}: {}", path, e)
+            })?;
+            let call = Cyp2d6Call {
+                genotype: (record.genotype != "None").then_some(record.genotype),
+                filter: record.filter,
+            };
+            result.push(Call {
+                sample: record.sample,
+                summary: call
+                    .genotype
+                    .clone()
+                    .unwrap_or_else(|| format!("no call ({})", &call.filter)),
+                notable: false,
+                details: serde_json::to_value(&call)?,
+            });
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::seqvars::special_loci::CallerParser as _;
+
+    #[test]
+    fn parse() -> Result<(), anyhow::Error> {
+        insta::assert_yaml_snapshot!(super::Parser.parse("tests/seqvars/special_loci/cyp2d6.tsv")?);
+
+        Ok(())
+    }
+}
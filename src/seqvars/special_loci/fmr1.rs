@@ -0,0 +1,174 @@
+//! Parsing of the `FMR1` CGG repeat from ExpansionHunter output.
+//!
+//! ExpansionHunter writes one JSON file per sample with the genotypes of all loci in its
+//! catalog; only the `FMR1` locus is used here.  The alleles are classified following the
+//! ACMG guidelines for fragile X testing.
+
+use super::{Call, CallerParser};
+
+/// The top-level ExpansionHunter output, limited to the fields of interest.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CallerOutput {
+    locus_results: indexmap::IndexMap<String, LocusResult>,
+    sample_parameters: SampleParameters,
+}
+
+/// The result for one locus.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct LocusResult {
+    variants: indexmap::IndexMap<String, VariantResult>,
+}
+
+/// The result for one variant of a locus.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct VariantResult {
+    genotype: Option<String>,
+    genotype_confidence_interval: Option<String>,
+    repeat_unit: String,
+}
+
+/// The sample parameters.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SampleParameters {
+    sample_id: String,
+}
+
+/// Classification of a `FMR1` repeat allele.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+    strum::Display,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum RepeatClass {
+    /// Up to 44 repeats.
+    Normal,
+    /// 45 to 54 repeats.
+    Intermediate,
+    /// 55 to 200 repeats.
+    Premutation,
+    /// More than 200 repeats.
+    FullMutation,
+}
+
+impl RepeatClass {
+    /// Classify an allele with `count` repeats.
+    pub fn of(count: u32) -> Self {
+        match count {
+            0..=44 => Self::Normal,
+            45..=54 => Self::Intermediate,
+            55..=200 => Self::Premutation,
+            _ => Self::FullMutation,
+        }
+    }
+}
+
+/// The `FMR1` repeat call of one sample.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Fmr1Call {
+    /// The repeat unit.
+    pub repeat_unit: String,
+    /// The repeat counts of the alleles, one for hemizygous calls.
+    pub repeat_counts: Vec<u32>,
+    /// The confidence intervals as written by ExpansionHunter.
+    pub confidence_interval: Option<String>,
+    /// The class of the longest allele, `None` for no-calls.
+    pub class: Option<RepeatClass>,
+}
+
+/// Parser for the `FMR1` locus in ExpansionHunter output.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Parser;
+
+impl CallerParser for Parser {
+    fn name(&self) -> &'static str {
+        "fmr1"
+    }
+
+    fn parse(&self, path: &str) -> Result<Vec<Call>, anyhow::Error> {
+        let reader = std::fs::File::open(path).map_err(|e| {
+            anyhow::anyhow!("could not open ExpansionHunter output {}: {}", path, e)
+        })?;
+        let output: CallerOutput = serde_json::from_reader(reader).map_err(|e| {
+            anyhow::anyhow!("could not parse ExpansionHunter output {}: {}", path, e)
+        })?;
+        let variant = output
+            .locus_results
+            .get("FMR1")
+            .and_then(|locus| locus.variants.get("FMR1"))
+            .ok_or_else(|| anyhow::anyhow!("no FMR1 locus in ExpansionHunter output {}", path))?;
+
+        let repeat_counts = variant
+            .genotype
+            .as_deref()
+            .unwrap_or_default()
+            .split('/')
+            .filter(|count| !count.is_empty())
+            .map(|count| {
+                count
+                    .parse::<u32>()
+                    .map_err(|e| anyhow::anyhow!("invalid FMR1 repeat count {}: {}", count, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let call = Fmr1Call {
+            repeat_unit: variant.repeat_unit.clone(),
+            class: repeat_counts
+                .iter()
+                .max()
+                .map(|count| RepeatClass::of(*count)),
+            repeat_counts,
+            confidence_interval: variant.genotype_confidence_interval.clone(),
+        };
+
+        Ok(vec![Call {
+            sample: output.sample_parameters.sample_id,
+            summary: match (variant.genotype.as_ref(), call.class) {
+                (Some(genotype), Some(class)) => {
+                    format!("({}){} ({})", &call.repeat_unit, genotype, class)
+                }
+                _ => "no call".into(),
+            },
+            notable: call
+                .class
+                .map(|class| class >= RepeatClass::Premutation)
+                .unwrap_or_default(),
+            details: serde_json::to_value(&call)?,
+        }])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RepeatClass;
+    use crate::seqvars::special_loci::CallerParser as _;
+
+    #[rstest::rstest]
+    #[case(30, RepeatClass::Normal)]
+    #[case(44, RepeatClass::Normal)]
+    #[case(45, RepeatClass::Intermediate)]
+    #[case(55, RepeatClass::Premutation)]
+    #[case(200, RepeatClass::Premutation)]
+    #[case(201, RepeatClass::FullMutation)]
+    fn repeat_class(#[case] count: u32, #[case] expected: RepeatClass) {
+        assert_eq!(RepeatClass::of(count), expected);
+    }
+
+    #[test]
+    fn parse() -> Result<(), anyhow::Error> {
+        insta::assert_yaml_snapshot!(super::Parser.parse("tests/seqvars/special_loci/fmr1.json")?);
+
+        Ok(())
+    }
+}
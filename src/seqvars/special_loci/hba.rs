@@ -0,0 +1,150 @@
+//! Parsing of the output of the `HBA1`/`HBA2` alpha-globin copy number callers.
+//!
+//! The caller writes a TSV file with the columns `Sample`, `Genotype`, `Total_CN`, and
+//! `Filter`.  The genotype is given as a pair of alpha-globin haplotypes, e.g., `aa/aa` or
+//! `-a3.7/aa`, and as `None` if no call could be made.  The alpha-thalassemia status is
+//! derived from the total number of `HBA1` and `HBA2` copies.
+
+use super::{Call, CallerParser};
+
+/// One row of the caller output.
+#[derive(Debug, Clone, serde::Deserialize)]
+struct CallerRecord {
+    #[serde(rename = "Sample")]
+    sample: String,
+    #[serde(rename = "Genotype")]
+    genotype: String,
+    #[serde(rename = "Total_CN")]
+    total_cn: String,
+    #[serde(rename = "Filter")]
+    filter: String,
+}
+
+/// The alpha-thalassemia status derived from the alpha-globin copy number.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, strum::Display,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AlphaThalStatus {
+    /// Four or more copies.
+    Normal,
+    /// Three copies, silent carrier (`-a/aa`).
+    SilentCarrier,
+    /// Two copies, alpha-thalassemia trait (`--/aa` or `-a/-a`).
+    Trait,
+    /// One copy, HbH disease.
+    HbhDisease,
+    /// No copy, Hb Bart's hydrops fetalis.
+    HbBarts,
+    /// The caller made no call.
+    NoCall,
+}
+
+impl AlphaThalStatus {
+    /// Derive from the total alpha-globin copy number.
+    fn from_total_cn(total_cn: Option<i32>) -> Self {
+        match total_cn {
+            None => AlphaThalStatus::NoCall,
+            Some(cn) if cn >= 4 => AlphaThalStatus::Normal,
+            Some(3) => AlphaThalStatus::SilentCarrier,
+            Some(2) => AlphaThalStatus::Trait,
+            Some(1) => AlphaThalStatus::HbhDisease,
+            Some(_) => AlphaThalStatus::HbBarts,
+        }
+    }
+}
+
+/// The alpha-globin call of one sample.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct HbaCall {
+    /// The alpha-globin haplotypes, `None` for no-calls.
+    pub genotype: Option<String>,
+    /// Total copy number of `HBA1` and `HBA2`, `None` for no-calls.
+    pub total_cn: Option<i32>,
+    /// The derived alpha-thalassemia status.
+    pub status: AlphaThalStatus,
+    /// The filter value of the call, `PASS` for good calls.
+    pub filter: String,
+}
+
+/// Parser for the output of the alpha-globin copy number callers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Parser;
+
+impl CallerParser for Parser {
+    fn name(&self) -> &'static str {
+        "hba"
+    }
+
+    fn parse(&self, path: &str) -> Result<Vec<Call>, anyhow::Error> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(b'\t')
+            .from_path(path)
+            .map_err(|e| anyhow::anyhow!("could not open HBA caller output {}: {}", path, e))?;
+        let mut result = Vec::new();
+        for record in reader.deserialize() {
+            let record: CallerRecord = record.map_err(|e| {
+                anyhow::anyhow!("could not parse HBA caller output {}: {}", path, e)
+            })?;
+            let total_cn = if record.total_cn == "None" {
+                None
+            } else {
+                Some(record.total_cn.parse::<i32>().map_err(|e| {
+                    anyhow::anyhow!(
+                        "invalid total copy number {} in HBA caller output {}: {}",
+                        &record.total_cn,
+                        path,
+                        e
+                    )
+                })?)
+            };
+            let call = HbaCall {
+                genotype: (record.genotype != "None").then_some(record.genotype),
+                total_cn,
+                status: AlphaThalStatus::from_total_cn(total_cn),
+                filter: record.filter,
+            };
+            result.push(Call {
+                sample: record.sample,
+                summary: match call.genotype.as_ref() {
+                    Some(genotype) => format!("{} ({})", genotype, call.status),
+                    None => format!("no call ({})", &call.filter),
+                },
+                notable: !matches!(
+                    call.status,
+                    AlphaThalStatus::Normal | AlphaThalStatus::NoCall
+                ),
+                details: serde_json::to_value(&call)?,
+            });
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::seqvars::special_loci::CallerParser as _;
+
+    #[rstest::rstest]
+    #[case(None, super::AlphaThalStatus::NoCall)]
+    #[case(Some(5), super::AlphaThalStatus::Normal)]
+    #[case(Some(4), super::AlphaThalStatus::Normal)]
+    #[case(Some(3), super::AlphaThalStatus::SilentCarrier)]
+    #[case(Some(2), super::AlphaThalStatus::Trait)]
+    #[case(Some(1), super::AlphaThalStatus::HbhDisease)]
+    #[case(Some(0), super::AlphaThalStatus::HbBarts)]
+    fn status_from_total_cn(
+        #[case] total_cn: Option<i32>,
+        #[case] expected: super::AlphaThalStatus,
+    ) {
+        assert_eq!(super::AlphaThalStatus::from_total_cn(total_cn), expected);
+    }
+
+    #[test]
+    fn parse() -> Result<(), anyhow::Error> {
+        insta::assert_yaml_snapshot!(super::Parser.parse("tests/seqvars/special_loci/hba.tsv")?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+//! Implementation of `seqvars special-loci` subcommand.
+//!
+//! Some loci of clinical interest cannot be called by the regular small and structural
+//! variant pipelines, e.g., the copy number of `SMN1` for SMA carrier screening or the
+//! `FMR1` repeat expansion.  Dedicated callers exist for them and this command collects
+//! their outputs into one structured `special_loci.json` report that VarFish Server
+//! displays next to the regular variants of the case.
+//!
+//! Each caller output is read by a `CallerParser` registered in `parsers()`.  Support for
+//! further loci is added by implementing the trait in a new module and registering it.
+
+pub mod cyp2d6;
+pub mod fmr1;
+pub mod hba;
+pub mod smn;
+
+/// Command line arguments for `seqvars special-loci` subcommand.
+#[derive(Debug, clap::Parser)]
+#[command(author, version, about = "collect outputs of dedicated callers for special loci", long_about = None)]
+pub struct Args {
+    /// Caller output as `NAME=PATH`, may be given multiple times, e.g., once per sample.
+    ///
+    /// The supported names are `smn` (SMNCopyNumberCaller or DRAGEN SMN caller JSON),
+    /// `cyp2d6` (Cyrius TSV), `hba` (alpha-globin copy number TSV), and `fmr1`
+    /// (ExpansionHunter JSON).
+    #[clap(long)]
+    pub caller_output: Vec<String>,
+    /// Path to output JSON file.
+    #[clap(long)]
+    pub path_out: String,
+}
+
+/// The call of a special locus for one sample.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Call {
+    /// The sample name.
+    pub sample: String,
+    /// Short human-readable summary of the call, e.g., the genotype.
+    pub summary: String,
+    /// Whether the call is of clinical note, e.g., a carrier or premutation.
+    pub notable: bool,
+    /// The caller-specific details.
+    pub details: serde_json::Value,
+}
+
+/// Trait for the parsers of the outputs of dedicated callers.
+pub trait CallerParser {
+    /// Name of the special locus, used on the command line and as key in the report.
+    fn name(&self) -> &'static str;
+    /// Parse the caller output at `path` into per-sample calls.
+    fn parse(&self, path: &str) -> Result<Vec<Call>, anyhow::Error>;
+}
+
+/// Return all registered parsers.
+pub fn parsers() -> Vec<Box<dyn CallerParser>> {
+    vec![
+        Box::new(smn::Parser),
+        Box::new(cyp2d6::Parser),
+        Box::new(hba::Parser),
+        Box::new(fmr1::Parser),
+    ]
+}
+
+/// The special loci report of a case.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Report {
+    /// The calls by special locus name.
+    pub loci: indexmap::IndexMap<String, Vec<Call>>,
+}
+
+impl Report {
+    /// Parse `caller_output` given as `NAME=PATH` with the matching parser from `parsers`.
+    pub fn add_caller_output(
+        &mut self,
+        parsers: &[Box<dyn CallerParser>],
+        caller_output: &str,
+    ) -> Result<(), anyhow::Error> {
+        let (name, path) = caller_output.split_once('=').ok_or_else(|| {
+            anyhow::anyhow!(
+                "caller output {} is not of the form NAME=PATH",
+                caller_output
+            )
+        })?;
+        let parser = parsers
+            .iter()
+            .find(|parser| parser.name() == name)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "no parser for special locus {}, supported are: {}",
+                    name,
+                    parsers
+                        .iter()
+                        .map(|parser| parser.name())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?;
+        let calls = parser.parse(path)?;
+        tracing::info!("loaded {} {} calls from {}", calls.len(), name, path);
+        self.loci.entry(name.to_string()).or_default().extend(calls);
+        Ok(())
+    }
+}
+
+/// Main entry point for `seqvars special-loci` sub command.
+pub fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    let before_anything = std::time::Instant::now();
+    tracing::info!("args_common = {:#?}", &args_common);
+    tracing::info!("args = {:#?}", &args);
+
+    let parsers = parsers();
+    let mut report = Report::default();
+    for caller_output in &args.caller_output {
+        report.add_caller_output(&parsers, caller_output)?;
+    }
+    if report.loci.is_empty() {
+        tracing::warn!(
+            "{}: no caller outputs given, writing empty report",
+            crate::codes::SPECIAL_LOCI_NO_CALLER_OUTPUTS
+        );
+    }
+
+    serde_json::to_writer_pretty(
+        crate::common::io::std::open_write_maybe_bgzf(
+            &args.path_out,
+            args_common.compression_level,
+        )?,
+        &report,
+    )
+    .map_err(|e| anyhow::anyhow!("could not write special loci report: {}", e))?;
+
+    tracing::info!(
+        "All of `seqvars special-loci` completed in {:?}",
+        before_anything.elapsed()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    #[rstest::rstest]
+    #[case("smn")]
+    #[case("smn=")]
+    #[case("gba=tests/seqvars/special_loci/smn.json")]
+    #[case("hba=tests/seqvars/special_loci/smn.json")]
+    fn add_caller_output_invalid(#[case] caller_output: &str) {
+        let mut report = super::Report::default();
+        assert!(report
+            .add_caller_output(&super::parsers(), caller_output)
+            .is_err());
+    }
+
+    #[test]
+    fn smoke_test() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_out = tmpdir.join("special_loci.json");
+
+        let args = super::Args {
+            caller_output: vec![
+                "smn=tests/seqvars/special_loci/smn.json".into(),
+                "cyp2d6=tests/seqvars/special_loci/cyp2d6.tsv".into(),
+                "fmr1=tests/seqvars/special_loci/fmr1.json".into(),
+            ],
+            path_out: path_out.to_str().unwrap().into(),
+        };
+        super::run(&crate::common::Args::default(), &args)?;
+
+        insta::assert_snapshot!(std::fs::read_to_string(&path_out)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+//! Parsing of the output of the SMN1/SMN2 copy number callers.
+//!
+//! The JSON output of Illumina's SMNCopyNumberCaller and of the DRAGEN SMN caller maps
+//! the sample names to the call records.  Only the fields relevant for SMA diagnostics and
+//! carrier screening are kept.
+
+use super::{Call, CallerParser};
+
+/// One call record from the caller output.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+struct CallerRecord {
+    #[serde(rename = "SMN1", alias = "SMN1_CN")]
+    smn1: Option<i32>,
+    #[serde(rename = "SMN2", alias = "SMN2_CN")]
+    smn2: Option<i32>,
+    #[serde(rename = "SMN2delta7-8", alias = "SMN2delta7-8_CN")]
+    smn2_delta7_8: Option<i32>,
+    #[serde(rename = "Total_CN_raw")]
+    total_cn_raw: Option<f64>,
+    #[serde(rename = "Full_length_CN_raw")]
+    full_length_cn_raw: Option<f64>,
+    #[serde(rename = "g.27134T>G_CN")]
+    g27134t_g: Option<i32>,
+    #[serde(rename = "isSMA")]
+    is_sma: Option<bool>,
+    #[serde(rename = "isCarrier")]
+    is_carrier: Option<bool>,
+}
+
+/// The SMA status derived from the SMN1 copy number.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, strum::Display,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SmaStatus {
+    /// No copy of SMN1, consistent with SMA.
+    Affected,
+    /// One copy of SMN1, SMA carrier.
+    Carrier,
+    /// Two copies of SMN1 on one chromosome may hide a carrier ("2+0"), indicated by
+    /// the `g.27134T>G` variant.
+    SilentCarrierRisk,
+    /// Two or more copies of SMN1 without indication of a silent carrier.
+    NotCarrier,
+    /// The caller made no call.
+    NoCall,
+}
+
+/// The SMN1/SMN2 call of one sample.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SmnCall {
+    /// The sample name.
+    pub sample: String,
+    /// The derived SMA status.
+    pub status: SmaStatus,
+    /// Copy number of SMN1.
+    pub smn1_cn: Option<i32>,
+    /// Copy number of SMN2.
+    pub smn2_cn: Option<i32>,
+    /// Copy number of SMN2 with deletion of exons 7 and 8.
+    pub smn2_delta7_8_cn: Option<i32>,
+    /// Raw total copy number of SMN1 and SMN2.
+    pub total_cn_raw: Option<f64>,
+    /// Raw copy number of full-length SMN1 and SMN2.
+    pub full_length_cn_raw: Option<f64>,
+    /// Copy number of the `g.27134T>G` variant linked to "2+0" silent carriers.
+    pub g27134t_g_cn: Option<i32>,
+}
+
+impl SmnCall {
+    /// Construct from the caller record of `sample`.
+    fn from_caller(sample: &str, record: CallerRecord) -> Self {
+        let status = if record.is_sma == Some(true) || record.smn1 == Some(0) {
+            SmaStatus::Affected
+        } else if record.is_carrier == Some(true) || record.smn1 == Some(1) {
+            SmaStatus::Carrier
+        } else if record.smn1 == Some(2) && record.g27134t_g.unwrap_or_default() > 0 {
+            SmaStatus::SilentCarrierRisk
+        } else if record.smn1.is_some() {
+            SmaStatus::NotCarrier
+        } else {
+            SmaStatus::NoCall
+        };
+        Self {
+            sample: sample.to_string(),
+            status,
+            smn1_cn: record.smn1,
+            smn2_cn: record.smn2,
+            smn2_delta7_8_cn: record.smn2_delta7_8,
+            total_cn_raw: record.total_cn_raw,
+            full_length_cn_raw: record.full_length_cn_raw,
+            g27134t_g_cn: record.g27134t_g,
+        }
+    }
+}
+
+/// Load the SMN calls from the JSON file at `path`.
+pub fn load_calls(path: &str) -> Result<Vec<SmnCall>, anyhow::Error> {
+    let reader = std::fs::File::open(path)
+        .map_err(|e| anyhow::anyhow!("could not open SMN caller output {}: {}", path, e))?;
+    let records: indexmap::IndexMap<String, CallerRecord> = serde_json::from_reader(reader)
+        .map_err(|e| anyhow::anyhow!("could not parse SMN caller output {}: {}", path, e))?;
+    Ok(records
+        .into_iter()
+        .map(|(sample, record)| SmnCall::from_caller(&sample, record))
+        .collect())
+}
+
+/// Parser for the output of SMNCopyNumberCaller and the DRAGEN SMN caller.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Parser;
+
+impl CallerParser for Parser {
+    fn name(&self) -> &'static str {
+        "smn"
+    }
+
+    fn parse(&self, path: &str) -> Result<Vec<Call>, anyhow::Error> {
+        let display_cn = |cn: Option<i32>| cn.map(|cn| cn.to_string()).unwrap_or(".".into());
+        load_calls(path)?
+            .into_iter()
+            .map(|call| {
+                Ok(Call {
+                    sample: call.sample.clone(),
+                    summary: format!(
+                        "SMN1:{} SMN2:{} ({})",
+                        display_cn(call.smn1_cn),
+                        display_cn(call.smn2_cn),
+                        call.status
+                    ),
+                    notable: matches!(
+                        call.status,
+                        SmaStatus::Affected | SmaStatus::Carrier | SmaStatus::SilentCarrierRisk
+                    ),
+                    details: serde_json::to_value(&call)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CallerRecord, SmaStatus, SmnCall};
+
+    #[rstest::rstest]
+    #[case(Some(0), None, None, SmaStatus::Affected)]
+    #[case(Some(1), None, None, SmaStatus::Carrier)]
+    #[case(None, Some(true), None, SmaStatus::Carrier)]
+    #[case(Some(2), None, Some(0), SmaStatus::NotCarrier)]
+    #[case(Some(2), None, Some(1), SmaStatus::SilentCarrierRisk)]
+    #[case(Some(3), None, Some(1), SmaStatus::NotCarrier)]
+    #[case(None, None, None, SmaStatus::NoCall)]
+    fn status(
+        #[case] smn1: Option<i32>,
+        #[case] is_carrier: Option<bool>,
+        #[case] g27134t_g: Option<i32>,
+        #[case] expected: SmaStatus,
+    ) {
+        let record = CallerRecord {
+            smn1,
+            is_carrier,
+            g27134t_g,
+            ..Default::default()
+        };
+        assert_eq!(SmnCall::from_caller("sample", record).status, expected);
+    }
+
+    #[test]
+    fn load_calls() -> Result<(), anyhow::Error> {
+        insta::assert_yaml_snapshot!(super::load_calls("tests/seqvars/special_loci/smn.json")?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,7 @@
+//! Long-running server modes of the worker.
+//!
+//! The servers load the databases once and answer requests without the startup cost of
+//! one process per query.
+
+pub mod rest;
+pub mod run;
@@ -0,0 +1,601 @@
+//! Implementation of the `server rest` subcommand, the HTTP server with a JSON API for
+//! seqvars and strucvars queries.
+//!
+//! The endpoints `POST /seqvars/query` and `POST /svs/query` accept the query settings
+//! as also read from `--path-query-json` together with the path to the ingested VCF file
+//! and return one page of the result records as JSON.
+//!
+//! The server listens right away while the databases are loaded in the background.  For
+//! container orchestration, `GET /healthz` reports that the process is alive and
+//! `GET /readyz` reports whether the databases have been loaded; queries are answered
+//! with `503 Service Unavailable` until then.
+
+use actix_web::{error, get, post, web, App, HttpResponse, HttpServer};
+use rand_core::SeedableRng as _;
+
+use crate::{
+    common::{GenomeRelease, OutputFormat},
+    seqvars, strucvars,
+};
+
+/// Command line arguments for `server rest` subcommand.
+#[derive(clap::Parser, Debug, Clone)]
+#[command(author, version, about = "Run HTTP server with JSON API for queries", long_about = None)]
+pub struct Args {
+    /// Genome release to assume.
+    #[arg(long, value_enum)]
+    pub genome_release: GenomeRelease,
+    /// Path to worker database to use for querying.
+    #[arg(long, required = true)]
+    pub path_db: String,
+    /// Address to listen on.
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    pub listen: std::net::SocketAddr,
+
+    /// Radius around BND sites used when building the database.
+    #[arg(long, default_value_t = 50)]
+    pub slack_bnd: i32,
+    /// Radius around INS sites used when building the database.
+    #[arg(long, default_value_t = 50)]
+    pub slack_ins: i32,
+    /// Minimal reciprocal overlap for SVs of the same type, used when building
+    /// the database.
+    #[arg(long, default_value_t = 0.8)]
+    pub min_overlap: f32,
+    /// Maximal distance to TAD to consider.
+    #[arg(long, default_value_t = 10_000)]
+    pub max_tad_distance: i32,
+    /// Path to the mehari transcript database for gene overlaps; defaults to the one in
+    /// `path_db`.
+    #[arg(long)]
+    pub path_mehari_tx_db: Option<String>,
+    /// Directory to cache the transcript interval trees in, as for `strucvars query`.
+    #[arg(long)]
+    pub path_tx_trees_cache: Option<String>,
+    /// Optional path to a TSV file with protein domains per transcript for seqvars
+    /// queries.
+    #[arg(long)]
+    pub path_protein_domains: Option<String>,
+}
+
+/// Number of records per page if not given in the request.
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Return the first page, the default of `QueryRequest::page`.
+fn default_page() -> usize {
+    1
+}
+
+/// Return `DEFAULT_PAGE_SIZE`, the default of `QueryRequest::page_size`.
+fn default_page_size() -> usize {
+    DEFAULT_PAGE_SIZE
+}
+
+/// Body of the query requests.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct QueryRequest<Q> {
+    /// The query, same as the content of the `--path-query-json` file.
+    pub query: Q,
+    /// Path to the ingested VCF file to query.
+    pub path_input: String,
+    /// The 1-based number of the page to return.
+    #[serde(default = "default_page")]
+    pub page: usize,
+    /// The number of records per page.
+    #[serde(default = "default_page_size")]
+    pub page_size: usize,
+    /// Optional maximal number of total records to write out.
+    #[serde(default)]
+    pub max_results: Option<usize>,
+    /// Optional seed for RNG.
+    #[serde(default)]
+    pub rng_seed: Option<u64>,
+}
+
+impl<Q> QueryRequest<Q> {
+    /// Check the pagination parameters.
+    fn check_page(&self) -> Result<(), actix_web::Error> {
+        if self.page == 0 || self.page_size == 0 {
+            Err(error::ErrorBadRequest(
+                "page and page_size must be greater than zero",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Initialize the random number generator from the seed if given or local entropy
+    /// source.
+    fn rng(&self) -> rand::rngs::StdRng {
+        if let Some(rng_seed) = self.rng_seed {
+            rand::rngs::StdRng::seed_from_u64(rng_seed)
+        } else {
+            rand::rngs::StdRng::from_entropy()
+        }
+    }
+}
+
+/// Body of the query responses.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct QueryResponse {
+    /// The 1-based number of the page.
+    pub page: usize,
+    /// The number of records per page.
+    pub page_size: usize,
+    /// The number of result records on all pages.
+    pub count_total: usize,
+    /// The result records of the page, with the payload as JSON object.
+    pub records: Vec<serde_json::Value>,
+}
+
+/// Collect the page with the 1-based number `page` of `page_size` from `records`.
+fn paginate<I>(records: I, page: usize, page_size: usize) -> Result<QueryResponse, anyhow::Error>
+where
+    I: Iterator<Item = Result<serde_json::Value, anyhow::Error>>,
+{
+    let first = (page - 1) * page_size;
+    let mut count_total = 0;
+    let mut page_records = Vec::new();
+    for record in records {
+        let record = record?;
+        if count_total >= first && page_records.len() < page_size {
+            page_records.push(record);
+        }
+        count_total += 1;
+    }
+    Ok(QueryResponse {
+        page,
+        page_size,
+        count_total,
+        records: page_records,
+    })
+}
+
+/// The state shared by all requests.
+struct State {
+    /// The genome release of the databases.
+    genome_release: GenomeRelease,
+    /// The path to the worker database.
+    path_db: String,
+    /// Radius around BND sites.
+    slack_bnd: i32,
+    /// Radius around INS sites.
+    slack_ins: i32,
+    /// Minimal reciprocal overlap for SVs of the same type.
+    min_overlap: f32,
+    /// Maximal distance to TAD to consider.
+    max_tad_distance: i32,
+    /// The databases, set once loaded.
+    databases: std::sync::OnceLock<Databases>,
+}
+
+impl State {
+    /// Return the databases or a `503 Service Unavailable` error while they are loading.
+    fn databases(&self) -> actix_web::Result<&Databases> {
+        self.databases
+            .get()
+            .ok_or_else(|| error::ErrorServiceUnavailable("databases are still loading"))
+    }
+}
+
+/// The databases for the queries.
+struct Databases {
+    /// The databases for seqvars queries.
+    seqvars_dbs: seqvars::query::QueryDbs,
+    /// The databases for strucvars queries.
+    strucvars_dbs: strucvars::query::QueryDbs,
+}
+
+impl Databases {
+    /// Load the databases given the command line arguments.
+    fn load(args: &Args) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            seqvars_dbs: seqvars::query::load_query_dbs(
+                &args.path_db,
+                args.genome_release,
+                args.max_tad_distance,
+                args.path_protein_domains.as_deref(),
+            )?,
+            strucvars_dbs: strucvars::query::load_query_dbs(
+                &args.path_db,
+                args.genome_release,
+                args.max_tad_distance,
+                args.path_mehari_tx_db.as_deref(),
+                args.path_tx_trees_cache.as_deref(),
+            )?,
+        })
+    }
+}
+
+/// Convert an error from running a query into an internal server error.
+fn query_failed(e: anyhow::Error) -> actix_web::Error {
+    tracing::error!("query failed: {}", e);
+    error::ErrorInternalServerError(format!("query failed: {}", e))
+}
+
+/// Report that the process is alive.
+#[get("/healthz")]
+async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().body("ok")
+}
+
+/// Report whether the databases have been loaded and queries can be answered.
+#[get("/readyz")]
+async fn readyz(state: web::Data<State>) -> actix_web::Result<HttpResponse> {
+    state.databases()?;
+    Ok(HttpResponse::Ok().body("ok"))
+}
+
+/// Run seqvars query and return the page of the result records.
+#[post("/seqvars/query")]
+async fn seqvars_query(
+    state: web::Data<State>,
+    request: web::Json<QueryRequest<serde_json::Value>>,
+) -> actix_web::Result<web::Json<QueryResponse>> {
+    let request = request.into_inner();
+    request.check_page()?;
+    let databases = state.databases()?;
+    let mut rng = request.rng();
+    let tmp_dir = tempfile::TempDir::new().map_err(|e| query_failed(e.into()))?;
+    let args = seqvars::query::Args {
+        genome_release: state.genome_release,
+        result_set_id: None,
+        case_uuid_id: None,
+        path_db: state.path_db.clone(),
+        path_query_json: String::new(),
+        path_input: request.path_input.clone(),
+        path_output: tmp_dir.path().join("out.tsv").to_string_lossy().to_string(),
+        output_format: OutputFormat::Tsv,
+        max_results: request.max_results,
+        path_output_info: None,
+        path_gene_summary: None,
+        explain: Vec::new(),
+        explain_all: false,
+        path_output_explain: None,
+        soft_filter: false,
+        filter_impact: false,
+        mode: seqvars::query::QueryMode::Default,
+        denovo_index: None,
+        denovo_min_dp: 10,
+        denovo_min_gq: 20.0,
+        path_denovo_candidates: None,
+        path_karyotypes: None,
+        path_case_rocksdb: None,
+        path_inhouse_rocksdb: None,
+        inhouse_cf_counts: "counts".into(),
+        rng_seed: request.rng_seed,
+        max_tad_distance: state.max_tad_distance,
+        with_cytobands: true,
+        path_reference: None,
+        path_protein_domains: None,
+        hpo_terms: Vec::new(),
+        path_phenopacket: None,
+    };
+
+    tracing::info!("running seqvars query on {}...", &args.path_input);
+    let query = seqvars::query::presets::resolve(request.query, &args.path_input)
+        .await
+        .map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+    let interpreter = seqvars::query::build_interpreter(
+        query,
+        &args.path_input,
+        state.genome_release,
+        &databases.seqvars_dbs,
+    )
+    .await
+    .map_err(query_failed)?;
+    seqvars::query::run_query(
+        &interpreter,
+        &args,
+        &databases.seqvars_dbs.annotator,
+        Some(&databases.seqvars_dbs.in_memory_dbs.cytobands),
+        &databases.seqvars_dbs.gene_hpo_sets,
+        &mut rng,
+    )
+    .await
+    .map_err(query_failed)?;
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(b'\t')
+        .quoting(false)
+        .from_path(&args.path_output)
+        .map_err(|e| query_failed(e.into()))?;
+    let records = reader
+        .deserialize::<seqvars::query::output::Record>()
+        .map(|record| {
+            let record = record.map_err(|e| anyhow::anyhow!("could not read record: {}", e))?;
+            let mut value = serde_json::to_value(&record)?;
+            value["payload"] = serde_json::from_str(&record.payload)
+                .map_err(|e| anyhow::anyhow!("could not parse payload: {}", e))?;
+            Ok(value)
+        });
+    Ok(web::Json(
+        paginate(records, request.page, request.page_size).map_err(query_failed)?,
+    ))
+}
+
+/// Run strucvars query and return the page of the result records.
+#[post("/svs/query")]
+async fn svs_query(
+    state: web::Data<State>,
+    request: web::Json<QueryRequest<serde_json::Value>>,
+) -> actix_web::Result<web::Json<QueryResponse>> {
+    let request = request.into_inner();
+    request.check_page()?;
+    let databases = state.databases()?;
+    let mut rng = request.rng();
+    let tmp_dir = tempfile::TempDir::new().map_err(|e| query_failed(e.into()))?;
+    let args = strucvars::query::Args {
+        genome_release: state.genome_release,
+        path_db: state.path_db.clone(),
+        path_query_json: String::new(),
+        path_input: request.path_input.clone(),
+        path_output: tmp_dir
+            .path()
+            .join("out.jsonl")
+            .to_string_lossy()
+            .to_string(),
+        output_format: OutputFormat::Jsonl,
+        max_results: request.max_results,
+        slack_bnd: state.slack_bnd,
+        slack_ins: state.slack_ins,
+        min_overlap: state.min_overlap,
+        max_tad_distance: state.max_tad_distance,
+        rng_seed: request.rng_seed,
+        path_mehari_tx_db: None,
+        path_tx_trees_cache: None,
+        bg_db_details: false,
+        path_summary: None,
+        path_karyotypes: None,
+    };
+
+    tracing::info!("running strucvars query on {}...", &args.path_input);
+    let query = strucvars::query::presets::resolve(request.query)
+        .map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+    let interpreter = strucvars::query::build_interpreter(query, &databases.strucvars_dbs.dbs);
+    strucvars::query::run_query(&interpreter, &args, &databases.strucvars_dbs, &mut rng)
+        .await
+        .map_err(query_failed)?;
+
+    let content = std::fs::read_to_string(&args.path_output).map_err(|e| query_failed(e.into()))?;
+    let records = content.lines().map(|line| {
+        serde_json::from_str(line).map_err(|e| anyhow::anyhow!("could not parse record: {}", e))
+    });
+    Ok(web::Json(
+        paginate(records, request.page, request.page_size).map_err(query_failed)?,
+    ))
+}
+
+/// Main entry point for `server rest` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::info!("args_common = {:?}", &args_common);
+    tracing::info!("args = {:?}", &args);
+
+    let state = web::Data::new(State {
+        genome_release: args.genome_release,
+        path_db: args.path_db.clone(),
+        slack_bnd: args.slack_bnd,
+        slack_ins: args.slack_ins,
+        min_overlap: args.min_overlap,
+        max_tad_distance: args.max_tad_distance,
+        databases: Default::default(),
+    });
+
+    tracing::info!("listening on {}...", &args.listen);
+    let server = {
+        let state = state.clone();
+        HttpServer::new(move || {
+            App::new()
+                .app_data(state.clone())
+                .service(healthz)
+                .service(readyz)
+                .service(seqvars_query)
+                .service(svs_query)
+                .wrap(actix_web::middleware::Logger::default())
+        })
+        .bind(args.listen)
+        .map_err(|e| anyhow::anyhow!("could not bind to {}: {}", &args.listen, e))?
+        .run()
+    };
+    let server_handle = server.handle();
+
+    // Load the databases while the server already answers health checks; stop the server
+    // if loading fails so the orchestration restarts the container.
+    let load = async {
+        let args = args.clone();
+        let databases = match tokio::task::spawn_blocking(move || Databases::load(&args)).await {
+            Ok(loaded) => loaded,
+            Err(e) => Err(anyhow::anyhow!("loading databases failed: {}", e)),
+        };
+        match databases {
+            Ok(databases) => {
+                if state.databases.set(databases).is_err() {
+                    unreachable!("databases are only loaded once");
+                }
+                tracing::info!("... databases loaded, ready for queries");
+                Ok(())
+            }
+            Err(e) => {
+                server_handle.stop(false).await;
+                Err(e)
+            }
+        }
+    };
+
+    let (res_server, res_load) = tokio::join!(server, load);
+    res_load?;
+    res_server.map_err(|e| anyhow::anyhow!("HTTP server failed: {}", e))
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::{test as actix_test, web, App};
+
+    use super::{Databases, QueryResponse, State};
+    use crate::common::GenomeRelease;
+
+    /// Return the state before the databases have been loaded.
+    fn unloaded_state() -> web::Data<State> {
+        web::Data::new(State {
+            genome_release: GenomeRelease::Grch37,
+            path_db: "tests/seqvars/query/db".into(),
+            slack_bnd: 50,
+            slack_ins: 50,
+            min_overlap: 0.8,
+            max_tad_distance: 10_000,
+            databases: Default::default(),
+        })
+    }
+
+    fn state() -> Result<web::Data<State>, anyhow::Error> {
+        let state = unloaded_state();
+        let databases = Databases {
+            seqvars_dbs: crate::seqvars::query::load_query_dbs(
+                "tests/seqvars/query/db",
+                GenomeRelease::Grch37,
+                10_000,
+                None,
+            )?,
+            strucvars_dbs: crate::strucvars::query::load_query_dbs(
+                "tests/strucvars/query/db",
+                GenomeRelease::Grch37,
+                10_000,
+                None,
+                None,
+            )?,
+        };
+        if state.databases.set(databases).is_err() {
+            anyhow::bail!("databases already loaded");
+        }
+        Ok(state)
+    }
+
+    #[actix_web::test]
+    async fn health_and_readiness() -> Result<(), anyhow::Error> {
+        use actix_web::http::StatusCode;
+
+        for (state, expected_ready) in [
+            (unloaded_state(), StatusCode::SERVICE_UNAVAILABLE),
+            (state()?, StatusCode::OK),
+        ] {
+            let app = actix_test::init_service(
+                App::new()
+                    .app_data(state)
+                    .service(super::healthz)
+                    .service(super::readyz)
+                    .service(super::seqvars_query),
+            )
+            .await;
+
+            let request = actix_test::TestRequest::get().uri("/healthz").to_request();
+            let response = actix_test::call_service(&app, request).await;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let request = actix_test::TestRequest::get().uri("/readyz").to_request();
+            let response = actix_test::call_service(&app, request).await;
+            assert_eq!(response.status(), expected_ready);
+        }
+
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(unloaded_state())
+                .service(super::seqvars_query),
+        )
+        .await;
+        let request = actix_test::TestRequest::post()
+            .uri("/seqvars/query")
+            .set_json(serde_json::json!({
+                "query": {},
+                "path_input": "tests/seqvars/query/Case_1.ingested.vcf",
+            }))
+            .to_request();
+        let response = actix_test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn paginate() -> Result<(), anyhow::Error> {
+        let records = || (0..5).map(|i| Ok(serde_json::json!(i)));
+
+        let response = super::paginate(records(), 2, 2)?;
+        assert_eq!(response.count_total, 5);
+        assert_eq!(
+            response.records,
+            vec![serde_json::json!(2), serde_json::json!(3)]
+        );
+        let response = super::paginate(records(), 3, 2)?;
+        assert_eq!(response.records, vec![serde_json::json!(4)]);
+        let response = super::paginate(records(), 4, 2)?;
+        assert!(response.records.is_empty());
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn seqvars_query() -> Result<(), anyhow::Error> {
+        let app =
+            actix_test::init_service(App::new().app_data(state()?).service(super::seqvars_query))
+                .await;
+
+        let query: serde_json::Value = serde_json::from_reader(std::fs::File::open(
+            "tests/seqvars/query/Case_1.query.json",
+        )?)?;
+        let request = actix_test::TestRequest::post()
+            .uri("/seqvars/query")
+            .set_json(serde_json::json!({
+                "query": query,
+                "path_input": "tests/seqvars/query/Case_1.ingested.vcf",
+                "page_size": 1,
+                "rng_seed": 42,
+            }))
+            .to_request();
+        let response: QueryResponse = actix_test::call_and_read_body_json(&app, request).await;
+        assert_eq!(response.page, 1);
+        assert!(response.records.len() <= 1);
+        assert_eq!(response.records.len(), response.count_total.min(1));
+        if let Some(record) = response.records.first() {
+            assert!(record["payload"].is_object());
+        }
+
+        let request = actix_test::TestRequest::post()
+            .uri("/seqvars/query")
+            .set_json(serde_json::json!({
+                "query": query,
+                "path_input": "tests/seqvars/query/Case_1.ingested.vcf",
+                "page": 0,
+            }))
+            .to_request();
+        let response = actix_test::call_service(&app, request).await;
+        assert_eq!(response.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn svs_query() -> Result<(), anyhow::Error> {
+        let app =
+            actix_test::init_service(App::new().app_data(state()?).service(super::svs_query)).await;
+
+        let query: serde_json::Value = serde_json::from_reader(std::fs::File::open(
+            "tests/strucvars/query/Case_3.query.json",
+        )?)?;
+        let request = actix_test::TestRequest::post()
+            .uri("/svs/query")
+            .set_json(serde_json::json!({
+                "query": query,
+                "path_input": "tests/strucvars/query/Case_3.ingested.vcf",
+                "page_size": 2,
+                "rng_seed": 42,
+            }))
+            .to_request();
+        let response: QueryResponse = actix_test::call_and_read_body_json(&app, request).await;
+        assert!(response.count_total > 0);
+        assert_eq!(response.records.len(), response.count_total.min(2));
+        assert!(response.records[0]["payload"].is_object());
+
+        Ok(())
+    }
+}
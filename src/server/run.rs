@@ -0,0 +1,361 @@
+//! Implementation of the `server run` subcommand, the gRPC server for structural variant
+//! queries.
+
+use std::sync::Arc;
+
+use clap::Parser;
+use thousands::Separable as _;
+
+use crate::{
+    common::{build_chrom_map, GenomeRelease},
+    strucvars::query::{
+        self, build_interpreter, load_query_dbs,
+        schema::{CaseQuery, StructuralVariant, SvSubType, SvType},
+        OutputFormat, QueryDbs,
+    },
+};
+
+/// Generated code for the gRPC service.
+pub mod pbs {
+    tonic::include_proto!("varfish.v1.server");
+}
+
+use pbs::strucvars_service_server::{StrucvarsService, StrucvarsServiceServer};
+
+/// Command line arguments for `server run` subcommand.
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Run gRPC server for strucvars queries", long_about = None)]
+pub struct Args {
+    /// Genome release to assume.
+    #[arg(long, value_enum)]
+    pub genome_release: GenomeRelease,
+    /// Path to worker database to use for querying.
+    #[arg(long, required = true)]
+    pub path_db: String,
+    /// Address to listen on.
+    #[arg(long, default_value = "127.0.0.1:50051")]
+    pub listen: std::net::SocketAddr,
+
+    /// Radius around BND sites used when building the database.
+    #[arg(long, default_value_t = 50)]
+    pub slack_bnd: i32,
+    /// Radius around INS sites used when building the database.
+    #[arg(long, default_value_t = 50)]
+    pub slack_ins: i32,
+    /// Minimal reciprocal overlap for SVs of the same type, used when building
+    /// the database.
+    #[arg(long, default_value_t = 0.8)]
+    pub min_overlap: f32,
+    /// Maximal distance to TAD to consider.
+    #[arg(long, default_value_t = 10_000)]
+    pub max_tad_distance: i32,
+    /// Path to the mehari transcript database for gene overlaps; defaults to the one in
+    /// `path_db`.
+    #[arg(long)]
+    pub path_mehari_tx_db: Option<String>,
+    /// Directory to cache the transcript interval trees in, as for `strucvars query`.
+    #[arg(long)]
+    pub path_tx_trees_cache: Option<String>,
+}
+
+/// The state shared by all requests.
+struct State {
+    /// The databases.
+    query_dbs: QueryDbs,
+    /// Mapping from chromosome name to index for the interval trees.
+    chrom_map: indexmap::IndexMap<String, usize>,
+    /// The genome release of the databases.
+    genome_release: GenomeRelease,
+    /// The path to the worker database.
+    path_db: String,
+    /// Radius around BND sites.
+    slack_bnd: i32,
+    /// Radius around INS sites.
+    slack_ins: i32,
+    /// Minimal reciprocal overlap for SVs of the same type.
+    min_overlap: f32,
+    /// Maximal distance to TAD to consider.
+    max_tad_distance: i32,
+}
+
+/// Parse the query JSON from a request, the defaults if empty.
+fn parse_query(query_json: &str) -> Result<CaseQuery, tonic::Status> {
+    if query_json.is_empty() {
+        Ok(CaseQuery::default())
+    } else {
+        serde_json::from_str(query_json)
+            .map_err(|e| anyhow::anyhow!("invalid query JSON: {}", e))
+            .and_then(crate::strucvars::query::presets::resolve)
+            .map_err(|e| tonic::Status::invalid_argument(e.to_string()))
+    }
+}
+
+/// Convert a structural variant from a request.
+fn convert_sv(sv: pbs::StructuralVariant) -> Result<StructuralVariant, tonic::Status> {
+    let sv_type: SvType = sv
+        .sv_type
+        .parse()
+        .map_err(|e| tonic::Status::invalid_argument(format!("{}", e)))?;
+    let sv_sub_type = if sv.sv_sub_type.is_empty() {
+        &sv.sv_type
+    } else {
+        &sv.sv_sub_type
+    };
+    let sv_sub_type: SvSubType =
+        serde_json::from_value(serde_json::Value::String(sv_sub_type.clone())).map_err(|e| {
+            tonic::Status::invalid_argument(format!("invalid SV sub type {}: {}", sv_sub_type, e))
+        })?;
+    Ok(StructuralVariant {
+        chrom: sv.chrom,
+        pos: sv.start,
+        sv_type,
+        sv_sub_type,
+        chrom2: if sv.chrom2.is_empty() {
+            None
+        } else {
+            Some(sv.chrom2)
+        },
+        end: sv.stop,
+        strand_orientation: Default::default(),
+        callers: Vec::new(),
+        call_info: Default::default(),
+    })
+}
+
+/// Implementation of the gRPC service.
+struct Service {
+    /// The shared state.
+    state: Arc<State>,
+}
+
+#[tonic::async_trait]
+impl StrucvarsService for Service {
+    async fn count_overlaps(
+        &self,
+        request: tonic::Request<pbs::CountOverlapsRequest>,
+    ) -> Result<tonic::Response<pbs::CountOverlapsResponse>, tonic::Status> {
+        let request = request.into_inner();
+        let case_query = parse_query(&request.query_json)?;
+        let counts = request
+            .svs
+            .into_iter()
+            .map(|sv| {
+                let sv = convert_sv(sv)?;
+                let overlaps = self.state.query_dbs.dbs.bg_dbs.count_overlaps(
+                    &sv,
+                    &case_query,
+                    &self.state.chrom_map,
+                    self.state.slack_ins,
+                    self.state.slack_bnd,
+                );
+                Ok(pbs::OverlapCounts {
+                    dbvar: overlaps.dbvar,
+                    dgv: overlaps.dgv,
+                    dgv_gs: overlaps.dgv_gs,
+                    g1k: overlaps.g1k,
+                    gnomad_genomes: overlaps.gnomad_genomes,
+                    gnomad_exomes: overlaps.gnomad_exomes,
+                    inhouse: overlaps.inhouse,
+                })
+            })
+            .collect::<Result<Vec<_>, tonic::Status>>()?;
+        Ok(tonic::Response::new(pbs::CountOverlapsResponse { counts }))
+    }
+
+    async fn query(
+        &self,
+        request: tonic::Request<pbs::QueryRequest>,
+    ) -> Result<tonic::Response<pbs::QueryResponse>, tonic::Status> {
+        let request = request.into_inner();
+        let case_query = parse_query(&request.query_json)?;
+        let output_format = if request.output_format.is_empty() {
+            OutputFormat::default()
+        } else {
+            <OutputFormat as clap::ValueEnum>::from_str(&request.output_format, true)
+                .map_err(tonic::Status::invalid_argument)?
+        };
+        let state = self.state.clone();
+        let args = query::Args {
+            genome_release: state.genome_release,
+            path_db: state.path_db.clone(),
+            path_query_json: String::new(),
+            path_input: request.path_input,
+            path_output: request.path_output,
+            output_format,
+            max_results: request.max_results.map(|max_results| max_results as usize),
+            slack_bnd: state.slack_bnd,
+            slack_ins: state.slack_ins,
+            min_overlap: state.min_overlap,
+            max_tad_distance: state.max_tad_distance,
+            rng_seed: request.rng_seed,
+            path_mehari_tx_db: None,
+            path_tx_trees_cache: None,
+            bg_db_details: false,
+            path_summary: None,
+            path_karyotypes: None,
+        };
+
+        // The VCF reader is not `Send`, so the query runs on a blocking thread.
+        let stats = tokio::task::spawn_blocking(move || {
+            tokio::runtime::Handle::current().block_on(run_query(&state, case_query, &args))
+        })
+        .await
+        .map_err(|e| tonic::Status::internal(format!("query task failed: {}", e)))?
+        .map_err(|e| tonic::Status::internal(format!("query failed: {}", e)))?;
+
+        Ok(tonic::Response::new(pbs::QueryResponse {
+            count_total: stats.count_total as u64,
+            count_passed: stats.count_passed as u64,
+        }))
+    }
+}
+
+/// Run `case_query` with `args` on the databases in `state`.
+async fn run_query(
+    state: &State,
+    case_query: CaseQuery,
+    args: &query::Args,
+) -> Result<query::QueryStats, anyhow::Error> {
+    use rand_core::SeedableRng as _;
+
+    tracing::info!("running query on {}...", &args.path_input);
+    let before_query = std::time::Instant::now();
+    let mut rng = if let Some(rng_seed) = args.rng_seed {
+        rand::rngs::StdRng::seed_from_u64(rng_seed)
+    } else {
+        rand::rngs::StdRng::from_entropy()
+    };
+    let interpreter = build_interpreter(case_query, &state.query_dbs.dbs);
+    let partial_guard = crate::common::partial::PartialOutputGuard::new(
+        &args.path_output,
+        crate::common::partial::OnFailure::Remove,
+    );
+    let stats = query::run_query(&interpreter, args, &state.query_dbs, &mut rng).await?;
+    partial_guard.finish();
+    tracing::info!(
+        "... {} records passed out of {} in {:?}",
+        stats.count_passed.separate_with_commas(),
+        stats.count_total.separate_with_commas(),
+        before_query.elapsed()
+    );
+    Ok(stats)
+}
+
+/// Main entry point for `server run` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    tracing::info!("args_common = {:?}", &args_common);
+    tracing::info!("args = {:?}", &args);
+
+    let state = Arc::new(State {
+        query_dbs: load_query_dbs(
+            &args.path_db,
+            args.genome_release,
+            args.max_tad_distance,
+            args.path_mehari_tx_db.as_deref(),
+            args.path_tx_trees_cache.as_deref(),
+        )?,
+        chrom_map: build_chrom_map(),
+        genome_release: args.genome_release,
+        path_db: args.path_db.clone(),
+        slack_bnd: args.slack_bnd,
+        slack_ins: args.slack_ins,
+        min_overlap: args.min_overlap,
+        max_tad_distance: args.max_tad_distance,
+    });
+
+    tracing::info!("listening on {}...", &args.listen);
+    tonic::transport::Server::builder()
+        .add_service(StrucvarsServiceServer::new(Service { state }))
+        .serve(args.listen)
+        .await
+        .map_err(|e| anyhow::anyhow!("gRPC server failed: {}", e))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::{pbs, pbs::strucvars_service_server::StrucvarsService as _, Service, State};
+    use crate::common::{build_chrom_map, GenomeRelease};
+
+    fn service() -> Result<Service, anyhow::Error> {
+        let path_db = "tests/strucvars/query/db";
+        Ok(Service {
+            state: Arc::new(State {
+                query_dbs: crate::strucvars::query::load_query_dbs(
+                    path_db,
+                    GenomeRelease::Grch37,
+                    10_000,
+                    None,
+                    None,
+                )?,
+                chrom_map: build_chrom_map(),
+                genome_release: GenomeRelease::Grch37,
+                path_db: path_db.into(),
+                slack_bnd: 50,
+                slack_ins: 50,
+                min_overlap: 0.8,
+                max_tad_distance: 10_000,
+            }),
+        })
+    }
+
+    #[tokio::test]
+    async fn count_overlaps() -> Result<(), anyhow::Error> {
+        let service = service()?;
+
+        let request = pbs::CountOverlapsRequest {
+            svs: vec![pbs::StructuralVariant {
+                chrom: "1".into(),
+                start: 1_000_000,
+                stop: 1_100_000,
+                sv_type: "DEL".into(),
+                ..Default::default()
+            }],
+            query_json: String::new(),
+        };
+        let response = service
+            .count_overlaps(tonic::Request::new(request))
+            .await?
+            .into_inner();
+        assert_eq!(response.counts.len(), 1);
+
+        let request = pbs::CountOverlapsRequest {
+            svs: vec![pbs::StructuralVariant {
+                sv_type: "XXX".into(),
+                ..Default::default()
+            }],
+            query_json: String::new(),
+        };
+        let status = service
+            .count_overlaps(tonic::Request::new(request))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn query() -> Result<(), anyhow::Error> {
+        let service = service()?;
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_output = format!("{}/out.tsv", tmpdir.to_string_lossy());
+
+        let request = pbs::QueryRequest {
+            query_json: std::fs::read_to_string("tests/strucvars/query/Case_3.query.json")?,
+            path_input: "tests/strucvars/query/Case_3.ingested.vcf".into(),
+            path_output: path_output.clone(),
+            rng_seed: Some(42),
+            ..Default::default()
+        };
+        let response = service
+            .query(tonic::Request::new(request))
+            .await?
+            .into_inner();
+        assert!(response.count_total > 0);
+        assert!(std::path::Path::new(&path_output).exists());
+
+        Ok(())
+    }
+}
@@ -43,13 +43,40 @@ fn create_tmp_files(
     Ok(files)
 }
 
+/// Write `record` to the file for its chromosome and SV type in `tmp_files`.
+fn write_tmp_record(
+    tmp_files: &mut HashMap<(usize, SvType), BufWriter<File>>,
+    chrom_map: &indexmap::IndexMap<String, usize>,
+    record: &super::output::Record,
+) -> Result<(), anyhow::Error> {
+    let chrom_no = *chrom_map
+        .get(&record.chromosome)
+        .expect("unknown chromosome");
+    let tmp_file = tmp_files
+        .get_mut(&(chrom_no, record.sv_type))
+        .expect("no file for chrom/sv_type");
+    to_writer(&mut *tmp_file, record)?;
+    tmp_file.write_all(&[b'\n'])?;
+    Ok(())
+}
+
+/// Return the path of the file with the records contributed by the case `case_uuid`.
+fn contribution_path(path_contributions: &Path, case_uuid: &uuid::Uuid) -> PathBuf {
+    path_contributions.join(format!("{}.jsonl", case_uuid))
+}
+
 /// Split the input into one file in `tmp_dir` for each chromosome and SV type.
 ///
+/// With `path_contributions`, the records of each case are written to the file
+/// `<case-uuid>.jsonl` in this directory instead, skipping cases that already have one,
+/// and then the records of all cases in the directory are split.
+///
 /// Async I/O is used here because we support reading from S3.
 async fn split_input_by_chrom_and_sv_type(
     tmp_dir: &tempfile::TempDir,
     input_vcf_paths: Vec<String>,
     genome_release: GenomeRelease,
+    path_contributions: Option<&Path>,
 ) -> Result<(), anyhow::Error> {
     tracing::info!("parse all input files and split them up");
     let mut tmp_files = create_tmp_files(tmp_dir)?;
@@ -61,7 +88,31 @@ async fn split_input_by_chrom_and_sv_type(
         let mut input_reader = open_vcf_reader(path_input).await?;
         let input_header = input_reader.read_header().await?;
 
-        let (pedigree, _) = crate::common::extract_pedigree_and_case_uuid(&input_header)?;
+        let (pedigree, case_uuid) = crate::common::extract_pedigree_and_case_uuid(&input_header)?;
+        let mut contribution = match path_contributions {
+            Some(path_contributions) => {
+                if case_uuid.is_nil() {
+                    anyhow::bail!(
+                        "no case UUID in header of {}, required for --path-contributions",
+                        path_input
+                    );
+                }
+                let path = contribution_path(path_contributions, &case_uuid);
+                if path.exists() {
+                    tracing::warn!(
+                        "{}: case {} of {} is already in the database, skipping",
+                        crate::codes::STRUCVARS_AGGREGATE_CASE_SKIPPED,
+                        &case_uuid,
+                        path_input
+                    );
+                    continue;
+                }
+                let path_tmp = path.with_extension("jsonl.tmp");
+                let writer = BufWriter::new(File::create(&path_tmp)?);
+                Some((path, path_tmp, writer))
+            }
+            None => None,
+        };
         let mut prev = std::time::Instant::now();
         let mut records = input_reader.records(&input_header);
         let before_parsing = Instant::now();
@@ -74,15 +125,13 @@ async fn split_input_by_chrom_and_sv_type(
                 &pedigree,
             )?;
 
-            let chrom_no = *chrom_map
-                .get(&input_record.chromosome)
-                .expect("unknown chromosome");
-            let sv_type = input_record.sv_type;
-            let mut tmp_file = tmp_files
-                .get_mut(&(chrom_no, sv_type))
-                .expect("no file for chrom/sv_type");
-            to_writer(&mut tmp_file, &input_record)?;
-            tmp_file.write_all(&[b'\n'])?;
+            match contribution.as_mut() {
+                Some((_, _, writer)) => {
+                    to_writer(&mut *writer, &input_record)?;
+                    writer.write_all(&[b'\n'])?;
+                }
+                None => write_tmp_record(&mut tmp_files, &chrom_map, &input_record)?,
+            }
 
             // Write out progress indicator every 60 seconds.
             if prev.elapsed().as_secs() >= 60 {
@@ -93,6 +142,11 @@ async fn split_input_by_chrom_and_sv_type(
             count_records += 1;
         }
 
+        if let Some((path, path_tmp, writer)) = contribution {
+            writer.into_inner()?.sync_all()?;
+            std::fs::rename(&path_tmp, &path)?;
+        }
+
         trace_rss_now();
         tracing::debug!(
             "total time spent parsing {} records: {:?}",
@@ -102,6 +156,26 @@ async fn split_input_by_chrom_and_sv_type(
 
         count_files += 1;
     }
+
+    if let Some(path_contributions) = path_contributions {
+        let mut paths = std::fs::read_dir(path_contributions)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.retain(|path| {
+            path.extension()
+                .map(|ext| ext == "jsonl")
+                .unwrap_or_default()
+        });
+        paths.sort();
+        tracing::info!("splitting records of {} cases", paths.len());
+        for path in paths {
+            for line in read_lines(&path)? {
+                let record: super::output::Record = serde_json::from_str(&line?)
+                    .map_err(|e| anyhow::anyhow!("invalid record in {:?}: {}", &path, e))?;
+                write_tmp_record(&mut tmp_files, &chrom_map, &record)?;
+            }
+        }
+    }
     tracing::info!(
         "total time spent parsing {} files: {:?}",
         count_files.separate_with_commas(),
@@ -278,7 +352,7 @@ pub struct Args {
     #[arg(long)]
     pub path_output: PathBuf,
     /// Input files to cluster, prefix with `@` to file with line-wise paths.
-    #[arg(required = true)]
+    #[arg(required_unless_present = "remove_case")]
     pub path_input: Vec<String>,
 
     /// Minimal reciprocal overlap to use (slightly more strict that the normal
@@ -291,6 +365,22 @@ pub struct Args {
     /// Padding to use for INS
     #[arg(long, default_value_t = 50)]
     pub slack_ins: i32,
+    /// Optional path to also write the binary background database for `strucvars
+    /// query` to, e.g., `$DB/grch37/strucvars/inhouse.bin`.
+    #[arg(long)]
+    pub path_output_bin: Option<PathBuf>,
+    /// Number of hours after which a lock of the output files held by a job on another
+    /// host is considered stale.
+    #[arg(long, default_value_t = 48)]
+    pub stale_lock_hours: u64,
+    /// Optional directory to keep the records of each case in, such that cases can be
+    /// added and removed without reading the VCF files of all cases again.
+    #[arg(long)]
+    pub path_contributions: Option<PathBuf>,
+    /// UUIDs of cases to remove from `--path-contributions`, e.g., after consent was
+    /// withdrawn.
+    #[arg(long, value_delimiter = ',', requires = "path_contributions")]
+    pub remove_case: Vec<uuid::Uuid>,
 }
 
 /// Main entry point for the `strucvars txt-to-bin` command.
@@ -321,17 +411,61 @@ pub async fn run(common_args: &crate::common::Args, args: &Args) -> Result<(), a
 
     trace_rss_now();
 
+    let _lock = crate::common::lock::LockFile::acquire(
+        &args.path_output.to_string_lossy(),
+        std::time::Duration::from_secs(args.stale_lock_hours * 3600),
+    )?;
+    // The contributions directory may be shared by jobs writing different outputs.
+    let _lock_contributions = args
+        .path_contributions
+        .as_ref()
+        .map(|path_contributions| {
+            crate::common::lock::LockFile::acquire(
+                &path_contributions.to_string_lossy(),
+                std::time::Duration::from_secs(args.stale_lock_hours * 3600),
+            )
+        })
+        .transpose()?;
+
+    if let Some(path_contributions) = args.path_contributions.as_ref() {
+        std::fs::create_dir_all(path_contributions)?;
+        for case_uuid in &args.remove_case {
+            let path = contribution_path(path_contributions, case_uuid);
+            std::fs::remove_file(&path).map_err(|e| {
+                anyhow::anyhow!("could not remove case {} at {:?}: {}", case_uuid, &path, e)
+            })?;
+            tracing::info!("removed case {}", case_uuid);
+        }
+    }
+
     // Read all input files and write all records by chromosome and SV type
     let tmp_dir = tempfile::TempDir::new()?;
     tracing::debug!("using tmpdir={:?}", &tmp_dir);
-    split_input_by_chrom_and_sv_type(&tmp_dir, input_vcf_paths, args.genome_release).await?;
+    split_input_by_chrom_and_sv_type(
+        &tmp_dir,
+        input_vcf_paths,
+        args.genome_release,
+        args.path_contributions.as_deref(),
+    )
+    .await?;
 
     // Read the output of the previous step by chromosome and SV type, perform
     // overlapping and merge such "compressed" data set to the final output
     // file.
     tracing::info!("Merging to output TSV file...");
     merge_split_files(&tmp_dir, args, &args.path_output)?;
-    tracing::info!("... done - don't forget to convert to binary");
+    if let Some(path_output_bin) = args.path_output_bin.as_ref() {
+        tracing::info!("Converting to binary file...");
+        crate::strucvars::txt_to_bin::vardbs::convert_to_bin(
+            &args.path_output,
+            path_output_bin,
+            crate::strucvars::txt_to_bin::vardbs::InputFileType::InhouseDb,
+            false,
+        )?;
+        tracing::info!("... done");
+    } else {
+        tracing::info!("... done - don't forget to convert to binary");
+    }
 
     Ok(())
 }
@@ -348,6 +482,8 @@ mod tests {
         let tmp_dir = TempDir::default();
         let common_args = CommonArgs {
             verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
         };
         let args = Args {
             genome_release: GenomeRelease::Grch37,
@@ -356,6 +492,10 @@ mod tests {
             min_overlap: 0.8,
             slack_bnd: 50,
             slack_ins: 50,
+            path_output_bin: None,
+            stale_lock_hours: 48,
+            path_contributions: None,
+            remove_case: vec![],
         };
 
         run(&common_args, &args).await?;
@@ -371,6 +511,8 @@ mod tests {
         let tmp_dir = TempDir::default();
         let common_args = CommonArgs {
             verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
         };
         let args = Args {
             genome_release: GenomeRelease::Grch37,
@@ -382,6 +524,10 @@ mod tests {
             min_overlap: 0.8,
             slack_bnd: 50,
             slack_ins: 50,
+            path_output_bin: None,
+            stale_lock_hours: 48,
+            path_contributions: None,
+            remove_case: vec![],
         };
 
         run(&common_args, &args).await?;
@@ -397,6 +543,8 @@ mod tests {
         let tmp_dir = TempDir::default();
         let common_args = CommonArgs {
             verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
         };
         let args = Args {
             genome_release: GenomeRelease::Grch37,
@@ -405,6 +553,10 @@ mod tests {
             min_overlap: 0.8,
             slack_bnd: 50,
             slack_ins: 50,
+            path_output_bin: None,
+            stale_lock_hours: 48,
+            path_contributions: None,
+            remove_case: vec![],
         };
 
         run(&common_args, &args).await?;
@@ -414,4 +566,82 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn run_smoke_output_bin() -> Result<(), anyhow::Error> {
+        use prost::Message;
+
+        let tmp_dir = TempDir::default();
+        let common_args = CommonArgs {
+            verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
+        };
+        let args = Args {
+            genome_release: GenomeRelease::Grch37,
+            path_output: tmp_dir.join("out.tsv"),
+            path_input: vec![
+                String::from("tests/strucvars/aggregate/oneline.vcf"),
+                String::from("tests/strucvars/aggregate/oneline.vcf"),
+            ],
+            min_overlap: 0.8,
+            slack_bnd: 50,
+            slack_ins: 50,
+            path_output_bin: Some(tmp_dir.join("inhouse.bin")),
+            stale_lock_hours: 48,
+            path_contributions: None,
+            remove_case: vec![],
+        };
+
+        run(&common_args, &args).await?;
+
+        let bg_db = crate::strucvars::pbs::BackgroundDatabase::decode(
+            std::fs::read(tmp_dir.join("inhouse.bin"))?.as_slice(),
+        )?;
+        insta::assert_debug_snapshot!(bg_db);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn run_smoke_contributions() -> Result<(), anyhow::Error> {
+        let tmp_dir = TempDir::default();
+        let common_args = CommonArgs {
+            verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
+        };
+        let args =
+            |path_output: &str, path_input: Vec<String>, remove_case: Vec<uuid::Uuid>| Args {
+                genome_release: GenomeRelease::Grch37,
+                path_output: tmp_dir.join(path_output),
+                path_input,
+                min_overlap: 0.8,
+                slack_bnd: 50,
+                slack_ins: 50,
+                path_output_bin: None,
+                stale_lock_hours: 48,
+                path_contributions: Some(tmp_dir.join("contributions")),
+                remove_case,
+            };
+        let path_input = String::from("tests/strucvars/aggregate/oneline.vcf");
+        let case_uuid: uuid::Uuid = "d2bad2ec-a75d-44b9-bd0a-83a3f1331b7c".parse()?;
+
+        // The second addition of the case is skipped.
+        run(
+            &common_args,
+            &args("added.tsv", vec![path_input.clone()], vec![]),
+        )
+        .await?;
+        run(&common_args, &args("again.tsv", vec![path_input], vec![])).await?;
+        let added = std::fs::read_to_string(tmp_dir.join("added.tsv"))?;
+        assert_eq!(added.lines().count(), 2);
+        assert_eq!(std::fs::read_to_string(tmp_dir.join("again.tsv"))?, added);
+
+        run(&common_args, &args("removed.tsv", vec![], vec![case_uuid])).await?;
+        let removed = std::fs::read_to_string(tmp_dir.join("removed.tsv"))?;
+        assert_eq!(removed.lines().count(), 1);
+
+        Ok(())
+    }
 }
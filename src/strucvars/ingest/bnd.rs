@@ -0,0 +1,202 @@
+//! Pairing of break-end (BND) mates and classification into events.
+//!
+//! Callers report each novel adjacency as two BND half-records, one at each side.  The
+//! `MATEID` of the input files is not carried over into the intermediate records, so the
+//! mates are paired by their coordinates: the position of each half must match the
+//! `END` of the other half within the break-end slack.  Pairs on the same chromosome
+//! are converted into symbolic deletions, duplications, or inversions.  Pairs across
+//! chromosomes are kept as one canonical BND record, the half with the lower position.
+
+use mehari::annotate::strucvars::{bnd::Breakend, SvSubType, SvType, VarFishStrucvarTsvRecord};
+
+/// Key of the INFO field with the resolved event type.
+pub const INFO_KEY: &str = "bnd_event";
+
+/// Event type resolved from a pair of BND mates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+pub enum MateEvent {
+    /// Deletion, `t[p[` at the lower and `]p]t` at the higher position.
+    #[strum(serialize = "DEL")]
+    Del,
+    /// Tandem duplication, `]p]t` at the lower and `t[p[` at the higher position.
+    #[strum(serialize = "DUP")]
+    Dup,
+    /// One junction of an inversion, `t]p]` or `[p[t` at both positions.
+    #[strum(serialize = "INV")]
+    Inv,
+    /// Translocation between two chromosomes.
+    #[strum(serialize = "TRA")]
+    Tra,
+}
+
+/// Whether `a` and `b` are the two halves of the same adjacency.
+fn are_mates(a: &VarFishStrucvarTsvRecord, b: &VarFishStrucvarTsvRecord, slack: i32) -> bool {
+    a.chromosome == b.chromosome2
+        && a.chromosome2 == b.chromosome
+        && (a.start - b.end).abs() <= slack
+        && (a.end - b.start).abs() <= slack
+}
+
+/// Classify the pair with the lower half `record` into an event.
+fn classify(record: &VarFishStrucvarTsvRecord) -> Result<MateEvent, anyhow::Error> {
+    if record.chromosome != record.chromosome2 {
+        return Ok(MateEvent::Tra);
+    }
+    let alt = record
+        .info
+        .alt
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("BND record without ALT allele: {:?}", record))?;
+    let bnd = Breakend::from_ref_alt_str("N", alt)?;
+    Ok(match (bnd.leading_base, bnd.left_open) {
+        (true, false) => MateEvent::Del,
+        (false, true) => MateEvent::Dup,
+        _ => MateEvent::Inv,
+    })
+}
+
+/// Turn the lower half `record` of a mate pair into the record of `event`.
+fn into_event(mut record: VarFishStrucvarTsvRecord, event: MateEvent) -> VarFishStrucvarTsvRecord {
+    let (sv_type, sv_sub_type) = match event {
+        MateEvent::Del => (SvType::Del, SvSubType::Del),
+        MateEvent::Dup => (SvType::Dup, SvSubType::Dup),
+        MateEvent::Inv => (SvType::Inv, SvSubType::Inv),
+        MateEvent::Tra => return record,
+    };
+    record.sv_type = sv_type;
+    record.sv_sub_type = sv_sub_type;
+    record.pe_orientation = sv_type.into();
+    record.info.alt = None;
+    record
+}
+
+/// Pair the BND mates in `records`, returning the records with one record per pair.
+///
+/// Records that are not BNDs or whose mate is not found are returned unchanged, without
+/// a `MateEvent`.  The callers of both halves are kept for the canonical record.
+pub fn resolve_mates(
+    records: Vec<VarFishStrucvarTsvRecord>,
+    slack: i32,
+) -> Result<Vec<(VarFishStrucvarTsvRecord, Option<MateEvent>)>, anyhow::Error> {
+    let mut records = records.into_iter().map(Some).collect::<Vec<_>>();
+    let mut result = Vec::with_capacity(records.len());
+    for i in 0..records.len() {
+        let Some(record) = records[i].take() else {
+            continue;
+        };
+        if record.sv_type != SvType::Bnd {
+            result.push((record, None));
+            continue;
+        }
+        let mate_idx = (i + 1..records.len()).find(|&j| {
+            records[j]
+                .as_ref()
+                .map(|mate| mate.sv_type == SvType::Bnd && are_mates(&record, mate, slack))
+                .unwrap_or(false)
+        });
+        match mate_idx {
+            None => result.push((record, None)),
+            Some(j) => {
+                let mate = records[j].take().expect("checked above");
+                let (mut lower, upper) =
+                    if (mate.chromosome_no, mate.start) < (record.chromosome_no, record.start) {
+                        (mate, record)
+                    } else {
+                        (record, mate)
+                    };
+                for caller in upper.callers {
+                    if !lower.callers.contains(&caller) {
+                        lower.callers.push(caller);
+                    }
+                }
+                let event = classify(&lower)?;
+                result.push((into_event(lower, event), Some(event)));
+            }
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::strucvars::{SvType, VarFishStrucvarTsvRecord};
+
+    use super::MateEvent;
+
+    fn bnd(
+        chrom: (u32, i32),
+        chrom2: (u32, i32),
+        alt: &str,
+        caller: &str,
+    ) -> VarFishStrucvarTsvRecord {
+        let mut record = VarFishStrucvarTsvRecord {
+            chromosome: chrom.0.to_string(),
+            chromosome_no: chrom.0,
+            start: chrom.1,
+            chromosome2: chrom2.0.to_string(),
+            chromosome_no2: chrom2.0,
+            end: chrom2.1,
+            sv_type: SvType::Bnd,
+            sv_sub_type: mehari::annotate::strucvars::SvSubType::Bnd,
+            callers: vec![caller.to_string()],
+            ..Default::default()
+        };
+        record.info.alt = Some(alt.to_string());
+        record
+    }
+
+    #[rstest::rstest]
+    #[case("N[1:2000[", "]1:1000]N", MateEvent::Del, SvType::Del)]
+    #[case("]1:2000]N", "N[1:1000[", MateEvent::Dup, SvType::Dup)]
+    #[case("N]1:2000]", "N]1:1000]", MateEvent::Inv, SvType::Inv)]
+    #[case("[1:2000[N", "[1:1000[N", MateEvent::Inv, SvType::Inv)]
+    fn resolve_mates_intra(
+        #[case] alt_lower: &str,
+        #[case] alt_upper: &str,
+        #[case] event: MateEvent,
+        #[case] sv_type: SvType,
+    ) -> Result<(), anyhow::Error> {
+        // The upper half comes first to check that the lower half is kept.
+        let records = vec![
+            bnd((1, 2010), (1, 1000), alt_upper, "DELLYv1.1.3"),
+            bnd((1, 1000), (1, 2000), alt_lower, "MANTAv1.6.0"),
+        ];
+
+        let resolved = super::resolve_mates(records, 50)?;
+
+        assert_eq!(resolved.len(), 1);
+        let (record, resolved_event) = &resolved[0];
+        assert_eq!(resolved_event, &Some(event));
+        assert_eq!(record.sv_type, sv_type);
+        assert_eq!((record.start, record.end), (1000, 2000));
+        assert_eq!(record.info.alt, None);
+        assert_eq!(record.callers, vec!["MANTAv1.6.0", "DELLYv1.1.3"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_mates_translocation() -> Result<(), anyhow::Error> {
+        let records = vec![
+            bnd((2, 321681), (17, 198982), "N]17:198982]", "DELLYv1.1.3"),
+            bnd((17, 198982), (2, 321681), "N]2:321681]", "DELLYv1.1.3"),
+            bnd((3, 1000), (5, 2000), "N[5:2000[", "DELLYv1.1.3"),
+        ];
+
+        let resolved = super::resolve_mates(records, 50)?;
+
+        assert_eq!(
+            resolved
+                .iter()
+                .map(|(record, event)| (record.chromosome.as_str(), record.sv_type, *event))
+                .collect::<Vec<_>>(),
+            vec![
+                ("2", SvType::Bnd, Some(MateEvent::Tra)),
+                ("3", SvType::Bnd, None),
+            ]
+        );
+        assert_eq!(resolved[0].0.callers, vec!["DELLYv1.1.3"]);
+
+        Ok(())
+    }
+}
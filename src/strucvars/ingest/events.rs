@@ -0,0 +1,285 @@
+//! Detection of whole-chromosome and arm-level events from CNV segments.
+//!
+//! Callers report aneuploidies and arm-level losses/gains as many smaller segments.  The code
+//! in this module aggregates the segments of each sample and emits one additional record for
+//! each chromosome or arm that is covered above a threshold fraction.
+
+use std::collections::BTreeMap;
+
+use mehari::annotate::strucvars::{SvSubType, SvType, VarFishStrucvarTsvRecord};
+
+use crate::common::{contig_length, cytoband::centromere, GenomeRelease};
+
+/// Acrocentric chromosomes; their short arms are not considered for whole-chromosome events.
+const ACROCENTRIC: &[&str] = &["13", "14", "15", "21", "22"];
+
+/// SV type, carrier sample indices, and callers of an event.
+type EventCarriers = (SvType, Vec<usize>, Vec<String>);
+
+/// Number of bases of `[start, end)` that are covered by the sorted, merged `intervals`.
+fn covered(intervals: &[(usize, usize)], start: usize, end: usize) -> usize {
+    intervals
+        .iter()
+        .map(|(b, e)| (*e).min(end).saturating_sub((*b).max(start)))
+        .sum()
+}
+
+/// Sort and merge overlapping or adjacent intervals.
+fn merge_intervals(mut intervals: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    intervals.sort();
+    let mut result: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in intervals {
+        match result.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => result.push((start, end)),
+        }
+    }
+    result
+}
+
+/// Return whether the genotype string describes a carrier of the variant.
+fn is_carrier(gt: Option<&String>) -> bool {
+    gt.map(|gt| gt.contains('1')).unwrap_or(false)
+}
+
+/// Describe the event in ISCN-like short notation, e.g., `+21`, `-X`, or `del(5q)`.
+fn event_label(sv_type: SvType, chrom: &str, arm: Option<char>) -> String {
+    match (sv_type, arm) {
+        (SvType::Del, None) => format!("-{}", chrom),
+        (_, None) => format!("+{}", chrom),
+        (SvType::Del, Some(arm)) => format!("del({}{})", chrom, arm),
+        (_, Some(arm)) => format!("dup({}{})", chrom, arm),
+    }
+}
+
+/// Detect whole-chromosome and arm-level events in the clustered `records` of one contig.
+///
+/// For each sample, the deletion and duplication segments that the sample carries are
+/// merged.  If at least `min_fraction` of the chromosome is covered, a whole-chromosome event
+/// is emitted; otherwise, an arm-level event is emitted for each arm that is covered by at
+/// least `min_fraction`.  Samples with the same event are reported in the same record.
+///
+/// Returns the event records together with their labels.
+pub fn detect_events(
+    genome_release: GenomeRelease,
+    records: &[VarFishStrucvarTsvRecord],
+    min_fraction: f32,
+) -> Vec<(VarFishStrucvarTsvRecord, String)> {
+    let Some(template) = records.first() else {
+        return Vec::new();
+    };
+    let chrom = annonars::common::cli::canonicalize(&template.chromosome);
+    let (Some(length), Some((cen_start, cen_end))) = (
+        contig_length(genome_release, &chrom),
+        centromere(genome_release, &chrom),
+    ) else {
+        return Vec::new();
+    };
+    let acrocentric = ACROCENTRIC.contains(&chrom.as_str());
+    let passes =
+        |covered: usize, total: usize| total > 0 && covered as f32 >= min_fraction * total as f32;
+
+    // Collect the samples per event, keyed by SV type and region.
+    let mut events: BTreeMap<(usize, usize, String), EventCarriers> = BTreeMap::new();
+    for sv_type in [SvType::Del, SvType::Dup] {
+        let num_samples = template.genotype.entries.len();
+        for sample_idx in 0..num_samples {
+            let segments = records
+                .iter()
+                .filter(|record| record.sv_type == sv_type)
+                .filter(|record| {
+                    is_carrier(
+                        record
+                            .genotype
+                            .entries
+                            .get(sample_idx)
+                            .and_then(|entry| entry.gt.as_ref()),
+                    )
+                })
+                .collect::<Vec<_>>();
+            if segments.is_empty() {
+                continue;
+            }
+            let intervals = merge_intervals(
+                segments
+                    .iter()
+                    .map(|record| ((record.start.max(1) - 1) as usize, record.end as usize))
+                    .collect(),
+            );
+
+            let p_covered = covered(&intervals, 0, cen_start);
+            let q_covered = covered(&intervals, cen_end, length);
+            let whole = if acrocentric {
+                passes(q_covered, length - cen_end)
+            } else {
+                passes(p_covered + q_covered, cen_start + length - cen_end)
+            };
+            let mut regions = Vec::new();
+            if whole {
+                regions.push((0, length, event_label(sv_type, &chrom, None)));
+            } else {
+                if !acrocentric && passes(p_covered, cen_start) {
+                    regions.push((0, cen_start, event_label(sv_type, &chrom, Some('p'))));
+                }
+                if passes(q_covered, length - cen_end) {
+                    regions.push((cen_end, length, event_label(sv_type, &chrom, Some('q'))));
+                }
+            }
+
+            for region in regions {
+                let entry = events
+                    .entry(region)
+                    .or_insert_with(|| (sv_type, Vec::new(), Vec::new()));
+                entry.1.push(sample_idx);
+                for caller in segments.iter().flat_map(|record| record.callers.iter()) {
+                    if !entry.2.contains(caller) {
+                        entry.2.push(caller.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    events
+        .into_iter()
+        .map(|((start, end, label), (sv_type, samples, callers))| {
+            let mut record = VarFishStrucvarTsvRecord {
+                start: start as i32 + 1,
+                end: end as i32,
+                start_ci_left: 0,
+                start_ci_right: 0,
+                end_ci_left: 0,
+                end_ci_right: 0,
+                callers,
+                sv_type,
+                sv_sub_type: match sv_type {
+                    SvType::Del => SvSubType::Del,
+                    _ => SvSubType::Dup,
+                },
+                info: Default::default(),
+                ..template.clone()
+            };
+            for (idx, entry) in record.genotype.entries.iter_mut().enumerate() {
+                *entry = mehari::annotate::strucvars::GenotypeInfo {
+                    name: entry.name.clone(),
+                    gt: Some(if samples.contains(&idx) { "0/1" } else { "0/0" }.to_string()),
+                    ..Default::default()
+                };
+            }
+            record.num_het = samples.len() as i32;
+            record.num_hom_ref = (record.genotype.entries.len() - samples.len()) as i32;
+            record.num_hom_alt = 0;
+            record.num_hemi_alt = 0;
+            record.num_hemi_ref = 0;
+            (record, label)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::strucvars::{
+        GenotypeCalls, GenotypeInfo, SvSubType, SvType, VarFishStrucvarTsvRecord,
+    };
+
+    use crate::common::GenomeRelease;
+
+    fn segment(
+        chrom: &str,
+        sv_type: SvType,
+        start: i32,
+        end: i32,
+        gts: &[&str],
+    ) -> VarFishStrucvarTsvRecord {
+        VarFishStrucvarTsvRecord {
+            chromosome: chrom.into(),
+            chromosome2: chrom.into(),
+            start,
+            end,
+            callers: vec!["Gcnv".into()],
+            sv_type,
+            sv_sub_type: match sv_type {
+                SvType::Del => SvSubType::Del,
+                _ => SvSubType::Dup,
+            },
+            genotype: GenotypeCalls {
+                entries: gts
+                    .iter()
+                    .enumerate()
+                    .map(|(i, gt)| GenotypeInfo {
+                        name: format!("sample-{}", i),
+                        gt: Some(gt.to_string()),
+                        ..Default::default()
+                    })
+                    .collect(),
+            },
+            ..Default::default()
+        }
+    }
+
+    fn labels(
+        events: &[(VarFishStrucvarTsvRecord, String)],
+    ) -> Vec<(String, i32, i32, Vec<String>)> {
+        events
+            .iter()
+            .map(|(record, label)| {
+                (
+                    label.clone(),
+                    record.start,
+                    record.end,
+                    record
+                        .genotype
+                        .entries
+                        .iter()
+                        .map(|e| e.gt.clone().unwrap_or_default())
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detect_trisomy() {
+        // chr21 on GRCh37 has a length of 48,129,895; the segments tile the q arm.
+        let records = vec![
+            segment("21", SvType::Dup, 14_300_001, 30_000_000, &["0/1", "0/0"]),
+            segment("21", SvType::Dup, 30_000_001, 48_000_000, &["0/1", "0/0"]),
+        ];
+        let events = super::detect_events(GenomeRelease::Grch37, &records, 0.9);
+        assert_eq!(
+            labels(&events),
+            vec![(
+                "+21".to_string(),
+                1,
+                48129895,
+                vec!["0/1".to_string(), "0/0".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn detect_arm_deletion() {
+        // chr5 on GRCh37: centromere at 46.4-49.4 Mbp, length 180,915,260.
+        let records = vec![
+            segment("5", SvType::Del, 49_500_001, 120_000_000, &["0/1", "0/1"]),
+            segment("5", SvType::Del, 120_000_001, 180_900_000, &["0/0", "0/1"]),
+            segment("5", SvType::Dup, 1_000, 20_000, &["0/1", "0/0"]),
+        ];
+        let events = super::detect_events(GenomeRelease::Grch37, &records, 0.9);
+        assert_eq!(
+            labels(&events),
+            vec![(
+                "del(5q)".to_string(),
+                49405642,
+                180915260,
+                vec!["0/0".to_string(), "0/1".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn detect_nothing_below_threshold() {
+        let records = vec![segment("5", SvType::Del, 49_500_001, 120_000_000, &["0/1"])];
+        assert!(super::detect_events(GenomeRelease::Grch37, &records, 0.9).is_empty());
+    }
+}
@@ -102,6 +102,22 @@ pub fn build_output_header(
                 "Effect annotations: 'Allele | Annotation | Gene_Name | Gene_ID'",
             ),
         )
+        .add_info(
+            "event".parse()?,
+            Map::<Info>::new(
+                Number::Count(1),
+                info::Type::String,
+                "Whole-chromosome or arm-level event aggregated from segments, e.g., '+21' or 'del(5q)'",
+            ),
+        )
+        .add_info(
+            super::bnd::INFO_KEY.parse()?,
+            Map::<Info>::new(
+                Number::Count(1),
+                info::Type::String,
+                "Event resolved from a pair of BND mates, one of DEL, DUP, INV, or TRA",
+            ),
+        )
         .add_format(
             key::CONDITIONAL_GENOTYPE_QUALITY,
             Map::<Format>::from(&key::CONDITIONAL_GENOTYPE_QUALITY),
@@ -155,6 +171,14 @@ pub fn build_output_header(
                 "Copy number of the variant in the sample",
             ),
         )
+        .add_format(
+            "mcn".parse()?,
+            Map::<Format>::new(
+                Number::Count(1),
+                format::Type::Float,
+                "Non-integer copy number of the variant in the sample, e.g., for mosaic aneuploidies",
+            ),
+        )
         .add_format(
             "anc".parse()?,
             Map::<Format>::new(
@@ -171,6 +195,14 @@ pub fn build_output_header(
                 "Point count (windows/targets/probes)",
             ),
         )
+        .add_format(
+            super::mito::FORMAT_KEY.parse()?,
+            Map::<Format>::new(
+                Number::Count(1),
+                format::Type::Float,
+                "Heteroplasmy fraction of the variant in the sample (chrMT only)",
+            ),
+        )
         .add_alternative_allele("DEL".parse()?, Map::<AlternativeAllele>::new("Deletion"))
         .add_alternative_allele("DUP".parse()?, Map::<AlternativeAllele>::new("Duplication"))
         .add_alternative_allele("INS".parse()?, Map::<AlternativeAllele>::new("Insertion"))
@@ -0,0 +1,171 @@
+//! Heteroplasmy fractions of chrMT structural variants.
+//!
+//! Callers of mitochondrial deletions report the fraction of mtDNA copies carrying the
+//! variant per sample, MitoSAlt as `FORMAT/HF` and GATK in mitochondria mode as
+//! `FORMAT/AF`.  The intermediate records do not carry these values, so the chrMT calls of
+//! the input files are read separately and the fraction of the call with the best
+//! reciprocal overlap is written as `FORMAT/hf`, as read by `strucvars query`.
+
+use futures::TryStreamExt;
+use mehari::common::noodles::open_vcf_readers;
+use noodles_vcf as vcf;
+
+use crate::common::{
+    interval::{Topology, MT_LENGTH},
+    reciprocal_overlap,
+};
+
+/// Key of the `FORMAT` field with the heteroplasmy fraction in the output.
+pub const FORMAT_KEY: &str = "hf";
+
+/// Keys of the `FORMAT` fields with the heteroplasmy fraction in the input, by priority.
+const INPUT_FORMAT_KEYS: &[&str] = &["HF", "AF"];
+
+/// A chrMT call with the heteroplasmy fraction for each sample.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeteroplasmyCall {
+    /// 0-based begin position.
+    pub begin: i32,
+    /// 0-based end position.
+    pub end: i32,
+    /// Heteroplasmy fraction by sample name.
+    pub fractions: indexmap::IndexMap<String, f32>,
+}
+
+/// Return the heteroplasmy fraction from the `FORMAT` fields of `sample`, if any.
+fn sample_fraction(sample: &vcf::record::genotypes::Sample<'_>) -> Option<f32> {
+    use vcf::record::genotypes::sample::{value::Array, Value};
+
+    INPUT_FORMAT_KEYS.iter().find_map(|key| {
+        let key = key.parse::<vcf::record::genotypes::keys::Key>().ok()?;
+        match sample.get(&key) {
+            Some(Some(Value::Float(fraction))) => Some(*fraction),
+            Some(Some(Value::Array(Array::Float(fractions)))) => {
+                fractions.first().copied().flatten()
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Read the chrMT calls with heteroplasmy fractions from the VCF files at `paths`.
+pub async fn read_calls(paths: &[String]) -> Result<Vec<HeteroplasmyCall>, anyhow::Error> {
+    let mut result = Vec::new();
+
+    for (path, mut reader) in paths.iter().zip(open_vcf_readers(paths).await?) {
+        let header = reader
+            .read_header()
+            .await
+            .map_err(|e| anyhow::anyhow!("problem reading header of {}: {}", path, e))?;
+        let sample_names = header.sample_names().iter().cloned().collect::<Vec<_>>();
+
+        let mut records = reader.records(&header);
+        while let Some(record) = records
+            .try_next()
+            .await
+            .map_err(|e| anyhow::anyhow!("problem reading record from {}: {}", path, e))?
+        {
+            if annonars::common::cli::canonicalize(&record.chromosome().to_string()) != "MT" {
+                continue;
+            }
+
+            let fractions = sample_names
+                .iter()
+                .zip(record.genotypes().values())
+                .filter_map(|(name, sample)| {
+                    sample_fraction(&sample).map(|fraction| (name.clone(), fraction))
+                })
+                .collect::<indexmap::IndexMap<_, _>>();
+            if fractions.is_empty() {
+                continue;
+            }
+
+            let pos: usize = record.position().into();
+            let end = match record
+                .info()
+                .get(&vcf::record::info::field::key::END_POSITION)
+            {
+                Some(Some(vcf::record::info::field::Value::Integer(end))) => *end,
+                _ => pos as i32,
+            };
+            result.push(HeteroplasmyCall {
+                begin: pos as i32 - 1,
+                end,
+                fractions,
+            });
+        }
+    }
+
+    Ok(result)
+}
+
+/// Return the call of `calls` with the best reciprocal overlap of at least `min_overlap`
+/// with the 0-based range `begin..end` on the circular chrMT, if any.
+pub fn best_match(
+    calls: &[HeteroplasmyCall],
+    begin: i32,
+    end: i32,
+    min_overlap: f32,
+) -> Option<&HeteroplasmyCall> {
+    let topology = Topology::Circular { length: MT_LENGTH };
+    calls
+        .iter()
+        .map(|call| {
+            (
+                reciprocal_overlap(begin..end, call.begin..call.end, topology),
+                call,
+            )
+        })
+        .filter(|(overlap, _)| *overlap >= min_overlap)
+        .max_by(|(lhs, _), (rhs, _)| lhs.total_cmp(rhs))
+        .map(|(_, call)| call)
+}
+
+#[cfg(test)]
+mod test {
+    use indexmap::IndexMap;
+
+    use super::HeteroplasmyCall;
+
+    #[tokio::test]
+    async fn read_calls() -> Result<(), anyhow::Error> {
+        let calls = super::read_calls(&["tests/strucvars/ingest/manta-mt-min.vcf".into()]).await?;
+
+        assert_eq!(
+            calls,
+            vec![HeteroplasmyCall {
+                begin: 8469,
+                end: 13447,
+                fractions: IndexMap::from([("SAMPLE".to_string(), 0.35)]),
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case::exact(8469, 13447, Some(0.35))]
+    #[case::shifted(8479, 13457, Some(0.35))]
+    #[case::too_small(8469, 9000, None)]
+    #[case::origin_spanning(16_400, 100, Some(0.9))]
+    fn best_match(#[case] begin: i32, #[case] end: i32, #[case] expected: Option<f32>) {
+        let calls = vec![
+            HeteroplasmyCall {
+                begin: 16_410,
+                end: 90,
+                fractions: IndexMap::from([("SAMPLE".to_string(), 0.9)]),
+            },
+            HeteroplasmyCall {
+                begin: 8469,
+                end: 13447,
+                fractions: IndexMap::from([("SAMPLE".to_string(), 0.35)]),
+            },
+        ];
+
+        assert_eq!(
+            super::best_match(&calls, begin, end, 0.8)
+                .map(|call| *call.fractions.get("SAMPLE").expect("sample missing")),
+            expected
+        );
+    }
+}
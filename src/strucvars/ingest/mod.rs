@@ -1,16 +1,19 @@
 //! Implementation of `strucvars ingest` subcommand.
 
-use crate::common::{self, worker_version, GenomeRelease};
+use crate::common::{self, noodles::open_vcf_writer, worker_version, GenomeRelease};
 use crate::flush_and_shutdown;
 use futures::future::join_all;
 use mehari::annotate::strucvars::guess_sv_caller;
 use mehari::common::io::std::is_gz;
-use mehari::common::noodles::{open_vcf_readers, open_vcf_writer, AsyncVcfReader, AsyncVcfWriter};
+use mehari::common::noodles::{open_vcf_readers, AsyncVcfReader, AsyncVcfWriter};
 use noodles_vcf as vcf;
 use rand_core::SeedableRng;
 use tokio::io::AsyncWriteExt;
 
+pub mod bnd;
+pub mod events;
 pub mod header;
+pub mod mito;
 
 /// Command line arguments for `strucvars ingest` subcommand.
 #[derive(Debug, clap::Parser)]
@@ -48,6 +51,10 @@ pub struct Args {
     /// Slack to use around insertions.
     #[arg(long, default_value_t = 50)]
     pub slack_ins: i32,
+    /// Minimal fraction of a chromosome or arm that CNV segments must cover for emitting an
+    /// aggregated whole-chromosome or arm-level event record.
+    #[arg(long, default_value_t = 0.9)]
+    pub min_event_fraction: f32,
 
     /// Seed for random number generator (UUIDs), if any.
     #[arg(long)]
@@ -55,11 +62,155 @@ pub struct Args {
     /// Maximal number of variants to write out; optional.
     #[clap(long)]
     pub max_var_count: Option<usize>,
+    /// Re-read the output file after writing and check the records against the header.
+    #[clap(long)]
+    pub validate_output: bool,
+}
+
+/// Per-sample `FORMAT` values of a record.
+type FormatValues = Vec<Vec<Option<vcf::record::genotypes::sample::Value>>>;
+
+/// Split non-integer copy numbers, e.g., from mosaic aneuploidies, into the rounded
+/// integer copy number `cn` and the original value in `mcn`.
+///
+/// The `mcn` key is only added if any sample has a non-integer copy number.
+fn split_mosaic_copy_number(
+    mut keys: Vec<vcf::record::genotypes::keys::Key>,
+    mut values: FormatValues,
+) -> Result<(Vec<vcf::record::genotypes::keys::Key>, FormatValues), anyhow::Error> {
+    use vcf::record::genotypes::sample::Value;
+
+    let Some(idx_cn) = keys.iter().position(|k| k.as_ref() == "cn") else {
+        return Ok((keys, values));
+    };
+    let has_float = values
+        .iter()
+        .any(|sample| matches!(sample.get(idx_cn), Some(Some(Value::Float(_)))));
+    if !has_float {
+        return Ok((keys, values));
+    }
+
+    keys.push("mcn".parse()?);
+    for sample in values.iter_mut() {
+        let mcn = match sample.get(idx_cn) {
+            Some(Some(Value::Float(cn))) => {
+                let cn = *cn;
+                sample[idx_cn] = Some(Value::Integer(cn.round() as i32));
+                Some(Value::Float(cn))
+            }
+            Some(Some(Value::Integer(cn))) => Some(Value::Float(*cn as f32)),
+            _ => None,
+        };
+        sample.resize(keys.len() - 1, None);
+        sample.push(mcn);
+    }
+    Ok((keys, values))
+}
+
+/// Add the heteroplasmy `fractions` of the samples, if any, as `FORMAT/hf`.
+fn add_heteroplasmy(
+    mut keys: Vec<vcf::record::genotypes::keys::Key>,
+    mut values: FormatValues,
+    fractions: Option<&[Option<f32>]>,
+) -> Result<(Vec<vcf::record::genotypes::keys::Key>, FormatValues), anyhow::Error> {
+    use vcf::record::genotypes::sample::Value;
+
+    let Some(fractions) = fractions else {
+        return Ok((keys, values));
+    };
+
+    keys.push(mito::FORMAT_KEY.parse()?);
+    for (sample, fraction) in values.iter_mut().zip(fractions.iter()) {
+        sample.resize(keys.len() - 1, None);
+        sample.push(fraction.map(Value::Float));
+    }
+    Ok((keys, values))
+}
+
+/// Expected copy number on `chrom` for an individual of the given `sex`.
+///
+/// Returns `None` on chrMT and on the gonosomes if the sex is unknown.
+fn expected_copy_number(chrom: &str, sex: mehari::ped::Sex) -> Option<i32> {
+    use mehari::ped::Sex;
+
+    match (annonars::common::cli::canonicalize(chrom).as_str(), sex) {
+        ("MT", _) => None,
+        ("X", Sex::Male) | ("Y", Sex::Male) => Some(1),
+        ("X", Sex::Female) => Some(2),
+        ("X", _) | ("Y", _) => None,
+        _ => Some(2),
+    }
+}
+
+/// Canonical genotype for copy number `cn` given the `expected` copy number.
+///
+/// Gains of two or more copies in diploid regions cannot be assigned to one allele
+/// and yield `./1`.
+fn genotype_from_copy_number(cn: i32, expected: i32) -> &'static str {
+    match (expected, cn) {
+        (1, 1) => "0",
+        (1, _) => "1",
+        (_, 0) => "1/1",
+        (_, cn) if cn == expected => "0/0",
+        (_, cn) if cn == expected - 1 || cn == expected + 1 => "0/1",
+        _ => "./1",
+    }
+}
+
+/// Derive the genotypes from the copy numbers in the `cn` key.
+///
+/// CNV callers such as GATK gCNV and DRAGEN CNV report the copy number rather than a
+/// meaningful genotype.  The genotype is derived for samples without a called genotype
+/// and, if `cn_callers_only`, for all samples.  `sexes` are the sexes of the samples in
+/// the order of `values` and are used for the gonosomes.
+fn derive_genotypes_from_copy_number(
+    chrom: &str,
+    sexes: &[mehari::ped::Sex],
+    cn_callers_only: bool,
+    mut keys: Vec<vcf::record::genotypes::keys::Key>,
+    mut values: FormatValues,
+) -> (Vec<vcf::record::genotypes::keys::Key>, FormatValues) {
+    use vcf::record::genotypes::{keys::key, sample::Value};
+
+    let Some(idx_cn) = keys.iter().position(|k| k.as_ref() == "cn") else {
+        return (keys, values);
+    };
+    let idx_cn = if keys.first() == Some(&key::GENOTYPE) {
+        idx_cn
+    } else {
+        keys.insert(0, key::GENOTYPE);
+        for sample in values.iter_mut() {
+            sample.insert(0, None);
+        }
+        idx_cn + 1
+    };
+
+    for (sample, sex) in values.iter_mut().zip(sexes.iter()) {
+        let has_gt = match sample.first() {
+            Some(Some(Value::String(gt))) => gt.chars().any(|c| c.is_ascii_digit()),
+            _ => false,
+        };
+        if has_gt && !cn_callers_only {
+            continue;
+        }
+        if let (Some(Some(Value::Integer(cn))), Some(expected)) =
+            (sample.get(idx_cn), expected_copy_number(chrom, *sex))
+        {
+            sample[0] = Some(Value::String(
+                genotype_from_copy_number(*cn, expected).to_string(),
+            ));
+        }
+    }
+    (keys, values)
 }
 
 async fn write_ingest_record(
     writer: &mut AsyncVcfWriter,
     input_record: &vcf::Record,
+    event: Option<&str>,
+    bnd_event: Option<bnd::MateEvent>,
+    sexes: &[mehari::ped::Sex],
+    heteroplasmy: Option<&[Option<f32>]>,
 ) -> Result<(), anyhow::Error> {
     // copy over CHROM, POS, REF
     let mut builder = vcf::Record::builder()
@@ -129,22 +280,32 @@ async fn write_ingest_record(
                 .collect::<Vec<_>>()
         })
         .collect::<Vec<_>>();
-    let output_keys = vcf::record::genotypes::Keys::try_from(
-        input_record
-            .genotypes()
-            .keys()
-            .iter()
-            .filter(|k| k.as_ref() != "FT")
-            .cloned()
-            .map(|k| {
-                if k.as_ref() == "CN" {
-                    "cn".parse().expect("invalid key: cn")
-                } else {
-                    k
-                }
-            })
-            .collect::<Vec<_>>(),
-    )?;
+    let output_keys = input_record
+        .genotypes()
+        .keys()
+        .iter()
+        .filter(|k| k.as_ref() != "FT")
+        .cloned()
+        .map(|k| {
+            if k.as_ref() == "CN" {
+                "cn".parse().expect("invalid key: cn")
+            } else {
+                k
+            }
+        })
+        .collect::<Vec<_>>();
+    let (output_keys, output_format_values) =
+        split_mosaic_copy_number(output_keys, output_format_values)?;
+    let (output_keys, output_format_values) = derive_genotypes_from_copy_number(
+        &input_record.chromosome().to_string(),
+        sexes,
+        cn_callers_only(input_record),
+        output_keys,
+        output_format_values,
+    );
+    let (output_keys, output_format_values) =
+        add_heteroplasmy(output_keys, output_format_values, heteroplasmy)?;
+    let output_keys = vcf::record::genotypes::Keys::try_from(output_keys)?;
     builder = builder.set_genotypes(vcf::record::Genotypes::new(
         output_keys,
         output_format_values,
@@ -157,7 +318,10 @@ async fn write_ingest_record(
         vcf::record::alternate_bases::allele::symbol::structural_variant::Type::Deletion |
         vcf::record::alternate_bases::allele::symbol::structural_variant::Type::Duplication |
         vcf::record::alternate_bases::allele::symbol::structural_variant::Type::CopyNumberVariation => {
-            let claim = if keys_with_value.contains("pev") || keys_with_value.contains("srv") {
+            // Events resolved from BND mates only make claims about the adjacency.
+            let claim = if bnd_event.is_some() {
+                "J"
+            } else if keys_with_value.contains("pev") || keys_with_value.contains("srv") {
                 "DJ"
             } else {
                 "D"
@@ -267,6 +431,21 @@ async fn write_ingest_record(
         anyhow::bail!("no callers INFO tag found");
     }
 
+    if let Some(event) = event {
+        info.insert(
+            "event".parse()?,
+            Some(vcf::record::info::field::Value::String(event.to_string())),
+        );
+    }
+    if let Some(bnd_event) = bnd_event {
+        info.insert(
+            bnd::INFO_KEY.parse()?,
+            Some(vcf::record::info::field::Value::String(
+                bnd_event.to_string(),
+            )),
+        );
+    }
+
     builder = builder.set_info(info);
 
     let record = builder.build()?;
@@ -277,6 +456,26 @@ async fn write_ingest_record(
         .map_err(|e| anyhow::anyhow!("Error writing VCF record: {}", e))
 }
 
+/// Whether all callers of `record` are CNV callers that report copy numbers rather than
+/// genotypes.
+fn cn_callers_only(record: &vcf::Record) -> bool {
+    use vcf::record::info::field::{value::Array, Value};
+
+    let callers = match "callers"
+        .parse()
+        .ok()
+        .and_then(|key| record.info().get(&key).cloned().flatten())
+    {
+        Some(Value::Array(Array::String(callers))) => callers.into_iter().flatten().collect(),
+        Some(Value::String(caller)) => vec![caller],
+        _ => Vec::new(),
+    };
+    !callers.is_empty()
+        && callers
+            .iter()
+            .all(|caller| caller.starts_with("GATK_GCNVv") || caller.starts_with("DRAGEN_CNVv"))
+}
+
 /// Write out variants from input files.
 async fn process_variants(
     pedigree: &mehari::ped::PedigreeByName,
@@ -318,8 +517,35 @@ async fn process_variants(
     }
     tracing::info!("... done converting input files");
 
-    tracing::info!("clustering SVs to output...");
-    // Read through temporary files by contig, cluster by overlap as configured, and write to `writer`.
+    tracing::info!("reading heteroplasmy fractions of chrMT calls...");
+    let heteroplasmy_calls = mito::read_calls(&args.path_in).await?;
+    tracing::info!(
+        "... done reading {} chrMT calls with heteroplasmy fractions",
+        heteroplasmy_calls.len()
+    );
+
+    // The sexes of the samples in the order of the output records, for deriving the
+    // genotypes of CNV callers on the gonosomes.
+    let sample_names = input_header
+        .first()
+        .expect("must have at least one input file")
+        .sample_names();
+    let sexes = sample_names
+        .iter()
+        .map(|name| {
+            pedigree
+                .individuals
+                .get(name)
+                .map(|individual| individual.sex)
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+
+    tracing::info!("clustering SVs...");
+    // Read through temporary files by contig and cluster by overlap as configured.  The
+    // records are written out only after pairing the BND mates across all contigs.
+    let mut records = Vec::new();
+    let mut events = Vec::new();
     for contig_no in 1..=25 {
         tracing::info!(
             "  contig: {}",
@@ -332,11 +558,62 @@ async fn process_variants(
             args.slack_bnd,
             args.min_overlap,
         )?;
-        for record in clusters {
-            write_ingest_record(output_writer, &record.try_into()?).await?;
+        let contig_events =
+            events::detect_events(args.genomebuild, &clusters, args.min_event_fraction);
+        for (record, event) in &contig_events {
+            tracing::info!(
+                "    event {} at {}:{}-{}",
+                event,
+                &record.chromosome,
+                record.start,
+                record.end
+            );
         }
+
+        records.extend(clusters);
+        events.extend(contig_events);
+    }
+    tracing::info!("... done clustering SVs");
+
+    tracing::info!("pairing BND mates and writing output...");
+    let mut records = bnd::resolve_mates(records, args.slack_bnd)?
+        .into_iter()
+        .map(|(record, bnd_event)| (record, None, bnd_event))
+        .chain(
+            events
+                .into_iter()
+                .map(|(record, event)| (record, Some(event), None)),
+        )
+        .collect::<Vec<_>>();
+    records.sort_by_key(|(record, _, _)| (record.chromosome_no, record.start));
+    for (record, event, bnd_event) in records {
+        let heteroplasmy = (annonars::common::cli::canonicalize(&record.chromosome) == "MT")
+            .then(|| {
+                mito::best_match(
+                    &heteroplasmy_calls,
+                    record.start - 1,
+                    record.end,
+                    args.min_overlap,
+                )
+            })
+            .flatten()
+            .map(|call| {
+                sample_names
+                    .iter()
+                    .map(|name| call.fractions.get(name).copied())
+                    .collect::<Vec<_>>()
+            });
+        write_ingest_record(
+            output_writer,
+            &record.try_into()?,
+            event.as_deref(),
+            bnd_event,
+            &sexes,
+            heteroplasmy.as_deref(),
+        )
+        .await?;
     }
-    tracing::info!("... done clustering SVs to output");
+    tracing::info!("... done writing output");
 
     Ok(())
 }
@@ -400,8 +677,13 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
     )
     .map_err(|e| anyhow::anyhow!("problem building output header: {}", e))?;
 
+    let partial_guard = crate::common::partial::PartialOutputGuard::new(
+        &args.path_out,
+        crate::common::partial::OnFailure::Remove,
+    );
     {
-        let mut output_writer = open_vcf_writer(&args.path_out).await?;
+        let mut output_writer =
+            open_vcf_writer(&args.path_out, args_common.compression_level).await?;
         output_writer
             .write_header(&output_header)
             .await
@@ -420,6 +702,9 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
         flush_and_shutdown!(output_writer);
     }
 
+    if args.validate_output {
+        crate::common::validate::validate_vcf(&args.path_out).await?;
+    }
     if is_gz(&args.path_out) {
         tracing::info!("Creating TBI index for BGZF VCF file...");
         crate::common::noodles::build_tbi(&args.path_out, &format!("{}.tbi", &args.path_out))
@@ -429,6 +714,7 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
     } else {
         tracing::info!("(not building TBI index for plain text VCF file");
     }
+    partial_guard.finish();
 
     tracing::info!(
         "All of `strucvars ingest` completed in {:?}",
@@ -439,8 +725,98 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
 
 #[cfg(test)]
 mod test {
+    use noodles_vcf as vcf;
+    use vcf::record::genotypes::sample::Value;
+
     use crate::common::GenomeRelease;
 
+    #[rstest::rstest]
+    #[case("integer", vec![Some(Value::Integer(3)), Some(Value::Integer(2))])]
+    #[case("float", vec![Some(Value::Float(2.4)), Some(Value::Integer(2))])]
+    #[case("missing", vec![Some(Value::Float(1.7)), None])]
+    fn split_mosaic_copy_number(
+        #[case] label: &str,
+        #[case] cns: Vec<Option<Value>>,
+    ) -> Result<(), anyhow::Error> {
+        mehari::common::set_snapshot_suffix!("{}", label);
+
+        let keys = vec!["GT".parse()?, "cn".parse()?];
+        let values = cns
+            .into_iter()
+            .map(|cn| vec![Some(Value::String("0/1".into())), cn])
+            .collect();
+
+        let (keys, values) = super::split_mosaic_copy_number(keys, values)?;
+
+        let keys = keys.iter().map(|key| key.to_string()).collect::<Vec<_>>();
+        let values = values
+            .iter()
+            .map(|sample| format!("{:?}", &sample[1..]))
+            .collect::<Vec<_>>();
+        insta::assert_yaml_snapshot!((keys, values));
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case("1", mehari::ped::Sex::Female, 0, Some("1/1"))]
+    #[case("1", mehari::ped::Sex::Female, 1, Some("0/1"))]
+    #[case("1", mehari::ped::Sex::Male, 2, Some("0/0"))]
+    #[case("1", mehari::ped::Sex::Unknown, 3, Some("0/1"))]
+    #[case("1", mehari::ped::Sex::Male, 4, Some("./1"))]
+    #[case("chrX", mehari::ped::Sex::Male, 0, Some("1"))]
+    #[case("X", mehari::ped::Sex::Male, 1, Some("0"))]
+    #[case("X", mehari::ped::Sex::Male, 2, Some("1"))]
+    #[case("X", mehari::ped::Sex::Female, 1, Some("0/1"))]
+    #[case("X", mehari::ped::Sex::Unknown, 1, None)]
+    #[case("Y", mehari::ped::Sex::Female, 1, None)]
+    #[case("MT", mehari::ped::Sex::Male, 1, None)]
+    fn genotype_from_copy_number(
+        #[case] chrom: &str,
+        #[case] sex: mehari::ped::Sex,
+        #[case] cn: i32,
+        #[case] expected: Option<&str>,
+    ) {
+        assert_eq!(
+            super::expected_copy_number(chrom, sex)
+                .map(|expected_cn| super::genotype_from_copy_number(cn, expected_cn)),
+            expected
+        );
+    }
+
+    #[rstest::rstest]
+    #[case(false, "1", "0/0")]
+    #[case(true, "0/1", "0/0")]
+    fn derive_genotypes_from_copy_number(
+        #[case] cn_callers_only: bool,
+        #[case] gt_called: &str,
+        #[case] gt_derived: &str,
+    ) -> Result<(), anyhow::Error> {
+        let keys = vec!["GT".parse()?, "cn".parse()?];
+        let values = vec![
+            vec![
+                Some(Value::String(gt_called.into())),
+                Some(Value::Integer(2)),
+            ],
+            vec![Some(Value::String(".".into())), Some(Value::Integer(2))],
+        ];
+        let sexes = [mehari::ped::Sex::Male, mehari::ped::Sex::Female];
+
+        let (keys, values) =
+            super::derive_genotypes_from_copy_number("1", &sexes, cn_callers_only, keys, values);
+
+        assert_eq!(keys.len(), 2);
+        let expected_first = if cn_callers_only {
+            gt_derived
+        } else {
+            gt_called
+        };
+        assert_eq!(values[0][0], Some(Value::String(expected_first.into())));
+        assert_eq!(values[1][0], Some(Value::String(gt_derived.into())));
+
+        Ok(())
+    }
+
     #[tracing_test::traced_test]
     #[tokio::test]
     async fn smoke_test_trio() -> Result<(), anyhow::Error> {
@@ -449,6 +825,7 @@ mod test {
         let args_common = Default::default();
         let args = super::Args {
             max_var_count: None,
+            validate_output: true,
             path_in: vec![
                 String::from("tests/strucvars/ingest/delly2-min.vcf"),
                 String::from("tests/strucvars/ingest/popdel-min.vcf"),
@@ -464,6 +841,7 @@ mod test {
             min_overlap: 0.8,
             slack_bnd: 50,
             slack_ins: 50,
+            min_event_fraction: 0.9,
             rng_seed: Some(42),
             file_date: String::from("20230421"),
             case_uuid: String::from("d2bad2ec-a75d-44b9-bd0a-83a3f1331b7c"),
@@ -482,6 +860,7 @@ mod test {
         let args_common = Default::default();
         let args = super::Args {
             max_var_count: None,
+            validate_output: true,
             path_in: vec![
                 String::from("tests/strucvars/ingest/dragen-cnv-min.vcf"),
                 String::from("tests/strucvars/ingest/dragen-sv-min.vcf"),
@@ -501,6 +880,7 @@ mod test {
             min_overlap: 0.8,
             slack_bnd: 50,
             slack_ins: 50,
+            min_event_fraction: 0.9,
             rng_seed: Some(42),
             file_date: String::from("20230421"),
             case_uuid: String::from("d2bad2ec-a75d-44b9-bd0a-83a3f1331b7c"),
@@ -512,6 +892,55 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn heteroplasmy_mt_deletion() -> Result<(), anyhow::Error> {
+        use crate::strucvars::query::schema::StructuralVariant;
+
+        let tmpdir = temp_testdir::TempDir::default();
+
+        let args_common = Default::default();
+        let args = super::Args {
+            max_var_count: None,
+            validate_output: true,
+            path_in: vec![String::from("tests/strucvars/ingest/manta-mt-min.vcf")],
+            path_cov_vcf: vec![],
+            path_ped: "tests/strucvars/ingest/manta-mt-min.ped".into(),
+            genomebuild: GenomeRelease::Grch37,
+            path_out: tmpdir
+                .join("out.vcf")
+                .to_str()
+                .expect("invalid path")
+                .into(),
+            min_overlap: 0.8,
+            slack_bnd: 50,
+            slack_ins: 50,
+            min_event_fraction: 0.9,
+            rng_seed: Some(42),
+            file_date: String::from("20230421"),
+            case_uuid: String::from("d2bad2ec-a75d-44b9-bd0a-83a3f1331b7c"),
+        };
+        super::run(&args_common, &args).await?;
+
+        // The fraction must be read back by `strucvars query`.
+        let mut reader = vcf::reader::Builder::default().build_from_path(&args.path_out)?;
+        let header = reader.read_header()?;
+        let svs = reader
+            .records(&header)
+            .map(|record| StructuralVariant::from_vcf(&record?, &header))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(svs.len(), 1);
+        assert_eq!(svs[0].chrom, "MT");
+        assert_eq!(
+            svs[0]
+                .call_info
+                .get("SAMPLE")
+                .and_then(|call_info| call_info.heteroplasmy),
+            Some(0.35)
+        );
+
+        Ok(())
+    }
+
     #[tracing_test::traced_test]
     #[tokio::test]
     async fn smoke_test_trio_gz() -> Result<(), anyhow::Error> {
@@ -520,6 +949,7 @@ mod test {
         let args_common = Default::default();
         let args = super::Args {
             max_var_count: None,
+            validate_output: true,
             path_in: vec![
                 String::from("tests/strucvars/ingest/delly2-min.vcf.gz"),
                 String::from("tests/strucvars/ingest/popdel-min.vcf.gz"),
@@ -535,6 +965,7 @@ mod test {
             min_overlap: 0.8,
             slack_bnd: 50,
             slack_ins: 50,
+            min_event_fraction: 0.9,
             rng_seed: Some(42),
             file_date: String::from("20230421"),
             case_uuid: String::from("d2bad2ec-a75d-44b9-bd0a-83a3f1331b7c"),
@@ -555,6 +986,7 @@ mod test {
         let args_common = Default::default();
         let args = super::Args {
             max_var_count: None,
+            validate_output: true,
             path_in: vec![
                 String::from("tests/strucvars/ingest/dragen-cnv-min.vcf.gz"),
                 String::from("tests/strucvars/ingest/dragen-sv-min.vcf.gz"),
@@ -574,6 +1006,7 @@ mod test {
             min_overlap: 0.8,
             slack_bnd: 50,
             slack_ins: 50,
+            min_event_fraction: 0.9,
             rng_seed: Some(42),
             file_date: String::from("20230421"),
             case_uuid: String::from("d2bad2ec-a75d-44b9-bd0a-83a3f1331b7c"),
@@ -0,0 +1,393 @@
+//! Implementation of `strucvars merge` subcommand.
+//!
+//! Merges ingested VCF files of the same case from different callers, e.g., Manta,
+//! DRAGEN-CNV, and Delly.  Calls of the same type whose confidence intervals around the
+//! start and end positions overlap and that share a carrier sample are merged into one
+//! record that lists all supporting callers in `INFO/callers`.
+
+use futures::TryStreamExt as _;
+use mehari::common::io::std::is_gz;
+use mehari::common::noodles::open_vcf_readers;
+use noodles_vcf as vcf;
+use tokio::io::AsyncWriteExt;
+use vcf::record::info::field::{key, value::Array, Value};
+
+use crate::common::noodles::open_vcf_writer;
+use crate::flush_and_shutdown;
+
+/// Command line arguments for `strucvars merge` subcommand.
+#[derive(Debug, clap::Parser)]
+#[command(author, version, about = "merge ingested structural variant VCFs of multiple callers", long_about = None)]
+pub struct Args {
+    /// Path to the ingested VCF files of the case, e.g., one per caller.
+    #[clap(long, required = true)]
+    pub path_in: Vec<String>,
+    /// Path to output file.
+    #[clap(long)]
+    pub path_out: String,
+    /// Slack to use around start and end of calls without `INFO/CIPOS` and `INFO/CIEND`.
+    #[arg(long, default_value_t = 50)]
+    pub slack: i32,
+}
+
+/// A call from one of the input files, prepared for clustering.
+#[derive(Debug, Clone)]
+struct Call {
+    /// The input record.
+    record: vcf::Record,
+    /// Index of the chromosome in the header contigs.
+    contig_no: usize,
+    /// Value of `INFO/SVTYPE`.
+    sv_type: String,
+    /// Value of `INFO/chr2`, if any.
+    chr2: Option<String>,
+    /// Confidence interval around the start position.
+    ci_start: (i32, i32),
+    /// Confidence interval around the end position.
+    ci_end: (i32, i32),
+    /// Whether each sample carries the variant.
+    carriers: Vec<bool>,
+}
+
+impl Call {
+    /// Prepare `record` from a file with `header` for clustering.
+    fn new(record: vcf::Record, header: &vcf::Header, slack: i32) -> Result<Self, anyhow::Error> {
+        let chrom = record.chromosome().to_string();
+        let contig_no = header
+            .contigs()
+            .get_index_of(chrom.as_str())
+            .ok_or_else(|| anyhow::anyhow!("contig {} not in header", &chrom))?;
+        let sv_type = match record.info().get(&key::SV_TYPE) {
+            Some(Some(Value::String(sv_type))) => sv_type.clone(),
+            _ => anyhow::bail!(
+                "no INFO/SVTYPE in record at {}:{}",
+                &chrom,
+                record.position()
+            ),
+        };
+        let key_chr2: key::Key = "chr2".parse()?;
+        let chr2 = match record.info().get(&key_chr2) {
+            Some(Some(Value::String(chr2))) => Some(chr2.clone()),
+            _ => None,
+        };
+
+        let start = usize::from(record.position()) as i32;
+        let end = match record.info().get(&key::END_POSITION) {
+            Some(Some(Value::Integer(end))) => *end,
+            _ => start,
+        };
+        let ci = |pos: i32, key: &key::Key| match record.info().get(key) {
+            Some(Some(Value::Array(Array::Integer(ci)))) if ci.len() == 2 => (
+                pos + ci[0].unwrap_or_default(),
+                pos + ci[1].unwrap_or_default(),
+            ),
+            _ => (pos - slack, pos + slack),
+        };
+        let ci_start = ci(start, &key::POSITION_CONFIDENCE_INTERVALS);
+        let ci_end = ci(end, &key::END_CONFIDENCE_INTERVALS);
+
+        let carriers = record
+            .genotypes()
+            .values()
+            .map(
+                |sample| match sample.get(&vcf::record::genotypes::keys::key::GENOTYPE) {
+                    Some(Some(vcf::record::genotypes::sample::Value::String(gt))) => gt
+                        .split(['/', '|'])
+                        .any(|allele| allele != "0" && allele != "."),
+                    _ => false,
+                },
+            )
+            .collect();
+
+        Ok(Self {
+            record,
+            contig_no,
+            sv_type,
+            chr2,
+            ci_start,
+            ci_end,
+            carriers,
+        })
+    }
+
+    /// Whether `self` and `other` describe the same variant and can be merged.
+    fn matches(&self, other: &Call) -> bool {
+        let overlaps = |a: (i32, i32), b: (i32, i32)| a.0 <= b.1 && b.0 <= a.1;
+        self.contig_no == other.contig_no
+            && self.sv_type == other.sv_type
+            && self.chr2 == other.chr2
+            && overlaps(self.ci_start, other.ci_start)
+            && overlaps(self.ci_end, other.ci_end)
+            && self
+                .carriers
+                .iter()
+                .zip(other.carriers.iter())
+                .any(|(a, b)| *a && *b)
+    }
+}
+
+/// Cluster `calls` such that each call matches the first call of its cluster.
+///
+/// The clusters are returned sorted by the position of their first call.
+fn cluster(mut calls: Vec<Call>) -> Vec<Vec<Call>> {
+    calls.sort_by_key(|call| (call.contig_no, call.ci_start.0));
+
+    let mut clusters: Vec<Vec<Call>> = Vec::new();
+    // Index of the first cluster whose first call may still match the following calls.
+    let mut first_open = 0;
+    for call in calls {
+        while first_open < clusters.len() && {
+            let first = &clusters[first_open][0];
+            first.contig_no != call.contig_no || first.ci_start.1 < call.ci_start.0
+        } {
+            first_open += 1;
+        }
+        match clusters[first_open..]
+            .iter_mut()
+            .find(|cluster| cluster[0].matches(&call))
+        {
+            Some(cluster) => cluster.push(call),
+            None => clusters.push(vec![call]),
+        }
+    }
+
+    clusters.sort_by_key(|cluster| {
+        (
+            cluster[0].contig_no,
+            usize::from(cluster[0].record.position()),
+        )
+    });
+    clusters
+}
+
+/// Return the string values of the INFO field `key` of `record`.
+fn info_strings(record: &vcf::Record, key: &key::Key) -> Vec<String> {
+    match record.info().get(key) {
+        Some(Some(Value::Array(Array::String(values)))) => {
+            values.iter().flatten().cloned().collect()
+        }
+        Some(Some(Value::String(value))) => vec![value.clone()],
+        _ => Vec::new(),
+    }
+}
+
+/// Merge the calls of `cluster` into one record.
+///
+/// The first call provides position and type.  The callers and SV claims are combined
+/// and each `FORMAT` value is taken from the first call that has it.
+fn merge(cluster: &[Call]) -> Result<vcf::Record, anyhow::Error> {
+    let mut record = cluster[0].record.clone();
+    if cluster.len() == 1 {
+        return Ok(record);
+    }
+
+    let key_callers: key::Key = "callers".parse()?;
+    let callers = cluster
+        .iter()
+        .flat_map(|call| info_strings(&call.record, &key_callers))
+        .collect::<indexmap::IndexSet<_>>();
+    record.info_mut().insert(
+        key_callers,
+        Some(Value::Array(Array::String(
+            callers.into_iter().map(Some).collect(),
+        ))),
+    );
+
+    let claims = cluster
+        .iter()
+        .flat_map(|call| info_strings(&call.record, &key::SV_CLAIM))
+        .collect::<String>();
+    if !claims.is_empty() {
+        let claim = ["D", "J"]
+            .into_iter()
+            .filter(|claim| claims.contains(*claim))
+            .collect::<String>();
+        record.info_mut().insert(
+            key::SV_CLAIM,
+            Some(Value::Array(Array::String(vec![Some(claim)]))),
+        );
+    }
+
+    let mut keys = cluster
+        .iter()
+        .flat_map(|call| call.record.genotypes().keys().iter().cloned())
+        .collect::<indexmap::IndexSet<_>>();
+    if keys.shift_remove(&vcf::record::genotypes::keys::key::GENOTYPE) {
+        keys.shift_insert(0, vcf::record::genotypes::keys::key::GENOTYPE);
+    }
+    let values = (0..cluster[0].carriers.len())
+        .map(|sample_no| {
+            keys.iter()
+                .map(|key| {
+                    cluster.iter().find_map(|call| {
+                        call.record
+                            .genotypes()
+                            .get_index(sample_no)
+                            .and_then(|sample| sample.get(key).flatten().cloned())
+                    })
+                })
+                .collect()
+        })
+        .collect();
+    *record.genotypes_mut() = vcf::record::Genotypes::new(
+        vcf::record::genotypes::Keys::try_from(keys.into_iter().collect::<Vec<_>>())?,
+        values,
+    );
+
+    Ok(record)
+}
+
+/// Build the output header from the first input header, adding the caller versions of
+/// the other input headers.
+fn build_output_header(input_headers: &[vcf::Header]) -> Result<vcf::Header, anyhow::Error> {
+    use vcf::header::record::value::Collection;
+
+    let key_version: vcf::header::record::key::Other = "x-varfish-version".parse()?;
+    let mut output_header = input_headers[0].clone();
+    for input_header in &input_headers[1..] {
+        if let Some(Collection::Structured(versions)) = input_header.get(&key_version) {
+            for (id, map) in versions {
+                let known = matches!(
+                    output_header.get(&key_version),
+                    Some(Collection::Structured(known)) if known.contains_key(id)
+                );
+                if !known {
+                    output_header.insert(
+                        key_version.clone(),
+                        vcf::header::record::Value::Map(id.clone(), map.clone()),
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(output_header)
+}
+
+/// Main entry point for `strucvars merge` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    let before_anything = std::time::Instant::now();
+    tracing::info!("args_common = {:#?}", &args_common);
+    tracing::info!("args = {:#?}", &args);
+
+    tracing::info!("reading input files...");
+    let mut input_readers = open_vcf_readers(&args.path_in).await?;
+    let mut input_headers = Vec::new();
+    let mut calls = Vec::new();
+    for (indexno, input_reader) in input_readers.iter_mut().enumerate() {
+        let input_header = input_reader
+            .read_header()
+            .await
+            .map_err(|e| anyhow::anyhow!("problem reading header: {}", e))?;
+        if let Some(first_header) = input_headers.first() {
+            if input_header.sample_names() != first_header.sample_names() {
+                anyhow::bail!(
+                    "input file #{} has different sample names than first one: {}",
+                    indexno,
+                    &args.path_in[indexno]
+                );
+            }
+        }
+        let mut records = input_reader.records(&input_header);
+        while let Some(record) = records
+            .try_next()
+            .await
+            .map_err(|e| anyhow::anyhow!("problem reading VCF: {}", e))?
+        {
+            calls.push(Call::new(record, &input_header, args.slack)?);
+        }
+        input_headers.push(input_header);
+    }
+    tracing::info!("... done reading {} calls", calls.len());
+
+    tracing::info!("merging calls...");
+    let clusters = cluster(calls);
+    tracing::info!("... done merging into {} calls", clusters.len());
+
+    let output_header = build_output_header(&input_headers)?;
+    let partial_guard = crate::common::partial::PartialOutputGuard::new(
+        &args.path_out,
+        crate::common::partial::OnFailure::Remove,
+    );
+    {
+        let mut output_writer =
+            open_vcf_writer(&args.path_out, args_common.compression_level).await?;
+        output_writer
+            .write_header(&output_header)
+            .await
+            .map_err(|e| anyhow::anyhow!("problem writing header: {}", e))?;
+        for cluster in &clusters {
+            output_writer
+                .write_record(&merge(cluster)?)
+                .await
+                .map_err(|e| anyhow::anyhow!("problem writing VCF record: {}", e))?;
+        }
+        flush_and_shutdown!(output_writer);
+    }
+
+    if is_gz(&args.path_out) {
+        tracing::info!("Creating TBI index for BGZF VCF file...");
+        crate::common::noodles::build_tbi(&args.path_out, &format!("{}.tbi", &args.path_out))
+            .await
+            .map_err(|e| anyhow::anyhow!("problem building TBI: {}", e))?;
+        tracing::info!("... done writing TBI index");
+    }
+    partial_guard.finish();
+
+    tracing::info!(
+        "All of `strucvars merge` completed in {:?}",
+        before_anything.elapsed()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    #[tokio::test]
+    async fn smoke_test() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_out = tmpdir.join("out.vcf");
+
+        let args = super::Args {
+            path_in: vec![
+                "tests/strucvars/merge/manta.vcf".into(),
+                "tests/strucvars/merge/gcnv.vcf".into(),
+            ],
+            path_out: path_out.to_str().unwrap().into(),
+            slack: 50,
+        };
+        super::run(&crate::common::Args::default(), &args).await?;
+
+        let output = std::fs::read_to_string(&path_out)?;
+        assert!(output.contains("##x-varfish-version=<ID=Gcnv,"));
+        let records = output
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| line.split('\t').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        // The overlapping deletions with a shared carrier are merged, the ones called in
+        // different samples are kept apart.
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| (record[0], record[1]))
+                .collect::<Vec<_>>(),
+            vec![
+                ("1", "1000"),
+                ("1", "100000"),
+                ("1", "100010"),
+                ("2", "5000")
+            ]
+        );
+        assert_eq!(
+            records[0][7],
+            "SVCLAIM=DJ;SVTYPE=DEL;END=5000;SVLEN=4001;callers=Manta,Gcnv"
+        );
+        assert_eq!(records[0][9], "0/1:30:20:8:25:6:.:1:.:12");
+        assert_eq!(
+            records[1][7],
+            "SVCLAIM=DJ;SVTYPE=DEL;END=200000;SVLEN=100001;callers=Manta"
+        );
+
+        Ok(())
+    }
+}
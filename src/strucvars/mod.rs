@@ -1,5 +1,6 @@
 pub mod aggregate;
 pub mod ingest;
+pub mod merge;
 pub mod pbs;
 pub mod query;
 pub mod txt_to_bin;
@@ -0,0 +1,355 @@
+//! Preliminary ACMG/ClinGen CNV scoring of deletions and duplications.
+//!
+//! This implements the semi-automatable parts of the scoring rubric by Riggs et al. (2020),
+//! i.e., the genomic content (section 1), the overlap with dosage-sensitive and benign
+//! genes and regions from the ClinGen dosage sensitivity curation (section 2), and the
+//! number of protein-coding genes (section 3).  The case-level evidence (section 4) and the
+//! inheritance (section 5) are not scored, so the classification is preliminary.
+//!
+//! Without the ClinGen curation lists, only the genomic content and the number of genes
+//! are scored.
+
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use super::{
+    dbrecords::clingen_dosage::{CurationKind, DosageDb, Record},
+    schema::{StructuralVariant, SvType},
+};
+
+/// Dosage score for sufficient evidence of dosage sensitivity.
+const SCORE_SUFFICIENT: u32 = 3;
+/// Dosage score for dosage sensitivity unlikely, i.e., benign.
+const SCORE_UNLIKELY: u32 = 40;
+
+/// Classification derived from the total score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Classification {
+    /// Total score of 0.99 or more.
+    Pathogenic,
+    /// Total score from 0.90 to 0.98.
+    LikelyPathogenic,
+    /// Total score from -0.89 to 0.89.
+    UncertainSignificance,
+    /// Total score from -0.98 to -0.90.
+    LikelyBenign,
+    /// Total score of -0.99 or less.
+    Benign,
+}
+
+impl Classification {
+    /// Classify the total `score`.
+    fn from_score(score: f32) -> Self {
+        if score >= 0.99 {
+            Classification::Pathogenic
+        } else if score >= 0.90 {
+            Classification::LikelyPathogenic
+        } else if score > -0.90 {
+            Classification::UncertainSignificance
+        } else if score > -0.99 {
+            Classification::LikelyBenign
+        } else {
+            Classification::Benign
+        }
+    }
+}
+
+/// One piece of evidence of the scoring.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Evidence {
+    /// Code of the evidence in the rubric, e.g., "2A".
+    pub code: String,
+    /// Points of the evidence.
+    pub points: f32,
+    /// Human-readable description of the evidence.
+    pub description: String,
+}
+
+/// Preliminary score of a deletion or duplication.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AcmgCnvScore {
+    /// Sum of the points of the evidence.
+    pub score: f32,
+    /// Classification derived from the score.
+    pub classification: Classification,
+    /// The evidence the score is based on.
+    pub evidence: Vec<Evidence>,
+}
+
+/// Gene content of a CNV from the transcript database.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GeneContent {
+    /// Number of protein-coding genes wholly or partially included.
+    pub protein_coding_genes: usize,
+    /// Whether a breakpoint lies within a protein-coding gene.
+    pub breakpoint_in_coding_gene: bool,
+}
+
+/// Join the names of `records` for descriptions.
+fn names(records: &[&Record]) -> String {
+    records
+        .iter()
+        .map(|record| format!("{} {}", record.kind, record.name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Compute the preliminary score of `sv` with the given `gene_content` and the ClinGen
+/// curation in `db`.
+///
+/// Returns `None` for SVs other than deletions and duplications.
+pub fn score(
+    db: &DosageDb,
+    sv: &StructuralVariant,
+    chrom_map: &IndexMap<String, usize>,
+    gene_content: &GeneContent,
+) -> Option<AcmgCnvScore> {
+    let loss = match sv.sv_type {
+        SvType::Del => true,
+        SvType::Dup => false,
+        _ => return None,
+    };
+    let (hi_or_ts, kind) = if loss { ("HI", "loss") } else { ("TS", "gain") };
+    let chrom_idx = *chrom_map.get(&sv.chrom).expect("invalid chromosome");
+    let (begin, end) = (sv.pos.saturating_sub(1), sv.end);
+    let records = db.overlapping_records(chrom_idx, begin, end);
+    let contained = |record: &&Record| begin <= record.begin && record.end <= end;
+
+    let mut evidence = Vec::new();
+    let mut add = |code: &str, points: f32, description: String| {
+        evidence.push(Evidence {
+            code: code.into(),
+            points,
+            description,
+        })
+    };
+
+    // Section 1: initial assessment of genomic content.
+    let dosage_sensitive = records.iter().any(|record| {
+            matches!(record.dosage_score(loss), Some(score) if (1..=SCORE_SUFFICIENT).contains(&score))
+        });
+    if gene_content.protein_coding_genes > 0 || dosage_sensitive {
+        add(
+            "1A",
+            0.0,
+            "contains protein-coding or other known functionally important elements".into(),
+        );
+    } else {
+        add(
+            "1B",
+            -0.60,
+            "does not contain protein-coding or other known functionally important elements".into(),
+        );
+    }
+
+    // Section 2: overlap with established dosage-sensitive or benign genes and regions.
+    let (established_complete, established_partial): (Vec<&Record>, Vec<&Record>) = records
+        .iter()
+        .copied()
+        .filter(|record| record.dosage_score(loss) == Some(SCORE_SUFFICIENT))
+        .partition(contained);
+    if !established_complete.is_empty() {
+        add(
+            "2A",
+            1.0,
+            format!(
+                "complete overlap of established {} {}",
+                hi_or_ts,
+                names(&established_complete)
+            ),
+        );
+    } else {
+        let (partial_genes, partial_regions): (Vec<&Record>, Vec<&Record>) = established_partial
+            .into_iter()
+            .partition(|record| record.kind == CurationKind::Gene);
+        if !partial_regions.is_empty() {
+            add(
+                "2B",
+                0.0,
+                format!(
+                    "partial overlap of established {} {}",
+                    hi_or_ts,
+                    names(&partial_regions)
+                ),
+            );
+        }
+        if loss && !partial_genes.is_empty() {
+            add(
+                "2C-2E",
+                0.0,
+                format!(
+                    "partial overlap of established HI {}; evaluate the involved exons \
+                        manually",
+                    names(&partial_genes)
+                ),
+            );
+        }
+    }
+    if !loss {
+        let (hi_complete, hi_partial): (Vec<&Record>, Vec<&Record>) = records
+            .iter()
+            .copied()
+            .filter(|record| {
+                record.kind == CurationKind::Gene && record.hi_score == Some(SCORE_SUFFICIENT)
+            })
+            .partition(contained);
+        if !hi_complete.is_empty() {
+            add(
+                "2H",
+                0.0,
+                format!("established HI {} fully contained", names(&hi_complete)),
+            );
+        }
+        if !hi_partial.is_empty() {
+            add(
+                "2I-2L",
+                0.0,
+                format!(
+                    "breakpoint within established HI {}; evaluate manually",
+                    names(&hi_partial)
+                ),
+            );
+        }
+    }
+    let benign = records
+        .iter()
+        .copied()
+        .filter(|record| {
+            record.kind == CurationKind::Region && record.dosage_score(loss) == Some(SCORE_UNLIKELY)
+        })
+        .collect::<Vec<_>>();
+    let benign_containing = benign
+        .iter()
+        .filter(|record| record.begin <= begin && end <= record.end)
+        .copied()
+        .collect::<Vec<_>>();
+    if !benign_containing.is_empty() {
+        let description = format!(
+            "completely contained within established benign {} {}",
+            kind,
+            names(&benign_containing)
+        );
+        if loss {
+            add("2F", -1.0, description);
+        } else if !gene_content.breakpoint_in_coding_gene {
+            add("2D", -1.0, description);
+        } else {
+            add(
+                "2E",
+                0.0,
+                format!("{}, breakpoint within protein-coding gene", description),
+            );
+        }
+    } else if !benign.is_empty() {
+        add(
+            "2G",
+            0.0,
+            format!(
+                "overlaps established benign {} {} with additional material",
+                kind,
+                names(&benign)
+            ),
+        );
+    }
+
+    // Section 3: number of protein-coding genes.
+    let (limit_b, limit_c) = if loss { (25, 35) } else { (35, 50) };
+    let (code, points) = match gene_content.protein_coding_genes {
+        n if n >= limit_c => ("3C", 0.90),
+        n if n >= limit_b => ("3B", 0.45),
+        _ => ("3A", 0.0),
+    };
+    add(
+        code,
+        points,
+        format!("{} protein-coding genes", gene_content.protein_coding_genes),
+    );
+
+    let score =
+        (evidence.iter().map(|evidence| evidence.points).sum::<f32>() * 100.0).round() / 100.0;
+    Some(AcmgCnvScore {
+        score,
+        classification: Classification::from_score(score),
+        evidence,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        common::build_chrom_map,
+        strucvars::query::{fixtures::build_sv, schema::SvType},
+    };
+
+    use crate::strucvars::query::dbrecords::clingen_dosage::test::load_db;
+
+    use super::{Classification, GeneContent};
+
+    #[rstest::rstest]
+    #[case::del_hi_gene(SvType::Del, 5_000, 25_000, 1, vec!["1A", "2A", "3A"], 1.0)]
+    #[case::del_hi_gene_partial(SvType::Del, 15_000, 25_000, 1, vec!["1A", "2C-2E", "3A"], 0.0)]
+    #[case::del_no_genes(SvType::Del, 50_000, 60_000, 0, vec!["1B", "3A"], -0.6)]
+    #[case::del_benign(SvType::Del, 120_000, 130_000, 0, vec!["1B", "2F", "3A"], -1.6)]
+    #[case::del_benign_larger(SvType::Del, 90_000, 130_000, 0, vec!["1B", "2G", "3A"], -0.6)]
+    #[case::del_many_genes(SvType::Del, 50_000, 60_000, 40, vec!["1A", "3C"], 0.9)]
+    #[case::dup_ts_gene(SvType::Dup, 5_000, 45_000, 2, vec!["1A", "2A", "2H", "3A"], 1.0)]
+    #[case::dup_hi_gene(SvType::Dup, 5_000, 25_000, 1, vec!["1A", "2H", "3A"], 0.0)]
+    #[case::dup_benign(SvType::Dup, 120_000, 130_000, 0, vec!["1B", "2D", "3A"], -1.6)]
+    #[case::dup_many_genes(SvType::Dup, 50_000, 60_000, 40, vec!["1A", "3B"], 0.45)]
+    fn score(
+        #[case] sv_type: SvType,
+        #[case] pos: i32,
+        #[case] end: i32,
+        #[case] protein_coding_genes: usize,
+        #[case] expected_codes: Vec<&str>,
+        #[case] expected_score: f32,
+    ) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let db = load_db(&tmpdir)?;
+        let sv = build_sv("1", sv_type, pos, end);
+        let gene_content = GeneContent {
+            protein_coding_genes,
+            breakpoint_in_coding_gene: false,
+        };
+
+        let score =
+            super::score(&db, &sv, &build_chrom_map(), &gene_content).expect("must be scored");
+
+        assert_eq!(
+            score
+                .evidence
+                .iter()
+                .map(|evidence| evidence.code.as_str())
+                .collect::<Vec<_>>(),
+            expected_codes
+        );
+        assert_eq!(score.score, expected_score);
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case(1.0, Classification::Pathogenic)]
+    #[case(0.9, Classification::LikelyPathogenic)]
+    #[case(0.0, Classification::UncertainSignificance)]
+    #[case(-0.9, Classification::LikelyBenign)]
+    #[case(-1.6, Classification::Benign)]
+    fn classification(#[case] score: f32, #[case] expected: Classification) {
+        assert_eq!(Classification::from_score(score), expected);
+    }
+
+    #[test]
+    fn score_other_sv_type() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let db = load_db(&tmpdir)?;
+        let sv = build_sv("1", SvType::Inv, 5_000, 25_000);
+
+        assert_eq!(
+            super::score(&db, &sv, &build_chrom_map(), &GeneContent::default()),
+            None
+        );
+
+        Ok(())
+    }
+}
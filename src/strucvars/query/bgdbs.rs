@@ -1,6 +1,10 @@
 //! Background database overlapping.
+//!
+//! The release of a background database is read from the optional text file
+//! `<name>.bin.version` next to its `.bin` file, e.g., `dgv.bin.version`, such that
+//! differences in counts between sites can be traced to database releases.
 
-use std::{ops::Range, path::Path, time::Instant};
+use std::{path::Path, time::Instant};
 
 use bio::data_structures::interval_tree::ArrayBackedIntervalTree;
 use indexmap::IndexMap;
@@ -10,7 +14,7 @@ use strum_macros::{Display, EnumString};
 use tracing::info;
 
 use crate::{
-    common::{trace_rss_now, GenomeRelease, CHROMS},
+    common::{interval::Topology, reciprocal_overlap, trace_rss_now, GenomeRelease, CHROMS},
     strucvars::pbs,
 };
 
@@ -26,23 +30,6 @@ pub trait BeginEnd {
     fn end(&self) -> i32;
 }
 
-pub fn reciprocal_overlap(lhs: &impl BeginEnd, rhs: &Range<i32>) -> f32 {
-    let lhs_b = lhs.begin();
-    let lhs_e = lhs.end();
-    let rhs_b = rhs.start;
-    let rhs_e = rhs.end;
-    let ovl_b = std::cmp::max(lhs_b, rhs_b);
-    let ovl_e = std::cmp::min(lhs_e, rhs_e);
-    if ovl_b >= ovl_e {
-        0f32
-    } else {
-        let ovl_len = (ovl_e - ovl_b) as f32;
-        let x1 = ovl_len / (lhs_e - lhs_b) as f32;
-        let x2 = ovl_len / (rhs_e - rhs_b) as f32;
-        x1.min(x2)
-    }
-}
-
 /// Alias for the interval tree that we use.
 type IntervalTree = ArrayBackedIntervalTree<i32, u32>;
 
@@ -53,6 +40,10 @@ pub struct BgDb {
     pub records: Vec<Vec<BgDbRecord>>,
     /// Interval trees, stored by chromosome.
     pub trees: Vec<IntervalTree>,
+    /// Names of the populations that `BgDbRecord::population_counts` refers to.
+    pub populations: Vec<String>,
+    /// Release of the database, if known.
+    pub version: Option<String>,
 }
 
 impl BgDb {
@@ -74,15 +65,16 @@ impl BgDb {
             .collect()
     }
 
-    pub fn count_overlaps(
-        &self,
+    /// Return the records overlapping with `sv` that are compatible with its type.
+    fn overlapping_records<'a>(
+        &'a self,
         chrom_map: &IndexMap<String, usize>,
         enabled: bool,
         min_overlap: Option<f32>,
         slack_ins: i32,
         slack_bnd: i32,
         sv: &StructuralVariant,
-    ) -> u32 {
+    ) -> impl Iterator<Item = &'a BgDbRecord> + 'a {
         let chrom_idx = *chrom_map.get(&sv.chrom).expect("invalid chromosome");
         let range = if sv.sv_type == SvType::Ins {
             (sv.pos - slack_ins)..(sv.pos + slack_ins)
@@ -91,23 +83,94 @@ impl BgDb {
         } else {
             (sv.pos - 1)..sv.end
         };
+        let sv_type = sv.sv_type;
+        let topology = Topology::from_chrom(&sv.chrom);
 
         self.trees[chrom_idx]
             .find(range.clone())
-            .iter()
-            .map(|e| &self.records[chrom_idx][*e.data() as usize])
-            .filter(|record| record.sv_type.is_compatible(sv.sv_type))
-            .filter(|record| {
+            .into_iter()
+            .map(move |e| &self.records[chrom_idx][*e.data() as usize])
+            .filter(move |record| record.sv_type.is_compatible(sv_type))
+            .filter(move |record| {
                 enabled
                     && (record.sv_type == SvType::Ins
                         || record.sv_type == SvType::Bnd
                         || min_overlap.map_or(true, |min_overlap| {
-                            (reciprocal_overlap(*record, &range)) >= min_overlap
+                            reciprocal_overlap(
+                                record.begin()..record.end(),
+                                range.clone(),
+                                topology,
+                            ) >= min_overlap
                         }))
             })
+    }
+
+    pub fn count_overlaps(
+        &self,
+        chrom_map: &IndexMap<String, usize>,
+        enabled: bool,
+        min_overlap: Option<f32>,
+        slack_ins: i32,
+        slack_bnd: i32,
+        sv: &StructuralVariant,
+    ) -> u32 {
+        self.overlapping_records(chrom_map, enabled, min_overlap, slack_ins, slack_bnd, sv)
             .map(|record| record.count)
             .sum::<u32>()
     }
+
+    /// Count overlaps by population, empty if the database has no population counts.
+    pub fn count_population_overlaps(
+        &self,
+        chrom_map: &IndexMap<String, usize>,
+        enabled: bool,
+        min_overlap: Option<f32>,
+        slack_ins: i32,
+        slack_bnd: i32,
+        sv: &StructuralVariant,
+    ) -> IndexMap<String, u32> {
+        if self.populations.is_empty() || !enabled {
+            return IndexMap::new();
+        }
+
+        let mut counts = vec![0u32; self.populations.len()];
+        for record in
+            self.overlapping_records(chrom_map, enabled, min_overlap, slack_ins, slack_bnd, sv)
+        {
+            for (count, record_count) in counts.iter_mut().zip(record.population_counts.iter()) {
+                *count += record_count;
+            }
+        }
+
+        self.populations.iter().cloned().zip(counts).collect()
+    }
+
+    /// Return the maximal allele frequency of the overlapping records by population,
+    /// empty if the database has no population counts.
+    pub fn max_population_afs(
+        &self,
+        chrom_map: &IndexMap<String, usize>,
+        enabled: bool,
+        min_overlap: Option<f32>,
+        slack_ins: i32,
+        slack_bnd: i32,
+        sv: &StructuralVariant,
+    ) -> IndexMap<String, f32> {
+        if self.populations.is_empty() || !enabled {
+            return IndexMap::new();
+        }
+
+        let mut afs = vec![0f32; self.populations.len()];
+        for record in
+            self.overlapping_records(chrom_map, enabled, min_overlap, slack_ins, slack_bnd, sv)
+        {
+            for (af, record_af) in afs.iter_mut().zip(record.population_afs.iter()) {
+                *af = af.max(*record_af);
+            }
+        }
+
+        self.populations.iter().cloned().zip(afs).collect()
+    }
 }
 
 /// Information to store for background database.
@@ -121,6 +184,12 @@ pub struct BgDbRecord {
     pub sv_type: SvType,
     /// Count associated with the record.
     pub count: u32,
+    /// Population-specific counts, indexed as `BgDb::populations`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub population_counts: Vec<u32>,
+    /// Population-specific allele frequencies, indexed as `BgDb::populations`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub population_afs: Vec<f32>,
 }
 
 impl BeginEnd for BgDbRecord {
@@ -133,13 +202,40 @@ impl BeginEnd for BgDbRecord {
     }
 }
 
+/// Return the index of `population` in `populations`, appending it if necessary.
+fn population_index(populations: &mut Vec<String>, population: &str) -> usize {
+    if let Some(idx) = populations.iter().position(|p| p == population) {
+        idx
+    } else {
+        populations.push(population.to_string());
+        populations.len() - 1
+    }
+}
+
+/// Read the release of the database at `path` from `<path>.version`, if any.
+fn read_version(path: &Path) -> Result<Option<String>, anyhow::Error> {
+    let mut path_version = path.as_os_str().to_owned();
+    path_version.push(".version");
+    let path_version = Path::new(&path_version);
+    if !path_version.exists() {
+        return Ok(None);
+    }
+    let version = std::fs::read_to_string(path_version)
+        .map_err(|e| anyhow::anyhow!("error reading {:?}: {}", &path_version, e))?;
+    let version = version.trim();
+    Ok((!version.is_empty()).then(|| version.to_string()))
+}
+
 /// Load background database from a `.bin` file as created by `strucvar txt-to-bin`.
 #[tracing::instrument]
 pub fn load_bg_db_records(path: &Path) -> Result<BgDb, anyhow::Error> {
     tracing::debug!("loading binary bg db records from {:?}", path);
 
     let before_loading = Instant::now();
-    let mut result = BgDb::default();
+    let mut result = BgDb {
+        version: read_version(path)?,
+        ..Default::default()
+    };
     for _ in CHROMS {
         result.records.push(Vec::new());
         result.trees.push(IntervalTree::new());
@@ -176,7 +272,21 @@ pub fn load_bg_db_records(path: &Path) -> Result<BgDb, anyhow::Error> {
                 pbs::SvType::Cnv => SvType::Cnv,
             },
             count: record.count,
+            population_counts: Vec::new(),
+            population_afs: Vec::new(),
         });
+        let bg_db_record = result.records[chrom_no]
+            .last_mut()
+            .expect("just pushed the record");
+        for population_count in &record.population_counts {
+            let idx = population_index(&mut result.populations, &population_count.population);
+            if bg_db_record.population_counts.len() <= idx {
+                bg_db_record.population_counts.resize(idx + 1, 0);
+                bg_db_record.population_afs.resize(idx + 1, 0f32);
+            }
+            bg_db_record.population_counts[idx] = population_count.count;
+            bg_db_record.population_afs[idx] = population_count.af;
+        }
     }
     tracing::debug!(
         "done loading background db with {} records from {:?} in {:?}",
@@ -195,10 +305,11 @@ pub fn load_bg_db_records(path: &Path) -> Result<BgDb, anyhow::Error> {
 }
 
 /// Enumeration of background database types.
-#[derive(Serialize, Deserialize, Debug, PartialEq, EnumString, Display)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, Display)]
 #[serde(rename_all = "kebab-case")]
 #[strum(serialize_all = "kebab-case")]
 pub enum BgDbType {
+    Dbvar,
     Dgv,
     DgvGs,
     G1k,
@@ -229,9 +340,49 @@ pub struct BgDbOverlaps {
     pub gnomad_genomes: u32,
     pub gnomad_exomes: u32,
     pub inhouse: u32,
+    /// Population-specific carrier counts in gnomAD SV, if available.
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    pub gnomad_genomes_populations: IndexMap<String, u32>,
+    /// Maximal population-specific allele frequency in gnomAD SV, if available.
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    pub gnomad_genomes_populations_max_af: IndexMap<String, f32>,
+}
+
+/// Background database record with the database it was taken from.
+#[derive(Serialize, Debug, Clone)]
+pub struct SourcedBgDbRecord {
+    /// The background database.
+    pub source: BgDbType,
+    /// Release of the background database, if known.
+    pub version: Option<String>,
+    /// The record.
+    #[serde(flatten)]
+    pub record: BgDbRecord,
 }
 
 impl BgDbBundle {
+    /// Return the loaded databases with their type, in the order of `BgDbType`.
+    fn loaded(&self) -> impl Iterator<Item = (BgDbType, &BgDb)> {
+        [
+            (BgDbType::Dbvar, &self.dbvar),
+            (BgDbType::Dgv, &self.dgv),
+            (BgDbType::DgvGs, &self.dgv_gs),
+            (BgDbType::G1k, &self.g1k),
+            (BgDbType::GnomadExomes, &self.gnomad_exomes),
+            (BgDbType::GnomadGenomes, &self.gnomad_genomes),
+            (BgDbType::Inhouse, &self.inhouse),
+        ]
+        .into_iter()
+        .filter_map(|(db_type, db)| db.as_ref().map(|db| (db_type, db)))
+    }
+
+    /// Return the releases of the loaded databases, `None` if unknown.
+    pub fn versions(&self) -> IndexMap<BgDbType, Option<String>> {
+        self.loaded()
+            .map(|(db_type, db)| (db_type, db.version.clone()))
+            .collect()
+    }
+
     pub fn fetch_records(
         &self,
         genome_range: &ChromRange,
@@ -239,6 +390,11 @@ impl BgDbBundle {
         db_type: BgDbType,
     ) -> Vec<BgDbRecord> {
         match db_type {
+            BgDbType::Dbvar => self
+                .dbvar
+                .as_ref()
+                .map(|dbvar| dbvar.fetch_records(genome_range, chrom_map))
+                .unwrap_or_default(),
             BgDbType::Dgv => self
                 .dgv
                 .as_ref()
@@ -272,6 +428,45 @@ impl BgDbBundle {
         }
     }
 
+    /// Return the records overlapping with `sv` that contribute to the counts of
+    /// `count_overlaps()`, tagged with their database.
+    pub fn overlapping_records(
+        &self,
+        sv: &StructuralVariant,
+        query: &CaseQuery,
+        chrom_map: &IndexMap<String, usize>,
+        slack_ins: i32,
+        slack_bnd: i32,
+    ) -> Vec<SourcedBgDbRecord> {
+        let mut result = Vec::new();
+        for (db_type, db) in self.loaded() {
+            let (enabled, min_overlap) = match db_type {
+                BgDbType::Dbvar => (query.svdb_dbvar_enabled, query.svdb_dbvar_min_overlap),
+                BgDbType::Dgv => (query.svdb_dgv_enabled, query.svdb_dgv_min_overlap),
+                BgDbType::DgvGs => (query.svdb_dgv_gs_enabled, query.svdb_dgv_gs_min_overlap),
+                BgDbType::G1k => (query.svdb_g1k_enabled, query.svdb_g1k_min_overlap),
+                BgDbType::GnomadExomes => (
+                    query.svdb_gnomad_exomes_enabled,
+                    query.svdb_gnomad_exomes_min_overlap,
+                ),
+                BgDbType::GnomadGenomes => (
+                    query.svdb_gnomad_genomes_enabled,
+                    query.svdb_gnomad_genomes_min_overlap,
+                ),
+                BgDbType::Inhouse => (query.svdb_inhouse_enabled, query.svdb_inhouse_min_overlap),
+            };
+            result.extend(
+                db.overlapping_records(chrom_map, enabled, min_overlap, slack_ins, slack_bnd, sv)
+                    .map(|record| SourcedBgDbRecord {
+                        source: db_type,
+                        version: db.version.clone(),
+                        record: record.clone(),
+                    }),
+            );
+        }
+        result
+    }
+
     pub fn count_overlaps(
         &self,
         sv: &StructuralVariant,
@@ -351,6 +546,34 @@ impl BgDbBundle {
                     sv,
                 )
             }),
+            gnomad_genomes_populations: self
+                .gnomad_genomes
+                .as_ref()
+                .map(|gnomad_genomes| {
+                    gnomad_genomes.count_population_overlaps(
+                        chrom_map,
+                        query.svdb_gnomad_genomes_enabled,
+                        query.svdb_gnomad_genomes_min_overlap,
+                        slack_ins,
+                        slack_bnd,
+                        sv,
+                    )
+                })
+                .unwrap_or_default(),
+            gnomad_genomes_populations_max_af: self
+                .gnomad_genomes
+                .as_ref()
+                .map(|gnomad_genomes| {
+                    gnomad_genomes.max_population_afs(
+                        chrom_map,
+                        query.svdb_gnomad_genomes_enabled,
+                        query.svdb_gnomad_genomes_min_overlap,
+                        slack_ins,
+                        slack_bnd,
+                        sv,
+                    )
+                })
+                .unwrap_or_default(),
         }
     }
 }
@@ -412,3 +635,31 @@ pub fn load_bg_dbs(
 
     Ok(result)
 }
+
+#[cfg(test)]
+mod test {
+    use super::{BgDbBundle, BgDbType};
+
+    #[test]
+    fn load_bg_db_records_version() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_bin = tmpdir.join("dgv.bin");
+        std::fs::copy(
+            "tests/strucvars/query/db/worker/grch37/strucvars/bgdbs/dgv.bin",
+            &path_bin,
+        )?;
+        assert_eq!(super::load_bg_db_records(&path_bin)?.version, None);
+
+        std::fs::write(tmpdir.join("dgv.bin.version"), "2020-02-25\n")?;
+        let bundle = BgDbBundle {
+            dgv: Some(super::load_bg_db_records(&path_bin)?),
+            ..Default::default()
+        };
+        assert_eq!(
+            bundle.versions().into_iter().collect::<Vec<_>>(),
+            vec![(BgDbType::Dgv, Some("2020-02-25".to_string()))]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+//! Records of the ClinGen dosage sensitivity curation.
+//!
+//! The ClinGen gene and region curation lists are read as downloaded from ClinGen, e.g.,
+//! `ClinGen_gene_curation_list_GRCh37.tsv`, and indexed by their genomic location.  The
+//! columns are found by the names in the header line.
+
+use std::{io::BufRead as _, path::Path};
+
+use bio::data_structures::interval_tree::ArrayBackedIntervalTree;
+use indexmap::IndexMap;
+use mehari::common::io::std::open_read_maybe_gz;
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+    common::{build_chrom_map, GenomeRelease, CHROMS},
+    strucvars::query::{interpreter::breakpoint_queries, schema::StructuralVariant},
+};
+
+/// Alias for the interval tree that we use.
+type IntervalTree = ArrayBackedIntervalTree<i32, u32>;
+
+/// Kind of a ClinGen curation record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, strum::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum CurationKind {
+    /// Curated gene.
+    Gene,
+    /// Curated genomic region.
+    Region,
+}
+
+/// Information to store for a ClinGen dosage sensitivity curation record.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Record {
+    /// Whether the record is a gene or a region.
+    pub kind: CurationKind,
+    /// Gene symbol or region name.
+    pub name: String,
+    /// 0-based begin position.
+    pub begin: i32,
+    /// End position.
+    pub end: i32,
+    /// Haploinsufficiency score, `None` if not yet evaluated.
+    pub hi_score: Option<u32>,
+    /// Triplosensitivity score, `None` if not yet evaluated.
+    pub ts_score: Option<u32>,
+}
+
+impl Record {
+    /// Return the dosage score relevant for a copy number loss or gain.
+    pub fn dosage_score(&self, loss: bool) -> Option<u32> {
+        if loss {
+            self.hi_score
+        } else {
+            self.ts_score
+        }
+    }
+}
+
+/// ClinGen dosage sensitivity curation with interval trees for overlap queries.
+#[derive(Default, Debug)]
+pub struct DosageDb {
+    /// Records, stored by chromosome.
+    pub records: Vec<Vec<Record>>,
+    /// Interval trees, stored by chromosome.
+    pub trees: Vec<IntervalTree>,
+}
+
+impl DosageDb {
+    /// Return the records overlapping with the 0-based, half-open interval on `chrom_idx`.
+    pub fn overlapping_records(&self, chrom_idx: usize, begin: i32, end: i32) -> Vec<&Record> {
+        let Some(tree) = self.trees.get(chrom_idx) else {
+            return Vec::new();
+        };
+        let mut idxs = tree
+            .find(begin..end)
+            .iter()
+            .map(|cursor| *cursor.data() as usize)
+            .collect::<Vec<_>>();
+        idxs.sort();
+        idxs.into_iter()
+            .map(|idx| &self.records[chrom_idx][idx])
+            .collect()
+    }
+
+    /// Return the genes and regions affected by `sv`.
+    ///
+    /// For break-ends and insertions, the records around the breakpoints are returned.
+    pub fn affected_records(
+        &self,
+        sv: &StructuralVariant,
+        chrom_map: &IndexMap<String, usize>,
+    ) -> Result<Vec<Record>, anyhow::Error> {
+        if self.trees.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        Ok(breakpoint_queries(sv, chrom_map)?
+            .into_iter()
+            .flat_map(|(chrom_idx, query)| {
+                self.overlapping_records(chrom_idx, query.start, query.end)
+            })
+            .cloned()
+            .collect())
+    }
+}
+
+/// Parse a ClinGen dosage score, `None` for "Not yet evaluated" and empty values.
+fn parse_score(value: &str) -> Option<u32> {
+    value.trim().parse().ok()
+}
+
+/// Parse a ClinGen genomic location, e.g., `chr1:1,000-2,000`, into chromosome name and
+/// 0-based, half-open interval.
+fn parse_location(value: &str) -> Option<(String, i32, i32)> {
+    let (chrom, range) = value.trim().rsplit_once(':')?;
+    let (start, end) = range.split_once('-')?;
+    let start: i32 = start.replace(',', "").trim().parse().ok()?;
+    let end: i32 = end.replace(',', "").trim().parse().ok()?;
+    Some((chrom.to_string(), start.saturating_sub(1), end))
+}
+
+/// Load the ClinGen curation list of `kind` at `path` into `db`.
+///
+/// Records without location or on contigs other than the canonical chromosomes are
+/// skipped.
+fn load_curation_list(
+    path: &Path,
+    kind: CurationKind,
+    db: &mut DosageDb,
+) -> Result<(), anyhow::Error> {
+    tracing::debug!("loading ClinGen {} curation from {:?}...", kind, path);
+    let chrom_map = build_chrom_map();
+    let name_column = match kind {
+        CurationKind::Gene => "Gene Symbol",
+        CurationKind::Region => "ISCA Region Name",
+    };
+
+    let reader = open_read_maybe_gz(path.to_str().unwrap())?;
+    let mut columns: Option<[usize; 4]> = None;
+    let mut total_count = 0;
+    for line in reader.lines() {
+        let line = line?;
+        if let Some(header) = line.strip_prefix('#') {
+            // The header is the last comment line, with the column names.
+            if header.contains("Haploinsufficiency Score") {
+                let names = header.split('\t').map(str::trim).collect::<Vec<_>>();
+                let column = |name: &str| {
+                    names
+                        .iter()
+                        .position(|n| *n == name)
+                        .ok_or_else(|| anyhow::anyhow!("missing column {:?} in {:?}", name, path))
+                };
+                columns = Some([
+                    column(name_column)?,
+                    column("Genomic Location")?,
+                    column("Haploinsufficiency Score")?,
+                    column("Triplosensitivity Score")?,
+                ]);
+            }
+            continue;
+        } else if line.trim().is_empty() {
+            continue;
+        }
+        let [name_idx, location_idx, hi_idx, ts_idx] =
+            columns.ok_or_else(|| anyhow::anyhow!("missing header line in {:?}", path))?;
+        let fields = line.split('\t').collect::<Vec<_>>();
+        let field = |idx: usize| fields.get(idx).copied().unwrap_or_default();
+        let Some((chrom, begin, end)) = parse_location(field(location_idx)) else {
+            continue;
+        };
+        let Some(&chrom_idx) = chrom_map.get(&chrom) else {
+            continue;
+        };
+
+        db.trees[chrom_idx].insert(begin..end, db.records[chrom_idx].len() as u32);
+        db.records[chrom_idx].push(Record {
+            kind,
+            name: field(name_idx).to_string(),
+            begin,
+            end,
+            hi_score: parse_score(field(hi_idx)),
+            ts_score: parse_score(field(ts_idx)),
+        });
+        total_count += 1;
+    }
+    tracing::debug!("... done loading {} records", total_count);
+
+    Ok(())
+}
+
+/// Load the ClinGen gene and region curation lists from the given paths.
+pub fn load_dosage_db_records(
+    path_genes: Option<&Path>,
+    path_regions: Option<&Path>,
+) -> Result<DosageDb, anyhow::Error> {
+    let mut result = DosageDb::default();
+    for _ in CHROMS {
+        result.records.push(Vec::new());
+        result.trees.push(IntervalTree::new());
+    }
+    if let Some(path_genes) = path_genes {
+        load_curation_list(path_genes, CurationKind::Gene, &mut result)?;
+    }
+    if let Some(path_regions) = path_regions {
+        load_curation_list(path_regions, CurationKind::Region, &mut result)?;
+    }
+    result.trees.iter_mut().for_each(|tree| tree.index());
+
+    Ok(result)
+}
+
+/// Load the ClinGen dosage sensitivity curation from database given the configuration.
+///
+/// The curation lists are optional; missing ones are skipped.
+#[tracing::instrument]
+pub fn load_dosage_db(
+    path_db: &str,
+    genome_release: GenomeRelease,
+) -> Result<DosageDb, anyhow::Error> {
+    info!("Loading ClinGen dosage sensitivity db");
+    let path = |name: &str| {
+        let path = Path::new(path_db).join(format!("{}/strucvars/{}", genome_release, name));
+        if path.exists() {
+            Some(path)
+        } else {
+            info!("  no ClinGen curation at {:?}, skipping", &path);
+            None
+        }
+    };
+    load_dosage_db_records(
+        path("clingen_genes.tsv").as_deref(),
+        path("clingen_regions.tsv").as_deref(),
+    )
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::{
+        common::build_chrom_map,
+        strucvars::query::schema::{StructuralVariant, SvSubType, SvType},
+    };
+    use mehari::annotate::strucvars::csq::interface::StrandOrientation;
+
+    use super::{CurationKind, DosageDb};
+
+    /// Write example gene and region curation lists to `tmpdir` and load them.
+    pub fn load_db(tmpdir: &temp_testdir::TempDir) -> Result<DosageDb, anyhow::Error> {
+        let path_genes = tmpdir.join("genes.tsv");
+        std::fs::write(
+            &path_genes,
+            "#ClinGen Gene Curation Results\n\
+             #Gene Symbol\tGene ID\tcytoBand\tGenomic Location\tHaploinsufficiency Score\t\
+             Haploinsufficiency Description\tTriplosensitivity Score\n\
+             HIGENE\t1\t1p36\tchr1:10,001-20,000\t3\tSufficient\t1\n\
+             TSGENE\t2\t1p36\tchr1:30001-40000\t0\tNone\t3\n\
+             NEWGENE\t3\t1p36\ttbd\tNot yet evaluated\t\tNot yet evaluated\n",
+        )?;
+        let path_regions = tmpdir.join("regions.tsv");
+        std::fs::write(
+            &path_regions,
+            "#ISCA ID\tISCA Region Name\tcytoBand\tGenomic Location\t\
+             Haploinsufficiency Score\tTriplosensitivity Score\n\
+             ISCA-1\tBenign region\t1p36\tchr1:100001-200000\t40\t40\n",
+        )?;
+        super::load_dosage_db_records(Some(&path_genes), Some(&path_regions))
+    }
+
+    #[rstest::rstest]
+    #[case("chr1:10,001-20,000", Some(("chr1".to_string(), 10_000, 20_000)))]
+    #[case("X:1-2", Some(("X".to_string(), 0, 2)))]
+    #[case("tbd", None)]
+    fn parse_location(#[case] value: &str, #[case] expected: Option<(String, i32, i32)>) {
+        assert_eq!(super::parse_location(value), expected);
+    }
+
+    #[test]
+    fn load_dosage_db_records() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let db = load_db(&tmpdir)?;
+
+        let chrom_idx = build_chrom_map()["1"];
+        assert_eq!(db.records[chrom_idx].len(), 3);
+        let record = &db.records[chrom_idx][0];
+        assert_eq!(record.kind, CurationKind::Gene);
+        assert_eq!(record.name, "HIGENE");
+        assert_eq!((record.begin, record.end), (10_000, 20_000));
+        assert_eq!((record.hi_score, record.ts_score), (Some(3), Some(1)));
+        assert_eq!(record.dosage_score(true), Some(3));
+        assert_eq!(db.records[chrom_idx][2].kind, CurationKind::Region);
+
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case::del(SvType::Del, 5_000, None, 35_000, vec!["HIGENE", "TSGENE"])]
+    #[case::del_between(SvType::Del, 21_000, None, 29_000, vec![])]
+    #[case::ins(SvType::Ins, 15_000, None, 15_000, vec!["HIGENE"])]
+    #[case::bnd(SvType::Bnd, 35_000, Some("1"), 150_000, vec!["TSGENE", "Benign region"])]
+    #[case::bnd_other_chrom2(SvType::Bnd, 15_000, Some("GL000192.1"), 100, vec!["HIGENE"])]
+    fn affected_records(
+        #[case] sv_type: SvType,
+        #[case] pos: i32,
+        #[case] chrom2: Option<&str>,
+        #[case] end: i32,
+        #[case] expected: Vec<&str>,
+    ) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let db = load_db(&tmpdir)?;
+        let sv = StructuralVariant {
+            chrom: "1".into(),
+            pos,
+            sv_type,
+            sv_sub_type: SvSubType::Del,
+            chrom2: chrom2.map(|chrom2| chrom2.into()),
+            end,
+            strand_orientation: StrandOrientation::ThreeToFive,
+            callers: vec![],
+            call_info: Default::default(),
+        };
+
+        let names = db
+            .affected_records(&sv, &build_chrom_map())?
+            .into_iter()
+            .map(|record| record.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, expected);
+
+        Ok(())
+    }
+}
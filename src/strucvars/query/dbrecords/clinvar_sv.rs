@@ -0,0 +1,249 @@
+//! Records of the ClinVar SV database.
+//!
+//! The ClinVar SVs are read from the `clinvar.bin` file written by `strucvars txt-to-bin`
+//! and indexed by their genomic location.
+
+use bio::data_structures::interval_tree::ArrayBackedIntervalTree;
+use indexmap::IndexMap;
+use prost::Message;
+use serde::Serialize;
+use thousands::Separable;
+use tracing::{info, warn};
+
+use crate::{
+    common::{interval::Topology, reciprocal_overlap, GenomeRelease, CHROMS},
+    strucvars::query::schema::{ChromRange, Pathogenicity, StructuralVariant, SvType},
+};
+
+/// Data structures for (de-)serialization as generated by `prost-build`.
+pub mod pbs {
+    include!(concat!(env!("OUT_DIR"), "/varfish.v1.clinvar.rs"));
+}
+
+/// Alias for the interval tree that we use.
+type IntervalTree = ArrayBackedIntervalTree<i32, u32>;
+
+/// Code for known Clinvar SV database.
+///
+/// NB that the records have 1-based coordinates whereas the trees use 0-based coordinates.
+#[derive(Default, Debug)]
+pub struct ClinvarSv {
+    /// Records, stored by chromosome.
+    pub records: Vec<Vec<pbs::SvRecord>>,
+    /// Interval trees, stored by chromosome.
+    pub trees: Vec<IntervalTree>,
+}
+
+impl ClinvarSv {
+    /// Fetches records of overlapping entries.
+    pub fn fetch_records(
+        &self,
+        chrom_range: &ChromRange,
+        chrom_map: &IndexMap<String, usize>,
+        min_patho: Option<Pathogenicity>,
+    ) -> Vec<pbs::SvRecord> {
+        let chrom_idx = *chrom_map
+            .get(&chrom_range.chromosome)
+            .expect("invalid chromosome");
+        let range = chrom_range.begin..chrom_range.end;
+
+        self.trees[chrom_idx]
+            .find(range)
+            .iter()
+            .map(|e| self.records[chrom_idx][*e.data() as usize].clone())
+            .filter(|record| {
+                record.pathogenicity >= min_patho.unwrap_or(Pathogenicity::Benign) as i32
+            })
+            .collect()
+    }
+
+    /// Returns the records overlapping with `sv` with the given minimal pathogenicity.
+    fn overlapping_records(
+        &self,
+        sv: &StructuralVariant,
+        chrom_map: &IndexMap<String, usize>,
+        min_patho: Option<Pathogenicity>,
+        min_overlap: Option<f32>,
+    ) -> Vec<&pbs::SvRecord> {
+        if sv.sv_type == SvType::Ins || sv.sv_type == SvType::Bnd {
+            return Vec::new();
+        }
+
+        let chrom_idx = *chrom_map.get(&sv.chrom).expect("invalid chromosome");
+        let range = sv.pos.saturating_sub(1)..sv.end;
+
+        self.trees[chrom_idx]
+            .find(range)
+            .into_iter()
+            .map(|e| &self.records[chrom_idx][*e.data() as usize])
+            .filter(|record| {
+                min_overlap.map_or(true, |min_overlap| {
+                    reciprocal_overlap(
+                        (record.start - 1)..record.stop,
+                        (sv.pos - 1)..sv.end,
+                        Topology::from_chrom(&sv.chrom),
+                    ) >= min_overlap
+                })
+            })
+            .filter(|record| {
+                record.pathogenicity >= min_patho.unwrap_or(Pathogenicity::Benign) as i32
+            })
+            .collect()
+    }
+
+    /// Returns the overlapping RCVs
+    pub fn overlapping_rcvs(
+        &self,
+        sv: &StructuralVariant,
+        chrom_map: &IndexMap<String, usize>,
+        min_patho: Option<Pathogenicity>,
+        min_overlap: Option<f32>,
+    ) -> Vec<u32> {
+        self.overlapping_records(sv, chrom_map, min_patho, min_overlap)
+            .into_iter()
+            .map(|record| record.rcv)
+            .collect()
+    }
+
+    /// Summarizes the overlapping pathogenic and likely pathogenic records.
+    pub fn pathogenic_overlap(
+        &self,
+        sv: &StructuralVariant,
+        chrom_map: &IndexMap<String, usize>,
+        min_overlap: Option<f32>,
+    ) -> PathogenicOverlap {
+        let records = self.overlapping_records(
+            sv,
+            chrom_map,
+            Some(Pathogenicity::LikelyPathogenic),
+            min_overlap,
+        );
+        PathogenicOverlap {
+            count: records.len() as u32,
+            worst_pathogenicity: records
+                .iter()
+                .filter_map(|record| pbs::Pathogenicity::try_from(record.pathogenicity).ok())
+                .filter_map(|pathogenicity| pathogenicity.try_into().ok())
+                .max(),
+        }
+    }
+}
+
+/// Summary of the pathogenic and likely pathogenic ClinVar SVs overlapping with an SV.
+#[derive(Serialize, Default, Debug, Clone, PartialEq)]
+pub struct PathogenicOverlap {
+    /// Number of overlapping pathogenic or likely pathogenic records.
+    pub count: u32,
+    /// Worst significance of the overlapping records, if any.
+    pub worst_pathogenicity: Option<Pathogenicity>,
+}
+
+// Load the Clinvar SV databases from database given the configuration.
+#[tracing::instrument]
+pub fn load_clinvar_sv(
+    path_db: &str,
+    genome_release: GenomeRelease,
+) -> Result<ClinvarSv, anyhow::Error> {
+    info!("loading binary ClinVar SV dbs");
+
+    let before_loading = std::time::Instant::now();
+    let mut result = ClinvarSv::default();
+    for _ in CHROMS {
+        result.records.push(Vec::new());
+        result.trees.push(IntervalTree::new());
+    }
+
+    let path =
+        std::path::Path::new(path_db).join(format!("{}/strucvars/clinvar.bin", genome_release));
+    let fcontents =
+        std::fs::read(&path).map_err(|e| anyhow::anyhow!("error reading {:?}: {}", &path, e))?;
+    let bg_db = pbs::SvDatabase::decode(std::io::Cursor::new(fcontents))
+        .map_err(|e| anyhow::anyhow!("error decoding {:?}: {}", &path, e))?;
+
+    let mut total_count = 0;
+    for record in bg_db.records.into_iter() {
+        let chrom_no = record.chrom_no as usize;
+        let key = (record.start - 1)..record.stop;
+        result.trees[chrom_no].insert(key, result.records[chrom_no].len() as u32);
+        result.records[chrom_no].push(record);
+        total_count += 1;
+    }
+    tracing::debug!(
+        "done loading {} clinvar SV records from {:?} in {:?}",
+        &total_count.separate_with_commas(),
+        &path,
+        before_loading.elapsed()
+    );
+
+    let before_building = std::time::Instant::now();
+    result.trees.iter_mut().for_each(|tree| tree.index());
+    tracing::debug!("done building itrees in {:?}", before_building.elapsed());
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use mehari::annotate::strucvars::csq::interface::StrandOrientation;
+
+    use crate::common::build_chrom_map;
+    use crate::strucvars::query::schema::{Pathogenicity, StructuralVariant, SvSubType, SvType};
+
+    use super::{pbs, ClinvarSv, IntervalTree, PathogenicOverlap};
+
+    fn build_clinvar_sv(records: &[(i32, i32, pbs::Pathogenicity)]) -> ClinvarSv {
+        let mut result = ClinvarSv {
+            records: vec![Vec::new()],
+            trees: vec![IntervalTree::new()],
+        };
+        for (rcv, (start, stop, pathogenicity)) in records.iter().enumerate() {
+            result.trees[0].insert((start - 1)..*stop, result.records[0].len() as u32);
+            result.records[0].push(pbs::SvRecord {
+                chrom_no: 0,
+                start: *start,
+                stop: *stop,
+                variation_type: pbs::VariationType::Del as i32,
+                pathogenicity: *pathogenicity as i32,
+                rcv: rcv as u32,
+            });
+        }
+        result.trees.iter_mut().for_each(|tree| tree.index());
+        result
+    }
+
+    #[rstest::rstest]
+    #[case::no_overlap(5_001, 6_000, 0, None)]
+    #[case::likely_pathogenic(1_001, 2_000, 1, Some(Pathogenicity::LikelyPathogenic))]
+    #[case::both(1_001, 4_000, 2, Some(Pathogenicity::Pathogenic))]
+    fn pathogenic_overlap(
+        #[case] pos: i32,
+        #[case] end: i32,
+        #[case] count: u32,
+        #[case] worst_pathogenicity: Option<Pathogenicity>,
+    ) {
+        let clinvar_sv = build_clinvar_sv(&[
+            (1_001, 2_000, pbs::Pathogenicity::LikelyPathogenic),
+            (1_001, 2_000, pbs::Pathogenicity::Benign),
+            (2_001, 4_000, pbs::Pathogenicity::Pathogenic),
+        ]);
+        let sv = StructuralVariant {
+            chrom: "1".into(),
+            pos,
+            sv_type: SvType::Del,
+            sv_sub_type: SvSubType::Del,
+            chrom2: None,
+            end,
+            strand_orientation: StrandOrientation::ThreeToFive,
+            callers: vec![],
+            call_info: Default::default(),
+        };
+
+        assert_eq!(
+            clinvar_sv.pathogenic_overlap(&sv, &build_chrom_map(), None),
+            PathogenicOverlap {
+                count,
+                worst_pathogenicity,
+            }
+        );
+    }
+}
@@ -0,0 +1,4 @@
+//! Parsing of third-party database records used by the query annotations.
+
+pub mod clingen_dosage;
+pub mod clinvar_sv;
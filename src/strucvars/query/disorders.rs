@@ -0,0 +1,296 @@
+//! Code for flagging SVs matching known genomic disorder regions.
+//!
+//! The regions are the recurrent CNV syndromes, e.g., from DECIPHER or the ClinGen
+//! recurrent CNV curation, converted to a BED file with the identifier, the syndrome name,
+//! and the copy number change (`loss`, `gain`, or `.` for either) in the fourth to sixth
+//! column.  An SV is flagged if it is a CNV of the matching type and its reciprocal
+//! overlap with the region reaches the threshold from the query.
+
+use std::path::Path;
+
+use bio::data_structures::interval_tree::ArrayBackedIntervalTree;
+use indexmap::IndexMap;
+use mehari::common::io::std::open_read_maybe_gz;
+use serde::Serialize;
+use tracing::info;
+
+use crate::common::{
+    build_chrom_map, interval::Topology, reciprocal_overlap, GenomeRelease, CHROMS,
+};
+
+use super::schema::{StructuralVariant, SvType};
+
+/// Alias for the interval tree that we use.
+type IntervalTree = ArrayBackedIntervalTree<i32, u32>;
+
+/// Default minimal reciprocal overlap for flagging an SV with a genomic disorder.
+pub const DEFAULT_MIN_OVERLAP: f32 = 0.5;
+
+/// Copy number change of a genomic disorder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyNumberChange {
+    /// Deletion syndrome.
+    Loss,
+    /// Duplication syndrome.
+    Gain,
+    /// Both deletions and duplications of the region are pathogenic.
+    Either,
+}
+
+impl CopyNumberChange {
+    /// Return whether an SV of `sv_type` matches the change.
+    fn matches(&self, sv_type: SvType) -> bool {
+        match sv_type {
+            SvType::Del => *self != CopyNumberChange::Gain,
+            SvType::Dup => *self != CopyNumberChange::Loss,
+            SvType::Cnv => true,
+            _ => false,
+        }
+    }
+}
+
+impl std::str::FromStr for CopyNumberChange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "loss" => Ok(CopyNumberChange::Loss),
+            "gain" => Ok(CopyNumberChange::Gain),
+            "." => Ok(CopyNumberChange::Either),
+            _ => anyhow::bail!("invalid copy number change: {:?}", s),
+        }
+    }
+}
+
+/// Information to store for a genomic disorder region.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    /// 0-based begin position.
+    pub begin: i32,
+    /// End position.
+    pub end: i32,
+    /// Identifier of the region.
+    pub id: String,
+    /// Name of the syndrome, e.g., "Williams-Beuren syndrome".
+    pub name: String,
+    /// The pathogenic copy number change.
+    pub change: CopyNumberChange,
+}
+
+/// A genomic disorder region matching an SV.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DisorderMatch {
+    /// Identifier of the region.
+    pub id: String,
+    /// Name of the syndrome.
+    pub name: String,
+    /// Reciprocal overlap of the SV with the region.
+    pub overlap: f32,
+}
+
+/// Genomic disorder regions with interval trees for overlap queries.
+#[derive(Default, Debug)]
+pub struct DisorderDb {
+    /// Records, stored by chromosome.
+    pub records: Vec<Vec<Record>>,
+    /// Interval trees, stored by chromosome.
+    pub trees: Vec<IntervalTree>,
+}
+
+impl DisorderDb {
+    /// Return the genomic disorders matching `sv` with a reciprocal overlap of at least
+    /// `min_overlap`, `DEFAULT_MIN_OVERLAP` if not given.
+    pub fn matching_records(
+        &self,
+        sv: &StructuralVariant,
+        chrom_map: &IndexMap<String, usize>,
+        min_overlap: Option<f32>,
+    ) -> Vec<DisorderMatch> {
+        if self.trees.is_empty() || !matches!(sv.sv_type, SvType::Del | SvType::Dup | SvType::Cnv) {
+            return Vec::new();
+        }
+        let min_overlap = min_overlap.unwrap_or(DEFAULT_MIN_OVERLAP);
+
+        let chrom_idx = *chrom_map.get(&sv.chrom).expect("invalid chromosome");
+        let sv_range = sv.pos.saturating_sub(1)..sv.end;
+        let topology = Topology::from_chrom(&sv.chrom);
+        let mut idxs = self.trees[chrom_idx]
+            .find(sv_range.clone())
+            .iter()
+            .map(|cursor| *cursor.data() as usize)
+            .collect::<Vec<_>>();
+        idxs.sort();
+        idxs.into_iter()
+            .map(|idx| &self.records[chrom_idx][idx])
+            .filter(|record| record.change.matches(sv.sv_type))
+            .filter_map(|record| {
+                let overlap =
+                    reciprocal_overlap(sv_range.clone(), record.begin..record.end, topology);
+                (overlap >= min_overlap).then(|| DisorderMatch {
+                    id: record.id.clone(),
+                    name: record.name.clone(),
+                    overlap,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Module with code for loading data from input.
+mod input {
+    use serde::Deserialize;
+
+    /// Type for record structs from input.
+    #[derive(Deserialize, Debug)]
+    pub struct Record {
+        /// Chromosome name
+        pub chrom: String,
+        /// 0-based begin position from BED.
+        pub begin: i32,
+        /// 0-based end position from BED.
+        pub end: i32,
+        /// Identifier of the region.
+        pub id: String,
+        /// Name of the syndrome.
+        pub name: String,
+        /// Copy number change, `loss`, `gain`, or `.`.
+        pub change: String,
+    }
+}
+
+/// Load the genomic disorder regions from the BED file at `path`.
+///
+/// Records on contigs other than the canonical chromosomes are skipped.
+#[tracing::instrument]
+pub fn load_disorder_db_records(path: &Path) -> Result<DisorderDb, anyhow::Error> {
+    tracing::debug!("loading genomic disorder records from {:?}...", path);
+    let chrom_map = build_chrom_map();
+
+    let mut result = DisorderDb::default();
+    for _ in CHROMS {
+        result.records.push(Vec::new());
+        result.trees.push(IntervalTree::new());
+    }
+
+    // Setup CSV reader for BED file - header is written as comment and must be
+    // ignored.
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false) // BED has no header
+        .comment(Some(b'#'))
+        .delimiter(b'\t')
+        .from_reader(open_read_maybe_gz(path.to_str().unwrap())?);
+    let mut total_count = 0;
+    for record in reader.deserialize() {
+        let record: input::Record = record?;
+        let Some(&chrom_idx) = chrom_map.get(&record.chrom) else {
+            continue;
+        };
+
+        let key = record.begin..record.end;
+        result.trees[chrom_idx].insert(key, result.records[chrom_idx].len() as u32);
+        result.records[chrom_idx].push(Record {
+            begin: record.begin,
+            end: record.end,
+            id: record.id,
+            name: record.name,
+            change: record.change.parse()?,
+        });
+
+        total_count += 1;
+    }
+    result.trees.iter_mut().for_each(|tree| tree.index());
+    tracing::debug!(
+        "... done loading {} records and building trees",
+        total_count
+    );
+
+    Ok(result)
+}
+
+/// Load the genomic disorder regions from database given the configuration.
+///
+/// The regions are optional; an empty database is returned if the file is missing.
+#[tracing::instrument]
+pub fn load_disorder_db(
+    path_db: &str,
+    genome_release: GenomeRelease,
+) -> Result<DisorderDb, anyhow::Error> {
+    info!("Loading genomic disorder db");
+    let path = Path::new(path_db).join(format!(
+        "{}/strucvars/genomic_disorders.bed",
+        genome_release
+    ));
+    if path.exists() {
+        load_disorder_db_records(&path)
+    } else {
+        info!("  no genomic disorder db at {:?}, skipping", &path);
+        Ok(DisorderDb::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        common::build_chrom_map,
+        strucvars::query::schema::{StructuralVariant, SvSubType, SvType},
+    };
+    use mehari::annotate::strucvars::csq::interface::StrandOrientation;
+
+    #[rstest::rstest]
+    #[case::del_loss(SvType::Del, 1_001, 2_000, None, vec!["DEL1"])]
+    #[case::del_small(SvType::Del, 1_001, 1_400, None, vec![])]
+    #[case::del_small_threshold(SvType::Del, 1_001, 1_400, Some(0.3), vec!["DEL1"])]
+    #[case::dup_gain(SvType::Dup, 1_001, 2_000, None, vec!["DUP1"])]
+    #[case::cnv(SvType::Cnv, 1_001, 2_000, None, vec!["DEL1", "DUP1"])]
+    #[case::either(SvType::Dup, 5_001, 6_000, None, vec!["ANY1"])]
+    #[case::inv(SvType::Inv, 1_001, 2_000, None, vec![])]
+    fn matching_records(
+        #[case] sv_type: SvType,
+        #[case] pos: i32,
+        #[case] end: i32,
+        #[case] min_overlap: Option<f32>,
+        #[case] expected: Vec<&str>,
+    ) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("genomic_disorders.bed");
+        std::fs::write(
+            &path,
+            "#chrom\tbegin\tend\tid\tname\tchange\n\
+             1\t1000\t2000\tDEL1\tDeletion syndrome\tloss\n\
+             1\t1000\t2000\tDUP1\tDuplication syndrome\tgain\n\
+             1\t5000\t6000\tANY1\tDosage syndrome\t.\n",
+        )?;
+        let db = super::load_disorder_db_records(&path)?;
+        let sv = StructuralVariant {
+            chrom: "1".into(),
+            pos,
+            sv_type,
+            sv_sub_type: SvSubType::Del,
+            chrom2: None,
+            end,
+            strand_orientation: StrandOrientation::ThreeToFive,
+            callers: vec![],
+            call_info: Default::default(),
+        };
+
+        let ids = db
+            .matching_records(&sv, &build_chrom_map(), min_overlap)
+            .into_iter()
+            .map(|record| record.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_invalid_change() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("genomic_disorders.bed");
+        std::fs::write(&path, "1\t1000\t2000\tX1\tSyndrome\tdeletion\n")?;
+
+        assert!(super::load_disorder_db_records(&path).is_err());
+
+        Ok(())
+    }
+}
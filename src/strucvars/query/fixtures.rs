@@ -0,0 +1,23 @@
+//! Fixtures shared by the tests of the `strucvars query` modules.
+
+use mehari::annotate::strucvars::csq::interface::StrandOrientation;
+
+use super::schema::{StructuralVariant, SvSubType, SvType};
+
+/// Build a `StructuralVariant` of `sv_type` on `chrom` from `pos` to `end`.
+///
+/// The other fields are set to those of a deletion without callers and call information;
+/// tests needing e.g. `chrom2` or `call_info` override them with struct update syntax.
+pub fn build_sv(chrom: &str, sv_type: SvType, pos: i32, end: i32) -> StructuralVariant {
+    StructuralVariant {
+        chrom: chrom.into(),
+        pos,
+        sv_type,
+        sv_sub_type: SvSubType::Del,
+        chrom2: None,
+        end,
+        strand_orientation: StrandOrientation::ThreeToFive,
+        callers: vec![],
+        call_info: Default::default(),
+    }
+}
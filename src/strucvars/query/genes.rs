@@ -1,10 +1,14 @@
 //! Code for supporting annotation with overlapping genes.
+//!
+//! Gene symbols given by the user may be outdated.  Symbols that are not the approved
+//! symbol of any gene are resolved via the previous and then the alias symbols of the
+//! HGNC cross-reference, see `XlinkDb::resolve_outdated_symbol()`.
 
 use std::{collections::HashSet, path::Path, time::Instant};
 
 use mehari::common::io::std::open_read_maybe_gz;
 use prost::Message;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tracing::info;
 
 use crate::{common::GenomeRelease, strucvars::pbs};
@@ -16,6 +20,10 @@ pub struct XlinkDbRecord {
     pub ensembl_gene_id: u32,
     pub symbol: String,
     pub hgnc_id: String,
+    /// Alias symbols of the gene.
+    pub alias_symbols: Vec<String>,
+    /// Previous symbols of the gene.
+    pub prev_symbols: Vec<String>,
 }
 
 /// The interlink DB.
@@ -29,6 +37,59 @@ pub struct XlinkDb {
     pub from_ensembl: multimap::MultiMap<u32, u32>,
     /// Link from HGNC ID to indices in records.
     pub from_hgnc: multimap::MultiMap<String, u32>,
+    /// Link from previous symbol to indices in records.
+    pub from_prev_symbol: multimap::MultiMap<String, u32>,
+    /// Link from alias symbol to indices in records.
+    pub from_alias_symbol: multimap::MultiMap<String, u32>,
+}
+
+impl XlinkDb {
+    /// Append `record` and link it from its identifiers.
+    pub fn push(&mut self, record: XlinkDbRecord) {
+        let idx = self.records.len() as u32;
+        self.from_entrez.insert(record.entrez_id, idx);
+        self.from_ensembl.insert(record.ensembl_gene_id, idx);
+        self.from_hgnc.insert(record.hgnc_id.clone(), idx);
+        for symbol in &record.prev_symbols {
+            self.from_prev_symbol.insert(symbol.clone(), idx);
+        }
+        for symbol in &record.alias_symbols {
+            self.from_alias_symbol.insert(symbol.clone(), idx);
+        }
+        self.records.push(record);
+    }
+
+    /// Return the genes that have `symbol` as a previous symbol or, if there are none,
+    /// as an alias symbol, one record per HGNC ID.
+    ///
+    /// More than one gene is returned for ambiguous symbols.
+    pub fn resolve_outdated_symbol(&self, symbol: &str) -> Vec<&XlinkDbRecord> {
+        let record_idxs = self
+            .from_prev_symbol
+            .get_vec(symbol)
+            .or_else(|| self.from_alias_symbol.get_vec(symbol))
+            .cloned()
+            .unwrap_or_default();
+        let mut result: Vec<&XlinkDbRecord> = Vec::new();
+        for record_idx in record_idxs {
+            let record = &self.records[record_idx as usize];
+            if !result.iter().any(|other| other.hgnc_id == record.hgnc_id) {
+                result.push(record);
+            }
+        }
+        result
+    }
+}
+
+/// A gene symbol that was remapped to the approved symbol of its gene.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemappedSymbol {
+    /// The symbol as given, e.g., a previous symbol.
+    pub symbol: String,
+    /// The approved symbol.
+    pub approved_symbol: String,
+    /// The HGNC ID of the gene.
+    pub hgnc_id: String,
 }
 
 #[tracing::instrument]
@@ -45,20 +106,13 @@ fn load_xlink_db(path: &Path) -> Result<XlinkDb, anyhow::Error> {
 
     let mut total_count = 0;
     for record in xlink_db.records.into_iter() {
-        result
-            .from_entrez
-            .insert(record.entrez_id, result.records.len() as u32);
-        result
-            .from_ensembl
-            .insert(record.ensembl_id, result.records.len() as u32);
-        result
-            .from_hgnc
-            .insert(record.hgnc_id.clone(), result.records.len() as u32);
-        result.records.push(XlinkDbRecord {
+        result.push(XlinkDbRecord {
             entrez_id: record.entrez_id,
             ensembl_gene_id: record.ensembl_id,
             symbol: record.symbol,
             hgnc_id: record.hgnc_id,
+            alias_symbols: record.alias_symbols,
+            prev_symbols: record.prev_symbols,
         });
         total_count += 1;
     }
@@ -195,3 +249,43 @@ pub fn load_gene_db(path_db: &str, genome_release: GenomeRelease) -> Result<Gene
 
     Ok(result)
 }
+
+#[cfg(test)]
+mod test {
+    use super::{XlinkDb, XlinkDbRecord};
+
+    /// Build a cross-reference with `C19orf12` as a previous symbol and `NBIA4` as an alias
+    /// of two genes.
+    fn xlink_db() -> XlinkDb {
+        let mut result = XlinkDb::default();
+        for (hgnc_id, symbol, prev_symbols, alias_symbols) in [
+            ("HGNC:25443", "MPAN", vec!["C19orf12"], vec!["NBIA4"]),
+            ("HGNC:1", "GENE1", vec![], vec!["NBIA4", "ALIAS1"]),
+            ("HGNC:2", "GENE2", vec![], vec!["C19orf12"]),
+        ] {
+            result.push(XlinkDbRecord {
+                hgnc_id: hgnc_id.into(),
+                symbol: symbol.into(),
+                prev_symbols: prev_symbols.into_iter().map(String::from).collect(),
+                alias_symbols: alias_symbols.into_iter().map(String::from).collect(),
+                ..Default::default()
+            });
+        }
+        result
+    }
+
+    #[rstest::rstest]
+    #[case::previous_before_alias("C19orf12", &["MPAN"])]
+    #[case::alias("ALIAS1", &["GENE1"])]
+    #[case::ambiguous_alias("NBIA4", &["MPAN", "GENE1"])]
+    #[case::unknown("UNKNOWN", &[])]
+    fn resolve_outdated_symbol(#[case] symbol: &str, #[case] expected: &[&str]) {
+        let xlink_db = xlink_db();
+        let symbols = xlink_db
+            .resolve_outdated_symbol(symbol)
+            .into_iter()
+            .map(|record| record.symbol.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(symbols, expected);
+    }
+}
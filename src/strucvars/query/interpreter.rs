@@ -3,11 +3,13 @@
 use std::collections::{HashMap, HashSet};
 
 use anyhow::anyhow;
+use indexmap::IndexMap;
 use tracing::{trace, warn};
 
 use super::{
     bgdbs::BgDbOverlaps,
-    masked::MaskedBreakpointCount,
+    genes::RemappedSymbol,
+    masked::{MaskedBreakpointCount, MaskedFraction},
     schema::{
         CaseQuery, Genotype, GenotypeChoice, Range, StructuralVariant, SvSubType, SvType,
         TranscriptEffect,
@@ -20,6 +22,46 @@ pub static BND_SLACK: i32 = 50;
 /// Slack around insertion position
 pub static INS_SLACK: i32 = 50;
 
+/// Returns the ranges to query for features affected by `sv` as pairs of the
+/// chromosome index in `chrom_map` and the 0-based range.
+///
+/// For break-ends and insertions, the ranges around the breakpoints are returned
+/// using `BND_SLACK` and `INS_SLACK`.  Breakpoints on contigs that are not in
+/// `chrom_map` are skipped.
+pub fn breakpoint_queries(
+    sv: &StructuralVariant,
+    chrom_map: &IndexMap<String, usize>,
+) -> Result<Vec<(usize, std::ops::Range<i32>)>, anyhow::Error> {
+    let queries = match sv.sv_type {
+        SvType::Bnd => {
+            let chrom2 = sv
+                .chrom2
+                .as_ref()
+                .ok_or_else(|| anyhow!("break-end without chrom2: {:?}", sv))?;
+            vec![
+                (
+                    &sv.chrom,
+                    sv.pos.saturating_sub(BND_SLACK)..sv.pos.saturating_add(BND_SLACK),
+                ),
+                (
+                    chrom2,
+                    sv.end.saturating_sub(BND_SLACK)..sv.end.saturating_add(BND_SLACK),
+                ),
+            ]
+        }
+        SvType::Ins => vec![(
+            &sv.chrom,
+            sv.pos.saturating_sub(INS_SLACK)..sv.pos.saturating_add(INS_SLACK),
+        )],
+        _ => vec![(&sv.chrom, sv.pos.saturating_sub(1)..sv.end)],
+    };
+
+    Ok(queries
+        .into_iter()
+        .filter_map(|(chrom, range)| chrom_map.get(chrom).map(|chrom_idx| (*chrom_idx, range)))
+        .collect())
+}
+
 /// Returns whether the intervals `[s1, e1)` and `[s2, e2)` overlap.
 pub fn overlaps(s1: i32, e1: i32, s2: i32, e2: i32) -> bool {
     s1 < e2 && e1 > s2
@@ -31,6 +73,8 @@ pub fn overlaps(s1: i32, e1: i32, s2: i32, e2: i32) -> bool {
 pub struct QueryInterpreter {
     pub query: CaseQuery,
     pub hgvs_allowlist: Option<HashSet<String>>,
+    /// Outdated symbols of the gene allow list that were remapped.
+    pub remapped_gene_symbols: Vec<RemappedSymbol>,
 }
 
 /// Result type for `QueryInterpreter::passes_genotype()`.
@@ -53,6 +97,15 @@ impl QueryInterpreter {
         QueryInterpreter {
             query,
             hgvs_allowlist,
+            remapped_gene_symbols: Vec::new(),
+        }
+    }
+
+    /// Record the outdated symbols of the gene allow list that were remapped.
+    pub fn with_remapped_gene_symbols(self, remapped_gene_symbols: Vec<RemappedSymbol>) -> Self {
+        QueryInterpreter {
+            remapped_gene_symbols,
+            ..self
         }
     }
 
@@ -82,7 +135,7 @@ impl QueryInterpreter {
         // `self.query.genotype` matches what we have in terms of `CallInfo` for
         // the sample in `sv`.  For this, we go through all `GenotypeCriteria`
         // in `self.query.genotype_criteria` and look for all matching such
-        // records (by genotype, sv sub type, size)...
+        // records (by genotype, sv sub type, size, sample)...
         for sample in query_samples {
             let query_genotype = *self
                 .query
@@ -108,6 +161,7 @@ impl QueryInterpreter {
             let mut pass_one_criteria = false;
             for criteria in &self.query.genotype_criteria {
                 if criteria.is_applicable_to(query_genotype, sv.sv_sub_type, sv.size())
+                    && criteria.is_applicable_to_sample(sample)
                     && criteria.is_call_info_pass(call_info)
                     && criteria.is_masked_pass(masked_count)
                 {
@@ -270,11 +324,31 @@ impl QueryInterpreter {
         let passes_dgv_gs = !self.query.svdb_dgv_gs_enabled
             || counts.dgv_gs <= self.query.svdb_dgv_gs_max_count.unwrap_or(counts.dgv_gs);
         let passes_gnomad_genomes = !self.query.svdb_gnomad_genomes_enabled
-            || counts.gnomad_genomes
+            || (counts.gnomad_genomes
                 <= self
                     .query
                     .svdb_gnomad_genomes_max_count
-                    .unwrap_or(counts.gnomad_genomes);
+                    .unwrap_or(counts.gnomad_genomes)
+                && self
+                    .query
+                    .svdb_gnomad_genomes_max_count_by_population
+                    .iter()
+                    .all(|(population, max_count)| {
+                        counts
+                            .gnomad_genomes_populations
+                            .get(population)
+                            .map_or(true, |count| count <= max_count)
+                    })
+                && self
+                    .query
+                    .svdb_gnomad_genomes_max_af_by_population
+                    .iter()
+                    .all(|(population, max_af)| {
+                        counts
+                            .gnomad_genomes_populations_max_af
+                            .get(population)
+                            .map_or(true, |af| af <= max_af)
+                    }));
         let passes_gnomad_exomes = !self.query.svdb_gnomad_exomes_enabled
             || counts.gnomad_exomes
                 <= self
@@ -309,6 +383,20 @@ impl QueryInterpreter {
             && passes_inhouse
     }
 
+    /// Determine whether an SV record with the given masked fractions passes the criteria.
+    pub fn passes_masked_fraction(&self, masked_fraction: &MaskedFraction) -> bool {
+        let pass_segdup = self
+            .query
+            .max_masked_fraction_segdup
+            .map_or(true, |max_fraction| masked_fraction.segdup <= max_fraction);
+        let pass_repeat = self
+            .query
+            .max_masked_fraction_repeat
+            .map_or(true, |max_fraction| masked_fraction.repeat <= max_fraction);
+
+        pass_segdup && pass_repeat
+    }
+
     /// Determine whether the `sv` passes the gene allow list filter.
     pub fn passes_genes(&self, ovl_hgvs_ids: &[String]) -> bool {
         if let Some(hgvs_allowlist) = self.hgvs_allowlist.as_ref() {
@@ -338,17 +426,19 @@ impl QueryInterpreter {
     }
 
     /// Determine whether the annotated `StructuralVariant` passes all criteria.
-    pub fn passes<CountBg, CountMasked, OvlHgvsIds, TxEffects>(
+    pub fn passes<CountBg, CountMasked, MaskedFractions, OvlHgvsIds, TxEffects>(
         &self,
         sv: &StructuralVariant,
         count_bg: &mut CountBg,
         count_masked: &mut CountMasked,
+        masked_fraction: &mut MaskedFractions,
         ovl_hgvs_ids: &mut OvlHgvsIds,
         tx_effects: &mut TxEffects,
     ) -> Result<PassesResult, anyhow::Error>
     where
         CountBg: FnMut(&StructuralVariant) -> BgDbOverlaps,
         CountMasked: FnMut(&StructuralVariant) -> MaskedBreakpointCount,
+        MaskedFractions: FnMut(&StructuralVariant) -> MaskedFraction,
         OvlHgvsIds: FnMut(&StructuralVariant) -> Vec<String>,
         TxEffects: FnMut(&StructuralVariant) -> Vec<TranscriptEffect>,
     {
@@ -363,6 +453,9 @@ impl QueryInterpreter {
         let passes_result = self.passes_genotype(sv, &count_masked(sv))?;
         if !passes_result.pass_all {
             Ok(Default::default())
+        } else if !self.passes_masked_fraction(&masked_fraction(sv)) {
+            trace!("... SV does not pass masked fraction filter");
+            Ok(Default::default())
         } else if !self.passes_genes(&ovl_hgvs_ids(sv)) {
             trace!("... SV does not gene allow list filter");
             Ok(Default::default())
@@ -400,6 +493,38 @@ mod tests {
         assert!(!overlaps(10, 20, 1, 10));
     }
 
+    #[rstest::rstest]
+    #[case::del("1", SvType::Del, None, vec![(0, 999..2_000)])]
+    #[case::ins("1", SvType::Ins, None, vec![(0, 950..1_050)])]
+    #[case::bnd("1", SvType::Bnd, Some("X"), vec![(0, 950..1_050), (22, 1_950..2_050)])]
+    #[case::bnd_other_chrom2("1", SvType::Bnd, Some("GL000192.1"), vec![(0, 950..1_050)])]
+    #[case::other_chrom("GL000192.1", SvType::Del, None, vec![])]
+    fn test_breakpoint_queries(
+        #[case] chrom: &str,
+        #[case] sv_type: SvType,
+        #[case] chrom2: Option<&str>,
+        #[case] expected: Vec<(usize, std::ops::Range<i32>)>,
+    ) -> Result<(), anyhow::Error> {
+        let sv = StructuralVariant {
+            chrom2: chrom2.map(|chrom2| chrom2.into()),
+            ..crate::strucvars::query::fixtures::build_sv(chrom, sv_type, 1_000, 2_000)
+        };
+
+        assert_eq!(
+            breakpoint_queries(&sv, &crate::common::build_chrom_map())?,
+            expected
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_breakpoint_queries_bnd_without_chrom2() {
+        let sv = crate::strucvars::query::fixtures::build_sv("1", SvType::Bnd, 1_000, 2_000);
+
+        assert!(breakpoint_queries(&sv, &crate::common::build_chrom_map()).is_err());
+    }
+
     #[test]
     fn test_query_interpreter_smoke() {
         let query = CaseQuery::default();
@@ -896,6 +1021,7 @@ mod tests {
             g1k: 5,
             inhouse: 5,
             dbvar: 5,
+            ..Default::default()
         };
 
         assert!(interpreter.passes_counts(&counts_pass));
@@ -930,11 +1056,95 @@ mod tests {
             g1k: 11,
             inhouse: 11,
             dbvar: 11,
+            ..Default::default()
         };
 
         assert!(!interpreter.passes_counts(&counts_fail));
     }
 
+    #[rstest::rstest]
+    #[case::below_max(3, true)]
+    #[case::at_max(5, true)]
+    #[case::above_max(6, false)]
+    fn test_query_interpreter_passes_counts_population(
+        #[case] nfe_count: u32,
+        #[case] expected: bool,
+    ) {
+        let query = CaseQuery {
+            svdb_gnomad_genomes_enabled: true,
+            svdb_gnomad_genomes_max_count: Some(100),
+            svdb_gnomad_genomes_max_count_by_population: IndexMap::from([("nfe".to_string(), 5)]),
+            ..CaseQuery::default()
+        };
+        let interpreter = QueryInterpreter::new(query, None);
+
+        let counts = BgDbOverlaps {
+            gnomad_genomes: 50,
+            gnomad_genomes_populations: IndexMap::from([
+                ("afr".to_string(), 40),
+                ("nfe".to_string(), nfe_count),
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(interpreter.passes_counts(&counts), expected);
+    }
+
+    #[rstest::rstest]
+    #[case::below_max(0.0005, true)]
+    #[case::at_max(0.001, true)]
+    #[case::above_max(0.002, false)]
+    fn test_query_interpreter_passes_counts_population_af(
+        #[case] nfe_af: f32,
+        #[case] expected: bool,
+    ) {
+        let query = CaseQuery {
+            svdb_gnomad_genomes_enabled: true,
+            svdb_gnomad_genomes_max_af_by_population: IndexMap::from([("nfe".to_string(), 0.001)]),
+            ..CaseQuery::default()
+        };
+        let interpreter = QueryInterpreter::new(query, None);
+
+        let counts = BgDbOverlaps {
+            gnomad_genomes: 50,
+            gnomad_genomes_populations_max_af: IndexMap::from([
+                ("afr".to_string(), 0.01),
+                ("nfe".to_string(), nfe_af),
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(interpreter.passes_counts(&counts), expected);
+    }
+
+    #[rstest::rstest]
+    #[case::no_limits(None, None, true)]
+    #[case::below_max(Some(0.5), Some(0.5), true)]
+    #[case::segdup_above_max(Some(0.1), None, false)]
+    #[case::repeat_above_max(None, Some(0.1), false)]
+    fn test_query_interpreter_passes_masked_fraction(
+        #[case] max_masked_fraction_segdup: Option<f32>,
+        #[case] max_masked_fraction_repeat: Option<f32>,
+        #[case] expected: bool,
+    ) {
+        let query = CaseQuery {
+            max_masked_fraction_segdup,
+            max_masked_fraction_repeat,
+            ..CaseQuery::default()
+        };
+        let interpreter = QueryInterpreter::new(query, None);
+
+        let masked_fraction = MaskedFraction {
+            repeat: 0.2,
+            segdup: 0.3,
+        };
+
+        assert_eq!(
+            interpreter.passes_masked_fraction(&masked_fraction),
+            expected
+        );
+    }
+
     #[test]
     fn test_query_interpreter_pass_genotype_fail_no_match() -> Result<(), anyhow::Error> {
         let query = CaseQuery {
@@ -1205,6 +1415,135 @@ mod tests {
         Ok(())
     }
 
+    #[rstest::rstest]
+    #[case::no_support(0, 0, true)]
+    #[case::father_support(1, 0, false)]
+    #[case::mother_support(0, 1, true)]
+    #[case::mother_more_support(0, 3, false)]
+    fn test_query_interpreter_pass_genotype_quality_per_sample(
+        #[case] father_srpr_var: u32,
+        #[case] mother_srpr_var: u32,
+        #[case] expected: bool,
+    ) -> Result<(), anyhow::Error> {
+        let query = CaseQuery {
+            genotype: IndexMap::from([
+                ("father".to_owned(), GenotypeChoice::Ref),
+                ("mother".to_owned(), GenotypeChoice::Ref),
+            ]),
+            genotype_criteria: vec![
+                GenotypeCriteria {
+                    select_sv_sub_type: vec![SvSubType::Del],
+                    select_sample: Some(vec!["father".to_owned()]),
+                    max_srpr_var: Some(0),
+                    ..GenotypeCriteria::new(GenotypeChoice::Ref)
+                },
+                GenotypeCriteria {
+                    select_sv_sub_type: vec![SvSubType::Del],
+                    select_sample: Some(vec!["mother".to_owned()]),
+                    max_srpr_var: Some(2),
+                    ..GenotypeCriteria::new(GenotypeChoice::Ref)
+                },
+            ],
+            ..CaseQuery::default()
+        };
+        let interpreter = QueryInterpreter::new(query, None);
+
+        let call_info = |split_read_var: u32| CallInfo {
+            genotype: Some("0/0".to_owned()),
+            paired_end_var: Some(0),
+            split_read_var: Some(split_read_var),
+            ..Default::default()
+        };
+        let sv = StructuralVariant {
+            chrom: "chr1".to_owned(),
+            pos: 1000,
+            sv_type: SvType::Del,
+            sv_sub_type: SvSubType::Del,
+            chrom2: None,
+            end: 2000,
+            callers: Vec::new(),
+            strand_orientation: StrandOrientation::ThreeToFive,
+            call_info: IndexMap::from([
+                ("father".to_owned(), call_info(father_srpr_var)),
+                ("mother".to_owned(), call_info(mother_srpr_var)),
+            ]),
+        };
+
+        assert_eq!(
+            interpreter
+                .passes_genotype(&sv, &Default::default())?
+                .pass_all,
+            expected
+        );
+        Ok(())
+    }
+
+    #[rstest::rstest]
+    #[case::no_parent_support(0, 0, true)]
+    #[case::father_pr_support(1, 0, false)]
+    #[case::mother_sr_support(0, 2, false)]
+    fn test_query_interpreter_pass_genotype_quality_de_novo(
+        #[case] father_pr_var: u32,
+        #[case] mother_sr_var: u32,
+        #[case] expected: bool,
+    ) -> Result<(), anyhow::Error> {
+        let query = CaseQuery {
+            genotype: IndexMap::from([
+                ("index".to_owned(), GenotypeChoice::Het),
+                ("father".to_owned(), GenotypeChoice::Ref),
+                ("mother".to_owned(), GenotypeChoice::Ref),
+            ]),
+            genotype_criteria: vec![
+                GenotypeCriteria {
+                    select_sv_sub_type: vec![SvSubType::Del],
+                    gt_one_of: Some(vec!["0/1".to_owned()]),
+                    min_gq: Some(20.0),
+                    min_srpr_var: Some(5),
+                    ..GenotypeCriteria::new(GenotypeChoice::Het)
+                },
+                GenotypeCriteria {
+                    select_sv_sub_type: vec![SvSubType::Del],
+                    gt_one_of: Some(vec!["0/0".to_owned()]),
+                    max_srpr_var: Some(0),
+                    ..GenotypeCriteria::new(GenotypeChoice::Ref)
+                },
+            ],
+            ..CaseQuery::default()
+        };
+        let interpreter = QueryInterpreter::new(query, None);
+
+        let call_info = |genotype: &str, paired_end_var: u32, split_read_var: u32| CallInfo {
+            genotype: Some(genotype.to_owned()),
+            quality: Some(30.0),
+            paired_end_var: Some(paired_end_var),
+            split_read_var: Some(split_read_var),
+            ..Default::default()
+        };
+        let sv = StructuralVariant {
+            chrom: "chr1".to_owned(),
+            pos: 1000,
+            sv_type: SvType::Del,
+            sv_sub_type: SvSubType::Del,
+            chrom2: None,
+            end: 2000,
+            callers: Vec::new(),
+            strand_orientation: StrandOrientation::ThreeToFive,
+            call_info: IndexMap::from([
+                ("index".to_owned(), call_info("0/1", 3, 3)),
+                ("father".to_owned(), call_info("0/0", father_pr_var, 0)),
+                ("mother".to_owned(), call_info("0/0", 0, mother_sr_var)),
+            ]),
+        };
+
+        assert_eq!(
+            interpreter
+                .passes_genotype(&sv, &Default::default())?
+                .pass_all,
+            expected
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_query_interpreter_passes_smoke() -> Result<(), anyhow::Error> {
         let query = CaseQuery::default();
@@ -1229,6 +1568,7 @@ mod tests {
             g1k: 5,
             inhouse: 5,
             dbvar: 5,
+            ..Default::default()
         };
 
         assert!(
@@ -1238,6 +1578,7 @@ mod tests {
                     &mut |_sv| counts_pass.clone(),
                     &mut |_sv| { Default::default() },
                     &mut |_sv| { Default::default() },
+                    &mut |_sv| { Default::default() },
                     &mut |_sv| { Default::default() }
                 )?
                 .pass_all
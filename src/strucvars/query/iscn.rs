@@ -0,0 +1,194 @@
+//! Generation of ISCN-like microarray nomenclature for CNVs, e.g.,
+//! `arr[GRCh38] 7q11.23(72,650,120_74,154,209)x1`.
+
+use indexmap::IndexMap;
+use thousands::Separable;
+
+use crate::common::{cytoband::Cytobands, karyotype::Karyotype, Chrom, GenomeRelease};
+
+use super::schema::{CallInfo, StructuralVariant, SvType};
+
+/// Return the copy number of the sample with `call_info` for a CNV of type `sv_type`.
+///
+/// The copy number from the caller is used if available.  Otherwise, it is derived from
+/// the number of alternate alleles in the genotype and the `baseline` copy number.
+fn copy_number(sv_type: SvType, call_info: &CallInfo, baseline: u32) -> Option<u32> {
+    if let Some(copy_number) = call_info.copy_number {
+        return Some(copy_number);
+    }
+    let num_alt = call_info
+        .genotype
+        .as_ref()?
+        .split(['/', '|'])
+        .filter(|allele| *allele == "1")
+        .count() as u32;
+    match sv_type {
+        SvType::Del => Some(baseline.saturating_sub(num_alt)),
+        SvType::Dup => Some(baseline + num_alt),
+        _ => None,
+    }
+}
+
+/// Build the ISCN description for the CNV `sv` with the given copy number.
+pub fn describe(
+    genome_release: GenomeRelease,
+    cytobands: &Cytobands,
+    sv: &StructuralVariant,
+    copy_number: u32,
+) -> Option<String> {
+    let (start, end) = (sv.pos.max(1) as usize, sv.end.max(sv.pos) as usize);
+    let bands = cytobands.describe_range(&sv.chrom, start, end)?;
+    Some(format!(
+        "arr[{}] {}({}_{})x{}",
+        genome_release.name(),
+        bands,
+        start.separate_with_commas(),
+        end.separate_with_commas(),
+        copy_number
+    ))
+}
+
+/// Build the ISCN descriptions for all samples that carry the CNV `sv`.
+///
+/// The baseline copy number of each sample is the ploidy of the chromosome in its
+/// karyotype from `karyotypes`, two if the karyotype is unknown.  Returns an empty map for
+/// SV types other than deletions, duplications, and CNVs, and skips samples with the
+/// baseline copy number.
+pub fn describe_samples(
+    genome_release: GenomeRelease,
+    cytobands: &Cytobands,
+    karyotypes: &IndexMap<String, Karyotype>,
+    sv: &StructuralVariant,
+) -> Result<IndexMap<String, String>, anyhow::Error> {
+    if !matches!(sv.sv_type, SvType::Del | SvType::Dup | SvType::Cnv) {
+        return Ok(Default::default());
+    }
+    let chrom: Chrom = annonars::common::cli::canonicalize(&sv.chrom).parse()?;
+    Ok(sv
+        .call_info
+        .iter()
+        .filter_map(|(sample, call_info)| {
+            let baseline = karyotypes
+                .get(sample)
+                .map_or(2, |karyotype| karyotype.ploidy(chrom) as u32);
+            let copy_number = copy_number(sv.sv_type, call_info, baseline)?;
+            if copy_number == baseline {
+                return None;
+            }
+            describe(genome_release, cytobands, sv, copy_number).map(|iscn| (sample.clone(), iscn))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use indexmap::IndexMap;
+
+    use crate::{
+        common::{
+            cytoband::{Band, Cytobands},
+            karyotype::Karyotype,
+            GenomeRelease,
+        },
+        strucvars::query::{
+            fixtures,
+            schema::{CallInfo, StructuralVariant, SvType},
+        },
+    };
+
+    fn cytobands() -> Cytobands {
+        Cytobands {
+            bands: [
+                (
+                    "7".to_string(),
+                    vec![
+                        Band {
+                            begin: 72_200_000,
+                            end: 73_300_000,
+                            name: "q11.22".to_string(),
+                        },
+                        Band {
+                            begin: 73_300_000,
+                            end: 77_400_000,
+                            name: "q11.23".to_string(),
+                        },
+                    ],
+                ),
+                (
+                    "X".to_string(),
+                    vec![Band {
+                        begin: 73_000_000,
+                        end: 74_700_000,
+                        name: "q13.2".to_string(),
+                    }],
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+
+    fn build_sv(
+        chrom: &str,
+        sv_type: SvType,
+        genotypes: &[(&str, &str, Option<u32>)],
+    ) -> StructuralVariant {
+        StructuralVariant {
+            call_info: genotypes
+                .iter()
+                .map(|(sample, genotype, copy_number)| {
+                    (
+                        sample.to_string(),
+                        CallInfo {
+                            genotype: Some(genotype.to_string()),
+                            copy_number: *copy_number,
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect::<IndexMap<_, _>>(),
+            ..fixtures::build_sv(chrom, sv_type, 73_650_120, 74_154_209)
+        }
+    }
+
+    #[test]
+    fn describe() {
+        let sv = build_sv("chr7", SvType::Del, &[]);
+        assert_eq!(
+            super::describe(GenomeRelease::Grch38, &cytobands(), &sv, 1),
+            Some("arr[GRCh38] 7q11.23(73,650,120_74,154,209)x1".to_string())
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("7", SvType::Del, &[("index", "0/1", None), ("father", "0/0", None)], &["index:x1"])]
+    #[case("7", SvType::Del, &[("index", "1/1", None), ("father", "0/1", None)], &["index:x0", "father:x1"])]
+    #[case("7", SvType::Dup, &[("index", "0/1", None), ("father", "./.", None)], &["index:x3"])]
+    #[case("7", SvType::Cnv, &[("index", "./1", Some(4)), ("father", "./.", None)], &["index:x4"])]
+    #[case("7", SvType::Inv, &[("index", "0/1", None)], &[])]
+    #[case("X", SvType::Del, &[("index", "0/1", None), ("father", "1", None)], &["index:x1", "father:x0"])]
+    #[case("X", SvType::Dup, &[("index", "0/1", None), ("father", "1", None)], &["index:x3", "father:x2"])]
+    #[case("X", SvType::Cnv, &[("index", "./1", Some(2)), ("father", "./1", Some(1))], &[])]
+    fn describe_samples(
+        #[case] chrom: &str,
+        #[case] sv_type: SvType,
+        #[case] genotypes: &[(&str, &str, Option<u32>)],
+        #[case] expected: &[&str],
+    ) -> Result<(), anyhow::Error> {
+        let sv = build_sv(chrom, sv_type, genotypes);
+        let karyotypes = [
+            ("index".to_string(), Karyotype::Xx),
+            ("father".to_string(), Karyotype::Xy),
+        ]
+        .into_iter()
+        .collect();
+        let result =
+            super::describe_samples(GenomeRelease::Grch38, &cytobands(), &karyotypes, &sv)?
+                .into_iter()
+                .map(|(sample, iscn)| format!("{}:{}", sample, iscn.rsplit(')').next().unwrap()))
+                .collect::<Vec<_>>();
+        assert_eq!(result, expected);
+
+        Ok(())
+    }
+}
@@ -106,6 +106,55 @@ impl MaskedDb {
 
         (any_left as u32) + (any_right as u32)
     }
+
+    /// Fraction of `sv`'s span that is covered by masked regions.
+    ///
+    /// For insertions and break-ends, the span is the one primary breakpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `chrom_map`: Mapping from chromosome name to index.
+    /// * `sv`: Structural variant to compute the masked fraction for.
+    ///
+    /// # Returns
+    ///
+    /// Covered fraction between 0 and 1.
+    pub fn masked_fraction(
+        &self,
+        chrom_map: &IndexMap<String, usize>,
+        sv: &StructuralVariant,
+    ) -> f32 {
+        let chrom_idx = *chrom_map.get(&sv.chrom).expect("invalid chromosome");
+        let span = if sv.sv_type == SvType::Ins || sv.sv_type == SvType::Bnd {
+            sv.pos.saturating_sub(1)..sv.pos
+        } else {
+            sv.pos.saturating_sub(1)..sv.end.max(sv.pos)
+        };
+
+        // Clip the overlapping regions to the span and merge them before summing up.
+        let mut regions = self.trees[chrom_idx]
+            .find(span.clone())
+            .iter()
+            .map(|e| {
+                (
+                    e.interval().start.max(span.start),
+                    e.interval().end.min(span.end),
+                )
+            })
+            .collect::<Vec<_>>();
+        regions.sort();
+        let mut covered = 0;
+        let mut covered_until = span.start;
+        for (begin, end) in regions {
+            let begin = begin.max(covered_until);
+            if end > begin {
+                covered += end - begin;
+                covered_until = end;
+            }
+        }
+
+        covered as f32 / (span.end - span.start).max(1) as f32
+    }
 }
 
 /// Information to store for background database.
@@ -181,6 +230,13 @@ pub struct MaskedDbBundle {
     pub segdup: MaskedDb,
 }
 
+/// Store the fractions of a structural variant's span covered by masked regions.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MaskedFraction {
+    pub repeat: f32,
+    pub segdup: f32,
+}
+
 /// Store masked region database counts for a structural variant.
 #[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct MaskedBreakpointCount {
@@ -211,6 +267,17 @@ impl MaskedDbBundle {
             segdup: self.segdup.masked_breakpoint_count(chrom_map, sv),
         }
     }
+
+    pub fn masked_fraction(
+        &self,
+        sv: &StructuralVariant,
+        chrom_map: &IndexMap<String, usize>,
+    ) -> MaskedFraction {
+        MaskedFraction {
+            repeat: self.repeat.masked_fraction(chrom_map, sv),
+            segdup: self.segdup.masked_fraction(chrom_map, sv),
+        }
+    }
 }
 
 // Load all masked region databases from database given the configuration.
@@ -311,6 +378,41 @@ mod test {
         assert_eq!(result, expected);
     }
 
+    #[rstest::rstest]
+    #[case(1, 20, 0.75)]
+    #[case(6, 105, 0.15)]
+    #[case(101, 101, 1.0)]
+    #[case(20, 50, 0.0)]
+    fn masked_fraction(
+        #[case] sv_pos: i32,
+        #[case] sv_end: i32,
+        #[case] expected: f32,
+        masked_db: super::MaskedDb,
+        chrom_map: indexmap::IndexMap<String, usize>,
+    ) {
+        let sv = crate::strucvars::query::schema::StructuralVariant {
+            chrom: String::from("1"),
+            pos: sv_pos,
+            end: sv_end,
+            chrom2: None,
+            sv_type: crate::strucvars::query::schema::SvType::Del,
+            sv_sub_type: crate::strucvars::query::schema::SvSubType::Del,
+            callers: Vec::new(),
+            strand_orientation:
+                mehari::annotate::strucvars::csq::interface::StrandOrientation::ThreeToFive,
+            call_info: Default::default(),
+        };
+
+        let result = masked_db.masked_fraction(&chrom_map, &sv);
+
+        assert!(
+            (result - expected).abs() < 1e-6,
+            "{} != {}",
+            result,
+            expected
+        );
+    }
+
     #[test]
     fn load_masked_db_records() -> Result<(), anyhow::Error> {
         let tmpdir = temp_testdir::TempDir::default();
@@ -1,17 +1,26 @@
 //! Code implementing the "strucvars query" sub command.
 
+pub mod acmg_cnv;
 pub mod bgdbs;
-pub mod clinvar;
+pub mod dbrecords;
+pub mod disorders;
+#[cfg(test)]
+pub mod fixtures;
 pub mod genes;
 pub mod interpreter;
+pub mod iscn;
 pub mod masked;
+pub mod mtsv;
 pub mod pathogenic;
+pub mod presets;
+pub mod regulatory;
 pub mod schema;
 pub mod tads;
 
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
     fs::File,
+    io::Write,
     time::Instant,
 };
 
@@ -36,20 +45,27 @@ use thousands::Separable;
 use uuid::Uuid;
 
 use crate::{
-    common::{build_chrom_map, numeric_gene_id, trace_rss_now},
-    common::{GenomeRelease, TadSet as TadSetChoice},
+    common::{build_chrom_map, cytoband::Cytobands, numeric_gene_id, trace_rss_now, tx_trees},
+    common::{pbs, GenomeRelease, TadSet as TadSetChoice},
     strucvars::query::{
         interpreter::QueryInterpreter, pathogenic::Record as KnownPathogenicRecord,
         schema::CaseQuery, schema::StructuralVariant,
     },
 };
 
+pub use crate::common::OutputFormat;
+
 use self::{
-    bgdbs::{load_bg_dbs, BgDbBundle, BgDbOverlaps},
-    clinvar::{load_clinvar_sv, ClinvarSv},
-    genes::{load_gene_db, GeneDb},
-    masked::{load_masked_dbs, MaskedBreakpointCount, MaskedDbBundle},
+    acmg_cnv::GeneContent,
+    bgdbs::{load_bg_dbs, BgDbBundle, BgDbOverlaps, BgDbType, SourcedBgDbRecord},
+    dbrecords::clingen_dosage::{self, load_dosage_db, DosageDb},
+    dbrecords::clinvar_sv::{self, load_clinvar_sv, ClinvarSv},
+    disorders::{load_disorder_db, DisorderDb, DisorderMatch},
+    genes::{load_gene_db, GeneDb, RemappedSymbol},
+    masked::{load_masked_dbs, MaskedBreakpointCount, MaskedDbBundle, MaskedFraction},
+    mtsv::{load_mt_sv_db, MtSvDb},
     pathogenic::{load_patho_dbs, PathoDbBundle},
+    regulatory::{load_regulatory_db, RegulatoryDb},
     schema::{CallInfo, SvSubType, SvType, TranscriptEffect},
     tads::{load_tads, TadSetBundle},
 };
@@ -77,6 +93,10 @@ pub struct Args {
     #[arg(long, required = true)]
     pub path_output: String,
 
+    /// Format of the output file.
+    #[arg(long, value_enum, default_value_t = OutputFormat::default())]
+    pub output_format: OutputFormat,
+
     /// Optional maximal number of total records to write out.
     #[arg(long)]
     pub max_results: Option<usize>,
@@ -96,6 +116,26 @@ pub struct Args {
     /// Optional seed for RNG.
     #[arg(long)]
     pub rng_seed: Option<u64>,
+    /// Path to the mehari transcript database for gene overlaps, e.g., to use the
+    /// ENSEMBL instead of the RefSeq transcripts; defaults to the one in `path_db`.
+    #[arg(long)]
+    pub path_mehari_tx_db: Option<String>,
+    /// Directory to cache the transcript interval trees built from the mehari transcript
+    /// database in; the trees are built on every start if not given.
+    #[arg(long)]
+    pub path_tx_trees_cache: Option<String>,
+    /// Details mode: list the background database records that contribute to the
+    /// overlap counts, with their database and its release, in the payload.
+    #[arg(long, default_value_t = false)]
+    pub bg_db_details: bool,
+    /// Optional path to write the query summary JSON to, including the releases of
+    /// the background databases.
+    #[arg(long)]
+    pub path_summary: Option<String>,
+    /// Optional path to a TSV file with the karyotypes of samples with non-standard
+    /// sex chromosome karyotypes, e.g., `XXY` or `X0`.
+    #[arg(long)]
+    pub path_karyotypes: Option<String>,
 }
 
 /// Gene information.
@@ -113,6 +153,10 @@ struct Gene {
     is_acmg: bool,
     /// Whether the gene is linked to an OMIM disease.
     is_disease_gene: bool,
+    /// Whether the SV overlaps a coding exon of the gene, only set for directly
+    /// overlapping genes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    coding_exon_overlap: Option<bool>,
 }
 
 /// Explanation of transcript effect per individual gene.
@@ -131,6 +175,8 @@ struct ResultPayload {
     callers: Vec<String>,
     /// The overlapping RCVs
     clinvar_ovl_rcvs: Vec<String>,
+    /// Summary of overlapping pathogenic/likely pathogenic ClinVar SVs.
+    clinvar_ovl_pathogenic: clinvar_sv::PathogenicOverlap,
     /// The directly overlapping genes.
     ovl_genes: Vec<Gene>,
     /// Genes that are not directly overlapping but contained in overlapping
@@ -138,6 +184,18 @@ struct ResultPayload {
     tad_genes: Vec<Gene>,
     /// Overlapping known pathogenic SV records.
     known_pathogenic: Vec<KnownPathogenicRecord>,
+    /// Known genomic disorders (recurrent CNV syndromes) matching the SV.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    genomic_disorders: Vec<DisorderMatch>,
+    /// Matching known mitochondrial SV records (chrMT only).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    known_mt_svs: Vec<mtsv::Record>,
+    /// Overlapping regulatory features, e.g., promoters and enhancers.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    regulatory_features: Vec<regulatory::Record>,
+    /// ClinGen haploinsufficiency and triplosensitivity scores of affected genes and regions.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    dosage_sensitivity: Vec<clingen_dosage::Record>,
     /// Information about the call support from the structural variant.
     call_info: IndexMap<String, CallInfo>,
     /// Whether there is an overlap with a disease gene in the overlap.
@@ -146,14 +204,32 @@ struct ResultPayload {
     tad_disease_gene: bool,
     /// The size of the SV, None for ins and BND
     sv_length: Option<u32>,
+    /// Whether the SV covers (nearly) the whole chromosome, e.g., for aneuploidies.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    whole_chromosome: bool,
+    /// The cytogenetic band(s) of the SV, e.g., `7q11.23`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cytoband: Option<String>,
+    /// ISCN-like description of the CNV for each carrier sample.
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    iscn: IndexMap<String, String>,
     /// Overlap counts with background databases.
     overlap_counts: BgDbOverlaps,
+    /// The background database records contributing to `overlap_counts`, only in
+    /// details mode.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    bg_db_records: Vec<SourcedBgDbRecord>,
     /// Overlap counts with masked sequenced.
     masked_breakpoints: MaskedBreakpointCount,
+    /// Fractions of the SV span covered by masked sequence.
+    masked_fraction: MaskedFraction,
     /// Distance to next TAD boundary.
     tad_boundary_distance: Option<u32>,
     /// Effects on the transcripts per gene.
     tx_effects: Vec<GeneTranscriptEffects>,
+    /// Preliminary ACMG/ClinGen CNV score, only for deletions and duplications.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    acmg_cnv: Option<acmg_cnv::AcmgCnvScore>,
 }
 
 /// A result record from the query.
@@ -175,6 +251,127 @@ struct ResultRecord {
     payload: String,
 }
 
+/// Writer for result records in the selected `OutputFormat`.
+enum ResultWriter {
+    /// Write TSV file.
+    Tsv(Box<csv::Writer<File>>),
+    /// Write JSONL file.
+    Jsonl(std::io::BufWriter<File>),
+    /// Write length-delimited protobuf messages.
+    Protobuf(std::io::BufWriter<File>),
+}
+
+impl ResultWriter {
+    /// Create writer for the file at `path` in the given format.
+    fn new(path: &str, output_format: OutputFormat) -> Result<Self, anyhow::Error> {
+        Ok(match output_format {
+            OutputFormat::Tsv => Self::Tsv(Box::new(
+                csv::WriterBuilder::new()
+                    .has_headers(true)
+                    .delimiter(b'\t')
+                    .quote_style(csv::QuoteStyle::Never)
+                    .from_path(path)?,
+            )),
+            OutputFormat::Jsonl => Self::Jsonl(std::io::BufWriter::new(File::create(path)?)),
+            OutputFormat::Protobuf => Self::Protobuf(std::io::BufWriter::new(File::create(path)?)),
+        })
+    }
+
+    /// Write `record` with the given `payload`.
+    ///
+    /// The `payload` member of `record` is overwritten.
+    fn write(
+        &mut self,
+        mut record: ResultRecord,
+        payload: &ResultPayload,
+    ) -> Result<(), anyhow::Error> {
+        match self {
+            Self::Tsv(writer) => {
+                record.payload = serde_json::to_string(payload)
+                    .map_err(|e| anyhow::anyhow!("could not serialize payload: {}", e))?;
+                writer
+                    .serialize(&record)
+                    .map_err(|e| anyhow::anyhow!("could not write record: {}", e))
+            }
+            Self::Jsonl(writer) => {
+                let mut value = serde_json::to_value(&record)?;
+                value["payload"] = serde_json::to_value(payload)
+                    .map_err(|e| anyhow::anyhow!("could not serialize payload: {}", e))?;
+                writeln!(writer, "{}", value)
+                    .map_err(|e| anyhow::anyhow!("could not write record: {}", e))
+            }
+            Self::Protobuf(writer) => {
+                let record = Self::to_protobuf(&record, payload)?;
+                let buf = prost::Message::encode_length_delimited_to_vec(&record);
+                writer
+                    .write_all(&buf)
+                    .map_err(|e| anyhow::anyhow!("could not write record: {}", e))
+            }
+        }
+    }
+
+    /// Convert `record` with `payload` into its protobuf representation.
+    fn to_protobuf(
+        record: &ResultRecord,
+        payload: &ResultPayload,
+    ) -> Result<pbs::StrucvarsResultRecord, anyhow::Error> {
+        let enum_name = |value: serde_json::Value| value.as_str().unwrap_or_default().to_string();
+        Ok(pbs::StrucvarsResultRecord {
+            sodar_uuid: record.sodar_uuid.to_string(),
+            release: record.release.clone(),
+            chromosome: record.chromosome.clone(),
+            chromosome_no: record.chromosome_no,
+            bin: record.bin,
+            chromosome2: record.chromosome2.clone(),
+            chromosome_no2: record.chromosome_no2,
+            bin2: record.bin2,
+            start: record.start,
+            end: record.end,
+            sv_type: enum_name(serde_json::to_value(record.sv_type)?),
+            sv_sub_type: enum_name(serde_json::to_value(record.sv_sub_type)?),
+            calls: payload
+                .call_info
+                .iter()
+                .map(|(sample, call_info)| pbs::StrucvarsCall {
+                    sample: sample.clone(),
+                    gt: call_info.genotype.clone(),
+                    gq: call_info.quality,
+                    paired_end_cov: call_info.paired_end_cov,
+                    paired_end_var: call_info.paired_end_var,
+                    split_read_cov: call_info.split_read_cov,
+                    split_read_var: call_info.split_read_var,
+                    copy_number: call_info.copy_number,
+                })
+                .collect(),
+            overlap_counts: Some(pbs::OverlapCounts {
+                dbvar: payload.overlap_counts.dbvar,
+                dgv: payload.overlap_counts.dgv,
+                dgv_gs: payload.overlap_counts.dgv_gs,
+                g1k: payload.overlap_counts.g1k,
+                gnomad_genomes: payload.overlap_counts.gnomad_genomes,
+                gnomad_exomes: payload.overlap_counts.gnomad_exomes,
+                inhouse: payload.overlap_counts.inhouse,
+            }),
+            ovl_genes: payload
+                .ovl_genes
+                .iter()
+                .filter_map(|gene| gene.symbol.clone())
+                .collect(),
+            payload_json: serde_json::to_string(payload)
+                .map_err(|e| anyhow::anyhow!("could not serialize payload: {}", e))?,
+        })
+    }
+
+    /// Flush the underlying writer.
+    fn flush(&mut self) -> Result<(), anyhow::Error> {
+        match self {
+            Self::Tsv(writer) => writer.flush(),
+            Self::Jsonl(writer) | Self::Protobuf(writer) => writer.flush(),
+        }
+        .map_err(|e| anyhow::anyhow!("could not flush output file: {}", e))
+    }
+}
+
 fn resolve_hgvs_id(gene_db: &GeneDb, hgvs_id: &str) -> Vec<Gene> {
     let record_idxs = gene_db.xlink.from_hgnc.get_vec(hgvs_id);
     if let Some(record_idxs) = record_idxs {
@@ -189,40 +386,56 @@ fn resolve_hgvs_id(gene_db: &GeneDb, hgvs_id: &str) -> Vec<Gene> {
                     hgnc_id: Some(record.hgnc_id.clone()),
                     is_acmg: gene_db.acmg.contains(record.entrez_id),
                     is_disease_gene: gene_db.mim2gene.contains(record.entrez_id),
+                    ..Default::default()
                 }
             })
             .collect()
     } else {
         vec![Gene {
-            entrez_id: None,
-            symbol: None,
-            ensembl_id: None,
             hgnc_id: Some(hgvs_id.to_string()),
-            is_acmg: false,
-            is_disease_gene: false,
+            ..Default::default()
         }]
     }
 }
 
 /// Utility struct to store statistics about counts.
 #[derive(Debug, Default)]
-struct QueryStats {
+pub(crate) struct QueryStats {
     pub count_passed: usize,
     pub count_total: usize,
     pub by_sv_type: BTreeMap<SvType, usize>,
 }
 
+/// Summary of a query as written to `Args::path_summary`.
+#[derive(Debug, Serialize)]
+struct QuerySummary<'a> {
+    /// Number of records passing the query.
+    count_passed: usize,
+    /// Total number of records.
+    count_total: usize,
+    /// Number of passing records by SV type.
+    by_sv_type: &'a BTreeMap<SvType, usize>,
+    /// Releases of the loaded background databases, `null` if unknown.
+    bg_db_versions: IndexMap<BgDbType, Option<String>>,
+    /// Outdated symbols of the gene allow list that were remapped.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    remapped_gene_symbols: Vec<RemappedSymbol>,
+}
+
 /// Run the `args.path_input` VCF file and run through the given `interpreter` writing to
 /// `args.path_output`.
-async fn run_query(
+pub(crate) async fn run_query(
     interpreter: &QueryInterpreter,
     args: &Args,
-    dbs: &InMemoryDbs,
-    mehari_tx_db: &TxSeqDatabase,
-    mehari_tx_idx: &TxIntervalTrees,
-    chrom_to_acc: &HashMap<String, String>,
+    query_dbs: &QueryDbs,
     rng: &mut rand::rngs::StdRng,
 ) -> Result<QueryStats, anyhow::Error> {
+    let QueryDbs {
+        dbs,
+        mehari_tx_db,
+        mehari_tx_idx,
+        chrom_to_acc,
+    } = query_dbs;
     let chrom_to_chrom_no = &CHROM_TO_CHROM_NO;
     let chrom_map = build_chrom_map();
     let mut stats = QueryStats::default();
@@ -231,12 +444,31 @@ async fn run_query(
     let mut input_reader = open_vcf_reader(&args.path_input).await?;
     let input_header = input_reader.read_header().await?;
 
-    // Create output TSV writer.
-    let mut csv_writer = csv::WriterBuilder::new()
-        .has_headers(true)
-        .delimiter(b'\t')
-        .quote_style(csv::QuoteStyle::Never)
-        .from_path(&args.path_output)?;
+    // The karyotypes of the samples give the baseline copy numbers for the ISCN
+    // descriptions; the sex is taken from the pedigree in the header, if any.
+    let pedigree = if input_header.other_records().contains_key("SAMPLE") {
+        crate::common::extract_pedigree_and_case_uuid(&input_header)?.0
+    } else {
+        Default::default()
+    };
+    let declared_karyotypes =
+        crate::common::karyotype::Karyotypes::from_optional_path(args.path_karyotypes.as_deref())?;
+    let karyotypes = input_header
+        .sample_names()
+        .iter()
+        .filter_map(|sample| {
+            let sex = pedigree
+                .individuals
+                .get(sample)
+                .map_or(mehari::ped::Sex::Unknown, |individual| individual.sex);
+            declared_karyotypes
+                .get(sample, sex)
+                .map(|karyotype| (sample.clone(), karyotype))
+        })
+        .collect::<indexmap::IndexMap<_, _>>();
+
+    // Create output writer.
+    let mut result_writer = ResultWriter::new(&args.path_output, args.output_format)?;
 
     // Read through input records using the query interpreter as a filter
     let mut records = input_reader.records(&input_header);
@@ -285,15 +517,16 @@ async fn run_query(
                 result_payload.masked_breakpoints.clone()
             },
             &mut |sv: &StructuralVariant| {
-                let sv_query: std::ops::Range<i32> =
-                    if matches!(sv.sv_type, SvType::Ins | SvType::Bnd) {
-                        sv.pos.saturating_sub(1)..sv.pos
-                    } else {
-                        sv.pos.saturating_sub(1)..sv.end
-                    };
-
-                ovl_hgnc_ids =
-                    overlapping_hgnc_ids(mehari_tx_db, mehari_tx_idx, chrom_idx, sv_query);
+                result_payload.masked_fraction = dbs.masked.masked_fraction(sv, &chrom_map);
+                result_payload.masked_fraction.clone()
+            },
+            &mut |sv: &StructuralVariant| {
+                ovl_hgnc_ids = overlapping_hgnc_ids(
+                    mehari_tx_db,
+                    mehari_tx_idx,
+                    chrom_idx,
+                    gene_overlap_range(sv),
+                );
                 ovl_hgnc_ids.sort();
                 ovl_hgnc_ids.dedup();
                 ovl_hgnc_ids.clone()
@@ -312,8 +545,28 @@ async fn run_query(
         )?;
 
         if passes.pass_all {
+            result_payload.cytoband = match record_sv.sv_type {
+                SvType::Ins | SvType::Bnd => dbs.cytobands.describe_range(
+                    &record_sv.chrom,
+                    record_sv.pos as usize,
+                    record_sv.pos as usize,
+                ),
+                _ => dbs.cytobands.describe_range(
+                    &record_sv.chrom,
+                    record_sv.pos as usize,
+                    record_sv.end as usize,
+                ),
+            };
             if record_sv.sv_type != SvType::Ins && record_sv.sv_type != SvType::Bnd {
-                result_payload.sv_length = Some((record_sv.end - record_sv.pos + 1) as u32);
+                result_payload.sv_length = record_sv.size();
+                result_payload.whole_chromosome =
+                    record_sv.is_whole_chromosome(args.genome_release);
+                result_payload.iscn = iscn::describe_samples(
+                    args.genome_release,
+                    &dbs.cytobands,
+                    &karyotypes,
+                    &record_sv,
+                )?;
             }
 
             // Copy effective and compatible genotypes to output.
@@ -330,9 +583,30 @@ async fn run_query(
             stats.count_passed += 1;
             *stats.by_sv_type.entry(record_sv.sv_type).or_default() += 1;
 
+            // In details mode, list the background records behind the overlap counts.
+            if args.bg_db_details {
+                result_payload.bg_db_records = dbs.bg_dbs.overlapping_records(
+                    &record_sv,
+                    &interpreter.query,
+                    &chrom_map,
+                    args.slack_ins,
+                    args.slack_bnd,
+                );
+            }
+
             // Get overlaps with known pathogenic SVs and ClinVar SVs
             result_payload.known_pathogenic =
                 dbs.patho_dbs.overlapping_records(&record_sv, &chrom_map);
+            result_payload.genomic_disorders = dbs.disorders.matching_records(
+                &record_sv,
+                &chrom_map,
+                interpreter.query.genomic_disorder_min_overlap,
+            );
+            result_payload.known_mt_svs = dbs.mt_svs.matching_records(&record_sv, args.min_overlap);
+            result_payload.regulatory_features =
+                dbs.regulatory.overlapping_records(&record_sv, &chrom_map)?;
+            result_payload.dosage_sensitivity =
+                dbs.dosage.affected_records(&record_sv, &chrom_map)?;
             result_payload.clinvar_ovl_rcvs = dbs
                 .clinvar_sv
                 .overlapping_rcvs(
@@ -344,6 +618,11 @@ async fn run_query(
                 .into_iter()
                 .map(|rcv| format!("RCV{rcv:09}"))
                 .collect();
+            result_payload.clinvar_ovl_pathogenic = dbs.clinvar_sv.pathogenic_overlap(
+                &record_sv,
+                &chrom_map,
+                interpreter.query.clinvar_sv_min_overlap,
+            );
 
             // Get genes in overlapping TADs
             let tad_hgnc_ids = {
@@ -373,10 +652,18 @@ async fn run_query(
                     .boundary_dist(TadSetChoice::Hesc, &record_sv, &chrom_map);
 
             // Convert the genes into more verbose records and put them into the result
+            let coding_hgnc_ids = coding_exon_hgnc_ids(
+                mehari_tx_db,
+                mehari_tx_idx,
+                chrom_idx,
+                gene_overlap_range(&record_sv),
+            );
             ovl_hgnc_ids.iter().for_each(|hgvs_id| {
-                result_payload
-                    .ovl_genes
-                    .append(&mut resolve_hgvs_id(&dbs.genes, hgvs_id))
+                let mut genes = resolve_hgvs_id(&dbs.genes, hgvs_id);
+                genes.iter_mut().for_each(|gene| {
+                    gene.coding_exon_overlap = Some(coding_hgnc_ids.contains(hgvs_id))
+                });
+                result_payload.ovl_genes.append(&mut genes)
             });
             result_payload.ovl_disease_gene = result_payload
                 .ovl_genes
@@ -392,6 +679,29 @@ async fn run_query(
                 .iter()
                 .any(|gene| gene.is_disease_gene);
 
+            // Compute the preliminary ACMG/ClinGen CNV score.
+            let breakpoint_range = |pos: i32| pos.saturating_sub(1)..pos;
+            let gene_content = GeneContent {
+                protein_coding_genes: protein_coding_hgnc_ids(
+                    mehari_tx_db,
+                    mehari_tx_idx,
+                    chrom_idx,
+                    gene_overlap_range(&record_sv),
+                )
+                .len(),
+                breakpoint_in_coding_gene: [record_sv.pos, record_sv.end].iter().any(|pos| {
+                    !protein_coding_hgnc_ids(
+                        mehari_tx_db,
+                        mehari_tx_idx,
+                        chrom_idx,
+                        breakpoint_range(*pos),
+                    )
+                    .is_empty()
+                }),
+            };
+            result_payload.acmg_cnv =
+                acmg_cnv::score(&dbs.dosage, &record_sv, &chrom_map, &gene_content);
+
             if let Some(max_results) = args.max_results {
                 if stats.count_total > max_results {
                     warn!(
@@ -420,6 +730,14 @@ async fn run_query(
                     )? as u32,
                     0,
                 )
+            } else if record_sv.is_origin_spanning() {
+                (
+                    mehari::annotate::seqvars::binning::bin_from_range(
+                        record_sv.pos - 1,
+                        crate::common::interval::MT_LENGTH,
+                    )? as u32,
+                    0,
+                )
             } else {
                 (
                     mehari::annotate::seqvars::binning::bin_from_range(
@@ -433,8 +751,8 @@ async fn run_query(
             // Finally, write out the record.
             let mut uuid_buf = [0u8; 16];
             rng.fill_bytes(&mut uuid_buf);
-            csv_writer
-                .serialize(&ResultRecord {
+            result_writer.write(
+                ResultRecord {
                     sodar_uuid: Uuid::from_bytes(uuid_buf),
                     release: match args.genome_release {
                         GenomeRelease::Grch37 => "GRCh37".into(),
@@ -459,12 +777,13 @@ async fn run_query(
                     pe_orientation: record_sv.strand_orientation,
                     sv_type: record_sv.sv_type,
                     sv_sub_type: record_sv.sv_sub_type,
-                    payload: serde_json::to_string(&result_payload)
-                        .map_err(|e| anyhow::anyhow!("could not serialize payload: {}", e))?,
-                })
-                .map_err(|e| anyhow::anyhow!("could not write record: {}", e))?;
+                    payload: Default::default(),
+                },
+                &result_payload,
+            )?;
         }
     }
+    result_writer.flush()?;
 
     Ok(stats)
 }
@@ -484,6 +803,7 @@ fn construct_gene(entrez_id: u32, gene_db: &GeneDb) -> Gene {
         hgnc_id: Some(record.hgnc_id.clone()),
         is_acmg: gene_db.acmg.contains(record.entrez_id),
         is_disease_gene: gene_db.mim2gene.contains(record.entrez_id),
+        ..Default::default()
     }
 }
 
@@ -695,7 +1015,11 @@ fn compute_tx_effects_for_breakpoint(
                     .or_default()
                     .extend(gene_tx_effects_for_bp(tx, sv.pos));
             } else {
-                tracing::warn!("could not resolve HGNC gene ID {:?}", tx.gene_id)
+                tracing::warn!(
+                    "{}: could not resolve HGNC gene ID {:?}",
+                    crate::codes::STRUCVARS_QUERY_UNKNOWN_GENE,
+                    tx.gene_id
+                )
             }
         }
 
@@ -756,7 +1080,11 @@ fn compute_tx_effects_for_linear(
                     .or_default()
                     .extend(gene_tx_effect_for_range(tx, sv.pos, sv.end));
             } else {
-                tracing::warn!("could not resolve HGNC gene ID {:?}", tx.gene_id)
+                tracing::warn!(
+                    "{}: could not resolve HGNC gene ID {:?}",
+                    crate::codes::STRUCVARS_QUERY_UNKNOWN_GENE,
+                    tx.gene_id
+                )
             }
         }
 
@@ -820,6 +1148,76 @@ pub fn overlapping_hgnc_ids(
         .collect::<Vec<_>>()
 }
 
+/// Return the 0-based, half-open range of `sv` to use for gene overlaps.
+fn gene_overlap_range(sv: &StructuralVariant) -> std::ops::Range<i32> {
+    if matches!(sv.sv_type, SvType::Ins | SvType::Bnd) {
+        sv.pos.saturating_sub(1)..sv.pos
+    } else {
+        sv.pos.saturating_sub(1)..sv.end
+    }
+}
+
+/// Return whether `query` overlaps with the coding part of an exon of `tx`.
+fn overlaps_coding_exon(tx: &Transcript, query: &std::ops::Range<i32>) -> bool {
+    tx.genome_alignments.iter().any(|genome_alignment| {
+        let (Some(cds_start), Some(cds_end)) =
+            (genome_alignment.cds_start, genome_alignment.cds_end)
+        else {
+            // non-coding transcript
+            return false;
+        };
+        genome_alignment.exons.iter().any(|exon_alignment| {
+            let begin = std::cmp::max(exon_alignment.alt_start_i, cds_start) - 1;
+            let end = std::cmp::min(exon_alignment.alt_end_i, cds_end);
+            begin < end && query.start < end && begin < query.end
+        })
+    })
+}
+
+/// Compute HGNC gene IDs with a coding exon overlapping the given interval.
+fn coding_exon_hgnc_ids(
+    tx_seq_db: &TxSeqDatabase,
+    tx_idx: &TxIntervalTrees,
+    chrom_idx: usize,
+    query: std::ops::Range<i32>,
+) -> HashSet<String> {
+    let tx_db = tx_seq_db
+        .tx_db
+        .as_ref()
+        .expect("transcripts must be present");
+    let tree = &tx_idx.trees[chrom_idx];
+    tree.find(query.clone())
+        .iter()
+        .map(|it| &tx_db.transcripts[*it.data() as usize])
+        .filter(|tx| overlaps_coding_exon(tx, &query))
+        .map(|tx| tx.gene_id.clone())
+        .collect()
+}
+
+/// Compute HGNC gene IDs of protein-coding genes overlapping the given interval.
+fn protein_coding_hgnc_ids(
+    tx_seq_db: &TxSeqDatabase,
+    tx_idx: &TxIntervalTrees,
+    chrom_idx: usize,
+    query: std::ops::Range<i32>,
+) -> HashSet<String> {
+    let tx_db = tx_seq_db
+        .tx_db
+        .as_ref()
+        .expect("transcripts must be present");
+    let tree = &tx_idx.trees[chrom_idx];
+    tree.find(query.clone())
+        .iter()
+        .map(|it| &tx_db.transcripts[*it.data() as usize])
+        .filter(|tx| {
+            tx.genome_alignments
+                .iter()
+                .any(|genome_alignment| genome_alignment.cds_start.is_some())
+        })
+        .map(|tx| tx.gene_id.clone())
+        .collect()
+}
+
 /// Bundle the used in-memory database to reduce argument count.
 #[derive(Default, Debug)]
 pub struct InMemoryDbs {
@@ -829,14 +1227,31 @@ pub struct InMemoryDbs {
     pub masked: MaskedDbBundle,
     pub genes: GeneDb,
     pub clinvar_sv: ClinvarSv,
+    pub mt_svs: MtSvDb,
+    pub regulatory: RegulatoryDb,
+    pub dosage: DosageDb,
+    pub disorders: DisorderDb,
+    pub cytobands: Cytobands,
+}
+
+/// Gene allow list translated to HGNC gene IDs.
+#[derive(Debug, Default, Clone)]
+pub struct TranslatedGeneAllowlist {
+    /// The HGNC gene IDs.
+    pub hgnc_ids: HashSet<String>,
+    /// The outdated symbols that were remapped to approved ones.
+    pub remapped_symbols: Vec<RemappedSymbol>,
 }
 
 /// Translate gene allow list to gene identifier sfrom
+///
+/// Outdated symbols are remapped via the previous and alias symbols of the genes.
 pub fn translate_gene_allowlist(
     gene_allowlist: &Vec<String>,
     dbs: &InMemoryDbs,
-) -> HashSet<String> {
+) -> TranslatedGeneAllowlist {
     let mut result = HashSet::new();
+    let mut remapped_symbols = Vec::new();
 
     let re_entrez = regex::Regex::new(r"^\d+").expect("invalid regex in source code");
     let re_ensembl: regex::Regex =
@@ -890,54 +1305,70 @@ pub fn translate_gene_allowlist(
         } else if let Some(gene_id) = symbol_to_id.get(gene) {
             result.insert(gene_id.clone());
         } else {
-            warn!("Could not map candidate gene symbol {}", &gene);
+            match dbs.genes.xlink.resolve_outdated_symbol(gene).as_slice() {
+                [] => warn!("Could not map candidate gene symbol {}", &gene),
+                [record] => {
+                    warn!(
+                        "{}: remapped outdated gene symbol {} to {} ({})",
+                        crate::codes::COMMON_GENE_SYMBOL_REMAPPED,
+                        &gene,
+                        &record.symbol,
+                        &record.hgnc_id
+                    );
+                    result.insert(record.hgnc_id.clone());
+                    remapped_symbols.push(RemappedSymbol {
+                        symbol: gene.to_string(),
+                        approved_symbol: record.symbol.clone(),
+                        hgnc_id: record.hgnc_id.clone(),
+                    });
+                }
+                records => warn!(
+                    "{}: ignoring outdated gene symbol {} of several genes: {}",
+                    crate::codes::COMMON_GENE_SYMBOL_AMBIGUOUS,
+                    &gene,
+                    records
+                        .iter()
+                        .map(|record| record.symbol.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            }
         }
     }
 
-    result
+    TranslatedGeneAllowlist {
+        hgnc_ids: result,
+        remapped_symbols,
+    }
 }
 
-/// Load database from the given path with the given genome release.
-pub fn load_databases(
-    path_worker_db: &str,
-    genome_release: GenomeRelease,
-    max_tad_distance: i32,
-) -> Result<InMemoryDbs, anyhow::Error> {
-    Ok(InMemoryDbs {
-        bg_dbs: load_bg_dbs(path_worker_db, genome_release)?,
-        patho_dbs: load_patho_dbs(path_worker_db, genome_release)?,
-        tad_sets: load_tads(path_worker_db, genome_release, max_tad_distance)?,
-        masked: load_masked_dbs(path_worker_db, genome_release)?,
-        genes: load_gene_db(path_worker_db, genome_release)?,
-        clinvar_sv: load_clinvar_sv(path_worker_db, genome_release)?,
-    })
+/// The worker and mehari databases used by the queries, loaded once.
+pub struct QueryDbs {
+    /// The worker databases.
+    pub dbs: InMemoryDbs,
+    /// The mehari transcript database.
+    pub mehari_tx_db: TxSeqDatabase,
+    /// The interval trees of the transcripts in `mehari_tx_db`.
+    pub mehari_tx_idx: TxIntervalTrees,
+    /// Mapping from canonical chromosome name to RefSeq accession.
+    pub chrom_to_acc: HashMap<String, String>,
 }
 
-/// Main entry point for `sv query` sub command.
-pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
-    let before_anything = Instant::now();
-    tracing::info!("args_common = {:?}", &args_common);
-    tracing::info!("args = {:?}", &args);
-
-    // Initialize the random number generator from command line seed if given or local entropy
-    // source.
-    let mut rng = if let Some(rng_seed) = args.rng_seed {
-        rand::rngs::StdRng::seed_from_u64(rng_seed)
-    } else {
-        rand::rngs::StdRng::from_entropy()
-    };
-
-    tracing::info!("Loading query...");
-    let query: CaseQuery = serde_json::from_reader(File::open(&args.path_query_json)?)?;
-    tracing::info!(
-        "... done loading query = {}",
-        &serde_json::to_string(&query)?
-    );
-
+/// Load the worker databases from `path_db` and the mehari transcript database from
+/// `path_mehari_tx_db`, defaulting to the one in `path_db`.
+///
+/// The transcript interval trees are cached in `path_tx_trees_cache` if given.
+pub fn load_query_dbs(
+    path_db: &str,
+    genome_release: GenomeRelease,
+    max_tad_distance: i32,
+    path_mehari_tx_db: Option<&str>,
+    path_tx_trees_cache: Option<&str>,
+) -> Result<QueryDbs, anyhow::Error> {
     tracing::info!("Loading worker databases...");
     let before_loading = Instant::now();
-    let path_worker_db = format!("{}/worker", &args.path_db);
-    let dbs = load_databases(&path_worker_db, args.genome_release, args.max_tad_distance)?;
+    let path_worker_db = format!("{}/worker", path_db);
+    let dbs = load_databases(&path_worker_db, genome_release, max_tad_distance)?;
     tracing::info!(
         "...done loading databases in {:?}",
         before_loading.elapsed()
@@ -947,11 +1378,13 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
 
     tracing::info!("Loading mehari tx database...");
     let before_loading = Instant::now();
-    let path_mehari_tx_db = format!(
-        "{}/mehari/{}/txs.bin.zst",
-        &args.path_db,
-        &args.genome_release.to_string()
-    );
+    let path_mehari_tx_db = path_mehari_tx_db.map(String::from).unwrap_or_else(|| {
+        format!(
+            "{}/mehari/{}/txs.bin.zst",
+            path_db,
+            &genome_release.to_string()
+        )
+    });
     tracing::debug!("  path = {}", &path_mehari_tx_db);
     let mehari_tx_db = mehari::annotate::seqvars::load_tx_db(&path_mehari_tx_db)?;
     tracing::info!(
@@ -960,8 +1393,17 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
     );
     tracing::info!("Building mehari index data structures...");
     let before_building = Instant::now();
-    let mehari_tx_idx = TxIntervalTrees::new(&mehari_tx_db, args.genome_release.into());
-    let chrom_to_acc = ASSEMBLY_INFOS[args.genome_release.into()]
+    let mehari_tx_idx = if let Some(path_tx_trees_cache) = path_tx_trees_cache {
+        tx_trees::load_or_build(
+            &mehari_tx_db,
+            &path_mehari_tx_db,
+            genome_release,
+            path_tx_trees_cache,
+        )?
+    } else {
+        TxIntervalTrees::new(&mehari_tx_db, genome_release.into())
+    };
+    let chrom_to_acc = ASSEMBLY_INFOS[genome_release.into()]
         .sequences
         .iter()
         .map(|record| {
@@ -978,29 +1420,92 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
 
     trace_rss_now();
 
-    tracing::info!("Translating gene allow list...");
-    let hgvs_allowlist = if let Some(gene_allowlist) = &query.gene_allowlist {
-        if gene_allowlist.is_empty() {
-            None
-        } else {
-            Some(translate_gene_allowlist(gene_allowlist, &dbs))
+    Ok(QueryDbs {
+        dbs,
+        mehari_tx_db,
+        mehari_tx_idx,
+        chrom_to_acc,
+    })
+}
+
+/// Construct the interpreter for `query`, translating its gene allow list with `dbs`.
+pub fn build_interpreter(query: CaseQuery, dbs: &InMemoryDbs) -> QueryInterpreter {
+    let translated = match &query.gene_allowlist {
+        Some(gene_allowlist) if !gene_allowlist.is_empty() => {
+            Some(translate_gene_allowlist(gene_allowlist, dbs))
         }
+        _ => None,
+    };
+    let remapped_gene_symbols = translated
+        .as_ref()
+        .map(|translated| translated.remapped_symbols.clone())
+        .unwrap_or_default();
+    QueryInterpreter::new(query, translated.map(|translated| translated.hgnc_ids))
+        .with_remapped_gene_symbols(remapped_gene_symbols)
+}
+
+/// Load database from the given path with the given genome release.
+pub fn load_databases(
+    path_worker_db: &str,
+    genome_release: GenomeRelease,
+    max_tad_distance: i32,
+) -> Result<InMemoryDbs, anyhow::Error> {
+    Ok(InMemoryDbs {
+        bg_dbs: load_bg_dbs(path_worker_db, genome_release)?,
+        patho_dbs: load_patho_dbs(path_worker_db, genome_release)?,
+        tad_sets: load_tads(path_worker_db, genome_release, max_tad_distance)?,
+        masked: load_masked_dbs(path_worker_db, genome_release)?,
+        genes: load_gene_db(path_worker_db, genome_release)?,
+        clinvar_sv: load_clinvar_sv(path_worker_db, genome_release)?,
+        mt_svs: load_mt_sv_db(path_worker_db, genome_release)?,
+        regulatory: load_regulatory_db(path_worker_db, genome_release)?,
+        dosage: load_dosage_db(path_worker_db, genome_release)?,
+        disorders: load_disorder_db(path_worker_db, genome_release)?,
+        cytobands: Cytobands::load(path_worker_db, genome_release)?,
+    })
+}
+
+/// Main entry point for `sv query` sub command.
+pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), anyhow::Error> {
+    let before_anything = Instant::now();
+    tracing::info!("args_common = {:?}", &args_common);
+    tracing::info!("args = {:?}", &args);
+
+    // Initialize the random number generator from command line seed if given or local entropy
+    // source.
+    let mut rng = if let Some(rng_seed) = args.rng_seed {
+        rand::rngs::StdRng::seed_from_u64(rng_seed)
     } else {
-        None
+        rand::rngs::StdRng::from_entropy()
     };
 
+    tracing::info!("Loading query...");
+    let query: CaseQuery =
+        presets::resolve(serde_json::from_reader(File::open(&args.path_query_json)?)?)?;
+    tracing::info!(
+        "... done loading query = {}",
+        &serde_json::to_string(&query)?
+    );
+
+    let query_dbs = load_query_dbs(
+        &args.path_db,
+        args.genome_release,
+        args.max_tad_distance,
+        args.path_mehari_tx_db.as_deref(),
+        args.path_tx_trees_cache.as_deref(),
+    )?;
+
+    tracing::info!("Translating gene allow list...");
+    let interpreter = build_interpreter(query, &query_dbs.dbs);
+
     tracing::info!("Running queries...");
     let before_query = Instant::now();
-    let query_stats = run_query(
-        &QueryInterpreter::new(query, hgvs_allowlist),
-        args,
-        &dbs,
-        &mehari_tx_db,
-        &mehari_tx_idx,
-        &chrom_to_acc,
-        &mut rng,
-    )
-    .await?;
+    let partial_guard = crate::common::partial::PartialOutputGuard::new(
+        &args.path_output,
+        crate::common::partial::OnFailure::Remove,
+    );
+    let query_stats = run_query(&interpreter, args, &query_dbs, &mut rng).await?;
+    partial_guard.finish();
     tracing::info!("... done running query in {:?}", before_query.elapsed());
     tracing::info!(
         "summary: {} records passed out of {}",
@@ -1011,6 +1516,22 @@ pub async fn run(args_common: &crate::common::Args, args: &Args) -> Result<(), a
     for (sv_type, count) in query_stats.by_sv_type.iter() {
         tracing::info!("{:?} -- {}", sv_type, count);
     }
+    let bg_db_versions = query_dbs.dbs.bg_dbs.versions();
+    tracing::info!("background database releases");
+    for (db_type, version) in bg_db_versions.iter() {
+        tracing::info!("{} -- {}", db_type, version.as_deref().unwrap_or("unknown"));
+    }
+    if let Some(path_summary) = &args.path_summary {
+        let summary = QuerySummary {
+            count_passed: query_stats.count_passed,
+            count_total: query_stats.count_total,
+            by_sv_type: &query_stats.by_sv_type,
+            bg_db_versions,
+            remapped_gene_symbols: interpreter.remapped_gene_symbols.clone(),
+        };
+        std::fs::write(path_summary, serde_json::to_string_pretty(&summary)?)
+            .map_err(|e| anyhow::anyhow!("could not write summary {}: {}", path_summary, e))?;
+    }
 
     trace_rss_now();
 
@@ -1036,12 +1557,18 @@ mod test {
             path_query_json: "tests/strucvars/query/Case_3.query.json".into(),
             path_input: "tests/strucvars/query/Case_3.ingested.vcf".into(),
             path_output,
+            output_format: Default::default(),
             max_results: None,
             slack_bnd: 50,
             slack_ins: 50,
             min_overlap: 0.8,
             max_tad_distance: 10_000,
             rng_seed: Some(42),
+            path_mehari_tx_db: None,
+            path_tx_trees_cache: None,
+            bg_db_details: false,
+            path_summary: None,
+            path_karyotypes: None,
         };
         super::run(&args_common, &args).await?;
 
@@ -1049,4 +1576,158 @@ mod test {
 
         Ok(())
     }
+
+    #[rstest::rstest]
+    #[case::five_prime_utr(0..950, true, false)]
+    #[case::start_codon(950..1000, true, true)]
+    #[case::before_start_codon(950..999, true, false)]
+    #[case::intron(1200..1400, true, false)]
+    #[case::last_coding_exon(1900..2100, true, true)]
+    #[case::three_prime_utr(2100..2400, true, false)]
+    #[case::non_coding(950..1000, false, false)]
+    fn overlaps_coding_exon(
+        #[case] query: std::ops::Range<i32>,
+        #[case] is_coding: bool,
+        #[case] expected: bool,
+    ) {
+        use mehari::pbs::txs::{ExonAlignment, GenomeAlignment, Transcript};
+
+        let (cds_start, cds_end) = if is_coding {
+            (Some(1_000), Some(2_000))
+        } else {
+            (None, None)
+        };
+        let tx = Transcript {
+            genome_alignments: vec![GenomeAlignment {
+                cds_start,
+                cds_end,
+                exons: vec![
+                    ExonAlignment {
+                        alt_start_i: 900,
+                        alt_end_i: 1_100,
+                        ..Default::default()
+                    },
+                    ExonAlignment {
+                        alt_start_i: 1_500,
+                        alt_end_i: 2_500,
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(super::overlaps_coding_exon(&tx, &query), expected);
+    }
+
+    #[rstest::rstest]
+    #[case::tsv(crate::strucvars::query::OutputFormat::Tsv)]
+    #[case::jsonl(crate::strucvars::query::OutputFormat::Jsonl)]
+    fn result_writer(#[case] output_format: super::OutputFormat) -> Result<(), anyhow::Error> {
+        mehari::common::set_snapshot_suffix!("{:?}", output_format);
+
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_output = format!("{}/out", tmpdir.to_string_lossy());
+
+        let mut writer = super::ResultWriter::new(&path_output, output_format)?;
+        writer.write(
+            super::ResultRecord {
+                release: "GRCh37".into(),
+                chromosome: "1".into(),
+                chromosome2: "1".into(),
+                start: 1_000,
+                end: 2_000,
+                ..Default::default()
+            },
+            &super::ResultPayload {
+                callers: vec!["DELLY".into()],
+                sv_length: Some(1_001),
+                ..Default::default()
+            },
+        )?;
+        writer.flush()?;
+
+        insta::assert_snapshot!(std::fs::read_to_string(&path_output)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn result_writer_protobuf() -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path_output = format!("{}/out", tmpdir.to_string_lossy());
+
+        let mut writer = super::ResultWriter::new(&path_output, super::OutputFormat::Protobuf)?;
+        let payload = super::ResultPayload {
+            callers: vec!["DELLY".into()],
+            sv_length: Some(1_001),
+            overlap_counts: super::BgDbOverlaps {
+                dgv: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        writer.write(
+            super::ResultRecord {
+                release: "GRCh37".into(),
+                chromosome: "1".into(),
+                chromosome2: "1".into(),
+                start: 1_000,
+                end: 2_000,
+                ..Default::default()
+            },
+            &payload,
+        )?;
+        writer.flush()?;
+        drop(writer);
+
+        let buf = std::fs::read(&path_output)?;
+        let record: crate::common::pbs::StrucvarsResultRecord =
+            prost::Message::decode_length_delimited(buf.as_slice())?;
+        assert_eq!(record.start, 1_000);
+        assert_eq!(record.end, 2_000);
+        assert_eq!(record.sv_type, "DEL");
+        assert_eq!(record.overlap_counts.map(|counts| counts.dgv), Some(2));
+        assert_eq!(record.payload_json, serde_json::to_string(&payload)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn translate_gene_allowlist_remapped() {
+        use super::genes::{RemappedSymbol, XlinkDbRecord};
+
+        let mut dbs = super::InMemoryDbs::default();
+        for (hgnc_id, symbol, prev_symbols, alias_symbols) in [
+            ("HGNC:25443", "MPAN", vec!["C19orf12"], vec![]),
+            ("HGNC:1", "GENE1", vec![], vec!["NBIA4"]),
+            ("HGNC:2", "GENE2", vec![], vec!["NBIA4"]),
+        ] {
+            dbs.genes.xlink.push(XlinkDbRecord {
+                hgnc_id: hgnc_id.into(),
+                symbol: symbol.into(),
+                prev_symbols: prev_symbols.into_iter().map(String::from).collect(),
+                alias_symbols: alias_symbols.into_iter().map(String::from).collect(),
+                ..Default::default()
+            });
+        }
+
+        let translated = super::translate_gene_allowlist(
+            &vec!["GENE1".into(), "C19orf12".into(), "NBIA4".into()],
+            &dbs,
+        );
+
+        let mut hgnc_ids = translated.hgnc_ids.into_iter().collect::<Vec<_>>();
+        hgnc_ids.sort();
+        assert_eq!(hgnc_ids, vec!["HGNC:1", "HGNC:25443"]);
+        assert_eq!(
+            translated.remapped_symbols,
+            vec![RemappedSymbol {
+                symbol: "C19orf12".into(),
+                approved_symbol: "MPAN".into(),
+                hgnc_id: "HGNC:25443".into(),
+            }]
+        );
+    }
 }
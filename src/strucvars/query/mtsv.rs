@@ -0,0 +1,169 @@
+//! Code for matching mitochondrial SVs against a reference set of known chrMT deletions.
+//!
+//! The mitochondrial genome is circular, so deletions may span the origin.  Such
+//! deletions are given with an end position that is smaller than the start position.
+
+use std::path::Path;
+
+use mehari::common::io::std::open_read_maybe_gz;
+use serde::Serialize;
+use tracing::info;
+
+use crate::common::{
+    interval::{is_mt, Topology},
+    reciprocal_overlap, GenomeRelease,
+};
+
+use super::schema::{StructuralVariant, SvType};
+
+/// Information to store for a known mitochondrial SV.
+#[derive(Default, Debug, Serialize, Clone, PartialEq)]
+pub struct Record {
+    /// 0-based begin position.
+    pub begin: i32,
+    /// End position, smaller than `begin` for deletions spanning the origin.
+    pub end: i32,
+    /// Identifier associated with the record, e.g., "common_deletion".
+    pub id: String,
+}
+
+/// Reference set of known mitochondrial SVs.
+///
+/// The set is small, so we simply do a linear scan instead of using an interval tree.
+#[derive(Default, Debug)]
+pub struct MtSvDb {
+    /// The records of the reference set.
+    pub records: Vec<Record>,
+}
+
+impl MtSvDb {
+    /// Return records matching the deletion `sv` with the given reciprocal overlap.
+    ///
+    /// Only deletions on the mitochondrial genome are considered.
+    pub fn matching_records(&self, sv: &StructuralVariant, min_overlap: f32) -> Vec<Record> {
+        if !is_mt(&sv.chrom) || sv.sv_type != SvType::Del {
+            return Vec::new();
+        }
+
+        let topology = Topology::from_chrom(&sv.chrom);
+        let sv_range = sv.pos.saturating_sub(1)..sv.end;
+        self.records
+            .iter()
+            .filter(|record| {
+                reciprocal_overlap(sv_range.clone(), record.begin..record.end, topology)
+                    >= min_overlap
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// Module with code for loading data from input.
+mod input {
+    use serde::Deserialize;
+
+    /// Type for record structs from input.
+    #[derive(Deserialize, Debug)]
+    pub struct Record {
+        /// Chromosome name
+        pub chrom: String,
+        /// 0-based begin position from BED.
+        pub begin: i32,
+        /// 0-based end position from BED.
+        pub end: i32,
+        /// Identifier of the record.
+        pub id: String,
+    }
+}
+
+/// Load the known mitochondrial SVs from the BED file at `path`.
+#[tracing::instrument]
+pub fn load_mt_sv_db_records(path: &Path) -> Result<MtSvDb, anyhow::Error> {
+    tracing::debug!("loading mitochondrial SV records from {:?}...", path);
+
+    // Setup CSV reader for BED file - header is written as comment and must be
+    // ignored.
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false) // BED has no header
+        .comment(Some(b'#'))
+        .delimiter(b'\t')
+        .from_reader(open_read_maybe_gz(path.to_str().unwrap())?);
+    let mut result = MtSvDb::default();
+    for record in reader.deserialize() {
+        let record: input::Record = record?;
+        if !is_mt(&record.chrom) {
+            anyhow::bail!("invalid chromosome for mitochondrial SV: {}", &record.chrom);
+        }
+        result.records.push(Record {
+            begin: record.begin,
+            end: record.end,
+            id: record.id,
+        });
+    }
+    tracing::debug!("... done loading {} records", result.records.len());
+
+    Ok(result)
+}
+
+/// Load the mitochondrial SV reference set from database given the configuration.
+///
+/// The reference set is optional; an empty set is returned if the file is missing.
+#[tracing::instrument]
+pub fn load_mt_sv_db(
+    path_db: &str,
+    genome_release: GenomeRelease,
+) -> Result<MtSvDb, anyhow::Error> {
+    info!("Loading mitochondrial SV db");
+    let path = Path::new(path_db).join(format!("{}/strucvars/mt_svs.bed", genome_release));
+    if path.exists() {
+        load_mt_sv_db_records(&path)
+    } else {
+        info!("  no mitochondrial SV db at {:?}, skipping", &path);
+        Ok(MtSvDb::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::strucvars::query::{fixtures::build_sv, schema::SvType};
+
+    use super::{MtSvDb, Record};
+
+    #[rstest::rstest]
+    #[case::common_deletion("MT", SvType::Del, 8_470, 13_447, vec!["common_deletion"])]
+    #[case::common_deletion_chr_m("chrM", SvType::Del, 8_480, 13_440, vec!["common_deletion"])]
+    #[case::wrong_type("MT", SvType::Dup, 8_470, 13_447, vec![])]
+    #[case::wrong_chrom("1", SvType::Del, 8_470, 13_447, vec![])]
+    #[case::origin_spanning("MT", SvType::Del, 16_001, 300, vec!["origin"])]
+    #[case::too_small("MT", SvType::Del, 9_000, 10_000, vec![])]
+    fn matching_records(
+        #[case] chrom: &str,
+        #[case] sv_type: SvType,
+        #[case] pos: i32,
+        #[case] end: i32,
+        #[case] expected: Vec<&str>,
+    ) {
+        let db = MtSvDb {
+            records: vec![
+                Record {
+                    begin: 8_469,
+                    end: 13_447,
+                    id: "common_deletion".into(),
+                },
+                Record {
+                    begin: 16_000,
+                    end: 300,
+                    id: "origin".into(),
+                },
+            ],
+        };
+        let sv = build_sv(chrom, sv_type, pos, end);
+
+        let ids = db
+            .matching_records(&sv, 0.8)
+            .into_iter()
+            .map(|record| record.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, expected);
+    }
+}
@@ -0,0 +1,160 @@
+//! Named query settings presets bundled with the worker.
+//!
+//! A query JSON may name a preset with the `preset` key, e.g.,
+//! `{"preset": "default-clinical-cnv", "sv_size_min": 1000}`.  The settings of the
+//! preset are used as the defaults and all other keys of the query JSON override them.
+
+use strum::IntoEnumIterator as _;
+use strum_macros::{Display, EnumIter, EnumString};
+
+use super::schema::{CaseQuery, SvType, TranscriptEffect};
+
+/// Key of the preset name in the query JSON.
+pub const PRESET_KEY: &str = "preset";
+
+/// The bundled presets.
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    EnumIter,
+    EnumString,
+    Display,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum Preset {
+    /// Rare CNVs and SVs affecting genes, for clinical diagnostics.
+    DefaultClinicalCnv,
+    /// Only filter out variants common in gnomAD or in-house, for research.
+    ResearchPermissive,
+    /// Only large events that are visible on the karyotype level.
+    KaryotypeLevelOnly,
+}
+
+impl Preset {
+    /// Return the query settings of the preset.
+    pub fn case_query(self) -> CaseQuery {
+        match self {
+            Preset::DefaultClinicalCnv => CaseQuery {
+                svdb_dgv_enabled: true,
+                svdb_dgv_min_overlap: Some(0.75),
+                svdb_dgv_max_count: Some(10),
+                svdb_dgv_gs_enabled: true,
+                svdb_dgv_gs_min_overlap: Some(0.75),
+                svdb_dgv_gs_max_count: Some(10),
+                svdb_gnomad_genomes_enabled: true,
+                svdb_gnomad_genomes_min_overlap: Some(0.75),
+                svdb_gnomad_genomes_max_count: Some(10),
+                svdb_gnomad_exomes_enabled: true,
+                svdb_gnomad_exomes_min_overlap: Some(0.75),
+                svdb_gnomad_exomes_max_count: Some(10),
+                svdb_dbvar_enabled: true,
+                svdb_dbvar_min_overlap: Some(0.75),
+                svdb_dbvar_max_count: Some(10),
+                svdb_g1k_enabled: true,
+                svdb_g1k_min_overlap: Some(0.75),
+                svdb_g1k_max_count: Some(10),
+                svdb_inhouse_enabled: true,
+                svdb_inhouse_min_overlap: Some(0.75),
+                svdb_inhouse_max_count: Some(10),
+                sv_size_min: Some(50),
+                tx_effects: vec![
+                    TranscriptEffect::TranscriptVariant,
+                    TranscriptEffect::ExonVariant,
+                    TranscriptEffect::SpliceRegionVariant,
+                ],
+                ..Default::default()
+            },
+            Preset::ResearchPermissive => CaseQuery {
+                svdb_gnomad_genomes_enabled: true,
+                svdb_gnomad_genomes_min_overlap: Some(0.5),
+                svdb_gnomad_genomes_max_count: Some(50),
+                svdb_inhouse_enabled: true,
+                svdb_inhouse_min_overlap: Some(0.5),
+                svdb_inhouse_max_count: Some(50),
+                ..Default::default()
+            },
+            Preset::KaryotypeLevelOnly => CaseQuery {
+                svdb_inhouse_enabled: true,
+                svdb_inhouse_min_overlap: Some(0.75),
+                svdb_inhouse_max_count: Some(5),
+                sv_size_min: Some(5_000_000),
+                sv_types: vec![SvType::Del, SvType::Dup, SvType::Inv, SvType::Bnd],
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Return the query settings of all presets by name.
+pub fn all() -> indexmap::IndexMap<String, CaseQuery> {
+    Preset::iter()
+        .map(|preset| (preset.to_string(), preset.case_query()))
+        .collect()
+}
+
+/// Parse the query JSON `value`, resolving the preset named by its `preset` key.
+pub fn resolve(mut value: serde_json::Value) -> Result<CaseQuery, anyhow::Error> {
+    let preset = value
+        .as_object_mut()
+        .and_then(|query| query.remove(PRESET_KEY));
+    let value = match preset {
+        None => value,
+        Some(name) => {
+            let name = name
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("preset name must be a string: {}", &name))?;
+            let preset: Preset = name
+                .parse()
+                .map_err(|_| anyhow::anyhow!("unknown strucvars query preset: {}", name))?;
+            let base = serde_json::to_value(preset.case_query())
+                .map_err(|e| anyhow::anyhow!("could not serialize preset: {}", e))?;
+            crate::common::overlay_json(base, value)
+        }
+    };
+    serde_json::from_value(value).map_err(|e| anyhow::anyhow!("invalid query JSON: {}", e))
+}
+
+#[cfg(test)]
+mod test {
+    use strum::IntoEnumIterator as _;
+
+    use super::Preset;
+
+    #[test]
+    fn round_trip() -> Result<(), anyhow::Error> {
+        for preset in Preset::iter() {
+            let query = super::resolve(serde_json::json!({ "preset": preset.to_string() }))?;
+            assert_eq!(query, preset.case_query());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_with_overrides() -> Result<(), anyhow::Error> {
+        let query = super::resolve(serde_json::json!({
+            "preset": "karyotype-level-only",
+            "sv_size_min": 1_000_000,
+        }))?;
+
+        assert_eq!(query.sv_size_min, Some(1_000_000));
+        assert_eq!(query.svdb_inhouse_max_count, Some(5));
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_unknown() {
+        let err = super::resolve(serde_json::json!({ "preset": "no-such-preset" })).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown strucvars query preset: no-such-preset"
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Code for annotating SVs with overlapping regulatory features.
+//!
+//! The features are taken from the ENSEMBL regulatory build, e.g., promoters, enhancers,
+//! and CTCF binding sites, converted to a BED file with the feature type and the stable
+//! identifier in the fourth and fifth column.
+
+use std::path::Path;
+
+use bio::data_structures::interval_tree::ArrayBackedIntervalTree;
+use indexmap::IndexMap;
+use mehari::common::io::std::open_read_maybe_gz;
+use serde::Serialize;
+use tracing::info;
+
+use crate::common::{build_chrom_map, GenomeRelease, CHROMS};
+
+use super::{interpreter::breakpoint_queries, schema::StructuralVariant};
+
+/// Alias for the interval tree that we use.
+type IntervalTree = ArrayBackedIntervalTree<i32, u32>;
+
+/// Information to store for a regulatory feature.
+#[derive(Default, Debug, Serialize, Clone, PartialEq)]
+pub struct Record {
+    /// 0-based begin position.
+    pub begin: i32,
+    /// End position.
+    pub end: i32,
+    /// Feature type, e.g., "enhancer", "promoter", or "CTCF_binding_site".
+    pub feature_type: String,
+    /// Stable identifier of the feature, e.g., "ENSR00000000001".
+    pub id: String,
+}
+
+/// Regulatory features with interval trees for overlap queries.
+#[derive(Default, Debug)]
+pub struct RegulatoryDb {
+    /// Records, stored by chromosome.
+    pub records: Vec<Vec<Record>>,
+    /// Interval trees, stored by chromosome.
+    pub trees: Vec<IntervalTree>,
+}
+
+impl RegulatoryDb {
+    /// Return the regulatory features overlapping with `sv`.
+    ///
+    /// For break-ends and insertions, the features around the breakpoints are returned.
+    pub fn overlapping_records(
+        &self,
+        sv: &StructuralVariant,
+        chrom_map: &IndexMap<String, usize>,
+    ) -> Result<Vec<Record>, anyhow::Error> {
+        if self.trees.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut result = Vec::new();
+        for (chrom_idx, query) in breakpoint_queries(sv, chrom_map)? {
+            let mut idxs = self.trees[chrom_idx]
+                .find(query)
+                .iter()
+                .map(|cursor| *cursor.data() as usize)
+                .collect::<Vec<_>>();
+            idxs.sort();
+            result.extend(
+                idxs.into_iter()
+                    .map(|idx| self.records[chrom_idx][idx].clone()),
+            );
+        }
+
+        Ok(result)
+    }
+}
+
+/// Module with code for loading data from input.
+mod input {
+    use serde::Deserialize;
+
+    /// Type for record structs from input.
+    #[derive(Deserialize, Debug)]
+    pub struct Record {
+        /// Chromosome name
+        pub chrom: String,
+        /// 0-based begin position from BED.
+        pub begin: i32,
+        /// 0-based end position from BED.
+        pub end: i32,
+        /// Feature type.
+        pub feature_type: String,
+        /// Stable identifier of the feature.
+        pub id: String,
+    }
+}
+
+/// Load the regulatory features from the BED file at `path`.
+///
+/// Records on contigs other than the canonical chromosomes are skipped.
+#[tracing::instrument]
+pub fn load_regulatory_db_records(path: &Path) -> Result<RegulatoryDb, anyhow::Error> {
+    tracing::debug!("loading regulatory feature records from {:?}...", path);
+    let chrom_map = build_chrom_map();
+
+    let mut result = RegulatoryDb::default();
+    for _ in CHROMS {
+        result.records.push(Vec::new());
+        result.trees.push(IntervalTree::new());
+    }
+
+    // Setup CSV reader for BED file - header is written as comment and must be
+    // ignored.
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false) // BED has no header
+        .comment(Some(b'#'))
+        .delimiter(b'\t')
+        .from_reader(open_read_maybe_gz(path.to_str().unwrap())?);
+    let mut total_count = 0;
+    for record in reader.deserialize() {
+        let record: input::Record = record?;
+        let Some(&chrom_idx) = chrom_map.get(&record.chrom) else {
+            continue;
+        };
+
+        let key = record.begin..record.end;
+        result.trees[chrom_idx].insert(key, result.records[chrom_idx].len() as u32);
+        result.records[chrom_idx].push(Record {
+            begin: record.begin,
+            end: record.end,
+            feature_type: record.feature_type,
+            id: record.id,
+        });
+
+        total_count += 1;
+    }
+    result.trees.iter_mut().for_each(|tree| tree.index());
+    tracing::debug!(
+        "... done loading {} records and building trees",
+        total_count
+    );
+
+    Ok(result)
+}
+
+/// Load the regulatory features from database given the configuration.
+///
+/// The features are optional; an empty database is returned if the file is missing.
+#[tracing::instrument]
+pub fn load_regulatory_db(
+    path_db: &str,
+    genome_release: GenomeRelease,
+) -> Result<RegulatoryDb, anyhow::Error> {
+    info!("Loading regulatory features db");
+    let path = Path::new(path_db).join(format!("{}/features/regulatory.bed", genome_release));
+    if path.exists() {
+        load_regulatory_db_records(&path)
+    } else {
+        info!("  no regulatory features db at {:?}, skipping", &path);
+        Ok(RegulatoryDb::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        common::build_chrom_map,
+        strucvars::query::{
+            fixtures::build_sv,
+            schema::{StructuralVariant, SvType},
+        },
+    };
+
+    #[rstest::rstest]
+    #[case::del_spanning(SvType::Del, 1_000, None, 6_000, vec!["ENSR1", "ENSR2"])]
+    #[case::del_partial(SvType::Del, 5_050, None, 5_100, vec!["ENSR2"])]
+    #[case::del_between(SvType::Del, 2_100, None, 4_900, vec![])]
+    #[case::ins(SvType::Ins, 5_020, None, 5_020, vec!["ENSR2"])]
+    #[case::bnd(SvType::Bnd, 10_000, Some("X"), 100_000, vec!["ENSR3"])]
+    #[case::bnd_other_chrom2(SvType::Bnd, 1_020, Some("GL000192.1"), 150, vec!["ENSR1"])]
+    fn overlapping_records(
+        #[case] sv_type: SvType,
+        #[case] pos: i32,
+        #[case] chrom2: Option<&str>,
+        #[case] end: i32,
+        #[case] expected: Vec<&str>,
+    ) -> Result<(), anyhow::Error> {
+        let tmpdir = temp_testdir::TempDir::default();
+        let path = tmpdir.join("regulatory.bed");
+        std::fs::write(
+            &path,
+            "#chrom\tbegin\tend\tfeature_type\tid\n\
+             1\t1000\t2000\tpromoter\tENSR1\n\
+             1\t5000\t5200\tenhancer\tENSR2\n\
+             GL000192.1\t100\t200\tenhancer\tENSR9\n\
+             X\t99950\t100100\tCTCF_binding_site\tENSR3\n",
+        )?;
+        let db = super::load_regulatory_db_records(&path)?;
+        let sv = StructuralVariant {
+            chrom2: chrom2.map(|chrom2| chrom2.into()),
+            ..build_sv("1", sv_type, pos, end)
+        };
+
+        let ids = db
+            .overlapping_records(&sv, &build_chrom_map())?
+            .into_iter()
+            .map(|record| record.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn overlapping_records_empty_db() -> Result<(), anyhow::Error> {
+        let db = super::RegulatoryDb::default();
+        let sv = build_sv("1", SvType::Del, 1_000, 6_000);
+
+        assert!(db.overlapping_records(&sv, &build_chrom_map())?.is_empty());
+
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
 //! Supporting code for SV query definition.
 
-use crate::common::TadSet;
+use crate::common::{
+    interval::{Interval, Topology},
+    TadSet,
+};
 use indexmap::IndexMap;
 use mehari::annotate::strucvars::{
     bnd::Breakend, csq::interface::StrandOrientation, PeOrientation,
@@ -10,7 +13,10 @@ use regex::Regex;
 use serde::{Deserialize, Deserializer, Serialize};
 use strum_macros::{Display, EnumIter, EnumString};
 
-use super::{clinvar, masked::MaskedBreakpointCount};
+use super::{dbrecords::clinvar_sv, masked::MaskedBreakpointCount};
+
+/// Version of the query JSON schema, incremented on incompatible changes.
+pub const SCHEMA_VERSION: u32 = 1;
 
 /// Range with 1-based positions
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -473,6 +479,9 @@ pub struct GenotypeCriteria {
     pub select_sv_min_size: Option<u32>,
     /// Select maximal size of SV to apply to (ignored for BND and INS)
     pub select_sv_max_size: Option<u32>,
+    /// Select names of the samples that this applies to, all samples if None
+    #[serde(default)]
+    pub select_sample: Option<Vec<String>>,
 
     // Maximal number of ends/breakpoints within segmental duplications
     pub max_brk_segdup: Option<u32>,
@@ -542,6 +551,12 @@ pub struct GenotypeCriteria {
     pub min_amq: Option<f32>,
     /// Maximal average mapping quality
     pub max_amq: Option<f32>,
+    /// Minimal mosaic fraction, see `CallInfo::mosaic_fraction()`
+    #[serde(default)]
+    pub min_mosaic_fraction: Option<f32>,
+    /// Maximal mosaic fraction, see `CallInfo::mosaic_fraction()`
+    #[serde(default)]
+    pub max_mosaic_fraction: Option<f32>,
 
     /// Whether missing genotype call leads to filter out variant
     #[serde(default = "default_as_true")]
@@ -565,6 +580,9 @@ pub struct GenotypeCriteria {
     /// Whether missing mapping quality information leads to filter out variant
     #[serde(default = "default_as_true")]
     pub missing_amq_ok: bool,
+    /// Whether missing mosaic copy number information leads to filter out variant
+    #[serde(default = "default_as_true")]
+    pub missing_mosaic_ok: bool,
 
     /// An optional comment
     pub comment: Option<String>,
@@ -578,6 +596,7 @@ impl GenotypeCriteria {
             select_sv_sub_type: vec![],
             select_sv_min_size: None,
             select_sv_max_size: None,
+            select_sample: None,
             max_brk_repeat: None,
             max_brk_segdup: None,
             max_brk_segduprepeat: None,
@@ -611,6 +630,8 @@ impl GenotypeCriteria {
             max_rd_dev: None,
             min_amq: None,
             max_amq: None,
+            min_mosaic_fraction: None,
+            max_mosaic_fraction: None,
             comment: None,
             missing_gt_ok: true,
             missing_gq_ok: true,
@@ -619,6 +640,7 @@ impl GenotypeCriteria {
             missing_srpr_ok: true,
             missing_rd_dev_ok: true,
             missing_amq_ok: true,
+            missing_mosaic_ok: true,
         }
     }
 
@@ -646,6 +668,14 @@ impl GenotypeCriteria {
         }
     }
 
+    /// Returns whether the `GenotypeCriteria` is applicable to the call of the
+    /// sample with the given name.
+    pub fn is_applicable_to_sample(&self, sample: &str) -> bool {
+        self.select_sample.as_ref().map_or(true, |select_sample| {
+            select_sample.iter().any(|name| name == sample)
+        })
+    }
+
     /// Returns whether the `GenotypeCriteria` is pass for the given `CallInfo`.
     ///
     /// Note that this only check the genotype and quality criteria.  Whether
@@ -1027,6 +1057,27 @@ impl GenotypeCriteria {
                 })
         });
 
+        // mosaic -- mosaic fraction
+
+        let pass_min_mosaic_fraction =
+            self.min_mosaic_fraction
+                .map_or(true, |min_mosaic_fraction| {
+                    call_info
+                        .mosaic_fraction()
+                        .map_or(self.missing_mosaic_ok, |mosaic_fraction| {
+                            mosaic_fraction >= min_mosaic_fraction
+                        })
+                });
+        let pass_max_mosaic_fraction =
+            self.max_mosaic_fraction
+                .map_or(true, |max_mosaic_fraction| {
+                    call_info
+                        .mosaic_fraction()
+                        .map_or(self.missing_mosaic_ok, |mosaic_fraction| {
+                            mosaic_fraction <= max_mosaic_fraction
+                        })
+                });
+
         pass_gt_one_of
             && pass_min_gq
             && pass_min_pr_cov
@@ -1057,6 +1108,8 @@ impl GenotypeCriteria {
             && pass_max_rd_dev
             && pass_min_amq
             && pass_max_amq
+            && pass_min_mosaic_fraction
+            && pass_max_mosaic_fraction
     }
 
     pub fn is_masked_pass(&self, masked_count: &MaskedBreakpointCount) -> bool {
@@ -1097,6 +1150,12 @@ pub struct CaseQuery {
     pub svdb_gnomad_genomes_min_overlap: Option<f32>,
     /// The maximal number of carriers for querying gnomAD SV.
     pub svdb_gnomad_genomes_max_count: Option<u32>,
+    /// The maximal number of carriers per gnomAD SV population, e.g., `{"nfe": 5}`.
+    #[serde(default)]
+    pub svdb_gnomad_genomes_max_count_by_population: IndexMap<String, u32>,
+    /// The maximal allele frequency per gnomAD SV population, e.g., `{"nfe": 0.001}`.
+    #[serde(default)]
+    pub svdb_gnomad_genomes_max_af_by_population: IndexMap<String, f32>,
     /// Whether to enable SVDB overlap queries with gnomAD exomes/ExAC.
     pub svdb_gnomad_exomes_enabled: bool,
     /// The minimal reciprocal overlap for querying gnomAD exomes/ExAC.
@@ -1126,12 +1185,19 @@ pub struct CaseQuery {
     pub clinvar_sv_min_overlap: Option<f32>,
     /// Minimal pathogenicity when overlapping with ClinVar SVs.
     pub clinvar_sv_min_pathogenicity: Option<Pathogenicity>,
+    /// Minimal reciprocal overlap for flagging known genomic disorders, 0.5 if not given.
+    pub genomic_disorder_min_overlap: Option<f32>,
 
     /// The minimal SV size to consider.
     pub sv_size_min: Option<u32>,
     /// The maximal SV size to consider.
     pub sv_size_max: Option<u32>,
 
+    /// Maximal fraction of the SV span covered by segmental duplications.
+    pub max_masked_fraction_segdup: Option<f32>,
+    /// Maximal fraction of the SV span covered by repeat-masked sequence.
+    pub max_masked_fraction_repeat: Option<f32>,
+
     /// The SV types to consider.
     pub sv_types: Vec<SvType>,
     /// The SV subtypes to consider.
@@ -1229,6 +1295,8 @@ impl Default for CaseQuery {
             svdb_gnomad_genomes_enabled: false,
             svdb_gnomad_genomes_min_overlap: None,
             svdb_gnomad_genomes_max_count: None,
+            svdb_gnomad_genomes_max_count_by_population: IndexMap::new(),
+            svdb_gnomad_genomes_max_af_by_population: IndexMap::new(),
             svdb_gnomad_exomes_enabled: false,
             svdb_gnomad_exomes_min_overlap: None,
             svdb_gnomad_exomes_max_count: None,
@@ -1243,10 +1311,13 @@ impl Default for CaseQuery {
             svdb_inhouse_max_count: None,
             sv_size_min: None,
             sv_size_max: None,
+            max_masked_fraction_segdup: None,
+            max_masked_fraction_repeat: None,
             sv_types: SvType::vec_all(),
             sv_sub_types: SvSubType::vec_all(),
             clinvar_sv_min_overlap: None,
             clinvar_sv_min_pathogenicity: None,
+            genomic_disorder_min_overlap: None,
             gene_allowlist: None,
             genomic_region: None,
             regulatory_overlap: 100,
@@ -1290,6 +1361,24 @@ pub struct CallInfo {
     pub point_count: Option<u32>,
     /// Average mapping quality, if applicable
     pub average_mapping_quality: Option<f32>,
+    /// Heteroplasmy fraction from the caller, if applicable (chrMT only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heteroplasmy: Option<f32>,
+    /// Non-integer copy number estimate, if applicable, e.g., 2.4 for a mosaic trisomy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mosaic_copy_number: Option<f32>,
+}
+
+impl CallInfo {
+    /// Return the mosaic fraction, if a non-integer copy number is available.
+    ///
+    /// This is the distance of the copy number estimate to the nearest integer copy
+    /// number, i.e., the fraction of cells with one more or one less copy than the
+    /// majority of cells, e.g., 0.4 for a copy number of 2.4.
+    pub fn mosaic_fraction(&self) -> Option<f32> {
+        self.mosaic_copy_number
+            .map(|copy_number| (copy_number - copy_number.round()).abs())
+    }
 }
 
 /// Definition of a structural variant with per-sample genotype calls.
@@ -1332,11 +1421,34 @@ impl StructuralVariant {
             || self.sv_sub_type == SvSubType::Bnd
         {
             None
+        } else if self.is_origin_spanning() {
+            Some(
+                Interval::new(self.pos - 1, self.end).len(Topology::from_chrom(&self.chrom)) as u32,
+            )
         } else {
             Some((self.end - self.pos + 1) as u32)
         }
     }
 
+    /// Return whether the SV is on the circular mitochondrial genome and spans the origin.
+    ///
+    /// Such SVs are given with an end position smaller than the start position.
+    pub fn is_origin_spanning(&self) -> bool {
+        Interval::new(self.pos - 1, self.end).spans_origin(Topology::from_chrom(&self.chrom))
+    }
+
+    /// Return whether the SV covers at least 90% of the chromosome, e.g., for
+    /// (mosaic) whole-chromosome aneuploidies.
+    pub fn is_whole_chromosome(&self, genome_release: crate::common::GenomeRelease) -> bool {
+        match (
+            self.size(),
+            crate::common::contig_length(genome_release, &self.chrom),
+        ) {
+            (Some(size), Some(length)) => size as f64 >= 0.9 * length as f64,
+            _ => false,
+        }
+    }
+
     /// Convert from VCF record.
     pub fn from_vcf(record: &vcf::Record, header: &vcf::Header) -> Result<Self, anyhow::Error> {
         let chrom = record.chromosome().to_string();
@@ -1523,6 +1635,24 @@ impl StructuralVariant {
                 None
             };
 
+            let heteroplasmy =
+                if let Some(Some(vcf::record::genotypes::sample::Value::Float(heteroplasmy))) =
+                    sample.get(&"hf".parse::<vcf::record::genotypes::keys::Key>()?)
+                {
+                    Some(*heteroplasmy)
+                } else {
+                    None
+                };
+            let mosaic_copy_number = if let Some(Some(
+                vcf::record::genotypes::sample::Value::Float(mosaic_copy_number),
+            )) =
+                sample.get(&"mcn".parse::<vcf::record::genotypes::keys::Key>()?)
+            {
+                Some(*mosaic_copy_number)
+            } else {
+                None
+            };
+
             result.insert(
                 name.clone(),
                 CallInfo {
@@ -1536,6 +1666,8 @@ impl StructuralVariant {
                     average_normalized_cov,
                     point_count,
                     average_mapping_quality,
+                    heteroplasmy,
+                    mosaic_copy_number,
                     ..Default::default()
                 },
             );
@@ -1545,34 +1677,34 @@ impl StructuralVariant {
     }
 }
 
-impl From<VariationType> for clinvar::pbs::VariationType {
+impl From<VariationType> for clinvar_sv::pbs::VariationType {
     fn from(val: VariationType) -> Self {
         match val {
-            VariationType::Complex => clinvar::pbs::VariationType::Complex,
-            VariationType::Microsatellite => clinvar::pbs::VariationType::Microsatellite,
-            VariationType::Dup => clinvar::pbs::VariationType::Dup,
-            VariationType::Del => clinvar::pbs::VariationType::Del,
-            VariationType::Bnd => clinvar::pbs::VariationType::Bnd,
-            VariationType::Cnv => clinvar::pbs::VariationType::Cnv,
-            VariationType::Inv => clinvar::pbs::VariationType::Inv,
-            VariationType::Ins => clinvar::pbs::VariationType::Ins,
+            VariationType::Complex => clinvar_sv::pbs::VariationType::Complex,
+            VariationType::Microsatellite => clinvar_sv::pbs::VariationType::Microsatellite,
+            VariationType::Dup => clinvar_sv::pbs::VariationType::Dup,
+            VariationType::Del => clinvar_sv::pbs::VariationType::Del,
+            VariationType::Bnd => clinvar_sv::pbs::VariationType::Bnd,
+            VariationType::Cnv => clinvar_sv::pbs::VariationType::Cnv,
+            VariationType::Inv => clinvar_sv::pbs::VariationType::Inv,
+            VariationType::Ins => clinvar_sv::pbs::VariationType::Ins,
         }
     }
 }
 
-impl TryInto<VariationType> for clinvar::pbs::VariationType {
+impl TryInto<VariationType> for clinvar_sv::pbs::VariationType {
     type Error = anyhow::Error;
 
     fn try_into(self) -> Result<VariationType, anyhow::Error> {
         Ok(match self {
-            clinvar::pbs::VariationType::Complex => VariationType::Complex,
-            clinvar::pbs::VariationType::Microsatellite => VariationType::Microsatellite,
-            clinvar::pbs::VariationType::Dup => VariationType::Dup,
-            clinvar::pbs::VariationType::Del => VariationType::Del,
-            clinvar::pbs::VariationType::Bnd => VariationType::Bnd,
-            clinvar::pbs::VariationType::Cnv => VariationType::Cnv,
-            clinvar::pbs::VariationType::Inv => VariationType::Inv,
-            clinvar::pbs::VariationType::Ins => VariationType::Ins,
+            clinvar_sv::pbs::VariationType::Complex => VariationType::Complex,
+            clinvar_sv::pbs::VariationType::Microsatellite => VariationType::Microsatellite,
+            clinvar_sv::pbs::VariationType::Dup => VariationType::Dup,
+            clinvar_sv::pbs::VariationType::Del => VariationType::Del,
+            clinvar_sv::pbs::VariationType::Bnd => VariationType::Bnd,
+            clinvar_sv::pbs::VariationType::Cnv => VariationType::Cnv,
+            clinvar_sv::pbs::VariationType::Inv => VariationType::Inv,
+            clinvar_sv::pbs::VariationType::Ins => VariationType::Ins,
         })
     }
 }
@@ -1602,6 +1734,7 @@ pub enum VariationType {
     PartialEq,
     PartialOrd,
     Eq,
+    Ord,
     Hash,
     Copy,
     Clone,
@@ -1621,28 +1754,28 @@ pub enum Pathogenicity {
     Pathogenic,
 }
 
-impl From<Pathogenicity> for clinvar::pbs::Pathogenicity {
+impl From<Pathogenicity> for clinvar_sv::pbs::Pathogenicity {
     fn from(val: Pathogenicity) -> Self {
         match val {
-            Pathogenicity::Benign => clinvar::pbs::Pathogenicity::Benign,
-            Pathogenicity::LikelyBenign => clinvar::pbs::Pathogenicity::LikelyBenign,
-            Pathogenicity::Uncertain => clinvar::pbs::Pathogenicity::Uncertain,
-            Pathogenicity::LikelyPathogenic => clinvar::pbs::Pathogenicity::LikelyPathogenic,
-            Pathogenicity::Pathogenic => clinvar::pbs::Pathogenicity::Pathogenic,
+            Pathogenicity::Benign => clinvar_sv::pbs::Pathogenicity::Benign,
+            Pathogenicity::LikelyBenign => clinvar_sv::pbs::Pathogenicity::LikelyBenign,
+            Pathogenicity::Uncertain => clinvar_sv::pbs::Pathogenicity::Uncertain,
+            Pathogenicity::LikelyPathogenic => clinvar_sv::pbs::Pathogenicity::LikelyPathogenic,
+            Pathogenicity::Pathogenic => clinvar_sv::pbs::Pathogenicity::Pathogenic,
         }
     }
 }
 
-impl TryInto<Pathogenicity> for clinvar::pbs::Pathogenicity {
+impl TryInto<Pathogenicity> for clinvar_sv::pbs::Pathogenicity {
     type Error = anyhow::Error;
 
     fn try_into(self) -> Result<Pathogenicity, anyhow::Error> {
         Ok(match self {
-            clinvar::pbs::Pathogenicity::Benign => Pathogenicity::Benign,
-            clinvar::pbs::Pathogenicity::LikelyBenign => Pathogenicity::LikelyBenign,
-            clinvar::pbs::Pathogenicity::Uncertain => Pathogenicity::Uncertain,
-            clinvar::pbs::Pathogenicity::LikelyPathogenic => Pathogenicity::LikelyPathogenic,
-            clinvar::pbs::Pathogenicity::Pathogenic => Pathogenicity::Pathogenic,
+            clinvar_sv::pbs::Pathogenicity::Benign => Pathogenicity::Benign,
+            clinvar_sv::pbs::Pathogenicity::LikelyBenign => Pathogenicity::LikelyBenign,
+            clinvar_sv::pbs::Pathogenicity::Uncertain => Pathogenicity::Uncertain,
+            clinvar_sv::pbs::Pathogenicity::LikelyPathogenic => Pathogenicity::LikelyPathogenic,
+            clinvar_sv::pbs::Pathogenicity::Pathogenic => Pathogenicity::Pathogenic,
         })
     }
 }
@@ -1670,6 +1803,92 @@ mod tests {
     use pretty_assertions::assert_eq;
     use serde_test::{assert_tokens, Token};
 
+    #[rstest::rstest]
+    #[case::no_thresholds(None, None, Some(2.4), true, true)]
+    #[case::min_pass(Some(0.3), None, Some(2.4), true, true)]
+    #[case::min_fail(Some(0.5), None, Some(2.4), true, false)]
+    #[case::max_fail(None, Some(0.2), Some(2.4), true, false)]
+    #[case::max_pass(None, Some(0.2), Some(3.0), true, true)]
+    #[case::missing_ok(Some(0.3), None, None, true, true)]
+    #[case::missing_not_ok(Some(0.3), None, None, false, false)]
+    fn test_genotype_criteria_is_call_info_pass_mosaic_fraction(
+        #[case] min_mosaic_fraction: Option<f32>,
+        #[case] max_mosaic_fraction: Option<f32>,
+        #[case] mosaic_copy_number: Option<f32>,
+        #[case] missing_mosaic_ok: bool,
+        #[case] expected: bool,
+    ) {
+        let crit = GenotypeCriteria {
+            min_mosaic_fraction,
+            max_mosaic_fraction,
+            missing_mosaic_ok,
+            ..GenotypeCriteria::new(GenotypeChoice::Het)
+        };
+        let call_info = CallInfo {
+            mosaic_copy_number,
+            ..Default::default()
+        };
+
+        assert_eq!(crit.is_call_info_pass(&call_info), expected);
+    }
+
+    #[rstest::rstest]
+    #[case(None, None)]
+    #[case(Some(2.0), Some(0.0))]
+    #[case(Some(2.4), Some(0.4))]
+    #[case(Some(1.7), Some(0.3))]
+    fn test_call_info_mosaic_fraction(
+        #[case] mosaic_copy_number: Option<f32>,
+        #[case] expected: Option<f32>,
+    ) {
+        let call_info = CallInfo {
+            mosaic_copy_number,
+            ..Default::default()
+        };
+
+        let actual = call_info.mosaic_fraction();
+
+        assert_eq!(actual.is_some(), expected.is_some());
+        if let (Some(actual), Some(expected)) = (actual, expected) {
+            assert!(
+                (actual - expected).abs() < 1e-5,
+                "{} != {}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[rstest::rstest]
+    #[case("chr21", 1, 46_709_983, SvType::Dup, true)]
+    #[case("21", 5_000_000, 46_000_000, SvType::Dup, false)]
+    #[case("chrX", 1, 150_000_000, SvType::Del, true)]
+    #[case("chr21", 1, 46_709_983, SvType::Bnd, false)]
+    fn test_structural_variant_is_whole_chromosome(
+        #[case] chrom: &str,
+        #[case] pos: i32,
+        #[case] end: i32,
+        #[case] sv_type: SvType,
+        #[case] expected: bool,
+    ) {
+        let sv = StructuralVariant {
+            chrom: chrom.into(),
+            pos,
+            sv_type,
+            sv_sub_type: SvSubType::Dup,
+            chrom2: None,
+            end,
+            strand_orientation: StrandOrientation::NotApplicable,
+            callers: Vec::new(),
+            call_info: Default::default(),
+        };
+
+        assert_eq!(
+            sv.is_whole_chromosome(crate::common::GenomeRelease::Grch38),
+            expected
+        );
+    }
+
     #[test]
     fn test_range_smoke() {
         assert_eq!(Range::new(1, 2), Range { start: 1, end: 2 });
@@ -1856,6 +2075,19 @@ mod tests {
         assert!(!crit.is_applicable_to(GenotypeChoice::Het, SvSubType::Del, Some(10000)));
     }
 
+    #[test]
+    fn test_genotype_criteria_is_applicable_to_sample() {
+        let crit = GenotypeCriteria::new(GenotypeChoice::Het);
+        assert!(crit.is_applicable_to_sample("index"));
+
+        let crit = GenotypeCriteria {
+            select_sample: Some(vec!["father".to_owned(), "mother".to_owned()]),
+            ..GenotypeCriteria::new(GenotypeChoice::Het)
+        };
+        assert!(!crit.is_applicable_to_sample("index"));
+        assert!(crit.is_applicable_to_sample("mother"));
+    }
+
     #[test]
     fn test_genotype_criteria_is_call_info_pass() {
         let crit = GenotypeCriteria {
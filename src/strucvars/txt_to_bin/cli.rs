@@ -100,6 +100,10 @@ pub struct Args {
     /// Path to output BIN file.
     #[arg(long)]
     pub path_output: PathBuf,
+    /// Also convert records that do not pass the filters of the database (gnomAD SV v4
+    /// only), by default only PASS records are converted.
+    #[arg(long, default_value_t = false)]
+    pub include_non_pass: bool,
 }
 
 /// Main entry point for the `strucvars txt-to-bin` command.
@@ -123,36 +127,55 @@ pub fn run(common_args: &crate::common::Args, args: &Args) -> Result<(), anyhow:
             &args.path_input,
             &args.path_output,
             InputFileType::InhouseDb,
+            args.include_non_pass,
+        )?,
+        InputType::StrucvarDbVar => vardbs::convert_to_bin(
+            &args.path_input,
+            &args.path_output,
+            InputFileType::Dbvar,
+            args.include_non_pass,
+        )?,
+        InputType::StrucvarDgv => vardbs::convert_to_bin(
+            &args.path_input,
+            &args.path_output,
+            InputFileType::Dgv,
+            args.include_non_pass,
+        )?,
+        InputType::StrucvarDgvGs => vardbs::convert_to_bin(
+            &args.path_input,
+            &args.path_output,
+            InputFileType::DgvGs,
+            args.include_non_pass,
+        )?,
+        InputType::StrucvarExacCnv => vardbs::convert_to_bin(
+            &args.path_input,
+            &args.path_output,
+            InputFileType::Exac,
+            args.include_non_pass,
+        )?,
+        InputType::StrucvarG1k => vardbs::convert_to_bin(
+            &args.path_input,
+            &args.path_output,
+            InputFileType::G1k,
+            args.include_non_pass,
         )?,
-        InputType::StrucvarDbVar => {
-            vardbs::convert_to_bin(&args.path_input, &args.path_output, InputFileType::Dbvar)?
-        }
-        InputType::StrucvarDgv => {
-            vardbs::convert_to_bin(&args.path_input, &args.path_output, InputFileType::Dgv)?
-        }
-        InputType::StrucvarDgvGs => {
-            vardbs::convert_to_bin(&args.path_input, &args.path_output, InputFileType::DgvGs)?
-        }
-        InputType::StrucvarExacCnv => {
-            vardbs::convert_to_bin(&args.path_input, &args.path_output, InputFileType::Exac)?
-        }
-        InputType::StrucvarG1k => {
-            vardbs::convert_to_bin(&args.path_input, &args.path_output, InputFileType::G1k)?
-        }
         InputType::StrucvarGnomadSv2 => vardbs::convert_to_bin(
             &args.path_input,
             &args.path_output,
             InputFileType::GnomadSv2,
+            args.include_non_pass,
         )?,
         InputType::StrucvarGnomadCnv4 => vardbs::convert_to_bin(
             &args.path_input,
             &args.path_output,
             InputFileType::GnomadCnv4,
+            args.include_non_pass,
         )?,
         InputType::StrucvarGnomadSv4 => vardbs::convert_to_bin(
             &args.path_input,
             &args.path_output,
             InputFileType::GnomadSv4,
+            args.include_non_pass,
         )?,
         InputType::MaskedRegion => masked::convert_to_bin(&args.path_input, &args.path_output)?,
         InputType::Xlink => xlink::convert_to_bin(&args.path_input, &args.path_output)?,
@@ -181,6 +204,8 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
         };
         let args = Args {
             assembly: Some(assembly),
@@ -189,6 +214,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/clinvar/clinvar-svs.jsonl.gz",
             ),
             path_output: tmp_dir.join("clinvar.bin"),
+            include_non_pass: false,
         };
 
         super::run(&common_args, &args)?;
@@ -201,6 +227,8 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
         };
         let args = Args {
             assembly: None,
@@ -209,6 +237,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch37/strucvar/inhouse.tsv",
             ),
             path_output: tmp_dir.join("strucvar_inhouse.bin"),
+            include_non_pass: false,
         };
 
         super::run(&common_args, &args)?;
@@ -221,6 +250,8 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
         };
         let args = Args {
             assembly: None,
@@ -229,6 +260,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch37/strucvar/dbvar.bed.gz",
             ),
             path_output: tmp_dir.join("strucvar_dbvar.bin"),
+            include_non_pass: false,
         };
 
         super::run(&common_args, &args)?;
@@ -241,6 +273,8 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
         };
         let args = Args {
             assembly: None,
@@ -249,6 +283,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch37/strucvar/dgv.bed.gz",
             ),
             path_output: tmp_dir.join("strucvar_dgv.bin"),
+            include_non_pass: false,
         };
 
         super::run(&common_args, &args)?;
@@ -261,6 +296,8 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
         };
         let args = Args {
             assembly: None,
@@ -269,6 +306,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch37/strucvar/dgv_gs.bed.gz",
             ),
             path_output: tmp_dir.join("strucvar_dgv_gs.bin"),
+            include_non_pass: false,
         };
 
         super::run(&common_args, &args)?;
@@ -281,6 +319,8 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
         };
         let args = Args {
             assembly: None,
@@ -289,6 +329,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch37/strucvar/exac.bed.gz",
             ),
             path_output: tmp_dir.join("exac.bin"),
+            include_non_pass: false,
         };
 
         super::run(&common_args, &args)?;
@@ -301,6 +342,8 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
         };
         let args = Args {
             assembly: None,
@@ -309,6 +352,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch37/strucvar/g1k.bed.gz",
             ),
             path_output: tmp_dir.join("g1k.bin"),
+            include_non_pass: false,
         };
 
         super::run(&common_args, &args)?;
@@ -321,6 +365,8 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
         };
         let args = Args {
             assembly: None,
@@ -329,6 +375,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch37/strucvar/gnomad_sv.bed.gz",
             ),
             path_output: tmp_dir.join("gnomad.bin"),
+            include_non_pass: false,
         };
 
         super::run(&common_args, &args)?;
@@ -341,6 +388,8 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
         };
         let args = Args {
             assembly: None,
@@ -349,6 +398,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch38/strucvar/gnomad-cnv.bed.gz",
             ),
             path_output: tmp_dir.join("gnomad-cnv.bin"),
+            include_non_pass: false,
         };
 
         super::run(&common_args, &args)?;
@@ -361,6 +411,8 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
         };
         let args = Args {
             assembly: None,
@@ -369,6 +421,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/vardbs/grch38/strucvar/gnomad-sv.bed.gz",
             ),
             path_output: tmp_dir.join("gnomad-sv.bin"),
+            include_non_pass: false,
         };
 
         super::run(&common_args, &args)?;
@@ -381,6 +434,8 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
         };
         let args = Args {
             assembly: None,
@@ -389,6 +444,7 @@ mod test {
                 "tests/db/to-bin/varfish-db-downloader/features/grch37/masked/repeat.bed.gz",
             ),
             path_output: tmp_dir.join("masked.bin"),
+            include_non_pass: false,
         };
 
         super::run(&common_args, &args)?;
@@ -401,12 +457,15 @@ mod test {
         let tmp_dir = temp_testdir::TempDir::default();
         let common_args = common::Args {
             verbose: Verbosity::new(0, 0),
+            compression_level: None,
+            path_manifest: None,
         };
         let args = Args {
             assembly: None,
             input_type: InputType::Xlink,
             path_input: String::from("tests/db/to-bin/varfish-db-downloader/genes/xlink/hgnc.tsv"),
             path_output: tmp_dir.join("xlink.bin"),
+            include_non_pass: false,
         };
 
         super::run(&common_args, &args)?;
@@ -5,6 +5,7 @@ use serde::Deserialize;
 use tracing::error;
 
 use crate::strucvars::aggregate::output::Record as InhouseDbRecord;
+use crate::strucvars::pbs::PopulationCount;
 use crate::strucvars::query::schema::SvType;
 
 /// dbVar database record as read from TSV file.
@@ -133,6 +134,97 @@ pub struct GnomadSv4Record {
     pub cnv_n_total: u32,
     /// Number of samples with a CNV at this site (CNV only).
     pub cnv_n_var: u32,
+    /// Value of the FILTER column, missing values are interpreted as "PASS".
+    #[serde(default)]
+    pub filter: Option<String>,
+    /// Number of African/African American carriers (v4 with populations only).
+    #[serde(default)]
+    pub afr_n_var: Option<u32>,
+    /// African/African American allele frequency (v4 with populations only).
+    #[serde(default)]
+    pub afr_af: Option<f32>,
+    /// Number of Amish carriers (v4 with populations only).
+    #[serde(default)]
+    pub ami_n_var: Option<u32>,
+    /// Amish allele frequency (v4 with populations only).
+    #[serde(default)]
+    pub ami_af: Option<f32>,
+    /// Number of Admixed American carriers (v4 with populations only).
+    #[serde(default)]
+    pub amr_n_var: Option<u32>,
+    /// Admixed American allele frequency (v4 with populations only).
+    #[serde(default)]
+    pub amr_af: Option<f32>,
+    /// Number of Ashkenazi Jewish carriers (v4 with populations only).
+    #[serde(default)]
+    pub asj_n_var: Option<u32>,
+    /// Ashkenazi Jewish allele frequency (v4 with populations only).
+    #[serde(default)]
+    pub asj_af: Option<f32>,
+    /// Number of East Asian carriers (v4 with populations only).
+    #[serde(default)]
+    pub eas_n_var: Option<u32>,
+    /// East Asian allele frequency (v4 with populations only).
+    #[serde(default)]
+    pub eas_af: Option<f32>,
+    /// Number of Finnish carriers (v4 with populations only).
+    #[serde(default)]
+    pub fin_n_var: Option<u32>,
+    /// Finnish allele frequency (v4 with populations only).
+    #[serde(default)]
+    pub fin_af: Option<f32>,
+    /// Number of Middle Eastern carriers (v4 with populations only).
+    #[serde(default)]
+    pub mid_n_var: Option<u32>,
+    /// Middle Eastern allele frequency (v4 with populations only).
+    #[serde(default)]
+    pub mid_af: Option<f32>,
+    /// Number of Non-Finnish European carriers (v4 with populations only).
+    #[serde(default)]
+    pub nfe_n_var: Option<u32>,
+    /// Non-Finnish European allele frequency (v4 with populations only).
+    #[serde(default)]
+    pub nfe_af: Option<f32>,
+    /// Number of South Asian carriers (v4 with populations only).
+    #[serde(default)]
+    pub sas_n_var: Option<u32>,
+    /// South Asian allele frequency (v4 with populations only).
+    #[serde(default)]
+    pub sas_af: Option<f32>,
+}
+
+impl GnomadSv4Record {
+    /// Return whether the record passes all filters.
+    pub fn is_pass(&self) -> bool {
+        matches!(
+            self.filter.as_deref(),
+            None | Some("") | Some(".") | Some("PASS")
+        )
+    }
+
+    /// Return the population-specific counts, skipping populations without values.
+    pub fn population_counts(&self) -> Vec<PopulationCount> {
+        [
+            ("afr", self.afr_n_var, self.afr_af),
+            ("ami", self.ami_n_var, self.ami_af),
+            ("amr", self.amr_n_var, self.amr_af),
+            ("asj", self.asj_n_var, self.asj_af),
+            ("eas", self.eas_n_var, self.eas_af),
+            ("fin", self.fin_n_var, self.fin_af),
+            ("mid", self.mid_n_var, self.mid_af),
+            ("nfe", self.nfe_n_var, self.nfe_af),
+            ("sas", self.sas_n_var, self.sas_af),
+        ]
+        .into_iter()
+        .filter_map(|(population, count, af)| {
+            count.map(|count| PopulationCount {
+                population: population.to_string(),
+                count,
+                af: af.unwrap_or_default(),
+            })
+        })
+        .collect()
+    }
 }
 
 /// gnomAD CNV v$ database record as read from TSV file.
@@ -166,6 +258,11 @@ pub struct InputRecord {
     pub end: i32,
     /// Number of carriers (or alleles), depending on database.
     pub count: u32,
+    /// Population-specific counts, if provided by the database.
+    pub population_counts: Vec<PopulationCount>,
+    /// Whether the record passes all filters of the database, `true` for databases
+    /// without filters.
+    pub is_pass: bool,
 }
 
 impl TryInto<Option<InputRecord>> for InhouseDbRecord {
@@ -179,6 +276,8 @@ impl TryInto<Option<InputRecord>> for InhouseDbRecord {
             begin: self.begin,
             end: self.end,
             count: self.carriers,
+            population_counts: Vec::new(),
+            is_pass: true,
         }))
     }
 }
@@ -211,6 +310,8 @@ impl TryInto<Option<InputRecord>> for DbVarRecord {
             end: self.end,
             sv_type,
             count: 1,
+            population_counts: Vec::new(),
+            is_pass: true,
         }))
     }
 }
@@ -251,6 +352,8 @@ impl TryInto<Option<InputRecord>> for DgvRecord {
             end: self.end,
             sv_type,
             count: self.observed_gains + self.observed_losses,
+            population_counts: Vec::new(),
+            is_pass: true,
         }))
     }
 }
@@ -274,6 +377,8 @@ impl TryInto<Option<InputRecord>> for DgvGsRecord {
             end: self.end_outer,
             sv_type,
             count: self.num_carriers,
+            population_counts: Vec::new(),
+            is_pass: true,
         }))
     }
 }
@@ -297,6 +402,8 @@ impl TryInto<Option<InputRecord>> for ExacRecord {
             end: self.end,
             sv_type,
             count: 1,
+            population_counts: Vec::new(),
+            is_pass: true,
         }))
     }
 }
@@ -325,6 +432,8 @@ impl TryInto<Option<InputRecord>> for GnomadSv2Record {
             end: self.end,
             sv_type,
             count: self.n_homalt + self.n_het,
+            population_counts: Vec::new(),
+            is_pass: true,
         }))
     }
 }
@@ -347,6 +456,8 @@ impl TryInto<Option<InputRecord>> for GnomadCnv4Record {
                 }
             },
             count: self.n_var,
+            population_counts: Vec::new(),
+            is_pass: true,
         }))
     }
 }
@@ -355,6 +466,8 @@ impl TryInto<Option<InputRecord>> for GnomadSv4Record {
     type Error = &'static str;
 
     fn try_into(self) -> Result<Option<InputRecord>, Self::Error> {
+        let population_counts = self.population_counts();
+        let is_pass = self.is_pass();
         Ok(Some(InputRecord {
             chromosome: self.chromosome.clone(),
             chromosome2: self.chromosome,
@@ -378,6 +491,8 @@ impl TryInto<Option<InputRecord>> for GnomadSv4Record {
                 + self.female_n_het
                 + self.female_n_homalt
                 + self.cnv_n_var,
+            population_counts,
+            is_pass,
         }))
     }
 }
@@ -405,6 +520,8 @@ impl TryInto<Option<InputRecord>> for G1kRecord {
             end: self.end,
             sv_type,
             count: self.n_homalt + self.n_het,
+            population_counts: Vec::new(),
+            is_pass: true,
         }))
     }
 }
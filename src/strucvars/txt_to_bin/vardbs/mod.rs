@@ -33,8 +33,12 @@ pub enum InputFileType {
     InhouseDb,
 }
 /// Deserialize from CSV reader to an `Option<records::InputRecord>`
+///
+/// Records that do not pass the filters of the database are skipped unless
+/// `include_non_pass` is set.
 fn deserialize_loop<Rec>(
     reader: &mut csv::Reader<Box<dyn std::io::BufRead>>,
+    include_non_pass: bool,
 ) -> Result<Vec<BgDbRecord>, anyhow::Error>
 where
     Rec: core::fmt::Debug + TryInto<Option<InputRecord>> + for<'de> serde::Deserialize<'de>,
@@ -44,6 +48,7 @@ where
 {
     let chrom_map = build_chrom_map();
     let mut result = Vec::new();
+    let mut count_non_pass = 0;
 
     for record in reader.deserialize() {
         let record: Rec = record?;
@@ -51,6 +56,12 @@ where
             .try_into()
             .map_err(|err| anyhow!("problem with parsing: {:?}", &err))?;
         if let Some(record) = maybe_record {
+            if !record.is_pass {
+                count_non_pass += 1;
+                if !include_non_pass {
+                    continue;
+                }
+            }
             result.push(BgDbRecord {
                 chrom_no: *chrom_map
                     .get(&record.chromosome)
@@ -71,10 +82,25 @@ where
                 start: record.begin + 1,
                 stop: record.end,
                 count: record.count,
+                population_counts: record.population_counts,
             });
         }
     }
 
+    if count_non_pass > 0 {
+        if include_non_pass {
+            tracing::info!(
+                "kept {} records not passing the filters",
+                count_non_pass.separate_with_commas()
+            );
+        } else {
+            tracing::info!(
+                "skipped {} records not passing the filters, use --include-non-pass to keep them",
+                count_non_pass.separate_with_commas()
+            );
+        }
+    }
+
     Ok(result)
 }
 
@@ -82,25 +108,36 @@ where
 pub fn deserialize_branch(
     input_type: InputFileType,
     reader: &mut csv::Reader<Box<dyn std::io::BufRead>>,
+    include_non_pass: bool,
 ) -> Result<Vec<BgDbRecord>, anyhow::Error> {
     match input_type {
-        InputFileType::Dbvar => deserialize_loop::<input::DbVarRecord>(reader),
-        InputFileType::Dgv => deserialize_loop::<input::DgvRecord>(reader),
-        InputFileType::DgvGs => deserialize_loop::<input::DgvGsRecord>(reader),
-        InputFileType::Exac => deserialize_loop::<input::ExacRecord>(reader),
-        InputFileType::G1k => deserialize_loop::<input::G1kRecord>(reader),
-        InputFileType::InhouseDb => deserialize_loop::<InhouseDbRecord>(reader),
-        InputFileType::GnomadSv2 => deserialize_loop::<input::GnomadSv2Record>(reader),
-        InputFileType::GnomadCnv4 => deserialize_loop::<input::GnomadCnv4Record>(reader),
-        InputFileType::GnomadSv4 => deserialize_loop::<input::GnomadSv4Record>(reader),
+        InputFileType::Dbvar => deserialize_loop::<input::DbVarRecord>(reader, include_non_pass),
+        InputFileType::Dgv => deserialize_loop::<input::DgvRecord>(reader, include_non_pass),
+        InputFileType::DgvGs => deserialize_loop::<input::DgvGsRecord>(reader, include_non_pass),
+        InputFileType::Exac => deserialize_loop::<input::ExacRecord>(reader, include_non_pass),
+        InputFileType::G1k => deserialize_loop::<input::G1kRecord>(reader, include_non_pass),
+        InputFileType::InhouseDb => deserialize_loop::<InhouseDbRecord>(reader, include_non_pass),
+        InputFileType::GnomadSv2 => {
+            deserialize_loop::<input::GnomadSv2Record>(reader, include_non_pass)
+        }
+        InputFileType::GnomadCnv4 => {
+            deserialize_loop::<input::GnomadCnv4Record>(reader, include_non_pass)
+        }
+        InputFileType::GnomadSv4 => {
+            deserialize_loop::<input::GnomadSv4Record>(reader, include_non_pass)
+        }
     }
 }
 
 /// Perform conversion to protobuf `.bin` file.
+///
+/// Only records passing the filters of the database are converted unless
+/// `include_non_pass` is set.
 pub fn convert_to_bin<P, Q>(
     path_input_tsv: P,
     path_output: Q,
     input_type: InputFileType,
+    include_non_pass: bool,
 ) -> Result<(), anyhow::Error>
 where
     P: AsRef<Path>,
@@ -117,7 +154,7 @@ where
         )?);
     let before_parsing = Instant::now();
 
-    let records = deserialize_branch(input_type, &mut reader)?;
+    let records = deserialize_branch(input_type, &mut reader, include_non_pass)?;
     let bg_db = BackgroundDatabase { records };
 
     tracing::debug!(
@@ -177,6 +214,10 @@ mod test {
         InputFileType::GnomadSv4,
         "tests/db/to-bin/varfish-db-downloader/vardbs/grch38/strucvar/gnomad-sv.bed.gz"
     )]
+    #[case::gnomad_sv4_populations(
+        InputFileType::GnomadSv4,
+        "tests/db/to-bin/varfish-db-downloader/vardbs/grch38/strucvar/gnomad-sv-populations.bed"
+    )]
     #[case::inhouse_db(
         InputFileType::InhouseDb,
         "tests/db/to-bin/varfish-db-downloader/vardbs/grch37/strucvar/inhouse.tsv"
@@ -203,9 +244,31 @@ mod test {
             .delimiter(b'\t')
             .from_reader(mehari::common::io::std::open_read_maybe_gz(path_input)?);
 
-        let records = super::deserialize_branch(input_type, &mut reader)?;
+        let records = super::deserialize_branch(input_type, &mut reader, false)?;
         insta::assert_yaml_snapshot!(records);
 
         Ok(())
     }
+
+    #[rstest::rstest]
+    #[case::pass_only(false, 2)]
+    #[case::include_non_pass(true, 3)]
+    fn deserialize_branch_include_non_pass(
+        #[case] include_non_pass: bool,
+        #[case] expected_count: usize,
+    ) -> Result<(), anyhow::Error> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .comment(Some(b'#'))
+            .delimiter(b'\t')
+            .from_reader(mehari::common::io::std::open_read_maybe_gz(
+                "tests/db/to-bin/varfish-db-downloader/vardbs/grch38/strucvar/gnomad-sv-populations.bed",
+            )?);
+
+        let records =
+            super::deserialize_branch(InputFileType::GnomadSv4, &mut reader, include_non_pass)?;
+        assert_eq!(records.len(), expected_count);
+
+        Ok(())
+    }
 }
@@ -21,9 +21,26 @@ pub mod input {
         pub gene_symbol: Option<String>,
         pub ensembl_gene_id: Option<String>,
         pub entrez_id: Option<u32>,
+        /// Alias symbols, separated by `|`.
+        #[serde(default)]
+        pub alias_symbol: Option<String>,
+        /// Previous symbols, separated by `|`.
+        #[serde(default)]
+        pub prev_symbol: Option<String>,
     }
 }
 
+/// Split the `|`-separated `symbols` of the HGNC TSV.
+fn split_symbols(symbols: Option<&str>) -> Vec<String> {
+    symbols
+        .unwrap_or_default()
+        .split('|')
+        .map(|symbol| symbol.trim())
+        .filter(|symbol| !symbol.is_empty())
+        .map(String::from)
+        .collect()
+}
+
 /// Perform conversion to protocolbuffers `.bin` file.
 pub fn convert_to_bin<P, Q>(path_input_tsv: P, path_output: Q) -> Result<(), anyhow::Error>
 where
@@ -52,6 +69,8 @@ where
                 hgnc_id,
                 ensembl_id: numeric_gene_id(&ensembl_gene_id)?,
                 symbol: gene_symbol,
+                alias_symbols: split_symbols(record.alias_symbol.as_deref()),
+                prev_symbols: split_symbols(record.prev_symbol.as_deref()),
             });
         }
     }